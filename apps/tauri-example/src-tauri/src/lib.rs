@@ -7,7 +7,7 @@ use tauri::AppHandle;
 use tauri::Manager;
 use tauri::Listener;
 use tauri::plugin::TauriPlugin;
-use tauri_plugin_zubridge::{self, plugin, StateManager, ZubridgeOptions};
+use tauri_plugin_zubridge::{self, plugin, StateManager, WindowActionsConfig, ZubridgeExt, ZubridgeOptions};
 use serde::{Deserialize, Serialize};
 use std::sync::Mutex;
 
@@ -32,8 +32,8 @@ impl Default for AppState {
     }
 }
 
-// Define actions
-#[derive(Deserialize, Debug)]
+// Define actions. They're also serialized, so Rust code can dispatch them with `dispatch_typed`
+#[derive(Serialize, Deserialize, Debug)]
 #[serde(tag = "type")]
 pub enum CounterAction {
     #[serde(rename = "COUNTER:INCREMENT")]
@@ -250,11 +250,7 @@ mod tray;
 // Initialize the Zubridge plugin with our custom state manager
 pub fn init() -> TauriPlugin<tauri::Wry> {
     let state_manager = AppStateManager::new();
-    let options = ZubridgeOptions {
-        event_name: "zubridge://state-update".to_string(),
-    };
-
-    plugin(state_manager, options)
+    plugin(state_manager, ZubridgeOptions::default())
 }
 
 #[cfg_attr(mobile, tauri::mobile_entry_point)]
@@ -262,12 +258,17 @@ pub fn run() {
     let initial_state = AppState::default();
     let state_manager = AppStateManager::new();
 
-    // Create custom options with the correct event name
     let options = ZubridgeOptions {
-        event_name: "zubridge://state-update".to_string(),
+        tray_actions: Some(tray::action_map().expect("invalid tray actions")),
+        // Closing the main window hides it to the tray, tracked in `windows.main.visible`
+        window_actions: Some(WindowActionsConfig {
+            close_to_tray: vec!["main".to_string()],
+            ..Default::default()
+        }),
+        ..Default::default()
     };
 
-    let zubridge_plugin = plugin(state_manager, options.clone());
+    let zubridge_plugin = plugin(state_manager, options);
 
     tauri::Builder::default()
         .plugin(zubridge_plugin)
@@ -290,10 +291,10 @@ pub fn run() {
             // Get the app handle to use in the closure
             let app_handle = app.app_handle().clone();
 
-            // Set up event listener for state updates - use the same options object
+            // Set up event listener for state updates, on the name the plugin emits them under
             let event_handle = app_handle.clone();
-            let event_name = options.event_name.clone();
-            app_handle.listen(&options.event_name, move |event| {
+            let event_name = app_handle.zubridge().event_names().state_update.clone();
+            app_handle.listen(event_name.clone(), move |event| {
                 println!("Event received: {}", event_name);
 
                 // The payload() method returns &str directly, not Option<&str>
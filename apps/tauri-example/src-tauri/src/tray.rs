@@ -1,13 +1,13 @@
-use crate::AppState; // Import AppState from lib.rs
+use crate::{AppState, CounterAction}; // Import AppState from lib.rs
 
 use tauri::{
     AppHandle,
     Manager,
     Runtime,
-    menu::{Menu, MenuBuilder, MenuItemBuilder, PredefinedMenuItem},
+    menu::{CheckMenuItemBuilder, Menu, MenuBuilder, MenuItemBuilder, PredefinedMenuItem},
     tray::{TrayIconBuilder, TrayIconEvent, TrayIcon},
 };
-use tauri_plugin_zubridge::{JsonValue, ZubridgeExt};
+use tauri_plugin_zubridge::{TrayActionMap, ZubridgeExt};
 use serde_json::json;
 
 // Make create_menu public so it can be called from main app lib.rs
@@ -32,11 +32,15 @@ pub fn create_menu<R: Runtime>(
     let increment = MenuItemBuilder::new("Increment").id("increment").build(app_handle)?;
     let decrement = MenuItemBuilder::new("Decrement").id("decrement").build(app_handle)?;
     let reset = MenuItemBuilder::new("Reset Counter").id("reset_counter").build(app_handle)?;
-    let toggle_theme = MenuItemBuilder::new("Toggle Theme").id("toggle_theme").build(app_handle)?;
+    let toggle_theme = CheckMenuItemBuilder::new("Dark Mode")
+        .id("toggle_theme")
+        .checked(state.theme.is_dark)
+        .build(app_handle)?;
     let show_window = MenuItemBuilder::new("Show Window").id("show_window").build(app_handle)?;
     let quit = MenuItemBuilder::new("Quit").id("quit").build(app_handle)?;
 
-    let menu = MenuBuilder::new(app_handle)
+    // A fixed id lets the plugin replace the bound menu when it's rebuilt
+    let menu = MenuBuilder::with_id(app_handle, "tray-menu")
         .items(&[
             &counter_display,
             &theme_display,
@@ -51,47 +55,31 @@ pub fn create_menu<R: Runtime>(
         ])
         .build()?;
 
+    // Let the plugin enable and disable items from the state
+    app_handle.zubridge().bind_menu(menu.clone())?;
+
     Ok(menu)
 }
 
-// Handles system tray events - Updated for v2 event structure
+// Maps tray menu items to the actions the plugin dispatches when they're clicked.
+// Counter actions use the reducer's own enum, so a typo in an action type doesn't compile.
+pub fn action_map() -> tauri_plugin_zubridge::Result<TrayActionMap> {
+    let map = TrayActionMap::new()
+        .typed_action("increment", &CounterAction::Increment)?
+        .typed_action("decrement", &CounterAction::Decrement)?
+        .typed_action("reset_counter", &CounterAction::Reset)?
+        // Checked while the theme is dark
+        .checked("toggle_theme", "theme.is_dark", "THEME:TOGGLE")
+        // Shows the main window again after it was closed to the tray
+        .action_with_payload("show_window", "WINDOW:SHOW", json!({ "label": "main" }))
+        // Nothing to reset while the counter is at zero
+        .enabled_when("reset_counter", |state| state["counter"].as_i64().unwrap_or(0) != 0);
+    Ok(map)
+}
+
+// Handles the tray items that don't map to actions - Updated for v2 event structure
 pub fn handle_tray_item_click<R: Runtime>(app_handle: &AppHandle<R>, id: &str) {
     match id {
-        "increment" => {
-            let _ = dispatch_bridge_action(
-                app_handle,
-                "COUNTER:INCREMENT",
-                Some(json!(1)), // Wrap payload in Some()
-            );
-        }
-        "decrement" => {
-            let _ = dispatch_bridge_action(
-                app_handle,
-                "COUNTER:DECREMENT",
-                Some(json!(1)), // Wrap payload in Some()
-            );
-        }
-        "reset_counter" => {
-            let _ = dispatch_bridge_action(
-                app_handle,
-                "COUNTER:RESET",
-                None, // Wrap payload in None
-            );
-        }
-        "toggle_theme" => {
-            let _ = dispatch_bridge_action(
-                app_handle,
-                "THEME:TOGGLE",
-                None, // Wrap payload in None
-            );
-        }
-        "show_window" => {
-            // Use get_webview_window in v2
-            if let Some(window) = app_handle.get_webview_window("main") {
-                let _ = window.show();
-                let _ = window.set_focus();
-            }
-        }
         "quit" => {
             app_handle.exit(0);
         }
@@ -99,37 +87,6 @@ pub fn handle_tray_item_click<R: Runtime>(app_handle: &AppHandle<R>, id: &str) {
     }
 }
 
-// Dispatch action using the Zubridge plugin
-fn dispatch_bridge_action<R: Runtime>(
-    app_handle: &AppHandle<R>,
-    action_type: &str,
-    payload: Option<JsonValue>,
-) -> Result<(), String> {
-    println!("Tray dispatch_bridge_action called with type: {}", action_type);
-
-    // Create the action object
-    let action = tauri_plugin_zubridge::ZubridgeAction {
-        action_type: action_type.to_string(),
-        payload,
-    };
-
-    // Print the action for debugging
-    println!("Tray creating action: {{ action_type: \"{}\", payload: {:?} }}",
-             action.action_type, action.payload);
-
-    // Use the plugin extension trait to dispatch the action
-    match app_handle.zubridge().dispatch_action(action) {
-        Ok(result) => {
-            println!("Tray action dispatched successfully, result: {:?}", result);
-            Ok(())
-        },
-        Err(e) => {
-            eprintln!("Tray failed to dispatch action: {}", e);
-            Err(format!("Failed to dispatch action: {}", e))
-        }
-    }
-}
-
 // Sets up the system tray - Updated for v2
 pub fn setup_tray<R: Runtime>(app_handle: AppHandle<R>) -> Result<TrayIcon<R>, Box<dyn std::error::Error>> {
     // Need initial state to build the first menu
@@ -165,10 +122,12 @@ pub fn setup_tray<R: Runtime>(app_handle: AppHandle<R>) -> Result<TrayIcon<R>, B
                 ..
             } = event
             {
-                let app = tray.app_handle();
-                if let Some(window) = app.get_webview_window("main") {
-                    let _ = window.show();
-                    let _ = window.set_focus();
+                let action = tauri_plugin_zubridge::ZubridgeAction {
+                    action_type: "WINDOW:SHOW".to_string(),
+                    payload: Some(json!({ "label": "main" })),
+                };
+                if let Err(e) = tray.app_handle().zubridge().dispatch_action(action) {
+                    eprintln!("Failed to show the main window: {}", e);
                 }
             }
         })
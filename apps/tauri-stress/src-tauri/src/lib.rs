@@ -0,0 +1,159 @@
+use std::time::Duration;
+
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use tauri::{Manager, WebviewUrl, WebviewWindowBuilder};
+use tauri_plugin_zubridge::{
+    parse_action, plugin, JsonValue, LoadProfile, StateManager, ZubridgeAction, ZubridgeExt, ZubridgeOptions,
+};
+
+// The load is configured with environment variables, so runs are easy to script and compare
+#[derive(Clone, Debug, Serialize)]
+pub struct StressConfig {
+    /// Dispatches per second, or 0 for as fast as possible
+    rate: u32,
+    seconds: u64,
+    threads: usize,
+    /// Items in the initial state, to make every emitted state large
+    items: usize,
+    /// Windows subscribed to state updates
+    windows: usize,
+}
+
+impl StressConfig {
+    fn from_env() -> Self {
+        fn var<T: std::str::FromStr>(name: &str, default: T) -> T {
+            std::env::var(name).ok().and_then(|value| value.parse().ok()).unwrap_or(default)
+        }
+
+        Self {
+            rate: var("STRESS_RATE", 200),
+            seconds: var("STRESS_SECONDS", 10),
+            threads: var("STRESS_THREADS", 2),
+            items: var("STRESS_ITEMS", 10_000),
+            windows: var("STRESS_WINDOWS", 4),
+        }
+    }
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Item {
+    id: usize,
+    label: String,
+    value: u64,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct StressState {
+    counter: u64,
+    items: Vec<Item>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(tag = "type", content = "payload")]
+pub enum StressAction {
+    #[serde(rename = "COUNTER:INCREMENT")]
+    Increment,
+    #[serde(rename = "ITEM:UPDATE")]
+    UpdateItem { index: usize, value: u64 },
+    #[serde(rename = "ITEM:ADD")]
+    AddItem { label: String },
+}
+
+pub struct StressStateManager {
+    state: StressState,
+}
+
+impl StressStateManager {
+    pub fn new(items: usize) -> Self {
+        let items = (0..items)
+            .map(|id| Item {
+                id,
+                label: format!("Item {}", id),
+                value: 0,
+            })
+            .collect();
+        Self {
+            state: StressState { counter: 0, items },
+        }
+    }
+}
+
+impl StateManager for StressStateManager {
+    fn get_initial_state(&self) -> JsonValue {
+        serde_json::to_value(&self.state).unwrap_or_default()
+    }
+
+    fn validate_action(&self, action: &JsonValue) -> tauri_plugin_zubridge::Result<()> {
+        parse_action::<StressAction>(action).map(drop)
+    }
+
+    fn dispatch_action(&mut self, action: JsonValue) -> JsonValue {
+        match serde_json::from_value::<StressAction>(action) {
+            Ok(StressAction::Increment) => self.state.counter += 1,
+            Ok(StressAction::UpdateItem { index, value }) => {
+                if let Some(item) = self.state.items.get_mut(index) {
+                    item.value = value;
+                }
+            }
+            Ok(StressAction::AddItem { label }) => {
+                let id = self.state.items.len();
+                self.state.items.push(Item { id, label, value: 0 });
+            }
+            Err(e) => log::warn!("stress: ignoring unknown action: {}", e),
+        }
+        self.get_initial_state()
+    }
+}
+
+/// A mix of small updates, updates deep in the large item list, and growth of the state
+fn generate_action(index: u64, items: usize) -> ZubridgeAction {
+    let (action_type, payload) = match index % 10 {
+        0 => ("ITEM:ADD", Some(json!({ "label": format!("Added {}", index) }))),
+        1..=5 => ("ITEM:UPDATE", Some(json!({ "index": index as usize % items.max(1), "value": index }))),
+        _ => ("COUNTER:INCREMENT", None),
+    };
+    ZubridgeAction {
+        action_type: action_type.to_string(),
+        payload,
+    }
+}
+
+#[cfg_attr(mobile, tauri::mobile_entry_point)]
+pub fn run() {
+    let config = StressConfig::from_env();
+    let state_manager = StressStateManager::new(config.items);
+
+    tauri::Builder::default()
+        .plugin(plugin(state_manager, ZubridgeOptions::default()))
+        .setup(move |app| {
+            for index in 0..config.windows {
+                let label = format!("load-{}", index);
+                WebviewWindowBuilder::new(app, &label, WebviewUrl::default())
+                    .title(format!("Zubridge Stress ({})", label))
+                    .inner_size(320.0, 200.0)
+                    .build()?;
+            }
+
+            let app_handle = app.app_handle().clone();
+            let config = config.clone();
+            std::thread::spawn(move || {
+                // Give the windows time to load and subscribe before the load starts
+                std::thread::sleep(Duration::from_secs(2));
+
+                let items = config.items;
+                let profile = LoadProfile::new(move |index| generate_action(index, items))
+                    .with_rate(config.rate)
+                    .with_duration(Duration::from_secs(config.seconds))
+                    .with_threads(config.threads);
+                println!("stress: running {:?}", config);
+                let report = app_handle.zubridge().run_load(&profile);
+                let report = json!({ "config": config, "report": report });
+                println!("{}", serde_json::to_string_pretty(&report).unwrap_or_default());
+                app_handle.exit(0);
+            });
+            Ok(())
+        })
+        .run(tauri::generate_context!())
+        .expect("error while running tauri application");
+}
@@ -1,11 +1,47 @@
-const COMMANDS: &[&str] = &["get_initial_state", "dispatch_action"];
+const COMMANDS: &[&str] = &[
+  "get_initial_state",
+  "dispatch_action",
+  "dispatch_batch",
+  "get_schema",
+  "set_ephemeral",
+  "get_ephemeral",
+  "load_slice",
+  "unload_slice",
+  "subscribe_state",
+  "unsubscribe_state",
+  "acknowledge_state",
+  "upload_blob",
+  "handshake",
+  "health",
+  "version",
+  "get_event_names",
+  "get_startup",
+  "request",
+  "respond",
+  "publish",
+  "report_error",
+  "save_snapshot",
+  "list_snapshots",
+  "diff_snapshots",
+  "restore_snapshot",
+  "simulate_action",
+  "export_state_graph",
+  "confirm",
+  "cancel_confirmation",
+  "search",
+  "report_appearance",
+  "load_fixture",
+  "soak",
+];
 
 fn main() {
   tauri_build::try_build(
     tauri_build::Attributes::new()
       .plugin(
         "zubridge",
-        tauri_build::InlinedPlugin::new().commands(&COMMANDS),
+        tauri_build::InlinedPlugin::new()
+          .commands(COMMANDS)
+          .permissions_path_pattern("permissions/default.toml"),
       )
   )
   .unwrap_or_else(|_| {
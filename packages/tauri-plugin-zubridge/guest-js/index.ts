@@ -1,5 +1,7 @@
 import { invoke } from '@tauri-apps/api/tauri';
+import { Channel } from '@tauri-apps/api/core';
 import { listen, Event } from '@tauri-apps/api/event';
+import { getCurrentWebviewWindow } from '@tauri-apps/api/webviewWindow';
 
 /**
  * Zubridge action with type and optional payload
@@ -10,11 +12,124 @@ export interface ZubridgeAction {
 }
 
 /**
- * Get the initial state from the Tauri backend
+ * JSON Schema of the state tree, and of each action keyed by action type
+ */
+export interface StateSchema {
+  state: unknown;
+  actions: Record<string, unknown>;
+}
+
+/**
+ * The protocol version this package speaks. The backend must report the same one
+ */
+export const PROTOCOL_VERSION = 1;
+
+/**
+ * The backend plugin's version
+ */
+export interface VersionInfo {
+  plugin_version: string;
+  protocol_version: number;
+  features: string[];
+}
+
+/**
+ * The backend plugin's status
+ */
+export interface Health {
+  version: VersionInfo;
+  state_manager: boolean;
+  loading: boolean;
+  revision: number;
+  windows_behind: number;
+  subsystems: { name: string; status: 'running' | 'stopped' | 'registered' }[];
+}
+
+/**
+ * Get the backend plugin's version and protocol version
+ */
+export async function getVersion(): Promise<VersionInfo> {
+  return await invoke<VersionInfo>('plugin:zubridge|version');
+}
+
+/**
+ * The names of the events the backend emits, scoped to the app identifier unless the plugin
+ * was set up with `legacy_event_names`
+ */
+export interface EventNames {
+  stateUpdate: string;
+  importProgress: string;
+  ephemeralUpdate: string;
+  sliceUpdate: string;
+  diagnostics: string;
+  startup: string;
+  request: string;
+  message: string;
+}
+
+let eventNames: Promise<EventNames> | undefined;
+
+/**
+ * Get the names of the events the backend emits. They don't change while the app runs, so the
+ * answer is cached
+ */
+export async function getEventNames(): Promise<EventNames> {
+  eventNames ??= invoke<EventNames>('plugin:zubridge|get_event_names').catch((error) => {
+    eventNames = undefined;
+    throw new Error(
+      `zubridge: failed to get the event names, so events can't be received. Check that the window is ` +
+        `granted 'zubridge:allow-get-event-names'. Cause: ${error instanceof Error ? error.message : String(error)}`,
+    );
+  });
+  return await eventNames;
+}
+
+/**
+ * Get the backend plugin's status, including its background subsystems
+ */
+export async function getHealth(): Promise<Health> {
+  return await invoke<Health>('plugin:zubridge|health');
+}
+
+/**
+ * Check that the backend speaks the same protocol as this package. Call it at startup
+ * @returns Promise that resolves to the backend's version, or rejects if it's incompatible
+ */
+export async function assertCompatible(): Promise<VersionInfo> {
+  const version = await getVersion();
+  if (version.protocol_version !== PROTOCOL_VERSION) {
+    throw new Error(
+      `tauri-plugin-zubridge ${version.plugin_version} speaks protocol ${version.protocol_version}, ` +
+        `but tauri-plugin-zubridge-api expects protocol ${PROTOCOL_VERSION}. Update both packages together`,
+    );
+  }
+  return version;
+}
+
+/**
+ * `get_initial_state`'s response when the frontend sends its protocol version
+ */
+export interface InitialStateEnvelope<T> {
+  protocol_version: number;
+  state: T;
+}
+
+/**
+ * Get the initial state from the Tauri backend. Sends this package's protocol version, so the
+ * backend rejects it with a clear error instead of answering in a shape it doesn't expect
  * @returns Promise that resolves to the serialized state
  */
 export async function getInitialState<T>(): Promise<T> {
-  return await invoke<T>('plugin:zubridge|get_initial_state');
+  const envelope = await invoke<InitialStateEnvelope<T>>('plugin:zubridge|get_initial_state', {
+    protocolVersion: PROTOCOL_VERSION,
+  });
+  if (envelope.protocol_version !== PROTOCOL_VERSION) {
+    throw new Error(
+      `tauri-plugin-zubridge answered with protocol ${envelope.protocol_version}, ` +
+        `but tauri-plugin-zubridge-api expects protocol ${PROTOCOL_VERSION}. Update both packages together`,
+    );
+  }
+  return envelope.state;
 }
 
 /**
@@ -26,29 +141,817 @@ export async function dispatchAction<T>(action: ZubridgeAction): Promise<T> {
   return await invoke<T>('plugin:zubridge|dispatch_action', { action });
 }
 
+/**
+ * A step a failed dispatch went through, such as an interceptor or the reducer
+ */
+export interface Breadcrumb {
+  step: string;
+  action_type: string;
+  outcome: string;
+  duration_micros: number;
+}
+
+/**
+ * The error a failed dispatch rejects with when the plugin's `dispatch_breadcrumbs` is on
+ */
+export interface TracedError {
+  message: string;
+  breadcrumbs: Breadcrumb[];
+}
+
+/**
+ * Whether a dispatch error carries breadcrumbs
+ */
+export function isTracedError(error: unknown): error is TracedError {
+  return typeof error === 'object' && error !== null && 'message' in error && 'breadcrumbs' in error;
+}
+
+/**
+ * Set the value at a dot-separated state path listed in the `mutable_paths` option
+ * @returns Promise that resolves to the serialized updated state
+ */
+export async function setPath<T>(path: string, value: unknown): Promise<T> {
+  return await dispatchAction<T>({ type: '__ZUBRIDGE:SET_PATH', payload: { path, value } });
+}
+
+/**
+ * Remove the value at a dot-separated state path listed in the `mutable_paths` option
+ * @returns Promise that resolves to the serialized updated state
+ */
+export async function deletePath<T>(path: string): Promise<T> {
+  return await dispatchAction<T>({ type: '__ZUBRIDGE:DELETE_PATH', payload: { path } });
+}
+
+/**
+ * Append a value to the array at a dot-separated state path listed in the `mutable_paths` option
+ * @returns Promise that resolves to the serialized updated state
+ */
+export async function pushPath<T>(path: string, value: unknown): Promise<T> {
+  return await dispatchAction<T>({ type: '__ZUBRIDGE:PUSH', payload: { path, value } });
+}
+
+/**
+ * How a batch handles an action that fails: `atomic` stops and restores the state from before
+ * the batch, `best-effort` dispatches every action and reports the failed ones
+ */
+export type BatchMode = 'atomic' | 'best-effort';
+
+/**
+ * The outcome of one action of a batch
+ */
+export interface BatchResult {
+  index: number;
+  ok: boolean;
+  error?: string | null;
+}
+
+/**
+ * Dispatch actions one after another, reporting the outcome of each as it's applied
+ * @param actions Actions to dispatch
+ * @param onResult Called with the outcome of each action, e.g. to show progress
+ * @param mode How a failed action is handled. Defaults to `best-effort`
+ * @returns Promise that resolves to the serialized state once the whole batch is applied
+ */
+export async function dispatchBatch<T>(
+  actions: ZubridgeAction[],
+  onResult?: (result: BatchResult) => void,
+  mode: BatchMode = 'best-effort',
+): Promise<T> {
+  const channel = new Channel<BatchResult>();
+  if (onResult) {
+    channel.onmessage = onResult;
+  }
+  return await invoke<T>('plugin:zubridge|dispatch_batch', { actions, mode, onResult: channel });
+}
+
+/**
+ * JSON-encode a payload for a header, escaping non-ASCII characters, which headers can't carry
+ */
+function headerJson(value: unknown): string {
+  return JSON.stringify(value).replace(/[\u007f-\uffff]/g, (c) => `\\u${c.charCodeAt(0).toString(16).padStart(4, '0')}`);
+}
+
+/**
+ * Send binary data too large for an action's payload in chunks to a temp file, then dispatch
+ * an action with the `upload` id the backend claims the file with and its size in `bytes` added
+ * to its payload
+ * @param data The data to upload
+ * @param actionType The type of the action dispatched once the upload is complete
+ * @param payload Optional fields added to the action's payload
+ * @param chunkSize How many bytes are sent per call. Defaults to 1 MiB
+ * @returns Promise that resolves to the serialized state once the action is dispatched
+ */
+export async function uploadBlob<T>(
+  data: Blob | ArrayBuffer | Uint8Array,
+  actionType: string,
+  payload?: Record<string, unknown>,
+  chunkSize = 1024 * 1024,
+): Promise<T> {
+  const blob = data instanceof Blob ? data : new Blob([data]);
+  const id = crypto.randomUUID();
+  let offset = 0;
+  for (;;) {
+    const chunk = new Uint8Array(await blob.slice(offset, offset + chunkSize).arrayBuffer());
+    offset += chunk.length;
+    const headers: Record<string, string> = { 'zubridge-upload-id': id };
+    if (offset >= blob.size) {
+      headers['zubridge-action-type'] = actionType;
+      if (payload) {
+        headers['zubridge-payload'] = headerJson(payload);
+      }
+      return await invoke<T>('plugin:zubridge|upload_blob', chunk, { headers });
+    }
+    await invoke('plugin:zubridge|upload_blob', chunk, { headers });
+  }
+}
+
+/**
+ * Get the JSON Schema of the state tree and each action
+ * @returns Promise that resolves to the schema, or null if the state manager doesn't provide one
+ */
+export async function getSchema(): Promise<StateSchema | null> {
+  return await invoke<StateSchema | null>('plugin:zubridge|get_schema');
+}
+
+/**
+ * Build the URL of a state snapshot served by the `zubridge://` protocol
+ * @param path Optional dot-separated path of a slice, e.g. `theme.is_dark`
+ * @returns URL that can be passed to `fetch`
+ */
+export function stateSnapshotUrl(path?: string): string {
+  const query = path ? `?path=${encodeURIComponent(path)}` : '';
+  const internals = (globalThis as any).__TAURI_INTERNALS__;
+  if (typeof internals?.convertFileSrc === 'function') {
+    return `${internals.convertFileSrc('state', 'zubridge')}${query}`;
+  }
+  return `zubridge://localhost/state${query}`;
+}
+
+/**
+ * Turn a blob URL stored in the state, as returned by the backend's `put_blob`, into a URL the
+ * webview can load, e.g. as an image's `src`
+ * @param reference The blob's `zubridge-blob://localhost/<id>?type=...` URL
+ */
+export function blobUrl(reference: string): string {
+  const match = /^zubridge-blob:\/\/localhost\/([0-9a-f]{64})(\?.*)?$/.exec(reference);
+  if (!match) return reference;
+  const internals = (globalThis as any).__TAURI_INTERNALS__;
+  if (typeof internals?.convertFileSrc === 'function') {
+    return `${internals.convertFileSrc(match[1], 'zubridge-blob')}${match[2] ?? ''}`;
+  }
+  return reference;
+}
+
+/**
+ * Fetch the current state, or a slice of it, through the `zubridge://` protocol.
+ * Large states are streamed instead of being returned through a single invoke result.
+ * @param path Optional dot-separated path of a slice
+ * @returns Promise that resolves to the state or slice
+ */
+export async function fetchState<T>(path?: string): Promise<T> {
+  const response = await fetch(stateSnapshotUrl(path));
+  if (!response.ok) {
+    throw new Error(`Failed to fetch state${path ? ` at ${path}` : ''}: ${response.status}`);
+  }
+  return (await response.json()) as T;
+}
+
 /**
  * Subscribe to state updates
  * @param callback Function to call when state updates
- * @param event Custom event name
+ * @param event Custom event name. Defaults to the backend's state update event
  * @returns Promise that resolves to an unlisten function
  */
 export async function subscribeToState<T>(
   callback: (state: T) => void,
-  event = 'zubridge://state-update',
+  event?: string,
 ): Promise<() => void> {
-  return await listen<string>(event, (eventPayload: Event<string>) => {
-    const state = JSON.parse(eventPayload.payload) as T;
+  event ??= (await getEventNames()).stateUpdate;
+  return await listen<string | SignedPayload>(event, async (eventPayload: Event<string | SignedPayload>) => {
+    const payload = eventPayload.payload;
+    const state = isSignedPayload(payload) ? await verifyPayload<T>(payload) : (JSON.parse(payload) as T);
     callback(state);
   });
 }
 
+/**
+ * A state update signed by the plugin when `payload_integrity` is enabled
+ */
+export interface SignedPayload {
+  revision: number;
+  /** The JSON-encoded payload */
+  data: string;
+  /** Hex HMAC-SHA256 of `<revision>.<data>` */
+  signature: string;
+}
+
+let signingKey: Promise<CryptoKey> | undefined;
+
+function isSignedPayload(payload: unknown): payload is SignedPayload {
+  return typeof payload === 'object' && payload !== null && 'signature' in payload && 'data' in payload;
+}
+
+/**
+ * Get the key state updates are signed with. The plugin hands it out once per page load, so
+ * call this before any other script on the page can
+ * @returns Promise that resolves to the key, imported for verifying signatures
+ */
+export async function handshake(): Promise<CryptoKey> {
+  signingKey ??= invoke<{ key: string; algorithm: string }>('plugin:zubridge|handshake').then(({ key }) => {
+    const bytes = new Uint8Array(key.match(/../g)!.map((byte) => parseInt(byte, 16)));
+    return crypto.subtle.importKey('raw', bytes, { name: 'HMAC', hash: 'SHA-256' }, false, ['verify']);
+  });
+  return await signingKey;
+}
+
+/**
+ * Check the signature of a signed state update and decode it
+ * @param payload The signed update
+ * @returns Promise that resolves to the payload, or rejects if the signature doesn't match
+ */
+export async function verifyPayload<T>(payload: SignedPayload): Promise<T> {
+  const key = await handshake();
+  const signature = new Uint8Array(payload.signature.match(/../g)!.map((byte) => parseInt(byte, 16)));
+  const message = new TextEncoder().encode(`${payload.revision}.${payload.data}`);
+  if (!(await crypto.subtle.verify('HMAC', key, signature, message))) {
+    throw new Error(`Invalid signature on state update ${payload.revision}`);
+  }
+  return JSON.parse(payload.data) as T;
+}
+
+/**
+ * Validate messages to the plugin in the isolation application's `__TAURI_ISOLATION_HOOK__`.
+ * Other messages are passed through unchanged
+ * @param message The message passed to the hook
+ * @param maxBytes The largest accepted payload, 1 MiB by default
+ * @returns The message, or throws if it's malformed
+ */
+export function validateIsolationMessage<M extends { cmd: string; payload?: unknown }>(
+  message: M,
+  maxBytes = 1024 * 1024,
+): M {
+  if (!message.cmd.startsWith('plugin:zubridge|')) {
+    return message;
+  }
+  const args = (message.payload ?? {}) as Record<string, unknown>;
+  if (JSON.stringify(args).length > maxBytes) {
+    throw new Error(`Zubridge message ${message.cmd} exceeds ${maxBytes} bytes`);
+  }
+  const actions = message.cmd.endsWith('|dispatch_batch') ? args.actions : message.cmd.endsWith('|dispatch_action') ? [args.action] : [];
+  if (!Array.isArray(actions) || !actions.every((action) => typeof action?.type === 'string' || typeof action?.action_type === 'string')) {
+    throw new Error(`Zubridge message ${message.cmd} has a malformed action`);
+  }
+  return message;
+}
+
+/**
+ * A state update received through a channel subscription
+ */
+export interface StateUpdate<T> {
+  revision: number;
+  state: T;
+  /** Updates superseded by this one while the window was catching up */
+  skipped: number;
+}
+
+/**
+ * Receive state updates through a channel owned by this window instead of the global event.
+ * Updates are acknowledged once the callback returns, and the backend coalesces the ones
+ * published while this window is behind
+ * @param callback Function to call with the current state and then with every update
+ * @returns Promise that resolves to an unsubscribe function
+ */
+export async function subscribeToStateChannel<T>(callback: (state: T) => void): Promise<() => Promise<void>> {
+  const channel = new Channel<StateUpdate<T>>();
+  channel.onmessage = (update) => {
+    try {
+      if (isSignedPayload(update.state)) {
+        verifyPayload<T>(update.state).then(callback, (error) => console.error(error));
+      } else {
+        callback(update.state);
+      }
+    } finally {
+      void invoke('plugin:zubridge|acknowledge_state');
+    }
+  };
+  const state = await invoke<T>('plugin:zubridge|subscribe_state', { onUpdate: channel });
+  callback(state);
+  return async () => {
+    await invoke('plugin:zubridge|unsubscribe_state');
+  };
+}
+
+/**
+ * Publish a transient value on the ephemeral channel, or clear it with null
+ * @param path Ephemeral path, which must be allowed by the plugin's `ephemeral_paths` option
+ * @param value Value to publish
+ */
+export async function setEphemeral(path: string, value: unknown): Promise<void> {
+  await invoke('plugin:zubridge|set_ephemeral', { path, value });
+}
+
+/**
+ * Get all current ephemeral values
+ * @returns Promise that resolves to the ephemeral values keyed by path
+ */
+export async function getEphemeral(): Promise<Record<string, unknown>> {
+  return await invoke<Record<string, unknown>>('plugin:zubridge|get_ephemeral');
+}
+
+/**
+ * Subscribe to ephemeral value updates
+ * @param callback Function to call with the updated path and value
+ * @returns Promise that resolves to an unlisten function
+ */
+export async function subscribeToEphemeral(
+  callback: (path: string, value: unknown) => void,
+): Promise<() => void> {
+  return await listen<{ path: string; value: unknown }>((await getEventNames()).ephemeralUpdate, (event) => {
+    callback(event.payload.path, event.payload.value);
+  });
+}
+
+/**
+ * Load a lazy slice for this window. From then on, the window receives targeted updates for it.
+ * @param slice Top-level state key listed in the plugin's `lazy_slices` option
+ * @returns Promise that resolves to the current value of the slice
+ */
+export async function loadSlice<T>(slice: string): Promise<T> {
+  return await invoke<T>('plugin:zubridge|load_slice', { slice });
+}
+
+/**
+ * Stop receiving updates for a lazy slice
+ * @param slice Top-level state key listed in the plugin's `lazy_slices` option
+ */
+export async function unloadSlice(slice: string): Promise<void> {
+  await invoke('plugin:zubridge|unload_slice', { slice });
+}
+
+/**
+ * One JSON Patch operation, as sent for slices listed in the plugin's `diffed_slices` option
+ */
+export interface PatchOperation {
+  op: 'add' | 'remove' | 'replace';
+  path: string;
+  value?: unknown;
+}
+
+type SliceUpdate<T> = { slice: string; value: T } | { slice: string; patch: PatchOperation[] };
+
+/**
+ * Apply a patch made by the plugin to a copy of `value`
+ */
+function applyPatch<T>(value: T, patch: PatchOperation[]): T {
+  let root: unknown = JSON.parse(JSON.stringify(value));
+  for (const operation of patch) {
+    if (operation.path === '') {
+      root = operation.value;
+      continue;
+    }
+    const segments = operation.path
+      .slice(1)
+      .split('/')
+      .map((segment) => segment.replace(/~1/g, '/').replace(/~0/g, '~'));
+    const key = segments.pop() as string;
+    let parent = root as Record<string, unknown>;
+    for (const segment of segments) {
+      parent = parent[segment] as Record<string, unknown>;
+    }
+    if (Array.isArray(parent)) {
+      if (operation.op === 'remove') {
+        parent.splice(Number(key), 1);
+      } else if (key === '-') {
+        parent.push(operation.value);
+      } else {
+        parent[Number(key)] = operation.value;
+      }
+    } else if (operation.op === 'remove') {
+      delete parent[key];
+    } else {
+      parent[key] = operation.value;
+    }
+  }
+  return root as T;
+}
+
+/**
+ * Load a lazy slice and subscribe to its updates
+ * @param slice Top-level state key listed in the plugin's `lazy_slices` option
+ * @param callback Function to call with the current value and on every update
+ * @returns Promise that resolves to an unsubscribe function, which also unloads the slice
+ */
+export async function subscribeToSlice<T>(
+  slice: string,
+  callback: (value: T) => void,
+): Promise<() => void> {
+  let current: T | undefined;
+  let loaded = false;
+  const unlisten = await listen<SliceUpdate<T>>((await getEventNames()).sliceUpdate, (event) => {
+    if (event.payload.slice !== slice) {
+      return;
+    }
+    if ('patch' in event.payload) {
+      // Patches apply to the value already held, so one arriving before the slice loaded is dropped
+      if (!loaded) {
+        return;
+      }
+      current = applyPatch(current as T, event.payload.patch);
+    } else {
+      current = event.payload.value;
+      loaded = true;
+    }
+    callback(current);
+  });
+  current = await loadSlice<T>(slice);
+  loaded = true;
+  callback(current);
+
+  return () => {
+    unlisten();
+    void unloadSlice(slice);
+  };
+}
+
+/**
+ * Subscribe to the results of a named query of a database-backed state manager. The query runs
+ * in the backend, and only changes to its results are sent
+ * @param query Name the query was registered under with `DatabaseConfig::query`
+ * @param callback Function to call with the current results and whenever they change
+ * @returns Promise that resolves to an unsubscribe function
+ */
+export async function subscribeToQuery<T>(query: string, callback: (results: T) => void): Promise<() => void> {
+  return await subscribeToSlice<T>(query, callback);
+}
+
+/**
+ * Startup progress reported while the app hydrates its state
+ */
+export interface StartupProgress {
+  stage: string;
+  progress: number;
+  done: boolean;
+}
+
+/**
+ * Get the latest startup progress
+ * @returns Promise that resolves to the startup progress
+ */
+export async function getStartup(): Promise<StartupProgress> {
+  return await invoke<StartupProgress>('plugin:zubridge|get_startup');
+}
+
+/**
+ * Subscribe to startup progress, starting with the latest reported progress
+ * @param callback Function to call with the startup progress
+ * @returns Promise that resolves to an unlisten function
+ */
+export async function subscribeToStartup(
+  callback: (progress: StartupProgress) => void,
+): Promise<() => void> {
+  const unlisten = await listen<StartupProgress>((await getEventNames()).startup, (event) => {
+    callback(event.payload);
+  });
+  callback(await getStartup());
+  return unlisten;
+}
+
+/**
+ * A request sent to this window by another window or by Rust
+ */
+export interface ZubridgeRequest<T = unknown> {
+  id: string;
+  topic: string;
+  payload: T;
+  /** Label of the requesting window, or null for requests made from Rust */
+  from: string | null;
+}
+
+/**
+ * Ask another window a question and wait for its answer
+ * @param window Label of the window to ask
+ * @param topic Topic the window handles with `onRequest`
+ * @param payload Optional payload passed to the handler
+ * @returns Promise that resolves to the handler's result, and rejects if it throws or times out
+ */
+export async function request<T>(window: string, topic: string, payload?: unknown): Promise<T> {
+  return await invoke<T>('plugin:zubridge|request', { target: window, topic, payload });
+}
+
+/**
+ * Answer requests on a topic sent to this window
+ * @param topic Topic to answer
+ * @param handler Function returning the answer, or a promise of it. Errors it throws are passed to the requester
+ * @returns Promise that resolves to an unlisten function
+ */
+export async function onRequest<T>(
+  topic: string,
+  handler: (payload: T, from: string | null) => unknown | Promise<unknown>,
+): Promise<() => void> {
+  const names = await getEventNames();
+  return await getCurrentWebviewWindow().listen<ZubridgeRequest<T>>(names.request, async (event) => {
+    const { id, payload, from } = event.payload;
+    if (event.payload.topic !== topic) {
+      return;
+    }
+    try {
+      const result = await handler(payload, from);
+      await invoke('plugin:zubridge|respond', { id, result: result ?? null });
+    } catch (error) {
+      const message = error instanceof Error ? error.message : String(error);
+      await invoke('plugin:zubridge|respond', { id, error: message });
+    }
+  });
+}
+
+/**
+ * A message published on a topic
+ */
+export interface ZubridgeMessage<T = unknown> {
+  topic: string;
+  payload: T;
+  /** Label of the publishing window, or null for messages published from Rust */
+  from: string | null;
+}
+
+/**
+ * Publish a message that doesn't belong in the store, like a toast or focus request
+ * @param topic Topic to publish on
+ * @param payload Optional message payload
+ * @param target Optional label pattern of the windows to deliver to, where `*` is a wildcard. Defaults to every window
+ */
+export async function publish(topic: string, payload?: unknown, target?: string): Promise<void> {
+  await invoke('plugin:zubridge|publish', { topic, payload, target });
+}
+
+/**
+ * Subscribe to messages on a topic delivered to this window
+ * @param topic Topic to subscribe to
+ * @param callback Function to call with each message payload and the publishing window
+ * @returns Promise that resolves to an unlisten function
+ */
+export async function subscribe<T>(
+  topic: string,
+  callback: (payload: T, from: string | null) => void,
+): Promise<() => void> {
+  const names = await getEventNames();
+  return await getCurrentWebviewWindow().listen<ZubridgeMessage<T>>(names.message, (event) => {
+    if (event.payload.topic === topic) {
+      callback(event.payload.payload, event.payload.from);
+    }
+  });
+}
+
+/**
+ * Context for an error reported to the backend
+ */
+export interface ErrorContext {
+  /** The component that threw */
+  component?: string;
+  /** The state revision the window had rendered */
+  revision?: number;
+}
+
+/**
+ * Forward an error caught in this window to the backend's error sink
+ * @param error The error to report
+ * @param context Optional component and state revision
+ */
+export async function reportError(error: unknown, context: ErrorContext = {}): Promise<void> {
+  const message = error instanceof Error ? error.message : String(error);
+  const stack = error instanceof Error ? error.stack : undefined;
+  await invoke('plugin:zubridge|report_error', {
+    error: { message, stack, component: context.component, revision: context.revision },
+  });
+}
+
+/**
+ * A JSON Patch operation
+ */
+export interface PatchOperation {
+  op: 'add' | 'remove' | 'replace';
+  path: string;
+  value?: unknown;
+}
+
+/**
+ * The difference between two state snapshots
+ */
+export interface SnapshotDiff {
+  /** JSON Patch turning the first snapshot into the second */
+  patch: PatchOperation[];
+  added: number;
+  removed: number;
+  changed: number;
+}
+
+/**
+ * Save the current state as a snapshot
+ * @param name Optional snapshot id. Defaults to the current timestamp
+ * @returns Promise that resolves to the snapshot id
+ */
+export async function saveSnapshot(name?: string): Promise<string> {
+  return await invoke<string>('plugin:zubridge|save_snapshot', { name });
+}
+
+/**
+ * List the ids of the saved snapshots
+ */
+export async function listSnapshots(): Promise<string[]> {
+  return await invoke<string[]>('plugin:zubridge|list_snapshots');
+}
+
+/**
+ * Compare two saved snapshots
+ * @param a Id of the first snapshot
+ * @param b Id of the second snapshot
+ */
+export async function diffSnapshots(a: string, b: string): Promise<SnapshotDiff> {
+  return await invoke<SnapshotDiff>('plugin:zubridge|diff_snapshots', { a, b });
+}
+
+/**
+ * Restore a saved snapshot, merged into the current state with the app's merge strategies.
+ * Needs the `zubridge:allow-restore-snapshot` permission. Fails from a read-only window, while
+ * the session is locked, or if the user isn't authorized for `__ZUBRIDGE:RESTORE_SNAPSHOT`
+ * @param id Id of the snapshot
+ * @returns The restored state
+ */
+export async function restoreSnapshot<T = unknown>(id: string): Promise<T> {
+  return await invoke<T>('plugin:zubridge|restore_snapshot', { id });
+}
+
+/**
+ * The state an action would produce, and how it differs from the current state
+ */
+export interface Simulation<T = unknown> {
+  state: T;
+  diff: SnapshotDiff;
+}
+
+/**
+ * Run an action against a copy of the current state without committing it
+ * @param action The action to simulate
+ */
+export async function simulateAction<T>(action: ZubridgeAction): Promise<Simulation<T>> {
+  return await invoke<Simulation<T>>('plugin:zubridge|simulate_action', { action });
+}
+
+/**
+ * Draw the shape of the state, its slices and the effects writing to them
+ * @param format 'mermaid' for a Mermaid flowchart or 'graphviz' for a DOT digraph
+ */
+export async function exportStateGraph(format: 'mermaid' | 'graphviz' = 'mermaid'): Promise<string> {
+  return await invoke<string>('plugin:zubridge|export_state_graph', { format });
+}
+
+/**
+ * Load a development fixture into the slice it's named after. Only available in debug builds
+ * @param name The fixture to load, or every fixture if omitted
+ * @returns Promise that resolves to the serialized state with the fixtures loaded
+ */
+export async function loadFixture<T>(name?: string): Promise<T> {
+  return await invoke<T>('plugin:zubridge|load_fixture', { name });
+}
+
+/**
+ * Run an action that was held for confirmation
+ * @param token The token from the `confirmations` slice
+ * @returns Promise that resolves to the updated state
+ */
+export async function confirm<T>(token: string): Promise<T> {
+  return await invoke<T>('plugin:zubridge|confirm', { token });
+}
+
+/**
+ * Drop an action that was held for confirmation
+ * @param token The token from the `confirmations` slice
+ */
+export async function cancelConfirmation(token: string): Promise<void> {
+  await invoke('plugin:zubridge|cancel_confirmation', { token });
+}
+
+/**
+ * A full-text search result
+ */
+export interface SearchHit {
+  id: string;
+  score: number;
+}
+
+/**
+ * Search a full-text index kept by the backend. Requires the plugin's `search` feature
+ * @param index Name of the search index
+ * @param query The search query
+ * @param limit Maximum number of results. Defaults to 20
+ * @returns Promise that resolves to the matching ids, best match first
+ */
+export async function search(index: string, query: string, limit?: number): Promise<SearchHit[]> {
+  return await invoke<SearchHit[]>('plugin:zubridge|search', { index, query, limit });
+}
+
+/**
+ * OS appearance and accessibility settings, as kept in the `system.appearance` slice
+ */
+export interface Appearance {
+  theme?: 'light' | 'dark' | null;
+  accent_color?: string | null;
+  reduced_motion?: boolean | null;
+  high_contrast?: boolean | null;
+}
+
+function readAccentColor(): string | undefined {
+  if (typeof CSS === 'undefined' || !CSS.supports('color', 'AccentColor')) {
+    return undefined;
+  }
+  const probe = document.createElement('div');
+  probe.style.color = 'AccentColor';
+  probe.style.display = 'none';
+  document.body.appendChild(probe);
+  const color = getComputedStyle(probe).color;
+  probe.remove();
+  return color;
+}
+
+/**
+ * Report this window's reduced motion, contrast and accent color settings to the backend,
+ * and keep reporting them as they change. Call it from a writable window, since reports from a
+ * read-only one are rejected
+ * @returns A function that stops watching
+ */
+export function watchAppearance(): () => void {
+  const reducedMotion = window.matchMedia('(prefers-reduced-motion: reduce)');
+  const highContrast = window.matchMedia('(prefers-contrast: more)');
+  const report = () => {
+    const appearance: Appearance = {
+      reduced_motion: reducedMotion.matches,
+      high_contrast: highContrast.matches,
+      accent_color: readAccentColor(),
+    };
+    invoke('plugin:zubridge|report_appearance', { appearance }).catch((error) => {
+      console.warn('zubridge: failed to report the appearance', error);
+    });
+  };
+
+  report();
+  reducedMotion.addEventListener('change', report);
+  highContrast.addEventListener('change', report);
+  return () => {
+    reducedMotion.removeEventListener('change', report);
+    highContrast.removeEventListener('change', report);
+  };
+}
+
 /**
  * Zubridge API
  */
 export const zubridge = {
+  getVersion,
+  getEventNames,
+  getHealth,
+  assertCompatible,
   getInitialState,
   dispatchAction,
+  isTracedError,
+  setPath,
+  deletePath,
+  pushPath,
+  dispatchBatch,
+  uploadBlob,
+  getSchema,
+  fetchState,
+  stateSnapshotUrl,
+  blobUrl,
   subscribeToState,
+  subscribeToStateChannel,
+  handshake,
+  verifyPayload,
+  validateIsolationMessage,
+  setEphemeral,
+  getEphemeral,
+  subscribeToEphemeral,
+  loadSlice,
+  unloadSlice,
+  subscribeToSlice,
+  subscribeToQuery,
+  getStartup,
+  subscribeToStartup,
+  request,
+  onRequest,
+  publish,
+  subscribe,
+  reportError,
+  saveSnapshot,
+  listSnapshots,
+  diffSnapshots,
+  restoreSnapshot,
+  simulateAction,
+  exportStateGraph,
+  loadFixture,
+  confirm,
+  cancelConfirmation,
+  search,
+  watchAppearance,
 };
 
 export default zubridge;
@@ -0,0 +1,87 @@
+use std::collections::VecDeque;
+use std::sync::{Arc, Mutex};
+
+use serde::Serialize;
+
+use crate::ids::IdGenerator;
+use crate::models::*;
+use crate::utils::now_millis;
+
+/// A dispatched action as recorded in the action log.
+#[derive(Clone, Debug, Serialize)]
+pub struct ActionLogEntry {
+    pub id: String,
+    pub action_type: String,
+    pub payload: Option<JsonValue>,
+    /// Milliseconds since the Unix epoch.
+    pub timestamp: u64,
+}
+
+/// A bounded log of the most recently dispatched actions.
+pub(crate) struct ActionLog {
+    capacity: usize,
+    ids: Arc<dyn IdGenerator>,
+    entries: Mutex<VecDeque<ActionLogEntry>>,
+}
+
+impl ActionLog {
+    pub(crate) fn new(capacity: usize, ids: Arc<dyn IdGenerator>) -> Self {
+        Self {
+            capacity,
+            ids,
+            entries: Mutex::new(VecDeque::with_capacity(capacity)),
+        }
+    }
+
+    pub(crate) fn record(&self, action_type: &str, payload: Option<&JsonValue>) {
+        if self.capacity == 0 {
+            return;
+        }
+        let Ok(mut entries) = self.entries.lock() else { return };
+        if entries.len() == self.capacity {
+            entries.pop_front();
+        }
+        entries.push_back(ActionLogEntry {
+            id: self.ids.next_id(),
+            action_type: action_type.to_string(),
+            payload: payload.cloned(),
+            timestamp: now_millis(),
+        });
+    }
+
+    /// The logged actions, oldest first.
+    pub(crate) fn entries(&self) -> Vec<ActionLogEntry> {
+        match self.entries.try_lock() {
+            Ok(entries) => entries.iter().cloned().collect(),
+            Err(_) => Vec::new(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicU64, Ordering};
+
+    fn log(capacity: usize) -> ActionLog {
+        let next = AtomicU64::new(0);
+        ActionLog::new(capacity, Arc::new(move || next.fetch_add(1, Ordering::Relaxed).to_string()))
+    }
+
+    #[test]
+    fn keeps_the_most_recent_actions() {
+        let log = log(2);
+        for action_type in ["A", "B", "C"] {
+            log.record(action_type, Some(&JsonValue::Null));
+        }
+        let entries: Vec<_> = log.entries().into_iter().map(|entry| (entry.id, entry.action_type)).collect();
+        assert_eq!(entries, [("1".into(), "B".into()), ("2".into(), "C".into())]);
+    }
+
+    #[test]
+    fn records_nothing_without_capacity() {
+        let log = log(0);
+        log.record("A", None);
+        assert!(log.entries().is_empty());
+    }
+}
@@ -0,0 +1,238 @@
+use std::collections::{BTreeMap, HashMap};
+use std::sync::Mutex;
+
+use crate::models::*;
+use crate::utils::stable_hash;
+
+#[derive(Clone, Debug)]
+enum AggregateOp {
+    Count,
+    /// Sum of a numeric entity field.
+    Sum(String),
+}
+
+/// A statistic over the entities of a collection in the state, published under
+/// `<stats_key>.<name>`. The collection may be an object keyed by id, a normalized
+/// `{ ids, entities }` object or an array.
+#[derive(Clone, Debug)]
+pub struct Aggregation {
+    pub name: String,
+    /// Dot-separated path of the collection in the state.
+    pub path: String,
+    op: AggregateOp,
+    group_by: Option<String>,
+}
+
+impl Aggregation {
+    /// Count the entities.
+    pub fn count(name: impl Into<String>, path: impl Into<String>) -> Self {
+        Self::new(name, path, AggregateOp::Count)
+    }
+
+    /// Sum a numeric field of the entities. Missing and non-numeric values count as zero.
+    pub fn sum(name: impl Into<String>, path: impl Into<String>, field: impl Into<String>) -> Self {
+        Self::new(name, path, AggregateOp::Sum(field.into()))
+    }
+
+    fn new(name: impl Into<String>, path: impl Into<String>, op: AggregateOp) -> Self {
+        Self {
+            name: name.into(),
+            path: path.into(),
+            op,
+            group_by: None,
+        }
+    }
+
+    /// Aggregate per value of `field`, publishing an object keyed by group instead of a number.
+    pub fn group_by(mut self, field: impl Into<String>) -> Self {
+        self.group_by = Some(field.into());
+        self
+    }
+
+    /// The group and value an entity contributes.
+    fn contribution(&self, entity: &JsonValue) -> (String, f64) {
+        let group = match self.group_by.as_deref().and_then(|field| value_at(entity, field)) {
+            Some(JsonValue::String(group)) => group.clone(),
+            Some(group) => group.to_string(),
+            None => "null".to_string(),
+        };
+        let value = match &self.op {
+            AggregateOp::Count => 1.0,
+            AggregateOp::Sum(field) => value_at(entity, field).and_then(JsonValue::as_f64).unwrap_or(0.0),
+        };
+        (group, value)
+    }
+}
+
+struct Contribution {
+    hash: u64,
+    group: String,
+    value: f64,
+}
+
+/// What each entity contributed, so only changed entities are folded in again.
+#[derive(Default)]
+struct AggregateState {
+    contributions: HashMap<String, Contribution>,
+    /// The total and number of contributing entities per group.
+    totals: BTreeMap<String, (f64, usize)>,
+}
+
+impl AggregateState {
+    fn subtract(&mut self, contribution: &Contribution) {
+        if let Some((total, entities)) = self.totals.get_mut(&contribution.group) {
+            *total -= contribution.value;
+            *entities -= 1;
+            if *entities == 0 {
+                self.totals.remove(&contribution.group);
+            }
+        }
+    }
+
+    fn add(&mut self, contribution: Contribution, id: String) {
+        let (total, entities) = self.totals.entry(contribution.group.clone()).or_default();
+        *total += contribution.value;
+        *entities += 1;
+        self.contributions.insert(id, contribution);
+    }
+}
+
+/// The registered aggregations and their running totals.
+pub(crate) struct Aggregates {
+    aggregations: Vec<Aggregation>,
+    states: Mutex<Vec<AggregateState>>,
+}
+
+impl Aggregates {
+    pub(crate) fn new(aggregations: Vec<Aggregation>) -> Self {
+        let states = aggregations.iter().map(|_| AggregateState::default()).collect();
+        Self {
+            aggregations,
+            states: Mutex::new(states),
+        }
+    }
+
+    pub(crate) fn is_empty(&self) -> bool {
+        self.aggregations.is_empty()
+    }
+
+    /// Bring every aggregation up to date with `state`, returning the stats slice.
+    pub(crate) fn slice(&self, state: &JsonValue) -> crate::Result<JsonValue> {
+        let mut states = self.states.lock().map_err(|e| crate::Error::StateError(e.to_string()))?;
+        let mut slice = serde_json::Map::new();
+        for (aggregation, aggregate) in self.aggregations.iter().zip(states.iter_mut()) {
+            let entities = entities_at(state, &aggregation.path);
+            let ids: HashMap<&str, &JsonValue> = entities.iter().map(|(id, entity)| (id.as_str(), *entity)).collect();
+
+            let removed: Vec<String> =
+                aggregate.contributions.keys().filter(|id| !ids.contains_key(id.as_str())).cloned().collect();
+            for id in removed {
+                if let Some(contribution) = aggregate.contributions.remove(&id) {
+                    aggregate.subtract(&contribution);
+                }
+            }
+
+            for (id, entity) in entities {
+                let hash = stable_hash(&entity.to_string());
+                if aggregate.contributions.get(&id).is_some_and(|contribution| contribution.hash == hash) {
+                    continue;
+                }
+                if let Some(previous) = aggregate.contributions.remove(&id) {
+                    aggregate.subtract(&previous);
+                }
+                let (group, value) = aggregation.contribution(entity);
+                aggregate.add(Contribution { hash, group, value }, id);
+            }
+
+            let value = match &aggregation.group_by {
+                Some(_) => {
+                    let groups = aggregate.totals.iter().map(|(group, (total, _))| (group.clone(), number(*total)));
+                    JsonValue::Object(groups.collect())
+                }
+                None => number(aggregate.totals.values().map(|(total, _)| total).sum()),
+            };
+            slice.insert(aggregation.name.clone(), value);
+        }
+        Ok(JsonValue::Object(slice))
+    }
+}
+
+/// Publish whole numbers as integers, so counts don't show up as `3.0`.
+fn number(value: f64) -> JsonValue {
+    if value.fract() == 0.0 && value.abs() < 9_007_199_254_740_992.0 {
+        JsonValue::from(value as i64)
+    } else {
+        JsonValue::from(value)
+    }
+}
+
+fn value_at<'a>(value: &'a JsonValue, path: &str) -> Option<&'a JsonValue> {
+    path.split('.')
+        .filter(|segment| !segment.is_empty())
+        .try_fold(value, |value, segment| value.get(segment))
+}
+
+/// The entities of the collection at `path` with their ids. Array elements are keyed by index.
+fn entities_at<'a>(state: &'a JsonValue, path: &str) -> Vec<(String, &'a JsonValue)> {
+    let Some(collection) = value_at(state, path) else {
+        return Vec::new();
+    };
+    match collection.get("entities").unwrap_or(collection) {
+        JsonValue::Object(entities) => entities.iter().map(|(id, entity)| (id.clone(), entity)).collect(),
+        JsonValue::Array(entities) => entities.iter().enumerate().map(|(index, entity)| (index.to_string(), entity)).collect(),
+        _ => Vec::new(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn aggregates() -> Aggregates {
+        Aggregates::new(vec![
+            Aggregation::count("todos", "todos"),
+            Aggregation::count("by_status", "todos").group_by("status"),
+            Aggregation::sum("estimate", "todos", "estimate"),
+        ])
+    }
+
+    #[test]
+    fn aggregates_normalized_collections() {
+        let state = json!({ "todos": {
+            "ids": ["a", "b"],
+            "entities": {
+                "a": { "status": "open", "estimate": 2 },
+                "b": { "status": "done", "estimate": 1.5 },
+            },
+        } });
+        assert_eq!(
+            aggregates().slice(&state).unwrap(),
+            json!({ "todos": 2, "by_status": { "open": 1, "done": 1 }, "estimate": 3.5 })
+        );
+    }
+
+    #[test]
+    fn folds_in_changed_and_removed_entities() {
+        let aggregates = aggregates();
+        let state = json!({ "todos": [
+            { "status": "open", "estimate": 2 },
+            { "status": "open", "estimate": 3 },
+        ] });
+        aggregates.slice(&state).unwrap();
+
+        let state = json!({ "todos": [{ "status": "done", "estimate": 2 }] });
+        assert_eq!(
+            aggregates.slice(&state).unwrap(),
+            json!({ "todos": 1, "by_status": { "done": 1 }, "estimate": 2 })
+        );
+    }
+
+    #[test]
+    fn counts_missing_collections_as_empty() {
+        assert_eq!(
+            aggregates().slice(&json!({})).unwrap(),
+            json!({ "todos": 0, "by_status": {}, "estimate": 0 })
+        );
+    }
+}
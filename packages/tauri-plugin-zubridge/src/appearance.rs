@@ -0,0 +1,136 @@
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use serde::{Deserialize, Serialize};
+
+use crate::models::*;
+
+/// OS appearance and accessibility settings. Fields are `None` until a source reports them.
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct Appearance {
+    /// "light" or "dark".
+    pub theme: Option<String>,
+    /// The system accent color as a CSS color.
+    pub accent_color: Option<String>,
+    pub reduced_motion: Option<bool>,
+    pub high_contrast: Option<bool>,
+}
+
+impl Appearance {
+    /// Overwrite the fields set in `update`, returning whether anything changed.
+    fn merge(&mut self, update: Appearance) -> bool {
+        let previous = self.clone();
+        if update.theme.is_some() {
+            self.theme = update.theme;
+        }
+        if update.accent_color.is_some() {
+            self.accent_color = update.accent_color;
+        }
+        if update.reduced_motion.is_some() {
+            self.reduced_motion = update.reduced_motion;
+        }
+        if update.high_contrast.is_some() {
+            self.high_contrast = update.high_contrast;
+        }
+        *self != previous
+    }
+}
+
+/// Reads appearance settings Tauri doesn't expose, e.g. from platform APIs. Polled on a
+/// background thread every `poll_interval`.
+pub trait AppearanceProvider: Send + Sync + 'static {
+    /// Return the current settings, leaving unknown fields `None`.
+    fn read(&self) -> Appearance;
+}
+
+/// Mirrors OS appearance settings into the `system.appearance` slice, dispatching
+/// `change_action` when they change.
+///
+/// The theme comes from the windows, reduced motion, contrast and accent color from windows
+/// that call `watchAppearance` from the frontend, and anything else from the `provider`.
+#[derive(Clone)]
+pub struct AppearanceConfig {
+    pub provider: Option<Arc<dyn AppearanceProvider>>,
+    /// How often the provider is polled. Defaults to 5 seconds.
+    pub poll_interval: Duration,
+    /// The action dispatched with the full appearance when it changes.
+    /// Defaults to "SYSTEM:APPEARANCE_CHANGED".
+    pub change_action: String,
+}
+
+impl Default for AppearanceConfig {
+    fn default() -> Self {
+        Self {
+            provider: None,
+            poll_interval: Duration::from_secs(5),
+            change_action: "SYSTEM:APPEARANCE_CHANGED".to_string(),
+        }
+    }
+}
+
+/// The last known appearance.
+#[derive(Default)]
+pub(crate) struct AppearanceState {
+    current: Mutex<Appearance>,
+}
+
+impl AppearanceState {
+    /// Merge an update, returning the new appearance if it changed.
+    pub(crate) fn update(&self, update: Appearance) -> crate::Result<Option<Appearance>> {
+        let mut current = self.current.lock().map_err(|e| crate::Error::StateError(e.to_string()))?;
+        Ok(current.merge(update).then(|| current.clone()))
+    }
+
+    /// Insert the appearance at `system.appearance`, keeping the rest of the `system` object.
+    pub(crate) fn insert_into(&self, root: &mut serde_json::Map<String, JsonValue>) -> crate::Result<()> {
+        let current = self.current.lock().map_err(|e| crate::Error::StateError(e.to_string()))?;
+        let appearance = serde_json::to_value(&*current).map_err(|e| crate::Error::SerializationError(e.to_string()))?;
+        let system = root.entry("system").or_insert_with(|| JsonValue::Object(Default::default()));
+        if let Some(system) = system.as_object_mut() {
+            system.insert("appearance".to_string(), appearance);
+        }
+        Ok(())
+    }
+}
+
+pub(crate) fn theme_name(theme: tauri::Theme) -> Option<String> {
+    match theme {
+        tauri::Theme::Light => Some("light".to_string()),
+        tauri::Theme::Dark => Some("dark".to_string()),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn merges_the_reported_fields() {
+        let state = AppearanceState::default();
+        let dark = Appearance {
+            theme: Some("dark".into()),
+            ..Default::default()
+        };
+        assert_eq!(state.update(dark.clone()).unwrap(), Some(dark.clone()));
+        assert_eq!(state.update(dark).unwrap(), None);
+        let motion = Appearance {
+            reduced_motion: Some(true),
+            ..Default::default()
+        };
+        let merged = state.update(motion).unwrap().unwrap();
+        assert_eq!((merged.theme.as_deref(), merged.reduced_motion), (Some("dark"), Some(true)));
+    }
+
+    #[test]
+    fn inserts_into_the_system_slice() {
+        let state = AppearanceState::default();
+        let mut root = serde_json::Map::new();
+        root.insert("system".into(), json!({ "locale": "en" }));
+        state.insert_into(&mut root).unwrap();
+        assert_eq!(root["system"]["locale"], "en");
+        assert_eq!(root["system"]["appearance"]["theme"], JsonValue::Null);
+        assert_eq!(theme_name(tauri::Theme::Dark).as_deref(), Some("dark"));
+    }
+}
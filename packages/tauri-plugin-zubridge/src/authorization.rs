@@ -0,0 +1,155 @@
+use std::collections::BTreeMap;
+
+use crate::models::*;
+use crate::utils::{get_path, glob_matches};
+
+#[derive(Clone, Debug)]
+struct AuthRule {
+    /// An action type pattern, where `*` matches any run of characters.
+    pattern: String,
+    /// The user needs one of these roles, if any are listed.
+    roles: Vec<String>,
+    /// The user's claims must have these values.
+    claims: BTreeMap<String, JsonValue>,
+}
+
+/// Actions that require roles or claims, checked against the `auth` slice of the state before
+/// they're dispatched.
+///
+/// The slice is read as `{ roles: ["admin", ...], claims: { ... } }`, as the app's sign-in flow
+/// wrote it. Actions without a matching rule aren't checked.
+#[derive(Clone, Debug)]
+pub struct AuthorizationConfig {
+    rules: Vec<AuthRule>,
+    /// The dot-separated path of the auth slice. Defaults to "auth".
+    pub auth_path: String,
+}
+
+impl Default for AuthorizationConfig {
+    fn default() -> Self {
+        Self {
+            rules: Vec::new(),
+            auth_path: "auth".to_string(),
+        }
+    }
+}
+
+impl AuthorizationConfig {
+    /// Require one of `roles` for actions matching `pattern`.
+    pub fn require_role<I, S>(mut self, pattern: impl Into<String>, roles: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        self.rules.push(AuthRule {
+            pattern: pattern.into(),
+            roles: roles.into_iter().map(Into::into).collect(),
+            claims: BTreeMap::new(),
+        });
+        self
+    }
+
+    /// Require the claim `claim` to equal `value` for actions matching `pattern`.
+    pub fn require_claim(mut self, pattern: impl Into<String>, claim: impl Into<String>, value: impl Into<JsonValue>) -> Self {
+        self.rules.push(AuthRule {
+            pattern: pattern.into(),
+            roles: Vec::new(),
+            claims: BTreeMap::from([(claim.into(), value.into())]),
+        });
+        self
+    }
+
+    /// Whether any rule applies to `action_type`, so the state only has to be read for those.
+    pub(crate) fn applies_to(&self, action_type: &str) -> bool {
+        self.rules.iter().any(|rule| glob_matches(&rule.pattern, action_type))
+    }
+
+    /// Whether writing the dot-separated `path` would change the auth slice: the slice itself,
+    /// anything in it, or anything holding it, up to the root of the state.
+    pub(crate) fn covers(&self, path: &str) -> bool {
+        let path = path.trim_matches('.');
+        let auth_path = self.auth_path.trim_matches('.');
+        path.is_empty()
+            || path == auth_path
+            || path.strip_prefix(auth_path).is_some_and(|rest| rest.starts_with('.'))
+            || auth_path.strip_prefix(path).is_some_and(|rest| rest.starts_with('.'))
+    }
+
+    /// Reject a window's write to `path` with [`crate::Error::Forbidden`] if it's covered by the
+    /// auth slice, which only the app's own sign-in flow may change.
+    pub(crate) fn ensure_outside(&self, action_type: &str, path: &str) -> crate::Result<()> {
+        if self.covers(path) {
+            return Err(crate::Error::Forbidden {
+                action_type: action_type.to_string(),
+                requirement: format!("a path outside {}", self.auth_path),
+            });
+        }
+        Ok(())
+    }
+
+    /// Reject `action_type` with [`crate::Error::Forbidden`] unless the auth slice in `state`
+    /// satisfies every rule matching it.
+    pub(crate) fn authorize(&self, action_type: &str, state: &JsonValue) -> crate::Result<()> {
+        let auth = get_path(state, &self.auth_path);
+        let roles: Vec<&str> = auth
+            .and_then(|auth| auth.get("roles"))
+            .and_then(JsonValue::as_array)
+            .map(|roles| roles.iter().filter_map(JsonValue::as_str).collect())
+            .unwrap_or_default();
+        let forbidden = |requirement: String| crate::Error::Forbidden {
+            action_type: action_type.to_string(),
+            requirement,
+        };
+
+        for rule in self.rules.iter().filter(|rule| glob_matches(&rule.pattern, action_type)) {
+            if !rule.roles.is_empty() && !rule.roles.iter().any(|role| roles.contains(&role.as_str())) {
+                return Err(forbidden(format!("one of the roles {}", rule.roles.join(", "))));
+            }
+            for (claim, expected) in &rule.claims {
+                let actual = auth.and_then(|auth| auth.get("claims")).and_then(|claims| claims.get(claim));
+                if actual != Some(expected) {
+                    return Err(forbidden(format!("the claim {} = {}", claim, expected)));
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn config() -> AuthorizationConfig {
+        AuthorizationConfig::default()
+            .require_role("ADMIN:*", ["admin"])
+            .require_claim("BILLING:*", "plan", "pro")
+    }
+
+    #[test]
+    fn checks_roles_and_claims_of_matching_actions() {
+        let config = config();
+        let admin = json!({ "auth": { "roles": ["admin"], "claims": { "plan": "free" } } });
+        assert!(config.authorize("ADMIN:WIPE", &admin).is_ok());
+        assert!(matches!(config.authorize("BILLING:REFUND", &admin), Err(crate::Error::Forbidden { .. })));
+        assert!(config.authorize("ADMIN:WIPE", &json!({})).is_err());
+        assert!(config.authorize("TODO:ADD", &json!({})).is_ok());
+        assert!(!config.applies_to("TODO:ADD"));
+    }
+
+    #[test]
+    fn covers_the_auth_slice_and_what_holds_it() {
+        let config = AuthorizationConfig {
+            auth_path: "session.user".into(),
+            ..config()
+        };
+        for path in ["", "session", "session.user", "session.user.roles", "session.user.claims.plan"] {
+            assert!(config.covers(path), "{}", path);
+        }
+        for path in ["session.theme", "session.username", "user", "todos"] {
+            assert!(!config.covers(path), "{}", path);
+        }
+        assert!(config.ensure_outside("__ZUBRIDGE:SET_PATH", "session.user.roles").is_err());
+    }
+}
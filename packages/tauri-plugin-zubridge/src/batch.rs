@@ -0,0 +1,53 @@
+use serde::{Deserialize, Serialize};
+
+/// How a batch handles an action that fails.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum BatchMode {
+    /// Apply the batch as a single update, stopping at the first failed action and restoring the
+    /// state from before the batch. Actions the plugin handles itself, such as window actions
+    /// and timers, can't be undone that way, so they fail the batch before taking effect.
+    /// Requires a state manager that implements [`crate::StateManager::restore_state`].
+    Atomic,
+    /// Dispatch every action, reporting the ones that failed.
+    #[default]
+    BestEffort,
+}
+
+/// The outcome of one action of a batch, or one chunk of an import, streamed as it's applied.
+#[derive(Clone, Debug, Serialize)]
+pub struct BatchResult {
+    pub index: usize,
+    pub ok: bool,
+    pub error: Option<String>,
+}
+
+impl BatchResult {
+    pub(crate) fn new<T>(index: usize, result: &crate::Result<T>) -> Self {
+        Self {
+            index,
+            ok: result.is_ok(),
+            error: result.as_ref().err().map(ToString::to_string),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reports_the_outcome_of_each_action() {
+        let ok = BatchResult::new(0, &Ok::<_, crate::Error>(()));
+        assert!(ok.ok && ok.error.is_none());
+        let failed = BatchResult::new(1, &Err::<(), _>(crate::Error::StateError("failed".into())));
+        assert_eq!((failed.index, failed.ok), (1, false));
+        assert!(failed.error.unwrap().contains("failed"));
+    }
+
+    #[test]
+    fn modes_are_kebab_case() {
+        assert_eq!(serde_json::to_value(BatchMode::BestEffort).unwrap(), "best-effort");
+        assert_eq!(serde_json::from_str::<BatchMode>("\"atomic\"").unwrap(), BatchMode::Atomic);
+    }
+}
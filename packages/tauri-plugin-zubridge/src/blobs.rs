@@ -0,0 +1,270 @@
+//! Content-addressed files under the plugin's data directory, for images and other binary data
+//! the state refers to by URL instead of embedding them as base64.
+//!
+//! A blob is stored under the hex SHA-256 of its contents, with its content type next to it in
+//! `<id>.type`, and served on the `zubridge-blob` protocol. Blobs the state no longer refers to
+//! are deleted by the garbage collector.
+
+use std::collections::HashSet;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use tauri::http::{header, Request, Response, StatusCode};
+use tauri::{AppHandle, Manager, Runtime, Url};
+
+use crate::desktop::Zubridge;
+use crate::models::*;
+use crate::utils::{hex, window_origin};
+
+const DEFAULT_CONTENT_TYPE: &str = "application/octet-stream";
+/// The extension of the file holding a blob's content type.
+const CONTENT_TYPE_EXTENSION: &str = "type";
+
+/// When blobs the state no longer refers to are deleted.
+#[derive(Clone, Debug)]
+pub struct BlobStoreConfig {
+    /// How often unreferenced blobs are collected. Defaults to 10 minutes.
+    pub gc_interval: Duration,
+    /// Blobs written more recently than this are kept even if unreferenced, so a blob stored
+    /// right before the action referring to it is dispatched survives. Defaults to 5 minutes.
+    pub grace_period: Duration,
+}
+
+impl Default for BlobStoreConfig {
+    fn default() -> Self {
+        Self {
+            gc_interval: Duration::from_secs(600),
+            grace_period: Duration::from_secs(300),
+        }
+    }
+}
+
+/// A stored blob, returned by [`Zubridge::put_blob`]. Put `url`, or the whole reference, in the
+/// state: the blob is kept as long as a string in the state, an inactive workspace's state or a
+/// saved snapshot is its URL.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct BlobRef {
+    /// The hex SHA-256 of the blob's contents.
+    pub id: String,
+    /// `zubridge-blob://localhost/<id>?type=<content type>`. Use `blobUrl` from
+    /// `tauri-plugin-zubridge-api` to load it in a webview.
+    pub url: String,
+    pub bytes: u64,
+    pub content_type: String,
+}
+
+pub(crate) struct BlobStore {
+    config: BlobStoreConfig,
+    dir: PathBuf,
+}
+
+impl BlobStore {
+    pub(crate) fn new(config: BlobStoreConfig, data_dir: &Path) -> Self {
+        Self {
+            config,
+            dir: data_dir.join("blobs"),
+        }
+    }
+
+    pub(crate) fn put(&self, data: &[u8], content_type: &str) -> crate::Result<BlobRef> {
+        let id = hex(&Sha256::digest(data));
+        std::fs::create_dir_all(&self.dir)?;
+        // Written again even if it exists, so the grace period starts over. The content type goes
+        // first, so a blob is never served without it
+        let path = self.dir.join(&id);
+        write_replacing(&path.with_extension(CONTENT_TYPE_EXTENSION), content_type.as_bytes())?;
+        write_replacing(&path, data)?;
+        let url = Url::parse_with_params(&format!("{}://localhost/{}", crate::BLOB_PROTOCOL, id), [("type", content_type)])
+            .map_err(|e| crate::Error::StateError(e.to_string()))?;
+        Ok(BlobRef {
+            id,
+            url: url.to_string(),
+            bytes: data.len() as u64,
+            content_type: content_type.to_string(),
+        })
+    }
+
+    /// The file holding the blob referenced by `reference`, a blob URL or id.
+    pub(crate) fn path(&self, reference: &str) -> crate::Result<PathBuf> {
+        let id = referenced_id(reference)
+            .or_else(|| is_blob_id(reference).then_some(reference))
+            .ok_or_else(|| crate::Error::UnknownBlob(reference.to_string()))?;
+        let path = self.dir.join(id);
+        if !path.is_file() {
+            return Err(crate::Error::UnknownBlob(reference.to_string()));
+        }
+        Ok(path)
+    }
+
+    /// The content type the blob in `path` was stored with.
+    pub(crate) fn content_type(path: &Path) -> String {
+        std::fs::read_to_string(path.with_extension(CONTENT_TYPE_EXTENSION))
+            .unwrap_or_else(|_| DEFAULT_CONTENT_TYPE.to_string())
+    }
+
+    /// Delete the blobs no string in any of `states` refers to, past the grace period. Returns
+    /// how many were deleted.
+    pub(crate) fn collect(&self, states: &[JsonValue]) -> crate::Result<usize> {
+        let mut referenced = HashSet::new();
+        for state in states {
+            collect_references(state, &mut referenced);
+        }
+
+        let entries = match std::fs::read_dir(&self.dir) {
+            Ok(entries) => entries,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(0),
+            Err(e) => return Err(e.into()),
+        };
+        let mut deleted = 0;
+        for entry in entries {
+            let entry = entry?;
+            let name = entry.file_name().to_string_lossy().into_owned();
+            // Leftover temp files from an interrupted write are collected too
+            if referenced.contains(name.as_str()) || !(is_blob_id(&name) || name.ends_with(".tmp")) {
+                continue;
+            }
+            let age = entry
+                .metadata()?
+                .modified()?
+                .elapsed()
+                .unwrap_or(Duration::ZERO);
+            if age < self.config.grace_period {
+                continue;
+            }
+            std::fs::remove_file(entry.path())?;
+            if is_blob_id(&name) {
+                match std::fs::remove_file(entry.path().with_extension(CONTENT_TYPE_EXTENSION)) {
+                    Err(e) if e.kind() != std::io::ErrorKind::NotFound => return Err(e.into()),
+                    _ => {}
+                }
+            }
+            deleted += 1;
+        }
+        Ok(deleted)
+    }
+}
+
+/// Write `contents` to a temp file next to `path` and rename it into place.
+fn write_replacing(path: &Path, contents: &[u8]) -> std::io::Result<()> {
+    let mut name = path.file_name().unwrap_or_default().to_os_string();
+    name.push(format!(".{}.tmp", uuid::Uuid::new_v4()));
+    let temp = path.with_file_name(name);
+    std::fs::write(&temp, contents)?;
+    std::fs::rename(&temp, path)
+}
+
+/// The id of the blob `text` refers to, as returned by `put_blob` or as rewritten by `blobUrl`.
+fn referenced_id(text: &str) -> Option<&str> {
+    let rest = text
+        .strip_prefix("zubridge-blob://localhost/")
+        .or_else(|| text.strip_prefix("http://zubridge-blob.localhost/"))
+        .or_else(|| text.strip_prefix("https://zubridge-blob.localhost/"))?;
+    let id = rest.split(['?', '#']).next()?;
+    is_blob_id(id).then_some(id)
+}
+
+fn is_blob_id(id: &str) -> bool {
+    id.len() == 64 && id.bytes().all(|byte| matches!(byte, b'0'..=b'9' | b'a'..=b'f'))
+}
+
+fn collect_references<'a>(value: &'a JsonValue, referenced: &mut HashSet<&'a str>) {
+    match value {
+        JsonValue::String(text) => {
+            if let Some(id) = referenced_id(text) {
+                referenced.insert(id);
+            }
+        }
+        JsonValue::Array(items) => items.iter().for_each(|item| collect_references(item, referenced)),
+        JsonValue::Object(fields) => fields.values().for_each(|field| collect_references(field, referenced)),
+        _ => {}
+    }
+}
+
+/// Serve blobs on the `zubridge-blob` protocol, with the content type they were stored with
+/// rather than the URL's `type` parameter, which any page could change to e.g. `text/html`.
+/// Blobs never change, so responses can be cached for good.
+pub(crate) fn handle_request<R: Runtime>(app: &AppHandle<R>, window: &str, request: Request<Vec<u8>>) -> Response<Vec<u8>> {
+    let mut response = Response::builder();
+    if let Some(origin) = window_origin(app, window) {
+        response = response.header(header::ACCESS_CONTROL_ALLOW_ORIGIN, origin);
+    }
+
+    let Some(zubridge) = app.try_state::<Zubridge<R>>() else {
+        return response.status(StatusCode::SERVICE_UNAVAILABLE).body(Vec::new()).unwrap_or_default();
+    };
+    let Ok(url) = Url::parse(&request.uri().to_string()) else {
+        return response.status(StatusCode::BAD_REQUEST).body(Vec::new()).unwrap_or_default();
+    };
+    let id = url.path().trim_start_matches('/');
+    let Ok(path) = zubridge.blob_path(id) else {
+        return response.status(StatusCode::NOT_FOUND).body(Vec::new()).unwrap_or_default();
+    };
+    let content_type = BlobStore::content_type(&path);
+
+    match std::fs::read(path) {
+        Ok(body) => response
+            .header(header::CONTENT_TYPE, content_type)
+            .header(header::X_CONTENT_TYPE_OPTIONS, "nosniff")
+            .header(header::ETAG, format!("\"{}\"", id))
+            .header(header::CACHE_CONTROL, "public, max-age=31536000, immutable")
+            .body(body),
+        Err(e) => response.status(StatusCode::INTERNAL_SERVER_ERROR).body(e.to_string().into_bytes()),
+    }
+    .unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn store(dir: &Path) -> BlobStore {
+        let config = BlobStoreConfig {
+            grace_period: Duration::ZERO,
+            ..Default::default()
+        };
+        BlobStore::new(config, dir)
+    }
+
+    #[test]
+    fn finds_blobs_by_url_or_id() {
+        let dir = tempfile::tempdir().unwrap();
+        let store = store(dir.path());
+        let blob = store.put(b"image", "image/png").unwrap();
+        assert_eq!(blob.url, format!("zubridge-blob://localhost/{}?type=image%2Fpng", blob.id));
+        assert_eq!(std::fs::read(store.path(&blob.url).unwrap()).unwrap(), b"image");
+        assert_eq!(BlobStore::content_type(&store.path(&blob.url).unwrap()), "image/png");
+        assert_eq!(store.path(&blob.id).unwrap(), store.path(&blob.url).unwrap());
+        let rewritten = format!("http://zubridge-blob.localhost/{}", blob.id);
+        assert_eq!(store.path(&rewritten).unwrap(), store.path(&blob.id).unwrap());
+        assert!(matches!(store.path("../state.json"), Err(crate::Error::UnknownBlob(_))));
+    }
+
+    #[test]
+    fn collects_blobs_the_state_no_longer_refers_to() {
+        let dir = tempfile::tempdir().unwrap();
+        let store = store(dir.path());
+        let kept = store.put(b"kept", "text/plain").unwrap();
+        let dropped = store.put(b"dropped", "text/plain").unwrap();
+        let state = serde_json::json!({ "avatars": [{ "url": kept.url }] });
+
+        assert_eq!(store.collect(&[state]).unwrap(), 1);
+        assert!(store.path(&kept.id).is_ok());
+        assert!(store.path(&dropped.id).is_err());
+        let mut names: Vec<String> = std::fs::read_dir(dir.path().join("blobs"))
+            .unwrap()
+            .map(|entry| entry.unwrap().file_name().to_string_lossy().into_owned())
+            .collect();
+        names.sort();
+        assert_eq!(names, [kept.id.clone(), format!("{}.type", kept.id)]);
+    }
+
+    #[test]
+    fn keeps_new_blobs_for_the_grace_period() {
+        let dir = tempfile::tempdir().unwrap();
+        let store = BlobStore::new(BlobStoreConfig::default(), dir.path());
+        store.put(b"pending", "text/plain").unwrap();
+        assert_eq!(store.collect(&[JsonValue::Null]).unwrap(), 0);
+    }
+}
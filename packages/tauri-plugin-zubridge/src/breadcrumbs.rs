@@ -0,0 +1,149 @@
+//! The steps a dispatch from a window went through, such as interceptors and the reducer,
+//! returned with its error so developers can see where the action was transformed or vetoed
+//! without turning on logging.
+
+use std::cell::RefCell;
+use std::collections::VecDeque;
+use std::time::Duration;
+
+use serde::Serialize;
+
+thread_local! {
+    /// The trail of the dispatch running on this thread. Dispatches run synchronously, so
+    /// concurrent dispatches on other threads keep their own.
+    static TRAIL: RefCell<Option<Trail>> = const { RefCell::new(None) };
+}
+
+struct Trail {
+    limit: usize,
+    steps: VecDeque<Breadcrumb>,
+}
+
+/// One step of a dispatch.
+#[derive(Clone, Debug, Serialize)]
+pub struct Breadcrumb {
+    /// What ran, e.g. `interceptor my_app::Redact`, `authorize`, `validate` or `reduce`.
+    pub step: String,
+    pub action_type: String,
+    /// What came out of it, e.g. `continue`, `transformed into 2 actions` or `rejected: ...`.
+    pub outcome: String,
+    pub duration_micros: u64,
+}
+
+/// Run `dispatch`, keeping the last `limit` steps it goes through and attaching them to its error
+/// as [`crate::Error::Traced`]. Dispatches nested in a traced one add to its trail instead.
+pub(crate) fn traced<T>(limit: usize, dispatch: impl FnOnce() -> crate::Result<T>) -> crate::Result<T> {
+    let nested = TRAIL.with(|trail| trail.borrow().is_some());
+    if limit == 0 || nested {
+        return dispatch();
+    }
+    TRAIL.with(|trail| {
+        *trail.borrow_mut() = Some(Trail {
+            limit,
+            steps: VecDeque::with_capacity(limit),
+        })
+    });
+    let _clear = ClearTrail;
+    let result = dispatch();
+    let steps = TRAIL.with(|trail| trail.borrow_mut().take()).map(|trail| trail.steps).unwrap_or_default();
+    result.map_err(|error| {
+        // A held action isn't a failure, and windows read the token from the message
+        if steps.is_empty() || matches!(error, crate::Error::ConfirmationRequired { .. }) {
+            return error;
+        }
+        crate::Error::Traced {
+            error: Box::new(error),
+            breadcrumbs: steps.into(),
+        }
+    })
+}
+
+/// Clears the trail even if the dispatch panics, so the next one on this thread isn't taken for a
+/// nested dispatch.
+struct ClearTrail;
+
+impl Drop for ClearTrail {
+    fn drop(&mut self) {
+        TRAIL.with(|trail| trail.borrow_mut().take());
+    }
+}
+
+/// Record a step of the traced dispatch on this thread, if any. `outcome` is only called then.
+pub(crate) fn record(step: impl Into<String>, action_type: &str, outcome: impl FnOnce() -> String, elapsed: Duration) {
+    TRAIL.with(|trail| {
+        if let Some(trail) = trail.borrow_mut().as_mut() {
+            if trail.steps.len() == trail.limit {
+                trail.steps.pop_front();
+            }
+            trail.steps.push_back(Breadcrumb {
+                step: step.into(),
+                action_type: action_type.to_string(),
+                outcome: outcome(),
+                duration_micros: elapsed.as_micros() as u64,
+            });
+        }
+    });
+}
+
+/// `ok`, or the error a step failed with.
+pub(crate) fn outcome<T>(result: &crate::Result<T>) -> String {
+    match result {
+        Ok(_) => "ok".to_string(),
+        Err(e) => format!("failed: {}", e),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn step(name: &str) {
+        record(name, "TODO:ADD", || "ok".to_string(), Duration::ZERO);
+    }
+
+    fn steps(result: crate::Result<()>) -> Vec<String> {
+        match result {
+            Err(crate::Error::Traced { breadcrumbs, .. }) => breadcrumbs.into_iter().map(|crumb| crumb.step).collect(),
+            _ => Vec::new(),
+        }
+    }
+
+    #[test]
+    fn attaches_the_last_steps_to_errors() {
+        let result = traced(2, || {
+            step("authorize");
+            step("validate");
+            step("reduce");
+            Err(crate::Error::ActionRejected("no".into()))
+        });
+        assert_eq!(steps(result), ["validate", "reduce"]);
+        // The trail is gone afterwards
+        step("untraced");
+        assert!(TRAIL.with(|trail| trail.borrow().is_none()));
+    }
+
+    #[test]
+    fn adds_nested_dispatches_to_the_outer_trail() {
+        let result = traced(5, || {
+            step("outer");
+            traced(5, || {
+                step("inner");
+                Ok(())
+            })?;
+            Err(crate::Error::ActionRejected("no".into()))
+        });
+        assert_eq!(steps(result), ["outer", "inner"]);
+    }
+
+    #[test]
+    fn leaves_held_actions_untraced() {
+        let result: crate::Result<()> = traced(5, || {
+            step("confirm");
+            Err(crate::Error::ConfirmationRequired {
+                action_type: "TODO:DELETE_ALL".into(),
+                token: "token".into(),
+            })
+        });
+        assert!(matches!(result, Err(crate::Error::ConfirmationRequired { .. })));
+    }
+}
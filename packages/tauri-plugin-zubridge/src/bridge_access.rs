@@ -0,0 +1,108 @@
+use tauri::ipc::{CommandArg, CommandItem, InvokeError};
+use tauri::{AppHandle, Manager, Runtime};
+
+use crate::desktop::{dispatcher, Zubridge};
+use crate::models::*;
+
+/// Access to the bridge for the app's own commands. Add it as a command argument instead of
+/// reaching into the state manager, so commands can't deadlock by locking it twice:
+///
+/// ```ignore
+/// #[tauri::command]
+/// fn archive_done(bridge: BridgeAccess<tauri::Wry>) -> tauri_plugin_zubridge::Result<JsonValue> {
+///     let state = bridge.state()?;
+///     bridge.dispatch(ZubridgeAction { action_type: "TODO:ARCHIVE".into(), payload: state.get("done").cloned() })
+/// }
+/// ```
+///
+/// Every call takes and releases the lock itself, and dispatches go through the whole pipeline,
+/// including aliases, interceptors and read-only window checks for the calling window.
+pub struct BridgeAccess<R: Runtime> {
+    app: AppHandle<R>,
+    window: String,
+}
+
+impl<R: Runtime> BridgeAccess<R> {
+    /// The label of the window that invoked the command.
+    pub fn window(&self) -> &str {
+        &self.window
+    }
+
+    /// A snapshot of the full state, including lazy slices and plugin-managed slices.
+    pub fn state(&self) -> crate::Result<JsonValue> {
+        self.zubridge().current_state()
+    }
+
+    /// Dispatch an action on behalf of the calling window and return the updated state.
+    pub fn dispatch(&self, action: ZubridgeAction) -> crate::Result<JsonValue> {
+        self.zubridge().dispatch_action_from(&self.window, action)
+    }
+
+    /// A dispatcher to hand to background work that outlives the command.
+    pub fn dispatcher(&self) -> Dispatcher {
+        dispatcher(&self.app)
+    }
+
+    fn zubridge(&self) -> &Zubridge<R> {
+        self.app.state::<Zubridge<R>>().inner()
+    }
+}
+
+impl<'de, R: Runtime> CommandArg<'de, R> for BridgeAccess<R> {
+    fn from_command(command: CommandItem<'de, R>) -> Result<Self, InvokeError> {
+        let webview = command.message.webview();
+        let app = webview.app_handle().clone();
+        if app.try_state::<Zubridge<R>>().is_none() {
+            return Err(InvokeError::from("the zubridge plugin isn't initialized"));
+        }
+        Ok(Self {
+            app,
+            window: webview.label().to_string(),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[derive(Default)]
+    struct Counter {
+        counter: i64,
+    }
+
+    impl StateManager for Counter {
+        fn get_initial_state(&self) -> JsonValue {
+            json!({ "counter": self.counter })
+        }
+
+        fn dispatch_action(&mut self, action: JsonValue) -> JsonValue {
+            if action["type"] == "INCREMENT" {
+                self.counter += 1;
+            }
+            self.get_initial_state()
+        }
+    }
+
+    fn increment() -> ZubridgeAction {
+        ZubridgeAction {
+            action_type: "INCREMENT".into(),
+            payload: None,
+        }
+    }
+
+    #[test]
+    fn dispatches_on_behalf_of_the_window() {
+        let app = crate::test::mock_app(Counter::default());
+        let bridge = BridgeAccess {
+            app: app.handle().clone(),
+            window: "main".into(),
+        };
+        assert_eq!(bridge.window(), "main");
+        assert_eq!(bridge.dispatch(increment()).unwrap()["counter"], 1);
+        assert_eq!(bridge.dispatcher().dispatch(increment()).unwrap()["counter"], 2);
+        assert_eq!(bridge.state().unwrap()["counter"], 2);
+        assert_eq!(app.last_state().unwrap()["counter"], 2);
+    }
+}
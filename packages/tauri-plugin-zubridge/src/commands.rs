@@ -1,20 +1,343 @@
-use tauri::{AppHandle, command, Runtime};
+use std::collections::BTreeMap;
+use tauri::ipc::{Channel, InvokeBody, Request};
+use tauri::{AppHandle, command, Runtime, WebviewWindow};
 
+use crate::batch::{BatchMode, BatchResult};
+
+use crate::error_reports::FrontendError;
+use crate::event_names::EventNames;
+use crate::health::{Health, VersionInfo};
+use crate::integrity::Handshake;
+use crate::snapshots::{Simulation, SnapshotDiff};
 use crate::models::*;
+use crate::startup::StartupProgress;
+use crate::subscriptions::StateUpdate;
+use crate::uploads;
+use crate::state_graph::GraphFormat;
 use crate::Result;
 use crate::ZubridgeExt;
 
-#[command(rename = "zubridge.get-initial-state")]
+#[command]
 pub(crate) async fn get_initial_state<R: Runtime>(
     app: AppHandle<R>,
+    protocol_version: Option<u32>,
 ) -> Result<JsonValue> {
-    app.zubridge().get_initial_state()
+    app.zubridge().get_initial_state_for(protocol_version)
 }
 
-#[command(rename = "zubridge.dispatch-action")]
+#[command]
 pub(crate) async fn dispatch_action<R: Runtime>(
+    app: AppHandle<R>,
+    window: WebviewWindow<R>,
+    action: ZubridgeAction,
+) -> Result<JsonValue> {
+    app.zubridge().dispatch_action_from(window.label(), action)
+}
+
+#[command]
+pub(crate) async fn dispatch_batch<R: Runtime>(
+    app: AppHandle<R>,
+    window: WebviewWindow<R>,
+    actions: Vec<ZubridgeAction>,
+    mode: Option<BatchMode>,
+    on_result: Channel<BatchResult>,
+) -> Result<JsonValue> {
+    let mode = mode.unwrap_or_default();
+    app.zubridge().dispatch_batch_from(Some(window.label()), actions, mode, |result| {
+        if let Err(e) = on_result.send(result) {
+            log::warn!("zubridge: failed to send a batch result: {}", e);
+        }
+    })
+}
+
+#[command]
+pub(crate) async fn get_schema<R: Runtime>(
+    app: AppHandle<R>,
+) -> Result<Option<StateSchema>> {
+    app.zubridge().get_schema()
+}
+
+#[command]
+pub(crate) async fn set_ephemeral<R: Runtime>(
+    app: AppHandle<R>,
+    window: WebviewWindow<R>,
+    path: String,
+    value: JsonValue,
+) -> Result<()> {
+    let zubridge = app.zubridge();
+    zubridge.ensure_writable(window.label())?;
+    zubridge.set_ephemeral(&path, value)
+}
+
+#[command]
+pub(crate) async fn get_ephemeral<R: Runtime>(
+    app: AppHandle<R>,
+) -> Result<BTreeMap<String, JsonValue>> {
+    app.zubridge().ephemeral_values()
+}
+
+#[command]
+pub(crate) async fn get_startup<R: Runtime>(
+    app: AppHandle<R>,
+) -> Result<StartupProgress> {
+    app.zubridge().startup_progress()
+}
+
+#[command]
+pub(crate) async fn health<R: Runtime>(
+    app: AppHandle<R>,
+) -> Result<Health> {
+    Ok(app.zubridge().health())
+}
+
+#[command]
+pub(crate) async fn version<R: Runtime>(
+    app: AppHandle<R>,
+) -> Result<VersionInfo> {
+    Ok(app.zubridge().version())
+}
+
+#[command]
+pub(crate) async fn get_event_names<R: Runtime>(
+    app: AppHandle<R>,
+) -> Result<EventNames> {
+    Ok(app.zubridge().event_names().clone())
+}
+
+#[command]
+pub(crate) async fn request<R: Runtime>(
+    app: AppHandle<R>,
+    window: WebviewWindow<R>,
+    target: String,
+    topic: String,
+    payload: Option<JsonValue>,
+) -> Result<JsonValue> {
+    app.zubridge()
+        .request_from(Some(window.label()), &target, &topic, payload.unwrap_or(JsonValue::Null))
+        .await
+}
+
+#[command]
+pub(crate) async fn respond<R: Runtime>(
+    app: AppHandle<R>,
+    window: WebviewWindow<R>,
+    id: String,
+    result: Option<JsonValue>,
+    error: Option<String>,
+) -> Result<()> {
+    let response = match error {
+        Some(error) => Err(error),
+        None => Ok(result.unwrap_or(JsonValue::Null)),
+    };
+    app.zubridge().respond(&id, window.label(), response)
+}
+
+#[command]
+pub(crate) async fn publish<R: Runtime>(
+    app: AppHandle<R>,
+    window: WebviewWindow<R>,
+    topic: String,
+    payload: Option<JsonValue>,
+    target: Option<String>,
+) -> Result<()> {
+    let target = target.as_deref().unwrap_or("*");
+    app.zubridge()
+        .publish_from(Some(window.label()), &topic, payload.unwrap_or(JsonValue::Null), target)
+}
+
+#[command]
+pub(crate) async fn report_error<R: Runtime>(
+    app: AppHandle<R>,
+    window: WebviewWindow<R>,
+    error: FrontendError,
+) -> Result<()> {
+    app.zubridge().report_error(window.label(), error)
+}
+
+#[command]
+pub(crate) async fn save_snapshot<R: Runtime>(
+    app: AppHandle<R>,
+    window: WebviewWindow<R>,
+    name: Option<String>,
+) -> Result<String> {
+    let zubridge = app.zubridge();
+    zubridge.ensure_writable(window.label())?;
+    zubridge.save_snapshot(name.as_deref())
+}
+
+#[command]
+pub(crate) async fn list_snapshots<R: Runtime>(
+    app: AppHandle<R>,
+) -> Result<Vec<String>> {
+    app.zubridge().snapshots()
+}
+
+#[command]
+pub(crate) async fn diff_snapshots<R: Runtime>(
+    app: AppHandle<R>,
+    a: String,
+    b: String,
+) -> Result<SnapshotDiff> {
+    app.zubridge().diff_snapshots(&a, &b)
+}
+
+#[command]
+pub(crate) async fn restore_snapshot<R: Runtime>(
+    app: AppHandle<R>,
+    window: WebviewWindow<R>,
+    id: String,
+) -> Result<JsonValue> {
+    app.zubridge().restore_snapshot_from(window.label(), &id)
+}
+
+#[command]
+pub(crate) async fn simulate_action<R: Runtime>(
     app: AppHandle<R>,
     action: ZubridgeAction,
+) -> Result<Simulation> {
+    app.zubridge().simulate(action)
+}
+
+#[command]
+pub(crate) async fn export_state_graph<R: Runtime>(
+    app: AppHandle<R>,
+    format: GraphFormat,
+) -> Result<String> {
+    app.zubridge().export_state_graph(format)
+}
+
+#[command]
+pub(crate) async fn confirm<R: Runtime>(
+    app: AppHandle<R>,
+    window: WebviewWindow<R>,
+    token: String,
+) -> Result<JsonValue> {
+    let zubridge = app.zubridge();
+    zubridge.ensure_writable(window.label())?;
+    zubridge.confirm(&token)
+}
+
+#[command]
+pub(crate) async fn cancel_confirmation<R: Runtime>(
+    app: AppHandle<R>,
+    window: WebviewWindow<R>,
+    token: String,
+) -> Result<()> {
+    let zubridge = app.zubridge();
+    zubridge.ensure_writable(window.label())?;
+    zubridge.cancel_confirmation(&token)
+}
+
+#[cfg(debug_assertions)]
+#[command]
+pub(crate) async fn load_fixture<R: Runtime>(
+    app: AppHandle<R>,
+    window: WebviewWindow<R>,
+    name: Option<String>,
 ) -> Result<JsonValue> {
-    app.zubridge().dispatch_action(action)
+    let zubridge = app.zubridge();
+    zubridge.ensure_writable(window.label())?;
+    zubridge.load_fixture(name.as_deref())
+}
+
+#[cfg(all(desktop, feature = "stress"))]
+#[command]
+pub(crate) async fn soak<R: Runtime>(
+    app: AppHandle<R>,
+    config: crate::SoakConfig,
+) -> Result<std::path::PathBuf> {
+    app.zubridge().start_soak(config)
+}
+
+#[cfg(feature = "search")]
+#[command]
+pub(crate) async fn search<R: Runtime>(
+    app: AppHandle<R>,
+    index: String,
+    query: String,
+    limit: Option<usize>,
+) -> Result<Vec<crate::effects::search::SearchHit>> {
+    app.zubridge().search(&index, &query, limit.unwrap_or(20))
+}
+
+#[command]
+pub(crate) async fn report_appearance<R: Runtime>(
+    app: AppHandle<R>,
+    window: WebviewWindow<R>,
+    appearance: crate::Appearance,
+) -> Result<()> {
+    let zubridge = app.zubridge();
+    zubridge.ensure_writable(window.label())?;
+    zubridge.update_appearance(appearance)
+}
+
+#[command]
+pub(crate) async fn load_slice<R: Runtime>(
+    app: AppHandle<R>,
+    window: WebviewWindow<R>,
+    slice: String,
+) -> Result<JsonValue> {
+    app.zubridge().load_slice(window.label(), &slice)
+}
+
+#[command]
+pub(crate) async fn unload_slice<R: Runtime>(
+    app: AppHandle<R>,
+    window: WebviewWindow<R>,
+    slice: String,
+) -> Result<()> {
+    app.zubridge().unload_slice(window.label(), &slice)
+}
+
+#[command]
+pub(crate) async fn subscribe_state<R: Runtime>(
+    app: AppHandle<R>,
+    window: WebviewWindow<R>,
+    on_update: Channel<StateUpdate>,
+) -> Result<JsonValue> {
+    app.zubridge().subscribe_state(window.label(), on_update)
+}
+
+#[command]
+pub(crate) async fn handshake<R: Runtime>(
+    app: AppHandle<R>,
+    window: WebviewWindow<R>,
+) -> Result<Handshake> {
+    app.zubridge().handshake(window.label())
+}
+
+#[command]
+pub(crate) async fn unsubscribe_state<R: Runtime>(
+    app: AppHandle<R>,
+    window: WebviewWindow<R>,
+) -> Result<()> {
+    app.zubridge().unsubscribe_state(window.label())
+}
+
+#[command]
+pub(crate) async fn acknowledge_state<R: Runtime>(
+    app: AppHandle<R>,
+    window: WebviewWindow<R>,
+) -> Result<()> {
+    app.zubridge().acknowledge_state(window.label())
+}
+
+#[command]
+pub(crate) async fn upload_blob<R: Runtime>(
+    app: AppHandle<R>,
+    window: WebviewWindow<R>,
+    request: Request<'_>,
+) -> Result<Option<JsonValue>> {
+    let InvokeBody::Raw(chunk) = request.body() else {
+        return Err(crate::Error::UploadError("expected a binary chunk".into()));
+    };
+    let header = |name: &str| request.headers().get(name).and_then(|value| value.to_str().ok());
+    let id = header(uploads::UPLOAD_ID_HEADER)
+        .ok_or_else(|| crate::Error::UploadError(format!("missing {} header", uploads::UPLOAD_ID_HEADER)))?;
+    let payload = header(uploads::PAYLOAD_HEADER)
+        .map(serde_json::from_str::<JsonValue>)
+        .transpose()
+        .map_err(|e| crate::Error::UploadError(e.to_string()))?;
+    let action = header(uploads::ACTION_TYPE_HEADER).map(|action_type| (action_type.to_string(), payload));
+    app.zubridge().upload_blob(window.label(), id, chunk, action)
 }
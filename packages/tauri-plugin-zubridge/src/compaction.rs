@@ -0,0 +1,28 @@
+use std::time::Duration;
+
+/// How much history a state manager should keep when it compacts its journals.
+#[derive(Clone, Debug, Default)]
+pub struct Retention {
+    /// Entries older than this are merged into the snapshot.
+    pub max_age: Option<Duration>,
+    /// Only the most recent entries are kept; older ones are merged into the snapshot.
+    pub max_entries: Option<usize>,
+}
+
+/// Schedules [`crate::StateManager::compact`] while the app is running.
+#[derive(Clone, Debug)]
+pub struct CompactionConfig {
+    /// How often compaction runs. Defaults to 10 minutes.
+    pub interval: Duration,
+    /// What's kept after each compaction.
+    pub retention: Retention,
+}
+
+impl Default for CompactionConfig {
+    fn default() -> Self {
+        Self {
+            interval: Duration::from_secs(600),
+            retention: Retention::default(),
+        }
+    }
+}
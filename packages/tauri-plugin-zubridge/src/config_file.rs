@@ -0,0 +1,117 @@
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+use crate::models::*;
+
+/// A config file whose contents are merged into the state under a fixed key.
+pub(crate) struct ConfigFile {
+    path: PathBuf,
+    key: String,
+    value: Mutex<JsonValue>,
+}
+
+impl ConfigFile {
+    /// Load the config file, falling back to an empty object if it can't be read.
+    pub(crate) fn load(path: PathBuf, key: String) -> Self {
+        let value = read_config_file(&path).unwrap_or_else(|e| {
+            log::warn!("zubridge: failed to load config file {}: {}", path.display(), e);
+            JsonValue::Object(Default::default())
+        });
+
+        Self {
+            path,
+            key,
+            value: Mutex::new(value),
+        }
+    }
+
+    pub(crate) fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// The currently loaded config contents.
+    pub(crate) fn value(&self) -> crate::Result<JsonValue> {
+        let value = self.value.lock().map_err(|e| crate::Error::ConfigError(e.to_string()))?;
+        Ok(value.clone())
+    }
+
+    /// Re-read the config file from disk. The previous contents are kept if the file is invalid.
+    pub(crate) fn reload(&self) -> crate::Result<()> {
+        let loaded = read_config_file(&self.path)?;
+        let mut value = self.value.lock().map_err(|e| crate::Error::ConfigError(e.to_string()))?;
+        *value = loaded;
+        Ok(())
+    }
+
+    /// Merge the config contents into `state` under the configured key.
+    ///
+    /// If the state already holds an object under that key, config values take precedence
+    /// over the existing entries.
+    pub(crate) fn apply(&self, mut state: JsonValue) -> crate::Result<JsonValue> {
+        let config = self.value()?;
+        let Some(root) = state.as_object_mut() else {
+            return Ok(state);
+        };
+
+        match (root.get_mut(&self.key), config) {
+            (Some(JsonValue::Object(existing)), JsonValue::Object(config)) => {
+                existing.extend(config);
+            }
+            (_, config) => {
+                root.insert(self.key.clone(), config);
+            }
+        }
+        Ok(state)
+    }
+}
+
+/// Read a config file as JSON, or as TOML when the file has a `.toml` extension.
+pub fn read_config_file(path: &Path) -> crate::Result<JsonValue> {
+    let contents = std::fs::read_to_string(path)?;
+    let is_toml = path
+        .extension()
+        .is_some_and(|ext| ext.eq_ignore_ascii_case("toml"));
+
+    if is_toml {
+        let value: toml::Value = toml::from_str(&contents).map_err(|e| crate::Error::ConfigError(e.to_string()))?;
+        serde_json::to_value(value).map_err(|e| crate::Error::SerializationError(e.to_string()))
+    } else {
+        serde_json::from_str(&contents).map_err(|e| crate::Error::ConfigError(e.to_string()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn merges_config_values_over_the_slice() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("settings.toml");
+        std::fs::write(&path, "theme = \"dark\"\n[editor]\ntabs = 4\n").unwrap();
+        let config = ConfigFile::load(path, "settings".into());
+
+        let state = config.apply(json!({ "settings": { "theme": "light", "font": "mono" } })).unwrap();
+        assert_eq!(
+            state["settings"],
+            json!({ "theme": "dark", "font": "mono", "editor": { "tabs": 4 } })
+        );
+        let state = config.apply(json!({ "settings": 1 })).unwrap();
+        assert_eq!(state["settings"]["theme"], "dark");
+    }
+
+    #[test]
+    fn keeps_the_previous_contents_of_invalid_files() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("settings.json");
+        let config = ConfigFile::load(path.clone(), "settings".into());
+        assert_eq!(config.value().unwrap(), json!({}));
+
+        std::fs::write(&path, r#"{ "theme": "dark" }"#).unwrap();
+        config.reload().unwrap();
+        std::fs::write(&path, "{").unwrap();
+        assert!(config.reload().is_err());
+        assert_eq!(config.value().unwrap(), json!({ "theme": "dark" }));
+    }
+}
@@ -0,0 +1,163 @@
+use std::collections::BTreeMap;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use serde::Serialize;
+
+use crate::models::*;
+use crate::utils::glob_matches;
+
+/// Asks the user to confirm an action natively, e.g. with `tauri-plugin-dialog`'s blocking
+/// message dialog. Called on a background thread.
+pub trait ConfirmationPrompt: Send + Sync + 'static {
+    /// Return true to run the action, false to cancel it.
+    fn confirm(&self, action: &ZubridgeAction, message: &str) -> bool;
+}
+
+/// Actions that only run once they're confirmed with `zubridge.confirm`, with the pending ones
+/// kept in the `state_key` slice.
+#[derive(Clone)]
+pub struct ConfirmationsConfig {
+    /// Action type patterns and the message shown for them, where `*` matches any run of characters.
+    actions: Vec<(String, String)>,
+    /// Asks for confirmation natively. Without one, the frontend renders the pending confirmations.
+    pub prompt: Option<Arc<dyn ConfirmationPrompt>>,
+    /// How long a pending confirmation stays valid. Defaults to 60 seconds.
+    pub ttl: Duration,
+    /// The state key pending confirmations are stored under. Defaults to "confirmations".
+    pub state_key: String,
+}
+
+impl Default for ConfirmationsConfig {
+    fn default() -> Self {
+        Self {
+            actions: Vec::new(),
+            prompt: None,
+            ttl: Duration::from_secs(60),
+            state_key: "confirmations".to_string(),
+        }
+    }
+}
+
+impl ConfirmationsConfig {
+    /// Require confirmation for actions matching `pattern`, showing `message`.
+    pub fn require(mut self, pattern: impl Into<String>, message: impl Into<String>) -> Self {
+        self.actions.push((pattern.into(), message.into()));
+        self
+    }
+
+    pub fn prompt<P: ConfirmationPrompt>(mut self, prompt: P) -> Self {
+        self.prompt = Some(Arc::new(prompt));
+        self
+    }
+
+    pub(crate) fn message_for(&self, action_type: &str) -> Option<&str> {
+        self.actions
+            .iter()
+            .find(|(pattern, _)| glob_matches(pattern, action_type))
+            .map(|(_, message)| message.as_str())
+    }
+}
+
+struct PendingAction {
+    action: ZubridgeAction,
+    message: String,
+    expires: Instant,
+}
+
+#[derive(Serialize)]
+struct PendingEntry<'a> {
+    action_type: &'a str,
+    payload: &'a Option<JsonValue>,
+    message: &'a str,
+}
+
+/// Actions waiting for confirmation, keyed by token.
+#[derive(Default)]
+pub(crate) struct PendingConfirmations {
+    pending: Mutex<BTreeMap<String, PendingAction>>,
+}
+
+impl PendingConfirmations {
+    /// Hold an action until it's confirmed, returning its token.
+    pub(crate) fn hold(&self, action: ZubridgeAction, message: &str, ttl: Duration) -> crate::Result<String> {
+        let token = uuid::Uuid::new_v4().to_string();
+        let mut pending = self.pending.lock().map_err(|e| crate::Error::StateError(e.to_string()))?;
+        let now = Instant::now();
+        pending.retain(|_, held| held.expires > now);
+        let held = PendingAction {
+            action,
+            message: message.to_string(),
+            expires: now + ttl,
+        };
+        pending.insert(token.clone(), held);
+        Ok(token)
+    }
+
+    /// Remove a pending action, returning it unless it expired.
+    pub(crate) fn take(&self, token: &str) -> crate::Result<Option<ZubridgeAction>> {
+        let mut pending = self.pending.lock().map_err(|e| crate::Error::StateError(e.to_string()))?;
+        let held = pending.remove(token);
+        Ok(held.filter(|held| held.expires > Instant::now()).map(|held| held.action))
+    }
+
+    pub(crate) fn slice(&self) -> crate::Result<JsonValue> {
+        let pending = self.pending.lock().map_err(|e| crate::Error::StateError(e.to_string()))?;
+        let now = Instant::now();
+        let entries: BTreeMap<&String, PendingEntry> = pending
+            .iter()
+            .filter(|(_, held)| held.expires > now)
+            .map(|(token, held)| {
+                let entry = PendingEntry {
+                    action_type: &held.action.action_type,
+                    payload: &held.action.payload,
+                    message: &held.message,
+                };
+                (token, entry)
+            })
+            .collect();
+        serde_json::to_value(entries).map_err(|e| crate::Error::SerializationError(e.to_string()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn action(action_type: &str) -> ZubridgeAction {
+        ZubridgeAction {
+            action_type: action_type.to_string(),
+            payload: None,
+        }
+    }
+
+    #[test]
+    fn finds_the_message_for_an_action() {
+        let config = ConfirmationsConfig::default()
+            .require("TODO:DELETE_*", "Delete for good?")
+            .require("*:RESET", "Start over?");
+        assert_eq!(config.message_for("TODO:DELETE_ALL"), Some("Delete for good?"));
+        assert_eq!(config.message_for("SETTINGS:RESET"), Some("Start over?"));
+        assert_eq!(config.message_for("TODO:ADD"), None);
+    }
+
+    #[test]
+    fn hands_out_held_actions_once() {
+        let pending = PendingConfirmations::default();
+        let token = pending.hold(action("TODO:DELETE_ALL"), "Delete for good?", Duration::from_secs(60)).unwrap();
+        let slice = pending.slice().unwrap();
+        assert_eq!(slice[&token]["message"], "Delete for good?");
+
+        assert_eq!(pending.take(&token).unwrap().unwrap().action_type, "TODO:DELETE_ALL");
+        assert!(pending.take(&token).unwrap().is_none());
+        assert_eq!(pending.slice().unwrap(), serde_json::json!({}));
+    }
+
+    #[test]
+    fn drops_expired_actions() {
+        let pending = PendingConfirmations::default();
+        let token = pending.hold(action("TODO:DELETE_ALL"), "Delete for good?", Duration::ZERO).unwrap();
+        assert_eq!(pending.slice().unwrap(), serde_json::json!({}));
+        assert!(pending.take(&token).unwrap().is_none());
+    }
+}
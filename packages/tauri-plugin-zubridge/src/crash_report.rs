@@ -0,0 +1,110 @@
+use std::path::{Path, PathBuf};
+
+use serde::Serialize;
+use tauri::{AppHandle, Manager, Runtime};
+
+use crate::action_log::ActionLogEntry;
+use crate::desktop::Zubridge;
+use crate::models::*;
+
+/// Configures crash reports written on panic or via `Zubridge::capture_report`.
+#[derive(Clone, Debug)]
+pub struct CrashReportConfig {
+    /// Install a panic hook that writes a report before the previous hook runs. Defaults to true.
+    pub panic_hook: bool,
+    /// Dot-separated state paths replaced with `"[redacted]"` in reports. `*` matches any key.
+    pub redact_paths: Vec<String>,
+    /// Include action payloads in the recent action log. Defaults to false.
+    pub include_action_payloads: bool,
+    /// Where reports are written. Defaults to `crash-reports` in the plugin's data directory.
+    pub directory: Option<PathBuf>,
+}
+
+impl Default for CrashReportConfig {
+    fn default() -> Self {
+        Self {
+            panic_hook: true,
+            redact_paths: Vec::new(),
+            include_action_payloads: false,
+            directory: None,
+        }
+    }
+}
+
+/// The contents of a crash bundle.
+#[derive(Debug, Serialize)]
+pub(crate) struct CrashReport {
+    pub reason: String,
+    pub timestamp: u64,
+    /// The redacted state, or `null` if the state manager couldn't be read.
+    pub state: JsonValue,
+    pub recent_actions: Vec<ActionLogEntry>,
+    pub diagnostics: JsonValue,
+}
+
+/// Write a report as `zubridge-report-<timestamp>.json` in `dir`.
+pub(crate) fn write_report(dir: &Path, report: &CrashReport) -> crate::Result<PathBuf> {
+    std::fs::create_dir_all(dir)?;
+    let path = dir.join(format!("zubridge-report-{}.json", report.timestamp));
+    let contents = serde_json::to_string_pretty(report).map_err(|e| crate::Error::SerializationError(e.to_string()))?;
+    std::fs::write(&path, contents)?;
+    Ok(path)
+}
+
+/// Write a crash report whenever the app panics, then defer to the previously installed hook.
+pub(crate) fn install_panic_hook<R: Runtime>(app: AppHandle<R>) {
+    let previous = std::panic::take_hook();
+    std::panic::set_hook(Box::new(move |info| {
+        if let Some(zubridge) = app.try_state::<Zubridge<R>>() {
+            match zubridge.capture_report(&format!("panic: {}", info)) {
+                Ok(path) => eprintln!("zubridge: crash report written to {}", path.display()),
+                Err(e) => eprintln!("zubridge: failed to write crash report: {}", e),
+            }
+        }
+        previous(info);
+    }));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ZubridgeExt;
+    use serde_json::json;
+
+    struct Session;
+
+    impl StateManager for Session {
+        fn get_initial_state(&self) -> JsonValue {
+            json!({ "user": { "name": "a", "token": "secret" } })
+        }
+
+        fn dispatch_action(&mut self, _action: JsonValue) -> JsonValue {
+            self.get_initial_state()
+        }
+    }
+
+    #[test]
+    fn writes_redacted_reports() {
+        let dir = tempfile::tempdir().unwrap();
+        let options = ZubridgeOptions {
+            crash_reports: Some(CrashReportConfig {
+                panic_hook: false,
+                redact_paths: vec!["user.token".into()],
+                directory: Some(dir.path().to_path_buf()),
+                ..Default::default()
+            }),
+            ..Default::default()
+        };
+        let app = crate::test::mock_app_with_options(Session, options);
+        app.dispatch("LOGIN", Some(json!({ "password": "hunter2" }))).unwrap();
+
+        let path = app.app().zubridge().capture_report("manual").unwrap();
+        assert!(path.starts_with(dir.path()));
+        let report: JsonValue = serde_json::from_str(&std::fs::read_to_string(path).unwrap()).unwrap();
+        assert_eq!(report["reason"], "manual");
+        assert_eq!(report["state"]["user"], json!({ "name": "a", "token": "[redacted]" }));
+        assert_eq!(report["recent_actions"][0]["action_type"], "LOGIN");
+        assert_eq!(report["recent_actions"][0]["payload"], JsonValue::Null);
+        assert_eq!(report["diagnostics"]["state_status"], "ok");
+    }
+}
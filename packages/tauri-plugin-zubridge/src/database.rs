@@ -0,0 +1,339 @@
+//! A [`StateManager`] whose source of truth is a SQLite or Postgres database, through sqlx's
+//! `Any` driver.
+//!
+//! The state is materialized from one query per top-level slice, each returning a single row with
+//! a single column holding the slice as JSON text. Dispatched actions run their handler and
+//! re-materialize the state in one transaction, so windows only ever see committed data. Changes
+//! made outside the app are picked up by polling, or with Postgres `LISTEN`.
+//!
+//! Named queries are materialized the same way, but only while a window holds them: they're
+//! lazy slices, loaded on demand and sent to their holders as patches when their results change.
+
+use std::collections::{BTreeSet, HashMap};
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use sqlx::any::AnyPoolOptions;
+use sqlx::{AnyConnection, AnyPool};
+use tokio::runtime::Runtime;
+
+use crate::models::*;
+
+/// The future returned by an action handler, borrowing the transaction's connection.
+pub type TransactionFuture<'c> = Pin<Box<dyn Future<Output = crate::Result<()>> + Send + 'c>>;
+
+type ActionHandler = Arc<dyn for<'c> Fn(&'c mut AnyConnection, Option<JsonValue>) -> TransactionFuture<'c> + Send + Sync>;
+
+/// How a [`DatabaseStateManager`] reads the state and applies actions.
+#[derive(Clone)]
+pub struct DatabaseConfig {
+    /// A `sqlite:` or `postgres:` connection URL.
+    url: String,
+    /// Top-level slices and the queries materializing them.
+    slices: Vec<(String, String)>,
+    /// Named queries, materialized while a window holds them.
+    queries: Vec<(String, String)>,
+    handlers: HashMap<String, ActionHandler>,
+    #[cfg(feature = "postgres")]
+    listen: Option<String>,
+    /// The most connections the pool opens. Defaults to 5.
+    pub max_connections: u32,
+    /// How often the state is read again to pick up changes made outside the app. Disabled by
+    /// default.
+    pub poll_interval: Option<Duration>,
+}
+
+impl DatabaseConfig {
+    pub fn new(url: impl Into<String>) -> Self {
+        Self {
+            url: url.into(),
+            slices: Vec::new(),
+            queries: Vec::new(),
+            handlers: HashMap::new(),
+            #[cfg(feature = "postgres")]
+            listen: None,
+            max_connections: 5,
+            poll_interval: None,
+        }
+    }
+
+    /// Materialize the slice `key` with `query`, which returns the slice as JSON text, e.g.
+    /// `SELECT json_group_array(json_object('id', id, 'title', title)) FROM todos` on SQLite or
+    /// `SELECT coalesce(json_agg(t), '[]')::text FROM todos t` on Postgres.
+    pub fn slice(mut self, key: impl Into<String>, query: impl Into<String>) -> Self {
+        self.slices.push((key.into(), query.into()));
+        self
+    }
+
+    /// Materialize the named query `name` with `query`, like a slice, while a window holds it,
+    /// e.g. with `subscribeToQuery("recentOrders", ...)` from the frontend.
+    pub fn query(mut self, name: impl Into<String>, query: impl Into<String>) -> Self {
+        self.queries.push((name.into(), query.into()));
+        self
+    }
+
+    /// Handle `action_type` with `handler`, called with the transaction's connection and the
+    /// action's payload. Returning an error rolls the transaction back and fails the dispatch.
+    /// Actions without a handler leave the state unchanged.
+    pub fn on_action<F>(mut self, action_type: impl Into<String>, handler: F) -> Self
+    where
+        F: for<'c> Fn(&'c mut AnyConnection, Option<JsonValue>) -> TransactionFuture<'c> + Send + Sync + 'static,
+    {
+        self.handlers.insert(action_type.into(), Arc::new(handler));
+        self
+    }
+
+    /// Read the state again whenever a notification arrives on the Postgres `channel`, e.g. one
+    /// sent with `pg_notify` by a trigger.
+    #[cfg(feature = "postgres")]
+    pub fn listen(mut self, channel: impl Into<String>) -> Self {
+        self.listen = Some(channel.into());
+        self
+    }
+}
+
+/// What the pool's tasks share with the state manager.
+struct Shared {
+    config: DatabaseConfig,
+    pool: AnyPool,
+    /// The state as last materialized.
+    state: Mutex<JsonValue>,
+    /// The named queries held by a window.
+    active: Mutex<BTreeSet<String>>,
+    /// Held while materializing the state until it's stored, so a refresh that started before a
+    /// transaction can't overwrite the state it committed.
+    writing: tokio::sync::Mutex<()>,
+}
+
+impl Shared {
+    /// The slices and the active named queries, with the queries materializing them.
+    fn materialized(&self) -> crate::Result<Vec<(String, String)>> {
+        let active = self.active.lock().map_err(|e| crate::Error::StateError(e.to_string()))?;
+        let queries = self.config.queries.iter().filter(|(name, _)| active.contains(name));
+        Ok(self.config.slices.iter().chain(queries).cloned().collect())
+    }
+}
+
+/// A [`StateManager`] backed by a database. See [`DatabaseConfig`].
+pub struct DatabaseStateManager {
+    shared: Arc<Shared>,
+    /// Runs the pool and the change watchers on a thread of its own, since the state manager is
+    /// called from synchronous code, including inside Tauri's async runtime.
+    runtime: Option<Runtime>,
+}
+
+impl DatabaseStateManager {
+    /// Connect to the database and materialize the state.
+    pub fn connect(config: DatabaseConfig) -> crate::Result<Self> {
+        sqlx::any::install_default_drivers();
+        let runtime = tokio::runtime::Builder::new_multi_thread()
+            .worker_threads(1)
+            .thread_name("zubridge-database")
+            .enable_all()
+            .build()?;
+        let options = AnyPoolOptions::new().max_connections(config.max_connections);
+        let url = config.url.clone();
+        let pool = wait(&runtime, async move { Ok(options.connect(&url).await?) })?;
+        let shared = Arc::new(Shared {
+            config,
+            pool,
+            state: Mutex::new(JsonValue::Null),
+            active: Mutex::new(BTreeSet::new()),
+            writing: tokio::sync::Mutex::new(()),
+        });
+        let reading = shared.clone();
+        wait(&runtime, async move { refresh(&reading).await })?;
+        Ok(Self {
+            shared,
+            runtime: Some(runtime),
+        })
+    }
+}
+
+impl StateManager for DatabaseStateManager {
+    fn get_initial_state(&self) -> JsonValue {
+        self.shared.state.lock().map(|state| state.clone()).unwrap_or(JsonValue::Null)
+    }
+
+    fn dispatch_action(&mut self, action: JsonValue) -> JsonValue {
+        let action_type = action.get("type").and_then(JsonValue::as_str).unwrap_or_default().to_string();
+        self.try_dispatch_action(action).unwrap_or_else(|e| {
+            log::warn!("zubridge: transaction for {} failed: {}", action_type, e);
+            self.get_initial_state()
+        })
+    }
+
+    fn try_dispatch_action(&mut self, action: JsonValue) -> crate::Result<JsonValue> {
+        let action_type = action.get("type").and_then(JsonValue::as_str).unwrap_or_default();
+        let handler = self.shared.config.handlers.get(action_type).cloned();
+        let (Some(handler), Some(runtime)) = (handler, &self.runtime) else { return Ok(self.get_initial_state()) };
+        let payload = action.get("payload").filter(|payload| !payload.is_null()).cloned();
+        let shared = self.shared.clone();
+        // The transaction is rolled back on failure, leaving the state unchanged
+        wait(runtime, async move { transact(&shared, handler, payload).await })
+    }
+
+    fn load_slice(&mut self, slice: &str) {
+        let query = self.shared.config.queries.iter().find(|(name, _)| name == slice);
+        let (Some((name, query)), Some(runtime)) = (query, &self.runtime) else { return };
+        if let Ok(mut active) = self.shared.active.lock() {
+            active.insert(name.clone());
+        }
+        let shared = self.shared.clone();
+        let statement = [(name.clone(), query.clone())];
+        let result = wait(runtime, async move {
+            let mut connection = shared.pool.acquire().await?;
+            materialize(&mut connection, &statement).await
+        });
+        let results = match result {
+            Ok(results) => results,
+            Err(e) => {
+                log::warn!("zubridge: failed to run query {}: {}", slice, e);
+                return;
+            }
+        };
+        if let Ok(mut state) = self.shared.state.lock() {
+            if let Some(root) = state.as_object_mut() {
+                root.extend(results);
+            }
+        }
+    }
+
+    fn evict_slice(&mut self, slice: &str) {
+        if let Ok(mut active) = self.shared.active.lock() {
+            active.remove(slice);
+        }
+        if let Ok(mut state) = self.shared.state.lock() {
+            if let Some(root) = state.as_object_mut() {
+                root.remove(slice);
+            }
+        }
+    }
+
+    fn query_slices(&self) -> Vec<String> {
+        self.shared.config.queries.iter().map(|(name, _)| name.clone()).collect()
+    }
+
+    fn watch_changes(&mut self, changed: Arc<dyn Fn() + Send + Sync>) {
+        let Some(runtime) = &self.runtime else { return };
+        if let Some(interval) = self.shared.config.poll_interval {
+            runtime.spawn(poll(self.shared.clone(), interval, changed.clone()));
+        }
+        #[cfg(feature = "postgres")]
+        if let Some(channel) = self.shared.config.listen.clone() {
+            runtime.spawn(listen(self.shared.clone(), channel, changed));
+        }
+    }
+}
+
+impl Drop for DatabaseStateManager {
+    fn drop(&mut self) {
+        // Dropping a runtime blocks, which panics inside Tauri's async runtime
+        if let Some(runtime) = self.runtime.take() {
+            runtime.shutdown_background();
+        }
+    }
+}
+
+/// Run `future` on `runtime` and wait for it. Spawning rather than `block_on` works from any
+/// thread, including one of another runtime's.
+fn wait<T: Send + 'static>(
+    runtime: &Runtime,
+    future: impl Future<Output = crate::Result<T>> + Send + 'static,
+) -> crate::Result<T> {
+    let (sender, receiver) = std::sync::mpsc::channel();
+    runtime.spawn(async move {
+        let _ = sender.send(future.await);
+    });
+    receiver
+        .recv()
+        .map_err(|_| crate::Error::StateError("the database runtime stopped".into()))?
+}
+
+/// Run `handler` and materialize the state it leaves in one transaction.
+async fn transact(shared: &Shared, handler: ActionHandler, payload: Option<JsonValue>) -> crate::Result<JsonValue> {
+    let _writing = shared.writing.lock().await;
+    let mut transaction = shared.pool.begin().await?;
+    handler(&mut transaction, payload).await?;
+    let state = JsonValue::Object(materialize(&mut transaction, &shared.materialized()?).await?);
+    transaction.commit().await?;
+    let mut current = shared.state.lock().map_err(|e| crate::Error::StateError(e.to_string()))?;
+    *current = state.clone();
+    Ok(state)
+}
+
+async fn materialize(
+    connection: &mut AnyConnection,
+    slices: &[(String, String)],
+) -> crate::Result<serde_json::Map<String, JsonValue>> {
+    let mut root = serde_json::Map::new();
+    for (key, query) in slices {
+        let json: Option<String> = sqlx::query_scalar(query).fetch_one(&mut *connection).await?;
+        let value = match json {
+            Some(json) => serde_json::from_str(&json)
+                .map_err(|e| crate::Error::SerializationError(format!("slice {}: {}", key, e)))?,
+            None => JsonValue::Null,
+        };
+        root.insert(key.clone(), value);
+    }
+    Ok(root)
+}
+
+/// Materialize the state, returning whether it changed.
+async fn refresh(shared: &Shared) -> crate::Result<bool> {
+    let _writing = shared.writing.lock().await;
+    let mut connection = shared.pool.acquire().await?;
+    let state = JsonValue::Object(materialize(&mut connection, &shared.materialized()?).await?);
+    let mut current = shared.state.lock().map_err(|e| crate::Error::StateError(e.to_string()))?;
+    if *current == state {
+        return Ok(false);
+    }
+    *current = state;
+    Ok(true)
+}
+
+async fn refresh_and_notify(shared: &Shared, changed: &Arc<dyn Fn() + Send + Sync>) {
+    match refresh(shared).await {
+        // Emitting locks the state manager, which may be waiting on this runtime for a dispatch
+        Ok(true) => {
+            let changed = changed.clone();
+            tokio::task::spawn_blocking(move || changed());
+        }
+        Ok(false) => {}
+        Err(e) => log::warn!("zubridge: failed to read the database: {}", e),
+    }
+}
+
+async fn poll(shared: Arc<Shared>, interval: Duration, changed: Arc<dyn Fn() + Send + Sync>) {
+    loop {
+        tokio::time::sleep(interval).await;
+        refresh_and_notify(&shared, &changed).await;
+    }
+}
+
+#[cfg(feature = "postgres")]
+async fn listen(shared: Arc<Shared>, channel: String, changed: Arc<dyn Fn() + Send + Sync>) {
+    let mut listener = match sqlx::postgres::PgListener::connect(&shared.config.url).await {
+        Ok(listener) => listener,
+        Err(e) => {
+            log::warn!("zubridge: failed to connect to listen on {}: {}", channel, e);
+            return;
+        }
+    };
+    if let Err(e) = listener.listen(&channel).await {
+        log::warn!("zubridge: failed to listen on {}: {}", channel, e);
+        return;
+    }
+    loop {
+        match listener.recv().await {
+            Ok(_) => refresh_and_notify(&shared, &changed).await,
+            // The listener reconnects on the next call
+            Err(e) => {
+                log::warn!("zubridge: lost the connection listening on {}: {}", channel, e);
+                tokio::time::sleep(Duration::from_secs(1)).await;
+            }
+        }
+    }
+}
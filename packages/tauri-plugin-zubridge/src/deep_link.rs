@@ -0,0 +1,129 @@
+use hmac::{Hmac, Mac};
+use sha2::Sha256;
+use tauri::Url;
+
+use crate::models::*;
+use crate::utils::{glob_matches, now_millis, unhex};
+
+/// Allows OS automation (Shortcuts, AutoHotkey, scripts) to dispatch actions through
+/// `myapp://zubridge/dispatch?type=...&payload=...` links.
+#[derive(Clone, Debug, Default)]
+pub struct DeepLinkConfig {
+    /// Action types that may be dispatched from a link, where `*` matches any run of characters.
+    /// Nothing is allowed when empty.
+    pub allowed_actions: Vec<String>,
+    /// When set, links must carry an `exp` parameter, the Unix time in seconds after which
+    /// they're rejected, and a `sig` parameter: the hex HMAC-SHA256 of the type, payload and
+    /// `exp`, each written as `<length in bytes>:<value>,`, keyed with this secret.
+    pub secret: Option<String>,
+}
+
+/// Parse and verify a dispatch link.
+pub(crate) fn parse(config: &DeepLinkConfig, link: &str) -> crate::Result<ZubridgeAction> {
+    let url = Url::parse(link).map_err(|e| rejected(format!("invalid URL: {}", e)))?;
+    if url.host_str() != Some("zubridge") || url.path() != "/dispatch" {
+        return Err(rejected(format!("not a zubridge dispatch link: {}", link)));
+    }
+
+    let query = |name: &str| {
+        url.query_pairs()
+            .find(|(key, _)| key == name)
+            .map(|(_, value)| value.into_owned())
+    };
+    let action_type = query("type").ok_or_else(|| rejected("missing action type".to_string()))?;
+    let raw_payload = query("payload");
+
+    if !config.allowed_actions.iter().any(|pattern| glob_matches(pattern, &action_type)) {
+        return Err(rejected(format!("action {} is not allowed from deep links", action_type)));
+    }
+
+    if let Some(secret) = &config.secret {
+        let expires = query("exp").ok_or_else(|| rejected("missing expiry".to_string()))?;
+        let expires_at: u64 = expires.parse().map_err(|_| rejected(format!("invalid expiry: {}", expires)))?;
+        if expires_at.saturating_mul(1000) < now_millis() {
+            return Err(rejected("expired link".to_string()));
+        }
+        let signature = query("sig").and_then(|sig| unhex(&sig)).unwrap_or_default();
+        let mut mac = Hmac::<Sha256>::new_from_slice(secret.as_bytes()).map_err(|e| rejected(e.to_string()))?;
+        for field in [action_type.as_str(), raw_payload.as_deref().unwrap_or_default(), &expires] {
+            mac.update(format!("{}:{},", field.len(), field).as_bytes());
+        }
+        mac.verify_slice(&signature).map_err(|_| rejected("invalid signature".to_string()))?;
+    }
+
+    let payload = raw_payload
+        .map(|payload| serde_json::from_str(&payload))
+        .transpose()
+        .map_err(|e| rejected(format!("payload is not valid JSON: {}", e)))?;
+
+    Ok(ZubridgeAction { action_type, payload })
+}
+
+fn rejected(reason: String) -> crate::Error {
+    crate::Error::DeepLinkRejected(reason)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utils::hex;
+
+    fn config() -> DeepLinkConfig {
+        DeepLinkConfig {
+            allowed_actions: vec!["COUNTER:*".to_string()],
+            secret: Some("secret".to_string()),
+        }
+    }
+
+    fn sign(action_type: &str, payload: &str, expires: &str) -> String {
+        let mut mac = Hmac::<Sha256>::new_from_slice(b"secret").unwrap();
+        for field in [action_type, payload, expires] {
+            mac.update(format!("{}:{},", field.len(), field).as_bytes());
+        }
+        hex(&mac.finalize().into_bytes())
+    }
+
+    fn link(action_type: &str, payload: &str, expires: u64, sig: &str) -> String {
+        let mut url = Url::parse("myapp://zubridge/dispatch").unwrap();
+        url.query_pairs_mut()
+            .append_pair("type", action_type)
+            .append_pair("payload", payload)
+            .append_pair("exp", &expires.to_string())
+            .append_pair("sig", sig);
+        url.into()
+    }
+
+    fn later() -> u64 {
+        now_millis() / 1000 + 60
+    }
+
+    #[test]
+    fn accepts_signed_links() {
+        let sig = sign("COUNTER:ADD", "5", &later().to_string());
+        let action = parse(&config(), &link("COUNTER:ADD", "5", later(), &sig)).unwrap();
+        assert_eq!(action.action_type, "COUNTER:ADD");
+        assert_eq!(action.payload, Some(serde_json::json!(5)));
+    }
+
+    #[test]
+    fn rejects_expired_links() {
+        let expires = now_millis() / 1000 - 1;
+        let sig = sign("COUNTER:ADD", "5", &expires.to_string());
+        let result = parse(&config(), &link("COUNTER:ADD", "5", expires, &sig));
+        assert!(matches!(result, Err(crate::Error::DeepLinkRejected(reason)) if reason == "expired link"));
+    }
+
+    #[test]
+    fn signs_fields_unambiguously() {
+        // Signed for type `COUNTER:ADD` with payload `5,` but sent with the boundary moved
+        let sig = sign("COUNTER:ADD", "5,", &later().to_string());
+        let result = parse(&config(), &link("COUNTER:ADD5", ",", later(), &sig));
+        assert!(matches!(result, Err(crate::Error::DeepLinkRejected(reason)) if reason == "invalid signature"));
+    }
+
+    #[test]
+    fn rejects_actions_outside_the_allow_list() {
+        let sig = sign("SESSION:UNLOCK", "", &later().to_string());
+        assert!(parse(&config(), &link("SESSION:UNLOCK", "", later(), &sig)).is_err());
+    }
+}
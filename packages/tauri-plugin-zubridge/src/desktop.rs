@@ -1,83 +1,3314 @@
+use arc_swap::ArcSwapOption;
 use serde::de::DeserializeOwned;
-use std::sync::{Arc, Mutex};
+use serde::Serialize;
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex, MutexGuard, TryLockError};
+use std::time::{Duration, Instant};
+use tauri::ipc::Channel;
+use tauri::menu::{Menu, MenuId};
 use tauri::{plugin::PluginApi, AppHandle, Runtime, Manager, Emitter};
 
+use crate::action_log::ActionLog;
+use crate::aggregates::Aggregates;
+use crate::batch::{BatchMode, BatchResult};
+use crate::blobs::{BlobRef, BlobStore};
+use crate::appearance::{self, Appearance, AppearanceProvider, AppearanceState};
+use crate::compaction::{CompactionConfig, Retention};
+use crate::config_file::ConfigFile;
+use crate::confirmations::{ConfirmationsConfig, PendingConfirmations};
+use crate::ephemeral::EphemeralChannel;
+use crate::error_reports::{ErrorReport, FrontendError};
+use crate::crash_report::{install_panic_hook, write_report, CrashReport};
+use crate::dialogs::{self, DialogResponder, Dialogs, DialogsConfig};
+#[cfg(feature = "automation")]
+use crate::effects::automation::{self, Automation};
+#[cfg(feature = "secrets")]
+use crate::secrets::Secrets;
+use crate::effects::cache::EffectCache;
+use crate::effects::offsets::EffectOffsets;
+use crate::emit_retry::{EmitRetries, MissedRevisions};
+#[cfg(feature = "reqwest")]
+use crate::effects::http::HttpEffects;
+#[cfg(feature = "reqwest")]
+use crate::webhooks::Webhooks;
+#[cfg(feature = "mqtt")]
+use crate::mqtt::MqttBridge;
+#[cfg(feature = "search")]
+use crate::effects::search::{SearchHit, SearchIndexes};
+use crate::experiments::Assignments;
+use crate::extensions::Extensions;
+use crate::feature_flags::FeatureFlags;
+#[cfg(debug_assertions)]
+use crate::fixtures;
+use crate::forms::{self, Form, Forms};
+use crate::fs_watch::FsWatch;
+use crate::import::ImportSink;
+use crate::breadcrumbs;
+#[cfg(debug_assertions)]
+use crate::dev_handoff::DevHandoff;
+use crate::dev_reload::LoadedPages;
+use crate::interceptors::run_interceptors;
+use crate::lazy_slices::LazySlices;
+use crate::merge;
+#[cfg(feature = "stress")]
+use crate::load::{LoadProfile, LoadReport};
+use crate::metrics::{Metrics, MetricsSnapshot};
 use crate::models::*;
+use crate::persistence::PersistenceWriter;
+use crate::paginator::{self, PageRequest, Paginators};
+use crate::platform_prefs::PlatformPrefs;
+use crate::path_actions;
+use crate::rpc::{PendingRequests, RpcRequest};
+use crate::sequences::Sequences;
+use crate::shared_memory::{SharedMemoryTransport, StateTransport};
+use crate::plugin_slices::PluginSlices;
+use crate::slice_router::SliceRouter;
+use crate::subscriptions::{StateUpdate, Subscriptions};
+use crate::integrity::{Handshake, PayloadSigner};
+use crate::initial_state::{InitialStateProvider, LoadError};
+use crate::health::{self, Health, InitialStateEnvelope, VersionInfo};
+use crate::event_names::EventNames;
+use crate::warm_start::WarmStartCache;
+use crate::sensitive::SensitivePaths;
+use crate::session::{self, SessionLock};
+use crate::state_graph::{GraphFormat, SliceOwner, StateGraph};
+use crate::snapshots::{self, Simulation, SnapshotDiff, Snapshots};
+#[cfg(feature = "stress")]
+use crate::soak::{self, SoakConfig};
+use crate::startup::{Startup, StartupProgress};
+use crate::state_check;
+use crate::state_waiters::StateWaiters;
+use crate::statechart::{self, Statecharts};
+use crate::subsystems::Subsystems;
+use crate::sync::{SyncStatuses, SyncTask};
+use crate::telemetry::Telemetry;
+use crate::timers::{self, Timers};
+use crate::trash::{self, Trash};
+use crate::updater::{self, Updater};
+use crate::uploads::{self, Uploads};
+use crate::window_actions::{self, WindowRegistry};
+use crate::workflows::{self, Workflows};
+use crate::workspaces::{self, Workspaces};
+use crate::utils::{catch_reducer_panic, get_path, glob_matches, now_millis, plugin_data_dir, redact_path};
+use crate::ZubridgeExt;
 
 pub fn init<R: Runtime, C: DeserializeOwned>(
   app: &AppHandle<R>,
   _api: PluginApi<R, C>,
+  options: ZubridgeOptions,
 ) -> crate::Result<Zubridge<R>> {
-  // Initialize with default options
-  let options = ZubridgeOptions::default();
+  let fs_watch = FsWatch::new(app.clone(), options.watch_debounce);
+  let startup = Startup::default();
+
+  // Hydrate the config slice and re-hydrate it whenever the file changes
+  let config_file = options
+    .config_file
+    .clone()
+    .map(|path| Arc::new(ConfigFile::load(path, options.config_key.clone())));
+  if let Some(config_file) = &config_file {
+    let app_handle = app.clone();
+    let watch_result = fs_watch.watch(config_file.path(), move |_| {
+      let zubridge = app_handle.zubridge();
+      if let Err(e) = zubridge.reload_config() {
+        zubridge.options.error_policy.report(format_args!("failed to reload config file"), &e);
+      }
+      None
+    });
+    if let Err(e) = watch_result {
+      log::warn!("zubridge: config file will not be live reloaded: {}", e);
+    }
+  }
+
+  startup.report("config", 0.2)?;
+  let feature_flags = FeatureFlags::new(options.feature_flags.clone());
+
+  let data_dir = plugin_data_dir(app)?;
+  let install_id = crate::install_id::load_or_create(&data_dir)?;
+  let sequences = Sequences::load(&data_dir)?;
+  let effect_offsets = EffectOffsets::load(&data_dir)?;
+
+  // Assign experiment variants and apply them to any bound flags
+  let experiments = if options.experiments.is_empty() {
+    None
+  } else {
+    let assignments = Assignments::load(&data_dir, &install_id, &options.experiments)?;
+    for experiment in &options.experiments {
+      if let (Some(flag), Some(variant)) = (&experiment.flag, assignments.get(&experiment.name)) {
+        feature_flags.set(flag, variant.into())?;
+      }
+    }
+    Some(assignments)
+  };
+
+  startup.report("plugin", 0.5)?;
+  let subsystems = Subsystems::default();
+  let persistence = PersistenceWriter::start(options.persistence.clone(), options.error_policy, &subsystems);
+  let telemetry = options
+    .telemetry
+    .clone()
+    .map(|config| Telemetry::start(config, install_id.clone(), data_dir.clone(), persistence.clone(), &subsystems));
+
+  if options.crash_reports.as_ref().is_some_and(|config| config.panic_hook) {
+    install_panic_hook(app.clone());
+  }
+  let action_log = ActionLog::new(options.action_log_size, options.id_generator.clone());
+  let ephemeral = EphemeralChannel::new(options.ephemeral_paths.clone());
+  let subscriptions = Subscriptions::new(options.subscription_max_in_flight);
+  let payload_signer = options.payload_integrity.then(PayloadSigner::new);
+  let event_names = EventNames::new(&app.config().identifier, &options);
+  let sensitive = if options.sensitive_paths.is_empty() {
+    None
+  } else {
+    let paths = options.sensitive_paths.clone();
+    Some(Arc::new(SensitivePaths::new(paths, options.encryption_key, &data_dir, &app.config().identifier)?))
+  };
+  let warm_start = options.warm_start.clone().map(|config| {
+    let cache = Arc::new(WarmStartCache::new(config, &data_dir, sensitive.clone(), persistence.clone()));
+    let flushed = cache.clone();
+    subsystems.register("warm-start", move || flushed.flush());
+    cache
+  });
+  let session_lock = options.session_lock.clone().map(SessionLock::new);
+  let workspaces = options.workspaces.clone().map(|config| {
+    let handle = app.clone();
+    subsystems.register("workspaces", move || {
+      if let Some(zubridge) = handle.try_state::<Zubridge<R>>() {
+        if let Err(e) = zubridge.save_active_workspace() {
+          zubridge.options.error_policy.report(format_args!("failed to save the active workspace"), &e);
+        }
+      }
+    });
+    Workspaces::load(config, &data_dir, sensitive.clone(), persistence.clone())
+  });
+  let blobs = options.blobs.clone().map(|config| BlobStore::new(config, &data_dir));
+  let uploads = Arc::new(Uploads::new(options.max_upload_bytes, options.max_pending_uploads));
+  let loaded_pages = options.dev_reload.as_ref().map(|_| LoadedPages::default());
+  #[cfg(debug_assertions)]
+  let dev_handoff = options.dev_handoff.clone().map(|config| {
+    let handoff = Arc::new(DevHandoff::new(config, &data_dir, sensitive.clone(), persistence.clone()));
+    spawn_dev_handoff(&subsystems, app.clone(), handoff.clone());
+    handoff
+  });
+  let discarded = uploads.clone();
+  subsystems.register("uploads", move || discarded.clear());
+  // Windows get the state cached by the last launch while the authoritative one loads
+  let placeholder = options.initial_state.as_ref().map(|provider| {
+    let cached = warm_start.as_ref().and_then(|cache| cache.cached());
+    Arc::new(cached.unwrap_or_else(|| provider.placeholder.clone()))
+  });
+  let loading = ArcSwapOption::from(placeholder);
+  if let Some(provider) = options.initial_state.clone() {
+    spawn_initial_state_load(app.clone(), provider);
+  }
+  let lazy_slices = LazySlices::new(options.lazy_slices.clone(), options.diffed_slices.clone());
+  let windows = options.window_actions.as_ref().map(|_| WindowRegistry::default());
+  let dialogs = options.dialogs.as_ref().map(|_| Dialogs::default());
+  let appearance = options.appearance.as_ref().map(|config| {
+    if let Some(provider) = config.provider.clone() {
+      spawn_appearance_poll(&subsystems, app.clone(), provider, config.poll_interval);
+    }
+    AppearanceState::default()
+  });
+  let confirmations = options.confirmations.as_ref().map(|_| PendingConfirmations::default());
+  let updater = options.updater.clone().map(Updater::new);
+  if options.tray_actions.is_some() {
+    app.on_menu_event(|app, event| {
+      if let Some(zubridge) = app.try_state::<Zubridge<R>>() {
+        zubridge.menu_clicked(event.id().as_ref());
+      }
+    });
+  }
+  if let Some(delay) = options.slice_eviction_delay {
+    spawn_slice_eviction(&subsystems, app.clone(), delay);
+  }
+  if let Some(config) = options.compaction.clone() {
+    spawn_compaction(&subsystems, app.clone(), config);
+  }
+  if let Some(config) = &options.blobs {
+    spawn_blob_gc(&subsystems, app.clone(), config.gc_interval);
+  }
+  let sync = options.sync.as_ref().map(|config| {
+    for task in &config.tasks {
+      spawn_sync_task(&subsystems, app.clone(), task.clone());
+    }
+    SyncStatuses::default()
+  });
+  let forms = Forms::new(options.forms.clone());
+  let statecharts = Statecharts::new(options.statecharts.clone());
+  let paginators = Paginators::new(options.paginators.clone());
+  let aggregates = Aggregates::new(options.aggregations.clone());
+  let slice_router = SliceRouter::new(options.slice_routes.clone())?;
+  let effect_cache = options.effect_cache_ttl.map(|ttl| Arc::new(EffectCache::new(ttl)));
+  #[cfg(feature = "reqwest")]
+  let http = HttpEffects::new(dispatcher(app), effect_cache.clone());
+  #[cfg(feature = "reqwest")]
+  let webhooks = options
+    .webhooks
+    .clone()
+    .map(|config| Webhooks::new(config, options.id_generator.clone(), options.error_policy));
+  let extensions = Extensions::start(&options.extensions, &subsystems, app, options.error_policy);
+  #[cfg(feature = "automation")]
+  let automation = options
+    .automation
+    .clone()
+    .map(|config| Automation::load(config, &data_dir, dispatcher(app)));
+  #[cfg(feature = "secrets")]
+  let secrets = options
+    .secrets
+    .clone()
+    .map(|config| Secrets::load(config, &app.config().identifier, &data_dir));
+  let platform_prefs = options.platform_prefs.clone().map(|config| {
+    let prefs = PlatformPrefs::new(config);
+    if let Err(e) = prefs.refresh() {
+      options.error_policy.report(format_args!("failed to read the platform preferences"), &e);
+    }
+    spawn_platform_prefs_poll(&subsystems, app.clone(), prefs.poll_interval());
+    prefs
+  });
+  #[cfg(feature = "mqtt")]
+  let mqtt = options.mqtt.clone().map(|config| {
+    let (bridge, connection) = MqttBridge::new(config);
+    spawn_mqtt(&subsystems, app.clone(), connection);
+    bridge
+  });
+  #[cfg(feature = "search")]
+  let search = SearchIndexes::new(options.search_indexes.clone())?;
+  let timers = options.timers.as_ref().map(|config| {
+    spawn_timer_ticks(&subsystems, app.clone(), config.resolution);
+    Timers::default()
+  });
+  #[cfg(debug_assertions)]
+  if options.fixtures.as_ref().is_some_and(|config| config.load_on_startup) {
+    spawn_fixture_load(&subsystems, app.clone());
+  }
+  #[cfg(feature = "e2e")]
+  crate::e2e::start_from_env(&subsystems, app.clone())?;
+  #[cfg(debug_assertions)]
+  if crate::repl::requested() {
+    crate::repl::start(app.clone());
+  }
+  #[cfg(feature = "stress")]
+  if let Ok(path) = std::env::var(soak::SOAK_VAR) {
+    spawn_soak_from_env(&subsystems, app.clone(), soak::read_config(std::path::Path::new(&path))?);
+  }
+  let workflows = Arc::new(Workflows::load(
+    options.workflows.clone(),
+    &data_dir,
+    options.id_generator.clone(),
+    persistence.clone(),
+  ));
+  if !workflows.is_empty() {
+    spawn_workflow_resume(&subsystems, app.clone());
+  }
+  let trash = options.trash.clone().map(|config| {
+    let trash = Trash::load(config, &data_dir, persistence.clone());
+    if let Err(e) = trash.purge_expired() {
+      options.error_policy.report(format_args!("failed to purge the trash"), &e);
+    }
+    trash
+  });
+  let shared_memory = match options.transport {
+    StateTransport::SharedMemory => Some(SharedMemoryTransport::new()?),
+    StateTransport::Event => None,
+  };
+  let emit_retries = Arc::new(EmitRetries::new(options.emit_retry.clone()));
 
   // Create the Zubridge struct with app handle and options
   Ok(Zubridge {
     app: app.clone(),
     options,
+    fs_watch,
+    config_file,
+    feature_flags,
+    experiments,
+    install_id,
+    sequences,
+    telemetry,
+    action_log,
+    data_dir,
+    ephemeral,
+    subscriptions,
+    payload_signer,
+    event_names,
+    warm_start,
+    sensitive,
+    session_lock,
+    workspaces,
+    blobs,
+    uploads,
+    loaded_pages,
+    #[cfg(debug_assertions)]
+    dev_handoff,
+    lazy_slices,
+    windows,
+    dialogs,
+    confirmations,
+    appearance,
+    updater,
+    sync,
+    menus: Mutex::new(HashMap::new()),
+    startup,
+    forms,
+    statecharts,
+    workflows,
+    timers,
+    requests: PendingRequests::default(),
+    paginators,
+    aggregates,
+    slice_router,
+    plugin_slices: PluginSlices::default(),
+    trash,
+    effect_cache,
+    effect_offsets,
+    #[cfg(feature = "reqwest")]
+    http,
+    #[cfg(feature = "reqwest")]
+    webhooks,
+    #[cfg(feature = "mqtt")]
+    mqtt,
+    extensions,
+    #[cfg(feature = "automation")]
+    automation,
+    #[cfg(feature = "secrets")]
+    secrets,
+    platform_prefs,
+    #[cfg(feature = "search")]
+    search,
+    shared_memory,
+    revision: AtomicU64::new(0),
+    missed_revisions: MissedRevisions::default(),
+    emit_retries,
+    committed: ArcSwapOption::empty(),
+    loading,
+    open_transactions: Mutex::new(HashMap::new()),
+    waiters: StateWaiters::default(),
+    metrics: Metrics::default(),
+    subsystems,
   })
 }
 
+/// A dispatcher for background work, which dispatches through the managed plugin.
+pub(crate) fn dispatcher<R: Runtime>(app: &AppHandle<R>) -> Dispatcher {
+  let app = app.clone();
+  Dispatcher::new(move |action| app.zubridge().dispatch_action(action))
+}
+
+/// Emit the placeholder state once the plugin is managed, then run the initial state provider
+/// on the async runtime.
+fn spawn_initial_state_load<R: Runtime>(app: AppHandle<R>, provider: InitialStateProvider) {
+  tauri::async_runtime::spawn(async move {
+    let zubridge = loop {
+      if let Some(zubridge) = app.try_state::<Zubridge<R>>() {
+        break zubridge;
+      }
+      tokio::time::sleep(Duration::from_millis(50)).await;
+    };
+    if let Err(e) = zubridge.emit_current_state() {
+      zubridge.options.error_policy.report(format_args!("failed to emit the placeholder state"), &e);
+    }
+    let loaded = provider.load().await;
+    if let Err(e) = zubridge.initial_state_loaded(loaded, provider.file()) {
+      zubridge.options.error_policy.report(format_args!("failed to load the initial state"), &e);
+    }
+  });
+}
+
+/// Resume the workflow runs persisted by a previous launch once the plugin is managed.
+fn spawn_workflow_resume<R: Runtime>(subsystems: &Subsystems, app: AppHandle<R>) {
+  subsystems.spawn("workflow-resume", move |signal| loop {
+    if let Some(zubridge) = app.try_state::<Zubridge<R>>() {
+      if let Err(e) = zubridge.workflows.resume(dispatcher(&app)) {
+        zubridge.options.error_policy.report(format_args!("failed to resume workflows"), &e);
+      }
+      return;
+    }
+    if signal.sleep(Duration::from_millis(100)) {
+      return;
+    }
+  });
+}
+
+/// Load every fixture once the plugin is managed.
+#[cfg(debug_assertions)]
+fn spawn_fixture_load<R: Runtime>(subsystems: &Subsystems, app: AppHandle<R>) {
+  subsystems.spawn("fixtures", move |signal| loop {
+    if let Some(zubridge) = app.try_state::<Zubridge<R>>() {
+      if let Err(e) = zubridge.load_fixture(None) {
+        zubridge.options.error_policy.report(format_args!("failed to load fixtures"), &e);
+      }
+      return;
+    }
+    if signal.sleep(Duration::from_millis(100)) {
+      return;
+    }
+  });
+}
+
+/// Restore the state handed over by the previous process once the plugin is managed and its
+/// initial state loaded, then keep writing the state for the next one.
+#[cfg(debug_assertions)]
+fn spawn_dev_handoff<R: Runtime>(subsystems: &Subsystems, app: AppHandle<R>, handoff: Arc<DevHandoff>) {
+  subsystems.spawn("dev-handoff", move |signal| {
+    let mut restored = false;
+    loop {
+      let stopping = signal.sleep(handoff.write_interval());
+      let Some(zubridge) = app.try_state::<Zubridge<R>>() else {
+        if stopping {
+          return;
+        }
+        continue;
+      };
+      let policy = zubridge.options.error_policy;
+      if !restored && !stopping {
+        if zubridge.loading.load().is_some() {
+          continue;
+        }
+        restored = true;
+        if let Some(state) = handoff.take_previous() {
+          log::debug!("zubridge: restoring the state handed over by the previous process");
+          if let Err(e) = zubridge.restore_dev_handoff(state) {
+            policy.report(format_args!("failed to restore the dev handoff"), &e);
+          }
+        }
+      }
+      // Until the previous state is restored, the handoff is the only copy of it
+      if restored {
+        if let Err(e) = handoff.flush() {
+          policy.report(format_args!("failed to write the dev handoff"), &e);
+        }
+      }
+      if stopping {
+        return;
+      }
+    }
+  });
+}
+
+/// Drive the MQTT connection once the plugin is managed: publish a snapshot on every connect
+/// and dispatch allowed actions from the command topic.
+#[cfg(feature = "mqtt")]
+fn spawn_mqtt<R: Runtime>(subsystems: &Subsystems, app: AppHandle<R>, mut connection: rumqttc::Connection) {
+  subsystems.spawn("mqtt", move |signal| loop {
+    let Some(zubridge) = app.try_state::<Zubridge<R>>() else {
+      if signal.sleep(Duration::from_millis(100)) {
+        return;
+      }
+      continue;
+    };
+    let Some(mqtt) = &zubridge.mqtt else { return };
+    if signal.sleep(Duration::ZERO) {
+      mqtt.disconnect();
+      return;
+    }
+
+    let policy = zubridge.options.error_policy;
+    match connection.recv_timeout(Duration::from_millis(100)) {
+      Ok(Ok(rumqttc::Event::Incoming(rumqttc::Packet::ConnAck(_)))) => {
+        let connected = zubridge.get_initial_state().and_then(|state| mqtt.connected(&state, zubridge.revision()));
+        if let Err(e) = connected {
+          policy.report(format_args!("failed to set up the MQTT connection"), &e);
+        }
+      }
+      Ok(Ok(rumqttc::Event::Incoming(rumqttc::Packet::Publish(publish)))) => {
+        if let Some(action) = mqtt.command(&publish.topic, &publish.payload) {
+          if let Err(e) = action.and_then(|action| zubridge.dispatch_action(action)) {
+            policy.report(format_args!("failed to dispatch an MQTT command"), &e);
+          }
+        }
+      }
+      Ok(Ok(_)) | Err(_) => {}
+      Ok(Err(e)) => {
+        // The client reconnects on the next poll
+        log::warn!("zubridge: MQTT connection failed: {}", e);
+        if signal.sleep(Duration::from_secs(1)) {
+          return;
+        }
+      }
+    }
+  });
+}
+
+/// Start the soak run configured by `ZUBRIDGE_SOAK` once the plugin is managed.
+#[cfg(feature = "stress")]
+fn spawn_soak_from_env<R: Runtime>(subsystems: &Subsystems, app: AppHandle<R>, config: SoakConfig) {
+  subsystems.spawn("soak-start", move |signal| loop {
+    if let Some(zubridge) = app.try_state::<Zubridge<R>>() {
+      match zubridge.start_soak(config) {
+        Ok(report) => log::info!("zubridge: soak run started, reporting to {}", report.display()),
+        Err(e) => zubridge.options.error_policy.report(format_args!("failed to start the soak run"), &e),
+      }
+      return;
+    }
+    if signal.sleep(Duration::from_millis(100)) {
+      return;
+    }
+  });
+}
+
+/// Tick running timers into the state and fire the ones that finished.
+fn spawn_timer_ticks<R: Runtime>(subsystems: &Subsystems, app: AppHandle<R>, resolution: Duration) {
+  subsystems.spawn("timers", move |signal| loop {
+    if signal.sleep(resolution) {
+      return;
+    }
+    if let Some(zubridge) = app.try_state::<Zubridge<R>>() {
+      if let Err(e) = zubridge.tick_timers() {
+        zubridge.options.error_policy.report(format_args!("failed to tick timers"), &e);
+      }
+    }
+  });
+}
+
+/// Poll an appearance provider for settings Tauri doesn't report.
+fn spawn_appearance_poll<R: Runtime>(
+  subsystems: &Subsystems,
+  app: AppHandle<R>,
+  provider: Arc<dyn AppearanceProvider>,
+  interval: Duration,
+) {
+  subsystems.spawn("appearance", move |signal| loop {
+    let appearance = provider.read();
+    if let Some(zubridge) = app.try_state::<Zubridge<R>>() {
+      if let Err(e) = zubridge.update_appearance(appearance) {
+        zubridge.options.error_policy.report(format_args!("failed to update the appearance"), &e);
+      }
+    }
+    if signal.sleep(interval) {
+      return;
+    }
+  });
+}
+
+/// Read the platform preferences for changes made outside the app, e.g. by policy tooling.
+fn spawn_platform_prefs_poll<R: Runtime>(subsystems: &Subsystems, app: AppHandle<R>, interval: Duration) {
+  subsystems.spawn("platform-prefs", move |signal| loop {
+    if signal.sleep(interval) {
+      return;
+    }
+    if let Some(zubridge) = app.try_state::<Zubridge<R>>() {
+      if let Err(e) = zubridge.refresh_platform_prefs() {
+        zubridge.options.error_policy.report(format_args!("failed to read the platform preferences"), &e);
+      }
+    }
+  });
+}
+
+/// Periodically evict lazy slices that no window has held for `delay`.
+fn spawn_slice_eviction<R: Runtime>(subsystems: &Subsystems, app: AppHandle<R>, delay: Duration) {
+  let tick = (delay / 4).max(Duration::from_millis(100));
+  subsystems.spawn("slice-eviction", move |signal| loop {
+    if signal.sleep(tick) {
+      return;
+    }
+    if let Some(zubridge) = app.try_state::<Zubridge<R>>() {
+      if let Err(e) = zubridge.evict_detached_slices(delay) {
+        zubridge.options.error_policy.report(format_args!("failed to evict detached slices"), &e);
+      }
+    }
+  });
+}
+
+/// Run the state manager's compaction on a schedule.
+fn spawn_compaction<R: Runtime>(subsystems: &Subsystems, app: AppHandle<R>, config: CompactionConfig) {
+  subsystems.spawn("compaction", move |signal| loop {
+    if signal.sleep(config.interval) {
+      return;
+    }
+    if let Some(zubridge) = app.try_state::<Zubridge<R>>() {
+      if let Err(e) = zubridge.compact(&config.retention) {
+        zubridge.options.error_policy.report(format_args!("scheduled compaction failed"), &e);
+      }
+    }
+  });
+}
+
+/// Delete unreferenced blobs on a schedule.
+fn spawn_blob_gc<R: Runtime>(subsystems: &Subsystems, app: AppHandle<R>, interval: Duration) {
+  subsystems.spawn("blob-gc", move |signal| loop {
+    if signal.sleep(interval) {
+      return;
+    }
+    if let Some(zubridge) = app.try_state::<Zubridge<R>>() {
+      if let Err(e) = zubridge.collect_blobs() {
+        zubridge.options.error_policy.report(format_args!("blob garbage collection failed"), &e);
+      }
+    }
+  });
+}
+
+/// Run a sync task right away, then again after its interval or backoff.
+fn spawn_sync_task<R: Runtime>(subsystems: &Subsystems, app: AppHandle<R>, task: SyncTask) {
+  subsystems.spawn(&format!("sync-{}", task.name), move |signal| {
+    let mut delay = Duration::ZERO;
+    loop {
+      if signal.sleep(delay) {
+        return;
+      }
+      delay = match app.try_state::<Zubridge<R>>() {
+        Some(zubridge) => zubridge.run_sync_task(&task),
+        // The plugin is still being set up
+        None => Duration::from_millis(100),
+      };
+    }
+  });
+}
+
 /// Access to the zubridge APIs.
 pub struct Zubridge<R: Runtime> {
   app: AppHandle<R>,
   options: ZubridgeOptions,
+  fs_watch: FsWatch<R>,
+  config_file: Option<Arc<ConfigFile>>,
+  feature_flags: FeatureFlags,
+  experiments: Option<Assignments>,
+  install_id: String,
+  sequences: Sequences,
+  telemetry: Option<Arc<Telemetry>>,
+  action_log: ActionLog,
+  data_dir: PathBuf,
+  ephemeral: EphemeralChannel,
+  subscriptions: Subscriptions,
+  payload_signer: Option<PayloadSigner>,
+  event_names: EventNames,
+  warm_start: Option<Arc<WarmStartCache>>,
+  sensitive: Option<Arc<SensitivePaths>>,
+  session_lock: Option<SessionLock>,
+  workspaces: Option<Workspaces>,
+  blobs: Option<BlobStore>,
+  uploads: Arc<Uploads>,
+  loaded_pages: Option<LoadedPages>,
+  #[cfg(debug_assertions)]
+  dev_handoff: Option<Arc<DevHandoff>>,
+  lazy_slices: LazySlices,
+  windows: Option<WindowRegistry>,
+  dialogs: Option<Dialogs>,
+  confirmations: Option<PendingConfirmations>,
+  appearance: Option<AppearanceState>,
+  updater: Option<Updater>,
+  sync: Option<SyncStatuses>,
+  /// Menus whose items are updated from the state, keyed by menu id.
+  menus: Mutex<HashMap<MenuId, Menu<R>>>,
+  startup: Startup,
+  forms: Forms,
+  statecharts: Statecharts,
+  workflows: Arc<Workflows>,
+  timers: Option<Timers>,
+  requests: PendingRequests,
+  paginators: Paginators,
+  aggregates: Aggregates,
+  slice_router: SliceRouter,
+  plugin_slices: PluginSlices,
+  trash: Option<Trash>,
+  effect_cache: Option<Arc<EffectCache>>,
+  effect_offsets: EffectOffsets,
+  #[cfg(feature = "reqwest")]
+  http: HttpEffects,
+  #[cfg(feature = "reqwest")]
+  webhooks: Option<Webhooks>,
+  #[cfg(feature = "mqtt")]
+  mqtt: Option<MqttBridge>,
+  extensions: Extensions,
+  #[cfg(feature = "automation")]
+  automation: Option<Automation>,
+  #[cfg(feature = "secrets")]
+  secrets: Option<Secrets>,
+  platform_prefs: Option<PlatformPrefs>,
+  #[cfg(feature = "search")]
+  search: SearchIndexes,
+  shared_memory: Option<SharedMemoryTransport>,
+  /// Incremented for every emitted state update.
+  revision: AtomicU64,
+  missed_revisions: MissedRevisions,
+  /// Windows an update failed to reach, retried in the background.
+  emit_retries: Arc<EmitRetries>,
+  /// The state manager's state as of the last committed dispatch, read while a dispatch holds the lock.
+  /// Recorded when the plugin publishes a state, so a write made straight through the
+  /// `Arc<Mutex<dyn StateManager>>` isn't in it until the next publish.
+  committed: ArcSwapOption<JsonValue>,
+  /// The placeholder state while the initial state provider is loading.
+  loading: ArcSwapOption<JsonValue>,
+  /// Transactions in progress by thread. A thread's state updates are held back until its
+  /// outermost transaction ends.
+  open_transactions: Mutex<HashMap<std::thread::ThreadId, OpenTransaction>>,
+  waiters: StateWaiters,
+  metrics: Metrics,
+  subsystems: Subsystems,
 }
 
 impl<R: Runtime> Zubridge<R> {
+  /// Access the file watcher used to turn file changes into actions
+  pub fn fs_watch(&self) -> &FsWatch<R> {
+    &self.fs_watch
+  }
+
+  /// Access the cache of effect results, if `effect_cache_ttl` is set
+  pub fn effect_cache(&self) -> Option<&EffectCache> {
+    self.effect_cache.as_deref()
+  }
+
+  /// Access the persisted record of completed effects, for effects that must not run again
+  /// after a crash or restart
+  pub fn effect_offsets(&self) -> &EffectOffsets {
+    &self.effect_offsets
+  }
+
+  /// Access the HTTP effect helpers, which dispatch the results of requests
+  #[cfg(feature = "reqwest")]
+  pub fn http(&self) -> &HttpEffects {
+    &self.http
+  }
+
+  /// Search a full-text index, returning the ids of the best matching entities first
+  #[cfg(feature = "search")]
+  pub fn search(&self, index: &str, query: &str, limit: usize) -> crate::Result<Vec<SearchHit>> {
+    self.search.search(index, query, limit, self.revision(), || self.current_state())
+  }
+
+  /// Start a run of a workflow and return its id
+  pub fn start_workflow(&self, workflow: &str, input: JsonValue) -> crate::Result<String> {
+    let id = self.workflows.start(workflow, input, dispatcher(&self.app))?;
+    self.emit_current_state()?;
+    Ok(id)
+  }
+
+  /// Get the current state of a statechart
+  pub fn chart_state(&self, chart: &str) -> Option<String> {
+    self.statecharts.current(chart)
+  }
+
+  /// Get the stable id generated for this install on first launch
+  pub fn install_id(&self) -> &str {
+    &self.install_id
+  }
+
+  /// Get the next value of a persisted counter, starting at 1. Values are never handed out
+  /// twice, even across restarts, so they can be used as ids instead of a "last id" in the state
+  pub fn next_sequence(&self, name: &str) -> crate::Result<u64> {
+    self.sequences.next(name)
+  }
+
+  /// Get the last value handed out by [`Zubridge::next_sequence`], if any
+  pub fn current_sequence(&self, name: &str) -> crate::Result<Option<u64>> {
+    self.sequences.current(name)
+  }
+
+  /// Access the registry of background subsystems, to register the app's own so they're shut
+  /// down in order on exit
+  pub fn subsystems(&self) -> &Subsystems {
+    &self.subsystems
+  }
+
+  /// Stop the background subsystems, newest first, waiting at most `shutdown_timeout`.
+  /// Called automatically when the app exits
+  pub fn shutdown(&self) {
+    self.subsystems.shutdown(self.options.shutdown_timeout);
+  }
+
+  /// Get the revision of the most recently emitted state update
+  pub fn revision(&self) -> u64 {
+    self.revision.load(Ordering::SeqCst)
+  }
+
+  /// Get the dispatch and emit counters and timings collected since the app started
+  pub fn metrics(&self) -> MetricsSnapshot {
+    self.metrics.snapshot()
+  }
+
+  /// Dispatch synthetic actions as described by `profile`, blocking until the run is over, and
+  /// report throughput, latency and the plugin metrics accumulated meanwhile
+  #[cfg(feature = "stress")]
+  pub fn run_load(&self, profile: &LoadProfile) -> LoadReport {
+    let before = self.metrics();
+    crate::load::run(profile, dispatcher(&self.app), before, || self.metrics())
+  }
+
+  /// Start a soak run on a background thread: `config.script` is dispatched over and over while
+  /// memory and lock-wait metrics are sampled. Returns the path of the report, which is
+  /// rewritten after every sample. The run stops early when the app exits
+  #[cfg(feature = "stress")]
+  pub fn start_soak(&self, config: SoakConfig) -> crate::Result<PathBuf> {
+    if config.script.is_empty() {
+      return Err(crate::Error::ConfigError("the soak script has no actions".into()));
+    }
+    let report_path = soak::report_path(&self.data_dir);
+    let app = self.app.clone();
+    let path = report_path.clone();
+    self.subsystems.spawn("soak", move |signal| {
+      let zubridge = app.zubridge();
+      let stopped = || signal.sleep(Duration::ZERO);
+      match soak::run(&config, dispatcher(&app), || zubridge.metrics(), stopped, &path) {
+        Ok(report) if report.suspected_leak => log::warn!(
+          "zubridge: soak run suspects a leak, memory grew {:.0} bytes per hour: {}",
+          report.memory_growth_per_hour.unwrap_or_default(),
+          path.display()
+        ),
+        Ok(_) => log::info!("zubridge: soak run finished: {}", path.display()),
+        Err(e) => zubridge.options.error_policy.report(format_args!("soak run failed"), &e),
+      }
+    });
+    Ok(report_path)
+  }
+
+  /// Get the first revision each out-of-date window missed because emitting to it failed.
+  /// Windows are removed once a later state update reaches them.
+  pub fn missed_revisions(&self) -> BTreeMap<String, u64> {
+    self.missed_revisions.all()
+  }
+
+  /// Get the plugin's version and the protocol version it speaks
+  pub fn version(&self) -> VersionInfo {
+    VersionInfo::current()
+  }
+
+  /// Get the plugin's version, whether the state is ready, and the status of its subsystems
+  pub fn health(&self) -> Health {
+    Health {
+      version: VersionInfo::current(),
+      state_manager: self.state_manager().is_ok(),
+      loading: self.loading.load().is_some(),
+      revision: self.revision(),
+      windows_behind: self.missed_revisions.all().len(),
+      subsystems: self.subsystems.statuses(),
+    }
+  }
+
   /// Get the event name used for state updates
   pub fn get_event_name(&self) -> String {
-    self.options.event_name.clone()
+    self.event_names.state_update.clone()
+  }
+
+  /// Get the names of all the events the plugin emits
+  pub fn event_names(&self) -> &EventNames {
+    &self.event_names
   }
 
-  /// Get the initial state from the state manager
+  /// Get the initial state from the state manager, without lazy slices
   pub fn get_initial_state(&self) -> crate::Result<JsonValue> {
-    if let Some(state_manager) = self.app.try_state::<Arc<Mutex<dyn StateManager>>>() {
-      let state_guard = state_manager.inner().lock().map_err(|e| crate::Error::StateError(e.to_string()))?;
-      let initial_state = state_guard.get_initial_state();
-      Ok(initial_state)
-    } else {
-      Err(crate::Error::StateError("StateManager not found in app state".into()))
+    let mut state = self.current_state()?;
+    self.lazy_slices.split(&mut state);
+    Ok(state)
+  }
+
+  /// Get the initial state for a frontend speaking `protocol_version`: wrapped in an
+  /// [`InitialStateEnvelope`] when it sent one, or bare for frontends that predate protocol
+  /// versions. Fails with [`crate::Error::ProtocolMismatch`] when the plugin can't answer it.
+  pub fn get_initial_state_for(&self, protocol_version: Option<u32>) -> crate::Result<JsonValue> {
+    let Some(frontend) = protocol_version else {
+      health::negotiate(0)?;
+      return self.get_initial_state();
+    };
+    let envelope = InitialStateEnvelope {
+      protocol_version: health::negotiate(frontend)?,
+      state: self.get_initial_state()?,
+    };
+    serde_json::to_value(envelope).map_err(|e| crate::Error::SerializationError(e.to_string()))
+  }
+
+  /// Get the full state including lazy slices.
+  ///
+  /// Readers don't wait for an in-flight dispatch: while the state manager is locked, they get
+  /// the state as of the last committed dispatch instead.
+  pub(crate) fn current_state(&self) -> crate::Result<JsonValue> {
+    self.read_state(false)
+  }
+
+  /// Read the full state, recording it as committed if `commit` is set or nothing was committed
+  /// yet. Plain reads don't, so they don't clone the state a second time.
+  fn read_state(&self, commit: bool) -> crate::Result<JsonValue> {
+    if let Some(placeholder) = self.loading.load_full() {
+      return self.project_state(placeholder.as_ref().clone());
     }
+    let state_manager = self.state_manager()?;
+    let initial_state = match state_manager.try_lock() {
+      Ok(state_guard) if commit || self.committed.load().is_none() => self.commit(state_guard.get_initial_state()),
+      Ok(state_guard) => state_guard.get_initial_state(),
+      Err(TryLockError::WouldBlock) => match self.committed.load_full() {
+        Some(committed) => committed.as_ref().clone(),
+        None => {
+          let state_guard = state_manager.lock().map_err(|e| crate::Error::StateError(e.to_string()))?;
+          self.commit(state_guard.get_initial_state())
+        }
+      },
+      Err(TryLockError::Poisoned(e)) => return Err(crate::Error::StateError(e.to_string())),
+    };
+
+    self.project_state(initial_state)
   }
 
-  /// Dispatch an action to the state manager and emit the updated state
-  pub fn dispatch_action(&self, action: ZubridgeAction) -> crate::Result<JsonValue> {
-    // Convert the action to JSON
-    let action_json = serde_json::json!({
-      "type": action.action_type,
-      "payload": action.payload
-    });
+  /// Wait until the state, as returned by [`Zubridge::get_initial_state`], matches `predicate`
+  /// and return it. The predicate is checked right away and again whenever a state is
+  /// published. Fails with [`crate::Error::WaitTimedOut`] after `timeout`.
+  pub fn wait_for(
+    &self,
+    predicate: impl Fn(&JsonValue) -> bool,
+    timeout: Duration,
+  ) -> crate::Result<JsonValue> {
+    let deadline = Instant::now() + timeout;
+    loop {
+      // Read the count before the state, so a state published in between isn't missed
+      let seen = self.waiters.published()?;
+      let state = self.get_initial_state()?;
+      if predicate(&state) {
+        return Ok(state);
+      }
+      if !self.waiters.wait_past(seen, deadline)? {
+        return Err(crate::Error::WaitTimedOut("the state to match".into()));
+      }
+    }
+  }
 
-    // Get the state manager from app state
-    if let Some(state_manager) = self.app.try_state::<Arc<Mutex<dyn StateManager>>>() {
-      // Lock the mutex to get mutable access to the state manager
-      let mut state_guard = state_manager.inner().lock().map_err(|e| crate::Error::StateError(e.to_string()))?;
-      let updated_state = state_guard.dispatch_action(action_json);
+  /// Read a typed view of the value at a dot-separated `path` of the state manager's state,
+  /// where an empty path is the whole state. The value is deserialized straight from the last
+  /// committed state, without cloning the JSON tree or waiting for an in-flight dispatch.
+  /// Plugin-managed slices aren't part of it, nor are writes made straight through the managed
+  /// `Arc<Mutex<dyn StateManager>>` until the next dispatch.
+  pub fn with_state<T: DeserializeOwned, O>(&self, path: &str, f: impl FnOnce(&T) -> O) -> crate::Result<O> {
+    let committed = self.committed_state()?;
+    let value = get_path(&committed, path).ok_or_else(|| crate::Error::StateError(format!("no value at {}", path)))?;
+    let typed: T = serde_path_to_error::deserialize(value).map_err(|e| {
+      let at = match e.path().to_string().as_str() {
+        "." => path.to_string(),
+        inner if path.is_empty() => inner.to_string(),
+        inner => format!("{}.{}", path, inner),
+      };
+      crate::Error::SerializationError(format!("{}: {}", at, e.into_inner()))
+    })?;
+    Ok(f(&typed))
+  }
 
-      // Drop the lock before emitting events
-      drop(state_guard);
+  /// The state manager's state as of the last committed dispatch
+  fn committed_state(&self) -> crate::Result<Arc<JsonValue>> {
+    if let Some(committed) = self.committed.load_full() {
+      return Ok(committed);
+    }
+    // Nothing was committed yet, so read the state once to record it
+    let state_manager = self.state_manager()?;
+    let state_guard = state_manager.lock().map_err(|e| crate::Error::StateError(e.to_string()))?;
+    Ok(Arc::new(self.commit(state_guard.get_initial_state())))
+  }
 
-      // Emit state update event
-      self.app
-        .emit(&self.options.event_name, updated_state.clone())
-        .map_err(|err| crate::Error::EmitError(err.to_string()))?;
+  /// Record the state manager's state as the last committed one
+  fn commit(&self, state: JsonValue) -> JsonValue {
+    self.committed.store(Some(Arc::new(state.clone())));
+    state
+  }
 
-      Ok(updated_state)
-    } else {
-      Err(crate::Error::StateError("StateManager not found in app state".into()))
+  /// Load a lazy slice for a window. The window receives targeted updates for the slice
+  /// on `zubridge://slice-update` until it calls [`Zubridge::unload_slice`] or is destroyed.
+  pub fn load_slice(&self, window: &str, slice: &str) -> crate::Result<JsonValue> {
+    if !self.lazy_slices.is_lazy(slice) {
+      return Err(crate::Error::UnknownSlice(slice.to_string()));
     }
+
+    let state_manager = self.state_manager()?;
+    let mut state_guard = state_manager.lock().map_err(|e| crate::Error::StateError(e.to_string()))?;
+    catch_reducer_panic(|| state_guard.load_slice(slice))?;
+    let state = self.commit(state_guard.get_initial_state());
+    let value = state.get(slice).cloned().unwrap_or(JsonValue::Null);
+    // Subscribed under the state lock, so the eviction thread sees the window holding it
+    self.lazy_slices.subscribe(window, slice, &value)?;
+    Ok(value)
   }
 
-  /// Set the options for the plugin
-  pub fn set_options(&mut self, options: ZubridgeOptions) {
-    self.options = options;
+  /// Stop sending updates for a lazy slice to a window
+  pub fn unload_slice(&self, window: &str, slice: &str) -> crate::Result<()> {
+    self.lazy_slices.unsubscribe(window, slice)
   }
 
-  /// Register a state manager
-  pub fn register_state_manager<S: StateManager>(&self, state_manager: S) -> crate::Result<()> {
-    let state_arc: Arc<Mutex<dyn StateManager>> = Arc::new(Mutex::new(state_manager));
-    self.app.manage(state_arc);
+  /// Get the windows holding each loaded lazy slice
+  pub fn slice_windows(&self) -> crate::Result<BTreeMap<String, Vec<String>>> {
+    self.lazy_slices.windows()
+  }
+
+  /// Send state updates to `window` through `channel` instead of the global event, and return
+  /// the current state to start from
+  pub fn subscribe_state(&self, window: &str, channel: Channel<StateUpdate>) -> crate::Result<JsonValue> {
+    self.subscriptions.subscribe(window, channel)?;
+    self.get_initial_state()
+  }
+
+  /// Go back to receiving state updates through the global event
+  pub fn unsubscribe_state(&self, window: &str) -> crate::Result<()> {
+    self.subscriptions.unsubscribe(window)
+  }
+
+  /// Mark a state update as handled by `window`, making room for the next one
+  pub fn acknowledge_state(&self, window: &str) -> crate::Result<()> {
+    self.subscriptions.acknowledge(window)
+  }
+
+  /// Hand `window` the key state updates are signed with, once per page load
+  pub fn handshake(&self, window: &str) -> crate::Result<Handshake> {
+    match &self.payload_signer {
+      Some(signer) => signer.handshake(window),
+      None => Err(crate::Error::ConfigError("payload integrity is not enabled".into())),
+    }
+  }
+
+  pub(crate) fn page_loading(&self, window: &str) {
+    if let Some(signer) = &self.payload_signer {
+      signer.reset(window);
+    }
+  }
+
+  /// Re-send the state to a window whose page was reloaded, after dispatching the
+  /// `reload_action` if there's one. First loads are left to the page's own initial state fetch
+  pub(crate) fn page_loaded(&self, window: &str) -> crate::Result<()> {
+    let (Some(config), Some(pages)) = (&self.options.dev_reload, &self.loaded_pages) else { return Ok(()) };
+    if !pages.loaded(window) {
+      return Ok(());
+    }
+    log::debug!("zubridge: {} reloaded, re-sending the state", window);
+    // While the initial state loads, the placeholder is all there is to send
+    if let (Some(action_type), false) = (&config.reload_action, self.loading.load().is_some()) {
+      self.dispatch_action(ZubridgeAction {
+        action_type: action_type.clone(),
+        payload: Some(serde_json::json!({ "window": window })),
+      })?;
+    }
+    self.emit_state_to(window)
+  }
+
+  /// Store `data` in the blob store, returning a reference to put in the state instead of the
+  /// data itself. Storing the same data again returns the same reference.
+  pub fn put_blob(&self, data: &[u8], content_type: &str) -> crate::Result<BlobRef> {
+    self.blob_store()?.put(data, content_type)
+  }
+
+  /// Get the file holding a blob, from its URL or id
+  pub fn blob_path(&self, reference: &str) -> crate::Result<PathBuf> {
+    self.blob_store()?.path(reference)
+  }
+
+  /// Delete the blobs no longer referred to by the state, the inactive workspaces' states or the
+  /// saved snapshots, returning how many were deleted. Runs every `gc_interval` on its own.
+  pub fn collect_blobs(&self) -> crate::Result<usize> {
+    let blobs = self.blob_store()?;
+    // The placeholder refers to nothing, which would collect every blob
+    if self.loading.load().is_some() {
+      return Ok(0);
+    }
+    let mut states = Vec::new();
+    let snapshots = Snapshots::new(&self.data_dir);
+    for id in snapshots.list()? {
+      states.push(self.decrypt_sensitive(snapshots.load(&id)?)?);
+    }
+    // Read together under the lock, since a workspace switch moves the state from one to the other
+    let state_manager = self.state_manager()?;
+    let state_guard = state_manager.lock().map_err(|e| crate::Error::StateError(e.to_string()))?;
+    let state = state_guard.get_initial_state();
+    if let Some(workspaces) = &self.workspaces {
+      states.extend(workspaces.inactive_states()?);
+    }
+    drop(state_guard);
+    states.push(self.project_state(state)?);
+    let deleted = blobs.collect(&states)?;
+    if deleted > 0 {
+      log::debug!("zubridge: deleted {} unreferenced blobs", deleted);
+    }
+    Ok(deleted)
+  }
+
+  fn blob_store(&self) -> crate::Result<&BlobStore> {
+    self.blobs.as_ref().ok_or_else(|| crate::Error::ConfigError("the blob store is not enabled".into()))
+  }
+
+  pub(crate) fn forget_window(&self, window: &str) -> crate::Result<()> {
+    self.missed_revisions.forget(window);
+    self.subscriptions.unsubscribe(window)?;
+    self.uploads.discard(window);
+    self.page_loading(window);
+    if let Some(pages) = &self.loaded_pages {
+      pages.forget(window);
+    }
+    self.lazy_slices.forget_window(window)
+  }
+
+  /// Ask the state manager to compact its journals and history now
+  pub fn compact(&self, retention: &Retention) -> crate::Result<()> {
+    let state_manager = self.state_manager()?;
+    let mut state_guard = state_manager.lock().map_err(|e| crate::Error::StateError(e.to_string()))?;
+    catch_reducer_panic(|| state_guard.compact(retention))
+  }
+
+  pub(crate) fn window_opened(&self, window: &tauri::Window<R>) -> crate::Result<()> {
+    let (Some(windows), Some(config)) = (&self.windows, &self.options.window_actions) else { return Ok(()) };
+    if config.starts_hidden(window.label()) {
+      window.hide()?;
+    }
+    windows.opened(window.label(), window.title().ok(), window.is_visible()?)?;
+    self.emit_current_state()?;
+    Ok(())
+  }
+
+  /// Hide a window instead of closing it if it's configured to close to the tray.
+  /// Returns whether the close should be prevented.
+  pub(crate) fn close_to_tray(&self, label: &str) -> crate::Result<bool> {
+    let (Some(windows), Some(config)) = (&self.windows, &self.options.window_actions) else { return Ok(false) };
+    let Some(window) = self.app.get_webview_window(label).filter(|_| config.closes_to_tray(label)) else {
+      return Ok(false);
+    };
+
+    window.hide()?;
+    if windows.visible(label, false)? {
+      self.emit_current_state()?;
+    }
+    Ok(true)
+  }
+
+  pub(crate) fn window_closed(&self, label: &str) -> crate::Result<()> {
+    let Some(windows) = &self.windows else { return Ok(()) };
+    if windows.closed(label)? {
+      self.emit_current_state()?;
+    }
+    Ok(())
+  }
+
+  pub(crate) fn window_focused(&self, label: &str, focused: bool) -> crate::Result<()> {
+    let Some(windows) = &self.windows else { return Ok(()) };
+    if windows.focused(label, focused)? {
+      self.emit_current_state()?;
+    }
+    Ok(())
+  }
+
+  /// Record a window's theme in the appearance slice
+  pub(crate) fn theme_changed(&self, theme: tauri::Theme) -> crate::Result<()> {
+    self.update_appearance(Appearance {
+      theme: appearance::theme_name(theme),
+      ..Default::default()
+    })
+  }
+
+  /// Merge appearance settings into the `system.appearance` slice, dispatching the configured
+  /// change action if anything changed. Fields left `None` keep their current value
+  pub fn update_appearance(&self, update: Appearance) -> crate::Result<()> {
+    let (Some(config), Some(state)) = (&self.options.appearance, &self.appearance) else { return Ok(()) };
+    if let Some(appearance) = state.update(update)? {
+      let payload = serde_json::to_value(appearance).map_err(|e| crate::Error::SerializationError(e.to_string()))?;
+      self.dispatch_action(ZubridgeAction {
+        action_type: config.change_action.clone(),
+        payload: Some(payload),
+      })?;
+    }
+    Ok(())
+  }
+
+  /// Read the platform preferences again and emit the state if they changed
+  fn refresh_platform_prefs(&self) -> crate::Result<()> {
+    let Some(prefs) = &self.platform_prefs else { return Ok(()) };
+    if prefs.refresh()? {
+      self.emit_current_state()?;
+    }
+    Ok(())
+  }
+
+  /// Dispatch `FILE:DROPPED { paths, window, position }` for a drop on an opted-in window
+  pub(crate) fn file_dropped(&self, label: &str, paths: &[PathBuf], position: (f64, f64)) -> crate::Result<()> {
+    if !self.options.file_drop_windows.iter().any(|pattern| glob_matches(pattern, label)) {
+      return Ok(());
+    }
+
+    self.dispatch_action(ZubridgeAction {
+      action_type: "FILE:DROPPED".to_string(),
+      payload: Some(serde_json::json!({
+        "paths": paths,
+        "window": label,
+        "position": { "x": position.0, "y": position.1 },
+      })),
+    })?;
+    Ok(())
+  }
+
+  /// Report hydration progress to the splash window on `zubridge://startup`, e.g. while
+  /// loading persisted state
+  pub fn report_startup(&self, stage: &str, progress: f64) -> crate::Result<()> {
+    let progress = self.startup.report(stage, progress)?;
+    self.app
+      .emit(&self.event_names.startup, progress)
+      .map_err(|err| crate::Error::EmitError(err.to_string()))
+  }
+
+  /// Publish a message that doesn't belong in the store, like a toast or focus request, to every
+  /// window subscribed to `topic`
+  pub fn publish(&self, topic: &str, payload: JsonValue) -> crate::Result<()> {
+    self.publish_from(None, topic, payload, "*")
+  }
+
+  /// Publish a message to the windows whose labels match `windows`, where `*` matches any run
+  /// of characters
+  pub fn publish_to(&self, windows: &str, topic: &str, payload: JsonValue) -> crate::Result<()> {
+    self.publish_from(None, topic, payload, windows)
+  }
+
+  /// Publish a message on behalf of a window, rejecting it if the window is read-only
+  pub(crate) fn publish_from(&self, from: Option<&str>, topic: &str, payload: JsonValue, windows: &str) -> crate::Result<()> {
+    if let Some(from) = from {
+      self.ensure_writable(from)?;
+    }
+
+    let message = serde_json::json!({ "topic": topic, "payload": payload, "from": from });
+    for label in self.app.webview_windows().keys() {
+      if glob_matches(windows, label) {
+        self.app
+          .emit_to(label.as_str(), &self.event_names.message, message.clone())
+          .map_err(|e| crate::Error::EmitError(e.to_string()))?;
+      }
+    }
+    Ok(())
+  }
+
+  /// Ask a window a question on `topic` and wait for its answer. The window answers with a
+  /// handler registered through `onRequest` in the frontend API. Fails with
+  /// [`crate::Error::RequestTimedOut`] after `request_timeout`.
+  pub async fn request(&self, window: &str, topic: &str, payload: JsonValue) -> crate::Result<JsonValue> {
+    self.request_from(None, window, topic, payload).await
+  }
+
+  /// Send a request on behalf of a window, or of Rust code if `from` is None
+  pub(crate) async fn request_from(
+    &self,
+    from: Option<&str>,
+    window: &str,
+    topic: &str,
+    payload: JsonValue,
+  ) -> crate::Result<JsonValue> {
+    self.ensure_window(window)?;
+
+    let (id, mut response) = self.requests.register(self.options.id_generator.next_id(), window)?;
+    let request = RpcRequest {
+      id: id.clone(),
+      topic: topic.to_string(),
+      payload,
+      from: from.map(str::to_string),
+    };
+    if let Err(e) = self.app.emit_to(window, &self.event_names.request, request) {
+      self.requests.forget(&id);
+      return Err(crate::Error::EmitError(e.to_string()));
+    }
+
+    match tokio::time::timeout(self.options.request_timeout, response.recv()).await {
+      Ok(Some(Ok(result))) => Ok(result),
+      Ok(Some(Err(error))) => Err(crate::Error::RequestFailed(error)),
+      Ok(None) | Err(_) => {
+        self.requests.forget(&id);
+        Err(crate::Error::RequestTimedOut {
+          window: window.to_string(),
+          topic: topic.to_string(),
+        })
+      }
+    }
+  }
+
+  /// Deliver `window`'s answer to a request. Late answers are ignored, and answers to requests
+  /// sent to other windows rejected.
+  pub(crate) fn respond(&self, id: &str, window: &str, response: Result<JsonValue, String>) -> crate::Result<()> {
+    if !self.requests.respond(id, window, response)? {
+      log::debug!("zubridge: dropping the response to request {}, which timed out", id);
+    }
     Ok(())
   }
+
+  /// Get the latest startup progress
+  pub fn startup_progress(&self) -> crate::Result<StartupProgress> {
+    self.startup.get()
+  }
+
+  /// Mark startup as complete once the initial state is ready: emits the final progress and the
+  /// current state, closes the splash window and shows the main window.
+  pub fn startup_complete(&self) -> crate::Result<()> {
+    // Fails if no state manager is registered yet
+    self.get_initial_state()?;
+
+    let progress = self.startup.complete()?;
+    self.app
+      .emit(&self.event_names.startup, progress)
+      .map_err(|err| crate::Error::EmitError(err.to_string()))?;
+
+    let config = &self.options.startup;
+    if let Some(main) = self.app.get_webview_window(&config.main_window) {
+      main.show()?;
+      main.set_focus()?;
+      if let Some(windows) = &self.windows {
+        windows.visible(&config.main_window, true)?;
+      }
+    }
+    if let Some(splash) = self.app.get_webview_window(&config.splash_window) {
+      splash.close()?;
+    }
+
+    self.emit_current_state()?;
+    Ok(())
+  }
+
+  /// Keep a tray or window menu in sync with the state. Binding a menu with the same id
+  /// again replaces the previous one, so menus can be rebuilt freely.
+  pub fn bind_menu(&self, menu: Menu<R>) -> crate::Result<()> {
+    let state = self.get_initial_state()?;
+    if let Some(tray_actions) = &self.options.tray_actions {
+      tray_actions.update_menu(&menu, &state)?;
+    }
+    let mut menus = self.menus.lock().map_err(|e| crate::Error::StateError(e.to_string()))?;
+    menus.insert(menu.id().clone(), menu);
+    Ok(())
+  }
+
+  fn menu_clicked(&self, item_id: &str) {
+    let Some(action) = self.options.tray_actions.as_ref().and_then(|map| map.get(item_id)) else {
+      return;
+    };
+    if let Err(e) = self.dispatch_action(action.clone()) {
+      log::warn!("zubridge: failed to dispatch {} for menu item {}: {}", action.action_type, item_id, e);
+      // Check items toggle themselves when clicked; put them back in line with the state
+      if let Err(e) = self.get_initial_state().and_then(|state| self.update_menus(&state)) {
+        log::warn!("zubridge: failed to update menus: {}", e);
+      }
+    }
+  }
+
+  fn update_menus(&self, state: &JsonValue) -> crate::Result<()> {
+    let Some(tray_actions) = &self.options.tray_actions else { return Ok(()) };
+    let menus = self.menus.lock().map_err(|e| crate::Error::StateError(e.to_string()))?;
+    for menu in menus.values() {
+      tray_actions.update_menu(menu, state)?;
+    }
+    Ok(())
+  }
+
+  fn evict_detached_slices(&self, delay: Duration) -> crate::Result<()> {
+    let expired = self.lazy_slices.take_expired(delay)?;
+    if expired.is_empty() {
+      return Ok(());
+    }
+
+    let state_manager = self.state_manager()?;
+    let mut state_guard = state_manager.lock().map_err(|e| crate::Error::StateError(e.to_string()))?;
+    for slice in &expired {
+      // A window may have loaded it again since it expired, under the same lock
+      if self.lazy_slices.is_held(slice)? {
+        continue;
+      }
+      log::debug!("zubridge: evicting detached slice {}", slice);
+      catch_reducer_panic(|| state_guard.evict_slice(slice))?;
+    }
+    Ok(())
+  }
+
+  /// Run an action against a copy of the current state and return the state it would produce,
+  /// without committing or emitting anything. Aliases are applied, but interceptors and
+  /// plugin-handled actions aren't, since they may have side effects. Requires a state manager
+  /// that implements [`StateManager::simulate_action`].
+  pub fn simulate(&self, mut action: ZubridgeAction) -> crate::Result<Simulation> {
+    if let Some(alias) = self.options.action_aliases.get(&action.action_type) {
+      action.action_type = alias.clone();
+    }
+
+    let action_json = self.action_json(&action);
+    let state_manager = self.state_manager()?;
+    let state_guard = state_manager.lock().map_err(|e| crate::Error::StateError(e.to_string()))?;
+    state_guard.validate_action(&action_json)?;
+    let current = state_guard.get_initial_state();
+    let simulated = catch_reducer_panic(|| state_guard.simulate_action(action_json))?;
+    drop(state_guard);
+
+    let simulated = simulated.ok_or(crate::Error::SimulationUnsupported)?;
+    let current = self.project_state(current)?;
+    let state = self.project_state(simulated)?;
+    let diff = snapshots::diff(&current, &state);
+    Ok(Simulation { state, diff })
+  }
+
+  /// Run `f`, emitting a single state update when it returns instead of one per dispatch.
+  ///
+  /// Until the outermost transaction ends, state updates from the calling thread are held back
+  /// and only the final state is emitted, even if `f` fails or panics. Other threads keep
+  /// publishing, so a dispatch from one of them publishes the transaction's changes so far, and
+  /// dispatches `f` hands to other threads aren't batched. Don't wait on [`Zubridge::wait_for`]
+  /// inside a transaction, since the thread's own updates aren't published before it ends.
+  pub fn transaction<T>(&self, f: impl FnOnce(&Self) -> T) -> T {
+    self.open_transactions().entry(std::thread::current().id()).or_default().depth += 1;
+    let _transaction = Transaction { zubridge: self };
+    f(self)
+  }
+
+  fn open_transactions(&self) -> MutexGuard<'_, HashMap<std::thread::ThreadId, OpenTransaction>> {
+    // Only ever held for a map update, which can't leave it inconsistent
+    self.open_transactions.lock().unwrap_or_else(|poisoned| poisoned.into_inner())
+  }
+
+  /// Dispatch an action to the state manager and emit the updated state
+  pub fn dispatch_action(&self, action: ZubridgeAction) -> crate::Result<JsonValue> {
+    match self.admit(action)? {
+      Admitted::Dispatch(action) => self.dispatch_confirmed(action),
+      Admitted::Handled(state) => Ok(state),
+    }
+  }
+
+  /// Resolve an action's alias, handle the session lock action, and check the action is allowed
+  /// and doesn't need confirmation
+  fn admit(&self, mut action: ZubridgeAction) -> crate::Result<Admitted> {
+    if let Some(alias) = self.options.action_aliases.get(&action.action_type) {
+      action.action_type = alias.clone();
+    }
+
+    if let Some(session_lock) = &self.session_lock {
+      if action.action_type == session::LOCK_ACTION {
+        return self.set_session_locked(session_lock.lock()).map(Admitted::Handled);
+      }
+    }
+    self.ensure_allowed(&action)?;
+
+    if let (Some(config), Some(confirmations)) = (&self.options.confirmations, &self.confirmations) {
+      if let Some(message) = config.message_for(&action.action_type) {
+        return Err(self.hold_for_confirmation(config, confirmations, action, message.to_string()));
+      }
+    }
+    Ok(Admitted::Dispatch(action))
+  }
+
+  /// Dispatch a typed action, such as a variant of the app's action enum, instead of one built
+  /// from strings. See [`ZubridgeAction::from_typed`] for the shape it must serialize to.
+  pub fn dispatch_typed<A: Serialize>(&self, action: A) -> crate::Result<JsonValue> {
+    self.dispatch_action(ZubridgeAction::from_typed(&action)?)
+  }
+
+  /// Run a pending action that requires confirmation. Fails with
+  /// [`crate::Error::UnknownConfirmation`] if the token expired or was already used.
+  pub fn confirm(&self, token: &str) -> crate::Result<JsonValue> {
+    let action = self.take_confirmation(token)?;
+    self.emit_current_state()?;
+    // The session may have been locked, or the user signed out, while the prompt was shown
+    self.ensure_allowed(&action)?;
+    self.dispatch_confirmed(action)
+  }
+
+  /// Drop a pending action that requires confirmation
+  pub fn cancel_confirmation(&self, token: &str) -> crate::Result<()> {
+    self.take_confirmation(token)?;
+    self.emit_current_state()?;
+    Ok(())
+  }
+
+  fn take_confirmation(&self, token: &str) -> crate::Result<ZubridgeAction> {
+    self
+      .confirmations
+      .as_ref()
+      .map(|confirmations| confirmations.take(token))
+      .transpose()?
+      .flatten()
+      .ok_or_else(|| crate::Error::UnknownConfirmation(token.to_string()))
+  }
+
+  /// Hold an action until it's confirmed, prompting natively if configured. Returns the
+  /// [`crate::Error::ConfirmationRequired`] error the dispatch fails with.
+  fn hold_for_confirmation(
+    &self,
+    config: &ConfirmationsConfig,
+    confirmations: &PendingConfirmations,
+    action: ZubridgeAction,
+    message: String,
+  ) -> crate::Error {
+    let action_type = action.action_type.clone();
+    let token = match confirmations.hold(action.clone(), &message, config.ttl) {
+      Ok(token) => token,
+      Err(e) => return e,
+    };
+    if let Err(e) = self.emit_current_state() {
+      self.options.error_policy.report(format_args!("failed to emit pending confirmation"), &e);
+    }
+
+    if let Some(prompt) = config.prompt.clone() {
+      let app = self.app.clone();
+      let token = token.clone();
+      std::thread::spawn(move || {
+        let result = if prompt.confirm(&action, &message) {
+          app.zubridge().confirm(&token).map(|_| ())
+        } else {
+          app.zubridge().cancel_confirmation(&token)
+        };
+        if let Err(e) = result {
+          let policy = app.zubridge().options.error_policy;
+          policy.report(format_args!("failed to resolve confirmation for {}", action.action_type), &e);
+        }
+      });
+    }
+    crate::Error::ConfirmationRequired { action_type, token }
+  }
+
+  /// Run an admitted action through the interceptors. The actions they rewrite it into are
+  /// checked like the action itself, so an allowed action can't be turned into one that isn't.
+  /// One that needs confirmation fails the dispatch, since it can't be held on its own
+  fn intercept(&self, action: ZubridgeAction) -> crate::Result<Vec<ZubridgeAction>> {
+    let admitted = action.action_type.clone();
+    let actions = run_interceptors(&self.options.interceptors, action, self.options.error_policy)?;
+    for action in actions.iter().filter(|action| action.action_type != admitted) {
+      self.ensure_allowed(action)?;
+      if self.options.confirmations.as_ref().and_then(|config| config.message_for(&action.action_type)).is_some() {
+        return Err(crate::Error::ActionRejected(format!(
+          "interceptors turned {} into {}, which requires confirmation",
+          admitted, action.action_type
+        )));
+      }
+    }
+    Ok(actions)
+  }
+
+  /// Dispatch an action that doesn't need, or already got, confirmation
+  fn dispatch_confirmed(&self, action: ZubridgeAction) -> crate::Result<JsonValue> {
+    let (actions, slices_changed) = self.route_action(action)?;
+    if actions.is_empty() && slices_changed {
+      return self.emit_current_state();
+    }
+    self.apply_actions(actions)
+  }
+
+  /// Run an action through the interceptors and apply the actions the plugin handles itself.
+  /// Returns the actions left for the state manager, and whether a plugin-managed slice changed
+  fn route_action(&self, action: ZubridgeAction) -> crate::Result<(Vec<ZubridgeAction>, bool)> {
+    self.route_intercepted(self.intercept(action)?)
+  }
+
+  /// Whether the plugin handles an action itself, with effects restoring the state manager's
+  /// state can't undo, such as opening a window or writing to the keychain. Mirrors the
+  /// routing in [`Zubridge::route_intercepted`], apart from path actions, which change the
+  /// state manager's state
+  fn handled_by_plugin(&self, action: &ZubridgeAction) -> bool {
+    let action_type = action.action_type.as_str();
+    #[cfg(feature = "secrets")]
+    if self.secrets.as_ref().is_some_and(|secrets| secrets.handles(action)) {
+      return true;
+    }
+    #[cfg(feature = "automation")]
+    if self.automation.is_some() && automation::is_automation_action(action_type) {
+      return true;
+    }
+    (self.session_lock.is_some() && action_type == session::LOCK_ACTION)
+      || (self.options.window_actions.is_some() && self.windows.is_some() && window_actions::is_window_action(action_type))
+      || (self.options.dialogs.is_some() && self.dialogs.is_some() && dialogs::is_dialog_action(action_type))
+      || (self.updater.is_some() && updater::is_updater_action(action_type))
+      || (!self.forms.is_empty() && forms::is_form_action(action_type))
+      || (!self.statecharts.is_empty() && action_type == statechart::SEND_ACTION)
+      || (!self.workflows.is_empty() && action_type == workflows::START_ACTION)
+      || (self.timers.is_some() && timers::is_timer_action(action_type))
+      || (!self.paginators.is_empty() && paginator::is_page_action(action_type))
+      || (self.trash.is_some() && trash::is_trash_action(action_type))
+      || (self.workspaces.is_some() && action_type == workspaces::SWITCH_ACTION)
+      || self.platform_prefs.as_ref().is_some_and(|prefs| prefs.handles(action))
+      || self.slice_router.is_routed(action_type)
+      || self.plugin_slices.owns_action(action_type)
+  }
+
+  /// Apply the plugin-handled actions among those the interceptors returned. Returns the
+  /// actions left for the state manager, and whether a plugin-managed slice changed
+  fn route_intercepted(&self, mut actions: Vec<ZubridgeAction>) -> crate::Result<(Vec<ZubridgeAction>, bool)> {
+    let mut slices_changed = false;
+    if let (Some(config), Some(windows)) = (&self.options.window_actions, &self.windows) {
+      let (window_actions, rest): (Vec<_>, Vec<_>) =
+        actions.into_iter().partition(|action| window_actions::is_window_action(&action.action_type));
+      for action in &window_actions {
+        slices_changed |= window_actions::apply(&self.app, config, windows, action)?;
+      }
+      actions = rest;
+    }
+
+    if let (Some(config), Some(dialogs)) = (&self.options.dialogs, &self.dialogs) {
+      let dialogs_changed;
+      (actions, dialogs_changed) = self.handle_dialog_actions(config, dialogs, actions)?;
+      slices_changed |= dialogs_changed;
+    }
+
+    if let Some(updater) = &self.updater {
+      let (updater_actions, rest): (Vec<_>, Vec<_>) =
+        actions.into_iter().partition(|action| updater::is_updater_action(&action.action_type));
+      for action in &updater_actions {
+        let app = self.app.clone();
+        updater.start(&action.action_type, Arc::new(move || {
+          let zubridge = app.zubridge();
+          if let Err(e) = zubridge.emit_current_state() {
+            zubridge.options.error_policy.report(format_args!("failed to emit updater state"), &e);
+          }
+        }))?;
+        slices_changed = true;
+      }
+      actions = rest;
+    }
+
+    if !self.forms.is_empty() {
+      let (form_actions, rest): (Vec<_>, Vec<_>) =
+        actions.into_iter().partition(|action| forms::is_form_action(&action.action_type));
+      for action in &form_actions {
+        if let Some((form, values)) = self.forms.apply(action)? {
+          self.submit_form(form, values);
+        }
+        slices_changed = true;
+      }
+      actions = rest;
+    }
+
+    if !self.statecharts.is_empty() {
+      let mut rest = Vec::with_capacity(actions.len());
+      for action in actions {
+        if action.action_type == statechart::SEND_ACTION {
+          rest.extend(self.statecharts.send(&action, || self.current_state())?);
+          slices_changed = true;
+        } else {
+          rest.push(action);
+        }
+      }
+      actions = rest;
+    }
+
+    if !self.workflows.is_empty() {
+      let (starts, rest): (Vec<_>, Vec<_>) =
+        actions.into_iter().partition(|action| action.action_type == workflows::START_ACTION);
+      for action in &starts {
+        let (workflow, input) = workflows::start_request(action)?;
+        self.workflows.start(&workflow, input, dispatcher(&self.app))?;
+        slices_changed = true;
+      }
+      actions = rest;
+    }
+
+    if let Some(timers) = &self.timers {
+      let (timer_actions, rest): (Vec<_>, Vec<_>) =
+        actions.into_iter().partition(|action| timers::is_timer_action(&action.action_type));
+      for action in &timer_actions {
+        timers.apply(action)?;
+        slices_changed = true;
+      }
+      actions = rest;
+    }
+
+    if !self.paginators.is_empty() {
+      let (page_actions, rest): (Vec<_>, Vec<_>) =
+        actions.into_iter().partition(|action| paginator::is_page_action(&action.action_type));
+      for action in &page_actions {
+        if let Some(request) = self.paginators.apply(action)? {
+          self.fetch_page(request, action.action_type == paginator::REFRESH_ACTION);
+        }
+        slices_changed = true;
+      }
+      actions = rest;
+    }
+
+    if let Some(trash) = &self.trash {
+      let mut rest = Vec::with_capacity(actions.len());
+      for action in actions {
+        if trash::is_trash_action(&action.action_type) {
+          rest.extend(trash.apply(&action)?);
+          slices_changed = true;
+          continue;
+        }
+        if trash.is_delete_action(&action.action_type) {
+          // Copy the entities before the reducer removes them
+          trash.observe_delete(&action, &self.current_state()?)?;
+        }
+        rest.push(action);
+      }
+      actions = rest;
+    }
+
+    if self.workspaces.is_some() {
+      let (switches, rest): (Vec<_>, Vec<_>) =
+        actions.into_iter().partition(|action| action.action_type == workspaces::SWITCH_ACTION);
+      for action in &switches {
+        self.swap_workspace(&Workspaces::target(action)?)?;
+        slices_changed = true;
+      }
+      actions = rest;
+    }
+
+    #[cfg(feature = "secrets")]
+    if let Some(secrets) = &self.secrets {
+      // Before path actions, so values written under the slice never reach the state manager
+      let (secret_actions, rest): (Vec<_>, Vec<_>) = actions.into_iter().partition(|action| secrets.handles(action));
+      for action in &secret_actions {
+        secrets.apply(action)?;
+        slices_changed = true;
+      }
+      actions = rest;
+    }
+
+    if let Some(prefs) = &self.platform_prefs {
+      // Before path actions, so values written under the slice never reach the state manager
+      let (prefs_actions, rest): (Vec<_>, Vec<_>) = actions.into_iter().partition(|action| prefs.handles(action));
+      for action in &prefs_actions {
+        prefs.apply(action)?;
+        slices_changed = true;
+      }
+      actions = rest;
+    }
+
+    #[cfg(feature = "automation")]
+    if let Some(automation) = &self.automation {
+      let (rule_actions, rest): (Vec<_>, Vec<_>) =
+        actions.into_iter().partition(|action| automation::is_automation_action(&action.action_type));
+      for action in &rule_actions {
+        automation.apply(action)?;
+        slices_changed = true;
+      }
+      actions = rest;
+    }
+
+    if !self.slice_router.is_empty() {
+      let (routed, rest): (Vec<_>, Vec<_>) =
+        actions.into_iter().partition(|action| self.slice_router.is_routed(&action.action_type));
+      for action in &routed {
+        self.slice_router.apply(action)?;
+        self.action_log.record(&action.action_type, action.payload.as_ref());
+        slices_changed = true;
+      }
+      actions = rest;
+    }
+
+    let (plugin_actions, rest): (Vec<_>, Vec<_>) =
+      actions.into_iter().partition(|action| self.plugin_slices.owns_action(&action.action_type));
+    for action in &plugin_actions {
+      self.plugin_slices.apply(action)?;
+      self.action_log.record(&action.action_type, action.payload.as_ref());
+      slices_changed = true;
+    }
+    actions = rest;
+
+    if !self.options.mutable_paths.is_empty() {
+      let (path_actions, rest): (Vec<_>, Vec<_>) =
+        actions.into_iter().partition(|action| path_actions::is_path_action(&action.action_type));
+      if !path_actions.is_empty() {
+        // Applied before the remaining actions, like the other plugin-handled actions
+        self.apply_path_actions(&path_actions)?;
+        slices_changed = true;
+      }
+      actions = rest;
+    }
+    Ok((actions, slices_changed))
+  }
+
+  /// Change the state manager's state at allowlisted paths and restore it as the new state
+  fn apply_path_actions(&self, actions: &[ZubridgeAction]) -> crate::Result<()> {
+    self.ensure_loaded()?;
+    let state_manager = self.state_manager()?;
+    let mut state_guard = state_manager.lock().map_err(|e| crate::Error::StateError(e.to_string()))?;
+    let mut state = state_guard.get_initial_state();
+    for action in actions {
+      path_actions::apply(&mut state, action, &self.options.mutable_paths)?;
+    }
+    if !catch_reducer_panic(|| state_guard.restore_state(state.clone()))? {
+      return Err(crate::Error::RollbackUnsupported);
+    }
+    self.commit(state);
+    drop(state_guard);
+
+    for action in actions {
+      self.action_log.record(&action.action_type, action.payload.as_ref());
+    }
+    Ok(())
+  }
+
+  /// Update the dialogs slice for dialog actions, replacing `DIALOG:CLOSE` with the
+  /// `DIALOG:RESULT` passed on to the state manager. Returns the remaining actions and
+  /// whether the slice changed.
+  fn handle_dialog_actions(
+    &self,
+    config: &DialogsConfig,
+    dialogs: &Dialogs,
+    actions: Vec<ZubridgeAction>,
+  ) -> crate::Result<(Vec<ZubridgeAction>, bool)> {
+    let mut changed = false;
+    let mut rest = Vec::with_capacity(actions.len());
+    for action in actions {
+      if !dialogs::is_dialog_action(&action.action_type) {
+        rest.push(action);
+        continue;
+      }
+
+      let id = dialogs::dialog_id(&action)?;
+      let field = |name: &str| action.payload.as_ref().and_then(|payload| payload.get(name)).cloned();
+      if action.action_type == dialogs::OPEN_ACTION {
+        let props = field("props").unwrap_or(JsonValue::Null);
+        // Open before presenting, so a presenter that responds right away closes it
+        dialogs.open(&id, props.clone(), config.presenter.is_some())?;
+        if let Some(presenter) = &config.presenter {
+          let app = self.app.clone();
+          let dialog = id.clone();
+          let responder = DialogResponder::new(move |result| {
+            let close = ZubridgeAction {
+              action_type: dialogs::CLOSE_ACTION.to_string(),
+              payload: Some(serde_json::json!({ "id": dialog, "result": result })),
+            };
+            let zubridge = app.zubridge();
+            if let Err(e) = zubridge.dispatch_action(close) {
+              zubridge.options.error_policy.report(format_args!("failed to close dialog {}", dialog), &e);
+            }
+          });
+          if !presenter.present(&id, &props, responder) {
+            dialogs.open(&id, props, false)?;
+          }
+        }
+        changed = true;
+      } else {
+        changed |= dialogs.close(&id)?;
+        rest.push(dialogs::result_action(&id, field("result").unwrap_or(JsonValue::Null)));
+      }
+    }
+    Ok((rest, changed))
+  }
+
+  /// Validate a form on a background thread and dispatch its submit action if it's valid
+  fn submit_form(&self, form: Form, values: JsonValue) {
+    let app = self.app.clone();
+    std::thread::spawn(move || {
+      let zubridge = app.zubridge();
+      let validated = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| form.validate_all(&values)));
+      let (errors, submit_error) = match validated {
+        Ok(errors) if errors.is_empty() => {
+          let submitted = zubridge.dispatch_action(form.submitted(values));
+          (errors, submitted.err().map(|e| e.to_string()))
+        }
+        Ok(errors) => (errors, None),
+        Err(_) => (BTreeMap::new(), Some(format!("validating form {} panicked", form.name))),
+      };
+      if let Err(e) = zubridge.forms.submitted(&form.name, errors, submit_error) {
+        zubridge.options.error_policy.report(format_args!("failed to update form {}", form.name), &e);
+      }
+      if let Err(e) = zubridge.emit_current_state() {
+        zubridge.options.error_policy.report(format_args!("failed to emit form state"), &e);
+      }
+    });
+  }
+
+  fn tick_timers(&self) -> crate::Result<()> {
+    let Some(timers) = &self.timers else { return Ok(()) };
+    let (done, running) = timers.tick()?;
+    if done.is_empty() {
+      if running {
+        self.emit_current_state()?;
+      }
+      return Ok(());
+    }
+    // Dispatching the completion actions emits the state with the finished timers removed
+    for action in done {
+      self.dispatch_action(action)?;
+    }
+    Ok(())
+  }
+
+  /// Fetch a page on a background thread and store it once it's loaded
+  fn fetch_page(&self, request: PageRequest, refresh: bool) {
+    let Some(paginator) = self.paginators.get(&request.paginator).cloned() else { return };
+    let app = self.app.clone();
+    std::thread::spawn(move || {
+      let zubridge = app.zubridge();
+      let result = paginator::fetch_page(&paginator, &request, zubridge.effect_cache(), refresh);
+      if let Err(e) = &result {
+        log::warn!("zubridge: failed to fetch page {} of {}: {}", request.page, request.paginator, e);
+      }
+      match zubridge.paginators.loaded(&request, result) {
+        Ok(true) => {
+          if let Err(e) = zubridge.emit_current_state() {
+            zubridge.options.error_policy.report(format_args!("failed to emit paginator state"), &e);
+          }
+        }
+        Ok(false) => {}
+        Err(e) => {
+          let context = format_args!("failed to store page {} of {}", request.page, request.paginator);
+          zubridge.options.error_policy.report(context, &e);
+        }
+      }
+    });
+  }
+
+  /// Run a sync task with a snapshot of the state and return how long to wait before the next run
+  fn run_sync_task(&self, task: &SyncTask) -> Duration {
+    let Some(statuses) = &self.sync else { return task.interval };
+    let result = (|| {
+      statuses.started(&task.name)?;
+      self.emit_current_state()?;
+      // Lazy slices are left out of the emitted state, but tasks get the whole state
+      let state = self.current_state()?;
+      let failures = statuses.finished(&task.name, task.run(&state, &dispatcher(&self.app)))?;
+      if failures > 0 {
+        log::warn!("zubridge: sync task {} failed {} times in a row", task.name, failures);
+      }
+      let delay = task.next_delay(failures);
+      statuses.scheduled(&task.name, delay)?;
+      self.emit_current_state()?;
+      Ok::<_, crate::Error>(delay)
+    })();
+
+    result.unwrap_or_else(|e| {
+      self.options.error_policy.report(format_args!("failed to run sync task {}", task.name), &e);
+      task.next_delay(1)
+    })
+  }
+
+  /// Dispatch actions one after another, calling `on_result` with the outcome of each as it's
+  /// applied. With [`BatchMode::BestEffort`], actions after a failed one are still dispatched;
+  /// with [`BatchMode::Atomic`], the actions are reduced under one lock and emitted as a single
+  /// update, and the first failure stops the batch and restores the state from before it, so
+  /// windows never see part of it. Returns the resulting state.
+  pub fn dispatch_batch(
+    &self,
+    actions: Vec<ZubridgeAction>,
+    mode: BatchMode,
+    on_result: impl FnMut(BatchResult),
+  ) -> crate::Result<JsonValue> {
+    self.dispatch_batch_from(None, actions, mode, on_result)
+  }
+
+  /// Dispatch a batch on behalf of a window, or from Rust if `window` is None
+  pub(crate) fn dispatch_batch_from(
+    &self,
+    window: Option<&str>,
+    actions: Vec<ZubridgeAction>,
+    mode: BatchMode,
+    mut on_result: impl FnMut(BatchResult),
+  ) -> crate::Result<JsonValue> {
+    if let Some(window) = window {
+      self.ensure_writable(window)?;
+      for action in &actions {
+        self.ensure_within_size(action)?;
+        self.ensure_outside_auth(action)?;
+      }
+    }
+    if mode == BatchMode::Atomic {
+      return self.dispatch_atomic_batch(actions, on_result);
+    }
+
+    for (index, action) in actions.into_iter().enumerate() {
+      let action_type = action.action_type.clone();
+      let result = self.dispatch_action(action);
+      if let Err(e) = &result {
+        log::debug!("zubridge: batch action {} ({}) failed: {}", index, action_type, e);
+      }
+      on_result(BatchResult::new(index, &result));
+    }
+    self.get_initial_state()
+  }
+
+  /// Dispatch a batch as a single update: path actions first, then the rest reduced under one
+  /// lock. If an action fails, the state from before the batch is restored before anything is
+  /// published. Plugin-handled actions fail the batch before they take effect, since restoring
+  /// the state can't undo them
+  fn dispatch_atomic_batch(
+    &self,
+    actions: Vec<ZubridgeAction>,
+    mut on_result: impl FnMut(BatchResult),
+  ) -> crate::Result<JsonValue> {
+    let count = actions.len();
+    let checkpoint = self.checkpoint()?;
+    let mut failed = None;
+    let mut reduced = Vec::new();
+    let mut indexes = Vec::new();
+    for (index, action) in actions.into_iter().enumerate() {
+      let routed = self.ensure_undoable(&action).and_then(|_| self.admit(action)).and_then(|admitted| match admitted {
+        Admitted::Dispatch(action) => {
+          let actions = self.intercept(action)?;
+          actions.iter().try_for_each(|action| self.ensure_undoable(action))?;
+          self.route_intercepted(actions).map(|(actions, _)| actions)
+        }
+        Admitted::Handled(_) => Ok(Vec::new()),
+      });
+      match routed {
+        Ok(actions) => {
+          indexes.extend(std::iter::repeat(index).take(actions.len()));
+          reduced.extend(actions);
+        }
+        Err(e) => {
+          failed = Some((index, e));
+          break;
+        }
+      }
+    }
+
+    let applied = match failed {
+      Some(failed) => Err(failed),
+      None => self.reduce_actions(reduced, Some(&checkpoint)).map_err(|(position, e)| {
+        let index = position.and_then(|position| indexes.get(position).copied()).unwrap_or_default();
+        (index, e)
+      }),
+    };
+    let (index, e) = match applied {
+      Ok(state) => {
+        (0..count).for_each(|index| on_result(BatchResult { index, ok: true, error: None }));
+        return Ok(state);
+      }
+      Err(failed) => failed,
+    };
+
+    log::debug!("zubridge: batch action {} failed: {}", index, e);
+    let message = e.to_string();
+    (0..index).for_each(|index| on_result(BatchResult { index, ok: true, error: None }));
+    on_result(BatchResult { index, ok: false, error: Some(message.clone()) });
+    self.rollback(checkpoint)?;
+    Err(crate::Error::BatchRolledBack { index, message })
+  }
+
+  /// Reject a plugin-handled action from an atomic batch
+  fn ensure_undoable(&self, action: &ZubridgeAction) -> crate::Result<()> {
+    if self.handled_by_plugin(action) {
+      return Err(crate::Error::ActionRejected(format!(
+        "{} can't be rolled back, so it can't be part of an atomic batch",
+        action.action_type
+      )));
+    }
+    Ok(())
+  }
+
+  /// Reject actions for the state manager while the initial state provider is loading
+  fn ensure_loaded(&self) -> crate::Result<()> {
+    if self.loading.load().is_some() {
+      return Err(crate::Error::StateLoading);
+    }
+    Ok(())
+  }
+
+  /// Hand the state loaded by the initial state provider to the state manager and emit it.
+  /// If loading failed, the state manager's own initial state is emitted instead. A `file` that
+  /// couldn't be parsed, or holds state invalid even after the `state_migrations`, is moved
+  /// aside; one that couldn't be read is left alone
+  fn initial_state_loaded(&self, loaded: Result<JsonValue, LoadError>, file: Option<&Path>) -> crate::Result<()> {
+    let restored = match loaded {
+      Ok(state) => self.restore_persisted_unlocked(state, "initial-state", file),
+      Err(LoadError::Missing) => Ok(()),
+      Err(LoadError::Invalid(message)) => {
+        self.persisted_state_invalid("initial-state", file, &crate::Error::InitialStateFailed(message));
+        Ok(())
+      }
+      Err(LoadError::Failed(message) | LoadError::Unreadable(message)) => Err(crate::Error::InitialStateFailed(message)),
+    };
+    // Stop loading either way, so windows aren't stuck on the placeholder
+    self.loading.store(None);
+    self.emit_current_state()?;
+    restored
+  }
+
+  /// Validate persisted state, migrating it if needed, and restore it. Invalid state is dropped
+  /// with a warning rather than failing, so the state manager keeps its own initial state
+  fn restore_persisted_unlocked(&self, state: JsonValue, source: &str, file: Option<&Path>) -> crate::Result<()> {
+    let state_manager = self.state_manager()?;
+    let mut state_guard = state_manager.lock().map_err(|e| crate::Error::StateError(e.to_string()))?;
+    self.restore_persisted(&mut state_guard, state, source, file).map(drop)
+  }
+
+  /// Restore persisted state like [`Self::restore_persisted_unlocked`], into a state manager the
+  /// caller holds locked. Returns whether it was restored
+  fn restore_persisted(
+    &self,
+    state_guard: &mut MutexGuard<'_, dyn StateManager>,
+    state: JsonValue,
+    source: &str,
+    file: Option<&Path>,
+  ) -> crate::Result<bool> {
+    match self.checked_state(state_guard, state) {
+      Ok(state) => self.restore_checked(state_guard, state).map(|_| true),
+      Err(e) => {
+        self.persisted_state_invalid(source, file, &e);
+        Ok(false)
+      }
+    }
+  }
+
+  /// Validate state from outside the state manager, running the `state_migrations` if it's
+  /// invalid. Fails with the error it's still invalid with after migrating
+  fn checked_state(&self, state_guard: &MutexGuard<'_, dyn StateManager>, state: JsonValue) -> crate::Result<JsonValue> {
+    let migrations = &self.options.state_migrations;
+    catch_reducer_panic(|| state_check::check(&**state_guard, state, migrations)).and_then(|checked| checked)
+  }
+
+  /// Restore checked state into the locked state manager and commit it
+  fn restore_checked(&self, state_guard: &mut MutexGuard<'_, dyn StateManager>, state: JsonValue) -> crate::Result<JsonValue> {
+    if !catch_reducer_panic(|| state_guard.restore_state(state.clone()))? {
+      return Err(crate::Error::RollbackUnsupported);
+    }
+    Ok(self.commit(state))
+  }
+
+  /// Warn that persisted state from `source` was dropped, on the `diagnostics` event, and move
+  /// its file aside so the next launch starts fresh
+  fn persisted_state_invalid(&self, source: &str, file: Option<&Path>, error: &crate::Error) {
+    log::warn!("zubridge: dropping invalid persisted state from {}: {}", source, error);
+    let quarantined = file.and_then(|file| match state_check::quarantine(file) {
+      Ok(quarantined) => Some(quarantined),
+      Err(e) => {
+        self.options.error_policy.report(format_args!("failed to move aside {}", file.display()), &e);
+        None
+      }
+    });
+    let diagnostic = serde_json::json!({
+      "kind": "invalid-persisted-state",
+      "source": source,
+      "message": error.to_string(),
+      "quarantined": quarantined,
+    });
+    if let Err(e) = self.app.emit(&self.event_names.diagnostics, diagnostic) {
+      self.options.error_policy.report(format_args!("failed to emit diagnostics"), &e);
+    }
+  }
+
+  /// Take the state manager's state to roll back to, failing if it can't be restored
+  fn checkpoint(&self) -> crate::Result<JsonValue> {
+    let state_manager = self.state_manager()?;
+    let mut state_guard = state_manager.lock().map_err(|e| crate::Error::StateError(e.to_string()))?;
+    let state = state_guard.get_initial_state();
+    // Restoring the state it's already in checks rollback is supported before anything is applied
+    if !catch_reducer_panic(|| state_guard.restore_state(state.clone()))? {
+      return Err(crate::Error::RollbackUnsupported);
+    }
+    Ok(state)
+  }
+
+  /// Restore a checkpoint and emit it. Only the state manager's state is restored, which is why
+  /// atomic batches can't hold plugin-handled actions.
+  fn rollback(&self, checkpoint: JsonValue) -> crate::Result<()> {
+    let state_manager = self.state_manager()?;
+    let mut state_guard = state_manager.lock().map_err(|e| crate::Error::StateError(e.to_string()))?;
+    if !catch_reducer_panic(|| state_guard.restore_state(checkpoint.clone()))? {
+      return Err(crate::Error::RollbackUnsupported);
+    }
+    self.commit(checkpoint);
+    drop(state_guard);
+    self.emit_current_state()?;
+    Ok(())
+  }
+
+  /// Restore the state handed over by the previous process and emit it
+  #[cfg(debug_assertions)]
+  fn restore_dev_handoff(&self, state: JsonValue) -> crate::Result<()> {
+    self.restore_persisted_unlocked(state, "dev-handoff", None)?;
+    self.emit_current_state()?;
+    Ok(())
+  }
+
+  /// Dispatch an action on behalf of a window, rejecting it if the window is read-only
+  pub fn dispatch_action_from(&self, window: &str, action: ZubridgeAction) -> crate::Result<JsonValue> {
+    self.ensure_writable(window)?;
+    self.ensure_within_size(&action)?;
+    self.ensure_outside_auth(&action)?;
+    breadcrumbs::traced(self.options.dispatch_breadcrumbs, || self.dispatch_action(action))
+  }
+
+  /// Append a chunk to the upload `id` from `window`. With `action`, the last chunk's action
+  /// type and payload, the file is closed and the action dispatched with the `upload` id to
+  /// claim it with [`Zubridge::take_upload`] and its size in `bytes` added to the payload. If
+  /// the dispatch fails, the file is deleted.
+  pub(crate) fn upload_blob(
+    &self,
+    window: &str,
+    id: &str,
+    chunk: &[u8],
+    action: Option<(String, Option<JsonValue>)>,
+  ) -> crate::Result<Option<JsonValue>> {
+    self.ensure_writable(window)?;
+    self.uploads.append(window, id, chunk)?;
+    let Some((action_type, payload)) = action else { return Ok(None) };
+    let (upload_id, bytes) = self.uploads.finish(window, id)?;
+    let dispatched = uploads::upload_action(action_type, payload, &upload_id, bytes)
+      .and_then(|action| self.dispatch_action_from(window, action));
+    if dispatched.is_err() {
+      self.uploads.forget(&upload_id);
+    }
+    dispatched.map(Some)
+  }
+
+  /// Claim the file of a finished upload, by the `upload` id in its action's payload. The file
+  /// is in a temp directory deleted when the app exits, so move or copy it somewhere permanent.
+  /// Each upload can only be claimed once.
+  pub fn take_upload(&self, upload_id: &str) -> crate::Result<PathBuf> {
+    self.uploads.take(upload_id)
+  }
+
+  /// Dispatch the action described by a `myapp://zubridge/dispatch?type=...&payload=...` link,
+  /// after checking it against the `deep_links` allowlist and signature
+  pub fn dispatch_deep_link(&self, link: &str) -> crate::Result<JsonValue> {
+    let config = self
+      .options
+      .deep_links
+      .as_ref()
+      .ok_or_else(|| crate::Error::DeepLinkRejected("deep link dispatch is disabled".into()))?;
+    let action = crate::deep_link::parse(config, link).map_err(|err| {
+      log::warn!(target: "zubridge::audit", "rejected deep link: {}", err);
+      err
+    })?;
+    log::info!(target: "zubridge::audit", "deep link dispatch {}", action.action_type);
+    self.dispatch_action(action)
+  }
+
+  /// Forward a second launch of the app, as reported by `tauri-plugin-single-instance`, to the
+  /// primary instance as an action. Does nothing unless `second_instance` is configured.
+  pub fn second_instance(&self, argv: &[String], cwd: &str) -> crate::Result<()> {
+    let Some(config) = &self.options.second_instance else { return Ok(()) };
+
+    if let Some(window) = config.focus_window.as_ref().and_then(|label| self.app.get_webview_window(label)) {
+      window.show()?;
+      window.unminimize()?;
+      window.set_focus()?;
+    }
+    if let Some(action) = config.action(argv, cwd) {
+      self.dispatch_action(action)?;
+    }
+    Ok(())
+  }
+
+  /// Whether a window only observes the state
+  pub fn is_read_only(&self, window: &str) -> bool {
+    self.options.read_only_windows.iter().any(|pattern| glob_matches(pattern, window))
+  }
+
+  pub(crate) fn ensure_writable(&self, window: &str) -> crate::Result<()> {
+    if self.is_read_only(window) {
+      return Err(crate::Error::ReadOnlyWindow(window.to_string()));
+    }
+    Ok(())
+  }
+
+  /// Reject an action while the session is locked, or if the user lacks the roles or claims it
+  /// requires
+  fn ensure_allowed(&self, action: &ZubridgeAction) -> crate::Result<()> {
+    let started = Instant::now();
+    let allowed = self.check_allowed(action);
+    breadcrumbs::record("authorize", &action.action_type, || breadcrumbs::outcome(&allowed), started.elapsed());
+    allowed
+  }
+
+  fn check_allowed(&self, action: &ZubridgeAction) -> crate::Result<()> {
+    if let Some(session_lock) = &self.session_lock {
+      session_lock.check(action)?;
+    }
+    if let Some(authorization) = self.options.authorization.as_ref().filter(|config| config.applies_to(&action.action_type)) {
+      // Read from the committed state, so only the auth slice is looked at rather than cloned
+      let state = self.committed_state()?;
+      authorization.authorize(&action.action_type, &state).map_err(|err| {
+        log::warn!(target: "zubridge::audit", "{}", err);
+        err
+      })?;
+    }
+    Ok(())
+  }
+
+  /// Reject a path action from a window that writes to the auth slice, so a window can't grant
+  /// itself the roles or claims authorization checks
+  fn ensure_outside_auth(&self, action: &ZubridgeAction) -> crate::Result<()> {
+    let Some(authorization) = &self.options.authorization else { return Ok(()) };
+    if !path_actions::is_path_action(&action.action_type) {
+      return Ok(());
+    }
+    authorization.ensure_outside(&action.action_type, path_actions::target(action)?)
+  }
+
+  /// Reject an action from a window whose payload is over `max_action_bytes` once encoded
+  fn ensure_within_size(&self, action: &ZubridgeAction) -> crate::Result<()> {
+    let (Some(limit), Some(payload)) = (self.options.max_action_bytes, &action.payload) else { return Ok(()) };
+    let bytes = serde_json::to_vec(payload).map_err(|e| crate::Error::SerializationError(e.to_string()))?.len();
+    if bytes > limit {
+      return Err(crate::Error::ActionTooLarge { action_type: action.action_type.clone(), bytes, limit });
+    }
+    Ok(())
+  }
+
+  /// Apply actions to the state manager under a single lock and emit the resulting state once
+  fn apply_actions(&self, actions: Vec<ZubridgeAction>) -> crate::Result<JsonValue> {
+    self.reduce_actions(actions, None).map_err(|(_, e)| e)
+  }
+
+  /// Apply actions like [`Self::apply_actions`]. If one fails and a `checkpoint` is given, it's
+  /// restored before the lock is released. Fails with the position of the failed action, if the
+  /// failure came from one
+  fn reduce_actions(
+    &self,
+    actions: Vec<ZubridgeAction>,
+    checkpoint: Option<&JsonValue>,
+  ) -> Result<JsonValue, (Option<usize>, crate::Error)> {
+    if actions.is_empty() {
+      return self.get_initial_state().map_err(|e| (None, e));
+    }
+    self.ensure_loaded().map_err(|e| (None, e))?;
+
+    // Lock the mutex to get mutable access to the state manager
+    let state_manager = self.state_manager().map_err(|e| (None, e))?;
+    let waiting = Instant::now();
+    let mut state_guard = state_manager.lock().map_err(|e| (None, crate::Error::StateError(e.to_string())))?;
+    self.metrics.lock_waited(waiting.elapsed());
+    let action_jsons: Vec<JsonValue> = actions.iter().map(|action| self.action_json(action)).collect();
+    for (position, (action, action_json)) in actions.iter().zip(&action_jsons).enumerate() {
+      let started = Instant::now();
+      let validated = state_guard.validate_action(action_json);
+      breadcrumbs::record("validate", &action.action_type, || breadcrumbs::outcome(&validated), started.elapsed());
+      validated.map_err(|e| (Some(position), e))?;
+    }
+    let reducing = Instant::now();
+    let mut updated_state = JsonValue::Null;
+    for (position, (action, action_json)) in actions.iter().zip(action_jsons).enumerate() {
+      let started = Instant::now();
+      let reduced = catch_reducer_panic(|| state_guard.try_dispatch_action(action_json)).and_then(|reduced| reduced);
+      breadcrumbs::record("reduce", &action.action_type, || breadcrumbs::outcome(&reduced), started.elapsed());
+      let err = match reduced {
+        Ok(state) => {
+          updated_state = state;
+          continue;
+        }
+        Err(err) => err,
+      };
+      let changed = match checkpoint {
+        Some(checkpoint) => match catch_reducer_panic(|| state_guard.restore_state(checkpoint.clone())) {
+          Ok(true) => {
+            self.commit(checkpoint.clone());
+            None
+          }
+          Ok(false) => return Err((Some(position), crate::Error::RollbackUnsupported)),
+          Err(e) => return Err((Some(position), e)),
+        },
+        // The failed reducer, or the actions before it, may have changed the state already, so
+        // it's published as it is rather than left out of sync with windows. The first read commits,
+        // so without a committed state no window has seen one yet
+        None => catch_reducer_panic(|| state_guard.get_initial_state())
+          .ok()
+          .filter(|actual| self.committed.load_full().is_some_and(|committed| *committed != *actual))
+          .map(|actual| self.commit(actual)),
+      };
+      drop(state_guard);
+      if let Some(state) = changed {
+        if let Err(e) = self.project_state(state).and_then(|state| self.publish_state(state)) {
+          self.options.error_policy.report(format_args!("failed to publish the state after a failed dispatch"), &e);
+        }
+      }
+      if let crate::Error::ReducerPanicked { message } = &err {
+        self.metrics.dispatched(reducing.elapsed(), false);
+        log::error!("zubridge: reducer panicked on {}: {}", action.action_type, message);
+        let diagnostic = serde_json::json!({
+          "kind": "reducer-panicked",
+          "action_type": action.action_type,
+          "message": message,
+        });
+        if let Err(e) = self.app.emit(&self.event_names.diagnostics, diagnostic) {
+          self.options.error_policy.report(format_args!("failed to emit diagnostics"), &e);
+        }
+      }
+      return Err((Some(position), err));
+    }
+
+    // Publish the new state to readers before dropping the lock, so they never see an older one
+    let updated_state = self.commit(updated_state);
+    drop(state_guard);
+    self.metrics.dispatched(reducing.elapsed(), true);
+
+    for action in &actions {
+      self.action_log.record(&action.action_type, action.payload.as_ref());
+      self.workflows.observe(action);
+      if let Some(telemetry) = &self.telemetry {
+        telemetry.record(&action.action_type, action.payload.as_ref());
+      }
+      #[cfg(feature = "reqwest")]
+      if let Some(webhooks) = &self.webhooks {
+        webhooks.action_dispatched(action);
+      }
+      #[cfg(feature = "automation")]
+      if let Some(automation) = &self.automation {
+        automation.observe(action, &updated_state);
+      }
+    }
+
+    let updated_state = self.project_state(updated_state).map_err(|e| (None, e))?;
+    self.publish_state(updated_state).map_err(|e| (None, e))
+  }
+
+  /// Convert an action to the JSON shape passed to the state manager
+  fn action_json(&self, action: &ZubridgeAction) -> JsonValue {
+    let mut action_json = serde_json::json!({
+      "type": action.action_type,
+      "payload": action.payload
+    });
+
+    if self.options.include_install_id {
+      action_json["meta"] = serde_json::json!({ "install_id": self.install_id });
+      log::debug!(target: "zubridge::audit", "dispatch {} install_id={}", action.action_type, self.install_id);
+    } else {
+      log::debug!(target: "zubridge::audit", "dispatch {}", action.action_type);
+    }
+    action_json
+  }
+
+  /// Load a fixture into the slice it's named after, or every fixture if `name` is None, and
+  /// emit the resulting state. Only available in debug builds.
+  #[cfg(debug_assertions)]
+  pub fn load_fixture(&self, name: Option<&str>) -> crate::Result<JsonValue> {
+    let config = self
+      .options
+      .fixtures
+      .as_ref()
+      .ok_or_else(|| crate::Error::ConfigError("fixtures are not configured".into()))?;
+    let names = match name {
+      Some(name) => vec![name.to_string()],
+      None => fixtures::names(&config.dir)?,
+    };
+    // Read every fixture first, so a broken one doesn't leave the others half loaded
+    let loaded = names
+      .into_iter()
+      .map(|name| fixtures::read(&config.dir, &name).map(|value| (name, value)))
+      .collect::<crate::Result<Vec<_>>>()?;
+
+    let state_manager = self.state_manager()?;
+    let mut state_guard = state_manager.lock().map_err(|e| crate::Error::StateError(e.to_string()))?;
+    for (slice, value) in loaded {
+      log::debug!("zubridge: loading fixture {}", slice);
+      catch_reducer_panic(|| state_guard.load_fixture(&slice, value))?;
+    }
+    self.commit(state_guard.get_initial_state());
+    drop(state_guard);
+    self.emit_current_state()
+  }
+
+  /// Get the JSON Schema of the state and actions, if the state manager provides one
+  pub fn get_schema(&self) -> crate::Result<Option<StateSchema>> {
+    let state_manager = self.state_manager()?;
+    let state_guard = state_manager.lock().map_err(|e| crate::Error::StateError(e.to_string()))?;
+    Ok(state_guard.schema())
+  }
+
+  /// Start a bulk import. Chunks sent to the returned sink are applied without emitting
+  /// per-chunk state updates; progress is reported on `zubridge://import-progress`.
+  pub fn import_stream(&self, import: &str) -> ImportSink<R> {
+    ImportSink::new(self.app.clone(), import.to_string())
+  }
+
+  /// Publish a transient value on the ephemeral channel, or clear it with `null`.
+  /// The value never reaches the state manager and isn't part of the emitted state.
+  pub fn set_ephemeral(&self, path: &str, value: impl Into<JsonValue>) -> crate::Result<()> {
+    let value = value.into();
+    self.ephemeral.set(path, value.clone())?;
+    self.app
+      .emit(&self.event_names.ephemeral_update, serde_json::json!({ "path": path, "value": value }))
+      .map_err(|err| crate::Error::EmitError(err.to_string()))
+  }
+
+  /// Get the current value of an ephemeral path
+  pub fn ephemeral(&self, path: &str) -> crate::Result<Option<JsonValue>> {
+    self.ephemeral.get(path)
+  }
+
+  /// Get all current ephemeral values, keyed by path
+  pub fn ephemeral_values(&self) -> crate::Result<BTreeMap<String, JsonValue>> {
+    self.ephemeral.all()
+  }
+
+  /// Get the current contents of the config file, if one is configured
+  pub fn config(&self) -> crate::Result<Option<JsonValue>> {
+    self.config_file.as_ref().map(|config_file| config_file.value()).transpose()
+  }
+
+  /// Re-read the config file from disk and emit the re-hydrated state
+  pub fn reload_config(&self) -> crate::Result<()> {
+    let Some(config_file) = &self.config_file else {
+      return Ok(());
+    };
+    config_file.reload()?;
+    self.emit_current_state()?;
+    Ok(())
+  }
+
+  /// Evaluate a feature flag for the active profile
+  pub fn flag(&self, name: &str) -> crate::Result<JsonValue> {
+    self.feature_flags.evaluate(name, None)
+  }
+
+  /// Evaluate a feature flag for a specific window
+  pub fn flag_for_window(&self, name: &str, window_label: &str) -> crate::Result<JsonValue> {
+    self.feature_flags.evaluate(name, Some(window_label))
+  }
+
+  /// Override a feature flag at runtime and emit the updated flags slice
+  pub fn set_flag(&self, name: &str, value: impl Into<JsonValue>) -> crate::Result<()> {
+    self.feature_flags.set(name, value.into())?;
+    self.emit_current_state()?;
+    Ok(())
+  }
+
+  /// Set the profile used to evaluate profile-targeted flag rules
+  pub fn set_profile(&self, profile: Option<String>) -> crate::Result<()> {
+    self.feature_flags.set_profile(profile)?;
+    self.emit_current_state()?;
+    Ok(())
+  }
+
+  /// Get the variant this install is assigned to for an experiment
+  pub fn experiment(&self, name: &str) -> Option<String> {
+    self.experiments.as_ref()?.get(name).map(str::to_string)
+  }
+
+  /// Get all experiment assignments for this install, keyed by experiment name
+  pub fn experiments(&self) -> BTreeMap<String, String> {
+    self.experiments.as_ref().map(|assignments| assignments.all().clone()).unwrap_or_default()
+  }
+
+  /// Deliver queued telemetry events now instead of waiting for the next batch
+  pub fn flush_telemetry(&self) {
+    if let Some(telemetry) = &self.telemetry {
+      telemetry.flush();
+    }
+  }
+
+  /// Forward an error reported by a window to the configured [`ErrorSink`](crate::ErrorSink),
+  /// along with the recent action log
+  pub fn report_error(&self, window: &str, error: FrontendError) -> crate::Result<()> {
+    let Some(config) = &self.options.error_reporting else {
+      log::error!("zubridge: window {} reported an error: {}", window, error.message);
+      return Ok(());
+    };
+
+    let mut recent_actions = self.action_log.entries();
+    if !config.include_action_payloads {
+      recent_actions.iter_mut().for_each(|entry| entry.payload = None);
+    }
+    let report = ErrorReport {
+      error,
+      window: window.to_string(),
+      current_revision: self.revision(),
+      recent_actions,
+      timestamp: now_millis(),
+    };
+    config.sink.report(&report);
+    Ok(())
+  }
+
+  /// Save the current state as a snapshot for support tooling, returning its id. Without a
+  /// name, the id comes from the `id_generator`
+  pub fn save_snapshot(&self, name: Option<&str>) -> crate::Result<String> {
+    let state = self.encrypt_sensitive(self.current_state()?)?;
+    let id = match name {
+      Some(name) => name.to_string(),
+      None => self.options.id_generator.next_id(),
+    };
+    Snapshots::new(&self.data_dir).save(&state, &id)?;
+    Ok(id)
+  }
+
+  /// Read a secret from the OS keychain. The state only holds its handle, so this is the
+  /// one way to get the value, and it isn't exposed to the frontend
+  #[cfg(feature = "secrets")]
+  pub fn reveal_secret(&self, name: &str) -> crate::Result<String> {
+    self
+      .secrets
+      .as_ref()
+      .ok_or_else(|| crate::Error::ConfigError("secrets are not enabled".into()))?
+      .reveal(name)
+  }
+
+  /// Lock the session, e.g. after the app has been idle for a while: every action outside the
+  /// `session_lock` allowlist is rejected with [`crate::Error::SessionLocked`], and the state's
+  /// `session.locked` flag is set so windows can show a lock screen
+  pub fn lock_session(&self) -> crate::Result<()> {
+    let locked = self.session_lock()?.lock();
+    self.set_session_locked(locked).map(|_| ())
+  }
+
+  /// Unlock the session, once the app has checked the user's password or other credentials
+  pub fn unlock_session(&self) -> crate::Result<()> {
+    let unlocked = self.session_lock()?.unlock();
+    self.set_session_locked(unlocked).map(|_| ())
+  }
+
+  /// Whether the session is locked
+  pub fn is_session_locked(&self) -> bool {
+    self.session_lock.as_ref().is_some_and(SessionLock::is_locked)
+  }
+
+  /// Switch to the workspace `id`: the state manager's state is kept as the current workspace's,
+  /// and replaced with the state `id` had when it was last active, or the template if it never was
+  pub fn switch_workspace(&self, id: &str) -> crate::Result<JsonValue> {
+    workspaces::validate(id)?;
+    self.swap_workspace(id)?;
+    self.emit_current_state()
+  }
+
+  /// The id of the active workspace
+  pub fn active_workspace(&self) -> crate::Result<String> {
+    self.workspaces()?.active()
+  }
+
+  fn workspaces(&self) -> crate::Result<&Workspaces> {
+    self.workspaces.as_ref().ok_or_else(|| crate::Error::ConfigError("workspaces are not enabled".into()))
+  }
+
+  /// Swap the state manager's state for workspace `id`'s. The state manager stays locked until
+  /// the swap is recorded, so no action lands in between. A saved state that can't be parsed,
+  /// or is invalid even after the `state_migrations`, is moved aside, and the workspace starts
+  /// over from the template
+  fn swap_workspace(&self, id: &str) -> crate::Result<()> {
+    let workspaces = self.workspaces()?;
+    let active = workspaces.active()?;
+    if active == id {
+      return Ok(());
+    }
+    self.ensure_loaded()?;
+    let state_manager = self.state_manager()?;
+    let mut state_guard = state_manager.lock().map_err(|e| crate::Error::StateError(e.to_string()))?;
+    let outgoing = state_guard.get_initial_state();
+    let saved = workspaces.saved_file(id);
+    let restored = match workspaces.state_of(id) {
+      Ok(incoming) => self.restore_persisted(&mut state_guard, incoming, "workspace", saved.as_deref())?,
+      Err(e @ crate::Error::SerializationError(_)) => {
+        self.persisted_state_invalid("workspace", saved.as_deref(), &e);
+        false
+      }
+      Err(e) => return Err(e),
+    };
+    if !restored {
+      self.restore_checked(&mut state_guard, workspaces.template())?;
+    }
+    workspaces.park(&active, outgoing)?;
+    workspaces.set_active(id)?;
+    drop(state_guard);
+    Ok(())
+  }
+
+  /// Persist the active workspace's state, so it's there when the app next launches
+  fn save_active_workspace(&self) -> crate::Result<()> {
+    let workspaces = self.workspaces()?;
+    let state_manager = self.state_manager()?;
+    let state = state_manager.lock().map_err(|e| crate::Error::StateError(e.to_string()))?.get_initial_state();
+    workspaces.save(&workspaces.active()?, &state)
+  }
+
+  fn session_lock(&self) -> crate::Result<&SessionLock> {
+    self.session_lock.as_ref().ok_or_else(|| crate::Error::ConfigError("session lock is not enabled".into()))
+  }
+
+  /// Emit the state after the session was locked or unlocked, if that changed anything
+  fn set_session_locked(&self, changed: bool) -> crate::Result<JsonValue> {
+    if changed {
+      self.emit_current_state()
+    } else {
+      self.get_initial_state()
+    }
+  }
+
+  /// Encrypt the `sensitive_paths` of a state, e.g. before the app persists or exports it.
+  /// Returns the state unchanged when no paths are sensitive
+  pub fn encrypt_sensitive(&self, mut state: JsonValue) -> crate::Result<JsonValue> {
+    if let Some(sensitive) = &self.sensitive {
+      sensitive.encrypt(&mut state)?;
+    }
+    Ok(state)
+  }
+
+  /// Decrypt the `sensitive_paths` of a state encrypted with [`Zubridge::encrypt_sensitive`]
+  pub fn decrypt_sensitive(&self, mut state: JsonValue) -> crate::Result<JsonValue> {
+    if let Some(sensitive) = &self.sensitive {
+      sensitive.decrypt(&mut state)?;
+    }
+    Ok(state)
+  }
+
+  /// The ids of the saved snapshots
+  pub fn snapshots(&self) -> crate::Result<Vec<String>> {
+    Snapshots::new(&self.data_dir).list()
+  }
+
+  /// Compare two saved snapshots, returning a JSON Patch from `a` to `b` and summary counts
+  pub fn diff_snapshots(&self, a: &str, b: &str) -> crate::Result<SnapshotDiff> {
+    let store = Snapshots::new(&self.data_dir);
+    Ok(snapshots::diff(&store.load(a)?, &store.load(b)?))
+  }
+
+  /// Restore a saved snapshot, merged into the current state with the `merge_strategies`.
+  /// Returns the restored state
+  pub fn restore_snapshot(&self, id: &str) -> crate::Result<JsonValue> {
+    let snapshot = Snapshots::new(&self.data_dir).load(id)?;
+    self.import_state(self.decrypt_sensitive(snapshot)?)
+  }
+
+  /// Restore a snapshot on behalf of a window, which must be writable and allowed to dispatch
+  /// `__ZUBRIDGE:RESTORE_SNAPSHOT`. Fails, restoring nothing, if it would change the auth slice
+  pub(crate) fn restore_snapshot_from(&self, window: &str, id: &str) -> crate::Result<JsonValue> {
+    self.ensure_writable(window)?;
+    self.ensure_allowed(&ZubridgeAction {
+      action_type: snapshots::RESTORE_ACTION.to_string(),
+      payload: Some(serde_json::json!({ "id": id })),
+    })?;
+    let snapshot = Snapshots::new(&self.data_dir).load(id)?;
+    self.merge_state(self.decrypt_sensitive(snapshot)?, Some(snapshots::RESTORE_ACTION))
+  }
+
+  /// Merge a state exported from this app, e.g. from another device or an older version, into
+  /// the current state with the `merge_strategies`, and restore it with
+  /// [`StateManager::restore_state`]. Keys the state manager doesn't hold, such as plugin
+  /// slices, are left out. Returns the restored state
+  pub fn import_state(&self, incoming: JsonValue) -> crate::Result<JsonValue> {
+    self.merge_state(incoming, None)
+  }
+
+  /// Merge `incoming` into the state like [`Self::import_state`]. On behalf of a window, named
+  /// by the `action_type` it's checked as, the merge must leave the auth slice as it was
+  fn merge_state(&self, incoming: JsonValue, window_action: Option<&str>) -> crate::Result<JsonValue> {
+    self.ensure_loaded()?;
+    let state_manager = self.state_manager()?;
+    let mut state_guard = state_manager.lock().map_err(|e| crate::Error::StateError(e.to_string()))?;
+    let local = state_guard.get_initial_state();
+    let mut state = merge::merge(&local, &incoming, &self.options.merge_strategies);
+    if let (JsonValue::Object(local), JsonValue::Object(state)) = (&local, &mut state) {
+      state.retain(|key, _| local.contains_key(key));
+    }
+    // Exported by another device or version, so it's checked like persisted state, but an
+    // invalid one fails the import rather than being dropped
+    let state = self.checked_state(&state_guard, state)?;
+    if let (Some(action_type), Some(authorization)) = (window_action, &self.options.authorization) {
+      if get_path(&state, &authorization.auth_path) != get_path(&local, &authorization.auth_path) {
+        authorization.ensure_outside(action_type, &authorization.auth_path)?;
+      }
+    }
+    let state = self.restore_checked(&mut state_guard, state)?;
+    drop(state_guard);
+    self.emit_current_state()?;
+    Ok(state)
+  }
+
+  /// The most recently dispatched actions, oldest first.
+  #[cfg(debug_assertions)]
+  pub(crate) fn recent_actions(&self) -> Vec<crate::action_log::ActionLogEntry> {
+    self.action_log.entries()
+  }
+
+  /// Draw the shape of the state, which slices the plugin and slice reducers own, and the
+  /// effects writing to them, as a Mermaid flowchart or Graphviz digraph.
+  pub fn export_state_graph(&self, format: GraphFormat) -> crate::Result<String> {
+    let app_state = {
+      let state_manager = self.state_manager()?;
+      let state_guard = state_manager.lock().map_err(|e| crate::Error::StateError(e.to_string()))?;
+      state_guard.get_initial_state()
+    };
+    let app_keys: BTreeSet<String> = app_state.as_object().map(|root| root.keys().cloned().collect()).unwrap_or_default();
+    let state = self.project_state(app_state)?;
+
+    // Slice reducers, other plugins and this one insert their slices after the state manager's
+    let owner = |key: &str| {
+      if self.slice_router.owns(key) || self.plugin_slices.owns(key) {
+        SliceOwner::Reducer
+      } else if app_keys.contains(key) {
+        SliceOwner::App
+      } else {
+        SliceOwner::Plugin
+      }
+    };
+    let mut graph = StateGraph::new(&state, owner, |key| self.lazy_slices.is_lazy(key));
+
+    if self.options.config_file.is_some() {
+      graph.add_effect("config file", &self.options.config_key, Some(&self.options.config_key));
+    }
+    for aggregation in &self.options.aggregations {
+      graph.add_effect("aggregate", &aggregation.name, Some(&self.options.stats_key));
+    }
+    for chart in &self.options.statecharts {
+      graph.add_effect("statechart", &chart.name, Some(&self.options.statecharts_key));
+    }
+    for workflow in &self.options.workflows {
+      graph.add_effect("workflow", &workflow.name, Some(&self.options.workflows_key));
+    }
+    for paginator in &self.options.paginators {
+      graph.add_effect("paginator", &paginator.name, Some(&self.options.paginators_key));
+    }
+    if let Some(sync) = &self.options.sync {
+      for task in &sync.tasks {
+        graph.add_effect("sync", &task.name, Some(&sync.state_key));
+      }
+    }
+    #[cfg(feature = "search")]
+    for index in &self.options.search_indexes {
+      graph.add_effect("search index", &index.name, index.path.split('.').next());
+    }
+    for extension in &self.options.extensions {
+      graph.add_effect("extension", &extension.name, None);
+    }
+    Ok(graph.render(format))
+  }
+
+  /// Write a crash bundle with the redacted state, recent actions and plugin diagnostics.
+  /// Returns the path of the written report.
+  pub fn capture_report(&self, reason: &str) -> crate::Result<PathBuf> {
+    let config = self.options.crash_reports.clone().unwrap_or_default();
+
+    // Never block here: this also runs from the panic hook, possibly while the state manager is locked
+    let mut state_status = "ok";
+    let mut state = match self.state_manager()?.try_lock() {
+      Ok(guard) => guard.get_initial_state(),
+      Err(TryLockError::Poisoned(poisoned)) => {
+        state_status = "poisoned";
+        poisoned.into_inner().get_initial_state()
+      }
+      Err(TryLockError::WouldBlock) => {
+        state_status = "locked";
+        JsonValue::Null
+      }
+    };
+    for path in &config.redact_paths {
+      redact_path(&mut state, path);
+    }
+    let state = self.encrypt_sensitive(state)?;
+
+    let mut recent_actions = self.action_log.entries();
+    if !config.include_action_payloads {
+      recent_actions.iter_mut().for_each(|entry| entry.payload = None);
+    }
+
+    let report = CrashReport {
+      reason: reason.to_string(),
+      timestamp: now_millis(),
+      state,
+      recent_actions,
+      diagnostics: serde_json::json!({
+        "plugin_version": env!("CARGO_PKG_VERSION"),
+        "install_id": self.install_id,
+        "os": std::env::consts::OS,
+        "arch": std::env::consts::ARCH,
+        "event_name": self.event_names.state_update,
+        "state_status": state_status,
+        "windows": self.app.webview_windows().keys().collect::<Vec<_>>(),
+      }),
+    };
+
+    let dir = config.directory.unwrap_or_else(|| self.data_dir.join("crash-reports"));
+    write_report(&dir, &report)
+  }
+
+  /// Set the options for the plugin
+  pub fn set_options(&mut self, options: ZubridgeOptions) {
+    self.options = options;
+  }
+
+  /// Register a state manager
+  pub fn register_state_manager<S: StateManager>(&self, state_manager: S) -> crate::Result<()> {
+    let state_arc: Arc<Mutex<dyn StateManager>> = Arc::new(Mutex::new(state_manager));
+    self.app.manage(state_arc);
+    Ok(())
+  }
+
+  /// Mount another plugin's state manager under the top-level `namespace` key. Actions typed
+  /// `<namespace>:*` are dispatched to it instead of the app's state manager. Fails with
+  /// [`crate::Error::SliceConflict`] if the key is already taken, by the app's state, a slice of
+  /// this plugin or another plugin's, and with [`crate::Error::ConfigError`] if the namespace is
+  /// a built-in action prefix such as `WINDOW`. Registered before the app's state manager,
+  /// as plugins usually are, the slice is checked against the app's state the first time it's
+  /// read instead, which fails with [`crate::Error::SliceConflict`] and unmounts the slice
+  pub fn register_slice<S: StateManager>(&self, namespace: &str, manager: S) -> crate::Result<()> {
+    let registered = self.state_manager().is_ok();
+    if registered && self.current_state()?.get(namespace).is_some() {
+      return Err(crate::Error::SliceConflict(namespace.to_string()));
+    }
+    self.plugin_slices.register(namespace, Box::new(manager), registered)?;
+    if registered {
+      self.emit_current_state()?;
+    }
+    Ok(())
+  }
+
+  pub(crate) fn state_manager(&self) -> crate::Result<Arc<Mutex<dyn StateManager>>> {
+    self.app
+      .try_state::<Arc<Mutex<dyn StateManager>>>()
+      .map(|state_manager| state_manager.inner().clone())
+      .ok_or_else(|| crate::Error::StateError("StateManager not found in app state".into()))
+  }
+
+  /// Apply plugin-managed slices to the state coming out of the state manager
+  fn project_state(&self, state: JsonValue) -> crate::Result<JsonValue> {
+    let mut state = match &self.config_file {
+      Some(config_file) => config_file.apply(state)?,
+      None => state,
+    };
+
+    // Aggregate the app's own state, before routed and plugin slices are added
+    if !self.aggregates.is_empty() {
+      let stats = self.aggregates.slice(&state)?;
+      if let Some(root) = state.as_object_mut() {
+        root.insert(self.options.stats_key.clone(), stats);
+      }
+    }
+
+    if let Some(root) = state.as_object_mut() {
+      self.slice_router.insert_slices(root)?;
+      // The loading placeholder doesn't tell whether the app's state has a slice's key
+      self.plugin_slices.insert_slices(root, self.loading.load().is_none())?;
+    }
+
+    if !self.feature_flags.is_empty() {
+      if let Some(root) = state.as_object_mut() {
+        let windows = self.app.webview_windows();
+        let slice = self.feature_flags.slice(windows.keys().map(String::as_str))?;
+        root.insert(self.options.feature_flags_key.clone(), slice);
+      }
+    }
+
+    if let (Some(windows), Some(config), Some(root)) = (&self.windows, &self.options.window_actions, state.as_object_mut()) {
+      root.insert(config.state_key.clone(), windows.slice()?);
+    }
+
+    if let (Some(dialogs), Some(config), Some(root)) = (&self.dialogs, &self.options.dialogs, state.as_object_mut()) {
+      root.insert(config.state_key.clone(), dialogs.slice()?);
+    }
+
+    if let (Some(confirmations), Some(config), Some(root)) =
+      (&self.confirmations, &self.options.confirmations, state.as_object_mut())
+    {
+      root.insert(config.state_key.clone(), confirmations.slice()?);
+    }
+
+    if let (Some(updater), Some(root)) = (&self.updater, state.as_object_mut()) {
+      root.insert(updater.state_key().to_string(), updater.slice()?);
+    }
+
+    if let (false, Some(root)) = (self.forms.is_empty(), state.as_object_mut()) {
+      root.insert(self.options.forms_key.clone(), self.forms.slice()?);
+    }
+
+    if let (false, Some(root)) = (self.statecharts.is_empty(), state.as_object_mut()) {
+      root.insert(self.options.statecharts_key.clone(), self.statecharts.slice()?);
+    }
+
+    if let (false, Some(root)) = (self.workflows.is_empty(), state.as_object_mut()) {
+      root.insert(self.options.workflows_key.clone(), self.workflows.slice()?);
+    }
+
+    if let (Some(timers), Some(config), Some(root)) = (&self.timers, &self.options.timers, state.as_object_mut()) {
+      root.insert(config.state_key.clone(), timers.slice()?);
+    }
+
+    if let (false, Some(root)) = (self.paginators.is_empty(), state.as_object_mut()) {
+      root.insert(self.options.paginators_key.clone(), self.paginators.slice()?);
+    }
+
+    if let (Some(appearance), Some(root)) = (&self.appearance, state.as_object_mut()) {
+      appearance.insert_into(root)?;
+    }
+
+    if let (Some(trash), Some(root)) = (&self.trash, state.as_object_mut()) {
+      root.insert(trash.state_key().to_string(), trash.slice()?);
+    }
+
+    #[cfg(feature = "automation")]
+    if let (Some(automation), Some(root)) = (&self.automation, state.as_object_mut()) {
+      root.insert(automation.state_key().to_string(), automation.slice()?);
+    }
+
+    if let (Some(session_lock), Some(root)) = (&self.session_lock, state.as_object_mut()) {
+      root.insert(session_lock.state_key().to_string(), session_lock.slice());
+    }
+
+    if let (Some(workspaces), Some(root)) = (&self.workspaces, state.as_object_mut()) {
+      root.insert(workspaces.state_key().to_string(), workspaces.slice()?);
+    }
+
+    #[cfg(feature = "secrets")]
+    if let (Some(secrets), Some(root)) = (&self.secrets, state.as_object_mut()) {
+      root.insert(secrets.state_key().to_string(), secrets.slice()?);
+    }
+
+    if let (Some(prefs), Some(root)) = (&self.platform_prefs, state.as_object_mut()) {
+      root.insert(prefs.state_key().to_string(), prefs.slice()?);
+    }
+
+    if let (Some(sync), Some(config), Some(root)) = (&self.sync, &self.options.sync, state.as_object_mut()) {
+      root.insert(config.state_key.clone(), sync.slice()?);
+    }
+
+    if let (Some(assignments), Some(root)) = (&self.experiments, state.as_object_mut()) {
+      let slice = serde_json::to_value(assignments.all()).map_err(|e| crate::Error::SerializationError(e.to_string()))?;
+      root.insert(self.options.experiments_key.clone(), slice);
+    }
+    Ok(state)
+  }
+
+  /// Emit the current state without dispatching an action
+  pub(crate) fn emit_current_state(&self) -> crate::Result<JsonValue> {
+    let state = self.read_state(true)?;
+    self.publish_state(state)
+  }
+
+  /// Emit the state after the state manager reported a change made outside dispatches
+  pub(crate) fn state_changed(&self) {
+    if let Err(e) = self.emit_current_state() {
+      self.options.error_policy.report(format_args!("failed to emit the changed state"), &e);
+    }
+  }
+
+  /// Broadcast the state without its lazy slices, and send changed lazy slices to the
+  /// windows holding them. Returns the broadcast state.
+  fn publish_state(&self, mut state: JsonValue) -> crate::Result<JsonValue> {
+    let lazy = self.lazy_slices.split(&mut state);
+    if let Some(open) = self.open_transactions().get_mut(&std::thread::current().id()) {
+      open.suppressed = true;
+      return Ok(state);
+    }
+    let policy = self.options.error_policy;
+    let emitted = self.emit(&state);
+    self.waiters.notify();
+    policy.recover(format_args!("failed to emit the state"), emitted)?;
+    if let Err(e) = self.update_menus(&state) {
+      policy.report(format_args!("failed to update menus"), &e);
+    }
+    // The placeholder isn't worth caching
+    let loading = self.loading.load().is_some();
+    if let (Some(cache), false) = (&self.warm_start, loading) {
+      if let Err(e) = cache.store(&state) {
+        policy.report(format_args!("failed to write the warm start cache"), &e);
+      }
+    }
+    #[cfg(debug_assertions)]
+    if let (Some(handoff), false) = (&self.dev_handoff, loading) {
+      if let Some(committed) = self.committed.load_full() {
+        handoff.store(committed);
+      }
+    }
+    #[cfg(feature = "reqwest")]
+    if let Some(webhooks) = &self.webhooks {
+      webhooks.state_published(&state);
+    }
+    self.extensions.state_published(&state, self.revision());
+    #[cfg(feature = "mqtt")]
+    if let Some(mqtt) = &self.mqtt {
+      if let Err(e) = mqtt.state_published(&state, self.revision()) {
+        policy.report(format_args!("failed to publish the state to MQTT"), &e);
+      }
+    }
+
+    if !lazy.is_empty() {
+      for update in self.lazy_slices.updates(lazy)? {
+        let payload = update.payload();
+        for window in &update.windows {
+          let result = self
+            .app
+            .emit_to(window.as_str(), &self.event_names.slice_update, payload.clone())
+            .map_err(|err| crate::Error::EmitError(err.to_string()));
+          let Err(err) = result else { continue };
+          // Send the slice again with the next update, whether or not it changed
+          self.missed_revisions.record([window.as_str()], self.revision());
+          self.lazy_slices.invalidate(&update.slice)?;
+          if !self.retry_emits(vec![window.clone()]) {
+            policy.recover(format_args!("failed to send slice {} to {}", update.slice, window), Err(err))?;
+          }
+        }
+      }
+    }
+    Ok(state)
+  }
+
+  fn emit(&self, state: &JsonValue) -> crate::Result<()> {
+    let revision = self.revision.fetch_add(1, Ordering::SeqCst) + 1;
+
+    let emitting = Instant::now();
+    let result = match &self.shared_memory {
+      Some(shared_memory) => {
+        let bytes = serde_json::to_vec(state).map_err(|e| crate::Error::SerializationError(e.to_string()))?;
+        let descriptor = shared_memory.write(revision, &bytes)?;
+        self.broadcast(revision, &descriptor)
+      }
+      None => self.broadcast(revision, state),
+    };
+    self.metrics.emitted(emitting.elapsed(), result.as_ref().is_ok_and(Vec::is_empty));
+
+    // Every update carries the full state, so the next one that gets through resyncs the
+    // windows that missed this one
+    match result {
+      Ok(failed) => {
+        self.missed_revisions.record(failed.iter().map(String::as_str), revision);
+        self.missed_revisions.resynced(revision, &failed);
+        if failed.is_empty() || self.retry_emits(failed.clone()) {
+          return Ok(());
+        }
+        Err(crate::Error::EmitError(format!("failed to reach {}", failed.join(", "))))
+      }
+      Err(err) => {
+        let windows = self.app.webview_windows();
+        self.missed_revisions.record(windows.keys().map(String::as_str), revision);
+        Err(crate::Error::EmitError(err.to_string()))
+      }
+    }
+  }
+
+  /// Send a state update to every window, signed when payload integrity is enabled. Returns the
+  /// windows it didn't reach
+  fn broadcast(&self, revision: u64, payload: &(impl Serialize + Clone)) -> tauri::Result<Vec<String>> {
+    if let Some(signer) = &self.payload_signer {
+      let signed = signer.sign(revision, payload)?;
+      return self.send_update(revision, &signed);
+    }
+    self.send_update(revision, payload)
+  }
+
+  /// Send a state update to the subscribed windows through their channels, and emit it to each
+  /// of the others. Returns the windows the emit didn't reach, so only they're retried.
+  fn send_update(&self, revision: u64, payload: &(impl Serialize + Clone)) -> tauri::Result<Vec<String>> {
+    if !self.subscriptions.is_empty() {
+      let value = serde_json::to_value(payload)?;
+      if let Err(e) = self.subscriptions.publish(&value, revision) {
+        self.options.error_policy.report(format_args!("failed to send state updates to subscribers"), &e);
+      }
+    }
+    let mut failed = Vec::new();
+    for label in self.app.webview_windows().into_keys() {
+      if self.subscriptions.is_subscribed(&label) {
+        continue;
+      }
+      if let Err(e) = self.app.emit_to(label.as_str(), &self.event_names.state_update, payload.clone()) {
+        log::debug!("zubridge: failed to emit the state to {}: {}", label, e);
+        failed.push(label);
+      }
+    }
+    Ok(failed)
+  }
+
+  /// Retry sending the current state to `windows` on a background thread, as set by
+  /// `emit_retry`. Returns false if retries are disabled.
+  fn retry_emits(&self, windows: Vec<String>) -> bool {
+    if self.options.emit_retry.attempts < 2 {
+      return false;
+    }
+    if !self.emit_retries.schedule(windows) {
+      return true;
+    }
+    let (app, retries) = (self.app.clone(), self.emit_retries.clone());
+    std::thread::spawn(move || {
+      let Some(zubridge) = app.try_state::<Zubridge<R>>() else {
+        // Not set up yet, so the first update after setup resyncs them
+        retries.run_pending(|_| true, |_| {});
+        return;
+      };
+      let resend = |window: &str| {
+        // Resynced by a later update in the meantime
+        if !zubridge.missed_revisions.contains(window) {
+          return true;
+        }
+        match zubridge.emit_state_to(window) {
+          Ok(()) => true,
+          // Closed since
+          Err(crate::Error::WindowError(_)) => {
+            zubridge.missed_revisions.forget(window);
+            true
+          }
+          Err(_) => false,
+        }
+      };
+      let gave_up = |window: &str| {
+        let err = crate::Error::EmitError(format!("every attempt to reach {} failed", window));
+        zubridge.options.error_policy.report(format_args!("failed to emit the state"), &err);
+      };
+      retries.run_pending(resend, gave_up);
+    });
+    true
+  }
+
+  /// Send the current state to `window` alone, e.g. after it reported a corrupted replica, along
+  /// with the lazy slices it holds. The update carries the current revision, and no other window
+  /// receives anything
+  pub fn emit_state_to(&self, window: &str) -> crate::Result<()> {
+    self.ensure_window(window)?;
+    let mut state = self.current_state()?;
+    let lazy = self.lazy_slices.split(&mut state);
+    let revision = self.revision();
+    match &self.payload_signer {
+      Some(signer) => {
+        let signed = signer.sign(revision, &state).map_err(|e| crate::Error::SerializationError(e.to_string()))?;
+        self.send_update_to(window, revision, &signed)?;
+      }
+      None => self.send_update_to(window, revision, &state)?,
+    }
+    // The window has the full state again
+    self.missed_revisions.forget(window);
+
+    for (slice, windows) in self.lazy_slices.windows()? {
+      if !windows.iter().any(|held| held == window) {
+        continue;
+      }
+      if let Some(value) = lazy.get(&slice) {
+        self.send_slice_to(window, &slice, value.clone())?;
+      }
+    }
+    Ok(())
+  }
+
+  /// Send the value at the dot-separated `path` to `window` alone, as a `slice-update` event with
+  /// `{ slice: path, value }`
+  pub fn emit_slice_to(&self, window: &str, path: &str) -> crate::Result<()> {
+    self.ensure_window(window)?;
+    let state = self.current_state()?;
+    let value = get_path(&state, path).ok_or_else(|| crate::Error::StateError(format!("no value at {}", path)))?;
+    self.send_slice_to(window, path, value.clone())
+  }
+
+  fn ensure_window(&self, window: &str) -> crate::Result<()> {
+    if self.app.get_webview_window(window).is_none() {
+      return Err(crate::Error::WindowError(format!("no window labelled {}", window)));
+    }
+    Ok(())
+  }
+
+  fn send_slice_to(&self, window: &str, slice: &str, value: JsonValue) -> crate::Result<()> {
+    let payload = serde_json::json!({ "slice": slice, "value": value });
+    self
+      .options
+      .emit_retry
+      .run(|| self.app.emit_to(window, &self.event_names.slice_update, payload.clone()))
+      .map_err(|e| crate::Error::EmitError(e.to_string()))
+  }
+
+  /// Send a state update to `window` through its channel if it's subscribed, or emit it to it
+  fn send_update_to(&self, window: &str, revision: u64, payload: &(impl Serialize + Clone)) -> crate::Result<()> {
+    let value = serde_json::to_value(payload).map_err(|e| crate::Error::SerializationError(e.to_string()))?;
+    if self.subscriptions.publish_to(window, &value, revision)? {
+      return Ok(());
+    }
+    self
+      .options
+      .emit_retry
+      .run(|| self.app.emit_to(window, &self.event_names.state_update, payload.clone()))
+      .map_err(|e| crate::Error::EmitError(e.to_string()))
+  }
+
+  /// Read a state written by the shared memory transport
+  pub(crate) fn read_shared_state(&self, key: &str) -> Option<Vec<u8>> {
+    self.shared_memory.as_ref()?.read(key)
+  }
+}
+
+/// What to do with a dispatched action once it's admitted
+enum Admitted {
+  /// Dispatch it, since it's allowed and doesn't need confirmation.
+  Dispatch(ZubridgeAction),
+  /// Nothing, since it was handled while being admitted. Holds the resulting state.
+  Handled(JsonValue),
+}
+
+/// A thread's open transactions.
+#[derive(Default)]
+struct OpenTransaction {
+  /// How deeply the thread's transactions are nested.
+  depth: usize,
+  /// Whether a state update was held back.
+  suppressed: bool,
+}
+
+/// Ends a transaction when dropped, and publishes the state held back if it was the thread's
+/// last one.
+struct Transaction<'a, R: Runtime> {
+  zubridge: &'a Zubridge<R>,
+}
+
+impl<R: Runtime> Drop for Transaction<'_, R> {
+  fn drop(&mut self) {
+    let zubridge = self.zubridge;
+    let suppressed = {
+      let mut open_transactions = zubridge.open_transactions();
+      let thread = std::thread::current().id();
+      match open_transactions.get_mut(&thread) {
+        Some(open) if open.depth > 1 => {
+          open.depth -= 1;
+          false
+        }
+        _ => open_transactions.remove(&thread).is_some_and(|open| open.suppressed),
+      }
+    };
+    if suppressed {
+      if let Err(e) = zubridge.emit_current_state() {
+        zubridge.options.error_policy.report(format_args!("failed to emit the state after a transaction"), &e);
+      }
+    }
+  }
 }
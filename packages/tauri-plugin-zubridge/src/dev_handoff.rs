@@ -0,0 +1,173 @@
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant, SystemTime};
+
+use crate::models::*;
+use crate::persistence::PersistenceWriter;
+use crate::sensitive::SensitivePaths;
+
+const HANDOFF_FILE: &str = "dev_handoff.json";
+
+/// The state handed from one process to the next when the app is restarted during development,
+/// e.g. by `cargo watch` after a reducer changed. Only available in debug builds.
+///
+/// The state manager's state is written to `dev_handoff.json` in the plugin's data directory
+/// while the app runs, since a restart may kill the process before it shuts down, and restored
+/// with [`StateManager::restore_state`] once the next process loaded its initial state.
+#[derive(Clone, Debug)]
+pub struct DevHandoffConfig {
+    /// How recent the handoff must be to be restored, so launching the app later starts from
+    /// its initial state. Defaults to 1 minute.
+    pub max_age: Duration,
+    /// How often the state is written at most. Defaults to 250 milliseconds.
+    pub write_interval: Duration,
+}
+
+impl Default for DevHandoffConfig {
+    fn default() -> Self {
+        Self {
+            max_age: Duration::from_secs(60),
+            write_interval: Duration::from_millis(250),
+        }
+    }
+}
+
+pub(crate) struct DevHandoff {
+    config: DevHandoffConfig,
+    path: PathBuf,
+    sensitive: Option<Arc<SensitivePaths>>,
+    writer: Arc<PersistenceWriter>,
+    /// The state left by the previous process, until it's restored.
+    previous: Mutex<Option<JsonValue>>,
+    /// The latest committed state.
+    latest: Mutex<Option<Arc<JsonValue>>>,
+    /// The state last written, and when.
+    written: Mutex<Option<(Arc<JsonValue>, Instant)>>,
+}
+
+impl DevHandoff {
+    /// Take over the handoff left by the previous process, if it's recent enough. A stale one
+    /// is removed; a fresh one is kept until it's restored and the state written again.
+    pub(crate) fn new(
+        config: DevHandoffConfig,
+        data_dir: &Path,
+        sensitive: Option<Arc<SensitivePaths>>,
+        writer: Arc<PersistenceWriter>,
+    ) -> Self {
+        let path = data_dir.join(HANDOFF_FILE);
+        let fresh = std::fs::metadata(&path)
+            .and_then(|metadata| metadata.modified())
+            .ok()
+            .and_then(|modified| SystemTime::now().duration_since(modified).ok())
+            .is_some_and(|age| age <= config.max_age);
+        let previous = if fresh {
+            read(&path, sensitive.as_deref())
+        } else {
+            let _ = std::fs::remove_file(&path);
+            None
+        };
+        Self {
+            config,
+            path,
+            sensitive,
+            writer,
+            previous: Mutex::new(previous),
+            latest: Mutex::new(None),
+            written: Mutex::new(None),
+        }
+    }
+
+    pub(crate) fn write_interval(&self) -> Duration {
+        self.config.write_interval
+    }
+
+    /// The state left by the previous process, the first time it's called.
+    pub(crate) fn take_previous(&self) -> Option<JsonValue> {
+        self.previous.lock().ok()?.take()
+    }
+
+    /// Keep `state` for the next process. It's written on the next `flush`.
+    pub(crate) fn store(&self, state: Arc<JsonValue>) {
+        if let Ok(mut latest) = self.latest.lock() {
+            *latest = Some(state);
+        }
+    }
+
+    /// Queue the state kept by `store` for writing if it changed since the last write, or rewrite
+    /// it before it gets too old to be restored while the app sits idle.
+    pub(crate) fn flush(&self) -> crate::Result<()> {
+        let Some(latest) = self.latest.lock().ok().and_then(|latest| latest.clone()) else { return Ok(()) };
+        let mut written = self.written.lock().map_err(|e| crate::Error::StateError(e.to_string()))?;
+        let current = written
+            .as_ref()
+            .is_some_and(|(state, at)| Arc::ptr_eq(state, &latest) && at.elapsed() < self.config.max_age / 2);
+        if current {
+            return Ok(());
+        }
+        *written = Some((latest.clone(), Instant::now()));
+        let mut state = latest.as_ref().clone();
+        if let Some(sensitive) = &self.sensitive {
+            sensitive.encrypt(&mut state)?;
+        }
+        let contents = serde_json::to_string(&state).map_err(|e| crate::Error::SerializationError(e.to_string()))?;
+        self.writer.write(self.path.clone(), contents);
+        Ok(())
+    }
+}
+
+/// The handoff at `path`. An unreadable one, or one encrypted with another key, is ignored.
+fn read(path: &Path, sensitive: Option<&SensitivePaths>) -> Option<JsonValue> {
+    let contents = std::fs::read_to_string(path).ok()?;
+    let mut state = serde_json::from_str(&contents).ok()?;
+    if let Some(sensitive) = sensitive {
+        if let Err(e) = sensitive.decrypt(&mut state) {
+            log::warn!("zubridge: ignoring the dev handoff: {}", e);
+            return None;
+        }
+    }
+    Some(state)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::error_policy::ErrorPolicy;
+    use crate::persistence::PersistenceConfig;
+    use crate::subsystems::Subsystems;
+    use serde_json::json;
+
+    fn handoff(config: DevHandoffConfig, data_dir: &Path) -> (DevHandoff, Subsystems) {
+        let subsystems = Subsystems::default();
+        let writer = PersistenceWriter::start(PersistenceConfig::default(), ErrorPolicy::default(), &subsystems);
+        (DevHandoff::new(config, data_dir, None, writer), subsystems)
+    }
+
+    #[test]
+    fn hands_the_state_to_the_next_process_once() {
+        let dir = tempfile::tempdir().unwrap();
+        let (first, subsystems) = handoff(DevHandoffConfig::default(), dir.path());
+        assert_eq!(first.take_previous(), None);
+        first.store(Arc::new(json!({ "count": 1 })));
+        first.flush().unwrap();
+        subsystems.shutdown(Duration::from_secs(5));
+
+        let (next, _subsystems) = handoff(DevHandoffConfig::default(), dir.path());
+        assert_eq!(next.take_previous(), Some(json!({ "count": 1 })));
+        assert_eq!(next.take_previous(), None);
+    }
+
+    #[test]
+    fn removes_stale_handoffs() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join(HANDOFF_FILE);
+        std::fs::write(&path, r#"{ "count": 1 }"#).unwrap();
+        let config = DevHandoffConfig {
+            max_age: Duration::ZERO,
+            ..Default::default()
+        };
+        std::thread::sleep(Duration::from_millis(10));
+        let (handoff, _subsystems) = handoff(config, dir.path());
+        assert_eq!(handoff.take_previous(), None);
+        assert!(!path.exists());
+    }
+}
@@ -0,0 +1,48 @@
+use std::collections::HashSet;
+use std::sync::Mutex;
+
+/// Re-sends the state to windows whose page was reloaded, e.g. by a `tauri dev` hot reload, so
+/// their store isn't left empty until the next dispatch.
+#[derive(Clone, Debug, Default)]
+pub struct DevReloadConfig {
+    /// An action dispatched with `{ window }` as payload before the state is re-sent, e.g.
+    /// `__RELOAD`, so reducers can reset state tied to the old page. None by default.
+    pub reload_action: Option<String>,
+}
+
+/// The windows that finished loading a page, to tell reloads from first loads.
+#[derive(Default)]
+pub(crate) struct LoadedPages {
+    windows: Mutex<HashSet<String>>,
+}
+
+impl LoadedPages {
+    /// Record that `window` finished loading a page. Returns true if it had loaded one before.
+    pub(crate) fn loaded(&self, window: &str) -> bool {
+        self.windows
+            .lock()
+            .map(|mut windows| !windows.insert(window.to_string()))
+            .unwrap_or(false)
+    }
+
+    pub(crate) fn forget(&self, window: &str) {
+        if let Ok(mut windows) = self.windows.lock() {
+            windows.remove(window);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn tells_reloads_from_first_loads() {
+        let pages = LoadedPages::default();
+        assert!(!pages.loaded("main"));
+        assert!(pages.loaded("main"));
+        assert!(!pages.loaded("settings"));
+        pages.forget("main");
+        assert!(!pages.loaded("main"));
+    }
+}
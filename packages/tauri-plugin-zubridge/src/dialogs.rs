@@ -0,0 +1,134 @@
+use std::collections::BTreeMap;
+use std::sync::{Arc, Mutex};
+
+use crate::models::*;
+
+pub(crate) const OPEN_ACTION: &str = "DIALOG:OPEN";
+pub(crate) const CLOSE_ACTION: &str = "DIALOG:CLOSE";
+pub(crate) const RESULT_ACTION: &str = "DIALOG:RESULT";
+
+/// Sends the result of a natively presented dialog back to the store.
+pub struct DialogResponder {
+    respond: Box<dyn FnOnce(JsonValue) + Send>,
+}
+
+impl DialogResponder {
+    pub(crate) fn new(respond: impl FnOnce(JsonValue) + Send + 'static) -> Self {
+        Self {
+            respond: Box::new(respond),
+        }
+    }
+
+    /// Close the dialog, dispatching `DIALOG:RESULT { id, result }`.
+    pub fn respond(self, result: impl Into<JsonValue>) {
+        (self.respond)(result.into())
+    }
+}
+
+/// Shows native dialogs, e.g. with `tauri-plugin-dialog`, for dialogs opened through the store.
+pub trait DialogPresenter: Send + Sync + 'static {
+    /// Present the dialog `id` if it should be native. Return false to leave it to the frontend.
+    fn present(&self, id: &str, props: &JsonValue, responder: DialogResponder) -> bool;
+}
+
+/// Built-in `DIALOG:OPEN` and `DIALOG:CLOSE` actions, with the open dialogs kept in the
+/// `state_key` slice.
+#[derive(Clone)]
+pub struct DialogsConfig {
+    /// The state key open dialogs are stored under. Defaults to "dialogs".
+    pub state_key: String,
+    /// Presents native dialogs. Without one, every dialog is rendered by the frontend.
+    pub presenter: Option<Arc<dyn DialogPresenter>>,
+}
+
+impl Default for DialogsConfig {
+    fn default() -> Self {
+        Self {
+            state_key: "dialogs".to_string(),
+            presenter: None,
+        }
+    }
+}
+
+/// The open dialogs, keyed by id.
+#[derive(Default)]
+pub(crate) struct Dialogs {
+    open: Mutex<BTreeMap<String, JsonValue>>,
+}
+
+impl Dialogs {
+    pub(crate) fn open(&self, id: &str, props: JsonValue, native: bool) -> crate::Result<()> {
+        let mut open = self.open.lock().map_err(|e| crate::Error::StateError(e.to_string()))?;
+        open.insert(id.to_string(), serde_json::json!({ "props": props, "native": native }));
+        Ok(())
+    }
+
+    pub(crate) fn close(&self, id: &str) -> crate::Result<bool> {
+        let mut open = self.open.lock().map_err(|e| crate::Error::StateError(e.to_string()))?;
+        Ok(open.remove(id).is_some())
+    }
+
+    pub(crate) fn slice(&self) -> crate::Result<JsonValue> {
+        let open = self.open.lock().map_err(|e| crate::Error::StateError(e.to_string()))?;
+        serde_json::to_value(&*open).map_err(|e| crate::Error::SerializationError(e.to_string()))
+    }
+}
+
+pub(crate) fn is_dialog_action(action_type: &str) -> bool {
+    matches!(action_type, OPEN_ACTION | CLOSE_ACTION)
+}
+
+/// The `id` of a dialog action.
+pub(crate) fn dialog_id(action: &ZubridgeAction) -> crate::Result<String> {
+    action
+        .payload
+        .as_ref()
+        .and_then(|payload| payload.get("id"))
+        .and_then(JsonValue::as_str)
+        .map(str::to_string)
+        .ok_or_else(|| crate::Error::StateError(format!("{} requires an id", action.action_type)))
+}
+
+/// The `DIALOG:RESULT` action dispatched to the state manager when a dialog closes.
+pub(crate) fn result_action(id: &str, result: JsonValue) -> ZubridgeAction {
+    ZubridgeAction {
+        action_type: RESULT_ACTION.to_string(),
+        payload: Some(serde_json::json!({ "id": id, "result": result })),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn keeps_open_dialogs_by_id() {
+        let dialogs = Dialogs::default();
+        dialogs.open("confirm", json!({ "title": "Delete?" }), true).unwrap();
+        assert_eq!(
+            dialogs.slice().unwrap(),
+            json!({ "confirm": { "props": { "title": "Delete?" }, "native": true } })
+        );
+        assert!(dialogs.close("confirm").unwrap());
+        assert!(!dialogs.close("confirm").unwrap());
+    }
+
+    #[test]
+    fn responders_dispatch_the_result() {
+        let open = ZubridgeAction {
+            action_type: OPEN_ACTION.into(),
+            payload: Some(json!({ "id": "confirm" })),
+        };
+        let id = dialog_id(&open).unwrap();
+        let (sender, results) = std::sync::mpsc::channel();
+        let responder = DialogResponder::new(move |result| sender.send(result_action(&id, result)).unwrap());
+        responder.respond(true);
+
+        let result = results.recv().unwrap();
+        assert_eq!(result.action_type, RESULT_ACTION);
+        assert_eq!(result.payload, Some(json!({ "id": "confirm", "result": true })));
+        assert!(dialog_id(&result_action("confirm", JsonValue::Null)).is_ok());
+        assert!(dialog_id(&ZubridgeAction { action_type: CLOSE_ACTION.into(), payload: None }).is_err());
+    }
+}
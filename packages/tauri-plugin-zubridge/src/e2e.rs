@@ -0,0 +1,229 @@
+//! A local test driver for end-to-end tests.
+//!
+//! With the `e2e` feature enabled and `ZUBRIDGE_E2E_PORT` set, the plugin listens on
+//! `127.0.0.1:<port>` for newline-delimited JSON commands, so WebDriver or Playwright tests can
+//! assert on the backend state instead of scraping the DOM. Each connection starts by sending
+//! the token from `ZUBRIDGE_E2E_TOKEN`, or the one printed at startup if it isn't set. The driver
+//! only runs in debug builds.
+//!
+//! ```text
+//! > {"command":"auth","token":"<token>"}
+//! < {"ok":true,"result":null}
+//! > {"command":"dispatch","action":{"action_type":"INCREMENT","payload":null}}
+//! < {"ok":true,"result":{"counter":1}}
+//! > {"command":"await","path":"counter","equals":3,"timeout_ms":2000}
+//! < {"ok":false,"error":"Timed out waiting for counter"}
+//! ```
+
+use std::io::{BufRead, BufReader, ErrorKind, Write};
+use std::net::{TcpListener, TcpStream};
+use std::time::Duration;
+
+use serde::{Deserialize, Serialize};
+use tauri::{AppHandle, Manager, Runtime};
+
+use crate::desktop::Zubridge;
+use crate::models::*;
+use crate::subsystems::Subsystems;
+use crate::utils::{get_path, hex, random_key};
+
+/// The environment variable holding the port the driver listens on.
+pub const PORT_VAR: &str = "ZUBRIDGE_E2E_PORT";
+/// The environment variable holding the token connections authenticate with. A random one is
+/// generated and printed when it isn't set.
+pub const TOKEN_VAR: &str = "ZUBRIDGE_E2E_TOKEN";
+
+#[derive(Deserialize)]
+#[serde(tag = "command", rename_all = "kebab-case")]
+enum Command {
+    /// Must come first on every connection.
+    Auth {
+        token: String,
+    },
+    Dispatch {
+        action: ZubridgeAction,
+    },
+    State,
+    /// Wait until the value at `path` equals `equals`, or until it exists if `equals` is missing.
+    Await {
+        path: String,
+        equals: Option<JsonValue>,
+        timeout_ms: Option<u64>,
+    },
+}
+
+#[derive(Serialize)]
+struct Response {
+    ok: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    result: Option<JsonValue>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    error: Option<String>,
+}
+
+impl From<crate::Result<JsonValue>> for Response {
+    fn from(result: crate::Result<JsonValue>) -> Self {
+        match result {
+            Ok(result) => Self { ok: true, result: Some(result), error: None },
+            Err(e) => Self { ok: false, result: None, error: Some(e.to_string()) },
+        }
+    }
+}
+
+/// Start the driver if `ZUBRIDGE_E2E_PORT` is set, in debug builds.
+pub(crate) fn start_from_env<R: Runtime>(subsystems: &Subsystems, app: AppHandle<R>) -> crate::Result<()> {
+    let Ok(port) = std::env::var(PORT_VAR) else { return Ok(()) };
+    if !cfg!(debug_assertions) {
+        log::warn!("zubridge: ignoring {} in a release build", PORT_VAR);
+        return Ok(());
+    }
+    let port: u16 = port
+        .parse()
+        .map_err(|_| crate::Error::ConfigError(format!("{} is not a port: {}", PORT_VAR, port)))?;
+    let token = match std::env::var(TOKEN_VAR) {
+        Ok(token) if !token.is_empty() => token,
+        _ => {
+            let token = hex(&random_key());
+            eprintln!("zubridge: e2e driver token: {}", token);
+            token
+        }
+    };
+
+    // Only accept connections from this machine
+    let listener = TcpListener::bind(("127.0.0.1", port))?;
+    listener.set_nonblocking(true)?;
+    log::info!("zubridge: e2e driver listening on 127.0.0.1:{}", port);
+
+    subsystems.spawn("e2e", move |signal| loop {
+        match listener.accept() {
+            Ok((stream, _)) => {
+                let (app, token) = (app.clone(), token.clone());
+                std::thread::spawn(move || {
+                    if let Err(e) = serve(&app, &token, stream) {
+                        log::warn!("zubridge: e2e connection failed: {}", e);
+                    }
+                });
+            }
+            Err(e) if e.kind() == ErrorKind::WouldBlock => {
+                if signal.sleep(Duration::from_millis(100)) {
+                    return;
+                }
+            }
+            Err(e) => {
+                log::warn!("zubridge: e2e driver stopped: {}", e);
+                return;
+            }
+        }
+    });
+    Ok(())
+}
+
+/// Answer commands on a connection until it's closed, or until it fails to authenticate.
+fn serve<R: Runtime>(app: &AppHandle<R>, token: &str, stream: TcpStream) -> std::io::Result<()> {
+    stream.set_nonblocking(false)?;
+    let mut writer = stream.try_clone()?;
+    let mut authenticated = false;
+    for line in BufReader::new(stream).lines() {
+        let line = line?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        let result = match serde_json::from_str::<Command>(&line) {
+            Ok(Command::Auth { token: sent }) if constant_time_eq(sent.as_bytes(), token.as_bytes()) => {
+                authenticated = true;
+                Ok(JsonValue::Null)
+            }
+            Ok(command) if authenticated => run(app, command),
+            Ok(_) => Err(crate::Error::Forbidden {
+                action_type: "e2e commands".into(),
+                requirement: "the driver token".into(),
+            }),
+            Err(e) => Err(crate::Error::SerializationError(e.to_string())),
+        };
+        let response = Response::from(result);
+        let mut reply = serde_json::to_vec(&response).map_err(|e| std::io::Error::new(ErrorKind::Other, e))?;
+        reply.push(b'\n');
+        writer.write_all(&reply)?;
+        // A wrong token ends the connection, so it can't be guessed on one
+        if !authenticated {
+            break;
+        }
+    }
+    Ok(())
+}
+
+fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    a.len() == b.len() && a.iter().zip(b).fold(0u8, |diff, (x, y)| diff | (x ^ y)) == 0
+}
+
+fn run<R: Runtime>(app: &AppHandle<R>, command: Command) -> crate::Result<JsonValue> {
+    let zubridge = app
+        .try_state::<Zubridge<R>>()
+        .ok_or_else(|| crate::Error::StateError("the plugin is not set up yet".into()))?;
+    match command {
+        Command::Auth { .. } => Ok(JsonValue::Null),
+        Command::Dispatch { action } => zubridge.dispatch_action(action),
+        Command::State => zubridge.get_initial_state(),
+        Command::Await { path, equals, timeout_ms } => {
+            let timeout = Duration::from_millis(timeout_ms.unwrap_or(5000));
+            let matches = |state: &JsonValue| match (get_path(state, &path), &equals) {
+                (Some(value), Some(expected)) => value == expected,
+                (Some(value), None) => !value.is_null(),
+                (None, _) => false,
+            };
+            zubridge.wait_for(matches, timeout).map_err(|e| match e {
+                crate::Error::WaitTimedOut(_) => crate::Error::WaitTimedOut(path.clone()),
+                e => e,
+            })
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Serve one connection on a mock app, returning the replies to `lines`.
+    fn exchange(lines: &[&str]) -> Vec<JsonValue> {
+        let listener = TcpListener::bind(("127.0.0.1", 0)).unwrap();
+        let mut client = TcpStream::connect(listener.local_addr().unwrap()).unwrap();
+        let (stream, _) = listener.accept().unwrap();
+        let server = std::thread::spawn(move || {
+            let app = tauri::test::mock_app();
+            serve(app.handle(), "token", stream).unwrap();
+        });
+        for line in lines {
+            client.write_all(format!("{}\n", line).as_bytes()).unwrap();
+        }
+        client.shutdown(std::net::Shutdown::Write).unwrap();
+        let replies = BufReader::new(client)
+            .lines()
+            .map(|line| serde_json::from_str(&line.unwrap()).unwrap())
+            .collect();
+        server.join().unwrap();
+        replies
+    }
+
+    #[test]
+    fn rejects_commands_before_the_token() {
+        let replies = exchange(&[r#"{"command":"state"}"#, r#"{"command":"auth","token":"token"}"#]);
+        assert_eq!(replies.len(), 1);
+        assert_eq!(replies[0]["ok"], false);
+        assert!(replies[0]["error"].as_str().unwrap().starts_with("Forbidden"));
+    }
+
+    #[test]
+    fn closes_connections_with_a_wrong_token() {
+        let replies = exchange(&[r#"{"command":"auth","token":"guess"}"#, r#"{"command":"state"}"#]);
+        assert_eq!(replies.len(), 1);
+        assert_eq!(replies[0]["ok"], false);
+    }
+
+    #[test]
+    fn runs_commands_after_the_token() {
+        let replies = exchange(&[r#"{"command":"auth","token":"token"}"#, r#"{"command":"state"}"#]);
+        assert_eq!(replies[0]["ok"], true);
+        // Past authentication, to the mock app without the plugin
+        assert_eq!(replies[1]["error"], "State error: the plugin is not set up yet");
+    }
+}
@@ -0,0 +1,308 @@
+//! User-defined automation rules, like "when a download completes, dispatch NOTIFY", with
+//! conditions written in [Rhai](https://rhai.rs) and evaluated under resource limits.
+
+use std::cell::Cell;
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+
+use rhai::{Engine, Scope, AST};
+use serde::{Deserialize, Serialize};
+
+use crate::models::*;
+use crate::utils::glob_matches;
+
+const RULES_FILE: &str = "automation.json";
+
+pub(crate) const SAVE_RULE_ACTION: &str = "AUTOMATION:SAVE_RULE";
+pub(crate) const DELETE_RULE_ACTION: &str = "AUTOMATION:DELETE_RULE";
+
+thread_local! {
+    /// Set while a rule dispatches its actions, so rules don't trigger each other.
+    static RUNNING_RULE: Cell<bool> = const { Cell::new(false) };
+}
+
+pub(crate) fn is_automation_action(action_type: &str) -> bool {
+    matches!(action_type, SAVE_RULE_ACTION | DELETE_RULE_ACTION)
+}
+
+/// Where rules are kept and what they may do.
+#[derive(Clone, Debug)]
+pub struct AutomationConfig {
+    /// Action types rules may dispatch, where `*` matches any run of characters. Nothing is
+    /// allowed when empty.
+    pub allowed_actions: Vec<String>,
+    /// The most operations a condition may run before it's stopped. Defaults to 10,000.
+    pub max_operations: u64,
+    /// The state key rules are stored under. Defaults to "automation".
+    pub state_key: String,
+}
+
+impl Default for AutomationConfig {
+    fn default() -> Self {
+        Self {
+            allowed_actions: Vec::new(),
+            max_operations: 10_000,
+            state_key: "automation".to_string(),
+        }
+    }
+}
+
+/// A rule saved with `AUTOMATION:SAVE_RULE`.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct AutomationRule {
+    pub id: String,
+    #[serde(default)]
+    pub name: String,
+    /// The action types that trigger the rule, where `*` matches any run of characters.
+    pub on: String,
+    /// A Rhai expression that must be true for the rule to fire, with `state` and `action` in scope.
+    #[serde(default)]
+    pub condition: Option<String>,
+    /// The actions dispatched when the rule fires.
+    pub dispatch: Vec<ZubridgeAction>,
+    #[serde(default = "enabled")]
+    pub enabled: bool,
+}
+
+fn enabled() -> bool {
+    true
+}
+
+struct CompiledRule {
+    rule: AutomationRule,
+    condition: Option<Arc<AST>>,
+}
+
+/// The saved rules, persisted to the plugin's data directory.
+pub(crate) struct Automation {
+    config: AutomationConfig,
+    engine: Arc<Engine>,
+    dispatcher: Dispatcher,
+    path: PathBuf,
+    rules: Mutex<BTreeMap<String, CompiledRule>>,
+}
+
+impl Automation {
+    pub(crate) fn load(config: AutomationConfig, data_dir: &Path, dispatcher: Dispatcher) -> Self {
+        let mut engine = Engine::new();
+        engine.set_max_operations(config.max_operations);
+        engine.set_max_expr_depths(32, 32);
+        engine.set_max_string_size(64 * 1024);
+        engine.set_max_array_size(10_000);
+        engine.set_max_map_size(10_000);
+        engine.disable_symbol("eval");
+
+        let path = data_dir.join(RULES_FILE);
+        let saved: Vec<AutomationRule> = std::fs::read_to_string(&path)
+            .ok()
+            .and_then(|contents| serde_json::from_str(&contents).ok())
+            .unwrap_or_default();
+        let mut rules = BTreeMap::new();
+        for rule in saved {
+            match compile(&engine, &config, rule) {
+                Ok(compiled) => {
+                    rules.insert(compiled.rule.id.clone(), compiled);
+                }
+                Err(e) => log::warn!("zubridge: skipping automation rule: {}", e),
+            }
+        }
+        Self {
+            config,
+            engine: Arc::new(engine),
+            dispatcher,
+            path,
+            rules: Mutex::new(rules),
+        }
+    }
+
+    /// Save or delete a rule.
+    pub(crate) fn apply(&self, action: &ZubridgeAction) -> crate::Result<()> {
+        let payload = action.payload.clone().unwrap_or_default();
+        let mut rules = self.rules.lock().map_err(|e| crate::Error::StateError(e.to_string()))?;
+        if action.action_type == SAVE_RULE_ACTION {
+            let rule: AutomationRule = serde_json::from_value(payload)
+                .map_err(|e| crate::Error::ActionRejected(format!("invalid automation rule: {}", e)))?;
+            let compiled = compile(&self.engine, &self.config, rule)?;
+            rules.insert(compiled.rule.id.clone(), compiled);
+        } else {
+            let id = payload.get("id").and_then(JsonValue::as_str).unwrap_or_default();
+            rules.remove(id);
+        }
+        self.persist(&rules)
+    }
+
+    pub(crate) fn state_key(&self) -> &str {
+        &self.config.state_key
+    }
+
+    pub(crate) fn slice(&self) -> crate::Result<JsonValue> {
+        let rules = self.rules.lock().map_err(|e| crate::Error::StateError(e.to_string()))?;
+        let rules: Vec<&AutomationRule> = rules.values().map(|compiled| &compiled.rule).collect();
+        serde_json::to_value(rules).map_err(|e| crate::Error::SerializationError(e.to_string()))
+    }
+
+    /// Fire the rules triggered by a dispatched action. Conditions are evaluated and actions
+    /// dispatched on the blocking thread pool, so they never hold up the dispatch.
+    pub(crate) fn observe(&self, action: &ZubridgeAction, state: &JsonValue) {
+        if RUNNING_RULE.with(Cell::get) {
+            return;
+        }
+        let Ok(rules) = self.rules.lock() else { return };
+        let triggered: Vec<(AutomationRule, Option<Arc<AST>>)> = rules
+            .values()
+            .filter(|compiled| compiled.rule.enabled && glob_matches(&compiled.rule.on, &action.action_type))
+            .map(|compiled| (compiled.rule.clone(), compiled.condition.clone()))
+            .collect();
+        drop(rules);
+        if triggered.is_empty() {
+            return;
+        }
+
+        let engine = self.engine.clone();
+        let (action, state, dispatcher) = (action.clone(), state.clone(), self.dispatcher.clone());
+        tauri::async_runtime::spawn_blocking(move || {
+            RUNNING_RULE.with(|running| running.set(true));
+            for (rule, condition) in triggered {
+                match condition.map(|condition| evaluate(&engine, &condition, &state, &action)) {
+                    Some(Ok(false)) => continue,
+                    Some(Err(e)) => {
+                        log::warn!("zubridge: automation rule {} failed: {}", rule.id, e);
+                        continue;
+                    }
+                    Some(Ok(true)) | None => {}
+                }
+                log::info!(target: "zubridge::audit", "automation rule {} fired on {}", rule.id, action.action_type);
+                for rule_action in rule.dispatch {
+                    if let Err(e) = dispatcher.dispatch(rule_action) {
+                        log::warn!("zubridge: automation rule {} failed to dispatch: {}", rule.id, e);
+                    }
+                }
+            }
+            RUNNING_RULE.with(|running| running.set(false));
+        });
+    }
+
+    fn persist(&self, rules: &BTreeMap<String, CompiledRule>) -> crate::Result<()> {
+        let rules: Vec<&AutomationRule> = rules.values().map(|compiled| &compiled.rule).collect();
+        let contents = serde_json::to_string(&rules).map_err(|e| crate::Error::SerializationError(e.to_string()))?;
+        let temp = self.path.with_extension("json.tmp");
+        std::fs::write(&temp, contents)?;
+        std::fs::rename(&temp, &self.path)?;
+        Ok(())
+    }
+}
+
+/// Check a rule against the allowlist and compile its condition.
+fn compile(engine: &Engine, config: &AutomationConfig, rule: AutomationRule) -> crate::Result<CompiledRule> {
+    if let Some(action) = rule
+        .dispatch
+        .iter()
+        .find(|action| !config.allowed_actions.iter().any(|pattern| glob_matches(pattern, &action.action_type)))
+    {
+        return Err(crate::Error::ActionRejected(format!(
+            "automation rule {} may not dispatch {}",
+            rule.id, action.action_type
+        )));
+    }
+    let condition = rule
+        .condition
+        .as_deref()
+        .map(|condition| engine.compile_expression(condition))
+        .transpose()
+        .map_err(|e| crate::Error::ActionRejected(format!("invalid condition in automation rule {}: {}", rule.id, e)))?;
+    Ok(CompiledRule {
+        rule,
+        condition: condition.map(Arc::new),
+    })
+}
+
+fn evaluate(engine: &Engine, condition: &AST, state: &JsonValue, action: &ZubridgeAction) -> Result<bool, String> {
+    let mut scope = Scope::new();
+    scope.push_dynamic("state", rhai::serde::to_dynamic(state).map_err(|e| e.to_string())?);
+    scope.push_dynamic("action", rhai::serde::to_dynamic(action).map_err(|e| e.to_string())?);
+    engine.eval_ast_with_scope::<bool>(&mut scope, condition).map_err(|e| e.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+    use std::sync::mpsc::{self, Receiver};
+    use std::time::Duration;
+
+    fn automation(data_dir: &Path) -> (Automation, Receiver<String>) {
+        let (sender, dispatched) = mpsc::channel();
+        let sender = Mutex::new(sender);
+        let dispatcher = Dispatcher::new(move |action| {
+            let _ = sender.lock().unwrap().send(action.action_type);
+            Ok(JsonValue::Null)
+        });
+        let config = AutomationConfig {
+            allowed_actions: vec!["NOTIFY:*".into()],
+            ..Default::default()
+        };
+        (Automation::load(config, data_dir, dispatcher), dispatched)
+    }
+
+    fn action(action_type: &str, payload: JsonValue) -> ZubridgeAction {
+        ZubridgeAction {
+            action_type: action_type.into(),
+            payload: Some(payload),
+        }
+    }
+
+    fn save_rule(condition: &str, dispatch: &str) -> ZubridgeAction {
+        let dispatch = json!([{ "action_type": dispatch }]);
+        action(
+            SAVE_RULE_ACTION,
+            json!({ "id": "notify", "on": "DOWNLOAD:*", "condition": condition, "dispatch": dispatch }),
+        )
+    }
+
+    #[test]
+    fn fires_rules_whose_conditions_hold() {
+        let dir = tempfile::tempdir().unwrap();
+        let (automation, dispatched) = automation(dir.path());
+        automation.apply(&save_rule("state.downloads > 1 && action.payload == \"a\"", "NOTIFY:DONE")).unwrap();
+
+        automation.observe(&action("DOWNLOAD:COMPLETE", json!("a")), &json!({ "downloads": 1 }));
+        automation.observe(&action("UPLOAD:COMPLETE", json!("a")), &json!({ "downloads": 2 }));
+        automation.observe(&action("DOWNLOAD:COMPLETE", json!("a")), &json!({ "downloads": 2 }));
+        assert_eq!(dispatched.recv_timeout(Duration::from_secs(5)).unwrap(), "NOTIFY:DONE");
+        assert!(dispatched.recv_timeout(Duration::from_millis(100)).is_err());
+    }
+
+    #[test]
+    fn rejects_disallowed_actions_and_invalid_conditions() {
+        let dir = tempfile::tempdir().unwrap();
+        let (automation, _dispatched) = automation(dir.path());
+        let disallowed = automation.apply(&save_rule("true", "USER:LOGOUT"));
+        assert!(matches!(disallowed, Err(crate::Error::ActionRejected(_))));
+        let invalid = automation.apply(&save_rule("state.downloads >", "NOTIFY:DONE"));
+        assert!(matches!(invalid, Err(crate::Error::ActionRejected(_))));
+        assert_eq!(automation.slice().unwrap(), json!([]));
+    }
+
+    #[test]
+    fn stops_runaway_conditions() {
+        let dir = tempfile::tempdir().unwrap();
+        let (automation, _dispatched) = automation(dir.path());
+        // Conditions are expressions, so only a script can loop
+        let condition = automation.engine.compile("let n = 0; loop { n += 1; }").unwrap();
+        assert!(evaluate(&automation.engine, &condition, &json!({}), &action("TICK", JsonValue::Null)).is_err());
+    }
+
+    #[test]
+    fn persists_saved_rules() {
+        let dir = tempfile::tempdir().unwrap();
+        let (first, _dispatched) = automation(dir.path());
+        first.apply(&save_rule("true", "NOTIFY:DONE")).unwrap();
+        let (reloaded, _dispatched) = automation(dir.path());
+        assert_eq!(reloaded.slice().unwrap()[0]["id"], "notify");
+
+        reloaded.apply(&action(DELETE_RULE_ACTION, json!({ "id": "notify" }))).unwrap();
+        let (reloaded, _dispatched) = automation(dir.path());
+        assert_eq!(reloaded.slice().unwrap(), json!([]));
+    }
+}
@@ -0,0 +1,153 @@
+//! Reuse the results of identical effects for a while instead of running them again.
+
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use serde::Serialize;
+
+use crate::models::*;
+use crate::utils::stable_hash;
+
+/// Hit and miss counts of an [`EffectCache`].
+#[derive(Clone, Copy, Debug, Default, Serialize)]
+pub struct CacheStats {
+    pub hits: u64,
+    pub misses: u64,
+    /// Entries dropped because their TTL passed.
+    pub expired: u64,
+    /// Entries currently cached, including expired ones not yet dropped.
+    pub entries: usize,
+}
+
+/// Results of effects keyed by the action type and a hash of the payload of the action that
+/// triggered them, kept for `ttl`.
+pub struct EffectCache {
+    ttl: Duration,
+    entries: Mutex<HashMap<u64, (Instant, JsonValue)>>,
+    hits: AtomicU64,
+    misses: AtomicU64,
+    expired: AtomicU64,
+}
+
+impl EffectCache {
+    pub(crate) fn new(ttl: Duration) -> Self {
+        Self {
+            ttl,
+            entries: Mutex::new(HashMap::new()),
+            hits: AtomicU64::new(0),
+            misses: AtomicU64::new(0),
+            expired: AtomicU64::new(0),
+        }
+    }
+
+    /// The cached result of the effect for `action`, if it's younger than the TTL.
+    pub fn get(&self, action: &ZubridgeAction) -> Option<JsonValue> {
+        let mut entries = self.entries.lock().ok()?;
+        let key = cache_key(action);
+        let cached = match entries.get(&key) {
+            Some((stored, value)) if stored.elapsed() < self.ttl => Some(value.clone()),
+            Some(_) => {
+                entries.remove(&key);
+                self.expired.fetch_add(1, Ordering::Relaxed);
+                None
+            }
+            None => None,
+        };
+
+        let counter = if cached.is_some() { &self.hits } else { &self.misses };
+        counter.fetch_add(1, Ordering::Relaxed);
+        cached
+    }
+
+    /// Cache the result of the effect for `action`.
+    pub fn insert(&self, action: &ZubridgeAction, result: JsonValue) {
+        let Ok(mut entries) = self.entries.lock() else { return };
+        let before = entries.len();
+        entries.retain(|_, (stored, _)| stored.elapsed() < self.ttl);
+        self.expired.fetch_add((before - entries.len()) as u64, Ordering::Relaxed);
+        entries.insert(cache_key(action), (Instant::now(), result));
+    }
+
+    /// Return the cached result for `action`, or run the effect and cache its result if it succeeds.
+    pub fn get_or_run<E>(&self, action: &ZubridgeAction, run: impl FnOnce() -> Result<JsonValue, E>) -> Result<JsonValue, E> {
+        if let Some(cached) = self.get(action) {
+            return Ok(cached);
+        }
+        let result = run()?;
+        self.insert(action, result.clone());
+        Ok(result)
+    }
+
+    /// Forget the cached result for `action`, e.g. after a mutation made it stale.
+    pub fn invalidate(&self, action: &ZubridgeAction) {
+        if let Ok(mut entries) = self.entries.lock() {
+            entries.remove(&cache_key(action));
+        }
+    }
+
+    pub fn clear(&self) {
+        if let Ok(mut entries) = self.entries.lock() {
+            entries.clear();
+        }
+    }
+
+    pub fn stats(&self) -> CacheStats {
+        CacheStats {
+            hits: self.hits.load(Ordering::Relaxed),
+            misses: self.misses.load(Ordering::Relaxed),
+            expired: self.expired.load(Ordering::Relaxed),
+            entries: self.entries.lock().map(|entries| entries.len()).unwrap_or_default(),
+        }
+    }
+}
+
+fn cache_key(action: &ZubridgeAction) -> u64 {
+    // Object keys are sorted unless serde_json's `preserve_order` is enabled, so equal payloads
+    // serialize the same way
+    let payload = action.payload.as_ref().map(JsonValue::to_string).unwrap_or_default();
+    stable_hash(&format!("{}\0{}", action.action_type, payload))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn fetch(id: u64) -> ZubridgeAction {
+        ZubridgeAction {
+            action_type: "FETCH".into(),
+            payload: Some(json!({ "id": id })),
+        }
+    }
+
+    #[test]
+    fn reuses_results_by_action() {
+        let cache = EffectCache::new(Duration::from_secs(60));
+        let run = |value: u64| move || Ok::<_, ()>(json!(value));
+        assert_eq!(cache.get_or_run(&fetch(1), run(1)), Ok(json!(1)));
+        assert_eq!(cache.get_or_run(&fetch(1), run(2)), Ok(json!(1)));
+        assert_eq!(cache.get_or_run(&fetch(2), run(3)), Ok(json!(3)));
+        // Failed effects aren't cached
+        assert_eq!(cache.get_or_run(&fetch(3), || Err("failed")), Err("failed"));
+
+        cache.invalidate(&fetch(1));
+        assert_eq!(cache.get_or_run(&fetch(1), run(4)), Ok(json!(4)));
+        let stats = cache.stats();
+        assert_eq!((stats.hits, stats.misses, stats.entries), (1, 4, 2));
+    }
+
+    #[test]
+    fn expires_results_after_the_ttl() {
+        let cache = EffectCache::new(Duration::ZERO);
+        cache.insert(&fetch(1), json!(1));
+        assert_eq!(cache.get(&fetch(1)), None);
+        cache.insert(&fetch(2), json!(2));
+        cache.insert(&fetch(3), json!(3));
+        let stats = cache.stats();
+        assert_eq!((stats.expired, stats.entries), (2, 1));
+        cache.clear();
+        assert_eq!(cache.stats().entries, 0);
+    }
+}
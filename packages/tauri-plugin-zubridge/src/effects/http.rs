@@ -0,0 +1,279 @@
+//! The `FETCH`/`SUCCESS`/`FAILURE` triad for HTTP requests.
+//!
+//! [`HttpEffects::fetch_into`] with the prefix `USERS` dispatches `USERS:FETCH { key, method, url }`, performs
+//! the request on the async runtime and then dispatches either `USERS:SUCCESS { key, status, data }`
+//! or `USERS:FAILURE { key, status, error }`. `data` is the parsed JSON body, or the body as a
+//! string if it isn't JSON.
+//!
+//! With `effect_cache_ttl` set, a successful result is reused for identical `FETCH` actions
+//! within the TTL, i.e. for the same key, method and URL: `SUCCESS` is dispatched right away with
+//! `cached: true` and no request is sent.
+
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+
+use reqwest::{Request, RequestBuilder};
+use tauri::async_runtime::JoinHandle;
+
+use crate::effects::cache::EffectCache;
+use crate::models::*;
+
+struct InFlight {
+    id: u64,
+    prefix: String,
+    task: JoinHandle<()>,
+}
+
+/// Performs HTTP requests off-thread and dispatches their results.
+#[derive(Clone)]
+pub struct HttpEffects {
+    dispatcher: Dispatcher,
+    cache: Option<Arc<EffectCache>>,
+    in_flight: Arc<Mutex<HashMap<String, InFlight>>>,
+    next_id: Arc<AtomicU64>,
+}
+
+impl HttpEffects {
+    pub(crate) fn new(dispatcher: Dispatcher, cache: Option<Arc<EffectCache>>) -> Self {
+        Self {
+            dispatcher,
+            cache,
+            in_flight: Arc::default(),
+            next_id: Arc::default(),
+        }
+    }
+
+    /// Perform `request`, dispatching `<prefix>:FETCH`, then `<prefix>:SUCCESS` or
+    /// `<prefix>:FAILURE`. Requests are deduplicated by prefix.
+    pub fn fetch_into(&self, prefix: &str, request: RequestBuilder) -> crate::Result<bool> {
+        self.fetch_keyed(prefix, prefix, request)
+    }
+
+    /// Like [`HttpEffects::fetch_into`], deduplicated by `key` instead, e.g. to fetch several
+    /// users at once under one prefix. The key is included in every payload.
+    ///
+    /// Returns false without sending anything if a request with the same key is in flight.
+    pub fn fetch_keyed(&self, prefix: &str, key: &str, request: RequestBuilder) -> crate::Result<bool> {
+        let mut in_flight = self.in_flight.lock().map_err(|e| crate::Error::StateError(e.to_string()))?;
+        if in_flight.contains_key(key) {
+            return Ok(false);
+        }
+
+        let (client, request) = request.build_split();
+        let request = match request {
+            Ok(request) => request,
+            Err(e) => {
+                self.dispatch(prefix, "FAILURE", serde_json::json!({ "key": key, "status": null, "error": e.to_string() }));
+                return Ok(true);
+            }
+        };
+        let fetch = self.dispatch(
+            prefix,
+            "FETCH",
+            serde_json::json!({ "key": key, "method": request.method().as_str(), "url": request.url().as_str() }),
+        );
+        if let Some(mut data) = self.cache.as_ref().and_then(|cache| cache.get(&fetch)) {
+            if let Some(payload) = data.as_object_mut() {
+                payload.insert("cached".to_string(), true.into());
+            }
+            self.dispatch(prefix, "SUCCESS", data);
+            return Ok(true);
+        }
+
+        let id = self.next_id.fetch_add(1, Ordering::SeqCst);
+        let effects = self.clone();
+        let (prefix, key) = (prefix.to_string(), key.to_string());
+        let task = {
+            let (prefix, key) = (prefix.clone(), key.clone());
+            tauri::async_runtime::spawn(async move {
+                let (suffix, payload) = match perform(client, request).await {
+                    Ok((status, data)) => {
+                        let payload = serde_json::json!({ "key": key, "status": status, "data": data });
+                        if let Some(cache) = &effects.cache {
+                            cache.insert(&fetch, payload.clone());
+                        }
+                        ("SUCCESS", payload)
+                    }
+                    Err((status, error)) => ("FAILURE", serde_json::json!({ "key": key, "status": status, "error": error })),
+                };
+                // Only dispatch if the request wasn't cancelled in the meantime
+                if effects.finish(&key, id) {
+                    effects.dispatch(&prefix, suffix, payload);
+                }
+            })
+        };
+        in_flight.insert(key, InFlight { id, prefix, task });
+        Ok(true)
+    }
+
+    /// Cancel the request in flight for `key`, dispatching `<prefix>:FAILURE` with
+    /// `cancelled: true`. Returns false if there was none.
+    pub fn cancel(&self, key: &str) -> crate::Result<bool> {
+        let removed = {
+            let mut in_flight = self.in_flight.lock().map_err(|e| crate::Error::StateError(e.to_string()))?;
+            in_flight.remove(key)
+        };
+        let Some(request) = removed else { return Ok(false) };
+
+        request.task.abort();
+        self.dispatch(
+            &request.prefix,
+            "FAILURE",
+            serde_json::json!({ "key": key, "status": null, "error": "cancelled", "cancelled": true }),
+        );
+        Ok(true)
+    }
+
+    /// Whether a request is in flight for `key`.
+    pub fn is_pending(&self, key: &str) -> bool {
+        self.in_flight.lock().map(|in_flight| in_flight.contains_key(key)).unwrap_or(false)
+    }
+
+    /// Forget the request `id` for `key`, returning false if it was cancelled or replaced.
+    fn finish(&self, key: &str, id: u64) -> bool {
+        let Ok(mut in_flight) = self.in_flight.lock() else { return false };
+        match in_flight.get(key) {
+            Some(request) if request.id == id => {
+                in_flight.remove(key);
+                true
+            }
+            _ => false,
+        }
+    }
+
+    /// Dispatch `<prefix>:<suffix>`, returning the dispatched action.
+    fn dispatch(&self, prefix: &str, suffix: &str, payload: JsonValue) -> ZubridgeAction {
+        let action = ZubridgeAction {
+            action_type: format!("{}:{}", prefix, suffix),
+            payload: Some(payload),
+        };
+        if let Err(e) = self.dispatcher.dispatch(action.clone()) {
+            log::warn!("zubridge: failed to dispatch {}: {}", action.action_type, e);
+        }
+        action
+    }
+}
+
+/// Send the request, returning the status and body, or the status (if any) and an error.
+async fn perform(client: reqwest::Client, request: Request) -> Result<(u16, JsonValue), (Option<u16>, String)> {
+    let response = client.execute(request).await.map_err(|e| (None, e.to_string()))?;
+    let status = response.status();
+    let body = response.bytes().await.map_err(|e| (Some(status.as_u16()), e.to_string()))?;
+    if !status.is_success() {
+        let error = String::from_utf8_lossy(&body).into_owned();
+        let error = if error.is_empty() { status.to_string() } else { error };
+        return Err((Some(status.as_u16()), error));
+    }
+
+    let data = serde_json::from_slice(&body)
+        .unwrap_or_else(|_| JsonValue::String(String::from_utf8_lossy(&body).into_owned()));
+    Ok((status.as_u16(), data))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+    use std::io::{BufRead, BufReader, Write};
+    use std::net::TcpListener;
+    use std::sync::mpsc::{self, Receiver};
+    use std::time::Duration;
+
+    /// Answer `/users` with JSON, `/slow` never, and anything else with 404, on a local port.
+    fn serve() -> String {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://{}", listener.local_addr().unwrap());
+        std::thread::spawn(move || {
+            for stream in listener.incoming() {
+                let mut reader = BufReader::new(stream.unwrap());
+                std::thread::spawn(move || {
+                    let mut request_line = String::new();
+                    reader.read_line(&mut request_line).unwrap();
+                    let mut header = String::new();
+                    while reader.read_line(&mut header).unwrap() > 2 {
+                        header.clear();
+                    }
+                    let response: &[u8] = match request_line.split(' ').nth(1) {
+                        Some("/users") => b"HTTP/1.1 200 OK\r\ncontent-length: 9\r\n\r\n[\"alice\"]",
+                        Some("/slow") => {
+                            std::thread::sleep(Duration::from_secs(10));
+                            return;
+                        }
+                        _ => b"HTTP/1.1 404 Not Found\r\ncontent-length: 7\r\n\r\nmissing",
+                    };
+                    let _ = reader.get_mut().write_all(response);
+                });
+            }
+        });
+        url
+    }
+
+    fn effects(cache: Option<Arc<EffectCache>>) -> (HttpEffects, Receiver<(String, JsonValue)>) {
+        let (sender, dispatched) = mpsc::channel();
+        let sender = Mutex::new(sender);
+        let dispatcher = Dispatcher::new(move |action| {
+            let _ = sender.lock().unwrap().send((action.action_type, action.payload.unwrap_or_default()));
+            Ok(JsonValue::Null)
+        });
+        (HttpEffects::new(dispatcher, cache), dispatched)
+    }
+
+    fn next(dispatched: &Receiver<(String, JsonValue)>) -> (String, JsonValue) {
+        dispatched.recv_timeout(Duration::from_secs(5)).unwrap()
+    }
+
+    #[test]
+    fn dispatches_fetch_then_success_or_failure() {
+        let url = serve();
+        let (effects, dispatched) = effects(None);
+        let client = reqwest::Client::new();
+
+        assert!(effects.fetch_into("USERS", client.get(format!("{}/users", url))).unwrap());
+        let (action_type, payload) = next(&dispatched);
+        assert_eq!((action_type.as_str(), &payload["method"]), ("USERS:FETCH", &json!("GET")));
+        let (action_type, payload) = next(&dispatched);
+        assert_eq!(action_type, "USERS:SUCCESS");
+        assert_eq!((payload["status"].clone(), payload["data"].clone()), (json!(200), json!(["alice"])));
+        assert!(!effects.is_pending("USERS"));
+
+        effects.fetch_keyed("USERS", "missing", client.get(format!("{}/missing", url))).unwrap();
+        next(&dispatched);
+        let (action_type, payload) = next(&dispatched);
+        assert_eq!(action_type, "USERS:FAILURE");
+        assert_eq!(payload, json!({ "key": "missing", "status": 404, "error": "missing" }));
+    }
+
+    #[test]
+    fn deduplicates_and_cancels_requests_in_flight() {
+        let url = serve();
+        let (effects, dispatched) = effects(None);
+        let client = reqwest::Client::new();
+
+        assert!(effects.fetch_into("USERS", client.get(format!("{}/slow", url))).unwrap());
+        assert!(!effects.fetch_into("USERS", client.get(format!("{}/slow", url))).unwrap());
+        assert_eq!(next(&dispatched).0, "USERS:FETCH");
+        assert!(effects.is_pending("USERS"));
+
+        assert!(effects.cancel("USERS").unwrap());
+        let (action_type, payload) = next(&dispatched);
+        assert_eq!((action_type.as_str(), &payload["cancelled"]), ("USERS:FAILURE", &json!(true)));
+        assert!(!effects.cancel("USERS").unwrap());
+        assert!(dispatched.recv_timeout(Duration::from_millis(100)).is_err());
+    }
+
+    #[test]
+    fn reuses_cached_results() {
+        let url = serve();
+        let (effects, dispatched) = effects(Some(Arc::new(EffectCache::new(Duration::from_secs(60)))));
+        let client = reqwest::Client::new();
+
+        effects.fetch_into("USERS", client.get(format!("{}/users", url))).unwrap();
+        next(&dispatched);
+        assert_eq!(next(&dispatched).1.get("cached"), None);
+        effects.fetch_into("USERS", client.get(format!("{}/users", url))).unwrap();
+        next(&dispatched);
+        let (action_type, payload) = dispatched.try_recv().unwrap();
+        assert_eq!((action_type.as_str(), &payload["cached"]), ("USERS:SUCCESS", &json!(true)));
+    }
+}
@@ -0,0 +1,10 @@
+//! Helpers for side effects that dispatch their results back into the store.
+
+#[cfg(feature = "automation")]
+pub mod automation;
+pub mod cache;
+#[cfg(feature = "reqwest")]
+pub mod http;
+pub mod offsets;
+#[cfg(feature = "search")]
+pub mod search;
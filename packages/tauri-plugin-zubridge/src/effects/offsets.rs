@@ -0,0 +1,169 @@
+//! Remember which effects completed, so effects that must not run twice (sending an email,
+//! writing a file) aren't repeated after a crash or restart.
+
+use std::collections::{BTreeMap, VecDeque};
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+use serde::{Deserialize, Serialize};
+
+const OFFSETS_FILE: &str = "effect_offsets.json";
+
+/// How many completed idempotency keys are remembered per effect. Older keys are forgotten first.
+const MAX_KEYS: usize = 10_000;
+
+/// The progress of one effect.
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+pub struct EffectOffset {
+    /// The highest revision the effect has processed, set with [`EffectOffsets::advance`].
+    pub cursor: Option<u64>,
+    /// Idempotency keys of completed runs, oldest first.
+    pub completed: VecDeque<String>,
+}
+
+/// Completed effects keyed by effect name, persisted to `effect_offsets.json` in the plugin's
+/// data directory before any call that records progress returns.
+pub struct EffectOffsets {
+    path: PathBuf,
+    effects: Mutex<BTreeMap<String, EffectOffset>>,
+}
+
+impl EffectOffsets {
+    /// Read the offsets from `data_dir`. Like sequences, an unreadable file is an error:
+    /// starting over would run completed effects again.
+    pub(crate) fn load(data_dir: &Path) -> crate::Result<Self> {
+        let path = data_dir.join(OFFSETS_FILE);
+        let effects = match std::fs::read_to_string(&path) {
+            Ok(contents) => serde_json::from_str(&contents)
+                .map_err(|e| crate::Error::SerializationError(format!("{}: {}", path.display(), e)))?,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => BTreeMap::new(),
+            Err(e) => return Err(e.into()),
+        };
+        Ok(Self {
+            path,
+            effects: Mutex::new(effects),
+        })
+    }
+
+    /// Run `run` unless `effect` already completed for `key`, e.g. the id of the action that
+    /// triggered it. The key is recorded once `run` succeeds, so a failed run is retried the
+    /// next time. Returns whether `run` was called.
+    ///
+    /// A crash between `run` finishing and the key being recorded runs the effect again, so
+    /// pass the key on to services that accept idempotency keys too.
+    pub fn run_once<E>(&self, effect: &str, key: &str, run: impl FnOnce() -> Result<(), E>) -> Result<bool, E>
+    where
+        E: From<crate::Error>,
+    {
+        if self.is_completed(effect, key)? {
+            return Ok(false);
+        }
+        run()?;
+        self.complete(effect, key)?;
+        Ok(true)
+    }
+
+    /// Whether `effect` completed for `key`.
+    pub fn is_completed(&self, effect: &str, key: &str) -> crate::Result<bool> {
+        let effects = self.effects.lock().map_err(|e| crate::Error::StateError(e.to_string()))?;
+        Ok(effects.get(effect).is_some_and(|offset| offset.completed.iter().any(|completed| completed == key)))
+    }
+
+    /// Record that `effect` completed for `key`.
+    pub fn complete(&self, effect: &str, key: &str) -> crate::Result<()> {
+        self.update(effect, |offset| {
+            if offset.completed.iter().any(|completed| completed == key) {
+                return;
+            }
+            if offset.completed.len() == MAX_KEYS {
+                offset.completed.pop_front();
+            }
+            offset.completed.push_back(key.to_string());
+        })
+    }
+
+    /// The highest revision `effect` has processed, for effects that consume actions in order.
+    pub fn cursor(&self, effect: &str) -> crate::Result<Option<u64>> {
+        let effects = self.effects.lock().map_err(|e| crate::Error::StateError(e.to_string()))?;
+        Ok(effects.get(effect).and_then(|offset| offset.cursor))
+    }
+
+    /// Move the cursor of `effect` to `revision`. The cursor never moves backwards.
+    pub fn advance(&self, effect: &str, revision: u64) -> crate::Result<()> {
+        self.update(effect, |offset| offset.cursor = offset.cursor.max(Some(revision)))
+    }
+
+    /// Forget everything recorded for `effect`, so it runs again for every key.
+    pub fn reset(&self, effect: &str) -> crate::Result<()> {
+        let mut effects = self.effects.lock().map_err(|e| crate::Error::StateError(e.to_string()))?;
+        let mut updated = effects.clone();
+        updated.remove(effect);
+        self.persist(&updated)?;
+        *effects = updated;
+        Ok(())
+    }
+
+    /// The progress of every effect.
+    pub fn all(&self) -> BTreeMap<String, EffectOffset> {
+        self.effects.lock().map(|effects| effects.clone()).unwrap_or_default()
+    }
+
+    fn update(&self, effect: &str, change: impl FnOnce(&mut EffectOffset)) -> crate::Result<()> {
+        let mut effects = self.effects.lock().map_err(|e| crate::Error::StateError(e.to_string()))?;
+        let mut offset = effects.get(effect).cloned().unwrap_or_default();
+        change(&mut offset);
+        let mut updated = effects.clone();
+        updated.insert(effect.to_string(), offset);
+        self.persist(&updated)?;
+        *effects = updated;
+        Ok(())
+    }
+
+    /// Write the offsets to a temporary file and move it into place, so a crash mid-write
+    /// can't lose them.
+    fn persist(&self, effects: &BTreeMap<String, EffectOffset>) -> crate::Result<()> {
+        let contents = serde_json::to_string(effects).map_err(|e| crate::Error::SerializationError(e.to_string()))?;
+        let temp = self.path.with_extension("json.tmp");
+        std::fs::write(&temp, contents)?;
+        std::fs::rename(&temp, &self.path)?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn succeed() -> crate::Result<()> {
+        Ok(())
+    }
+
+    #[test]
+    fn runs_effects_once_per_key_across_restarts() {
+        let dir = tempfile::tempdir().unwrap();
+        let offsets = EffectOffsets::load(dir.path()).unwrap();
+        let failed: Result<bool, crate::Error> =
+            offsets.run_once("email", "a", || Err(crate::Error::StateError("failed".into())));
+        assert!(failed.is_err());
+        assert!(offsets.run_once("email", "a", succeed).unwrap());
+        assert!(!offsets.run_once("email", "a", succeed).unwrap());
+        offsets.advance("email", 5).unwrap();
+        offsets.advance("email", 3).unwrap();
+
+        let offsets = EffectOffsets::load(dir.path()).unwrap();
+        assert!(offsets.is_completed("email", "a").unwrap());
+        assert!(!offsets.is_completed("upload", "a").unwrap());
+        assert_eq!(offsets.cursor("email").unwrap(), Some(5));
+
+        offsets.reset("email").unwrap();
+        assert!(offsets.run_once("email", "a", succeed).unwrap());
+        assert_eq!(offsets.cursor("email").unwrap(), None);
+    }
+
+    #[test]
+    fn fails_to_load_unreadable_offsets() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::write(dir.path().join(OFFSETS_FILE), "{").unwrap();
+        assert!(EffectOffsets::load(dir.path()).is_err());
+    }
+}
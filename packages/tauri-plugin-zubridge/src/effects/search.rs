@@ -0,0 +1,261 @@
+//! Full-text search over collections in the state, backed by an in-memory tantivy index.
+//!
+//! Each [`SearchIndex`] covers the collection at a state path, either an object keyed by id or
+//! a normalized `{ ids, entities }` object. Indexes are brought up to date with the latest state
+//! revision before each search, re-indexing only the entities that changed since.
+
+use std::collections::HashMap;
+use std::sync::Mutex;
+
+use serde::Serialize;
+use tantivy::collector::TopDocs;
+use tantivy::query::QueryParser;
+use tantivy::schema::{Field, Schema, Value, STORED, STRING, TEXT};
+use tantivy::{Index, IndexReader, IndexWriter, ReloadPolicy, TantivyDocument, Term};
+
+use crate::models::*;
+use crate::utils::stable_hash;
+
+/// The memory budget of each index writer.
+const WRITER_MEMORY: usize = 15_000_000;
+
+/// A full-text index over the entities of a collection in the state.
+#[derive(Clone, Debug)]
+pub struct SearchIndex {
+    pub name: String,
+    /// Dot-separated path of the collection in the state.
+    pub path: String,
+    /// Dot-separated paths of the entity fields to index.
+    pub fields: Vec<String>,
+}
+
+impl SearchIndex {
+    pub fn new(name: impl Into<String>, path: impl Into<String>) -> Self {
+        Self {
+            name: name.into(),
+            path: path.into(),
+            fields: Vec::new(),
+        }
+    }
+
+    /// Index `field` of every entity. Non-string values are indexed as JSON.
+    pub fn field(mut self, field: impl Into<String>) -> Self {
+        self.fields.push(field.into());
+        self
+    }
+}
+
+/// A search result, best matches first.
+#[derive(Clone, Debug, Serialize)]
+pub struct SearchHit {
+    pub id: String,
+    pub score: f32,
+}
+
+struct IndexedCollection {
+    config: SearchIndex,
+    index: Index,
+    reader: IndexReader,
+    writer: Mutex<IndexWriter>,
+    id_field: Field,
+    fields: Vec<Field>,
+    /// Hashes of the indexed entities, keyed by id.
+    hashes: Mutex<HashMap<String, u64>>,
+}
+
+impl IndexedCollection {
+    fn new(config: SearchIndex) -> crate::Result<Self> {
+        let mut schema = Schema::builder();
+        let id_field = schema.add_text_field("id", STRING | STORED);
+        let fields = config.fields.iter().map(|field| schema.add_text_field(field, TEXT)).collect();
+        let index = Index::create_in_ram(schema.build());
+        let writer = index.writer_with_num_threads(1, WRITER_MEMORY).map_err(search_error)?;
+        let reader = index
+            .reader_builder()
+            .reload_policy(ReloadPolicy::Manual)
+            .try_into()
+            .map_err(search_error)?;
+        Ok(Self {
+            config,
+            index,
+            reader,
+            writer: Mutex::new(writer),
+            id_field,
+            fields,
+            hashes: Mutex::new(HashMap::new()),
+        })
+    }
+
+    /// Re-index the entities that were added, changed or removed.
+    fn sync(&self, state: &JsonValue) -> crate::Result<()> {
+        let empty = serde_json::Map::new();
+        let entities = entities_at(state, &self.config.path).unwrap_or(&empty);
+        let mut hashes = self.hashes.lock().map_err(|e| crate::Error::StateError(e.to_string()))?;
+        let mut writer = self.writer.lock().map_err(|e| crate::Error::StateError(e.to_string()))?;
+
+        let mut changed = false;
+        let removed: Vec<String> = hashes.keys().filter(|id| !entities.contains_key(*id)).cloned().collect();
+        for id in removed {
+            writer.delete_term(Term::from_field_text(self.id_field, &id));
+            hashes.remove(&id);
+            changed = true;
+        }
+
+        for (id, entity) in entities {
+            let hash = stable_hash(&entity.to_string());
+            if hashes.get(id) == Some(&hash) {
+                continue;
+            }
+            writer.delete_term(Term::from_field_text(self.id_field, id));
+            let mut document = TantivyDocument::default();
+            document.add_text(self.id_field, id);
+            for (path, field) in self.config.fields.iter().zip(&self.fields) {
+                match value_at(entity, path) {
+                    Some(JsonValue::String(text)) => document.add_text(*field, text),
+                    Some(JsonValue::Null) | None => {}
+                    Some(value) => document.add_text(*field, value.to_string()),
+                }
+            }
+            writer.add_document(document).map_err(search_error)?;
+            hashes.insert(id.clone(), hash);
+            changed = true;
+        }
+
+        if changed {
+            writer.commit().map_err(search_error)?;
+            self.reader.reload().map_err(search_error)?;
+        }
+        Ok(())
+    }
+
+    fn search(&self, query: &str, limit: usize) -> crate::Result<Vec<SearchHit>> {
+        let parser = QueryParser::for_index(&self.index, self.fields.clone());
+        // Lenient parsing, since queries usually come straight from a search box
+        let (query, _) = parser.parse_query_lenient(query);
+        let searcher = self.reader.searcher();
+        let top = searcher.search(&query, &TopDocs::with_limit(limit)).map_err(search_error)?;
+
+        let mut hits = Vec::with_capacity(top.len());
+        for (score, address) in top {
+            let document: TantivyDocument = searcher.doc(address).map_err(search_error)?;
+            if let Some(id) = document.get_first(self.id_field).and_then(|value| value.as_str()) {
+                hits.push(SearchHit {
+                    id: id.to_string(),
+                    score,
+                });
+            }
+        }
+        Ok(hits)
+    }
+}
+
+/// The configured search indexes and the state revision they were last synced to.
+pub(crate) struct SearchIndexes {
+    indexes: Vec<IndexedCollection>,
+    synced_revision: Mutex<Option<u64>>,
+}
+
+impl SearchIndexes {
+    pub(crate) fn new(indexes: Vec<SearchIndex>) -> crate::Result<Self> {
+        Ok(Self {
+            indexes: indexes.into_iter().map(IndexedCollection::new).collect::<crate::Result<_>>()?,
+            synced_revision: Mutex::new(None),
+        })
+    }
+
+    /// Search an index, first syncing every index with `state` if the revision moved on.
+    pub(crate) fn search(
+        &self,
+        name: &str,
+        query: &str,
+        limit: usize,
+        revision: u64,
+        state: impl FnOnce() -> crate::Result<JsonValue>,
+    ) -> crate::Result<Vec<SearchHit>> {
+        let index = self
+            .indexes
+            .iter()
+            .find(|index| index.config.name == name)
+            .ok_or_else(|| crate::Error::SearchError(format!("no search index named {}", name)))?;
+
+        {
+            let mut synced_revision = self.synced_revision.lock().map_err(|e| crate::Error::StateError(e.to_string()))?;
+            if *synced_revision != Some(revision) {
+                let state = state()?;
+                for index in &self.indexes {
+                    index.sync(&state)?;
+                }
+                *synced_revision = Some(revision);
+            }
+        }
+        index.search(query, limit)
+    }
+}
+
+fn search_error(error: impl std::fmt::Display) -> crate::Error {
+    crate::Error::SearchError(error.to_string())
+}
+
+fn value_at<'a>(value: &'a JsonValue, path: &str) -> Option<&'a JsonValue> {
+    path.split('.')
+        .filter(|segment| !segment.is_empty())
+        .try_fold(value, |value, segment| value.get(segment))
+}
+
+/// The entities of the collection at `path`, keyed by id.
+fn entities_at<'a>(state: &'a JsonValue, path: &str) -> Option<&'a serde_json::Map<String, JsonValue>> {
+    let collection = value_at(state, path)?;
+    collection.get("entities").unwrap_or(collection).as_object()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn indexes() -> SearchIndexes {
+        let todos = SearchIndex::new("todos", "todos").field("title").field("meta.tags");
+        let notes = SearchIndex::new("notes", "notes").field("body");
+        SearchIndexes::new(vec![todos, notes]).unwrap()
+    }
+
+    fn ids(hits: Vec<SearchHit>) -> Vec<String> {
+        let mut ids: Vec<String> = hits.into_iter().map(|hit| hit.id).collect();
+        ids.sort();
+        ids
+    }
+
+    #[test]
+    fn searches_indexed_fields_of_collections() {
+        let indexes = indexes();
+        let state = json!({
+            "todos": {
+                "1": { "title": "Buy milk", "meta": { "tags": ["errand"] } },
+                "2": { "title": "Write report", "meta": { "tags": ["work"] } },
+            },
+            "notes": { "ids": ["a"], "entities": { "a": { "body": "milk is in the fridge" } } },
+        });
+        let search = |name, query| indexes.search(name, query, 10, 1, || Ok(state.clone())).unwrap();
+        assert_eq!(ids(search("todos", "milk")), ["1"]);
+        assert_eq!(ids(search("todos", "work")), ["2"]);
+        assert_eq!(ids(search("notes", "milk")), ["a"]);
+        // Lenient about syntax errors
+        assert_eq!(ids(search("todos", "milk AND (")), ["1"]);
+        let unknown = indexes.search("users", "milk", 10, 1, || Ok(state.clone()));
+        assert!(matches!(unknown, Err(crate::Error::SearchError(_))));
+    }
+
+    #[test]
+    fn resyncs_changed_and_removed_entities_on_new_revisions() {
+        let indexes = indexes();
+        let first = json!({ "todos": { "1": { "title": "Buy milk" }, "2": { "title": "Buy bread" } } });
+        assert_eq!(ids(indexes.search("todos", "buy", 10, 1, || Ok(first)).unwrap()), ["1", "2"]);
+
+        let second = json!({ "todos": { "1": { "title": "Drink milk" } } });
+        // The same revision isn't synced again
+        let unsynced = indexes.search("todos", "buy", 10, 1, || Ok(second.clone())).unwrap();
+        assert_eq!(ids(unsynced), ["1", "2"]);
+        assert!(ids(indexes.search("todos", "buy", 10, 2, || Ok(second)).unwrap()).is_empty());
+        assert_eq!(ids(indexes.search("todos", "drink", 10, 2, || unreachable!()).unwrap()), ["1"]);
+    }
+}
@@ -0,0 +1,277 @@
+use std::collections::BTreeMap;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+/// How emits that fail, e.g. because a window is being torn down, are retried.
+#[derive(Clone, Debug)]
+pub struct EmitRetryConfig {
+    /// How many times an emit is attempted in total. Defaults to 3.
+    pub attempts: u32,
+    /// How long to wait before the first retry, doubled for every further retry. Defaults to 10ms.
+    pub backoff: Duration,
+}
+
+impl Default for EmitRetryConfig {
+    fn default() -> Self {
+        Self {
+            attempts: 3,
+            backoff: Duration::from_millis(10),
+        }
+    }
+}
+
+impl EmitRetryConfig {
+    /// Run `emit` until it succeeds or the attempts run out, returning the last error. Sleeps
+    /// between attempts, so only for emits to a single window off the dispatch path.
+    pub(crate) fn run(&self, mut emit: impl FnMut() -> tauri::Result<()>) -> tauri::Result<()> {
+        let mut attempt = 1;
+        loop {
+            match emit() {
+                Ok(()) => return Ok(()),
+                Err(e) if attempt >= self.attempts => return Err(e),
+                Err(e) => {
+                    let backoff = self.backoff_before(attempt + 1);
+                    log::debug!("zubridge: emit attempt {} failed, retrying in {:?}: {}", attempt, backoff, e);
+                    std::thread::sleep(backoff);
+                    attempt += 1;
+                }
+            }
+        }
+    }
+
+    /// How long to wait before `attempt`, counting the first one as 1.
+    fn backoff_before(&self, attempt: u32) -> Duration {
+        self.backoff.saturating_mul(2u32.saturating_pow(attempt.saturating_sub(2)))
+    }
+}
+
+#[derive(Default)]
+struct Pending {
+    /// The next attempt for each window, with when it's due.
+    windows: BTreeMap<String, (Instant, u32)>,
+    /// Whether a thread is retrying them.
+    running: bool,
+}
+
+/// Windows a state update failed to reach, retried one by one in the background, so the
+/// dispatch that emitted it doesn't wait and the windows it reached don't get it twice.
+pub(crate) struct EmitRetries {
+    config: EmitRetryConfig,
+    pending: Mutex<Pending>,
+}
+
+impl EmitRetries {
+    pub(crate) fn new(config: EmitRetryConfig) -> Self {
+        Self {
+            config,
+            pending: Mutex::new(Pending::default()),
+        }
+    }
+
+    /// Schedule a retry for the `windows` after their first attempt failed. Returns true if a
+    /// thread has to be started to run it with [`EmitRetries::run_pending`].
+    pub(crate) fn schedule(&self, windows: impl IntoIterator<Item = String>) -> bool {
+        if self.config.attempts < 2 {
+            return false;
+        }
+        let Ok(mut pending) = self.pending.lock() else { return false };
+        let due = Instant::now() + self.config.backoff_before(2);
+        for window in windows {
+            pending.windows.entry(window).or_insert((due, 2));
+        }
+        !pending.windows.is_empty() && !std::mem::replace(&mut pending.running, true)
+    }
+
+    /// Retry the scheduled windows as they come due with `retry`, which returns false if the
+    /// window still wasn't reached, until none are left. Windows out of attempts are passed to
+    /// `gave_up`.
+    pub(crate) fn run_pending(&self, mut retry: impl FnMut(&str) -> bool, mut gave_up: impl FnMut(&str)) {
+        loop {
+            let (window, attempt) = {
+                let Ok(mut pending) = self.pending.lock() else { return };
+                let next = pending.windows.iter().min_by_key(|(_, (due, _))| *due);
+                let Some((window, due)) = next.map(|(window, (due, _))| (window.clone(), *due)) else {
+                    pending.running = false;
+                    return;
+                };
+                let wait = due.saturating_duration_since(Instant::now());
+                if !wait.is_zero() {
+                    drop(pending);
+                    std::thread::sleep(wait);
+                    continue;
+                }
+                let attempt = pending.windows.remove(&window).map_or(2, |(_, attempt)| attempt);
+                (window, attempt)
+            };
+            if retry(&window) {
+                continue;
+            }
+            if attempt >= self.config.attempts {
+                gave_up(&window);
+                continue;
+            }
+            if let Ok(mut pending) = self.pending.lock() {
+                let due = Instant::now() + self.config.backoff_before(attempt + 1);
+                pending.windows.entry(window).or_insert((due, attempt + 1));
+            }
+        }
+    }
+}
+
+/// The first revision each window missed because emitting to it failed. Windows are resynced
+/// with the full state by the next successful emit.
+#[derive(Default)]
+pub(crate) struct MissedRevisions {
+    missed: Mutex<BTreeMap<String, u64>>,
+}
+
+impl MissedRevisions {
+    pub(crate) fn record<'a>(&self, windows: impl IntoIterator<Item = &'a str>, revision: u64) {
+        let Ok(mut missed) = self.missed.lock() else { return };
+        for window in windows {
+            missed.entry(window.to_string()).or_insert(revision);
+        }
+    }
+
+    /// Forget the windows that just received `revision` in full, i.e. all but `failed`.
+    pub(crate) fn resynced(&self, revision: u64, failed: &[String]) {
+        let Ok(mut missed) = self.missed.lock() else { return };
+        missed.retain(|window, first| {
+            if failed.contains(window) {
+                return true;
+            }
+            log::info!("zubridge: resynced {} at revision {} after missing revision {}", window, revision, first);
+            false
+        });
+    }
+
+    pub(crate) fn contains(&self, window: &str) -> bool {
+        self.missed.lock().is_ok_and(|missed| missed.contains_key(window))
+    }
+
+    pub(crate) fn forget(&self, window: &str) {
+        if let Ok(mut missed) = self.missed.lock() {
+            missed.remove(window);
+        }
+    }
+
+    pub(crate) fn all(&self) -> BTreeMap<String, u64> {
+        self.missed.lock().map(|missed| missed.clone()).unwrap_or_default()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn retries(attempts: u32) -> EmitRetries {
+        EmitRetries::new(EmitRetryConfig {
+            attempts,
+            backoff: Duration::from_millis(1),
+        })
+    }
+
+    #[test]
+    fn retries_each_window_until_it_is_reached() {
+        let retries = retries(3);
+        assert!(retries.schedule(["a".to_string(), "b".to_string()]));
+        // Already running
+        assert!(!retries.schedule(["a".to_string()]));
+
+        let mut attempts = BTreeMap::new();
+        let mut gave_up = Vec::new();
+        retries.run_pending(
+            |window| {
+                let attempt = attempts.entry(window.to_string()).or_insert(1);
+                *attempt += 1;
+                window == "a" || *attempt == 3
+            },
+            |window| gave_up.push(window.to_string()),
+        );
+        assert_eq!(attempts, BTreeMap::from([("a".to_string(), 2), ("b".to_string(), 3)]));
+        assert!(gave_up.is_empty());
+        // Done, so the next failure starts a thread again
+        assert!(retries.schedule(["a".to_string()]));
+    }
+
+    #[test]
+    fn gives_up_after_the_last_attempt() {
+        let retries = retries(3);
+        retries.schedule(["a".to_string()]);
+        let mut tried = 0;
+        let mut gave_up = Vec::new();
+        retries.run_pending(
+            |_| {
+                tried += 1;
+                false
+            },
+            |window| gave_up.push(window.to_string()),
+        );
+        // The first of the 3 attempts was the emit that failed
+        assert_eq!(tried, 2);
+        assert_eq!(gave_up, ["a"]);
+    }
+
+    #[test]
+    fn doubles_the_backoff_for_every_retry() {
+        let config = EmitRetryConfig { attempts: 5, backoff: Duration::from_millis(10) };
+        let backoffs: Vec<u128> = (2..=5).map(|attempt| config.backoff_before(attempt).as_millis()).collect();
+        assert_eq!(backoffs, [10, 20, 40, 80]);
+        // Saturates instead of overflowing
+        assert_eq!(config.backoff_before(u32::MAX), config.backoff * u32::MAX);
+    }
+
+    #[test]
+    fn retries_emits_until_they_succeed_or_run_out_of_attempts() {
+        let config = EmitRetryConfig { attempts: 3, backoff: Duration::from_millis(5) };
+        let mut tried = 0;
+        let started = Instant::now();
+        let emitted = config.run(|| {
+            tried += 1;
+            if tried < 3 { Err(tauri::Error::WebviewNotFound) } else { Ok(()) }
+        });
+        assert!(emitted.is_ok());
+        assert_eq!(tried, 3);
+        // Waited 5ms, then 10ms
+        assert!(started.elapsed() >= Duration::from_millis(15));
+
+        tried = 0;
+        let emitted = config.run(|| {
+            tried += 1;
+            Err(tauri::Error::WebviewNotFound)
+        });
+        assert!(matches!(emitted, Err(tauri::Error::WebviewNotFound)));
+        assert_eq!(tried, 3);
+    }
+
+    #[test]
+    fn waits_out_the_backoff_before_retrying_a_window() {
+        let retries = EmitRetries::new(EmitRetryConfig { attempts: 3, backoff: Duration::from_millis(5) });
+        let started = Instant::now();
+        retries.schedule(["a".to_string()]);
+        let mut retried_after = Vec::new();
+        retries.run_pending(
+            |_| {
+                retried_after.push(started.elapsed());
+                false
+            },
+            |_| {},
+        );
+        assert_eq!(retried_after.len(), 2);
+        assert!(retried_after[0] >= Duration::from_millis(5));
+        assert!(retried_after[1] - retried_after[0] >= Duration::from_millis(10));
+    }
+
+    #[test]
+    fn schedules_nothing_without_retries() {
+        assert!(!retries(1).schedule(["a".to_string()]));
+    }
+
+    #[test]
+    fn keeps_windows_that_missed_the_update_they_failed() {
+        let missed = MissedRevisions::default();
+        missed.record(["a", "b"], 4);
+        missed.resynced(5, &["b".to_string()]);
+        assert_eq!(missed.all(), BTreeMap::from([("b".to_string(), 4)]));
+    }
+}
@@ -0,0 +1,69 @@
+use std::collections::BTreeMap;
+use std::sync::Mutex;
+
+use crate::models::*;
+use crate::utils::glob_matches;
+
+/// Holds transient values (cursor positions, drag previews, meters) shared between windows.
+///
+/// Values live outside the state tree: they're never passed to the state manager and are
+/// published on their own event, so high-frequency updates don't touch the durable store.
+pub(crate) struct EphemeralChannel {
+    paths: Vec<String>,
+    values: Mutex<BTreeMap<String, JsonValue>>,
+}
+
+impl EphemeralChannel {
+    pub(crate) fn new(paths: Vec<String>) -> Self {
+        Self {
+            paths,
+            values: Mutex::new(BTreeMap::new()),
+        }
+    }
+
+    fn allowed(&self, path: &str) -> bool {
+        self.paths.iter().any(|pattern| glob_matches(pattern, path))
+    }
+
+    /// Store a value, or clear it when `value` is null.
+    pub(crate) fn set(&self, path: &str, value: JsonValue) -> crate::Result<()> {
+        if !self.allowed(path) {
+            return Err(crate::Error::NotEphemeral(path.to_string()));
+        }
+
+        let mut values = self.values.lock().map_err(|e| crate::Error::StateError(e.to_string()))?;
+        if value.is_null() {
+            values.remove(path);
+        } else {
+            values.insert(path.to_string(), value);
+        }
+        Ok(())
+    }
+
+    pub(crate) fn get(&self, path: &str) -> crate::Result<Option<JsonValue>> {
+        let values = self.values.lock().map_err(|e| crate::Error::StateError(e.to_string()))?;
+        Ok(values.get(path).cloned())
+    }
+
+    pub(crate) fn all(&self) -> crate::Result<BTreeMap<String, JsonValue>> {
+        let values = self.values.lock().map_err(|e| crate::Error::StateError(e.to_string()))?;
+        Ok(values.clone())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn stores_values_under_allowed_paths() {
+        let channel = EphemeralChannel::new(vec!["cursor.*".into()]);
+        channel.set("cursor.main", json!({ "x": 1 })).unwrap();
+        assert_eq!(channel.get("cursor.main").unwrap(), Some(json!({ "x": 1 })));
+        assert!(matches!(channel.set("todos", json!([])), Err(crate::Error::NotEphemeral(_))));
+
+        channel.set("cursor.main", JsonValue::Null).unwrap();
+        assert!(channel.all().unwrap().is_empty());
+    }
+}
@@ -1,4 +1,11 @@
-use serde::{ser::Serializer, Serialize};
+use serde::{
+  ser::{SerializeStruct, Serializer},
+  Serialize,
+};
+
+use crate::breadcrumbs::Breadcrumb;
+#[cfg(any(test, feature = "test"))]
+use crate::models::JsonValue;
 
 pub type Result<T> = std::result::Result<T, Error>;
 
@@ -6,6 +13,8 @@ pub type Result<T> = std::result::Result<T, Error>;
 pub enum Error {
   #[error(transparent)]
   Io(#[from] std::io::Error),
+  #[error(transparent)]
+  Tauri(#[from] tauri::Error),
   #[cfg(mobile)]
   #[error(transparent)]
   PluginInvoke(#[from] tauri::plugin::mobile::PluginInvokeError),
@@ -18,6 +27,154 @@ pub enum Error {
 
   #[error("Serialization error: {0}")]
   SerializationError(String),
+
+  #[error("File watch error: {0}")]
+  WatchError(String),
+
+  #[error("Config file error: {0}")]
+  ConfigError(String),
+
+  #[error("Unknown feature flag: {0}")]
+  UnknownFlag(String),
+
+  #[error("Action rejected: {0}")]
+  ActionRejected(String),
+
+  #[error("Invalid action at {path}: {message}")]
+  InvalidAction { path: String, message: String },
+
+  #[error("Invalid state at {path}: {message}")]
+  InvalidState { path: String, message: String },
+
+  #[error("Path is not configured as ephemeral: {0}")]
+  NotEphemeral(String),
+
+  #[error("Slice is not configured as lazy: {0}")]
+  UnknownSlice(String),
+
+  #[error("Reducer panicked: {message}")]
+  ReducerPanicked { message: String },
+
+  #[error("Interceptor panicked: {message}")]
+  InterceptorPanicked { message: String },
+
+  #[error("Window is read-only: {0}")]
+  ReadOnlyWindow(String),
+
+  #[error("Deep link rejected: {0}")]
+  DeepLinkRejected(String),
+
+  #[error("Window error: {0}")]
+  WindowError(String),
+
+  #[error("Statechart {chart} has no transition for {event} from {state}")]
+  IllegalTransition { chart: String, state: String, event: String },
+
+  #[error("Request {topic} to {window} timed out")]
+  RequestTimedOut { window: String, topic: String },
+
+  #[error("Request failed: {0}")]
+  RequestFailed(String),
+
+  #[error("Window {window} can't answer request {id}, which was sent to another window")]
+  ResponseRejected { id: String, window: String },
+
+  /// A command invoked over IPC by [`crate::test::MockApp`] failed, with the error it returned
+  #[cfg(any(test, feature = "test"))]
+  #[error("Command failed: {0}")]
+  CommandFailed(JsonValue),
+
+  #[error("Unknown snapshot: {0}")]
+  UnknownSnapshot(String),
+
+  #[error("The state manager doesn't support simulating actions")]
+  SimulationUnsupported,
+
+  #[error("Action {action_type} requires confirmation: {token}")]
+  ConfirmationRequired { action_type: String, token: String },
+
+  #[error("Unknown or expired confirmation: {0}")]
+  UnknownConfirmation(String),
+
+  #[error("Search error: {0}")]
+  SearchError(String),
+
+  #[error("Extension error: {0}")]
+  ExtensionError(String),
+
+  #[cfg(feature = "mqtt")]
+  #[error("MQTT error: {0}")]
+  MqttError(String),
+
+  #[cfg(feature = "wasm-reducers")]
+  #[error("WASM reducer error: {0}")]
+  WasmError(String),
+
+  #[cfg(feature = "secrets")]
+  #[error("Keychain error: {0}")]
+  SecretsError(String),
+
+  #[cfg(feature = "secrets")]
+  #[error("Unknown secret: {0}")]
+  UnknownSecret(String),
+
+  #[cfg(any(feature = "sqlite", feature = "postgres"))]
+  #[error("Database error: {0}")]
+  Database(#[from] sqlx::Error),
+
+  #[error("Platform preferences error: {0}")]
+  PrefsError(String),
+
+  #[error("The state manager doesn't support restoring a previous state")]
+  RollbackUnsupported,
+
+  #[error("Timed out waiting for {0}")]
+  WaitTimedOut(String),
+
+  #[error("Unknown fixture: {0}")]
+  UnknownFixture(String),
+
+  #[error("Batch rolled back after action {index} failed: {message}")]
+  BatchRolledBack { index: usize, message: String },
+
+  #[error("The initial state is still loading")]
+  StateLoading,
+
+  #[error("Failed to load the initial state: {0}")]
+  InitialStateFailed(String),
+
+  #[error("Window {0} already completed the handshake for this page")]
+  HandshakeRejected(String),
+
+  #[error("The frontend speaks protocol {frontend}, but tauri-plugin-zubridge {plugin_version} speaks protocol {backend}; update both packages together")]
+  ProtocolMismatch { frontend: u32, backend: u32, plugin_version: &'static str },
+
+  #[error("Payload of {action_type} is {bytes} bytes, over the {limit} byte limit; send it with zubridge.upload-blob")]
+  ActionTooLarge { action_type: String, bytes: usize, limit: usize },
+
+  #[error("Upload error: {0}")]
+  UploadError(String),
+
+  #[error("Upload is {bytes} bytes, over the {limit} byte limit")]
+  UploadTooLarge { bytes: u64, limit: u64 },
+
+  #[error("Unknown blob: {0}")]
+  UnknownBlob(String),
+
+  #[error("Failed to decrypt the sensitive value at {0}")]
+  DecryptionFailed(String),
+
+  #[error("The session is locked: {0} rejected")]
+  SessionLocked(String),
+
+  #[error("Forbidden: {action_type} requires {requirement}")]
+  Forbidden { action_type: String, requirement: String },
+
+  #[error("Slice {0} is already part of the state")]
+  SliceConflict(String),
+
+  #[error("{error}")]
+  Traced { error: Box<Error>, breadcrumbs: Vec<Breadcrumb> },
 }
 
 impl Serialize for Error {
@@ -25,6 +182,15 @@ impl Serialize for Error {
   where
     S: Serializer,
   {
-    serializer.serialize_str(self.to_string().as_ref())
+    match self {
+      // Sent to windows as `{ message, breadcrumbs }` so they can see where the dispatch failed
+      Error::Traced { error, breadcrumbs } => {
+        let mut envelope = serializer.serialize_struct("Error", 2)?;
+        envelope.serialize_field("message", &error.to_string())?;
+        envelope.serialize_field("breadcrumbs", breadcrumbs)?;
+        envelope.end()
+      }
+      _ => serializer.serialize_str(self.to_string().as_ref()),
+    }
   }
 }
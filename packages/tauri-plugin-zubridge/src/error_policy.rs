@@ -0,0 +1,68 @@
+use std::fmt::{Arguments, Display};
+
+/// How the plugin handles failures that aren't the direct result of a call, such as emitting
+/// the state after it was updated, persisting plugin data, panicking interceptors and failures
+/// in background threads.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum ErrorPolicy {
+    /// Return failures to the caller. Failures in background work, which have no caller, are
+    /// logged.
+    #[default]
+    Propagate,
+    /// Log failures and carry on where possible, e.g. keep an update the state manager already
+    /// applied even if it couldn't be emitted, and skip a panicking interceptor.
+    LogAndContinue,
+    /// Return failures to the caller, and panic on failures in background work instead of logging
+    /// them. Useful in development and tests, so no failure goes unnoticed.
+    Strict,
+}
+
+impl ErrorPolicy {
+    /// Handle the failure of a step the caller can do without.
+    pub(crate) fn recover(self, context: Arguments, result: crate::Result<()>) -> crate::Result<()> {
+        match (self, result) {
+            (ErrorPolicy::LogAndContinue, Err(e)) => {
+                log::warn!("zubridge: {}: {}", context, e);
+                Ok(())
+            }
+            (_, result) => result,
+        }
+    }
+
+    /// Handle a failure in background work, which has no caller to return it to.
+    pub(crate) fn report(self, context: Arguments, error: &dyn Display) {
+        match self {
+            ErrorPolicy::Strict => panic!("zubridge: {}: {}", context, error),
+            _ => log::warn!("zubridge: {}: {}", context, error),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn failed() -> crate::Result<()> {
+        Err(crate::Error::StateError("failed".into()))
+    }
+
+    #[test]
+    fn only_log_and_continue_recovers() {
+        assert!(ErrorPolicy::LogAndContinue.recover(format_args!("emit"), failed()).is_ok());
+        assert!(ErrorPolicy::Propagate.recover(format_args!("emit"), failed()).is_err());
+        assert!(ErrorPolicy::Strict.recover(format_args!("emit"), failed()).is_err());
+        assert!(ErrorPolicy::Strict.recover(format_args!("emit"), Ok(())).is_ok());
+    }
+
+    #[test]
+    #[should_panic(expected = "zubridge: sync: failed")]
+    fn strict_panics_on_background_failures() {
+        ErrorPolicy::Strict.report(format_args!("sync"), &"failed");
+    }
+
+    #[test]
+    fn other_policies_log_background_failures() {
+        ErrorPolicy::Propagate.report(format_args!("sync"), &"failed");
+        ErrorPolicy::LogAndContinue.report(format_args!("sync"), &"failed");
+    }
+}
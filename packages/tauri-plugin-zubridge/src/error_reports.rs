@@ -0,0 +1,51 @@
+use std::sync::Arc;
+
+use serde::{Deserialize, Serialize};
+
+use crate::action_log::ActionLogEntry;
+
+/// An error caught in a window and passed to `zubridge.report-error`.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct FrontendError {
+    pub message: String,
+    pub stack: Option<String>,
+    /// The component that threw, e.g. from a React error boundary.
+    pub component: Option<String>,
+    /// The state revision the window had rendered when the error happened.
+    pub revision: Option<u64>,
+}
+
+/// A frontend error enriched by the plugin.
+#[derive(Clone, Debug, Serialize)]
+pub struct ErrorReport {
+    pub error: FrontendError,
+    /// The label of the window that reported the error.
+    pub window: String,
+    /// The revision of the most recently emitted state.
+    pub current_revision: u64,
+    pub recent_actions: Vec<ActionLogEntry>,
+    /// Milliseconds since the Unix epoch.
+    pub timestamp: u64,
+}
+
+/// Receives frontend error reports. Implement this to forward them to your error tracker.
+pub trait ErrorSink: Send + Sync + 'static {
+    fn report(&self, report: &ErrorReport);
+}
+
+/// Configures where errors reported by windows are sent.
+#[derive(Clone)]
+pub struct ErrorReportingConfig {
+    pub(crate) sink: Arc<dyn ErrorSink>,
+    /// Include action payloads in the recent action log. Defaults to false.
+    pub include_action_payloads: bool,
+}
+
+impl ErrorReportingConfig {
+    pub fn new<S: ErrorSink>(sink: S) -> Self {
+        Self {
+            sink: Arc::new(sink),
+            include_action_payloads: false,
+        }
+    }
+}
@@ -0,0 +1,84 @@
+use serde::Serialize;
+
+use crate::models::*;
+
+const LEGACY_PREFIX: &str = "zubridge://";
+
+/// The names of the events the plugin emits, as returned by `zubridge.get-event-names`.
+///
+/// By default they're scoped to the app identifier, e.g. `com_example_app/zubridge/state-update`,
+/// so a webview hosting content from several zubridge-enabled apps doesn't receive the others'
+/// events. Tauri doesn't allow `.` in event names, so it's replaced with `_`. With
+/// `legacy_event_names`, they're the `zubridge://` names of the `*_EVENT` constants.
+#[derive(Clone, Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct EventNames {
+    pub state_update: String,
+    pub import_progress: String,
+    pub ephemeral_update: String,
+    pub slice_update: String,
+    pub diagnostics: String,
+    pub startup: String,
+    pub request: String,
+    pub message: String,
+}
+
+impl EventNames {
+    pub(crate) fn new(identifier: &str, options: &ZubridgeOptions) -> Self {
+        let prefix = if options.legacy_event_names {
+            LEGACY_PREFIX.to_string()
+        } else {
+            format!("{}/zubridge/", scope(identifier))
+        };
+        let name = |event: &str| format!("{}{}", prefix, event.strip_prefix(LEGACY_PREFIX).unwrap_or(event));
+        Self {
+            state_update: options.event_name.clone().unwrap_or_else(|| name(crate::STATE_UPDATE_EVENT)),
+            import_progress: name(crate::IMPORT_PROGRESS_EVENT),
+            ephemeral_update: name(crate::EPHEMERAL_UPDATE_EVENT),
+            slice_update: name(crate::SLICE_UPDATE_EVENT),
+            diagnostics: name(crate::DIAGNOSTICS_EVENT),
+            startup: name(crate::STARTUP_EVENT),
+            request: name(crate::REQUEST_EVENT),
+            message: name(crate::MESSAGE_EVENT),
+        }
+    }
+}
+
+/// `identifier` with the characters Tauri rejects in event names replaced.
+fn scope(identifier: &str) -> String {
+    identifier
+        .chars()
+        .map(|c| if c.is_ascii_alphanumeric() || matches!(c, '-' | '_') { c } else { '_' })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn scopes_names_to_the_app_identifier() {
+        let names = EventNames::new("com.example.app", &ZubridgeOptions::default());
+        assert_eq!(names.state_update, "com_example_app/zubridge/state-update");
+        assert_eq!(names.message, "com_example_app/zubridge/message");
+    }
+
+    #[test]
+    fn keeps_legacy_and_custom_names() {
+        let options = ZubridgeOptions {
+            legacy_event_names: true,
+            ..Default::default()
+        };
+        let names = EventNames::new("com.example.app", &options);
+        assert_eq!(names.state_update, crate::STATE_UPDATE_EVENT);
+        assert_eq!(names.diagnostics, crate::DIAGNOSTICS_EVENT);
+
+        let options = ZubridgeOptions {
+            event_name: Some("state".into()),
+            ..Default::default()
+        };
+        let names = EventNames::new("com.example.app", &options);
+        assert_eq!(names.state_update, "state");
+        assert_eq!(names.startup, "com_example_app/zubridge/startup");
+    }
+}
@@ -0,0 +1,164 @@
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+
+use crate::utils::stable_hash;
+
+const ASSIGNMENTS_FILE: &str = "experiments.json";
+
+/// A variant of an [`Experiment`] with its relative weight.
+#[derive(Clone, Debug)]
+pub struct ExperimentVariant {
+    pub name: String,
+    pub weight: u32,
+}
+
+/// An A/B experiment whose variant is assigned deterministically from the install id.
+///
+/// Assignments are persisted, so changing the weights later doesn't move existing installs
+/// between variants.
+#[derive(Clone, Debug)]
+pub struct Experiment {
+    pub name: String,
+    pub variants: Vec<ExperimentVariant>,
+    /// A feature flag overridden with the assigned variant name.
+    pub flag: Option<String>,
+}
+
+impl Experiment {
+    pub fn new(name: impl Into<String>) -> Self {
+        Self {
+            name: name.into(),
+            variants: Vec::new(),
+            flag: None,
+        }
+    }
+
+    /// Add a variant with the given relative weight.
+    pub fn variant(mut self, name: impl Into<String>, weight: u32) -> Self {
+        self.variants.push(ExperimentVariant {
+            name: name.into(),
+            weight,
+        });
+        self
+    }
+
+    /// Drive a feature flag from this experiment; the flag takes the assigned variant name as its value.
+    pub fn bind_flag(mut self, flag: impl Into<String>) -> Self {
+        self.flag = Some(flag.into());
+        self
+    }
+
+    fn has_variant(&self, name: &str) -> bool {
+        self.variants.iter().any(|variant| variant.name == name)
+    }
+
+    /// Pick a variant by hashing the install id together with the experiment name.
+    fn bucket(&self, install_id: &str) -> Option<&ExperimentVariant> {
+        let total: u64 = self.variants.iter().map(|variant| u64::from(variant.weight)).sum();
+        if total == 0 {
+            return None;
+        }
+
+        let mut point = stable_hash(&format!("{}:{}", install_id, self.name)) % total;
+        self.variants.iter().find(|variant| {
+            let weight = u64::from(variant.weight);
+            if point < weight {
+                true
+            } else {
+                point -= weight;
+                false
+            }
+        })
+    }
+}
+
+/// Experiment assignments for this install, keyed by experiment name.
+pub(crate) struct Assignments {
+    path: PathBuf,
+    assigned: BTreeMap<String, String>,
+}
+
+impl Assignments {
+    /// Load persisted assignments from `dir` and assign any experiments that don't have one yet.
+    pub(crate) fn load(dir: &Path, install_id: &str, experiments: &[Experiment]) -> crate::Result<Self> {
+        let path = dir.join(ASSIGNMENTS_FILE);
+        let mut assigned: BTreeMap<String, String> = std::fs::read_to_string(&path)
+            .ok()
+            .and_then(|contents| serde_json::from_str(&contents).ok())
+            .unwrap_or_default();
+
+        let mut changed = false;
+        for experiment in experiments {
+            let still_valid = assigned
+                .get(&experiment.name)
+                .is_some_and(|variant| experiment.has_variant(variant));
+            if still_valid {
+                continue;
+            }
+            if let Some(variant) = experiment.bucket(install_id) {
+                assigned.insert(experiment.name.clone(), variant.name.clone());
+                changed = true;
+            }
+        }
+
+        let assignments = Self { path, assigned };
+        if changed {
+            assignments.save()?;
+        }
+        Ok(assignments)
+    }
+
+    pub(crate) fn get(&self, experiment: &str) -> Option<&str> {
+        self.assigned.get(experiment).map(String::as_str)
+    }
+
+    pub(crate) fn all(&self) -> &BTreeMap<String, String> {
+        &self.assigned
+    }
+
+    fn save(&self) -> crate::Result<()> {
+        let contents =
+            serde_json::to_string_pretty(&self.assigned).map_err(|e| crate::Error::SerializationError(e.to_string()))?;
+        std::fs::write(&self.path, contents)?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn experiment() -> Experiment {
+        Experiment::new("onboarding").variant("control", 1).variant("wizard", 1)
+    }
+
+    #[test]
+    fn assigns_the_same_variant_to_an_install() {
+        let experiment = experiment();
+        let variant = experiment.bucket("install").unwrap().name.clone();
+        assert_eq!(experiment.bucket("install").unwrap().name, variant);
+        assert!(Experiment::new("empty").variant("never", 0).bucket("install").is_none());
+    }
+
+    #[test]
+    fn keeps_persisted_assignments_when_weights_change() {
+        let dir = tempfile::tempdir().unwrap();
+        let assigned = Assignments::load(dir.path(), "install", &[experiment()]).unwrap();
+        let variant = assigned.get("onboarding").unwrap().to_string();
+
+        // Every install would now get the other variant
+        let other = if variant == "control" { "wizard" } else { "control" };
+        let reweighted = Experiment::new("onboarding").variant(&variant, 0).variant(other, 1);
+        let reloaded = Assignments::load(dir.path(), "install", &[reweighted]).unwrap();
+        assert_eq!(reloaded.get("onboarding"), Some(variant.as_str()));
+    }
+
+    #[test]
+    fn reassigns_installs_whose_variant_was_removed() {
+        let dir = tempfile::tempdir().unwrap();
+        Assignments::load(dir.path(), "install", &[experiment()]).unwrap();
+        let replaced = Experiment::new("onboarding").variant("tour", 1);
+        let reloaded = Assignments::load(dir.path(), "install", &[replaced]).unwrap();
+        assert_eq!(reloaded.get("onboarding"), Some("tour"));
+    }
+}
@@ -0,0 +1,465 @@
+//! Extension processes that see a filtered, read-only projection of the state over stdio and
+//! propose actions through JSON-RPC 2.0.
+//!
+//! Messages are newline-delimited JSON. The plugin sends the projection whenever it changes:
+//!
+//! ```text
+//! < {"jsonrpc":"2.0","method":"state","params":{"revision":4,"state":{"todos":[]}}}
+//! > {"jsonrpc":"2.0","id":1,"method":"dispatch","params":{"action_type":"TODO:ADD","payload":"Buy milk"}}
+//! < {"jsonrpc":"2.0","id":1,"result":{"todos":["Buy milk"]}}
+//! > {"jsonrpc":"2.0","id":2,"method":"getState"}
+//! ```
+
+use std::io::{BufRead, BufReader, Read, Write};
+use std::path::PathBuf;
+use std::process::{Child, ChildStdin, ChildStdout, Command, Stdio};
+use std::sync::mpsc::{self, Receiver, SyncSender, TrySendError};
+use std::sync::{Arc, Mutex};
+
+use serde::{Deserialize, Serialize};
+use tauri::{AppHandle, Manager, Runtime};
+
+use crate::desktop::Zubridge;
+use crate::error_policy::ErrorPolicy;
+use crate::models::*;
+use crate::subsystems::Subsystems;
+use crate::utils::{get_path, glob_matches};
+
+/// The longest message accepted from an extension.
+const MAX_MESSAGE_BYTES: u64 = 1024 * 1024;
+/// State updates queued for an extension that isn't reading them. Older ones aren't needed,
+/// since every update carries the full projection.
+const QUEUE_SIZE: usize = 16;
+
+/// An extension process and what it may see and do.
+#[derive(Clone, Debug)]
+pub struct ExtensionConfig {
+    /// Identifies the extension in logs, and as the `extension:<name>` window label its
+    /// dispatches are checked against `read_only_windows` with.
+    pub name: String,
+    pub program: PathBuf,
+    pub args: Vec<String>,
+    /// The only environment variables the extension gets.
+    pub env: Vec<(String, String)>,
+    /// Dot-separated state paths included in the projection. Nothing is visible when empty.
+    pub readable_paths: Vec<String>,
+    /// Action types the extension may dispatch, where `*` matches any run of characters.
+    /// Nothing is allowed when empty.
+    pub allowed_actions: Vec<String>,
+}
+
+impl ExtensionConfig {
+    pub fn new(name: impl Into<String>, program: impl Into<PathBuf>) -> Self {
+        Self {
+            name: name.into(),
+            program: program.into(),
+            args: Vec::new(),
+            env: Vec::new(),
+            readable_paths: Vec::new(),
+            allowed_actions: Vec::new(),
+        }
+    }
+
+    pub fn args<I, S>(mut self, args: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        self.args.extend(args.into_iter().map(Into::into));
+        self
+    }
+
+    pub fn env(mut self, key: impl Into<String>, value: impl Into<String>) -> Self {
+        self.env.push((key.into(), value.into()));
+        self
+    }
+
+    pub fn readable<I, S>(mut self, paths: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        self.readable_paths.extend(paths.into_iter().map(Into::into));
+        self
+    }
+
+    pub fn allow_actions<I, S>(mut self, action_types: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        self.allowed_actions.extend(action_types.into_iter().map(Into::into));
+        self
+    }
+
+    fn label(&self) -> String {
+        format!("extension:{}", self.name)
+    }
+
+    /// The values at `readable_paths`, at the same paths of an otherwise empty object.
+    fn project(&self, state: &JsonValue) -> JsonValue {
+        let mut projection = JsonValue::Object(Default::default());
+        for path in &self.readable_paths {
+            let Some(value) = get_path(state, path) else { continue };
+            let slot = path.split('.').filter(|segment| !segment.is_empty()).try_fold(&mut projection, |current, segment| {
+                if current.is_null() {
+                    *current = JsonValue::Object(Default::default());
+                }
+                current.as_object_mut().map(|map| map.entry(segment.to_string()).or_insert(JsonValue::Null))
+            });
+            // A path inside a value that's already included has nothing to add
+            if let Some(slot) = slot {
+                *slot = value.clone();
+            }
+        }
+        projection
+    }
+}
+
+#[derive(Deserialize)]
+struct RpcRequest {
+    id: Option<JsonValue>,
+    method: String,
+    #[serde(default)]
+    params: JsonValue,
+}
+
+#[derive(Serialize)]
+struct RpcError {
+    code: i32,
+    message: String,
+}
+
+#[derive(Serialize)]
+struct RpcMessage<'a> {
+    jsonrpc: &'static str,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    id: Option<&'a JsonValue>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    method: Option<&'static str>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    params: Option<JsonValue>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    result: Option<JsonValue>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    error: Option<RpcError>,
+}
+
+impl<'a> RpcMessage<'a> {
+    fn notification(method: &'static str, params: JsonValue) -> Self {
+        Self {
+            jsonrpc: "2.0",
+            id: None,
+            method: Some(method),
+            params: Some(params),
+            result: None,
+            error: None,
+        }
+    }
+
+    fn response(id: &'a JsonValue, result: Result<JsonValue, RpcError>) -> Self {
+        let (result, error) = match result {
+            Ok(result) => (Some(result), None),
+            Err(error) => (None, Some(error)),
+        };
+        Self {
+            jsonrpc: "2.0",
+            id: Some(id),
+            method: None,
+            params: None,
+            result,
+            error,
+        }
+    }
+
+    fn line(&self) -> Option<String> {
+        serde_json::to_string(self).ok().map(|line| line + "\n")
+    }
+}
+
+/// A running extension process.
+struct Extension {
+    config: ExtensionConfig,
+    outgoing: SyncSender<String>,
+    /// The last projection sent, so unchanged projections aren't sent again.
+    last_sent: Mutex<Option<JsonValue>>,
+}
+
+/// The running extension processes.
+pub(crate) struct Extensions {
+    extensions: Vec<Arc<Extension>>,
+}
+
+impl Extensions {
+    /// Spawn every extension. The processes are killed when the app exits. Extensions that fail
+    /// to start are reported through `policy` and left out.
+    pub(crate) fn start<R: Runtime>(
+        configs: &[ExtensionConfig],
+        subsystems: &Subsystems,
+        app: &AppHandle<R>,
+        policy: ErrorPolicy,
+    ) -> Self {
+        let extensions = configs
+            .iter()
+            .filter_map(|config| match spawn_extension(config, subsystems, app) {
+                Ok(extension) => Some(extension),
+                Err(e) => {
+                    policy.report(format_args!("failed to start extension {}", config.name), &e);
+                    None
+                }
+            })
+            .collect();
+        Self { extensions }
+    }
+
+    /// Send each extension its projection of `state`, if it changed.
+    pub(crate) fn state_published(&self, state: &JsonValue, revision: u64) {
+        for extension in &self.extensions {
+            extension.send_state(state, revision);
+        }
+    }
+}
+
+fn spawn_extension<R: Runtime>(config: &ExtensionConfig, subsystems: &Subsystems, app: &AppHandle<R>) -> crate::Result<Arc<Extension>> {
+    let mut child = Command::new(&config.program)
+        .args(&config.args)
+        .env_clear()
+        .envs(config.env.iter().cloned())
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::inherit())
+        .spawn()
+        .map_err(|e| crate::Error::ExtensionError(format!("failed to spawn {}: {}", config.program.display(), e)))?;
+    let (Some(stdin), Some(stdout)) = (child.stdin.take(), child.stdout.take()) else {
+        kill(child);
+        return Err(crate::Error::ExtensionError(format!("{} has no stdio", config.name)));
+    };
+
+    let (outgoing, queue) = mpsc::sync_channel(QUEUE_SIZE);
+    let extension = Arc::new(Extension {
+        config: config.clone(),
+        outgoing,
+        last_sent: Mutex::new(None),
+    });
+    spawn_writer(&config.name, stdin, queue);
+    let reader = extension.clone();
+    let app = app.clone();
+    subsystems.spawn(&format!("extension-{}", config.name), move |_| read_requests(&app, &reader, stdout));
+    // Registered after the reader, so it runs first on exit and the reader sees EOF
+    subsystems.register(format!("extension-{}-process", config.name), move || kill(child));
+    Ok(extension)
+}
+
+impl Extension {
+    fn send_state(&self, state: &JsonValue, revision: u64) {
+        let projection = self.config.project(state);
+        let Ok(mut last_sent) = self.last_sent.lock() else { return };
+        if last_sent.as_ref() == Some(&projection) {
+            return;
+        }
+        let params = serde_json::json!({ "revision": revision, "state": projection });
+        let Some(line) = RpcMessage::notification("state", params).line() else { return };
+        match self.outgoing.try_send(line) {
+            Ok(()) => *last_sent = Some(projection),
+            // Send the projection again with the next update
+            Err(TrySendError::Full(_)) => *last_sent = None,
+            Err(TrySendError::Disconnected(_)) => {}
+        }
+    }
+
+    fn handle<R: Runtime>(&self, zubridge: &Zubridge<R>, request: RpcRequest) -> Result<JsonValue, RpcError> {
+        let failed = |e: crate::Error| RpcError { code: -32000, message: e.to_string() };
+        match request.method.as_str() {
+            "getState" => zubridge.get_initial_state().map(|state| self.config.project(&state)).map_err(failed),
+            "dispatch" => {
+                let action: ZubridgeAction = serde_json::from_value(request.params).map_err(|e| RpcError {
+                    code: -32602,
+                    message: format!("invalid action: {}", e),
+                })?;
+                if !self.config.allowed_actions.iter().any(|pattern| glob_matches(pattern, &action.action_type)) {
+                    log::warn!(target: "zubridge::audit", "extension {} may not dispatch {}", self.config.name, action.action_type);
+                    return Err(failed(crate::Error::ActionRejected(format!(
+                        "action {} is not allowed from extension {}",
+                        action.action_type, self.config.name
+                    ))));
+                }
+                log::info!(target: "zubridge::audit", "extension {} dispatch {}", self.config.name, action.action_type);
+                zubridge
+                    .dispatch_action_from(&self.config.label(), action)
+                    .map(|state| self.config.project(&state))
+                    .map_err(failed)
+            }
+            method => Err(RpcError {
+                code: -32601,
+                message: format!("unknown method {}", method),
+            }),
+        }
+    }
+}
+
+/// Handle requests from an extension until it closes its stdout.
+fn read_requests<R: Runtime>(app: &AppHandle<R>, extension: &Extension, stdout: ChildStdout) {
+    let name = &extension.config.name;
+    let mut reader = BufReader::new(stdout);
+    let mut line = String::new();
+    loop {
+        line.clear();
+        match reader.by_ref().take(MAX_MESSAGE_BYTES + 1).read_line(&mut line) {
+            Ok(0) => break,
+            Ok(read) if read as u64 > MAX_MESSAGE_BYTES => {
+                log::warn!("zubridge: extension {} sent a message over {} bytes, stopping", name, MAX_MESSAGE_BYTES);
+                break;
+            }
+            Ok(_) => {}
+            Err(e) => {
+                log::warn!("zubridge: failed to read from extension {}: {}", name, e);
+                break;
+            }
+        }
+        if line.trim().is_empty() {
+            continue;
+        }
+
+        let response = match serde_json::from_str::<RpcRequest>(&line) {
+            Ok(request) => {
+                let id = request.id.clone();
+                let result = match app.try_state::<Zubridge<R>>() {
+                    Some(zubridge) => extension.handle(zubridge.inner(), request),
+                    None => Err(RpcError {
+                        code: -32000,
+                        message: "the zubridge plugin isn't initialized".into(),
+                    }),
+                };
+                // Notifications get no response
+                let Some(id) = id else { continue };
+                RpcMessage::response(&id, result).line()
+            }
+            Err(e) => RpcMessage::response(
+                &JsonValue::Null,
+                Err(RpcError {
+                    code: -32700,
+                    message: e.to_string(),
+                }),
+            )
+            .line(),
+        };
+        if let Some(response) = response {
+            if extension.outgoing.send(response).is_err() {
+                break;
+            }
+        }
+    }
+    log::debug!("zubridge: extension {} closed its output", name);
+}
+
+/// Write queued messages to the extension's stdin until it exits.
+fn spawn_writer(name: &str, mut stdin: ChildStdin, queue: Receiver<String>) {
+    let name = name.to_string();
+    let spawned = std::thread::Builder::new()
+        .name(format!("zubridge-extension-{}-writer", name))
+        .spawn(move || {
+            for line in queue {
+                if let Err(e) = stdin.write_all(line.as_bytes()).and_then(|_| stdin.flush()) {
+                    log::debug!("zubridge: extension {} stopped reading: {}", name, e);
+                    break;
+                }
+            }
+        });
+    if let Err(e) = spawned {
+        log::warn!("zubridge: failed to start an extension writer: {}", e);
+    }
+}
+
+fn kill(mut child: Child) {
+    if let Err(e) = child.kill() {
+        log::debug!("zubridge: failed to kill an extension: {}", e);
+    }
+    let _ = child.wait();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ZubridgeExt;
+    use serde_json::json;
+
+    #[derive(Default)]
+    struct Todos {
+        todos: Vec<JsonValue>,
+    }
+
+    impl StateManager for Todos {
+        fn get_initial_state(&self) -> JsonValue {
+            json!({ "todos": self.todos, "user": { "name": "a", "token": "secret" } })
+        }
+
+        fn dispatch_action(&mut self, action: JsonValue) -> JsonValue {
+            if action["type"] == "TODO:ADD" {
+                self.todos.push(action["payload"].clone());
+            }
+            self.get_initial_state()
+        }
+    }
+
+    fn extension() -> (Extension, Receiver<String>) {
+        let config = ExtensionConfig::new("sync", "sync-extension")
+            .readable(["todos", "user.name"])
+            .allow_actions(["TODO:*"]);
+        let (outgoing, queue) = mpsc::sync_channel(QUEUE_SIZE);
+        let extension = Extension {
+            config,
+            outgoing,
+            last_sent: Mutex::new(None),
+        };
+        (extension, queue)
+    }
+
+    fn request(method: &str, params: JsonValue) -> RpcRequest {
+        RpcRequest {
+            id: Some(json!(1)),
+            method: method.into(),
+            params,
+        }
+    }
+
+    #[test]
+    fn projects_only_the_readable_paths() {
+        let (extension, _queue) = extension();
+        let state = json!({ "todos": [], "user": { "name": "a", "token": "secret" }, "other": 1 });
+        assert_eq!(extension.config.project(&state), json!({ "todos": [], "user": { "name": "a" } }));
+        let nested = ExtensionConfig::new("nested", "nested").readable(["user", "user.name", "missing"]);
+        assert_eq!(nested.project(&state), json!({ "user": { "name": "a", "token": "secret" } }));
+    }
+
+    #[test]
+    fn sends_changed_projections_only() {
+        let (extension, queue) = extension();
+        extension.send_state(&json!({ "todos": [], "other": 1 }), 1);
+        extension.send_state(&json!({ "todos": [], "other": 2 }), 2);
+        extension.send_state(&json!({ "todos": ["a"] }), 3);
+
+        let sent: Vec<JsonValue> = queue.try_iter().map(|line| serde_json::from_str(&line).unwrap()).collect();
+        let revisions: Vec<_> = sent.iter().map(|message| message["params"]["revision"].clone()).collect();
+        assert_eq!(revisions, [json!(1), json!(3)]);
+        assert_eq!(sent[0]["method"], "state");
+    }
+
+    #[test]
+    fn dispatches_allowed_actions_only() {
+        let app = crate::test::mock_app(Todos::default());
+        let zubridge = app.app().zubridge();
+        let (extension, _queue) = extension();
+
+        let add = json!({ "action_type": "TODO:ADD", "payload": "a" });
+        let state = extension.handle(zubridge, request("dispatch", add));
+        assert_eq!(state.ok(), Some(json!({ "todos": ["a"], "user": { "name": "a" } })));
+        let rejected = extension.handle(zubridge, request("dispatch", json!({ "action_type": "USER:LOGOUT" })));
+        assert_eq!(rejected.err().map(|error| error.code), Some(-32000));
+        let invalid = extension.handle(zubridge, request("dispatch", json!({})));
+        assert_eq!(invalid.err().map(|error| error.code), Some(-32602));
+        let unknown = extension.handle(zubridge, request("shutdown", JsonValue::Null));
+        assert_eq!(unknown.err().map(|error| error.code), Some(-32601));
+        let state = extension.handle(zubridge, request("getState", JsonValue::Null));
+        assert_eq!(state.ok().unwrap()["user"], json!({ "name": "a" }));
+    }
+}
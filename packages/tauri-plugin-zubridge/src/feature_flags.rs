@@ -0,0 +1,218 @@
+use std::collections::HashMap;
+use std::sync::Mutex;
+
+use serde_json::Map;
+
+use crate::models::*;
+use crate::utils::glob_matches;
+
+/// Who a [`FlagRule`] applies to.
+#[derive(Clone, Debug)]
+pub enum FlagTarget {
+    /// Windows whose label matches the pattern. `*` matches any run of characters.
+    Window(String),
+    /// The active profile, as set with `Zubridge::set_profile`.
+    Profile(String),
+}
+
+/// Overrides a flag's default value for a window or profile.
+#[derive(Clone, Debug)]
+pub struct FlagRule {
+    pub target: FlagTarget,
+    pub value: JsonValue,
+}
+
+impl FlagRule {
+    pub fn window(pattern: impl Into<String>, value: impl Into<JsonValue>) -> Self {
+        Self {
+            target: FlagTarget::Window(pattern.into()),
+            value: value.into(),
+        }
+    }
+
+    pub fn profile(profile: impl Into<String>, value: impl Into<JsonValue>) -> Self {
+        Self {
+            target: FlagTarget::Profile(profile.into()),
+            value: value.into(),
+        }
+    }
+}
+
+/// A feature flag declared in Rust.
+///
+/// Rules are evaluated in order and the first matching rule wins; the default is used when none match.
+#[derive(Clone, Debug)]
+pub struct FeatureFlag {
+    pub name: String,
+    pub default: JsonValue,
+    pub rules: Vec<FlagRule>,
+}
+
+impl FeatureFlag {
+    pub fn new(name: impl Into<String>, default: impl Into<JsonValue>) -> Self {
+        Self {
+            name: name.into(),
+            default: default.into(),
+            rules: Vec::new(),
+        }
+    }
+
+    /// Add a targeting rule to the flag.
+    pub fn rule(mut self, rule: FlagRule) -> Self {
+        self.rules.push(rule);
+        self
+    }
+
+    fn has_window_rules(&self) -> bool {
+        self.rules.iter().any(|rule| matches!(rule.target, FlagTarget::Window(_)))
+    }
+}
+
+struct FlagsInner {
+    profile: Option<String>,
+    /// Values set at runtime, which take precedence over rules and defaults.
+    overrides: HashMap<String, JsonValue>,
+}
+
+/// Evaluates declared feature flags and tracks runtime overrides.
+pub(crate) struct FeatureFlags {
+    flags: Vec<FeatureFlag>,
+    inner: Mutex<FlagsInner>,
+}
+
+impl FeatureFlags {
+    pub(crate) fn new(flags: Vec<FeatureFlag>) -> Self {
+        Self {
+            flags,
+            inner: Mutex::new(FlagsInner {
+                profile: None,
+                overrides: HashMap::new(),
+            }),
+        }
+    }
+
+    pub(crate) fn is_empty(&self) -> bool {
+        self.flags.is_empty()
+    }
+
+    fn lock(&self) -> crate::Result<std::sync::MutexGuard<'_, FlagsInner>> {
+        self.inner.lock().map_err(|e| crate::Error::StateError(e.to_string()))
+    }
+
+    pub(crate) fn set_profile(&self, profile: Option<String>) -> crate::Result<()> {
+        self.lock()?.profile = profile;
+        Ok(())
+    }
+
+    pub(crate) fn set(&self, name: &str, value: JsonValue) -> crate::Result<()> {
+        if !self.flags.iter().any(|flag| flag.name == name) {
+            return Err(crate::Error::UnknownFlag(name.to_string()));
+        }
+        self.lock()?.overrides.insert(name.to_string(), value);
+        Ok(())
+    }
+
+    /// Evaluate a flag, optionally for a specific window.
+    pub(crate) fn evaluate(&self, name: &str, window: Option<&str>) -> crate::Result<JsonValue> {
+        let flag = self
+            .flags
+            .iter()
+            .find(|flag| flag.name == name)
+            .ok_or_else(|| crate::Error::UnknownFlag(name.to_string()))?;
+        let inner = self.lock()?;
+        Ok(evaluate_flag(flag, &inner, window))
+    }
+
+    /// Build the flags slice for the given window labels.
+    ///
+    /// `values` holds every flag evaluated without a window, and `windows` holds per-window values
+    /// for flags that have window rules.
+    pub(crate) fn slice<'a>(&self, windows: impl Iterator<Item = &'a str>) -> crate::Result<JsonValue> {
+        let inner = self.lock()?;
+
+        let values: Map<String, JsonValue> = self
+            .flags
+            .iter()
+            .map(|flag| (flag.name.clone(), evaluate_flag(flag, &inner, None)))
+            .collect();
+
+        let mut by_window = Map::new();
+        for label in windows {
+            let window_values: Map<String, JsonValue> = self
+                .flags
+                .iter()
+                .filter(|flag| flag.has_window_rules())
+                .map(|flag| (flag.name.clone(), evaluate_flag(flag, &inner, Some(label))))
+                .collect();
+            if !window_values.is_empty() {
+                by_window.insert(label.to_string(), JsonValue::Object(window_values));
+            }
+        }
+
+        Ok(serde_json::json!({
+            "values": values,
+            "windows": by_window,
+        }))
+    }
+}
+
+fn evaluate_flag(flag: &FeatureFlag, inner: &FlagsInner, window: Option<&str>) -> JsonValue {
+    if let Some(value) = inner.overrides.get(&flag.name) {
+        return value.clone();
+    }
+
+    flag.rules
+        .iter()
+        .find(|rule| match &rule.target {
+            FlagTarget::Window(pattern) => window.is_some_and(|label| glob_matches(pattern, label)),
+            FlagTarget::Profile(profile) => inner.profile.as_deref() == Some(profile.as_str()),
+        })
+        .map(|rule| rule.value.clone())
+        .unwrap_or_else(|| flag.default.clone())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn flags() -> FeatureFlags {
+        FeatureFlags::new(vec![
+            FeatureFlag::new("beta", false)
+                .rule(FlagRule::window("settings-*", true))
+                .rule(FlagRule::profile("staff", true)),
+            FeatureFlag::new("theme", "light"),
+        ])
+    }
+
+    #[test]
+    fn takes_the_first_matching_rule() {
+        let flags = flags();
+        assert_eq!(flags.evaluate("beta", None).unwrap(), json!(false));
+        assert_eq!(flags.evaluate("beta", Some("settings-main")).unwrap(), json!(true));
+        assert_eq!(flags.evaluate("beta", Some("main")).unwrap(), json!(false));
+        flags.set_profile(Some("staff".to_string())).unwrap();
+        assert_eq!(flags.evaluate("beta", Some("main")).unwrap(), json!(true));
+    }
+
+    #[test]
+    fn prefers_runtime_overrides() {
+        let flags = flags();
+        flags.set("beta", json!("forced")).unwrap();
+        assert_eq!(flags.evaluate("beta", Some("settings-main")).unwrap(), json!("forced"));
+        assert!(matches!(flags.set("missing", json!(1)), Err(crate::Error::UnknownFlag(_))));
+        assert!(matches!(flags.evaluate("missing", None), Err(crate::Error::UnknownFlag(_))));
+    }
+
+    #[test]
+    fn lists_window_values_for_flags_with_window_rules() {
+        let slice = flags().slice(["main", "settings-main"].into_iter()).unwrap();
+        assert_eq!(
+            slice,
+            json!({
+                "values": { "beta": false, "theme": "light" },
+                "windows": { "main": { "beta": false }, "settings-main": { "beta": true } },
+            })
+        );
+    }
+}
@@ -0,0 +1,74 @@
+use std::path::{Path, PathBuf};
+
+use crate::models::*;
+
+/// Development data loaded into the state from a directory of JSON files, one per slice.
+///
+/// A file named `todos.json` is loaded into the `todos` slice through
+/// [`StateManager::load_fixture`]. Fixtures are only available in debug builds.
+#[derive(Clone, Debug)]
+pub struct FixturesConfig {
+    /// The directory holding the fixture files.
+    pub dir: PathBuf,
+    /// Load every fixture once the plugin is set up. Defaults to true.
+    pub load_on_startup: bool,
+}
+
+impl FixturesConfig {
+    pub fn new(dir: impl Into<PathBuf>) -> Self {
+        Self {
+            dir: dir.into(),
+            load_on_startup: true,
+        }
+    }
+}
+
+/// The names of the fixtures in `dir`, sorted.
+pub(crate) fn names(dir: &Path) -> crate::Result<Vec<String>> {
+    let mut names: Vec<String> = std::fs::read_dir(dir)?
+        .filter_map(|entry| entry.ok())
+        .filter_map(|entry| entry.file_name().to_str()?.strip_suffix(".json").map(str::to_string))
+        .filter(|name| valid_name(name))
+        .collect();
+    names.sort();
+    Ok(names)
+}
+
+/// Read the fixture for the slice `name` from `dir`.
+pub(crate) fn read(dir: &Path, name: &str) -> crate::Result<JsonValue> {
+    if !valid_name(name) {
+        return Err(crate::Error::UnknownFixture(name.to_string()));
+    }
+    let path = dir.join(format!("{}.json", name));
+    let contents = std::fs::read_to_string(&path).map_err(|e| match e.kind() {
+        std::io::ErrorKind::NotFound => crate::Error::UnknownFixture(name.to_string()),
+        _ => e.into(),
+    })?;
+    serde_json::from_str(&contents).map_err(|e| crate::Error::SerializationError(format!("{}: {}", path.display(), e)))
+}
+
+fn valid_name(name: &str) -> bool {
+    !name.is_empty()
+        && !name.starts_with('.')
+        && name.chars().all(|c| c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '.'))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn lists_and_reads_fixtures_by_slice() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::write(dir.path().join("todos.json"), r#"[{ "title": "a" }]"#).unwrap();
+        std::fs::write(dir.path().join("settings.json"), "{}").unwrap();
+        std::fs::write(dir.path().join(".hidden.json"), "{}").unwrap();
+        std::fs::write(dir.path().join("notes.txt"), "").unwrap();
+
+        assert_eq!(names(dir.path()).unwrap(), ["settings", "todos"]);
+        assert_eq!(read(dir.path(), "todos").unwrap(), json!([{ "title": "a" }]));
+        assert!(matches!(read(dir.path(), "users"), Err(crate::Error::UnknownFixture(_))));
+        assert!(matches!(read(dir.path(), "../todos"), Err(crate::Error::UnknownFixture(_))));
+    }
+}
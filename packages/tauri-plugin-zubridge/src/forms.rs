@@ -0,0 +1,288 @@
+use std::collections::BTreeMap;
+use std::sync::{Arc, Mutex};
+
+use serde::Serialize;
+
+use crate::models::*;
+
+pub(crate) const SET_FIELD_ACTION: &str = "FORM:SET_FIELD";
+pub(crate) const SUBMIT_ACTION: &str = "FORM:SUBMIT";
+pub(crate) const RESET_ACTION: &str = "FORM:RESET";
+
+/// Validates one field given its value and all of the form's values. Returns an error message.
+pub type FieldValidator = Arc<dyn Fn(&JsonValue, &JsonValue) -> Option<String> + Send + Sync>;
+
+/// Validates the whole form on submit, returning error messages keyed by field.
+pub type FormValidator = Arc<dyn Fn(&JsonValue) -> BTreeMap<String, String> + Send + Sync>;
+
+/// A form kept in the `forms_key` slice and driven by `FORM:*` actions.
+#[derive(Clone)]
+pub struct Form {
+    pub name: String,
+    /// The values the form starts with and is reset to. Defaults to an empty object.
+    pub initial_values: JsonValue,
+    /// The action dispatched with `{ form, values }` when the form is submitted without errors.
+    /// Defaults to "FORM:SUBMITTED".
+    pub submit_action: String,
+    fields: Vec<(String, FieldValidator)>,
+    validators: Vec<FormValidator>,
+}
+
+impl Form {
+    pub fn new(name: impl Into<String>) -> Self {
+        Self {
+            name: name.into(),
+            initial_values: JsonValue::Object(Default::default()),
+            submit_action: "FORM:SUBMITTED".to_string(),
+            fields: Vec::new(),
+            validators: Vec::new(),
+        }
+    }
+
+    pub fn initial_values(mut self, values: impl Into<JsonValue>) -> Self {
+        self.initial_values = values.into();
+        self
+    }
+
+    pub fn submit_action(mut self, action_type: impl Into<String>) -> Self {
+        self.submit_action = action_type.into();
+        self
+    }
+
+    /// Validate `field` whenever it's set and on submit. Keep these fast: they run while the
+    /// `FORM:SET_FIELD` action is dispatched.
+    pub fn field<F>(mut self, field: impl Into<String>, validator: F) -> Self
+    where
+        F: Fn(&JsonValue, &JsonValue) -> Option<String> + Send + Sync + 'static,
+    {
+        self.fields.push((field.into(), Arc::new(validator)));
+        self
+    }
+
+    /// Validate the whole form on submit. These run on a background thread, so they may query
+    /// databases or other backend-only data.
+    pub fn validate<F>(mut self, validator: F) -> Self
+    where
+        F: Fn(&JsonValue) -> BTreeMap<String, String> + Send + Sync + 'static,
+    {
+        self.validators.push(Arc::new(validator));
+        self
+    }
+
+    fn validate_field(&self, field: &str, values: &JsonValue) -> Option<String> {
+        let value = values.get(field).unwrap_or(&JsonValue::Null);
+        self.fields
+            .iter()
+            .filter(|(name, _)| name == field)
+            .find_map(|(_, validator)| validator(value, values))
+    }
+
+    /// Run every field and form validator.
+    pub(crate) fn validate_all(&self, values: &JsonValue) -> BTreeMap<String, String> {
+        let mut errors = BTreeMap::new();
+        for (field, _) in &self.fields {
+            if let Some(error) = self.validate_field(field, values) {
+                errors.entry(field.clone()).or_insert(error);
+            }
+        }
+        for validator in &self.validators {
+            for (field, error) in validator(values) {
+                errors.entry(field).or_insert(error);
+            }
+        }
+        errors
+    }
+
+    pub(crate) fn submitted(&self, values: JsonValue) -> ZubridgeAction {
+        ZubridgeAction {
+            action_type: self.submit_action.clone(),
+            payload: Some(serde_json::json!({ "form": self.name, "values": values })),
+        }
+    }
+}
+
+#[derive(Clone, Debug, Serialize)]
+struct FormState {
+    values: JsonValue,
+    /// Whether the values differ from the initial values.
+    dirty: bool,
+    /// Error messages keyed by field.
+    errors: BTreeMap<String, String>,
+    submitting: bool,
+    /// Why the last submit failed after validation passed, e.g. a reducer error.
+    submit_error: Option<String>,
+}
+
+impl FormState {
+    fn new(form: &Form) -> Self {
+        Self {
+            values: form.initial_values.clone(),
+            dirty: false,
+            errors: BTreeMap::new(),
+            submitting: false,
+            submit_error: None,
+        }
+    }
+}
+
+/// The state of every form, keyed by name.
+pub(crate) struct Forms {
+    forms: Vec<Form>,
+    states: Mutex<BTreeMap<String, FormState>>,
+}
+
+impl Forms {
+    pub(crate) fn new(forms: Vec<Form>) -> Self {
+        let states = forms.iter().map(|form| (form.name.clone(), FormState::new(form))).collect();
+        Self {
+            forms,
+            states: Mutex::new(states),
+        }
+    }
+
+    pub(crate) fn is_empty(&self) -> bool {
+        self.forms.is_empty()
+    }
+
+    /// Apply a form action. Returns the form and values to validate and submit for `FORM:SUBMIT`.
+    pub(crate) fn apply(&self, action: &ZubridgeAction) -> crate::Result<Option<(Form, JsonValue)>> {
+        let field = |name: &str| action.payload.as_ref().and_then(|payload| payload.get(name));
+        let name = field("form")
+            .and_then(JsonValue::as_str)
+            .ok_or_else(|| crate::Error::StateError(format!("{} requires a form", action.action_type)))?;
+        let form = self
+            .forms
+            .iter()
+            .find(|form| form.name == name)
+            .ok_or_else(|| crate::Error::StateError(format!("no form named {}", name)))?;
+
+        let mut states = self.states.lock().map_err(|e| crate::Error::StateError(e.to_string()))?;
+        let state = states.entry(name.to_string()).or_insert_with(|| FormState::new(form));
+        match action.action_type.as_str() {
+            SET_FIELD_ACTION => {
+                let key = field("field")
+                    .and_then(JsonValue::as_str)
+                    .ok_or_else(|| crate::Error::StateError(format!("{} requires a field", SET_FIELD_ACTION)))?;
+                let Some(values) = state.values.as_object_mut() else {
+                    return Err(crate::Error::StateError(format!("the values of form {} aren't an object", name)));
+                };
+                values.insert(key.to_string(), field("value").cloned().unwrap_or(JsonValue::Null));
+                state.dirty = state.values != form.initial_values;
+                match form.validate_field(key, &state.values) {
+                    Some(error) => state.errors.insert(key.to_string(), error),
+                    None => state.errors.remove(key),
+                };
+                Ok(None)
+            }
+            SUBMIT_ACTION if state.submitting => Ok(None),
+            SUBMIT_ACTION => {
+                state.submitting = true;
+                state.submit_error = None;
+                Ok(Some((form.clone(), state.values.clone())))
+            }
+            _ => {
+                *state = FormState::new(form);
+                Ok(None)
+            }
+        }
+    }
+
+    /// Record the outcome of a submit.
+    pub(crate) fn submitted(
+        &self,
+        name: &str,
+        errors: BTreeMap<String, String>,
+        submit_error: Option<String>,
+    ) -> crate::Result<()> {
+        let mut states = self.states.lock().map_err(|e| crate::Error::StateError(e.to_string()))?;
+        if let Some(state) = states.get_mut(name) {
+            state.submitting = false;
+            state.errors = errors;
+            state.submit_error = submit_error;
+        }
+        Ok(())
+    }
+
+    pub(crate) fn slice(&self) -> crate::Result<JsonValue> {
+        let states = self.states.lock().map_err(|e| crate::Error::StateError(e.to_string()))?;
+        serde_json::to_value(&*states).map_err(|e| crate::Error::SerializationError(e.to_string()))
+    }
+}
+
+pub(crate) fn is_form_action(action_type: &str) -> bool {
+    matches!(action_type, SET_FIELD_ACTION | SUBMIT_ACTION | RESET_ACTION)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn signup() -> Form {
+        Form::new("signup")
+            .initial_values(json!({ "email": "" }))
+            .field("email", |value, _| {
+                (!value.as_str().unwrap_or_default().contains('@')).then(|| "invalid email".to_string())
+            })
+            .validate(|values| {
+                let mut errors = BTreeMap::new();
+                if values["email"] == "taken@example.com" {
+                    errors.insert("email".to_string(), "already registered".to_string());
+                }
+                errors
+            })
+    }
+
+    fn action(action_type: &str, payload: JsonValue) -> ZubridgeAction {
+        ZubridgeAction {
+            action_type: action_type.to_string(),
+            payload: Some(payload),
+        }
+    }
+
+    fn set_email(forms: &Forms, email: &str) {
+        let set = action(SET_FIELD_ACTION, json!({ "form": "signup", "field": "email", "value": email }));
+        assert!(forms.apply(&set).unwrap().is_none());
+    }
+
+    #[test]
+    fn validates_fields_as_they_are_set() {
+        let forms = Forms::new(vec![signup()]);
+        set_email(&forms, "nope");
+        let slice = forms.slice().unwrap();
+        assert_eq!(slice["signup"]["errors"], json!({ "email": "invalid email" }));
+        assert_eq!(slice["signup"]["dirty"], true);
+
+        set_email(&forms, "a@example.com");
+        assert_eq!(forms.slice().unwrap()["signup"]["errors"], json!({}));
+    }
+
+    #[test]
+    fn runs_every_validator_on_submit() {
+        let forms = Forms::new(vec![signup()]);
+        set_email(&forms, "taken@example.com");
+        let submit = action(SUBMIT_ACTION, json!({ "form": "signup" }));
+        let (form, values) = forms.apply(&submit).unwrap().unwrap();
+        // Submitting already
+        assert!(forms.apply(&submit).unwrap().is_none());
+
+        let errors = form.validate_all(&values);
+        assert_eq!(errors, BTreeMap::from([("email".to_string(), "already registered".to_string())]));
+        forms.submitted("signup", errors, None).unwrap();
+        assert_eq!(forms.slice().unwrap()["signup"]["submitting"], false);
+        let submitted = form.submitted(values);
+        assert_eq!(submitted.action_type, "FORM:SUBMITTED");
+        assert_eq!(submitted.payload.unwrap()["values"], json!({ "email": "taken@example.com" }));
+    }
+
+    #[test]
+    fn resets_to_the_initial_values() {
+        let forms = Forms::new(vec![signup()]);
+        set_email(&forms, "nope");
+        forms.apply(&action(RESET_ACTION, json!({ "form": "signup" }))).unwrap();
+        let slice = forms.slice().unwrap();
+        assert_eq!(slice["signup"]["values"], json!({ "email": "" }));
+        assert_eq!(slice["signup"]["dirty"], false);
+        assert_eq!(slice["signup"]["errors"], json!({}));
+    }
+}
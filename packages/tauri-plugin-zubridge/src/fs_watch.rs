@@ -0,0 +1,221 @@
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use notify::{RecommendedWatcher, RecursiveMode};
+use notify_debouncer_mini::{new_debouncer, DebounceEventResult, Debouncer};
+use tauri::{AppHandle, Runtime};
+
+use crate::models::*;
+use crate::ZubridgeExt;
+
+/// Maps a changed path to the action that should be dispatched for it.
+/// Returning `None` skips the change.
+pub type FsWatchMapper = Arc<dyn Fn(&Path) -> Option<ZubridgeAction> + Send + Sync>;
+
+struct WatchEntry {
+    /// The directory handed to the OS watcher for this entry.
+    watched: PathBuf,
+    mapper: FsWatchMapper,
+}
+
+type WatchEntries = Arc<Mutex<HashMap<PathBuf, WatchEntry>>>;
+
+/// Watches files on disk and dispatches actions when they change.
+///
+/// Change events are debounced, so an editor writing a file several times in a row
+/// results in a single dispatch.
+pub struct FsWatch<R: Runtime> {
+    app: AppHandle<R>,
+    debounce: Duration,
+    entries: WatchEntries,
+    debouncer: Mutex<Option<Debouncer<RecommendedWatcher>>>,
+}
+
+impl<R: Runtime> FsWatch<R> {
+    pub(crate) fn new(app: AppHandle<R>, debounce: Duration) -> Self {
+        Self {
+            app,
+            debounce,
+            entries: Arc::new(Mutex::new(HashMap::new())),
+            debouncer: Mutex::new(None),
+        }
+    }
+
+    /// Watch a file or directory, dispatching the action returned by `mapper` for every change.
+    ///
+    /// Files are watched through their parent directory so that editors which replace files
+    /// on save keep triggering events. Directories are watched recursively.
+    ///
+    /// The debouncer is locked before the entries, here and in [`FsWatch::unwatch`], so the two
+    /// can't deadlock each other.
+    pub fn watch<P, F>(&self, path: P, mapper: F) -> crate::Result<()>
+    where
+        P: AsRef<Path>,
+        F: Fn(&Path) -> Option<ZubridgeAction> + Send + Sync + 'static,
+    {
+        let path = normalize(path.as_ref())?;
+        let (watched, mode) = if path.is_dir() {
+            (path.clone(), RecursiveMode::Recursive)
+        } else {
+            let parent = path
+                .parent()
+                .ok_or_else(|| crate::Error::WatchError(format!("{} has no parent directory", path.display())))?;
+            (parent.to_path_buf(), RecursiveMode::NonRecursive)
+        };
+
+        let mut debouncer = self.debouncer.lock().map_err(|e| crate::Error::WatchError(e.to_string()))?;
+        if debouncer.is_none() {
+            *debouncer = Some(self.create_debouncer()?);
+        }
+        debouncer
+            .as_mut()
+            .expect("debouncer initialized above")
+            .watcher()
+            .watch(&watched, mode)
+            .map_err(|e| crate::Error::WatchError(e.to_string()))?;
+
+        let mut entries = self.entries.lock().map_err(|e| crate::Error::WatchError(e.to_string()))?;
+        entries.insert(path, WatchEntry { watched, mapper: Arc::new(mapper) });
+        Ok(())
+    }
+
+    /// Watch a JSON file and dispatch `action_type` with the parsed contents as payload whenever it changes.
+    pub fn watch_json<P: AsRef<Path>>(&self, path: P, action_type: &str) -> crate::Result<()> {
+        let action_type = action_type.to_string();
+        self.watch(path, move |changed| {
+            let contents = std::fs::read_to_string(changed)
+                .map_err(|e| log::warn!("zubridge: failed to read {}: {}", changed.display(), e))
+                .ok()?;
+            let payload = serde_json::from_str::<JsonValue>(&contents)
+                .map_err(|e| log::warn!("zubridge: failed to parse {}: {}", changed.display(), e))
+                .ok()?;
+            Some(ZubridgeAction {
+                action_type: action_type.clone(),
+                payload: Some(payload),
+            })
+        })
+    }
+
+    /// Stop watching a path previously passed to [`FsWatch::watch`].
+    pub fn unwatch<P: AsRef<Path>>(&self, path: P) -> crate::Result<()> {
+        let path = normalize(path.as_ref())?;
+        // Locked in the same order as in `watch`: the debouncer, then the entries
+        let mut debouncer = self.debouncer.lock().map_err(|e| crate::Error::WatchError(e.to_string()))?;
+        let mut entries = self.entries.lock().map_err(|e| crate::Error::WatchError(e.to_string()))?;
+        let Some(entry) = entries.remove(&path) else {
+            return Ok(());
+        };
+
+        // Other entries may share the same watched directory
+        if entries.values().any(|other| other.watched == entry.watched) {
+            return Ok(());
+        }
+        drop(entries);
+
+        if let Some(debouncer) = debouncer.as_mut() {
+            debouncer
+                .watcher()
+                .unwatch(&entry.watched)
+                .map_err(|e| crate::Error::WatchError(e.to_string()))?;
+        }
+        Ok(())
+    }
+
+    fn create_debouncer(&self) -> crate::Result<Debouncer<RecommendedWatcher>> {
+        let app = self.app.clone();
+        let entries = self.entries.clone();
+
+        new_debouncer(self.debounce, move |result: DebounceEventResult| {
+            let events = match result {
+                Ok(events) => events,
+                Err(e) => {
+                    log::warn!("zubridge: file watch error: {}", e);
+                    return;
+                }
+            };
+
+            // Collect the actions first so the entries lock isn't held while dispatching
+            let actions: Vec<ZubridgeAction> = {
+                let Ok(entries) = entries.lock() else { return };
+                events
+                    .iter()
+                    .flat_map(|event| {
+                        entries
+                            .iter()
+                            .filter(|(path, _)| event.path.starts_with(path))
+                            .filter_map(|(_, entry)| (entry.mapper)(&event.path))
+                            .collect::<Vec<_>>()
+                    })
+                    .collect()
+            };
+
+            for action in actions {
+                if let Err(e) = app.zubridge().dispatch_action(action) {
+                    log::warn!("zubridge: failed to dispatch file watch action: {}", e);
+                }
+            }
+        })
+        .map_err(|e| crate::Error::WatchError(e.to_string()))
+    }
+}
+
+/// Resolve a path to its canonical form so it matches the paths reported by the OS watcher.
+fn normalize(path: &Path) -> crate::Result<PathBuf> {
+    if let Ok(canonical) = path.canonicalize() {
+        return Ok(canonical);
+    }
+
+    // The file may not exist yet; canonicalize its parent instead
+    let parent = path.parent().filter(|p| !p.as_os_str().is_empty()).unwrap_or(Path::new("."));
+    let name = path
+        .file_name()
+        .ok_or_else(|| crate::Error::WatchError(format!("invalid watch path: {}", path.display())))?;
+    Ok(parent.canonicalize()?.join(name))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[derive(Default)]
+    struct Settings {
+        settings: JsonValue,
+    }
+
+    impl StateManager for Settings {
+        fn get_initial_state(&self) -> JsonValue {
+            json!({ "settings": self.settings })
+        }
+
+        fn dispatch_action(&mut self, action: JsonValue) -> JsonValue {
+            if action["type"] == "SETTINGS_CHANGED" {
+                self.settings = action["payload"].clone();
+            }
+            self.get_initial_state()
+        }
+    }
+
+    #[test]
+    fn dispatches_the_contents_of_changed_json_files() {
+        let app = crate::test::mock_app(Settings::default());
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("settings.json");
+        app.app().zubridge().fs_watch().watch_json(&path, "SETTINGS_CHANGED").unwrap();
+        std::fs::write(&path, r#"{ "theme": "dark" }"#).unwrap();
+
+        let state = app.wait_for(|state| !state["settings"].is_null(), Duration::from_secs(10)).unwrap();
+        assert_eq!(state["settings"], json!({ "theme": "dark" }));
+        app.app().zubridge().fs_watch().unwatch(&path).unwrap();
+    }
+
+    #[test]
+    fn normalizes_paths_that_do_not_exist_yet() {
+        let dir = tempfile::tempdir().unwrap();
+        let canonical = dir.path().canonicalize().unwrap();
+        assert_eq!(normalize(&dir.path().join("new.json")).unwrap(), canonical.join("new.json"));
+        assert!(normalize(&dir.path().join("missing").join("new.json")).is_err());
+    }
+}
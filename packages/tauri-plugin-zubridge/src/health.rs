@@ -0,0 +1,112 @@
+use serde::Serialize;
+
+use crate::models::*;
+use crate::subsystems::SubsystemStatus;
+
+/// The version of the commands and events shared with `tauri-plugin-zubridge-api`. Bumped on
+/// breaking changes, so the frontend package can refuse to run against an incompatible backend.
+pub const PROTOCOL_VERSION: u32 = 1;
+
+/// The oldest protocol version the plugin still answers. Frontends that predate protocol
+/// versions don't send one with `zubridge.get-initial-state` and count as version 0.
+pub const MIN_PROTOCOL_VERSION: u32 = 0;
+
+/// `zubridge.get-initial-state`'s response to a frontend that sent its protocol version.
+#[derive(Clone, Debug, Serialize)]
+pub struct InitialStateEnvelope {
+    /// The protocol version the response follows, which the frontend should check.
+    pub protocol_version: u32,
+    pub state: JsonValue,
+}
+
+/// The protocol version to answer a frontend speaking `frontend` with, or an error naming
+/// both versions when the plugin can't.
+pub(crate) fn negotiate(frontend: u32) -> crate::Result<u32> {
+    if !(MIN_PROTOCOL_VERSION..=PROTOCOL_VERSION).contains(&frontend) {
+        return Err(crate::Error::ProtocolMismatch {
+            frontend,
+            backend: PROTOCOL_VERSION,
+            plugin_version: env!("CARGO_PKG_VERSION"),
+        });
+    }
+    Ok(frontend)
+}
+
+/// The plugin's version, returned by `zubridge.version`.
+#[derive(Clone, Debug, Serialize)]
+pub struct VersionInfo {
+    /// The plugin crate's semver.
+    pub plugin_version: &'static str,
+    pub protocol_version: u32,
+    /// The optional features the plugin was built with.
+    pub features: Vec<&'static str>,
+}
+
+impl VersionInfo {
+    pub(crate) fn current() -> Self {
+        let mut features = Vec::new();
+        if cfg!(feature = "schema") {
+            features.push("schema");
+        }
+        if cfg!(feature = "reqwest") {
+            features.push("reqwest");
+        }
+        if cfg!(feature = "search") {
+            features.push("search");
+        }
+        if cfg!(feature = "mqtt") {
+            features.push("mqtt");
+        }
+        if cfg!(feature = "wasm-reducers") {
+            features.push("wasm-reducers");
+        }
+        if cfg!(feature = "automation") {
+            features.push("automation");
+        }
+        if cfg!(feature = "secrets") {
+            features.push("secrets");
+        }
+        Self {
+            plugin_version: env!("CARGO_PKG_VERSION"),
+            protocol_version: PROTOCOL_VERSION,
+            features,
+        }
+    }
+}
+
+/// The plugin's status, returned by `zubridge.health`.
+#[derive(Clone, Debug, Serialize)]
+pub struct Health {
+    pub version: VersionInfo,
+    /// Whether a state manager is registered.
+    pub state_manager: bool,
+    /// Whether the initial state provider is still loading.
+    pub loading: bool,
+    /// The revision of the last emitted state update.
+    pub revision: u64,
+    /// How many windows missed a state update and haven't been resynced yet.
+    pub windows_behind: usize,
+    pub subsystems: Vec<SubsystemStatus>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn answers_supported_protocol_versions() {
+        assert_eq!(negotiate(MIN_PROTOCOL_VERSION).unwrap(), MIN_PROTOCOL_VERSION);
+        assert_eq!(negotiate(PROTOCOL_VERSION).unwrap(), PROTOCOL_VERSION);
+        let Err(crate::Error::ProtocolMismatch { frontend, backend, .. }) = negotiate(PROTOCOL_VERSION + 1) else {
+            panic!("expected a protocol mismatch");
+        };
+        assert_eq!((frontend, backend), (PROTOCOL_VERSION + 1, PROTOCOL_VERSION));
+    }
+
+    #[test]
+    fn reports_the_enabled_features() {
+        let version = VersionInfo::current();
+        assert_eq!(version.plugin_version, env!("CARGO_PKG_VERSION"));
+        assert_eq!(version.features.contains(&"reqwest"), cfg!(feature = "reqwest"));
+    }
+}
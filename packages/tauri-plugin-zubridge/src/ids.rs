@@ -0,0 +1,45 @@
+/// Generates ids for action log entries, unnamed snapshots, workflow runs and window request
+/// correlation. Implement it to make ids deterministic in tests or to follow your own id format.
+pub trait IdGenerator: Send + Sync + 'static {
+    fn next_id(&self) -> String;
+}
+
+impl<F> IdGenerator for F
+where
+    F: Fn() -> String + Send + Sync + 'static,
+{
+    fn next_id(&self) -> String {
+        self()
+    }
+}
+
+/// The default [`IdGenerator`]: UUIDv7, which sort in the order they were generated.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct UuidV7;
+
+impl IdGenerator for UuidV7 {
+    fn next_id(&self) -> String {
+        uuid::Uuid::now_v7().to_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicU64, Ordering};
+
+    #[test]
+    fn uuid_v7_ids_sort_in_generation_order() {
+        let ids: Vec<_> = (0..16).map(|_| UuidV7.next_id()).collect();
+        let mut sorted = ids.clone();
+        sorted.sort();
+        assert_eq!(ids, sorted);
+    }
+
+    #[test]
+    fn closures_generate_ids() {
+        let next = AtomicU64::new(0);
+        let ids = move || format!("id-{}", next.fetch_add(1, Ordering::Relaxed));
+        assert_eq!((ids.next_id(), ids.next_id()), ("id-0".to_string(), "id-1".to_string()));
+    }
+}
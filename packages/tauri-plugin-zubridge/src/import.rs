@@ -0,0 +1,165 @@
+use serde::Serialize;
+use tauri::ipc::Channel;
+use tauri::{AppHandle, Emitter, Runtime};
+
+use crate::batch::BatchResult;
+
+use crate::models::*;
+use crate::utils::catch_reducer_panic;
+use crate::ZubridgeExt;
+
+/// Progress of a bulk import, emitted on [`crate::IMPORT_PROGRESS_EVENT`] after every chunk.
+#[derive(Clone, Debug, Serialize)]
+pub struct ImportProgress {
+    pub import: String,
+    pub chunks: usize,
+    pub records: usize,
+    /// The expected number of records, if the import was given one.
+    pub total: Option<usize>,
+    pub done: bool,
+}
+
+/// Accepts chunks of records for a bulk import, returned by `Zubridge::import_stream`.
+///
+/// Chunks are applied through [`StateManager::import_chunk`] without emitting state updates;
+/// a single update is emitted when the import finishes or the sink is dropped.
+pub struct ImportSink<R: Runtime> {
+    app: AppHandle<R>,
+    progress: ImportProgress,
+    results: Option<Channel<BatchResult>>,
+    finished: bool,
+}
+
+impl<R: Runtime> ImportSink<R> {
+    pub(crate) fn new(app: AppHandle<R>, import: String) -> Self {
+        Self {
+            app,
+            progress: ImportProgress {
+                import,
+                chunks: 0,
+                records: 0,
+                total: None,
+                done: false,
+            },
+            results: None,
+            finished: false,
+        }
+    }
+
+    /// Set the expected number of records, which is included in progress events.
+    pub fn with_total(mut self, total: usize) -> Self {
+        self.progress.total = Some(total);
+        self
+    }
+
+    /// Stream the outcome of every chunk to `channel`, e.g. one passed in by the frontend to
+    /// show which chunks failed. `index` is the chunk's position in the import.
+    pub fn with_channel(mut self, channel: Channel<BatchResult>) -> Self {
+        self.results = Some(channel);
+        self
+    }
+
+    /// Apply a chunk of records and emit a progress event.
+    pub fn send(&mut self, records: Vec<JsonValue>) -> crate::Result<()> {
+        let count = records.len();
+        let result = self.apply(records);
+        if let Some(channel) = &self.results {
+            let index = self.progress.chunks;
+            if let Err(e) = channel.send(BatchResult::new(index, &result)) {
+                log::warn!("zubridge: failed to send the result of import chunk {}: {}", index, e);
+            }
+        }
+        result?;
+
+        self.progress.chunks += 1;
+        self.progress.records += count;
+        self.emit_progress()
+    }
+
+    fn apply(&self, records: Vec<JsonValue>) -> crate::Result<()> {
+        let state_manager = self.app.zubridge().state_manager()?;
+        let mut state_guard = state_manager.lock().map_err(|e| crate::Error::StateError(e.to_string()))?;
+        let import = &self.progress.import;
+        catch_reducer_panic(|| state_guard.import_chunk(import, records))
+    }
+
+    /// Finish the import and emit the resulting state.
+    pub fn finish(mut self) -> crate::Result<JsonValue> {
+        self.complete()
+    }
+
+    fn complete(&mut self) -> crate::Result<JsonValue> {
+        self.finished = true;
+        self.progress.done = true;
+        self.emit_progress()?;
+        self.app.zubridge().emit_current_state()
+    }
+
+    fn emit_progress(&self) -> crate::Result<()> {
+        self.app
+            .emit(&self.app.zubridge().event_names().import_progress, &self.progress)
+            .map_err(|e| crate::Error::EmitError(e.to_string()))
+    }
+}
+
+impl<R: Runtime> Drop for ImportSink<R> {
+    fn drop(&mut self) {
+        // Make sure windows see the imported records even if the import was abandoned
+        if !self.finished {
+            if let Err(e) = self.complete() {
+                log::warn!("zubridge: failed to emit state after import: {}", e);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[derive(Default)]
+    struct Contacts {
+        contacts: Vec<JsonValue>,
+    }
+
+    impl StateManager for Contacts {
+        fn get_initial_state(&self) -> JsonValue {
+            json!({ "contacts": self.contacts })
+        }
+
+        fn dispatch_action(&mut self, _action: JsonValue) -> JsonValue {
+            self.get_initial_state()
+        }
+
+        fn import_chunk(&mut self, _import: &str, records: Vec<JsonValue>) {
+            self.contacts.extend(records);
+        }
+    }
+
+    #[test]
+    fn applies_chunks_and_emits_the_state_once() {
+        let app = crate::test::mock_app(Contacts::default());
+        let mut sink = app.app().zubridge().import_stream("contacts").with_total(3);
+        sink.send(vec![json!("a"), json!("b")]).unwrap();
+        sink.send(vec![json!("c")]).unwrap();
+        assert_eq!(app.last_state(), None);
+        let state = sink.finish().unwrap();
+        assert_eq!(state["contacts"], json!(["a", "b", "c"]));
+
+        let names = app.app().zubridge().event_names().clone();
+        let progress = app.payloads(&names.import_progress);
+        let records: Vec<_> = progress.iter().map(|progress| (progress["records"].clone(), progress["done"].clone())).collect();
+        assert_eq!(records, [(json!(2), json!(false)), (json!(3), json!(false)), (json!(3), json!(true))]);
+        assert_eq!(app.last_state().unwrap()["contacts"], json!(["a", "b", "c"]));
+    }
+
+    #[test]
+    fn emits_the_state_when_abandoned() {
+        let app = crate::test::mock_app(Contacts::default());
+        let mut sink = app.app().zubridge().import_stream("contacts");
+        sink.send(vec![json!("a")]).unwrap();
+        drop(sink);
+        assert_eq!(app.last_state().unwrap()["contacts"], json!(["a"]));
+    }
+}
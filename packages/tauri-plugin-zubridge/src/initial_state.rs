@@ -0,0 +1,115 @@
+use std::future::Future;
+use std::path::{Path, PathBuf};
+use std::pin::Pin;
+use std::sync::Arc;
+
+use crate::models::*;
+
+/// Loads the initial state, returning an error message if it can't.
+pub type InitialStateFn = Arc<dyn Fn() -> Pin<Box<dyn Future<Output = Result<JsonValue, String>> + Send>> + Send + Sync>;
+
+/// Loads the initial state in the background, e.g. from disk or the network, so heavy loading
+/// doesn't hold up plugin setup.
+///
+/// Until it resolves, windows get the placeholder state and actions for the state manager are
+/// rejected. The loaded state is then handed to [`StateManager::restore_state`] and emitted.
+#[derive(Clone)]
+pub struct InitialStateProvider {
+    source: Source,
+    /// The state windows get while loading. Defaults to `{ "loading": true }`.
+    pub placeholder: JsonValue,
+}
+
+impl InitialStateProvider {
+    pub fn new<F, Fut>(load: F) -> Self
+    where
+        F: Fn() -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = Result<JsonValue, String>> + Send + 'static,
+    {
+        Self {
+            source: Source::Load(Arc::new(move || Box::pin(load()))),
+            placeholder: serde_json::json!({ "loading": true }),
+        }
+    }
+
+    /// Load the state persisted as JSON in `path`. A missing file leaves the state manager's own
+    /// initial state, and an invalid one is moved aside so the next launch doesn't trip on it
+    /// again. One that can't be read fails the load and is left alone.
+    pub fn from_file(path: impl Into<PathBuf>) -> Self {
+        Self {
+            source: Source::File(path.into()),
+            placeholder: serde_json::json!({ "loading": true }),
+        }
+    }
+
+    pub fn placeholder(mut self, placeholder: JsonValue) -> Self {
+        self.placeholder = placeholder;
+        self
+    }
+
+    pub(crate) async fn load(&self) -> Result<JsonValue, LoadError> {
+        let path = match &self.source {
+            Source::Load(load) => return load().await.map_err(LoadError::Failed),
+            Source::File(path) => path,
+        };
+        let contents = match tokio::fs::read_to_string(path).await {
+            Ok(contents) => contents,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Err(LoadError::Missing),
+            Err(e) => return Err(LoadError::Unreadable(e.to_string())),
+        };
+        serde_json::from_str(&contents).map_err(|e| LoadError::Invalid(e.to_string()))
+    }
+
+    /// The file the state is loaded from, with `from_file`.
+    pub(crate) fn file(&self) -> Option<&Path> {
+        match &self.source {
+            Source::File(path) => Some(path),
+            Source::Load(_) => None,
+        }
+    }
+}
+
+#[derive(Clone)]
+enum Source {
+    Load(InitialStateFn),
+    File(PathBuf),
+}
+
+/// Why the initial state wasn't loaded.
+#[derive(Debug)]
+pub(crate) enum LoadError {
+    /// The loader passed to [`InitialStateProvider::new`] failed.
+    Failed(String),
+    /// Nothing was persisted yet, e.g. on first launch.
+    Missing,
+    /// The file couldn't be read, e.g. for lack of permission. It's left where it is, since it
+    /// may well be fine.
+    Unreadable(String),
+    /// The file doesn't hold JSON.
+    Invalid(String),
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn load(path: &Path) -> Result<JsonValue, LoadError> {
+        tauri::async_runtime::block_on(InitialStateProvider::from_file(path).load())
+    }
+
+    #[test]
+    fn tells_unreadable_files_from_invalid_ones() {
+        let dir = tempfile::tempdir().unwrap();
+        let file = dir.path().join("state.json");
+        assert!(matches!(load(&file), Err(LoadError::Missing)));
+
+        std::fs::write(&file, "{ not json").unwrap();
+        assert!(matches!(load(&file), Err(LoadError::Invalid(_))));
+
+        // Reading a directory fails the way an unreadable file does
+        assert!(matches!(load(dir.path()), Err(LoadError::Unreadable(_))));
+
+        std::fs::write(&file, r#"{ "counter": 1 }"#).unwrap();
+        assert_eq!(load(&file).unwrap(), serde_json::json!({ "counter": 1 }));
+    }
+}
@@ -0,0 +1,39 @@
+use std::path::Path;
+
+const INSTALL_ID_FILE: &str = "install-id";
+
+/// Read the install id from `dir`, generating and persisting a new one on first launch.
+pub(crate) fn load_or_create(dir: &Path) -> crate::Result<String> {
+    let path = dir.join(INSTALL_ID_FILE);
+    if let Ok(existing) = std::fs::read_to_string(&path) {
+        let existing = existing.trim();
+        if uuid::Uuid::parse_str(existing).is_ok() {
+            return Ok(existing.to_string());
+        }
+        log::warn!("zubridge: ignoring invalid install id in {}", path.display());
+    }
+
+    let id = uuid::Uuid::new_v4().to_string();
+    std::fs::write(&path, &id)?;
+    Ok(id)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn keeps_the_id_across_launches() {
+        let dir = tempfile::tempdir().unwrap();
+        let id = load_or_create(dir.path()).unwrap();
+        assert_eq!(load_or_create(dir.path()).unwrap(), id);
+    }
+
+    #[test]
+    fn replaces_an_invalid_id() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::write(dir.path().join(INSTALL_ID_FILE), "not an id").unwrap();
+        let id = load_or_create(dir.path()).unwrap();
+        assert!(uuid::Uuid::parse_str(&id).is_ok());
+    }
+}
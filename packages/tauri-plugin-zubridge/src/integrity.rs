@@ -0,0 +1,102 @@
+//! Signed state updates, so a frontend running under Tauri's isolation pattern, or any
+//! frontend that doesn't trust every script on the page, can tell updates from the plugin apart
+//! from forged events.
+//!
+//! Each window gets the signing key once per page load through `zubridge.handshake`. Updates are
+//! sent as `{ revision, data, signature }`, where `data` is the JSON-encoded state and
+//! `signature` the hex HMAC-SHA256 of `<revision>.<data>`.
+
+use std::collections::HashSet;
+use std::sync::Mutex;
+
+use serde::Serialize;
+
+use crate::utils::{hex, hmac_sha256, random_key};
+
+pub(crate) const ALGORITHM: &str = "HMAC-SHA256";
+
+/// What a window needs to verify signed updates.
+#[derive(Clone, Debug, Serialize)]
+pub struct Handshake {
+    /// The hex signing key, the same for every window until the app restarts.
+    pub key: String,
+    pub algorithm: &'static str,
+}
+
+/// A state update with its signature.
+#[derive(Clone, Debug, Serialize)]
+pub struct SignedPayload {
+    pub revision: u64,
+    /// The JSON-encoded payload, signed as is so the signature doesn't depend on how it's
+    /// re-encoded on the other side.
+    pub data: String,
+    pub signature: String,
+}
+
+pub(crate) struct PayloadSigner {
+    key: [u8; 32],
+    /// Windows that got the key since their page last loaded.
+    handshakes: Mutex<HashSet<String>>,
+}
+
+impl PayloadSigner {
+    pub(crate) fn new() -> Self {
+        Self {
+            key: random_key(),
+            handshakes: Mutex::new(HashSet::new()),
+        }
+    }
+
+    /// Hand the key to `window`, once per page load, so scripts loaded after the app's own can't
+    /// ask for it.
+    pub(crate) fn handshake(&self, window: &str) -> crate::Result<Handshake> {
+        let mut handshakes = self.handshakes.lock().map_err(|e| crate::Error::StateError(e.to_string()))?;
+        if !handshakes.insert(window.to_string()) {
+            return Err(crate::Error::HandshakeRejected(window.to_string()));
+        }
+        Ok(Handshake {
+            key: hex(&self.key),
+            algorithm: ALGORITHM,
+        })
+    }
+
+    /// Allow `window` to handshake again, after it navigated or was destroyed.
+    pub(crate) fn reset(&self, window: &str) {
+        if let Ok(mut handshakes) = self.handshakes.lock() {
+            handshakes.remove(window);
+        }
+    }
+
+    pub(crate) fn sign(&self, revision: u64, payload: &impl Serialize) -> serde_json::Result<SignedPayload> {
+        let data = serde_json::to_string(payload)?;
+        let signature = hex(&hmac_sha256(&self.key, format!("{}.{}", revision, data).as_bytes()));
+        Ok(SignedPayload { revision, data, signature })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn hands_out_the_key_once_until_reset() {
+        let signer = PayloadSigner::new();
+        let handshake = signer.handshake("main").unwrap();
+        assert_eq!(handshake.key.len(), 64);
+        assert!(signer.handshake("main").is_err());
+        assert!(signer.handshake("other").is_ok());
+
+        signer.reset("main");
+        assert_eq!(signer.handshake("main").unwrap().key, handshake.key);
+    }
+
+    #[test]
+    fn signs_the_revision_and_data() {
+        let signer = PayloadSigner::new();
+        let signed = signer.sign(7, &serde_json::json!({ "counter": 1 })).unwrap();
+        assert_eq!(signed.data, r#"{"counter":1}"#);
+        assert_eq!(signed.signature, hex(&hmac_sha256(&signer.key, br#"7.{"counter":1}"#)));
+        assert_ne!(signer.sign(8, &serde_json::json!({ "counter": 1 })).unwrap().signature, signed.signature);
+        assert_ne!(PayloadSigner::new().sign(7, &serde_json::json!({ "counter": 1 })).unwrap().signature, signed.signature);
+    }
+}
@@ -0,0 +1,143 @@
+use std::time::Instant;
+
+use crate::breadcrumbs;
+use crate::error_policy::ErrorPolicy;
+use crate::models::*;
+use crate::utils::catch_reducer_panic;
+
+/// The outcome of running an action through a [`DispatchInterceptor`].
+#[derive(Debug)]
+pub enum Interception {
+    /// Dispatch the (possibly modified) action.
+    Continue(ZubridgeAction),
+    /// Replace the action with zero or more actions, applied together as one atomic batch.
+    Transformed(Vec<ZubridgeAction>),
+    /// Veto the action. The reason is returned to the caller as [`crate::Error::ActionRejected`].
+    Rejected(String),
+}
+
+/// Inspects actions before they reach the state manager and can transform, split or veto them.
+/// Actions it rewrites an action into are checked against the session lock, authorization and
+/// confirmations like dispatched ones.
+pub trait DispatchInterceptor: Send + Sync + 'static {
+    fn intercept(&self, action: ZubridgeAction) -> Interception;
+
+    /// How the interceptor is named in the breadcrumbs returned with failed dispatches.
+    /// Defaults to its type name.
+    fn name(&self) -> &str {
+        std::any::type_name::<Self>()
+    }
+}
+
+impl<F> DispatchInterceptor for F
+where
+    F: Fn(ZubridgeAction) -> Interception + Send + Sync + 'static,
+{
+    fn intercept(&self, action: ZubridgeAction) -> Interception {
+        self(action)
+    }
+}
+
+/// Run an action through every interceptor in order. Actions produced by one interceptor
+/// are passed through the interceptors that follow it. A panicking interceptor fails the dispatch
+/// with [`crate::Error::InterceptorPanicked`], or is skipped under [`ErrorPolicy::LogAndContinue`].
+pub(crate) fn run_interceptors(
+    interceptors: &[std::sync::Arc<dyn DispatchInterceptor>],
+    action: ZubridgeAction,
+    policy: ErrorPolicy,
+) -> crate::Result<Vec<ZubridgeAction>> {
+    let mut actions = vec![action];
+    for interceptor in interceptors {
+        let mut next = Vec::with_capacity(actions.len());
+        for action in actions {
+            let fallback = (policy == ErrorPolicy::LogAndContinue).then(|| action.clone());
+            let action_type = action.action_type.clone();
+            let started = Instant::now();
+            let intercepted = catch_reducer_panic(|| interceptor.intercept(action));
+            breadcrumbs::record(
+                format!("interceptor {}", interceptor.name()),
+                &action_type,
+                || match &intercepted {
+                    Ok(Interception::Continue(_)) => "continue".to_string(),
+                    Ok(Interception::Transformed(actions)) => format!("transformed into {} actions", actions.len()),
+                    Ok(Interception::Rejected(reason)) => format!("rejected: {}", reason),
+                    Err(e) => format!("failed: {}", e),
+                },
+                started.elapsed(),
+            );
+            let interception = match intercepted {
+                Ok(interception) => interception,
+                Err(e) => {
+                    let message = match e {
+                        crate::Error::ReducerPanicked { message } => message,
+                        e => e.to_string(),
+                    };
+                    let Some(action) = fallback else {
+                        return Err(crate::Error::InterceptorPanicked { message });
+                    };
+                    log::warn!("zubridge: interceptor panicked on {}, skipping it: {}", action.action_type, message);
+                    Interception::Continue(action)
+                }
+            };
+            match interception {
+                Interception::Continue(action) => next.push(action),
+                Interception::Transformed(actions) => next.extend(actions),
+                Interception::Rejected(reason) => return Err(crate::Error::ActionRejected(reason)),
+            }
+        }
+        actions = next;
+    }
+    Ok(actions)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use super::*;
+
+    fn action(action_type: &str) -> ZubridgeAction {
+        ZubridgeAction {
+            action_type: action_type.to_string(),
+            payload: None,
+        }
+    }
+
+    fn types(actions: &[ZubridgeAction]) -> Vec<&str> {
+        actions.iter().map(|action| action.action_type.as_str()).collect()
+    }
+
+    #[test]
+    fn passes_split_actions_through_later_interceptors() {
+        let split = |moved: ZubridgeAction| match moved.action_type.as_str() {
+            "TODO:MOVE" => Interception::Transformed(vec![action("TODO:REMOVE"), action("TODO:ADD")]),
+            _ => Interception::Continue(moved),
+        };
+        let prefix = |mut action: ZubridgeAction| {
+            action.action_type = format!("app/{}", action.action_type);
+            Interception::Continue(action)
+        };
+        let interceptors: Vec<Arc<dyn DispatchInterceptor>> = vec![Arc::new(split), Arc::new(prefix)];
+        let actions = run_interceptors(&interceptors, action("TODO:MOVE"), ErrorPolicy::Propagate).unwrap();
+        assert_eq!(types(&actions), ["app/TODO:REMOVE", "app/TODO:ADD"]);
+    }
+
+    #[test]
+    fn rejects_vetoed_actions() {
+        let veto = |_: ZubridgeAction| Interception::Rejected("read only".to_string());
+        let interceptors: Vec<Arc<dyn DispatchInterceptor>> = vec![Arc::new(veto)];
+        let result = run_interceptors(&interceptors, action("TODO:ADD"), ErrorPolicy::Propagate);
+        assert!(matches!(result, Err(crate::Error::ActionRejected(reason)) if reason == "read only"));
+    }
+
+    #[test]
+    fn skips_panicking_interceptors_only_when_told_to() {
+        let panicking = |_: ZubridgeAction| -> Interception { panic!("broken") };
+        let interceptors: Vec<Arc<dyn DispatchInterceptor>> = vec![Arc::new(panicking)];
+        let result = run_interceptors(&interceptors, action("TODO:ADD"), ErrorPolicy::Propagate);
+        assert!(matches!(result, Err(crate::Error::InterceptorPanicked { message }) if message == "broken"));
+
+        let actions = run_interceptors(&interceptors, action("TODO:ADD"), ErrorPolicy::LogAndContinue).unwrap();
+        assert_eq!(types(&actions), ["TODO:ADD"]);
+    }
+}
@@ -0,0 +1,244 @@
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use crate::models::*;
+use crate::snapshots::{self, PatchOperation};
+use crate::utils::stable_hash;
+
+/// A changed lazy slice and the windows that should receive it.
+pub(crate) struct SliceUpdate {
+    pub(crate) slice: String,
+    pub(crate) value: JsonValue,
+    /// The patch from the value last sent, for diffed slices that were sent before.
+    pub(crate) patch: Option<Vec<PatchOperation>>,
+    pub(crate) windows: Vec<String>,
+}
+
+impl SliceUpdate {
+    /// `{ slice, patch }`, or `{ slice, value }` when the slice is sent in full.
+    pub(crate) fn payload(&self) -> JsonValue {
+        match &self.patch {
+            Some(patch) => serde_json::json!({ "slice": self.slice, "patch": patch }),
+            None => serde_json::json!({ "slice": self.slice, "value": self.value }),
+        }
+    }
+}
+
+#[derive(Default)]
+struct Holders {
+    windows: BTreeSet<String>,
+    /// Hash of the value last sent to the holders, so unchanged slices aren't re-sent.
+    last_sent: Option<u64>,
+    /// The value last sent to the holders of a diffed slice, the next patch is made against.
+    last_value: Option<JsonValue>,
+}
+
+/// Tracks which windows have loaded which lazy slices.
+///
+/// Lazy slices are kept out of the broadcast state. A window fetches a slice with
+/// `zubridge.load-slice` and from then on receives targeted updates for it alone.
+pub(crate) struct LazySlices {
+    slices: Vec<String>,
+    /// Slices sent as JSON Patches against the value last sent.
+    diffed: Vec<String>,
+    holders: Mutex<HashMap<String, Holders>>,
+    /// When each slice lost its last holder.
+    detached: Mutex<HashMap<String, Instant>>,
+}
+
+impl LazySlices {
+    pub(crate) fn new(slices: Vec<String>, diffed: Vec<String>) -> Self {
+        Self {
+            slices,
+            diffed,
+            holders: Mutex::new(HashMap::new()),
+            detached: Mutex::new(HashMap::new()),
+        }
+    }
+
+    pub(crate) fn is_lazy(&self, slice: &str) -> bool {
+        self.slices.iter().any(|lazy| lazy == slice)
+    }
+
+    fn is_diffed(&self, slice: &str) -> bool {
+        self.diffed.iter().any(|diffed| diffed == slice)
+    }
+
+    /// Remove the lazy slices from the state and return them.
+    pub(crate) fn split(&self, state: &mut JsonValue) -> BTreeMap<String, JsonValue> {
+        let Some(root) = state.as_object_mut() else {
+            return BTreeMap::new();
+        };
+        self.slices
+            .iter()
+            .filter_map(|slice| root.remove(slice).map(|value| (slice.clone(), value)))
+            .collect()
+    }
+
+    /// Record that `window` holds `slice` from now on.
+    pub(crate) fn subscribe(&self, window: &str, slice: &str, value: &JsonValue) -> crate::Result<()> {
+        if !self.is_lazy(slice) {
+            return Err(crate::Error::UnknownSlice(slice.to_string()));
+        }
+
+        let mut holders = self.holders.lock().map_err(|e| crate::Error::StateError(e.to_string()))?;
+        let entry = holders.entry(slice.to_string()).or_default();
+        entry.windows.insert(window.to_string());
+        entry.last_sent = Some(hash(value));
+        entry.last_value = self.is_diffed(slice).then(|| value.clone());
+
+        let mut detached = self.detached.lock().map_err(|e| crate::Error::StateError(e.to_string()))?;
+        detached.remove(slice);
+        Ok(())
+    }
+
+    pub(crate) fn unsubscribe(&self, window: &str, slice: &str) -> crate::Result<()> {
+        let mut holders = self.holders.lock().map_err(|e| crate::Error::StateError(e.to_string()))?;
+        if let Some(entry) = holders.get_mut(slice) {
+            entry.windows.remove(window);
+        }
+        self.release_empty(&mut holders)
+    }
+
+    /// Drop every slice held by a window, e.g. once it's destroyed.
+    pub(crate) fn forget_window(&self, window: &str) -> crate::Result<()> {
+        let mut holders = self.holders.lock().map_err(|e| crate::Error::StateError(e.to_string()))?;
+        for entry in holders.values_mut() {
+            entry.windows.remove(window);
+        }
+        self.release_empty(&mut holders)
+    }
+
+    /// Remove slices nobody holds anymore and start their detach timers.
+    fn release_empty(&self, holders: &mut HashMap<String, Holders>) -> crate::Result<()> {
+        let mut detached = self.detached.lock().map_err(|e| crate::Error::StateError(e.to_string()))?;
+        holders.retain(|slice, entry| {
+            if entry.windows.is_empty() {
+                detached.insert(slice.clone(), Instant::now());
+            }
+            !entry.windows.is_empty()
+        });
+        Ok(())
+    }
+
+    /// Whether any window holds `slice`.
+    pub(crate) fn is_held(&self, slice: &str) -> crate::Result<bool> {
+        let holders = self.holders.lock().map_err(|e| crate::Error::StateError(e.to_string()))?;
+        Ok(holders.contains_key(slice))
+    }
+
+    /// Take the slices that have been detached for at least `delay`. Check they're still not
+    /// held, under the state manager's lock, before evicting them.
+    pub(crate) fn take_expired(&self, delay: Duration) -> crate::Result<Vec<String>> {
+        let mut detached = self.detached.lock().map_err(|e| crate::Error::StateError(e.to_string()))?;
+        let expired: Vec<String> = detached
+            .iter()
+            .filter(|(_, since)| since.elapsed() >= delay)
+            .map(|(slice, _)| slice.clone())
+            .collect();
+        for slice in &expired {
+            detached.remove(slice);
+        }
+        Ok(expired)
+    }
+
+    /// Forget what was last sent for `slice`, so the next update re-sends it in full.
+    pub(crate) fn invalidate(&self, slice: &str) -> crate::Result<()> {
+        let mut holders = self.holders.lock().map_err(|e| crate::Error::StateError(e.to_string()))?;
+        if let Some(entry) = holders.get_mut(slice) {
+            entry.last_sent = None;
+            entry.last_value = None;
+        }
+        Ok(())
+    }
+
+    /// The windows holding each slice.
+    pub(crate) fn windows(&self) -> crate::Result<BTreeMap<String, Vec<String>>> {
+        let holders = self.holders.lock().map_err(|e| crate::Error::StateError(e.to_string()))?;
+        Ok(holders
+            .iter()
+            .map(|(slice, entry)| (slice.clone(), entry.windows.iter().cloned().collect()))
+            .collect())
+    }
+
+    /// Work out which held slices changed since they were last sent.
+    pub(crate) fn updates(&self, values: BTreeMap<String, JsonValue>) -> crate::Result<Vec<SliceUpdate>> {
+        let mut holders = self.holders.lock().map_err(|e| crate::Error::StateError(e.to_string()))?;
+        let mut updates = Vec::new();
+        for (slice, value) in values {
+            let Some(entry) = holders.get_mut(&slice) else { continue };
+            let hash = hash(&value);
+            if entry.last_sent == Some(hash) {
+                continue;
+            }
+            entry.last_sent = Some(hash);
+            let patch = entry.last_value.as_ref().map(|last| snapshots::diff(last, &value).patch);
+            entry.last_value = self.is_diffed(&slice).then(|| value.clone());
+            updates.push(SliceUpdate {
+                slice,
+                value,
+                patch,
+                windows: entry.windows.iter().cloned().collect(),
+            });
+        }
+        Ok(updates)
+    }
+}
+
+fn hash(value: &JsonValue) -> u64 {
+    stable_hash(&value.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn slices() -> LazySlices {
+        LazySlices::new(vec!["history".into(), "logs".into()], vec!["logs".into()])
+    }
+
+    #[test]
+    fn detaches_slices_once_their_last_holder_leaves() {
+        let slices = slices();
+        slices.subscribe("main", "history", &json!([1])).unwrap();
+        slices.subscribe("other", "history", &json!([1])).unwrap();
+        slices.unsubscribe("main", "history").unwrap();
+        assert!(slices.is_held("history").unwrap());
+        assert!(slices.take_expired(Duration::ZERO).unwrap().is_empty());
+
+        slices.forget_window("other").unwrap();
+        assert!(!slices.is_held("history").unwrap());
+        assert_eq!(slices.take_expired(Duration::ZERO).unwrap(), vec!["history".to_string()]);
+        assert!(slices.take_expired(Duration::ZERO).unwrap().is_empty());
+    }
+
+    #[test]
+    fn expired_slices_loaded_again_are_held() {
+        let slices = slices();
+        slices.subscribe("main", "history", &json!([1])).unwrap();
+        slices.unsubscribe("main", "history").unwrap();
+        let expired = slices.take_expired(Duration::ZERO).unwrap();
+        slices.subscribe("main", "history", &json!([1])).unwrap();
+        assert_eq!(expired, vec!["history".to_string()]);
+        assert!(slices.is_held("history").unwrap());
+    }
+
+    #[test]
+    fn sends_changed_slices_to_their_holders() {
+        let slices = slices();
+        assert!(slices.subscribe("main", "settings", &json!({})).is_err());
+        slices.subscribe("main", "history", &json!([1])).unwrap();
+        slices.subscribe("main", "logs", &json!(["a"])).unwrap();
+
+        let unchanged = BTreeMap::from([("history".to_string(), json!([1]))]);
+        assert!(slices.updates(unchanged).unwrap().is_empty());
+
+        let changed = BTreeMap::from([("history".to_string(), json!([1, 2])), ("logs".to_string(), json!(["a", "b"]))]);
+        let updates = slices.updates(changed).unwrap();
+        assert_eq!(updates[0].payload(), json!({ "slice": "history", "value": [1, 2] }));
+        assert_eq!(updates[1].windows, vec!["main".to_string()]);
+        assert!(updates[1].payload()["patch"].is_array());
+    }
+}
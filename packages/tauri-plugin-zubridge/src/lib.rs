@@ -3,6 +3,8 @@ use tauri::{
   plugin::{Builder, TauriPlugin},
   Manager, Runtime,
 };
+#[cfg(desktop)]
+use tauri::{webview::PageLoadEvent, DragDropEvent, RunEvent, WindowEvent};
 
 pub use models::*;
 
@@ -11,11 +13,209 @@ mod desktop;
 #[cfg(mobile)]
 mod mobile;
 
+mod action_log;
+mod aggregates;
+#[cfg(desktop)]
+mod appearance;
+mod authorization;
+mod batch;
+mod breadcrumbs;
+#[cfg(desktop)]
+mod blobs;
+#[cfg(desktop)]
+mod bridge_access;
 mod commands;
+mod compaction;
+mod config_file;
+mod confirmations;
+#[cfg(desktop)]
+mod crash_report;
+#[cfg(any(feature = "sqlite", feature = "postgres"))]
+mod database;
+#[cfg(debug_assertions)]
+mod dev_handoff;
+mod dev_reload;
+mod deep_link;
+mod dialogs;
+#[cfg(all(desktop, feature = "e2e"))]
+pub mod e2e;
+#[cfg(desktop)]
+mod emit_retry;
+pub mod effects;
+mod ephemeral;
 mod error;
+mod error_policy;
+mod error_reports;
+mod event_names;
+mod experiments;
+#[cfg(desktop)]
+mod extensions;
+mod feature_flags;
+#[cfg(debug_assertions)]
+mod fixtures;
+#[cfg(desktop)]
+mod forms;
+#[cfg(desktop)]
+mod fs_watch;
+mod health;
+mod ids;
+mod initial_state;
+#[cfg(desktop)]
+mod import;
+mod integrity;
+mod install_id;
+mod interceptors;
+mod lazy_slices;
+#[cfg(all(desktop, feature = "stress"))]
+mod load;
+mod merge;
+mod metrics;
 mod models;
+#[cfg(all(desktop, feature = "mqtt"))]
+mod mqtt;
+mod paginator;
+mod persistence;
+mod plugin_slices;
+mod path_actions;
+mod platform_prefs;
+#[cfg(desktop)]
+mod protocol;
+#[cfg(all(desktop, debug_assertions))]
+mod repl;
+mod rpc;
+#[cfg(feature = "schema")]
+mod schema;
+#[cfg(feature = "secrets")]
+mod secrets;
+mod sensitive;
+mod session;
+mod sequences;
+#[cfg(desktop)]
+mod shared_memory;
+mod snapshots;
+#[cfg(all(desktop, feature = "stress"))]
+mod soak;
+mod single_instance;
+mod slice_router;
+mod state_graph;
+mod startup;
+mod state_check;
+mod state_waiters;
+mod statechart;
+mod subscriptions;
+mod subsystems;
+mod sync;
+mod telemetry;
+mod timers;
+#[cfg(desktop)]
+mod tray;
+mod trash;
+#[cfg(all(desktop, any(test, feature = "test")))]
+pub mod test;
+#[cfg(desktop)]
+mod updater;
+mod uploads;
+mod utils;
+mod warm_start;
+#[cfg(feature = "wasm-reducers")]
+mod wasm_reducers;
+#[cfg(feature = "reqwest")]
+mod webhooks;
+#[cfg(desktop)]
+mod window_actions;
+mod workflows;
+mod workspaces;
 
+pub use action_log::ActionLogEntry;
+pub use aggregates::Aggregation;
+#[cfg(desktop)]
+pub use appearance::{Appearance, AppearanceConfig, AppearanceProvider};
+#[cfg(desktop)]
+pub use bridge_access::BridgeAccess;
+pub use authorization::AuthorizationConfig;
+pub use batch::{BatchMode, BatchResult};
+#[cfg(desktop)]
+pub use blobs::{BlobRef, BlobStoreConfig};
+pub use compaction::{CompactionConfig, Retention};
+pub use config_file::read_config_file;
+pub use confirmations::{ConfirmationPrompt, ConfirmationsConfig};
+#[cfg(desktop)]
+pub use crash_report::CrashReportConfig;
+pub use deep_link::DeepLinkConfig;
+pub use dialogs::{DialogPresenter, DialogResponder, DialogsConfig};
+#[cfg(desktop)]
+pub use emit_retry::EmitRetryConfig;
+pub use breadcrumbs::Breadcrumb;
+#[cfg(any(feature = "sqlite", feature = "postgres"))]
+pub use database::{DatabaseConfig, DatabaseStateManager, TransactionFuture};
+#[cfg(debug_assertions)]
+pub use dev_handoff::DevHandoffConfig;
+pub use dev_reload::DevReloadConfig;
 pub use error::{Error, Result};
+pub use error_policy::ErrorPolicy;
+pub use error_reports::{ErrorReport, ErrorReportingConfig, ErrorSink, FrontendError};
+pub use event_names::EventNames;
+pub use experiments::{Experiment, ExperimentVariant};
+#[cfg(desktop)]
+pub use extensions::ExtensionConfig;
+pub use feature_flags::{FeatureFlag, FlagRule, FlagTarget};
+#[cfg(debug_assertions)]
+pub use fixtures::FixturesConfig;
+pub use forms::{FieldValidator, Form, FormValidator};
+pub use health::{Health, InitialStateEnvelope, VersionInfo, MIN_PROTOCOL_VERSION, PROTOCOL_VERSION};
+pub use ids::{IdGenerator, UuidV7};
+pub use initial_state::{InitialStateFn, InitialStateProvider};
+#[cfg(desktop)]
+pub use import::{ImportProgress, ImportSink};
+pub use integrity::{Handshake, SignedPayload};
+pub use interceptors::{DispatchInterceptor, Interception};
+#[cfg(all(desktop, feature = "stress"))]
+pub use load::{ActionGenerator, LoadProfile, LoadReport};
+pub use merge::MergeStrategy;
+pub use metrics::MetricsSnapshot;
+#[cfg(all(desktop, feature = "mqtt"))]
+pub use mqtt::{MqttConfig, MqttOptions, QoS};
+#[cfg(desktop)]
+pub use shared_memory::{SharedMemoryDescriptor, StateTransport};
+pub use snapshots::{PatchOperation, Simulation, SnapshotDiff};
+#[cfg(all(desktop, feature = "stress"))]
+pub use soak::{SoakConfig, SoakReport, SoakSample};
+#[cfg(all(desktop, debug_assertions))]
+pub use repl::REPL_VAR;
+#[cfg(feature = "secrets")]
+pub use secrets::SecretsConfig;
+pub use sensitive::ENCRYPTED_FIELD;
+pub use session::SessionLockConfig;
+pub use single_instance::{SecondInstanceConfig, SecondInstanceMapper};
+pub use slice_router::{SliceReducer, SliceRoute};
+pub use paginator::{Page, PageFetchFn, PageRequest, Paginator};
+pub use persistence::{FsyncPolicy, PersistenceConfig};
+pub use platform_prefs::{PlatformPrefsBackend, PlatformPrefsConfig};
+pub use startup::{StartupConfig, StartupProgress};
+pub use state_check::StateMigration;
+pub use state_graph::GraphFormat;
+pub use statechart::{StateEffect, Statechart, TransitionGuard};
+pub use subscriptions::StateUpdate;
+pub use subsystems::{SubsystemStatus, Subsystems};
+pub use sync::{SyncConfig, SyncFn, SyncTask};
+pub use telemetry::{TelemetryConfig, TelemetryEvent, TelemetrySink};
+pub use timers::TimersConfig;
+pub use trash::TrashConfig;
+pub use warm_start::WarmStartConfig;
+#[cfg(desktop)]
+pub use fs_watch::{FsWatch, FsWatchMapper};
+#[cfg(desktop)]
+pub use tray::{StatePredicate, TrayActionMap};
+#[cfg(desktop)]
+pub use updater::{UpdateReporter, UpdaterBackend, UpdaterConfig, UpdaterState, UpdaterStatus};
+#[cfg(desktop)]
+pub use window_actions::{WindowActionsConfig, WindowDefinition};
+#[cfg(feature = "wasm-reducers")]
+pub use wasm_reducers::{WasmLimits, WasmReducer};
+#[cfg(feature = "reqwest")]
+pub use webhooks::{Webhook, WebhookPayload, WebhooksConfig, DELIVERY_HEADER, SIGNATURE_HEADER};
+pub use workflows::{Workflow, WorkflowContext, WorkflowFn};
+pub use workspaces::WorkspacesConfig;
 
 #[cfg(desktop)]
 use desktop::Zubridge;
@@ -25,37 +225,230 @@ use mobile::Zubridge;
 /// Extensions to [`tauri::App`], [`tauri::AppHandle`] and [`tauri::Window`] to access the zubridge APIs.
 pub trait ZubridgeExt<R: Runtime> {
   fn zubridge(&self) -> &Zubridge<R>;
+
+  /// Mount a state manager under `namespace` in the app's state, for plugins that keep their
+  /// state in the app's zubridge store. See [`crate::Zubridge::register_slice`].
+  /// Fails if the zubridge plugin isn't set up yet, so register it before the plugins using it.
+  #[cfg(desktop)]
+  fn register_slice<S: StateManager>(&self, namespace: &str, manager: S) -> Result<()>;
 }
 
 impl<R: Runtime, T: Manager<R>> crate::ZubridgeExt<R> for T {
   fn zubridge(&self) -> &Zubridge<R> {
     self.state::<Zubridge<R>>().inner()
   }
+
+  #[cfg(desktop)]
+  fn register_slice<S: StateManager>(&self, namespace: &str, manager: S) -> Result<()> {
+    self
+      .try_state::<Zubridge<R>>()
+      .ok_or_else(|| Error::ConfigError("the zubridge plugin isn't set up yet".into()))?
+      .register_slice(namespace, manager)
+  }
 }
 
 // Constants for commands and events
 pub const GET_INITIAL_STATE_COMMAND: &str = "zubridge.get-initial-state";
 pub const DISPATCH_ACTION_COMMAND: &str = "zubridge.dispatch-action";
+pub const DISPATCH_BATCH_COMMAND: &str = "zubridge.dispatch-batch";
+pub const GET_SCHEMA_COMMAND: &str = "zubridge.get-schema";
+pub const SET_EPHEMERAL_COMMAND: &str = "zubridge.set-ephemeral";
+pub const GET_EPHEMERAL_COMMAND: &str = "zubridge.get-ephemeral";
+pub const LOAD_SLICE_COMMAND: &str = "zubridge.load-slice";
+pub const UNLOAD_SLICE_COMMAND: &str = "zubridge.unload-slice";
+pub const GET_STARTUP_COMMAND: &str = "zubridge.get-startup";
+pub const REQUEST_COMMAND: &str = "zubridge.request";
+pub const RESPOND_COMMAND: &str = "zubridge.respond";
+pub const PUBLISH_COMMAND: &str = "zubridge.publish";
+pub const REPORT_ERROR_COMMAND: &str = "zubridge.report-error";
+pub const SAVE_SNAPSHOT_COMMAND: &str = "zubridge.save-snapshot";
+pub const LIST_SNAPSHOTS_COMMAND: &str = "zubridge.list-snapshots";
+pub const DIFF_SNAPSHOTS_COMMAND: &str = "zubridge.diff-snapshots";
+pub const RESTORE_SNAPSHOT_COMMAND: &str = "zubridge.restore-snapshot";
+pub const SIMULATE_ACTION_COMMAND: &str = "zubridge.simulate-action";
+pub const EXPORT_STATE_GRAPH_COMMAND: &str = "zubridge.export-state-graph";
+pub const SUBSCRIBE_STATE_COMMAND: &str = "zubridge.subscribe-state";
+pub const HANDSHAKE_COMMAND: &str = "zubridge.handshake";
+pub const HEALTH_COMMAND: &str = "zubridge.health";
+pub const VERSION_COMMAND: &str = "zubridge.version";
+pub const GET_EVENT_NAMES_COMMAND: &str = "zubridge.get-event-names";
+pub const UNSUBSCRIBE_STATE_COMMAND: &str = "zubridge.unsubscribe-state";
+pub const ACKNOWLEDGE_STATE_COMMAND: &str = "zubridge.acknowledge-state";
+pub const UPLOAD_BLOB_COMMAND: &str = "zubridge.upload-blob";
+pub const CONFIRM_COMMAND: &str = "zubridge.confirm";
+pub const CANCEL_CONFIRMATION_COMMAND: &str = "zubridge.cancel-confirmation";
+pub const SEARCH_COMMAND: &str = "zubridge.search";
+pub const REPORT_APPEARANCE_COMMAND: &str = "zubridge.report-appearance";
+pub const LOAD_FIXTURE_COMMAND: &str = "zubridge.load-fixture";
+pub const SOAK_COMMAND: &str = "zubridge.soak";
+// The `legacy_event_names`; by default each is scoped to the app identifier, see [`EventNames`]
 pub const STATE_UPDATE_EVENT: &str = "zubridge://state-update";
+pub const IMPORT_PROGRESS_EVENT: &str = "zubridge://import-progress";
+pub const EPHEMERAL_UPDATE_EVENT: &str = "zubridge://ephemeral-update";
+pub const SLICE_UPDATE_EVENT: &str = "zubridge://slice-update";
+pub const DIAGNOSTICS_EVENT: &str = "zubridge://diagnostics";
+pub const STARTUP_EVENT: &str = "zubridge://startup";
+pub const REQUEST_EVENT: &str = "zubridge://request";
+pub const MESSAGE_EVENT: &str = "zubridge://message";
+pub const SHARED_MEMORY_PROTOCOL: &str = "zubridge-shm";
+pub const STATE_PROTOCOL: &str = "zubridge";
+pub const BLOB_PROTOCOL: &str = "zubridge-blob";
+
+/// The plugin builder with all commands and protocols registered.
+fn builder<R: Runtime>() -> Builder<R> {
+    let builder = Builder::new("zubridge")
+        .invoke_handler(tauri::generate_handler![
+            commands::get_initial_state,
+            commands::dispatch_action,
+            commands::dispatch_batch,
+            commands::get_schema,
+            commands::set_ephemeral,
+            commands::get_ephemeral,
+            commands::load_slice,
+            commands::unload_slice,
+            commands::subscribe_state,
+            commands::unsubscribe_state,
+            commands::acknowledge_state,
+            commands::upload_blob,
+            commands::handshake,
+            commands::health,
+            commands::version,
+            commands::get_event_names,
+            commands::get_startup,
+            commands::request,
+            commands::respond,
+            commands::publish,
+            commands::report_error,
+            commands::save_snapshot,
+            commands::list_snapshots,
+            commands::diff_snapshots,
+            commands::restore_snapshot,
+            commands::simulate_action,
+            commands::export_state_graph,
+            commands::confirm,
+            commands::cancel_confirmation,
+            commands::report_appearance,
+            #[cfg(debug_assertions)]
+            commands::load_fixture,
+            #[cfg(all(desktop, feature = "stress"))]
+            commands::soak,
+            #[cfg(feature = "search")]
+            commands::search
+        ]);
+
+    #[cfg(desktop)]
+    let builder = builder
+        .register_uri_scheme_protocol(SHARED_MEMORY_PROTOCOL, |ctx, request| {
+            shared_memory::handle_request(ctx.app_handle(), ctx.webview_label(), request)
+        })
+        .register_uri_scheme_protocol(STATE_PROTOCOL, |ctx, request| {
+            protocol::handle_request(ctx.app_handle(), ctx.webview_label(), request)
+        })
+        .register_uri_scheme_protocol(BLOB_PROTOCOL, |ctx, request| {
+            blobs::handle_request(ctx.app_handle(), ctx.webview_label(), request)
+        })
+        .on_window_ready(|window| {
+            if let Some(zubridge) = window.try_state::<Zubridge<R>>() {
+                if let Err(e) = zubridge.window_opened(&window) {
+                    log::warn!("zubridge: failed to register window {}: {}", window.label(), e);
+                }
+                if let Ok(theme) = window.theme() {
+                    if let Err(e) = zubridge.theme_changed(theme) {
+                        log::warn!("zubridge: failed to update the appearance: {}", e);
+                    }
+                }
+            }
+        })
+        .on_page_load(|webview, payload| {
+            let Some(zubridge) = webview.try_state::<Zubridge<R>>() else { return };
+            match payload.event() {
+                // A new page has to handshake again
+                PageLoadEvent::Started => zubridge.page_loading(webview.label()),
+                PageLoadEvent::Finished => {
+                    if let Err(e) = zubridge.page_loaded(webview.label()) {
+                        log::warn!("zubridge: failed to re-send the state to reloaded {}: {}", webview.label(), e);
+                    }
+                }
+            }
+        })
+        .on_event(|app, event| {
+            let Some(zubridge) = app.try_state::<Zubridge<R>>() else { return };
+            if let RunEvent::Exit = event {
+                zubridge.shutdown();
+                return;
+            }
+            let RunEvent::WindowEvent { label, event, .. } = event else { return };
+            match event {
+                WindowEvent::Destroyed => {
+                    // Windows hold lazy slices until they're destroyed
+                    if let Err(e) = zubridge.forget_window(label) {
+                        log::warn!("zubridge: failed to release slices for {}: {}", label, e);
+                    }
+                    if let Err(e) = zubridge.window_closed(label) {
+                        log::warn!("zubridge: failed to unregister window {}: {}", label, e);
+                    }
+                }
+                WindowEvent::CloseRequested { api, .. } => match zubridge.close_to_tray(label) {
+                    Ok(true) => api.prevent_close(),
+                    Ok(false) => {}
+                    Err(e) => log::warn!("zubridge: failed to hide {} to the tray: {}", label, e),
+                },
+                WindowEvent::DragDrop(DragDropEvent::Drop { paths, position }) => {
+                    if let Err(e) = zubridge.file_dropped(label, paths, (position.x, position.y)) {
+                        log::warn!("zubridge: failed to dispatch file drop on {}: {}", label, e);
+                    }
+                }
+                WindowEvent::ThemeChanged(theme) => {
+                    if let Err(e) = zubridge.theme_changed(*theme) {
+                        log::warn!("zubridge: failed to update the appearance: {}", e);
+                    }
+                }
+                WindowEvent::Focused(focused) => {
+                    if let Err(e) = zubridge.window_focused(label, *focused) {
+                        log::warn!("zubridge: failed to update window {}: {}", label, e);
+                    }
+                }
+                _ => {}
+            }
+        });
+
+    builder
+}
 
 /// Creates the Zubridge plugin with the provided state manager and options.
 /// The plugin manages the state and emits events on updates.
 pub fn plugin<R: Runtime, S: StateManager>(
     state_manager: S,
-    options: ZubridgeOptions,
+    mut options: ZubridgeOptions,
 ) -> TauriPlugin<R> {
+    // Slices the state manager materializes on demand are lazy and diffed
+    for slice in state_manager.query_slices() {
+        if !options.lazy_slices.contains(&slice) {
+            options.lazy_slices.push(slice.clone());
+        }
+        if !options.diffed_slices.contains(&slice) {
+            options.diffed_slices.push(slice);
+        }
+    }
     let state_arc: Arc<Mutex<dyn StateManager>> = Arc::new(Mutex::new(state_manager));
 
-    Builder::new("zubridge")
-        .invoke_handler(tauri::generate_handler![
-            commands::get_initial_state,
-            commands::dispatch_action
-        ])
+    builder()
         .setup(move |app, api| {
             #[cfg(mobile)]
-            let zubridge = mobile::init(app, api)?;
+            let zubridge = mobile::init(app, api, options.clone())?;
+            #[cfg(desktop)]
+            let zubridge = desktop::init(app, api, options.clone())?;
+
+            // Let the state manager emit changes made outside dispatches
             #[cfg(desktop)]
-            let zubridge = desktop::init(app, api)?;
+            if let Ok(mut state_manager) = state_arc.lock() {
+                let handle = app.clone();
+                state_manager.watch_changes(Arc::new(move || {
+                    if let Some(zubridge) = handle.try_state::<Zubridge<R>>() {
+                        zubridge.state_changed();
+                    }
+                }));
+            }
 
             // Register the state manager and options
             app.manage(state_arc);
@@ -76,16 +469,12 @@ pub fn plugin_default<R: Runtime, S: StateManager>(
 /// Initializes the plugin without a state manager.
 /// You'll need to register a state manager manually using the ZubridgeExt API.
 pub fn init<R: Runtime>() -> TauriPlugin<R> {
-  Builder::new("zubridge")
-    .invoke_handler(tauri::generate_handler![
-        commands::get_initial_state,
-        commands::dispatch_action
-    ])
+  builder()
     .setup(|app, api| {
       #[cfg(mobile)]
-      let zubridge = mobile::init(app, api)?;
+      let zubridge = mobile::init(app, api, ZubridgeOptions::default())?;
       #[cfg(desktop)]
-      let zubridge = desktop::init(app, api)?;
+      let zubridge = desktop::init(app, api, ZubridgeOptions::default())?;
       app.manage(zubridge);
       Ok(())
     })
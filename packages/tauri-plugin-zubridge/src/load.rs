@@ -0,0 +1,167 @@
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use serde::Serialize;
+
+use crate::metrics::MetricsSnapshot;
+use crate::models::*;
+
+/// Builds the action dispatched at each step of a load run, given the step's index.
+pub type ActionGenerator = Arc<dyn Fn(u64) -> ZubridgeAction + Send + Sync>;
+
+/// Synthetic dispatch load for `Zubridge::run_load`, used to validate performance changes.
+#[derive(Clone)]
+pub struct LoadProfile {
+    generator: ActionGenerator,
+    /// Dispatches per second across all threads, or 0 to dispatch as fast as possible.
+    /// Defaults to 100.
+    pub rate: u32,
+    /// How long to generate load for. Defaults to 10 seconds.
+    pub duration: Duration,
+    /// How many threads dispatch concurrently. Defaults to 1.
+    pub threads: usize,
+}
+
+impl LoadProfile {
+    pub fn new<F>(generator: F) -> Self
+    where
+        F: Fn(u64) -> ZubridgeAction + Send + Sync + 'static,
+    {
+        Self {
+            generator: Arc::new(generator),
+            rate: 100,
+            duration: Duration::from_secs(10),
+            threads: 1,
+        }
+    }
+
+    pub fn with_rate(mut self, rate: u32) -> Self {
+        self.rate = rate;
+        self
+    }
+
+    pub fn with_duration(mut self, duration: Duration) -> Self {
+        self.duration = duration;
+        self
+    }
+
+    pub fn with_threads(mut self, threads: usize) -> Self {
+        self.threads = threads.max(1);
+        self
+    }
+}
+
+/// The outcome of a load run.
+#[derive(Clone, Debug, Serialize)]
+pub struct LoadReport {
+    pub dispatched: u64,
+    pub failed: u64,
+    pub elapsed_millis: u64,
+    /// Dispatches per second actually achieved.
+    pub rate: f64,
+    /// Round-trip time of `dispatch_action`, including emitting the state.
+    pub mean_dispatch_micros: u64,
+    pub max_dispatch_micros: u64,
+    /// Plugin metrics accumulated during the run.
+    pub metrics: MetricsSnapshot,
+}
+
+/// Run `profile` with `dispatcher`, blocking until it's done. `before` is the plugin's metrics
+/// snapshot from before the run, and `after` takes the one after it.
+pub(crate) fn run(
+    profile: &LoadProfile,
+    dispatcher: Dispatcher,
+    before: MetricsSnapshot,
+    after: impl FnOnce() -> MetricsSnapshot,
+) -> LoadReport {
+    let next_index = Arc::new(AtomicU64::new(0));
+    let failed = Arc::new(AtomicU64::new(0));
+    let total_micros = Arc::new(AtomicU64::new(0));
+    let max_micros = Arc::new(AtomicU64::new(0));
+    // Each thread paces itself to its share of the rate
+    let interval = match profile.rate {
+        0 => Duration::ZERO,
+        rate => Duration::from_secs_f64(profile.threads as f64 / f64::from(rate)),
+    };
+
+    let started = Instant::now();
+    let deadline = started + profile.duration;
+    let threads: Vec<_> = (0..profile.threads)
+        .map(|_| {
+            let generator = profile.generator.clone();
+            let dispatcher = dispatcher.clone();
+            let (next_index, failed) = (next_index.clone(), failed.clone());
+            let (total_micros, max_micros) = (total_micros.clone(), max_micros.clone());
+            std::thread::spawn(move || {
+                let mut next = Instant::now();
+                while Instant::now() < deadline {
+                    let action = generator(next_index.fetch_add(1, Ordering::Relaxed));
+                    let dispatching = Instant::now();
+                    if dispatcher.dispatch(action).is_err() {
+                        failed.fetch_add(1, Ordering::Relaxed);
+                    }
+                    let micros = dispatching.elapsed().as_micros() as u64;
+                    total_micros.fetch_add(micros, Ordering::Relaxed);
+                    max_micros.fetch_max(micros, Ordering::Relaxed);
+
+                    next += interval;
+                    if let Some(wait) = next.checked_duration_since(Instant::now()) {
+                        std::thread::sleep(wait);
+                    }
+                }
+            })
+        })
+        .collect();
+    for thread in threads {
+        if thread.join().is_err() {
+            log::warn!("zubridge: a load thread panicked");
+        }
+    }
+
+    let elapsed = started.elapsed();
+    let dispatched = next_index.load(Ordering::Relaxed);
+    LoadReport {
+        dispatched,
+        failed: failed.load(Ordering::Relaxed),
+        elapsed_millis: elapsed.as_millis() as u64,
+        rate: dispatched as f64 / elapsed.as_secs_f64().max(f64::EPSILON),
+        mean_dispatch_micros: total_micros.load(Ordering::Relaxed) / dispatched.max(1),
+        max_dispatch_micros: max_micros.load(Ordering::Relaxed),
+        metrics: after().since(&before),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn dispatches_generated_actions_until_the_deadline() {
+        let profile = LoadProfile::new(|index| ZubridgeAction {
+            action_type: if index % 2 == 0 { "ok" } else { "fail" }.into(),
+            payload: None,
+        })
+        .with_rate(0)
+        .with_duration(Duration::from_millis(20))
+        .with_threads(2);
+        let dispatcher = Dispatcher::new(|action| match action.action_type.as_str() {
+            "ok" => Ok(JsonValue::Null),
+            _ => Err(crate::Error::StateError("failed".into())),
+        });
+        let before = MetricsSnapshot {
+            dispatches: 3,
+            ..Default::default()
+        };
+        let after = MetricsSnapshot {
+            dispatches: 10,
+            ..Default::default()
+        };
+
+        let report = run(&profile, dispatcher, before, || after);
+        assert!(report.dispatched > 0);
+        assert!(report.failed > 0 && report.failed < report.dispatched);
+        assert!(report.elapsed_millis >= 20);
+        assert_eq!(report.metrics.dispatches, 7);
+    }
+}
@@ -0,0 +1,176 @@
+use std::collections::BTreeMap;
+use std::fmt;
+use std::sync::Arc;
+
+use crate::models::*;
+
+/// Combines the local and incoming values, `null` when missing, into the merged value.
+pub type MergeFn = Arc<dyn Fn(&JsonValue, &JsonValue) -> JsonValue + Send + Sync>;
+
+/// How a value coming from an import or a restored snapshot is combined with the local one,
+/// registered per path in `merge_strategies`.
+#[derive(Clone)]
+pub enum MergeStrategy {
+    /// Take the incoming value, dropping the local one. The default.
+    Replace,
+    /// Merge objects key by key, keeping local keys the incoming value doesn't have. Other
+    /// values are replaced.
+    DeepMerge,
+    /// Keep the local value, taking the incoming one only if there's none.
+    KeepLocal,
+    /// Combine the local and incoming values with a [`MergeFn`].
+    Custom(MergeFn),
+}
+
+impl MergeStrategy {
+    pub fn custom(merge: impl Fn(&JsonValue, &JsonValue) -> JsonValue + Send + Sync + 'static) -> Self {
+        Self::Custom(Arc::new(merge))
+    }
+}
+
+impl fmt::Debug for MergeStrategy {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Replace => f.write_str("Replace"),
+            Self::DeepMerge => f.write_str("DeepMerge"),
+            Self::KeepLocal => f.write_str("KeepLocal"),
+            Self::Custom(_) => f.write_str("Custom(..)"),
+        }
+    }
+}
+
+/// Merge `incoming` into `local`, with the strategies registered for dot-separated paths, where
+/// a `*` segment matches any key. Paths without one inherit their parent's `Replace` or
+/// `DeepMerge`; the root is replaced unless told otherwise.
+pub(crate) fn merge(local: &JsonValue, incoming: &JsonValue, strategies: &BTreeMap<String, MergeStrategy>) -> JsonValue {
+    let merger = Merger { strategies };
+    merger
+        .merge(&mut Vec::new(), Some(local), Some(incoming), &MergeStrategy::Replace)
+        .unwrap_or(JsonValue::Null)
+}
+
+struct Merger<'a> {
+    strategies: &'a BTreeMap<String, MergeStrategy>,
+}
+
+impl Merger<'_> {
+    /// The merged value at `path`, or `None` if it's dropped.
+    fn merge(
+        &self,
+        path: &mut Vec<String>,
+        local: Option<&JsonValue>,
+        incoming: Option<&JsonValue>,
+        inherited: &MergeStrategy,
+    ) -> Option<JsonValue> {
+        let strategy = self.strategy(path).unwrap_or(inherited);
+        match strategy {
+            MergeStrategy::KeepLocal => local.or(incoming).cloned(),
+            MergeStrategy::Custom(merge) => {
+                Some(merge(local.unwrap_or(&JsonValue::Null), incoming.unwrap_or(&JsonValue::Null)))
+            }
+            MergeStrategy::Replace | MergeStrategy::DeepMerge => {
+                let deep = matches!(strategy, MergeStrategy::DeepMerge);
+                // Replaced values are only walked when a path below has a strategy of its own
+                let walk = deep || self.has_strategy_below(path);
+                match (local, incoming) {
+                    (Some(JsonValue::Object(local)), Some(JsonValue::Object(incoming))) if walk => {
+                        let mut merged = serde_json::Map::new();
+                        for key in local.keys().chain(incoming.keys().filter(|key| !local.contains_key(*key))) {
+                            path.push(key.clone());
+                            if let Some(value) = self.merge(path, local.get(key), incoming.get(key), strategy) {
+                                merged.insert(key.clone(), value);
+                            }
+                            path.pop();
+                        }
+                        Some(JsonValue::Object(merged))
+                    }
+                    _ if deep => incoming.or(local).cloned(),
+                    _ => incoming.cloned(),
+                }
+            }
+        }
+    }
+
+    fn strategy(&self, path: &[String]) -> Option<&MergeStrategy> {
+        self.strategies
+            .iter()
+            .find(|(pattern, _)| {
+                let segments: Vec<&str> = pattern.split('.').collect();
+                segments.len() == path.len() && segments_match(&segments, path)
+            })
+            .map(|(_, strategy)| strategy)
+    }
+
+    fn has_strategy_below(&self, path: &[String]) -> bool {
+        self.strategies.keys().any(|pattern| {
+            let segments: Vec<&str> = pattern.split('.').collect();
+            segments.len() > path.len() && segments_match(&segments[..path.len()], path)
+        })
+    }
+}
+
+fn segments_match(pattern: &[&str], path: &[String]) -> bool {
+    pattern.iter().zip(path).all(|(segment, key)| *segment == "*" || segment == key)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn strategies(entries: Vec<(&str, MergeStrategy)>) -> BTreeMap<String, MergeStrategy> {
+        entries.into_iter().map(|(path, strategy)| (path.to_string(), strategy)).collect()
+    }
+
+    #[test]
+    fn replaces_by_default() {
+        let local = json!({ "todos": [1], "settings": { "theme": "dark" } });
+        let incoming = json!({ "todos": [2] });
+        assert_eq!(merge(&local, &incoming, &BTreeMap::new()), incoming);
+    }
+
+    #[test]
+    fn applies_strategies_below_replaced_values() {
+        let local = json!({ "settings": { "theme": "dark", "font": 12 }, "todos": [1], "draft": "local" });
+        let incoming = json!({ "settings": { "theme": "light" }, "todos": [2], "draft": "incoming" });
+        let merged = merge(
+            &local,
+            &incoming,
+            &strategies(vec![("settings", MergeStrategy::DeepMerge), ("draft", MergeStrategy::KeepLocal)]),
+        );
+        assert_eq!(
+            merged,
+            json!({ "settings": { "theme": "light", "font": 12 }, "todos": [2], "draft": "local" })
+        );
+    }
+
+    #[test]
+    fn matches_any_key_with_a_wildcard() {
+        let local = json!({ "users": { "a": { "name": "A", "seen": 5 }, "b": { "name": "B", "seen": 1 } } });
+        let incoming = json!({ "users": { "a": { "name": "A2", "seen": 3 }, "c": { "name": "C", "seen": 2 } } });
+        let latest = MergeStrategy::custom(|local, incoming| {
+            let (local, incoming) = (local.as_u64().unwrap_or(0), incoming.as_u64().unwrap_or(0));
+            json!(local.max(incoming))
+        });
+        let merged = merge(
+            &local,
+            &incoming,
+            &strategies(vec![("users", MergeStrategy::DeepMerge), ("users.*.seen", latest)]),
+        );
+        assert_eq!(
+            merged,
+            json!({ "users": {
+                "a": { "name": "A2", "seen": 5 },
+                "b": { "name": "B", "seen": 1 },
+                "c": { "name": "C", "seen": 2 },
+            } })
+        );
+    }
+
+    #[test]
+    fn keeps_incoming_values_where_the_local_one_is_missing() {
+        let keep_local = strategies(vec![("draft", MergeStrategy::KeepLocal)]);
+        let merged = merge(&json!({}), &json!({ "draft": "incoming" }), &keep_local);
+        assert_eq!(merged, json!({ "draft": "incoming" }));
+    }
+}
@@ -0,0 +1,137 @@
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::Duration;
+
+use serde::Serialize;
+
+/// Counters and timings of dispatches and state emits since the app started, as returned by
+/// `Zubridge::metrics`. Times are in microseconds.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize)]
+pub struct MetricsSnapshot {
+    /// Batches of actions applied to the state manager under one lock.
+    pub dispatches: u64,
+    pub failed_dispatches: u64,
+    /// Time spent waiting for the state manager's lock.
+    pub lock_wait_micros: u64,
+    pub max_lock_wait_micros: u64,
+    /// Time spent in the state manager's reducers.
+    pub reducer_micros: u64,
+    pub max_reducer_micros: u64,
+    pub emits: u64,
+    pub failed_emits: u64,
+    pub emit_micros: u64,
+    pub max_emit_micros: u64,
+}
+
+impl MetricsSnapshot {
+    /// The counters accumulated since `earlier`. Maximums are kept as they are.
+    pub fn since(&self, earlier: &MetricsSnapshot) -> MetricsSnapshot {
+        MetricsSnapshot {
+            dispatches: self.dispatches.saturating_sub(earlier.dispatches),
+            failed_dispatches: self.failed_dispatches.saturating_sub(earlier.failed_dispatches),
+            lock_wait_micros: self.lock_wait_micros.saturating_sub(earlier.lock_wait_micros),
+            max_lock_wait_micros: self.max_lock_wait_micros,
+            reducer_micros: self.reducer_micros.saturating_sub(earlier.reducer_micros),
+            max_reducer_micros: self.max_reducer_micros,
+            emits: self.emits.saturating_sub(earlier.emits),
+            failed_emits: self.failed_emits.saturating_sub(earlier.failed_emits),
+            emit_micros: self.emit_micros.saturating_sub(earlier.emit_micros),
+            max_emit_micros: self.max_emit_micros,
+        }
+    }
+}
+
+/// A total and maximum duration, updated without locking.
+#[derive(Default)]
+struct Timing {
+    total: AtomicU64,
+    max: AtomicU64,
+}
+
+impl Timing {
+    fn record(&self, elapsed: Duration) {
+        let micros = elapsed.as_micros() as u64;
+        self.total.fetch_add(micros, Ordering::Relaxed);
+        self.max.fetch_max(micros, Ordering::Relaxed);
+    }
+}
+
+/// Collects [`MetricsSnapshot`] counters for every dispatch and emit.
+#[derive(Default)]
+pub(crate) struct Metrics {
+    dispatches: AtomicU64,
+    failed_dispatches: AtomicU64,
+    lock_wait: Timing,
+    reducer: Timing,
+    emits: AtomicU64,
+    failed_emits: AtomicU64,
+    emit: Timing,
+}
+
+impl Metrics {
+    pub(crate) fn lock_waited(&self, elapsed: Duration) {
+        self.lock_wait.record(elapsed);
+    }
+
+    pub(crate) fn dispatched(&self, elapsed: Duration, ok: bool) {
+        self.dispatches.fetch_add(1, Ordering::Relaxed);
+        if !ok {
+            self.failed_dispatches.fetch_add(1, Ordering::Relaxed);
+        }
+        self.reducer.record(elapsed);
+    }
+
+    pub(crate) fn emitted(&self, elapsed: Duration, ok: bool) {
+        self.emits.fetch_add(1, Ordering::Relaxed);
+        if !ok {
+            self.failed_emits.fetch_add(1, Ordering::Relaxed);
+        }
+        self.emit.record(elapsed);
+    }
+
+    pub(crate) fn snapshot(&self) -> MetricsSnapshot {
+        MetricsSnapshot {
+            dispatches: self.dispatches.load(Ordering::Relaxed),
+            failed_dispatches: self.failed_dispatches.load(Ordering::Relaxed),
+            lock_wait_micros: self.lock_wait.total.load(Ordering::Relaxed),
+            max_lock_wait_micros: self.lock_wait.max.load(Ordering::Relaxed),
+            reducer_micros: self.reducer.total.load(Ordering::Relaxed),
+            max_reducer_micros: self.reducer.max.load(Ordering::Relaxed),
+            emits: self.emits.load(Ordering::Relaxed),
+            failed_emits: self.failed_emits.load(Ordering::Relaxed),
+            emit_micros: self.emit.total.load(Ordering::Relaxed),
+            max_emit_micros: self.emit.max.load(Ordering::Relaxed),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn counts_failures_and_tracks_maximums() {
+        let metrics = Metrics::default();
+        metrics.lock_waited(Duration::from_micros(5));
+        metrics.dispatched(Duration::from_micros(30), true);
+        metrics.dispatched(Duration::from_micros(10), false);
+        metrics.emitted(Duration::from_micros(7), false);
+
+        let snapshot = metrics.snapshot();
+        assert_eq!((snapshot.dispatches, snapshot.failed_dispatches), (2, 1));
+        assert_eq!((snapshot.reducer_micros, snapshot.max_reducer_micros), (40, 30));
+        assert_eq!((snapshot.lock_wait_micros, snapshot.max_lock_wait_micros), (5, 5));
+        assert_eq!((snapshot.emits, snapshot.failed_emits, snapshot.emit_micros), (1, 1, 7));
+    }
+
+    #[test]
+    fn subtracts_counters_but_keeps_maximums() {
+        let metrics = Metrics::default();
+        metrics.dispatched(Duration::from_micros(50), true);
+        let earlier = metrics.snapshot();
+        metrics.dispatched(Duration::from_micros(20), false);
+
+        let since = metrics.snapshot().since(&earlier);
+        assert_eq!((since.dispatches, since.failed_dispatches), (1, 1));
+        assert_eq!((since.reducer_micros, since.max_reducer_micros), (20, 50));
+    }
+}
@@ -13,6 +13,7 @@ tauri::ios_plugin_binding!(init_plugin_zubridge);
 pub fn init<R: Runtime, C: DeserializeOwned>(
   _app: &AppHandle<R>,
   api: PluginApi<R, C>,
+  _options: ZubridgeOptions,
 ) -> crate::Result<Zubridge<R>> {
   #[cfg(target_os = "android")]
   let handle = api.register_android_plugin("", "ExamplePlugin")?;
@@ -1,32 +1,503 @@
-use serde::{Deserialize};
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
 use std::fmt::Debug;
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::time::Duration;
+
+#[cfg(desktop)]
+use crate::crash_report::CrashReportConfig;
+use crate::aggregates::Aggregation;
+use crate::authorization::AuthorizationConfig;
+#[cfg(desktop)]
+use crate::blobs::BlobStoreConfig;
+#[cfg(desktop)]
+use crate::appearance::AppearanceConfig;
+use crate::compaction::{CompactionConfig, Retention};
+use crate::confirmations::ConfirmationsConfig;
+use crate::deep_link::DeepLinkConfig;
+use crate::dialogs::DialogsConfig;
+#[cfg(desktop)]
+use crate::emit_retry::EmitRetryConfig;
+use crate::error_policy::ErrorPolicy;
+#[cfg(feature = "automation")]
+use crate::effects::automation::AutomationConfig;
+#[cfg(feature = "secrets")]
+use crate::secrets::SecretsConfig;
+#[cfg(feature = "search")]
+use crate::effects::search::SearchIndex;
+use crate::error_reports::ErrorReportingConfig;
+use crate::experiments::Experiment;
+#[cfg(desktop)]
+use crate::extensions::ExtensionConfig;
+#[cfg(debug_assertions)]
+use crate::fixtures::FixturesConfig;
+use crate::feature_flags::FeatureFlag;
+use crate::forms::Form;
+use crate::ids::{IdGenerator, UuidV7};
+use crate::initial_state::InitialStateProvider;
+#[cfg(debug_assertions)]
+use crate::dev_handoff::DevHandoffConfig;
+use crate::dev_reload::DevReloadConfig;
+use crate::interceptors::DispatchInterceptor;
+use crate::merge::MergeStrategy;
+#[cfg(all(desktop, feature = "mqtt"))]
+use crate::mqtt::MqttConfig;
+use crate::paginator::Paginator;
+use crate::persistence::PersistenceConfig;
+use crate::platform_prefs::PlatformPrefsConfig;
+use crate::session::SessionLockConfig;
+use crate::single_instance::SecondInstanceConfig;
+use crate::slice_router::SliceRoute;
+use crate::startup::StartupConfig;
+use crate::state_check::StateMigration;
+use crate::statechart::Statechart;
+use crate::sync::SyncConfig;
+#[cfg(desktop)]
+use crate::updater::UpdaterConfig;
+#[cfg(desktop)]
+use crate::shared_memory::StateTransport;
+#[cfg(desktop)]
+use crate::tray::TrayActionMap;
+#[cfg(desktop)]
+use crate::window_actions::WindowActionsConfig;
+use crate::telemetry::TelemetryConfig;
+use crate::timers::TimersConfig;
+use crate::trash::TrashConfig;
+use crate::warm_start::WarmStartConfig;
+#[cfg(feature = "reqwest")]
+use crate::webhooks::WebhooksConfig;
+use crate::workflows::Workflow;
+use crate::workspaces::WorkspacesConfig;
 
 pub use serde_json::Value as JsonValue;
 
 /// An action to be dispatched to the state manager.
-#[derive(Deserialize, Debug)]
+#[derive(Deserialize, Serialize, Clone, Debug)]
 pub struct ZubridgeAction {
-    /// A string label for the action
+    /// A string label for the action. Also accepted as `type`, the field's name in the
+    /// frontend packages.
+    #[serde(alias = "type")]
     pub action_type: String,
     /// An optional payload for the action
     pub payload: Option<JsonValue>,
 }
 
+impl ZubridgeAction {
+    /// Convert a typed action into a `ZubridgeAction`. `action` must serialize to a
+    /// `{ type, payload }` object, e.g. an enum with `#[serde(tag = "type", content = "payload")]`,
+    /// so action types are checked at compile time instead of spelled out as strings.
+    pub fn from_typed<A: Serialize>(action: &A) -> crate::Result<Self> {
+        let value = serde_json::to_value(action).map_err(|e| crate::Error::SerializationError(e.to_string()))?;
+        let JsonValue::Object(mut fields) = value else {
+            return Err(crate::Error::SerializationError(format!("typed action is not an object: {}", value)));
+        };
+        let action_type = match fields.remove("type") {
+            Some(JsonValue::String(action_type)) => action_type,
+            _ => return Err(crate::Error::SerializationError("typed action has no string `type`".into())),
+        };
+        let payload = fields.remove("payload");
+        if let Some(field) = fields.keys().next() {
+            return Err(crate::Error::SerializationError(format!(
+                "typed action {} has a `{}` field outside its payload",
+                action_type, field
+            )));
+        }
+        Ok(Self { action_type, payload })
+    }
+}
+
+/// Parse an action passed to [`StateManager::dispatch_action`] into a typed action. On failure
+/// the error names the JSON path that didn't match and the type expected there, e.g.
+/// `Invalid action at payload.value: invalid type: string "5", expected i32`.
+pub fn parse_action<A: DeserializeOwned>(action: &JsonValue) -> crate::Result<A> {
+    serde_path_to_error::deserialize(action).map_err(|e| crate::Error::InvalidAction {
+        path: e.path().to_string(),
+        message: e.into_inner().to_string(),
+    })
+}
+
+/// Parse a state passed to [`StateManager::validate_state`] into the typed state. On failure the
+/// error names the JSON path that didn't match, e.g.
+/// `Invalid state at todos[0].done: invalid type: string "yes", expected a boolean`.
+pub fn parse_state<S: DeserializeOwned>(state: &JsonValue) -> crate::Result<S> {
+    serde_path_to_error::deserialize(state).map_err(|e| crate::Error::InvalidState {
+        path: e.path().to_string(),
+        message: e.into_inner().to_string(),
+    })
+}
+
+/// Dispatches actions from background work such as sync tasks, as if they came from Rust code.
+#[derive(Clone)]
+pub struct Dispatcher {
+    dispatch: Arc<dyn Fn(ZubridgeAction) -> crate::Result<JsonValue> + Send + Sync>,
+}
+
+impl Dispatcher {
+    pub(crate) fn new(dispatch: impl Fn(ZubridgeAction) -> crate::Result<JsonValue> + Send + Sync + 'static) -> Self {
+        Self {
+            dispatch: Arc::new(dispatch),
+        }
+    }
+
+    /// Dispatch an action and return the updated state.
+    pub fn dispatch(&self, action: ZubridgeAction) -> crate::Result<JsonValue> {
+        (self.dispatch)(action)
+    }
+}
+
 /// Options for the Zubridge plugin.
 #[derive(Clone)]
 pub struct ZubridgeOptions {
-    /// The event name to use for state updates. Defaults to "zubridge://state-update".
-    pub event_name: String,
+    /// The event name to use for state updates. Defaults to `<app identifier>/zubridge/state-update`,
+    /// see [`crate::EventNames`].
+    pub event_name: Option<String>,
+    /// Emit events under the `zubridge://` names of earlier versions, shared by every app, instead
+    /// of names scoped to the app identifier. Defaults to false.
+    pub legacy_event_names: bool,
+    /// How long file changes are debounced before [`crate::FsWatch`] dispatches an action. Defaults to 300ms.
+    pub watch_debounce: Duration,
+    /// A JSON or TOML file whose contents are merged into the state under `config_key`.
+    /// The file is watched and re-hydrated when it changes on disk.
+    pub config_file: Option<PathBuf>,
+    /// The state key the config file is merged under. Defaults to "config".
+    pub config_key: String,
+    /// Feature flags exposed to the frontend through the `feature_flags_key` slice.
+    pub feature_flags: Vec<FeatureFlag>,
+    /// The state key feature flags are stored under. Defaults to "feature_flags".
+    pub feature_flags_key: String,
+    /// A/B experiments assigned from the install id and exposed through the `experiments_key` slice.
+    pub experiments: Vec<Experiment>,
+    /// The state key experiment assignments are stored under. Defaults to "experiments".
+    pub experiments_key: String,
+    /// Include the install id in the `meta` of every action passed to the state manager,
+    /// and in the `zubridge::audit` log. Defaults to false.
+    pub include_install_id: bool,
+    /// Opt-in telemetry derived from dispatched actions. Disabled by default.
+    pub telemetry: Option<TelemetryConfig>,
+    /// How many recently dispatched actions are kept for crash reports. Defaults to 50.
+    pub action_log_size: usize,
+    /// Generates ids for action log entries, unnamed snapshots, workflow runs and window
+    /// requests. Defaults to [`UuidV7`].
+    pub id_generator: Arc<dyn IdGenerator>,
+    /// Write crash reports on panic and configure what they contain. Disabled by default.
+    #[cfg(desktop)]
+    pub crash_reports: Option<CrashReportConfig>,
+    /// Forward errors reported by windows with `zubridge.report-error` to an [`ErrorSink`](crate::ErrorSink).
+    /// Disabled by default.
+    pub error_reporting: Option<ErrorReportingConfig>,
+    /// Renames action types before dispatch, keyed by the old type, so renamed actions keep
+    /// working for older frontends (e.g. `"RESET"` → `"COUNTER:RESET"`).
+    pub action_aliases: HashMap<String, String>,
+    /// Interceptors run in order on every dispatched action, after aliases are applied.
+    pub interceptors: Vec<Arc<dyn DispatchInterceptor>>,
+    /// How many dispatch steps, such as interceptors and the reducer, are returned with the error
+    /// when a dispatch from a window fails. 0 turns breadcrumbs off. Defaults to 10.
+    pub dispatch_breadcrumbs: usize,
+    /// How emit failures, persistence failures, panicking interceptors and failures in
+    /// background work are handled. Defaults to [`ErrorPolicy::Propagate`].
+    pub error_policy: ErrorPolicy,
+    /// State paths that `__ZUBRIDGE:SET_PATH`, `__ZUBRIDGE:DELETE_PATH` and `__ZUBRIDGE:PUSH`
+    /// may change without a reducer, where `*` matches any run of characters. Requires a state
+    /// manager that implements [`StateManager::restore_state`]. Disabled when empty, the default.
+    pub mutable_paths: Vec<String>,
+    /// Labels of windows that only observe the state, where `*` matches any run of characters.
+    /// They receive state updates, but dispatches from them fail with `Error::ReadOnlyWindow`.
+    pub read_only_windows: Vec<String>,
+    /// Handle `WINDOW:OPEN`, `WINDOW:CLOSE` and `WINDOW:FOCUS` in the plugin and mirror the
+    /// open windows into a state slice. Disabled by default.
+    #[cfg(desktop)]
+    pub window_actions: Option<WindowActionsConfig>,
+    /// Actions dispatched for tray and menu item clicks, and state predicates enabling items
+    /// of the menus passed to `Zubridge::bind_menu`. Disabled by default.
+    #[cfg(desktop)]
+    pub tray_actions: Option<TrayActionMap>,
+    /// Labels of windows whose file drops are dispatched as `FILE:DROPPED` actions, where `*`
+    /// matches any run of characters. Disabled for every window by default.
+    pub file_drop_windows: Vec<String>,
+    /// Handle `DIALOG:OPEN` and `DIALOG:CLOSE` in the plugin, keeping open dialogs in a state
+    /// slice and dispatching `DIALOG:RESULT` when they close. Disabled by default.
+    pub dialogs: Option<DialogsConfig>,
+    /// Actions that only run once confirmed with `zubridge.confirm`, e.g. destructive actions
+    /// bound to tray and menu items. Disabled by default.
+    pub confirmations: Option<ConfirmationsConfig>,
+    /// Dispatch an action for second launches passed to `Zubridge::second_instance`.
+    /// Disabled by default.
+    pub second_instance: Option<SecondInstanceConfig>,
+    /// Accept `myapp://zubridge/dispatch?type=...&payload=...` links passed to
+    /// `Zubridge::dispatch_deep_link`. Disabled by default.
+    pub deep_links: Option<DeepLinkConfig>,
+    /// Handle `UPDATER:CHECK` and `UPDATER:INSTALL` with an [`UpdaterBackend`](crate::UpdaterBackend)
+    /// and mirror its progress into a state slice. Disabled by default.
+    #[cfg(desktop)]
+    pub updater: Option<UpdaterConfig>,
+    /// Mirror OS appearance and accessibility settings into the `system.appearance` slice.
+    /// Disabled by default.
+    #[cfg(desktop)]
+    pub appearance: Option<AppearanceConfig>,
+    /// Forms driven by `FORM:*` actions and validated in Rust, exposed through the `forms_key` slice.
+    pub forms: Vec<Form>,
+    /// The state key forms are stored under. Defaults to "forms".
+    pub forms_key: String,
+    /// State machines driven by `CHART:SEND` actions, exposed through the `statecharts_key` slice.
+    pub statecharts: Vec<Statechart>,
+    /// The state key statecharts are stored under. Defaults to "statecharts".
+    pub statecharts_key: String,
+    /// Long-running workflows started with `WORKFLOW:START` or `Zubridge::start_workflow`,
+    /// with unfinished runs exposed through the `workflows_key` slice.
+    pub workflows: Vec<Workflow>,
+    /// The state key workflow runs are stored under. Defaults to "workflows".
+    pub workflows_key: String,
+    /// Handle `TIMER:*` actions in the plugin, ticking timers into a state slice. Disabled by default.
+    pub timers: Option<TimersConfig>,
+    /// Paginated lists driven by `PAGE:*` actions, exposed through the `paginators_key` slice.
+    pub paginators: Vec<Paginator>,
+    /// The state key paginators are stored under. Defaults to "paginators".
+    pub paginators_key: String,
+    /// Move entities removed by delete actions to a trash slice, restorable with `TRASH:RESTORE`.
+    /// Disabled by default.
+    pub trash: Option<TrashConfig>,
+    /// Counts and sums over collections in the state manager's state, kept up to date by the
+    /// plugin and published under `stats_key`. Slices from `slice_routes` and other plugins
+    /// aren't aggregated.
+    pub aggregations: Vec<Aggregation>,
+    /// The state key aggregations are published under. Defaults to "stats".
+    pub stats_key: String,
+    /// Slices owned by [`SliceReducer`](crate::SliceReducer)s run in the plugin, e.g. reducers
+    /// contributed by extensions. Actions routed to them don't reach the state manager.
+    pub slice_routes: Vec<SliceRoute>,
+    /// Full-text indexes over collections in the state, queried with `zubridge.search`.
+    #[cfg(feature = "search")]
+    pub search_indexes: Vec<SearchIndex>,
+    /// Load development data into the state from a directory of JSON fixtures. Only available
+    /// in debug builds. Disabled by default.
+    #[cfg(debug_assertions)]
+    pub fixtures: Option<FixturesConfig>,
+    /// Re-send the state to windows whose page was reloaded. Enabled by default in debug builds.
+    pub dev_reload: Option<DevReloadConfig>,
+    /// Restore the state of the previous process when the app is restarted during development.
+    /// Only available in debug builds. Disabled by default.
+    #[cfg(debug_assertions)]
+    pub dev_handoff: Option<DevHandoffConfig>,
+    /// How long effect results are reused for identical actions. Disabled by default.
+    pub effect_cache_ttl: Option<Duration>,
+    /// POST signed payloads to companion services on matching actions or states. Disabled by default.
+    #[cfg(feature = "reqwest")]
+    pub webhooks: Option<WebhooksConfig>,
+    /// Publish state patches to an MQTT broker and accept actions from a command topic.
+    /// Disabled by default.
+    #[cfg(all(desktop, feature = "mqtt"))]
+    pub mqtt: Option<MqttConfig>,
+    /// Extension processes that get a filtered, read-only projection of the state over stdio
+    /// and propose actions through JSON-RPC.
+    #[cfg(desktop)]
+    pub extensions: Vec<ExtensionConfig>,
+    /// User-defined automation rules, saved through the `automation` slice and evaluated on
+    /// every dispatched action. Disabled by default.
+    #[cfg(feature = "automation")]
+    pub automation: Option<AutomationConfig>,
+    /// Keep the values written to the `secrets` slice in the OS keychain, with only a handle per
+    /// secret in the state. Disabled by default.
+    #[cfg(feature = "secrets")]
+    pub secrets: Option<SecretsConfig>,
+    /// Keep the `settings` slice in the platform's preference store, where policy tooling can
+    /// manage it. Disabled by default.
+    pub platform_prefs: Option<PlatformPrefsConfig>,
+    /// How long `Zubridge::request` waits for the target window to respond. Defaults to 10 seconds.
+    pub request_timeout: Duration,
+    /// Sync tasks run in the background, with their status in a state slice. Disabled by default.
+    pub sync: Option<SyncConfig>,
+    /// Paths that may be used with the ephemeral channel, where `*` matches any run of characters.
+    /// Ephemeral values bypass the state manager and are published on `zubridge://ephemeral-update`.
+    pub ephemeral_paths: Vec<String>,
+    /// Top-level state keys left out of the broadcast state. Windows fetch them with
+    /// `zubridge.load-slice` and then receive targeted updates on `zubridge://slice-update`.
+    pub lazy_slices: Vec<String>,
+    /// Lazy slices sent to the windows holding them as JSON Patches against the value last sent,
+    /// instead of in full. Suits large collections that change a few rows at a time.
+    pub diffed_slices: Vec<String>,
+    /// How imported state and restored snapshots are merged into the current state, by
+    /// dot-separated path where `*` matches any key. Paths without a strategy inherit their
+    /// parent's, and the state is replaced by default.
+    pub merge_strategies: BTreeMap<String, MergeStrategy>,
+    /// How long a lazy slice may go without any window holding it before
+    /// [`StateManager::evict_slice`] is called. Disabled by default.
+    pub slice_eviction_delay: Option<Duration>,
+    /// The splash and main windows swapped by `Zubridge::startup_complete`.
+    pub startup: StartupConfig,
+    /// Load the initial state in the background instead of taking the state manager's own.
+    pub initial_state: Option<InitialStateProvider>,
+    /// Run in order on persisted state that fails [`StateManager::validate_state`] before it's
+    /// restored. State still invalid afterwards is dropped. None by default.
+    pub state_migrations: Vec<StateMigration>,
+    /// Keep the last emitted state for the next launch, and serve it while `initial_state` loads.
+    /// Disabled by default.
+    pub warm_start: Option<WarmStartConfig>,
+    /// How the warm start cache, workspace states and other persisted state are written: in the
+    /// background, coalesced per file, and synced to disk as `fsync` says.
+    pub persistence: PersistenceConfig,
+    /// Periodically call [`StateManager::compact`] so journals and history don't grow unbounded.
+    /// Disabled by default.
+    pub compaction: Option<CompactionConfig>,
+    /// How long background subsystems get to stop when the app exits. Defaults to 5 seconds.
+    pub shutdown_timeout: Duration,
+    /// How state updates are delivered to windows. Defaults to [`StateTransport::Event`].
+    #[cfg(desktop)]
+    pub transport: StateTransport,
+    /// How failed state emits are retried before the windows are marked as out of date.
+    #[cfg(desktop)]
+    pub emit_retry: EmitRetryConfig,
+    /// How many updates a window subscribed with `zubridge.subscribe-state` may have
+    /// unacknowledged before newer ones are coalesced. Defaults to 2.
+    pub subscription_max_in_flight: usize,
+    /// Sign state updates, and hand each window the key through `zubridge.handshake`, so the
+    /// frontend can reject forged updates, e.g. with the isolation pattern. Disabled by default.
+    pub payload_integrity: bool,
+    /// Reject actions from windows whose payload is larger than this once encoded, with
+    /// [`crate::Error::ActionTooLarge`]. Large binary payloads can be sent with
+    /// `zubridge.upload-blob` instead. Unlimited by default.
+    pub max_action_bytes: Option<usize>,
+    /// Reject uploads sent with `zubridge.upload-blob` once they're larger than this, with
+    /// [`crate::Error::UploadTooLarge`]. Defaults to 512 MiB.
+    pub max_upload_bytes: Option<u64>,
+    /// How many uploads a window may have in progress at once. Defaults to 4.
+    pub max_pending_uploads: usize,
+    /// Store binary data with [`crate::Zubridge::put_blob`] and serve it on the `zubridge-blob`
+    /// protocol, deleting blobs the state no longer refers to. Disabled by default.
+    #[cfg(desktop)]
+    pub blobs: Option<BlobStoreConfig>,
+    /// Dot-separated state paths encrypted wherever the plugin writes the state to disk:
+    /// snapshots, the warm start cache and crash reports. `*` matches any key.
+    pub sensitive_paths: Vec<String>,
+    /// The key `sensitive_paths` are encrypted with. Defaults to a key generated on first launch
+    /// and stored in the OS keychain with the `secrets` feature, or else in the plugin's data
+    /// directory, readable only by the current user.
+    pub encryption_key: Option<[u8; 32]>,
+    /// Allow locking the session with [`crate::Zubridge::lock_session`], rejecting every action
+    /// outside `allowed_actions` until it's unlocked. Disabled by default.
+    pub session_lock: Option<SessionLockConfig>,
+    /// Roles and claims required to dispatch actions, checked against the `auth` slice.
+    /// Disabled by default.
+    pub authorization: Option<AuthorizationConfig>,
+    /// Keep a separate state per workspace or tenant, switched with `WORKSPACE:SWITCH` or
+    /// [`crate::Zubridge::switch_workspace`]. Disabled by default.
+    pub workspaces: Option<WorkspacesConfig>,
 }
 
 impl Default for ZubridgeOptions {
     fn default() -> Self {
         Self {
-            event_name: "zubridge://state-update".to_string(),
+            event_name: None,
+            legacy_event_names: false,
+            watch_debounce: Duration::from_millis(300),
+            config_file: None,
+            config_key: "config".to_string(),
+            feature_flags: Vec::new(),
+            feature_flags_key: "feature_flags".to_string(),
+            experiments: Vec::new(),
+            experiments_key: "experiments".to_string(),
+            include_install_id: false,
+            telemetry: None,
+            action_log_size: 50,
+            id_generator: Arc::new(UuidV7),
+            #[cfg(desktop)]
+            crash_reports: None,
+            error_reporting: None,
+            action_aliases: HashMap::new(),
+            interceptors: Vec::new(),
+            dispatch_breadcrumbs: 10,
+            error_policy: ErrorPolicy::default(),
+            mutable_paths: Vec::new(),
+            read_only_windows: Vec::new(),
+            #[cfg(desktop)]
+            window_actions: None,
+            #[cfg(desktop)]
+            tray_actions: None,
+            file_drop_windows: Vec::new(),
+            dialogs: None,
+            confirmations: None,
+            second_instance: None,
+            deep_links: None,
+            #[cfg(desktop)]
+            updater: None,
+            #[cfg(desktop)]
+            appearance: None,
+            forms: Vec::new(),
+            forms_key: "forms".to_string(),
+            statecharts: Vec::new(),
+            statecharts_key: "statecharts".to_string(),
+            workflows: Vec::new(),
+            workflows_key: "workflows".to_string(),
+            timers: None,
+            paginators: Vec::new(),
+            paginators_key: "paginators".to_string(),
+            trash: None,
+            aggregations: Vec::new(),
+            stats_key: "stats".to_string(),
+            slice_routes: Vec::new(),
+            #[cfg(feature = "search")]
+            search_indexes: Vec::new(),
+            #[cfg(debug_assertions)]
+            fixtures: None,
+            dev_reload: cfg!(debug_assertions).then(DevReloadConfig::default),
+            #[cfg(debug_assertions)]
+            dev_handoff: None,
+            effect_cache_ttl: None,
+            #[cfg(feature = "reqwest")]
+            webhooks: None,
+            #[cfg(all(desktop, feature = "mqtt"))]
+            mqtt: None,
+            #[cfg(desktop)]
+            extensions: Vec::new(),
+            #[cfg(feature = "automation")]
+            automation: None,
+            #[cfg(feature = "secrets")]
+            secrets: None,
+            platform_prefs: None,
+            request_timeout: Duration::from_secs(10),
+            sync: None,
+            ephemeral_paths: Vec::new(),
+            lazy_slices: Vec::new(),
+            diffed_slices: Vec::new(),
+            merge_strategies: BTreeMap::new(),
+            slice_eviction_delay: None,
+            startup: StartupConfig::default(),
+            initial_state: None,
+            state_migrations: Vec::new(),
+            warm_start: None,
+            persistence: PersistenceConfig::default(),
+            compaction: None,
+            shutdown_timeout: Duration::from_secs(5),
+            #[cfg(desktop)]
+            transport: StateTransport::Event,
+            #[cfg(desktop)]
+            emit_retry: EmitRetryConfig::default(),
+            subscription_max_in_flight: 2,
+            payload_integrity: false,
+            max_action_bytes: None,
+            max_upload_bytes: Some(512 * 1024 * 1024),
+            max_pending_uploads: 4,
+            #[cfg(desktop)]
+            blobs: None,
+            sensitive_paths: Vec::new(),
+            encryption_key: None,
+            session_lock: None,
+            authorization: None,
+            workspaces: None,
         }
     }
 }
 
+/// The JSON Schema of the state tree and of each action, as returned by `zubridge.get-schema`.
+#[derive(Clone, Debug, Default, Serialize)]
+pub struct StateSchema {
+    /// The schema of the full state tree.
+    pub state: JsonValue,
+    /// Action schemas keyed by action type.
+    pub actions: BTreeMap<String, JsonValue>,
+}
+
 /// A trait that manages state for the app.
 pub trait StateManager: Send + Sync + 'static {
     /// Get the initial state of the app.
@@ -34,4 +505,89 @@ pub trait StateManager: Send + Sync + 'static {
 
     /// Apply an action to the state and return the new state.
     fn dispatch_action(&mut self, action: JsonValue) -> JsonValue;
+
+    /// Apply an action like `dispatch_action`, for state managers that can fail to, e.g. when
+    /// a database transaction is rolled back. An error fails the dispatch, and is returned to
+    /// the caller. Calls `dispatch_action` by default.
+    fn try_dispatch_action(&mut self, action: JsonValue) -> crate::Result<JsonValue> {
+        Ok(self.dispatch_action(action))
+    }
+
+    /// Check that an action is well-formed before any action of its dispatch is applied. An
+    /// error fails the dispatch, and is returned to the caller. Accepts everything by default;
+    /// typed state managers can return `parse_action::<MyAction>(action).map(drop)`.
+    fn validate_action(&self, _action: &JsonValue) -> crate::Result<()> {
+        Ok(())
+    }
+
+    /// Check that persisted state, such as the one loaded by the `initial_state` provider, fits
+    /// the current state shape before it's passed to `restore_state`. Accepts everything by
+    /// default; typed state managers can return `parse_state::<MyState>(state).map(drop)`.
+    fn validate_state(&self, _state: &JsonValue) -> crate::Result<()> {
+        Ok(())
+    }
+
+    /// Apply an action to a copy of the state and return the state it would produce, leaving the
+    /// real state untouched. Used by `Zubridge::simulate`. Returns `None` by default, meaning
+    /// simulation isn't supported; state managers that are `Clone` can return
+    /// `Some(self.clone().dispatch_action(action))`.
+    fn simulate_action(&self, _action: JsonValue) -> Option<JsonValue> {
+        None
+    }
+
+    /// Replace the state with one previously returned by `get_initial_state`, undoing the
+    /// actions dispatched since. Used to roll back [`crate::BatchMode::Atomic`] batches.
+    /// Returns false by default, meaning rollback isn't supported.
+    fn restore_state(&mut self, _state: JsonValue) -> bool {
+        false
+    }
+
+    /// Apply a chunk of records from a bulk import started with `Zubridge::import_stream`.
+    /// The default implementation dispatches an `__ZUBRIDGE:IMPORT_CHUNK` action with
+    /// `{ import, records }` as payload.
+    fn import_chunk(&mut self, import: &str, records: Vec<JsonValue>) {
+        self.dispatch_action(serde_json::json!({
+            "type": "__ZUBRIDGE:IMPORT_CHUNK",
+            "payload": { "import": import, "records": records },
+        }));
+    }
+
+    /// Replace a slice with development data from a fixture, loaded at startup or with
+    /// `zubridge.load-fixture` in debug builds. The default implementation dispatches an
+    /// `__ZUBRIDGE:LOAD_FIXTURE` action with `{ slice, value }` as payload.
+    fn load_fixture(&mut self, slice: &str, value: JsonValue) {
+        self.dispatch_action(serde_json::json!({
+            "type": "__ZUBRIDGE:LOAD_FIXTURE",
+            "payload": { "slice": slice, "value": value },
+        }));
+    }
+
+    /// Drop a lazy slice that no window has held for `slice_eviction_delay`, so memory-heavy
+    /// data can be freed. The state manager is expected to reload it when it's requested again.
+    fn evict_slice(&mut self, _slice: &str) {}
+
+    /// Called when a window loads a lazy slice, before it's read, so state managers can
+    /// materialize slices on demand, such as the results of a query.
+    fn load_slice(&mut self, _slice: &str) {}
+
+    /// Slices the state manager materializes on demand with `load_slice`, e.g. named queries.
+    /// They're added to the `lazy_slices` and `diffed_slices` options. None by default.
+    fn query_slices(&self) -> Vec<String> {
+        Vec::new()
+    }
+
+    /// Merge old journal, WAL or history entries into a snapshot, keeping what `retention` allows.
+    /// Called on the `compaction` schedule and by `Zubridge::compact`.
+    fn compact(&mut self, _retention: &Retention) {}
+
+    /// Called once the plugin is set up, with a callback that reads the state again and emits
+    /// it. For state managers whose state also changes outside dispatches, e.g. a database
+    /// written by other processes.
+    fn watch_changes(&mut self, _changed: Arc<dyn Fn() + Send + Sync>) {}
+
+    /// Describe the state and actions as JSON Schema. With the `schema` feature enabled,
+    /// typed state managers can implement this with [`StateSchema::from_types`].
+    fn schema(&self) -> Option<StateSchema> {
+        None
+    }
 }
@@ -0,0 +1,231 @@
+//! Bridge the store to an MQTT broker, so a fleet backend can supervise kiosk and IoT
+//! deployments: state changes are published as JSON Patches and actions are accepted from a
+//! command topic.
+
+use std::sync::Mutex;
+
+use rumqttc::{Client, Connection};
+pub use rumqttc::{MqttOptions, QoS};
+use serde::Serialize;
+
+use crate::models::*;
+use crate::snapshots::{self, PatchOperation};
+use crate::utils::glob_matches;
+
+/// Where the bridge publishes and which actions it accepts.
+#[derive(Clone)]
+pub struct MqttConfig {
+    /// The broker to connect to, and the client id, keep-alive and credentials to connect with.
+    pub options: MqttOptions,
+    /// Receives `{ revision, patch }` for every state update, where `patch` is a JSON Patch from
+    /// the previously published state. Defaults to `<prefix>/state`.
+    pub state_topic: String,
+    /// Receives the retained `{ revision, state }` on every connect and every `snapshot_every`
+    /// updates, so new subscribers can start from it. Defaults to `<prefix>/state/snapshot`.
+    pub snapshot_topic: String,
+    /// Accepts actions as `{ "action_type": ..., "payload": ... }`. Defaults to `<prefix>/commands`.
+    pub command_topic: String,
+    /// Action types accepted from the command topic, where `*` matches any run of characters.
+    /// Nothing is allowed when empty.
+    pub allowed_actions: Vec<String>,
+    /// Defaults to [`QoS::AtLeastOnce`].
+    pub qos: QoS,
+    /// How many patches are published between snapshots. Defaults to 100.
+    pub snapshot_every: u64,
+}
+
+impl MqttConfig {
+    /// Publish and subscribe under `prefix`, e.g. `fleet/<device id>`.
+    pub fn new(options: MqttOptions, prefix: &str) -> Self {
+        Self {
+            options,
+            state_topic: format!("{}/state", prefix),
+            snapshot_topic: format!("{}/state/snapshot", prefix),
+            command_topic: format!("{}/commands", prefix),
+            allowed_actions: Vec::new(),
+            qos: QoS::AtLeastOnce,
+            snapshot_every: 100,
+        }
+    }
+
+    pub fn allow_actions<I, S>(mut self, action_types: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        self.allowed_actions.extend(action_types.into_iter().map(Into::into));
+        self
+    }
+}
+
+#[derive(Serialize)]
+struct StatePatch<'a> {
+    revision: u64,
+    patch: &'a [PatchOperation],
+}
+
+#[derive(Serialize)]
+struct StateSnapshot<'a> {
+    revision: u64,
+    state: &'a JsonValue,
+}
+
+/// The last published state, to diff the next one against.
+struct Published {
+    state: JsonValue,
+    patches_since_snapshot: u64,
+}
+
+/// Publishes state updates to the broker. The connection is driven by the `mqtt` subsystem.
+pub(crate) struct MqttBridge {
+    config: MqttConfig,
+    client: Client,
+    published: Mutex<Option<Published>>,
+}
+
+impl MqttBridge {
+    pub(crate) fn new(config: MqttConfig) -> (Self, Connection) {
+        let (client, connection) = Client::new(config.options.clone(), 64);
+        let bridge = Self {
+            config,
+            client,
+            published: Mutex::new(None),
+        };
+        (bridge, connection)
+    }
+
+    /// Subscribe to the command topic and publish a snapshot, after every (re)connect.
+    pub(crate) fn connected(&self, state: &JsonValue, revision: u64) -> crate::Result<()> {
+        self.client
+            .try_subscribe(&self.config.command_topic, self.config.qos)
+            .map_err(|e| crate::Error::MqttError(e.to_string()))?;
+        self.publish_snapshot(state, revision)
+    }
+
+    /// Publish the patch from the last published state to `state`. Updates are dropped rather
+    /// than blocking the dispatch while the client's queue is full, and a snapshot follows.
+    pub(crate) fn state_published(&self, state: &JsonValue, revision: u64) -> crate::Result<()> {
+        let mut published = self.published.lock().map_err(|e| crate::Error::StateError(e.to_string()))?;
+        let Some(last) = published.as_mut() else {
+            drop(published);
+            return self.publish_snapshot(state, revision);
+        };
+        if last.patches_since_snapshot + 1 >= self.config.snapshot_every.max(1) {
+            drop(published);
+            return self.publish_snapshot(state, revision);
+        }
+
+        let diff = snapshots::diff(&last.state, state);
+        if diff.patch.is_empty() {
+            return Ok(());
+        }
+        let message = serde_json::to_vec(&StatePatch {
+            revision,
+            patch: &diff.patch,
+        })
+        .map_err(|e| crate::Error::SerializationError(e.to_string()))?;
+        match self.client.try_publish(&self.config.state_topic, self.config.qos, false, message) {
+            Ok(()) => {
+                last.state = state.clone();
+                last.patches_since_snapshot += 1;
+                Ok(())
+            }
+            Err(e) => {
+                // Subscribers can't apply later patches without this one
+                *published = None;
+                Err(crate::Error::MqttError(e.to_string()))
+            }
+        }
+    }
+
+    /// Parse an action received on the command topic, if it's allowed.
+    pub(crate) fn command(&self, topic: &str, payload: &[u8]) -> Option<crate::Result<ZubridgeAction>> {
+        if topic != self.config.command_topic {
+            return None;
+        }
+        let action = serde_json::from_slice::<ZubridgeAction>(payload)
+            .map_err(|e| crate::Error::ActionRejected(format!("invalid MQTT command: {}", e)))
+            .and_then(|action| {
+                if self.config.allowed_actions.iter().any(|pattern| glob_matches(pattern, &action.action_type)) {
+                    Ok(action)
+                } else {
+                    Err(crate::Error::ActionRejected(format!("action {} is not allowed from MQTT", action.action_type)))
+                }
+            });
+        Some(action)
+    }
+
+    pub(crate) fn disconnect(&self) {
+        if let Err(e) = self.client.try_disconnect() {
+            log::debug!("zubridge: failed to disconnect from the MQTT broker: {}", e);
+        }
+    }
+
+    fn publish_snapshot(&self, state: &JsonValue, revision: u64) -> crate::Result<()> {
+        let mut published = self.published.lock().map_err(|e| crate::Error::StateError(e.to_string()))?;
+        let message = serde_json::to_vec(&StateSnapshot { revision, state })
+            .map_err(|e| crate::Error::SerializationError(e.to_string()))?;
+        self.client
+            .try_publish(&self.config.snapshot_topic, self.config.qos, true, message)
+            .map_err(|e| crate::Error::MqttError(e.to_string()))?;
+        *published = Some(Published {
+            state: state.clone(),
+            patches_since_snapshot: 0,
+        });
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    // Nothing drives the connection, so requests stay queued in the client
+    fn bridge(snapshot_every: u64) -> (MqttBridge, Connection) {
+        let options = MqttOptions::new("test", "localhost", 1883);
+        let mut config = MqttConfig::new(options, "fleet/1").allow_actions(["TODO:*"]);
+        config.snapshot_every = snapshot_every;
+        MqttBridge::new(config)
+    }
+
+    fn patches_since_snapshot(bridge: &MqttBridge) -> Option<u64> {
+        bridge.published.lock().unwrap().as_ref().map(|published| published.patches_since_snapshot)
+    }
+
+    #[test]
+    fn accepts_allowed_commands_only() {
+        let (bridge, _connection) = bridge(100);
+        assert!(bridge.command("fleet/1/state", b"{}").is_none());
+        let action = bridge.command("fleet/1/commands", br#"{ "action_type": "TODO:ADD", "payload": 1 }"#);
+        assert_eq!(action.unwrap().unwrap().action_type, "TODO:ADD");
+        let rejected = bridge.command("fleet/1/commands", br#"{ "action_type": "USER:LOGOUT" }"#);
+        assert!(matches!(rejected, Some(Err(crate::Error::ActionRejected(_)))));
+        assert!(matches!(bridge.command("fleet/1/commands", b"{"), Some(Err(crate::Error::ActionRejected(_)))));
+    }
+
+    #[test]
+    fn publishes_patches_between_snapshots() {
+        let (bridge, _connection) = bridge(3);
+        bridge.connected(&json!({ "count": 0 }), 0).unwrap();
+        assert_eq!(patches_since_snapshot(&bridge), Some(0));
+        bridge.state_published(&json!({ "count": 1 }), 1).unwrap();
+        // Unchanged states aren't published
+        bridge.state_published(&json!({ "count": 1 }), 2).unwrap();
+        bridge.state_published(&json!({ "count": 2 }), 3).unwrap();
+        assert_eq!(patches_since_snapshot(&bridge), Some(2));
+        bridge.state_published(&json!({ "count": 3 }), 4).unwrap();
+        assert_eq!(patches_since_snapshot(&bridge), Some(0));
+    }
+
+    #[test]
+    fn follows_a_dropped_patch_with_a_snapshot() {
+        let (bridge, _connection) = bridge(1000);
+        bridge.state_published(&json!({ "count": 0 }), 0).unwrap();
+        let mut revision = 1;
+        while bridge.state_published(&json!({ "count": revision }), revision).is_ok() {
+            revision += 1;
+        }
+        assert_eq!(patches_since_snapshot(&bridge), None);
+    }
+}
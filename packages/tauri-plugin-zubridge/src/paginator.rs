@@ -0,0 +1,331 @@
+use std::collections::BTreeMap;
+use std::sync::{Arc, Mutex};
+
+use serde::{Deserialize, Serialize};
+
+use crate::effects::cache::EffectCache;
+use crate::models::*;
+
+pub(crate) const GOTO_ACTION: &str = "PAGE:GOTO";
+pub(crate) const NEXT_ACTION: &str = "PAGE:NEXT";
+pub(crate) const PREV_ACTION: &str = "PAGE:PREV";
+pub(crate) const SET_SIZE_ACTION: &str = "PAGE:SET_SIZE";
+pub(crate) const REFRESH_ACTION: &str = "PAGE:REFRESH";
+
+/// The page a [`PageFetchFn`] should load. Pages are numbered from 1.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+pub struct PageRequest {
+    pub paginator: String,
+    pub page: u64,
+    pub page_size: u64,
+}
+
+/// One page of items, and the total number of items if the backend knows it.
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+pub struct Page {
+    pub items: Vec<JsonValue>,
+    pub total: Option<u64>,
+}
+
+/// Loads a page from the backend. Called on a background thread.
+pub type PageFetchFn = Arc<dyn Fn(&PageRequest) -> Result<Page, String> + Send + Sync>;
+
+/// A paginated list kept in the `paginators_key` slice and driven by `PAGE:*` actions.
+#[derive(Clone)]
+pub struct Paginator {
+    pub name: String,
+    /// Items per page. Defaults to 20.
+    pub page_size: u64,
+    /// How many pages on either side of the current page stay in memory. Defaults to 2.
+    pub keep_pages: u64,
+    fetch: PageFetchFn,
+}
+
+impl Paginator {
+    pub fn new<F>(name: impl Into<String>, fetch: F) -> Self
+    where
+        F: Fn(&PageRequest) -> Result<Page, String> + Send + Sync + 'static,
+    {
+        Self {
+            name: name.into(),
+            page_size: 20,
+            keep_pages: 2,
+            fetch: Arc::new(fetch),
+        }
+    }
+
+    pub fn page_size(mut self, page_size: u64) -> Self {
+        self.page_size = page_size.max(1);
+        self
+    }
+
+    pub fn keep_pages(mut self, keep_pages: u64) -> Self {
+        self.keep_pages = keep_pages;
+        self
+    }
+
+    /// Load a page, turning a panic into a failure.
+    pub(crate) fn fetch(&self, request: &PageRequest) -> Result<Page, String> {
+        std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| (self.fetch)(request)))
+            .unwrap_or_else(|_| Err(format!("fetching page {} of {} panicked", request.page, self.name)))
+    }
+}
+
+#[derive(Clone, Debug, Serialize)]
+struct PaginatorState {
+    page: u64,
+    page_size: u64,
+    total: Option<u64>,
+    loading: bool,
+    error: Option<String>,
+    /// The items of the current page, if loaded.
+    items: Option<Vec<JsonValue>>,
+    /// Loaded pages near the current page, keyed by page number.
+    pages: BTreeMap<u64, Vec<JsonValue>>,
+}
+
+/// The state of every paginator, keyed by name.
+pub(crate) struct Paginators {
+    paginators: Vec<Paginator>,
+    states: Mutex<BTreeMap<String, PaginatorState>>,
+}
+
+impl Paginators {
+    pub(crate) fn new(paginators: Vec<Paginator>) -> Self {
+        let states = paginators
+            .iter()
+            .map(|paginator| {
+                let state = PaginatorState {
+                    page: 1,
+                    page_size: paginator.page_size,
+                    total: None,
+                    loading: false,
+                    error: None,
+                    items: None,
+                    pages: BTreeMap::new(),
+                };
+                (paginator.name.clone(), state)
+            })
+            .collect();
+        Self {
+            paginators,
+            states: Mutex::new(states),
+        }
+    }
+
+    pub(crate) fn is_empty(&self) -> bool {
+        self.paginators.is_empty()
+    }
+
+    pub(crate) fn get(&self, name: &str) -> Option<&Paginator> {
+        self.paginators.iter().find(|paginator| paginator.name == name)
+    }
+
+    /// Move to another page or page size. Returns the page to fetch, if it isn't loaded yet.
+    pub(crate) fn apply(&self, action: &ZubridgeAction) -> crate::Result<Option<PageRequest>> {
+        let field = |name: &str| action.payload.as_ref().and_then(|payload| payload.get(name));
+        let name = field("paginator")
+            .and_then(JsonValue::as_str)
+            .ok_or_else(|| crate::Error::StateError(format!("{} requires a paginator", action.action_type)))?;
+        let number = |name: &str| {
+            field(name)
+                .and_then(JsonValue::as_u64)
+                .ok_or_else(|| crate::Error::StateError(format!("{} requires a numeric {}", action.action_type, name)))
+        };
+
+        let mut states = self.states.lock().map_err(|e| crate::Error::StateError(e.to_string()))?;
+        let state = states
+            .get_mut(name)
+            .ok_or_else(|| crate::Error::StateError(format!("no paginator named {}", name)))?;
+        let mut refresh = false;
+        let page = match action.action_type.as_str() {
+            GOTO_ACTION => number("page")?,
+            NEXT_ACTION => state.page + 1,
+            PREV_ACTION => state.page.saturating_sub(1),
+            SET_SIZE_ACTION => {
+                let page_size = number("page_size")?.max(1);
+                // Keep the first item of the current page in view
+                let first_item = (state.page - 1) * state.page_size;
+                state.page_size = page_size;
+                state.pages.clear();
+                first_item / page_size + 1
+            }
+            _ => {
+                refresh = true;
+                state.pages.clear();
+                state.page
+            }
+        };
+
+        state.page = match page_count(state) {
+            Some(count) => page.clamp(1, count.max(1)),
+            None => page.max(1),
+        };
+        let (page, keep_pages) = (state.page, self.get(name).map_or(0, |paginator| paginator.keep_pages));
+        state.pages.retain(|number, _| number.abs_diff(page) <= keep_pages);
+        state.items = state.pages.get(&page).cloned();
+        if state.items.is_some() && !refresh {
+            return Ok(None);
+        }
+
+        state.loading = true;
+        state.error = None;
+        Ok(Some(PageRequest {
+            paginator: name.to_string(),
+            page: state.page,
+            page_size: state.page_size,
+        }))
+    }
+
+    /// Store a fetched page and prune pages far from the current one. Returns whether the slice
+    /// changed, which it doesn't for pages fetched before the page size changed.
+    pub(crate) fn loaded(&self, request: &PageRequest, result: Result<Page, String>) -> crate::Result<bool> {
+        let keep_pages = self.get(&request.paginator).map_or(0, |paginator| paginator.keep_pages);
+        let mut states = self.states.lock().map_err(|e| crate::Error::StateError(e.to_string()))?;
+        let Some(state) = states.get_mut(&request.paginator) else { return Ok(false) };
+        if state.page_size != request.page_size {
+            return Ok(false);
+        }
+
+        let current = request.page == state.page;
+        match result {
+            Ok(page) => {
+                if page.total.is_some() {
+                    state.total = page.total;
+                }
+                state.pages.insert(request.page, page.items);
+            }
+            Err(error) if current => state.error = Some(error),
+            Err(_) => {}
+        }
+        if current {
+            state.loading = false;
+        }
+
+        let page = state.page;
+        state.pages.retain(|number, _| number.abs_diff(page) <= keep_pages);
+        state.items = state.pages.get(&page).cloned();
+        Ok(true)
+    }
+
+    pub(crate) fn slice(&self) -> crate::Result<JsonValue> {
+        let states = self.states.lock().map_err(|e| crate::Error::StateError(e.to_string()))?;
+        serde_json::to_value(&*states).map_err(|e| crate::Error::SerializationError(e.to_string()))
+    }
+}
+
+fn page_count(state: &PaginatorState) -> Option<u64> {
+    state.total.map(|total| (total + state.page_size - 1) / state.page_size)
+}
+
+pub(crate) fn is_page_action(action_type: &str) -> bool {
+    matches!(action_type, GOTO_ACTION | NEXT_ACTION | PREV_ACTION | SET_SIZE_ACTION | REFRESH_ACTION)
+}
+
+/// Fetch a page, reusing a cached copy from the effect cache if there is one.
+/// `refresh` drops the cached copy first.
+pub(crate) fn fetch_page(
+    paginator: &Paginator,
+    request: &PageRequest,
+    cache: Option<&EffectCache>,
+    refresh: bool,
+) -> Result<Page, String> {
+    let Some(cache) = cache else { return paginator.fetch(request) };
+    let key = ZubridgeAction {
+        action_type: "PAGE:FETCH".to_string(),
+        payload: serde_json::to_value(request).ok(),
+    };
+    if refresh {
+        cache.invalidate(&key);
+    }
+
+    let page = cache.get_or_run(&key, || {
+        let page = paginator.fetch(request)?;
+        serde_json::to_value(page).map_err(|e| e.to_string())
+    })?;
+    serde_json::from_value(page).map_err(|e| e.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn paginators() -> Paginators {
+        let items = Paginator::new("items", |_| Err("unused".to_string())).page_size(10).keep_pages(1);
+        Paginators::new(vec![items])
+    }
+
+    fn action(action_type: &str, mut payload: JsonValue) -> ZubridgeAction {
+        payload["paginator"] = json!("items");
+        ZubridgeAction {
+            action_type: action_type.to_string(),
+            payload: Some(payload),
+        }
+    }
+
+    fn page(items: u64, total: u64) -> Result<Page, String> {
+        Ok(Page {
+            items: (0..items).map(JsonValue::from).collect(),
+            total: Some(total),
+        })
+    }
+
+    #[test]
+    fn fetches_pages_that_are_not_loaded() {
+        let paginators = paginators();
+        let request = paginators.apply(&action(GOTO_ACTION, json!({ "page": 1 }))).unwrap().unwrap();
+        assert_eq!(request, PageRequest { paginator: "items".into(), page: 1, page_size: 10 });
+        assert!(paginators.loaded(&request, page(10, 25)).unwrap());
+        assert_eq!(paginators.slice().unwrap()["items"]["loading"], false);
+
+        // Loaded already
+        assert!(paginators.apply(&action(GOTO_ACTION, json!({ "page": 1 }))).unwrap().is_none());
+        // Refreshed anyway
+        assert!(paginators.apply(&action(REFRESH_ACTION, json!({}))).unwrap().is_some());
+    }
+
+    #[test]
+    fn clamps_to_the_known_pages() {
+        let paginators = paginators();
+        let request = paginators.apply(&action(GOTO_ACTION, json!({ "page": 1 }))).unwrap().unwrap();
+        paginators.loaded(&request, page(10, 25)).unwrap();
+
+        let request = paginators.apply(&action(GOTO_ACTION, json!({ "page": 9 }))).unwrap().unwrap();
+        assert_eq!(request.page, 3);
+        let request = paginators.apply(&action(PREV_ACTION, json!({}))).unwrap().unwrap();
+        assert_eq!(request.page, 2);
+    }
+
+    #[test]
+    fn keeps_the_first_item_in_view_when_resizing() {
+        let paginators = paginators();
+        let request = paginators.apply(&action(GOTO_ACTION, json!({ "page": 3 }))).unwrap().unwrap();
+        let resized = paginators.apply(&action(SET_SIZE_ACTION, json!({ "page_size": 5 }))).unwrap().unwrap();
+        assert_eq!((resized.page, resized.page_size), (5, 5));
+        // Fetched at the old size, so dropped
+        assert!(!paginators.loaded(&request, page(10, 25)).unwrap());
+    }
+
+    #[test]
+    fn drops_pages_far_from_the_current_one() {
+        let paginators = paginators();
+        for number in 1..=3 {
+            let request = paginators.apply(&action(GOTO_ACTION, json!({ "page": number }))).unwrap().unwrap();
+            paginators.loaded(&request, page(10, 100)).unwrap();
+        }
+        let slice = paginators.slice().unwrap();
+        let pages: Vec<&String> = slice["items"]["pages"].as_object().unwrap().keys().collect();
+        assert_eq!(pages, ["2", "3"]);
+    }
+
+    #[test]
+    fn shows_errors_for_the_current_page() {
+        let paginators = paginators();
+        let request = paginators.apply(&action(GOTO_ACTION, json!({ "page": 1 }))).unwrap().unwrap();
+        paginators.loaded(&request, Err("offline".to_string())).unwrap();
+        let slice = paginators.slice().unwrap();
+        assert_eq!(slice["items"]["error"], "offline");
+        assert_eq!(slice["items"]["loading"], false);
+    }
+}
@@ -0,0 +1,195 @@
+use crate::models::*;
+use crate::utils::glob_matches;
+
+pub(crate) const SET_PATH_ACTION: &str = "__ZUBRIDGE:SET_PATH";
+pub(crate) const DELETE_PATH_ACTION: &str = "__ZUBRIDGE:DELETE_PATH";
+pub(crate) const PUSH_ACTION: &str = "__ZUBRIDGE:PUSH";
+
+pub(crate) fn is_path_action(action_type: &str) -> bool {
+    matches!(action_type, SET_PATH_ACTION | DELETE_PATH_ACTION | PUSH_ACTION)
+}
+
+/// The path a path action writes to.
+pub(crate) fn target(action: &ZubridgeAction) -> crate::Result<&str> {
+    action
+        .payload
+        .as_ref()
+        .and_then(|payload| payload.get("path"))
+        .and_then(JsonValue::as_str)
+        .ok_or_else(|| crate::Error::ActionRejected(format!("{} requires a path", action.action_type)))
+}
+
+/// Apply a path action to `state`, if its path matches one of the `mutable_paths` patterns.
+pub(crate) fn apply(state: &mut JsonValue, action: &ZubridgeAction, mutable_paths: &[String]) -> crate::Result<()> {
+    let payload = action.payload.as_ref();
+    let path = target(action)?;
+    if !mutable_paths.iter().any(|pattern| glob_matches(pattern, path)) {
+        return Err(crate::Error::ActionRejected(format!("{} is not a mutable path", path)));
+    }
+    let segments: Vec<&str> = path.split('.').filter(|segment| !segment.is_empty()).collect();
+    let Some((last, parents)) = segments.split_last() else {
+        return Err(crate::Error::ActionRejected("the root of the state is not a mutable path".into()));
+    };
+    let value = || payload.and_then(|payload| payload.get("value")).cloned().unwrap_or(JsonValue::Null);
+
+    match action.action_type.as_str() {
+        SET_PATH_ACTION => {
+            let parent = parent_mut(state, parents, true).ok_or_else(|| missing(path))?;
+            set_child(parent, last, value(), path)
+        }
+        DELETE_PATH_ACTION => {
+            let Some(parent) = parent_mut(state, parents, false) else { return Ok(()) };
+            match parent {
+                JsonValue::Object(map) => {
+                    map.remove(*last);
+                }
+                JsonValue::Array(items) => {
+                    if let Some(index) = last.parse::<usize>().ok().filter(|index| *index < items.len()) {
+                        items.remove(index);
+                    }
+                }
+                _ => {}
+            }
+            Ok(())
+        }
+        _ => {
+            let parent = parent_mut(state, parents, true).ok_or_else(|| missing(path))?;
+            let target = child_mut(parent, last, true).ok_or_else(|| missing(path))?;
+            if target.is_null() {
+                *target = JsonValue::Array(Vec::new());
+            }
+            let JsonValue::Array(items) = target else {
+                return Err(crate::Error::ActionRejected(format!("{} is not an array", path)));
+            };
+            items.push(value());
+            Ok(())
+        }
+    }
+}
+
+/// Walk to the parent of the value at a path, creating missing objects along the way if
+/// `create` is set. Returns None if the path doesn't exist.
+fn parent_mut<'a>(state: &'a mut JsonValue, parents: &[&str], create: bool) -> Option<&'a mut JsonValue> {
+    parents.iter().try_fold(state, |current, segment| child_mut(current, segment, create))
+}
+
+fn child_mut<'a>(value: &'a mut JsonValue, segment: &str, create: bool) -> Option<&'a mut JsonValue> {
+    if create && value.is_null() {
+        *value = JsonValue::Object(Default::default());
+    }
+    match value {
+        JsonValue::Object(map) => {
+            if create {
+                Some(map.entry(segment.to_string()).or_insert(JsonValue::Null))
+            } else {
+                map.get_mut(segment)
+            }
+        }
+        JsonValue::Array(items) => segment.parse::<usize>().ok().and_then(|index| items.get_mut(index)),
+        _ => None,
+    }
+}
+
+fn set_child(parent: &mut JsonValue, segment: &str, value: JsonValue, path: &str) -> crate::Result<()> {
+    // A parent created along the way
+    if parent.is_null() {
+        *parent = JsonValue::Object(Default::default());
+    }
+    match parent {
+        JsonValue::Object(map) => {
+            map.insert(segment.to_string(), value);
+            Ok(())
+        }
+        JsonValue::Array(items) => {
+            let slot = segment.parse::<usize>().ok().and_then(|index| items.get_mut(index)).ok_or_else(|| missing(path))?;
+            *slot = value;
+            Ok(())
+        }
+        _ => Err(missing(path)),
+    }
+}
+
+fn missing(path: &str) -> crate::Error {
+    crate::Error::ActionRejected(format!("{} can't be reached in the state", path))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn action(action_type: &str, payload: JsonValue) -> ZubridgeAction {
+        ZubridgeAction {
+            action_type: action_type.to_string(),
+            payload: Some(payload),
+        }
+    }
+
+    fn mutable() -> Vec<String> {
+        vec!["settings.*".to_string(), "todos".to_string()]
+    }
+
+    #[test]
+    fn set_replaces_a_value() {
+        let mut state = json!({ "settings": { "theme": "light" } });
+        apply(&mut state, &action(SET_PATH_ACTION, json!({ "path": "settings.theme", "value": "dark" })), &mutable()).unwrap();
+        assert_eq!(state, json!({ "settings": { "theme": "dark" } }));
+    }
+
+    #[test]
+    fn set_creates_missing_parents() {
+        let mut state = json!({});
+        apply(&mut state, &action(SET_PATH_ACTION, json!({ "path": "settings.editor.tabs", "value": 4 })), &mutable()).unwrap();
+        assert_eq!(state, json!({ "settings": { "editor": { "tabs": 4 } } }));
+    }
+
+    #[test]
+    fn delete_removes_a_value() {
+        let mut state = json!({ "settings": { "theme": "dark", "tabs": 4 } });
+        apply(&mut state, &action(DELETE_PATH_ACTION, json!({ "path": "settings.theme" })), &mutable()).unwrap();
+        assert_eq!(state, json!({ "settings": { "tabs": 4 } }));
+    }
+
+    #[test]
+    fn delete_of_a_missing_path_is_a_no_op() {
+        let mut state = json!({ "settings": {} });
+        apply(&mut state, &action(DELETE_PATH_ACTION, json!({ "path": "settings.editor.tabs" })), &mutable()).unwrap();
+        assert_eq!(state, json!({ "settings": {} }));
+    }
+
+    #[test]
+    fn push_appends_to_an_array() {
+        let mut state = json!({ "todos": ["a"] });
+        apply(&mut state, &action(PUSH_ACTION, json!({ "path": "todos", "value": "b" })), &mutable()).unwrap();
+        assert_eq!(state, json!({ "todos": ["a", "b"] }));
+    }
+
+    #[test]
+    fn push_creates_a_missing_array() {
+        let mut state = json!({});
+        apply(&mut state, &action(PUSH_ACTION, json!({ "path": "settings.recent", "value": 1 })), &mutable()).unwrap();
+        assert_eq!(state, json!({ "settings": { "recent": [1] } }));
+    }
+
+    #[test]
+    fn push_rejects_a_non_array() {
+        let mut state = json!({ "settings": { "theme": "dark" } });
+        let result = apply(&mut state, &action(PUSH_ACTION, json!({ "path": "settings.theme", "value": 1 })), &mutable());
+        assert!(matches!(result, Err(crate::Error::ActionRejected(_))));
+    }
+
+    #[test]
+    fn rejects_paths_outside_mutable_paths() {
+        let mut state = json!({ "user": { "role": "viewer" } });
+        let result = apply(&mut state, &action(SET_PATH_ACTION, json!({ "path": "user.role", "value": "admin" })), &mutable());
+        assert!(matches!(result, Err(crate::Error::ActionRejected(_))));
+        assert_eq!(state, json!({ "user": { "role": "viewer" } }));
+    }
+
+    #[test]
+    fn rejects_a_missing_path() {
+        let mut state = json!({});
+        let result = apply(&mut state, &action(SET_PATH_ACTION, json!({ "value": 1 })), &mutable());
+        assert!(matches!(result, Err(crate::Error::ActionRejected(_))));
+    }
+}
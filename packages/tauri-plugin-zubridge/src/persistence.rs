@@ -0,0 +1,231 @@
+use std::collections::{BTreeMap, BTreeSet};
+use std::fs::{File, OpenOptions};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use crate::error_policy::ErrorPolicy;
+use crate::subsystems::Subsystems;
+
+/// When persisted files are flushed to the disk with fsync, trading durability on power loss
+/// for write latency. Without it, a file can be lost or empty after a power loss, but never
+/// half-written, since it replaces the previous one with a rename.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum FsyncPolicy {
+    /// After every write.
+    Always,
+    /// At most once per interval, for every file written since.
+    Interval(Duration),
+    /// Leave it to the OS.
+    #[default]
+    Never,
+}
+
+/// How the plugin writes the state it persists, such as the warm start cache and workspace
+/// states. Writes happen on a background thread, so saving a large state doesn't hold up
+/// dispatches.
+#[derive(Clone, Debug)]
+pub struct PersistenceConfig {
+    /// Defaults to [`FsyncPolicy::Never`].
+    pub fsync: FsyncPolicy,
+    /// How long a write waits for a later one to the same file, which replaces it. Defaults to
+    /// 100 milliseconds.
+    pub coalesce_delay: Duration,
+}
+
+impl Default for PersistenceConfig {
+    fn default() -> Self {
+        Self {
+            fsync: FsyncPolicy::Never,
+            coalesce_delay: Duration::from_millis(100),
+        }
+    }
+}
+
+enum Pending {
+    Write(Vec<u8>),
+    Remove,
+}
+
+#[derive(Default)]
+struct Queue {
+    pending: BTreeMap<PathBuf, Pending>,
+    /// Files written but not synced yet, with `FsyncPolicy::Interval`.
+    unsynced: BTreeSet<PathBuf>,
+}
+
+/// Writes persisted files on the `persistence-writer` background thread, keeping only the latest
+/// contents queued for each file.
+pub(crate) struct PersistenceWriter {
+    config: PersistenceConfig,
+    policy: ErrorPolicy,
+    queue: Mutex<Queue>,
+}
+
+impl PersistenceWriter {
+    /// Start writing in the background. Start it before the subsystems persisting through it, so
+    /// what they queue on shutdown is written after they've stopped.
+    pub(crate) fn start(config: PersistenceConfig, policy: ErrorPolicy, subsystems: &Subsystems) -> Arc<Self> {
+        let writer = Arc::new(Self {
+            config,
+            policy,
+            queue: Mutex::new(Queue::default()),
+        });
+        // Registered first so it runs last, once the background thread has stopped
+        let flushed = writer.clone();
+        subsystems.register("persistence", move || {
+            flushed.flush();
+            flushed.sync_written();
+        });
+        let background = writer.clone();
+        subsystems.spawn("persistence-writer", move |signal| {
+            let mut synced = Instant::now();
+            while !signal.sleep(background.config.coalesce_delay) {
+                background.flush();
+                if let FsyncPolicy::Interval(interval) = background.config.fsync {
+                    if synced.elapsed() >= interval {
+                        background.sync_written();
+                        synced = Instant::now();
+                    }
+                }
+            }
+        });
+        writer
+    }
+
+    /// Queue `contents` to be written to `path`, replacing a write to it still queued.
+    pub(crate) fn write(&self, path: PathBuf, contents: impl Into<Vec<u8>>) {
+        self.queue(path, Pending::Write(contents.into()));
+    }
+
+    /// Queue the removal of `path`, dropping a write to it still queued.
+    pub(crate) fn remove(&self, path: PathBuf) {
+        self.queue(path, Pending::Remove);
+    }
+
+    /// Write everything queued.
+    fn flush(&self) {
+        let pending = match self.queue.lock() {
+            Ok(mut queue) => std::mem::take(&mut queue.pending),
+            Err(_) => return,
+        };
+        let sync = self.config.fsync == FsyncPolicy::Always;
+        let mut written = Vec::new();
+        for (path, pending) in pending {
+            let result = match pending {
+                Pending::Write(contents) => {
+                    write_atomically(&path, &contents, sync).map(|_| written.push(path.clone()))
+                }
+                Pending::Remove => match std::fs::remove_file(&path) {
+                    Err(e) if e.kind() != std::io::ErrorKind::NotFound => Err(e),
+                    _ => Ok(()),
+                },
+            };
+            if let Err(e) = result {
+                self.policy.report(format_args!("failed to write {}", path.display()), &e);
+            }
+        }
+        if matches!(self.config.fsync, FsyncPolicy::Interval(_)) {
+            if let Ok(mut queue) = self.queue.lock() {
+                queue.unsynced.extend(written);
+            }
+        }
+    }
+
+    fn queue(&self, path: PathBuf, pending: Pending) {
+        if let Ok(mut queue) = self.queue.lock() {
+            queue.pending.insert(path, pending);
+        }
+    }
+
+    /// Sync the files written since the last sync, with `FsyncPolicy::Interval`.
+    fn sync_written(&self) {
+        let unsynced = match self.queue.lock() {
+            Ok(mut queue) => std::mem::take(&mut queue.unsynced),
+            Err(_) => return,
+        };
+        for path in unsynced {
+            // Opened for writing, since Windows can't flush a read-only handle
+            let synced = OpenOptions::new().write(true).open(&path).and_then(|file| file.sync_all());
+            match synced {
+                Ok(()) => sync_dir(&path),
+                // Removed since
+                Err(e) if e.kind() == std::io::ErrorKind::NotFound => {}
+                Err(e) => self.policy.report(format_args!("failed to sync {}", path.display()), &e),
+            }
+        }
+    }
+}
+
+/// Write `contents` to a temp file next to `path` and rename it into place, so readers never
+/// see a half-written file. For files that must be on disk before the caller goes on; others go
+/// through [`PersistenceWriter`].
+pub(crate) fn write_atomically(path: &Path, contents: &[u8], sync: bool) -> std::io::Result<()> {
+    if let Some(dir) = path.parent() {
+        std::fs::create_dir_all(dir)?;
+    }
+    let mut name = path.file_name().unwrap_or_default().to_os_string();
+    name.push(".tmp");
+    let temp = path.with_file_name(name);
+    let mut file = File::create(&temp)?;
+    file.write_all(contents)?;
+    if sync {
+        file.sync_all()?;
+    }
+    drop(file);
+    std::fs::rename(&temp, path)?;
+    if sync {
+        sync_dir(path);
+    }
+    Ok(())
+}
+
+/// Sync the directory of `path`, so a rename into it survives a power loss. Only possible, and
+/// needed, on Unix.
+#[cfg(unix)]
+fn sync_dir(path: &Path) {
+    if let Some(dir) = path.parent() {
+        let _ = File::open(dir).and_then(|dir| dir.sync_all());
+    }
+}
+
+#[cfg(not(unix))]
+fn sync_dir(_path: &Path) {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn writes_atomically_into_missing_directories() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("nested").join("state.json");
+        write_atomically(&path, b"first", false).unwrap();
+        write_atomically(&path, b"second", true).unwrap();
+        assert_eq!(std::fs::read(&path).unwrap(), b"second");
+        assert_eq!(std::fs::read_dir(path.parent().unwrap()).unwrap().count(), 1);
+    }
+
+    #[test]
+    fn keeps_the_latest_write_and_flushes_it_on_shutdown() {
+        let dir = tempfile::tempdir().unwrap();
+        let config = PersistenceConfig {
+            coalesce_delay: Duration::from_secs(60),
+            ..Default::default()
+        };
+        let subsystems = Subsystems::default();
+        let writer = PersistenceWriter::start(config, ErrorPolicy::default(), &subsystems);
+        let (kept, removed) = (dir.path().join("kept.json"), dir.path().join("removed.json"));
+        std::fs::write(&removed, "old").unwrap();
+        writer.write(kept.clone(), "first");
+        writer.write(kept.clone(), "second");
+        writer.write(removed.clone(), "new");
+        writer.remove(removed.clone());
+        assert!(!kept.exists());
+
+        subsystems.shutdown(Duration::from_secs(5));
+        assert_eq!(std::fs::read_to_string(&kept).unwrap(), "second");
+        assert!(!removed.exists());
+    }
+}
@@ -0,0 +1,265 @@
+use std::collections::{BTreeMap, BTreeSet};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use crate::models::*;
+use crate::path_actions::{DELETE_PATH_ACTION, SET_PATH_ACTION};
+
+pub(crate) const SET_ACTION: &str = "SETTINGS:SET";
+pub(crate) const RESET_ACTION: &str = "SETTINGS:RESET";
+
+/// Reads and writes settings in the platform's preference store, e.g. `NSUserDefaults` on
+/// macOS, the Registry on Windows or GSettings on Linux, where enterprise policy tooling
+/// (configuration profiles, Group Policy, dconf locks) can manage them.
+///
+/// Methods are called with the keys configured with [`PlatformPrefsConfig::key`], from dispatches
+/// and from a background thread. Report failures as [`crate::Error::PrefsError`].
+pub trait PlatformPrefsBackend: Send + Sync + 'static {
+    /// The value of `key`, or `None` if it isn't set.
+    fn read(&self, key: &str) -> crate::Result<Option<JsonValue>>;
+
+    /// Set `key` to `value` in the user's scope.
+    fn write(&self, key: &str, value: &JsonValue) -> crate::Result<()>;
+
+    /// Remove the user's value of `key`, so it falls back to a system-wide value or the default.
+    fn remove(&self, key: &str) -> crate::Result<()>;
+
+    /// Whether `key` is enforced by policy, in which case windows can't change it.
+    /// Returns false by default.
+    fn managed(&self, _key: &str) -> bool {
+        false
+    }
+}
+
+/// Stores the `state_key` slice in a [`PlatformPrefsBackend`] instead of the state manager.
+///
+/// The slice is `{ values: { <key>: value }, managed: [<key>] }`. Windows change a value with
+/// `SETTINGS:SET { key, value }`, or a path action on `<state_key>.values.<key>`, and reset it
+/// with `SETTINGS:RESET { key }`; changes to managed keys are rejected. The store is polled
+/// every `poll_interval`, so values changed by policy tooling reach the windows.
+#[derive(Clone)]
+pub struct PlatformPrefsConfig {
+    backend: Arc<dyn PlatformPrefsBackend>,
+    /// The keys stored, with the values used while they aren't set.
+    defaults: BTreeMap<String, JsonValue>,
+    /// The state key the settings are stored under. Defaults to "settings".
+    pub state_key: String,
+    /// How often the store is read for outside changes. Defaults to 30 seconds.
+    pub poll_interval: Duration,
+}
+
+impl PlatformPrefsConfig {
+    pub fn new<B: PlatformPrefsBackend>(backend: B) -> Self {
+        Self {
+            backend: Arc::new(backend),
+            defaults: BTreeMap::new(),
+            state_key: "settings".to_string(),
+            poll_interval: Duration::from_secs(30),
+        }
+    }
+
+    /// Store `key`, with `default` used while it isn't set.
+    pub fn key(mut self, key: impl Into<String>, default: impl Into<JsonValue>) -> Self {
+        self.defaults.insert(key.into(), default.into());
+        self
+    }
+}
+
+#[derive(Default, PartialEq)]
+struct Settings {
+    values: BTreeMap<String, JsonValue>,
+    managed: BTreeSet<String>,
+}
+
+/// The settings as last read from the backend.
+pub(crate) struct PlatformPrefs {
+    config: PlatformPrefsConfig,
+    settings: Mutex<Settings>,
+}
+
+impl PlatformPrefs {
+    /// Start from the defaults until the backend is read with `refresh`.
+    pub(crate) fn new(config: PlatformPrefsConfig) -> Self {
+        let settings = Settings {
+            values: config.defaults.clone(),
+            managed: BTreeSet::new(),
+        };
+        Self {
+            config,
+            settings: Mutex::new(settings),
+        }
+    }
+
+    /// Read every key from the backend, returning whether anything changed.
+    pub(crate) fn refresh(&self) -> crate::Result<bool> {
+        let mut read = Settings::default();
+        for key in self.config.defaults.keys() {
+            read.values.insert(key.clone(), self.read(key)?);
+            if self.config.backend.managed(key) {
+                read.managed.insert(key.clone());
+            }
+        }
+        let mut settings = self.settings.lock().map_err(|e| crate::Error::StateError(e.to_string()))?;
+        if *settings == read {
+            return Ok(false);
+        }
+        *settings = read;
+        Ok(true)
+    }
+
+    pub(crate) fn poll_interval(&self) -> Duration {
+        self.config.poll_interval
+    }
+
+    /// Whether the plugin handles `action` instead of the state manager: the settings actions,
+    /// and path actions writing under the `state_key` slice.
+    pub(crate) fn handles(&self, action: &ZubridgeAction) -> bool {
+        match action.action_type.as_str() {
+            SET_ACTION | RESET_ACTION => true,
+            SET_PATH_ACTION | DELETE_PATH_ACTION => self.path_key(action).is_some(),
+            _ => false,
+        }
+    }
+
+    /// Apply `SETTINGS:SET { key, value }` or `SETTINGS:RESET { key }`, or the equivalent path
+    /// action on `<state_key>.values.<key>`.
+    pub(crate) fn apply(&self, action: &ZubridgeAction) -> crate::Result<()> {
+        let field = |name: &str| action.payload.as_ref().and_then(|payload| payload.get(name));
+        let key = match action.action_type.as_str() {
+            SET_ACTION | RESET_ACTION => field("key").and_then(JsonValue::as_str).map(str::to_string),
+            _ => self.path_key(action),
+        }
+        .ok_or_else(|| crate::Error::ActionRejected(format!("{} requires a setting key", action.action_type)))?;
+        if !self.config.defaults.contains_key(&key) {
+            return Err(crate::Error::ActionRejected(format!("unknown setting: {}", key)));
+        }
+        // Checked against the store rather than the last poll, so a fresh policy applies at once
+        if self.config.backend.managed(&key) {
+            return Err(crate::Error::ActionRejected(format!("setting {} is managed by policy", key)));
+        }
+
+        let value = if matches!(action.action_type.as_str(), RESET_ACTION | DELETE_PATH_ACTION) {
+            self.config.backend.remove(&key)?;
+            self.read(&key)?
+        } else {
+            let value = field("value")
+                .cloned()
+                .ok_or_else(|| crate::Error::ActionRejected(format!("setting {} requires a value", key)))?;
+            self.config.backend.write(&key, &value)?;
+            value
+        };
+        self.settings
+            .lock()
+            .map_err(|e| crate::Error::StateError(e.to_string()))?
+            .values
+            .insert(key, value);
+        Ok(())
+    }
+
+    pub(crate) fn state_key(&self) -> &str {
+        &self.config.state_key
+    }
+
+    pub(crate) fn slice(&self) -> crate::Result<JsonValue> {
+        let settings = self.settings.lock().map_err(|e| crate::Error::StateError(e.to_string()))?;
+        Ok(serde_json::json!({ "values": settings.values, "managed": settings.managed }))
+    }
+
+    /// The stored value of `key`, or its default.
+    fn read(&self, key: &str) -> crate::Result<JsonValue> {
+        Ok(match self.config.backend.read(key)? {
+            Some(value) => value,
+            None => self.config.defaults.get(key).cloned().unwrap_or(JsonValue::Null),
+        })
+    }
+
+    /// The setting a path action targets, if its path is `<state_key>.values.<key>`.
+    fn path_key(&self, action: &ZubridgeAction) -> Option<String> {
+        let path = action.payload.as_ref()?.get("path")?.as_str()?;
+        let key = path.strip_prefix(&self.config.state_key)?.strip_prefix(".values.")?;
+        (!key.is_empty() && !key.contains('.')).then(|| key.to_string())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    /// An in-memory store where keys starting with `policy.` are managed.
+    #[derive(Clone, Default)]
+    struct Store(Arc<Mutex<BTreeMap<String, JsonValue>>>);
+
+    impl PlatformPrefsBackend for Store {
+        fn read(&self, key: &str) -> crate::Result<Option<JsonValue>> {
+            Ok(self.0.lock().unwrap().get(key).cloned())
+        }
+
+        fn write(&self, key: &str, value: &JsonValue) -> crate::Result<()> {
+            self.0.lock().unwrap().insert(key.to_string(), value.clone());
+            Ok(())
+        }
+
+        fn remove(&self, key: &str) -> crate::Result<()> {
+            self.0.lock().unwrap().remove(key);
+            Ok(())
+        }
+
+        fn managed(&self, key: &str) -> bool {
+            key.starts_with("policy.")
+        }
+    }
+
+    fn prefs(store: &Store) -> PlatformPrefs {
+        let config = PlatformPrefsConfig::new(store.clone())
+            .key("theme", "light")
+            .key("policy.updates", true);
+        PlatformPrefs::new(config)
+    }
+
+    fn action(action_type: &str, payload: JsonValue) -> ZubridgeAction {
+        ZubridgeAction {
+            action_type: action_type.into(),
+            payload: Some(payload),
+        }
+    }
+
+    #[test]
+    fn writes_and_resets_settings_in_the_store() {
+        let store = Store::default();
+        let prefs = prefs(&store);
+        prefs.apply(&action(SET_ACTION, json!({ "key": "theme", "value": "dark" }))).unwrap();
+        assert_eq!(store.0.lock().unwrap()["theme"], "dark");
+        assert_eq!(prefs.slice().unwrap()["values"]["theme"], "dark");
+
+        let reset = action(DELETE_PATH_ACTION, json!({ "path": "settings.values.theme" }));
+        assert!(prefs.handles(&reset));
+        prefs.apply(&reset).unwrap();
+        assert_eq!(prefs.slice().unwrap()["values"]["theme"], "light");
+        assert!(!prefs.handles(&action(SET_PATH_ACTION, json!({ "path": "todos.values.theme" }))));
+    }
+
+    #[test]
+    fn rejects_unknown_and_managed_settings() {
+        let store = Store::default();
+        let prefs = prefs(&store);
+        for payload in [json!({ "key": "font", "value": 1 }), json!({ "key": "policy.updates", "value": false })] {
+            assert!(matches!(prefs.apply(&action(SET_ACTION, payload)), Err(crate::Error::ActionRejected(_))));
+        }
+        assert!(prefs.apply(&action(SET_ACTION, json!({ "key": "theme" }))).is_err());
+        assert!(store.0.lock().unwrap().is_empty());
+    }
+
+    #[test]
+    fn picks_up_changes_made_outside_the_app() {
+        let store = Store::default();
+        let prefs = prefs(&store);
+        assert!(prefs.refresh().unwrap());
+        assert!(!prefs.refresh().unwrap());
+        assert_eq!(prefs.slice().unwrap()["managed"], json!(["policy.updates"]));
+
+        store.write("policy.updates", &json!(false)).unwrap();
+        assert!(prefs.refresh().unwrap());
+        assert_eq!(prefs.slice().unwrap()["values"]["policy.updates"], false);
+    }
+}
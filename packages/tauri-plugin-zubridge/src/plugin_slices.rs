@@ -0,0 +1,200 @@
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Mutex, RwLock};
+
+use crate::models::*;
+use crate::utils::catch_reducer_panic;
+
+/// The prefixes of the actions the plugin handles itself, which a slice's actions would be
+/// routed away from or shadow.
+const RESERVED_NAMESPACES: &[&str] = &[
+    "AUTOMATION", "CHART", "DIALOG", "FORM", "PAGE", "SECRETS", "SESSION", "SETTINGS", "TIMER", "TRASH", "UPDATER",
+    "WINDOW", "WORKFLOW", "WORKSPACE", "__ZUBRIDGE",
+];
+
+/// A state manager mounted by another plugin under its own top-level key.
+struct PluginSlice {
+    namespace: String,
+    manager: Mutex<Box<dyn StateManager>>,
+    /// Whether the app's state is known not to have the key already. Slices registered before
+    /// the app's state manager are checked the first time the state is read.
+    checked: AtomicBool,
+}
+
+/// Slices other Tauri plugins mount into the app's state with `ZubridgeExt::register_slice`.
+/// Actions typed `<namespace>:*` go to the slice's state manager instead of the app's.
+#[derive(Default)]
+pub(crate) struct PluginSlices {
+    slices: RwLock<Vec<PluginSlice>>,
+}
+
+impl PluginSlices {
+    /// Mount `manager` under `namespace`, unless another plugin already did. `checked` tells
+    /// whether the app's state was checked for the key already.
+    pub(crate) fn register(&self, namespace: &str, manager: Box<dyn StateManager>, checked: bool) -> crate::Result<()> {
+        if namespace.is_empty() || namespace.contains(['.', ':']) {
+            return Err(crate::Error::ConfigError(format!("invalid slice namespace: {:?}", namespace)));
+        }
+        if RESERVED_NAMESPACES.contains(&namespace) {
+            let message = format!("slice namespace {} is reserved for built-in actions", namespace);
+            return Err(crate::Error::ConfigError(message));
+        }
+        let mut slices = self.slices.write().map_err(|e| crate::Error::StateError(e.to_string()))?;
+        if slices.iter().any(|slice| slice.namespace == namespace) {
+            return Err(crate::Error::SliceConflict(namespace.to_string()));
+        }
+        slices.push(PluginSlice {
+            namespace: namespace.to_string(),
+            manager: Mutex::new(manager),
+            checked: AtomicBool::new(checked),
+        });
+        Ok(())
+    }
+
+    /// Whether `action_type` belongs to a mounted slice.
+    pub(crate) fn owns_action(&self, action_type: &str) -> bool {
+        let Some((namespace, _)) = action_type.split_once(':') else { return false };
+        self.owns(namespace)
+    }
+
+    /// Whether `key` is a mounted slice.
+    pub(crate) fn owns(&self, key: &str) -> bool {
+        self.slices
+            .read()
+            .map(|slices| slices.iter().any(|slice| slice.namespace == key))
+            .unwrap_or(false)
+    }
+
+    /// Validate and apply `action` with the state manager of the slice it belongs to.
+    pub(crate) fn apply(&self, action: &ZubridgeAction) -> crate::Result<()> {
+        let slices = self.slices.read().map_err(|e| crate::Error::StateError(e.to_string()))?;
+        let Some(slice) = action
+            .action_type
+            .split_once(':')
+            .and_then(|(namespace, _)| slices.iter().find(|slice| slice.namespace == namespace))
+        else {
+            return Ok(());
+        };
+        let action_json = serde_json::json!({ "type": action.action_type, "payload": action.payload });
+        let mut manager = slice.manager.lock().map_err(|e| crate::Error::StateError(e.to_string()))?;
+        manager.validate_action(&action_json)?;
+        catch_reducer_panic(|| manager.try_dispatch_action(action_json))??;
+        Ok(())
+    }
+
+    /// Add the mounted slices to the state. With `check`, a slice not checked yet whose key
+    /// the state already has is unmounted, failing with [`crate::Error::SliceConflict`], rather
+    /// than replacing the app's own state.
+    pub(crate) fn insert_slices(
+        &self,
+        root: &mut serde_json::Map<String, JsonValue>,
+        check: bool,
+    ) -> crate::Result<()> {
+        let conflict = {
+            let slices = self.slices.read().map_err(|e| crate::Error::StateError(e.to_string()))?;
+            let conflict = slices.iter().find(|slice| {
+                check && !slice.checked.load(Ordering::SeqCst) && root.contains_key(&slice.namespace)
+            });
+            match conflict {
+                Some(slice) => slice.namespace.clone(),
+                None => {
+                    for slice in slices.iter() {
+                        if check {
+                            slice.checked.store(true, Ordering::SeqCst);
+                        }
+                        let manager = slice.manager.lock().map_err(|e| crate::Error::StateError(e.to_string()))?;
+                        root.insert(slice.namespace.clone(), manager.get_initial_state());
+                    }
+                    return Ok(());
+                }
+            }
+        };
+        log::error!("zubridge: unmounting slice {}, which the app's state already has", conflict);
+        let mut slices = self.slices.write().map_err(|e| crate::Error::StateError(e.to_string()))?;
+        slices.retain(|slice| slice.namespace != conflict);
+        Err(crate::Error::SliceConflict(conflict))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[derive(Default)]
+    struct Counter {
+        counter: i64,
+    }
+
+    impl StateManager for Counter {
+        fn get_initial_state(&self) -> JsonValue {
+            json!({ "counter": self.counter })
+        }
+
+        fn dispatch_action(&mut self, action: JsonValue) -> JsonValue {
+            match action["type"].as_str() {
+                Some("counter:INCREMENT") => self.counter += 1,
+                Some("counter:PANIC") => panic!("reducer failed"),
+                _ => {}
+            }
+            self.get_initial_state()
+        }
+    }
+
+    fn action(action_type: &str) -> ZubridgeAction {
+        ZubridgeAction {
+            action_type: action_type.into(),
+            payload: None,
+        }
+    }
+
+    #[test]
+    fn routes_namespaced_actions_to_their_slice() {
+        let slices = PluginSlices::default();
+        slices.register("counter", Box::<Counter>::default(), true).unwrap();
+        assert!(slices.owns_action("counter:INCREMENT"));
+        assert!(!slices.owns_action("todos:ADD") && !slices.owns_action("counter"));
+        slices.apply(&action("counter:INCREMENT")).unwrap();
+        slices.apply(&action("todos:ADD")).unwrap();
+        assert!(slices.apply(&action("counter:PANIC")).is_err());
+
+        let mut root = serde_json::Map::new();
+        slices.insert_slices(&mut root, true).unwrap();
+        assert_eq!(JsonValue::Object(root), json!({ "counter": { "counter": 1 } }));
+    }
+
+    #[test]
+    fn rejects_invalid_and_taken_namespaces() {
+        let slices = PluginSlices::default();
+        slices.register("counter", Box::<Counter>::default(), true).unwrap();
+        assert!(matches!(
+            slices.register("counter", Box::<Counter>::default(), true),
+            Err(crate::Error::SliceConflict(_))
+        ));
+        for namespace in ["", "a.b", "a:b", "WINDOW", "__ZUBRIDGE"] {
+            assert!(matches!(
+                slices.register(namespace, Box::<Counter>::default(), true),
+                Err(crate::Error::ConfigError(_))
+            ));
+        }
+    }
+
+    #[test]
+    fn unmounts_slices_that_turn_out_to_collide_with_the_app_state() {
+        let slices = PluginSlices::default();
+        slices.register("counter", Box::<Counter>::default(), false).unwrap();
+        slices.register("other", Box::<Counter>::default(), false).unwrap();
+        let app_state = |key: &str| json!({ key: "the app's" }).as_object().unwrap().clone();
+
+        // Not checked against a placeholder state
+        slices.insert_slices(&mut app_state("counter"), false).unwrap();
+        let mut root = app_state("counter");
+        assert!(matches!(slices.insert_slices(&mut root, true), Err(crate::Error::SliceConflict(_))));
+        assert!(!slices.owns("counter") && slices.owns("other"));
+
+        let mut root = app_state("counter");
+        slices.insert_slices(&mut root, true).unwrap();
+        assert_eq!(JsonValue::Object(root), json!({ "counter": "the app's", "other": { "counter": 0 } }));
+        // Checked once
+        slices.insert_slices(&mut app_state("other"), true).unwrap();
+    }
+}
@@ -0,0 +1,119 @@
+use sha2::{Digest, Sha256};
+use tauri::http::{header, Request, Response, StatusCode};
+use tauri::{AppHandle, Manager, Runtime, Url};
+
+use crate::desktop::Zubridge;
+use crate::utils::{get_path, hex, window_origin};
+
+/// Serve state snapshots on the `zubridge` protocol.
+///
+/// `zubridge://localhost/state` returns the current state, lazy slices included, and `?path=theme.is_dark` narrows it
+/// to a slice. Responses carry a hash of their body as their `ETag`, so webviews can revalidate
+/// cached snapshots with `If-None-Match` and skip receiving them again.
+pub(crate) fn handle_request<R: Runtime>(app: &AppHandle<R>, window: &str, request: Request<Vec<u8>>) -> Response<Vec<u8>> {
+    let mut response = Response::builder();
+    if let Some(origin) = window_origin(app, window) {
+        response = response.header(header::ACCESS_CONTROL_ALLOW_ORIGIN, origin);
+    }
+
+    let Some(zubridge) = app.try_state::<Zubridge<R>>() else {
+        return response.status(StatusCode::SERVICE_UNAVAILABLE).body(Vec::new()).unwrap_or_default();
+    };
+    let Ok(url) = Url::parse(&request.uri().to_string()) else {
+        return response.status(StatusCode::BAD_REQUEST).body(Vec::new()).unwrap_or_default();
+    };
+    if url.path() != "/state" {
+        return response.status(StatusCode::NOT_FOUND).body(Vec::new()).unwrap_or_default();
+    }
+
+    let path = url
+        .query_pairs()
+        .find(|(key, _)| key == "path")
+        .map(|(_, value)| value.into_owned())
+        .unwrap_or_default();
+    let state = match zubridge.current_state() {
+        Ok(state) => state,
+        Err(e) => {
+            return response
+                .status(StatusCode::INTERNAL_SERVER_ERROR)
+                .body(e.to_string().into_bytes())
+                .unwrap_or_default()
+        }
+    };
+    let body = match get_path(&state, &path).map(serde_json::to_vec) {
+        Some(Ok(body)) => body,
+        Some(Err(e)) => {
+            return response
+                .status(StatusCode::INTERNAL_SERVER_ERROR)
+                .body(e.to_string().into_bytes())
+                .unwrap_or_default()
+        }
+        None => return response.status(StatusCode::NOT_FOUND).body(Vec::new()).unwrap_or_default(),
+    };
+
+    // Unlike the state revision, only changes when the slice served does
+    let etag = format!("\"{}\"", hex(&Sha256::digest(&body)[..16]));
+    let not_modified = request
+        .headers()
+        .get(header::IF_NONE_MATCH)
+        .is_some_and(|value| value.as_bytes() == etag.as_bytes());
+    let response = response
+        .header(header::ETAG, &etag)
+        .header(header::CACHE_CONTROL, "no-cache");
+    if not_modified {
+        return response.status(StatusCode::NOT_MODIFIED).body(Vec::new()).unwrap_or_default();
+    }
+    response
+        .header(header::CONTENT_TYPE, "application/json")
+        .body(body)
+        .unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::*;
+    use serde_json::json;
+
+    struct Theme;
+
+    impl StateManager for Theme {
+        fn get_initial_state(&self) -> JsonValue {
+            json!({ "theme": { "is_dark": true } })
+        }
+
+        fn dispatch_action(&mut self, _action: JsonValue) -> JsonValue {
+            self.get_initial_state()
+        }
+    }
+
+    fn get(app: &crate::test::MockApp, uri: &str, etag: Option<&str>) -> Response<Vec<u8>> {
+        let mut request = Request::builder().uri(uri);
+        if let Some(etag) = etag {
+            request = request.header(header::IF_NONE_MATCH, etag);
+        }
+        handle_request(app.handle(), "main", request.body(Vec::new()).unwrap())
+    }
+
+    #[test]
+    fn serves_slices_with_an_etag() {
+        let app = crate::test::mock_app(Theme);
+        let response = get(&app, "zubridge://localhost/state?path=theme.is_dark", None);
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(response.body(), b"true");
+
+        let etag = response.headers()[header::ETAG].to_str().unwrap().to_string();
+        let cached = get(&app, "zubridge://localhost/state?path=theme.is_dark", Some(&etag));
+        assert_eq!(cached.status(), StatusCode::NOT_MODIFIED);
+        assert!(cached.body().is_empty());
+        let full = get(&app, "zubridge://localhost/state", Some(&etag));
+        assert_eq!(serde_json::from_slice::<JsonValue>(full.body()).unwrap()["theme"]["is_dark"], true);
+    }
+
+    #[test]
+    fn answers_unknown_paths_with_not_found() {
+        let app = crate::test::mock_app(Theme);
+        assert_eq!(get(&app, "zubridge://localhost/state?path=missing", None).status(), StatusCode::NOT_FOUND);
+        assert_eq!(get(&app, "zubridge://localhost/other", None).status(), StatusCode::NOT_FOUND);
+    }
+}
@@ -0,0 +1,209 @@
+//! A REPL on stdin for inspecting the state and dispatching actions while the app runs, in
+//! debug builds only. Start the app with the arguments `zubridge repl`, or with `ZUBRIDGE_REPL`
+//! set:
+//!
+//! ```text
+//! zubridge> get todos.0
+//! { "id": 1, "title": "Write docs", "done": false }
+//! zubridge> dispatch TODO:TOGGLE {"id":1}
+//! ok (revision 4)
+//! ```
+
+use std::io::{BufRead, Write};
+use std::time::Duration;
+
+use tauri::{AppHandle, Manager, Runtime};
+
+use crate::desktop::Zubridge;
+use crate::models::*;
+use crate::utils::get_path;
+
+/// The environment variable that starts the REPL when set.
+pub const REPL_VAR: &str = "ZUBRIDGE_REPL";
+
+const PROMPT: &str = "zubridge> ";
+
+const HELP: &str = "\
+get [path]                  print the state, or the value at a dotted path
+keys [path]                 list the keys of the state, or of the object at a path
+dispatch <type> [payload]   dispatch an action, with an optional JSON payload
+simulate <type> [payload]   show what an action would change, without dispatching it
+log [n]                     print the last n dispatched actions (10 by default)
+revision                    print the current state revision
+help                        print this help
+exit                        stop the REPL; the app keeps running";
+
+/// Whether the app was started with `zubridge repl` or `ZUBRIDGE_REPL` set.
+pub(crate) fn requested() -> bool {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    std::env::var_os(REPL_VAR).is_some() || args.windows(2).any(|pair| pair[0] == "zubridge" && pair[1] == "repl")
+}
+
+/// Read commands from stdin once the plugin is managed.
+pub(crate) fn start<R: Runtime>(app: AppHandle<R>) {
+    // Not a subsystem: a blocking read on stdin can't be interrupted, so it would hold up shutdown
+    let spawned = std::thread::Builder::new().name("zubridge-repl".into()).spawn(move || {
+        while app.try_state::<Zubridge<R>>().is_none() {
+            std::thread::sleep(Duration::from_millis(100));
+        }
+        let zubridge = app.state::<Zubridge<R>>();
+        println!("zubridge: REPL ready, type `help` for commands");
+        let stdin = std::io::stdin();
+        loop {
+            print!("{}", PROMPT);
+            let _ = std::io::stdout().flush();
+            let mut line = String::new();
+            match stdin.lock().read_line(&mut line) {
+                Ok(0) => return,
+                Ok(_) => {}
+                Err(e) => {
+                    log::warn!("zubridge: REPL stopped: {}", e);
+                    return;
+                }
+            }
+            let line = line.trim();
+            if line.is_empty() {
+                continue;
+            }
+            if line == "exit" || line == "quit" {
+                return;
+            }
+            match run(&zubridge, line) {
+                Ok(output) => println!("{}", output),
+                Err(e) => println!("error: {}", e),
+            }
+        }
+    });
+    if let Err(e) = spawned {
+        log::warn!("zubridge: failed to start the REPL: {}", e);
+    }
+}
+
+fn run<R: Runtime>(zubridge: &Zubridge<R>, line: &str) -> crate::Result<String> {
+    let (command, rest) = line.split_once(char::is_whitespace).unwrap_or((line, ""));
+    let rest = rest.trim();
+    match command {
+        "help" => Ok(HELP.to_string()),
+        "get" => {
+            let state = zubridge.current_state()?;
+            let value = value_at(&state, rest)?;
+            pretty(value)
+        }
+        "keys" => {
+            let state = zubridge.current_state()?;
+            match value_at(&state, rest)? {
+                JsonValue::Object(fields) => Ok(fields.keys().cloned().collect::<Vec<_>>().join("\n")),
+                JsonValue::Array(items) => Ok(format!("an array of {} items", items.len())),
+                _ => Err(crate::Error::StateError(format!("{} is not an object", rest))),
+            }
+        }
+        "dispatch" => {
+            zubridge.dispatch_action(parse_action(rest)?)?;
+            Ok(format!("ok (revision {})", zubridge.revision()))
+        }
+        "simulate" => {
+            let simulation = zubridge.simulate(parse_action(rest)?)?;
+            pretty(&serde_json::to_value(simulation.diff).map_err(|e| crate::Error::SerializationError(e.to_string()))?)
+        }
+        "log" => {
+            let count = if rest.is_empty() {
+                10
+            } else {
+                rest.parse().map_err(|_| crate::Error::StateError(format!("not a count: {}", rest)))?
+            };
+            let entries = zubridge.recent_actions();
+            let lines: Vec<String> = entries[entries.len().saturating_sub(count)..]
+                .iter()
+                .map(|entry| match &entry.payload {
+                    Some(payload) => format!("{} {} {}", entry.timestamp, entry.action_type, payload),
+                    None => format!("{} {}", entry.timestamp, entry.action_type),
+                })
+                .collect();
+            Ok(lines.join("\n"))
+        }
+        "revision" => Ok(zubridge.revision().to_string()),
+        _ => Err(crate::Error::StateError(format!("unknown command `{}`, type `help` for commands", command))),
+    }
+}
+
+fn value_at<'a>(state: &'a JsonValue, path: &str) -> crate::Result<&'a JsonValue> {
+    if path.is_empty() {
+        return Ok(state);
+    }
+    get_path(state, path).ok_or_else(|| crate::Error::StateError(format!("nothing at {}", path)))
+}
+
+/// Parse `<type> [payload]`, where the payload is JSON.
+fn parse_action(input: &str) -> crate::Result<ZubridgeAction> {
+    let (action_type, payload) = input.split_once(char::is_whitespace).unwrap_or((input, ""));
+    if action_type.is_empty() {
+        return Err(crate::Error::StateError("missing action type".into()));
+    }
+    let payload = match payload.trim() {
+        "" => None,
+        payload => Some(serde_json::from_str(payload).map_err(|e| crate::Error::SerializationError(e.to_string()))?),
+    };
+    Ok(ZubridgeAction {
+        action_type: action_type.to_string(),
+        payload,
+    })
+}
+
+fn pretty(value: &JsonValue) -> crate::Result<String> {
+    serde_json::to_string_pretty(value).map_err(|e| crate::Error::SerializationError(e.to_string()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ZubridgeExt;
+    use serde_json::json;
+
+    #[derive(Default)]
+    struct Todos {
+        todos: Vec<JsonValue>,
+    }
+
+    impl StateManager for Todos {
+        fn get_initial_state(&self) -> JsonValue {
+            json!({ "todos": self.todos, "filter": "all" })
+        }
+
+        fn dispatch_action(&mut self, action: JsonValue) -> JsonValue {
+            if action["type"] == "TODO:ADD" {
+                self.todos.push(action["payload"].clone());
+            }
+            self.get_initial_state()
+        }
+
+        fn simulate_action(&self, action: JsonValue) -> Option<JsonValue> {
+            let mut copy = Todos { todos: self.todos.clone() };
+            Some(copy.dispatch_action(action))
+        }
+    }
+
+    #[test]
+    fn inspects_the_state_and_dispatches_actions() {
+        let app = crate::test::mock_app(Todos::default());
+        let zubridge = app.app().zubridge();
+        assert_eq!(run(zubridge, "dispatch TODO:ADD {\"title\": \"a\"}").unwrap(), "ok (revision 1)");
+        assert_eq!(run(zubridge, "get todos.0.title").unwrap(), "\"a\"");
+        assert_eq!(run(zubridge, "keys").unwrap(), "filter\ntodos");
+        assert_eq!(run(zubridge, "keys todos").unwrap(), "an array of 1 items");
+        assert_eq!(run(zubridge, "revision").unwrap(), "1");
+        assert!(run(zubridge, "log 1").unwrap().ends_with("TODO:ADD {\"title\":\"a\"}"));
+        let simulated = run(zubridge, "simulate TODO:ADD \"b\"").unwrap();
+        assert!(simulated.contains("\"added\": 1"), "{}", simulated);
+        assert_eq!(run(zubridge, "revision").unwrap(), "1");
+    }
+
+    #[test]
+    fn reports_invalid_commands() {
+        let app = crate::test::mock_app(Todos::default());
+        let zubridge = app.app().zubridge();
+        for line in ["get missing", "keys filter", "dispatch", "dispatch TODO:ADD {", "log many", "reset"] {
+            assert!(run(zubridge, line).is_err(), "{}", line);
+        }
+        assert!(run(zubridge, "help").unwrap().contains("simulate <type> [payload]"));
+    }
+}
@@ -0,0 +1,103 @@
+use std::collections::HashMap;
+use std::sync::Mutex;
+
+use serde::Serialize;
+use tauri::async_runtime::{self, Receiver, Sender};
+
+use crate::models::*;
+
+/// The payload of `zubridge://request` events, sent to the window a request is addressed to.
+#[derive(Clone, Debug, Serialize)]
+pub(crate) struct RpcRequest {
+    pub(crate) id: String,
+    pub(crate) topic: String,
+    pub(crate) payload: JsonValue,
+    /// The label of the requesting window, or null for requests made from Rust.
+    pub(crate) from: Option<String>,
+}
+
+struct Pending {
+    /// The label of the window the request was sent to, the only one that may answer it.
+    window: String,
+    sender: Sender<Result<JsonValue, String>>,
+}
+
+/// Requests waiting for a response, keyed by correlation id.
+#[derive(Default)]
+pub(crate) struct PendingRequests {
+    pending: Mutex<HashMap<String, Pending>>,
+}
+
+impl PendingRequests {
+    /// Register a request to `window` under the correlation id `id`, returning it and the
+    /// receiver of its response.
+    pub(crate) fn register(
+        &self,
+        id: String,
+        window: &str,
+    ) -> crate::Result<(String, Receiver<Result<JsonValue, String>>)> {
+        let (sender, receiver) = async_runtime::channel(1);
+        let mut pending = self.pending.lock().map_err(|e| crate::Error::StateError(e.to_string()))?;
+        let request = Pending {
+            window: window.to_string(),
+            sender,
+        };
+        pending.insert(id.clone(), request);
+        Ok((id, receiver))
+    }
+
+    /// Deliver a response from `window`. Returns false if the request already timed out or was
+    /// answered, and fails if it was sent to another window, since ids may be predictable.
+    pub(crate) fn respond(&self, id: &str, window: &str, response: Result<JsonValue, String>) -> crate::Result<bool> {
+        let mut pending = self.pending.lock().map_err(|e| crate::Error::StateError(e.to_string()))?;
+        match pending.get(id) {
+            Some(request) if request.window != window => Err(crate::Error::ResponseRejected {
+                id: id.to_string(),
+                window: window.to_string(),
+            }),
+            Some(_) => Ok(pending.remove(id).is_some_and(|request| request.sender.try_send(response).is_ok())),
+            None => Ok(false),
+        }
+    }
+
+    pub(crate) fn forget(&self, id: &str) {
+        if let Ok(mut pending) = self.pending.lock() {
+            pending.remove(id);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn delivers_each_response_once() {
+        let pending = PendingRequests::default();
+        let (id, mut receiver) = pending.register("1".into(), "main").unwrap();
+        assert!(pending.respond(&id, "main", Ok(JsonValue::Bool(true))).unwrap());
+        assert!(!pending.respond(&id, "main", Ok(JsonValue::Null)).unwrap());
+        assert_eq!(receiver.try_recv().unwrap(), Ok(JsonValue::Bool(true)));
+    }
+
+    #[test]
+    fn drops_forgotten_requests() {
+        let pending = PendingRequests::default();
+        let (id, mut receiver) = pending.register("1".into(), "main").unwrap();
+        pending.forget(&id);
+        assert!(!pending.respond(&id, "main", Err("late".into())).unwrap());
+        assert!(receiver.try_recv().is_err());
+    }
+
+    #[test]
+    fn rejects_responses_from_other_windows() {
+        let pending = PendingRequests::default();
+        let (id, mut receiver) = pending.register("1".into(), "main").unwrap();
+        let forged = pending.respond(&id, "settings", Ok(JsonValue::Bool(false)));
+        assert!(matches!(forged, Err(crate::Error::ResponseRejected { .. })));
+        assert!(receiver.try_recv().is_err());
+        // The request is still waiting for its window's answer
+        assert!(pending.respond(&id, "main", Ok(JsonValue::Bool(true))).unwrap());
+        assert_eq!(receiver.try_recv().unwrap(), Ok(JsonValue::Bool(true)));
+    }
+}
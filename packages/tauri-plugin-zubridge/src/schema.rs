@@ -0,0 +1,71 @@
+use schemars::JsonSchema;
+
+use crate::models::*;
+
+impl StateSchema {
+    /// Generate the schema from a state type and an action enum tagged by `type`.
+    ///
+    /// Each variant of the action enum becomes its own entry in `actions`, keyed by its tag.
+    pub fn from_types<S: JsonSchema, A: JsonSchema>() -> Self {
+        let state = serde_json::to_value(schemars::schema_for!(S)).unwrap_or_default();
+        let actions = serde_json::to_value(schemars::schema_for!(A)).unwrap_or_default();
+
+        let definitions = actions.get("definitions").cloned();
+        let variants = actions
+            .get("oneOf")
+            .or_else(|| actions.get("anyOf"))
+            .and_then(JsonValue::as_array)
+            .cloned()
+            .unwrap_or_default();
+
+        let actions = variants
+            .into_iter()
+            .filter_map(|mut variant| {
+                let action_type = action_tag(&variant)?;
+                if let (Some(definitions), Some(variant)) = (&definitions, variant.as_object_mut()) {
+                    variant.insert("definitions".to_string(), definitions.clone());
+                }
+                Some((action_type, variant))
+            })
+            .collect();
+
+        Self { state, actions }
+    }
+}
+
+/// Read the `type` tag of an action variant schema, which schemars emits as a single-value enum.
+fn action_tag(variant: &JsonValue) -> Option<String> {
+    let tag = variant.get("properties")?.get("type")?;
+    tag.get("const")
+        .or_else(|| tag.get("enum").and_then(|values| values.get(0)))
+        .and_then(JsonValue::as_str)
+        .map(str::to_string)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde::{Deserialize, Serialize};
+
+    #[derive(Serialize, Deserialize, JsonSchema)]
+    struct Counter {
+        count: i64,
+    }
+
+    #[derive(Serialize, Deserialize, JsonSchema)]
+    #[serde(tag = "type", content = "payload")]
+    enum CounterAction {
+        #[serde(rename = "COUNTER:INCREMENT")]
+        Increment,
+        #[serde(rename = "COUNTER:SET")]
+        Set(Counter),
+    }
+
+    #[test]
+    fn keys_action_schemas_by_type() {
+        let schema = StateSchema::from_types::<Counter, CounterAction>();
+        assert_eq!(schema.state["properties"]["count"]["type"], "integer");
+        assert_eq!(schema.actions.keys().collect::<Vec<_>>(), ["COUNTER:INCREMENT", "COUNTER:SET"]);
+        assert!(schema.actions["COUNTER:SET"]["definitions"]["Counter"].is_object());
+    }
+}
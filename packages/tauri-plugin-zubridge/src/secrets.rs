@@ -0,0 +1,219 @@
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+use serde::{Deserialize, Serialize};
+
+use crate::models::*;
+use crate::path_actions::{DELETE_PATH_ACTION, SET_PATH_ACTION};
+use crate::persistence::write_atomically;
+use crate::utils::now_millis;
+
+const SECRETS_FILE: &str = "secrets.json";
+
+pub(crate) const SET_ACTION: &str = "SECRETS:SET";
+pub(crate) const DELETE_ACTION: &str = "SECRETS:DELETE";
+
+/// Keeps secrets such as API tokens in the OS keychain. Only a handle per secret appears in the
+/// `state_key` slice; Rust code reads the value with `Zubridge::reveal_secret`.
+#[derive(Clone, Debug)]
+pub struct SecretsConfig {
+    /// The keychain service the secrets are stored under. Defaults to the app's identifier.
+    pub service: Option<String>,
+    /// The state key the handles are stored under. Defaults to "secrets".
+    pub state_key: String,
+}
+
+impl Default for SecretsConfig {
+    fn default() -> Self {
+        Self {
+            service: None,
+            state_key: "secrets".to_string(),
+        }
+    }
+}
+
+/// What the state holds in place of a secret.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+struct SecretHandle {
+    /// `secret://<name>`, for the frontend to tell a secret is set without seeing it.
+    handle: String,
+    /// Milliseconds since the Unix epoch.
+    updated_at: u64,
+}
+
+/// The secrets in the keychain, with their handles persisted to the plugin's data directory since
+/// the keychain can't list them.
+pub(crate) struct Secrets {
+    config: SecretsConfig,
+    service: String,
+    path: PathBuf,
+    handles: Mutex<BTreeMap<String, SecretHandle>>,
+}
+
+impl Secrets {
+    pub(crate) fn load(config: SecretsConfig, identifier: &str, data_dir: &Path) -> Self {
+        let path = data_dir.join(SECRETS_FILE);
+        let handles = std::fs::read_to_string(&path)
+            .ok()
+            .and_then(|contents| serde_json::from_str(&contents).ok())
+            .unwrap_or_default();
+        Self {
+            service: config.service.clone().unwrap_or_else(|| identifier.to_string()),
+            config,
+            path,
+            handles: Mutex::new(handles),
+        }
+    }
+
+    /// Whether the plugin handles `action` instead of the state manager: the secrets actions,
+    /// and path actions writing under the `state_key` slice.
+    pub(crate) fn handles(&self, action: &ZubridgeAction) -> bool {
+        match action.action_type.as_str() {
+            SET_ACTION | DELETE_ACTION => true,
+            SET_PATH_ACTION | DELETE_PATH_ACTION => self.path_name(action).is_some(),
+            _ => false,
+        }
+    }
+
+    /// Apply `SECRETS:SET { name, value }` or `SECRETS:DELETE { name }`, or the equivalent path
+    /// action on `<state_key>.<name>`.
+    pub(crate) fn apply(&self, action: &ZubridgeAction) -> crate::Result<()> {
+        let field = |name: &str| action.payload.as_ref().and_then(|payload| payload.get(name));
+        let name = match action.action_type.as_str() {
+            SET_ACTION | DELETE_ACTION => field("name").and_then(JsonValue::as_str).map(str::to_string),
+            _ => self.path_name(action),
+        }
+        .filter(|name| !name.is_empty())
+        .ok_or_else(|| crate::Error::ActionRejected(format!("{} requires a secret name", action.action_type)))?;
+
+        if matches!(action.action_type.as_str(), DELETE_ACTION | DELETE_PATH_ACTION) {
+            return self.delete(&name);
+        }
+        let value = field("value")
+            .and_then(JsonValue::as_str)
+            .ok_or_else(|| crate::Error::ActionRejected(format!("secret {} must be a string", name)))?;
+        self.entry(&name)?
+            .set_password(value)
+            .map_err(|e| crate::Error::SecretsError(e.to_string()))?;
+        self.update(|handles| {
+            let handle = SecretHandle {
+                handle: format!("secret://{}", name),
+                updated_at: now_millis(),
+            };
+            handles.insert(name.clone(), handle);
+        })
+    }
+
+    /// The value of the secret `name`.
+    pub(crate) fn reveal(&self, name: &str) -> crate::Result<String> {
+        let known = self
+            .handles
+            .lock()
+            .map_err(|e| crate::Error::StateError(e.to_string()))?
+            .contains_key(name);
+        if !known {
+            return Err(crate::Error::UnknownSecret(name.to_string()));
+        }
+        self.entry(name)?.get_password().map_err(|e| match e {
+            keyring::Error::NoEntry => crate::Error::UnknownSecret(name.to_string()),
+            e => crate::Error::SecretsError(e.to_string()),
+        })
+    }
+
+    fn delete(&self, name: &str) -> crate::Result<()> {
+        match self.entry(name)?.delete_credential() {
+            Ok(()) | Err(keyring::Error::NoEntry) => {}
+            Err(e) => return Err(crate::Error::SecretsError(e.to_string())),
+        }
+        self.update(|handles| {
+            handles.remove(name);
+        })
+    }
+
+    fn entry(&self, name: &str) -> crate::Result<keyring::Entry> {
+        keyring::Entry::new(&self.service, name).map_err(|e| crate::Error::SecretsError(e.to_string()))
+    }
+
+    /// The secret a path action targets, if its path is `<state_key>.<name>`.
+    fn path_name(&self, action: &ZubridgeAction) -> Option<String> {
+        let path = action.payload.as_ref()?.get("path")?.as_str()?;
+        let name = path.strip_prefix(&self.config.state_key)?.strip_prefix('.')?;
+        (!name.is_empty() && !name.contains('.')).then(|| name.to_string())
+    }
+
+    /// Change the handles and persist them.
+    fn update(&self, change: impl FnOnce(&mut BTreeMap<String, SecretHandle>)) -> crate::Result<()> {
+        let mut handles = self.handles.lock().map_err(|e| crate::Error::StateError(e.to_string()))?;
+        change(&mut handles);
+        let contents = serde_json::to_string(&*handles).map_err(|e| crate::Error::SerializationError(e.to_string()))?;
+        write_atomically(&self.path, contents.as_bytes(), false)?;
+        Ok(())
+    }
+
+    pub(crate) fn state_key(&self) -> &str {
+        &self.config.state_key
+    }
+
+    pub(crate) fn slice(&self) -> crate::Result<JsonValue> {
+        let handles = self.handles.lock().map_err(|e| crate::Error::StateError(e.to_string()))?;
+        serde_json::to_value(&*handles).map_err(|e| crate::Error::SerializationError(e.to_string()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn action(action_type: &str, payload: JsonValue) -> ZubridgeAction {
+        ZubridgeAction {
+            action_type: action_type.into(),
+            payload: Some(payload),
+        }
+    }
+
+    fn secrets(data_dir: &Path) -> Secrets {
+        // Mock credentials don't outlive their entry, so values can't be read back
+        keyring::set_default_credential_builder(keyring::mock::default_credential_builder());
+        Secrets::load(SecretsConfig::default(), "com.example.app", data_dir)
+    }
+
+    #[test]
+    fn handles_secrets_and_path_actions_under_the_slice() {
+        let dir = tempfile::tempdir().unwrap();
+        let secrets = secrets(dir.path());
+        assert!(secrets.handles(&action(SET_ACTION, json!({}))));
+        assert!(secrets.handles(&action(SET_PATH_ACTION, json!({ "path": "secrets.token" }))));
+        assert!(!secrets.handles(&action(SET_PATH_ACTION, json!({ "path": "secrets.token.value" }))));
+        assert!(!secrets.handles(&action(DELETE_PATH_ACTION, json!({ "path": "secretsx.token" }))));
+        assert!(!secrets.handles(&action("TODO:ADD", json!({}))));
+    }
+
+    #[test]
+    fn persists_handles_without_values() {
+        let dir = tempfile::tempdir().unwrap();
+        let secrets = secrets(dir.path());
+        secrets.apply(&action(SET_ACTION, json!({ "name": "token", "value": "hunter2" }))).unwrap();
+        secrets.apply(&action(SET_PATH_ACTION, json!({ "path": "secrets.api", "value": "key" }))).unwrap();
+        assert_eq!(secrets.slice().unwrap()["token"]["handle"], "secret://token");
+        let persisted = std::fs::read_to_string(dir.path().join(SECRETS_FILE)).unwrap();
+        assert!(!persisted.contains("hunter2"));
+
+        secrets.apply(&action(DELETE_PATH_ACTION, json!({ "path": "secrets.api" }))).unwrap();
+        let reloaded = Secrets::load(SecretsConfig::default(), "com.example.app", dir.path());
+        let names: Vec<String> = reloaded.slice().unwrap().as_object().unwrap().keys().cloned().collect();
+        assert_eq!(names, ["token"]);
+    }
+
+    #[test]
+    fn rejects_invalid_actions_and_unknown_secrets() {
+        let dir = tempfile::tempdir().unwrap();
+        let secrets = secrets(dir.path());
+        let missing_name = secrets.apply(&action(SET_ACTION, json!({ "value": "hunter2" })));
+        assert!(matches!(missing_name, Err(crate::Error::ActionRejected(_))));
+        let not_a_string = secrets.apply(&action(SET_ACTION, json!({ "name": "token", "value": 1 })));
+        assert!(matches!(not_a_string, Err(crate::Error::ActionRejected(_))));
+        assert!(matches!(secrets.reveal("token"), Err(crate::Error::UnknownSecret(_))));
+    }
+}
@@ -0,0 +1,280 @@
+//! Encryption of the state paths listed in `sensitive_paths` wherever the plugin writes the state
+//! to disk, so the rest of it stays readable and diffable.
+//!
+//! Each sensitive value is replaced with `{ "$encrypted": "<hex nonce and ciphertext>" }`, sealed
+//! with ChaCha20-Poly1305 and bound to its path. The nonce is derived from the path and the value,
+//! so an unchanged value encrypts to the same text and doesn't show up in snapshot diffs.
+
+use std::path::Path;
+
+use chacha20poly1305::aead::{Aead, KeyInit, Payload};
+use chacha20poly1305::{ChaCha20Poly1305, Key, Nonce};
+
+use crate::models::*;
+use crate::utils::{hex, hmac_sha256, random_key, unhex};
+
+const KEY_FILE: &str = "state.key";
+/// The keychain entry the key is stored under, with the `secrets` feature.
+#[cfg(feature = "secrets")]
+const KEY_ENTRY: &str = "zubridge-state-key";
+const NONCE_LEN: usize = 12;
+
+/// The field an encrypted value is stored under.
+pub const ENCRYPTED_FIELD: &str = "$encrypted";
+
+pub(crate) struct SensitivePaths {
+    paths: Vec<String>,
+    key: [u8; 32],
+    cipher: ChaCha20Poly1305,
+}
+
+impl SensitivePaths {
+    /// Encrypt `paths` with `key`, or with a key generated on first launch and stored in the
+    /// OS keychain under `service`, with the `secrets` feature, or in `data_dir`.
+    pub(crate) fn new(paths: Vec<String>, key: Option<[u8; 32]>, data_dir: &Path, service: &str) -> crate::Result<Self> {
+        let key = match key {
+            Some(key) => key,
+            None => load_or_create_key(data_dir, service)?,
+        };
+        Ok(Self {
+            paths,
+            cipher: ChaCha20Poly1305::new(Key::from_slice(&key)),
+            key,
+        })
+    }
+
+    /// Replace the values at the sensitive paths with their encrypted form. Values already
+    /// encrypted are left as they are.
+    pub(crate) fn encrypt(&self, state: &mut JsonValue) -> crate::Result<()> {
+        for path in &self.paths {
+            let segments: Vec<&str> = path.split('.').filter(|segment| !segment.is_empty()).collect();
+            visit(state, &segments, &mut String::new(), &mut |path, value| self.seal(path, value))?;
+        }
+        Ok(())
+    }
+
+    /// Undo [`SensitivePaths::encrypt`]. Fails with [`crate::Error::DecryptionFailed`] if a value
+    /// was encrypted with another key or moved to another path.
+    pub(crate) fn decrypt(&self, state: &mut JsonValue) -> crate::Result<()> {
+        for path in &self.paths {
+            let segments: Vec<&str> = path.split('.').filter(|segment| !segment.is_empty()).collect();
+            visit(state, &segments, &mut String::new(), &mut |path, value| self.open(path, value))?;
+        }
+        Ok(())
+    }
+
+    fn seal(&self, path: &str, value: &mut JsonValue) -> crate::Result<()> {
+        if sealed_text(value).is_some() {
+            return Ok(());
+        }
+        let plaintext = serde_json::to_vec(value).map_err(|e| crate::Error::SerializationError(e.to_string()))?;
+        let mut input = path.as_bytes().to_vec();
+        input.push(0);
+        input.extend_from_slice(&plaintext);
+        let nonce = &hmac_sha256(&self.key, &input)[..NONCE_LEN];
+        let ciphertext = self
+            .cipher
+            .encrypt(Nonce::from_slice(nonce), Payload { msg: &plaintext, aad: path.as_bytes() })
+            .map_err(|_| crate::Error::StateError(format!("failed to encrypt {}", path)))?;
+        *value = serde_json::json!({ ENCRYPTED_FIELD: format!("{}{}", hex(nonce), hex(&ciphertext)) });
+        Ok(())
+    }
+
+    fn open(&self, path: &str, value: &mut JsonValue) -> crate::Result<()> {
+        let Some(text) = sealed_text(value) else { return Ok(()) };
+        let sealed = unhex(text)
+            .filter(|sealed| sealed.len() > NONCE_LEN)
+            .ok_or_else(|| crate::Error::DecryptionFailed(path.to_string()))?;
+        let (nonce, ciphertext) = sealed.split_at(NONCE_LEN);
+        let plaintext = self
+            .cipher
+            .decrypt(Nonce::from_slice(nonce), Payload { msg: ciphertext, aad: path.as_bytes() })
+            .map_err(|_| crate::Error::DecryptionFailed(path.to_string()))?;
+        *value = serde_json::from_slice(&plaintext).map_err(|e| crate::Error::SerializationError(e.to_string()))?;
+        Ok(())
+    }
+}
+
+/// The text of an encrypted value, if `value` is one.
+fn sealed_text(value: &JsonValue) -> Option<&str> {
+    match value {
+        JsonValue::Object(fields) if fields.len() == 1 => fields.get(ENCRYPTED_FIELD)?.as_str(),
+        _ => None,
+    }
+}
+
+/// Call `f` with each value matching `segments` and its concrete dotted path.
+/// A `*` segment matches every key of an object or element of an array.
+fn visit(
+    value: &mut JsonValue,
+    segments: &[&str],
+    path: &mut String,
+    f: &mut impl FnMut(&str, &mut JsonValue) -> crate::Result<()>,
+) -> crate::Result<()> {
+    let Some((segment, rest)) = segments.split_first() else {
+        return f(path, value);
+    };
+
+    let mut visit_child = |key: &str, child: &mut JsonValue| {
+        let len = path.len();
+        if !path.is_empty() {
+            path.push('.');
+        }
+        path.push_str(key);
+        let result = visit(child, rest, path, f);
+        path.truncate(len);
+        result
+    };
+    match value {
+        JsonValue::Object(map) if *segment == "*" => {
+            for (key, child) in map.iter_mut() {
+                visit_child(key, child)?;
+            }
+        }
+        JsonValue::Object(map) => {
+            if let Some(child) = map.get_mut(*segment) {
+                visit_child(segment, child)?;
+            }
+        }
+        JsonValue::Array(items) if *segment == "*" => {
+            for (index, child) in items.iter_mut().enumerate() {
+                visit_child(&index.to_string(), child)?;
+            }
+        }
+        JsonValue::Array(items) => {
+            if let Some(child) = segment.parse::<usize>().ok().and_then(|index| items.get_mut(index)) {
+                visit_child(segment, child)?;
+            }
+        }
+        _ => {}
+    }
+    Ok(())
+}
+
+/// Read the key from the keychain, generating and storing a new one on first launch. A key
+/// stored in `dir` by a build without the `secrets` feature is moved to the keychain.
+#[cfg(feature = "secrets")]
+fn load_or_create_key(dir: &Path, service: &str) -> crate::Result<[u8; 32]> {
+    let entry = keyring::Entry::new(service, KEY_ENTRY).map_err(|e| crate::Error::SecretsError(e.to_string()))?;
+    match entry.get_password() {
+        Ok(existing) => match parse_key(&existing) {
+            Some(key) => return Ok(key),
+            None => log::warn!("zubridge: ignoring invalid state key in the keychain"),
+        },
+        Err(keyring::Error::NoEntry) => {}
+        Err(e) => return Err(crate::Error::SecretsError(e.to_string())),
+    }
+
+    let path = dir.join(KEY_FILE);
+    let key = read_key_file(&path).unwrap_or_else(random_key);
+    entry.set_password(&hex(&key)).map_err(|e| crate::Error::SecretsError(e.to_string()))?;
+    if path.exists() {
+        std::fs::remove_file(&path)?;
+    }
+    Ok(key)
+}
+
+/// Read the key from `dir`, generating and persisting a new one, readable only by the current
+/// user, on first launch.
+#[cfg(not(feature = "secrets"))]
+fn load_or_create_key(dir: &Path, _service: &str) -> crate::Result<[u8; 32]> {
+    let path = dir.join(KEY_FILE);
+    if let Some(key) = read_key_file(&path) {
+        return Ok(key);
+    }
+
+    let key = random_key();
+    let mut options = std::fs::OpenOptions::new();
+    options.write(true).create(true).truncate(true);
+    #[cfg(unix)]
+    std::os::unix::fs::OpenOptionsExt::mode(&mut options, 0o600);
+    let mut file = options.open(&path)?;
+    // `mode` only applies to new files, and an invalid key file may be readable by others
+    #[cfg(unix)]
+    file.set_permissions(std::os::unix::fs::PermissionsExt::from_mode(0o600))?;
+    std::io::Write::write_all(&mut file, hex(&key).as_bytes())?;
+    Ok(key)
+}
+
+fn read_key_file(path: &Path) -> Option<[u8; 32]> {
+    let existing = std::fs::read_to_string(path).ok()?;
+    let key = parse_key(&existing);
+    if key.is_none() {
+        log::warn!("zubridge: ignoring invalid state key in {}", path.display());
+    }
+    key
+}
+
+fn parse_key(text: &str) -> Option<[u8; 32]> {
+    unhex(text.trim()).and_then(|key| <[u8; 32]>::try_from(key).ok())
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    fn sensitive(key: [u8; 32]) -> SensitivePaths {
+        let paths = vec!["auth.token".to_string(), "accounts.*.password".to_string()];
+        SensitivePaths::new(paths, Some(key), Path::new("unused"), "app").unwrap()
+    }
+
+    #[test]
+    fn encrypts_only_the_sensitive_paths() {
+        let original = json!({
+            "auth": { "token": "secret", "user": "sam" },
+            "accounts": [{ "password": "hunter2" }, { "password": "letmein" }],
+        });
+        let paths = sensitive([7; 32]);
+        let mut state = original.clone();
+        paths.encrypt(&mut state).unwrap();
+        assert_eq!(state["auth"]["user"], "sam");
+        assert!(sealed_text(&state["auth"]["token"]).is_some());
+        assert!(sealed_text(&state["accounts"][1]["password"]).is_some());
+
+        // Unchanged values encrypt to the same text
+        let mut again = original.clone();
+        paths.encrypt(&mut again).unwrap();
+        assert_eq!(again, state);
+
+        paths.decrypt(&mut state).unwrap();
+        assert_eq!(state, original);
+    }
+
+    #[test]
+    fn rejects_values_moved_or_sealed_with_another_key() {
+        let paths = sensitive([7; 32]);
+        let mut state = json!({ "accounts": [{ "password": "hunter2" }, { "password": "letmein" }] });
+        paths.encrypt(&mut state).unwrap();
+
+        let mut other_key = state.clone();
+        assert!(matches!(sensitive([8; 32]).decrypt(&mut other_key), Err(crate::Error::DecryptionFailed(_))));
+
+        let mut swapped = state.clone();
+        swapped["accounts"][0] = state["accounts"][1].clone();
+        let result = paths.decrypt(&mut swapped);
+        assert!(matches!(result, Err(crate::Error::DecryptionFailed(path)) if path == "accounts.0.password"));
+    }
+
+    #[cfg(not(feature = "secrets"))]
+    #[test]
+    fn generates_a_key_once() {
+        let dir = tempfile::tempdir().unwrap();
+        let key = load_or_create_key(dir.path(), "app").unwrap();
+        assert_eq!(load_or_create_key(dir.path(), "app").unwrap(), key);
+        assert_ne!(load_or_create_key(tempfile::tempdir().unwrap().path(), "app").unwrap(), key);
+    }
+
+    #[cfg(all(unix, not(feature = "secrets")))]
+    #[test]
+    fn stores_the_key_readable_only_by_the_user() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::write(dir.path().join(KEY_FILE), "not a key").unwrap();
+        load_or_create_key(dir.path(), "app").unwrap();
+        let mode = std::fs::metadata(dir.path().join(KEY_FILE)).unwrap().permissions().mode();
+        assert_eq!(mode & 0o777, 0o600);
+    }
+}
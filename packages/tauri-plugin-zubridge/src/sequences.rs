@@ -0,0 +1,81 @@
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+use crate::persistence::write_atomically;
+
+const SEQUENCES_FILE: &str = "sequences.json";
+
+/// Monotonic counters keyed by name, persisted to the plugin's data directory.
+pub(crate) struct Sequences {
+    path: PathBuf,
+    counters: Mutex<BTreeMap<String, u64>>,
+}
+
+impl Sequences {
+    /// Read the counters from `data_dir`. Unlike other persisted plugin data, an unreadable file
+    /// is an error: starting the counters over would hand out values that were already used.
+    pub(crate) fn load(data_dir: &Path) -> crate::Result<Self> {
+        let path = data_dir.join(SEQUENCES_FILE);
+        let counters = match std::fs::read_to_string(&path) {
+            Ok(contents) => serde_json::from_str(&contents)
+                .map_err(|e| crate::Error::SerializationError(format!("{}: {}", path.display(), e)))?,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => BTreeMap::new(),
+            Err(e) => return Err(e.into()),
+        };
+        Ok(Self {
+            path,
+            counters: Mutex::new(counters),
+        })
+    }
+
+    /// Increment a counter and return its new value, starting at 1. The value is persisted
+    /// before it's returned.
+    pub(crate) fn next(&self, name: &str) -> crate::Result<u64> {
+        let mut counters = self.counters.lock().map_err(|e| crate::Error::StateError(e.to_string()))?;
+        let value = counters.get(name).copied().unwrap_or(0) + 1;
+        let mut updated = counters.clone();
+        updated.insert(name.to_string(), value);
+        self.persist(&updated)?;
+        *counters = updated;
+        Ok(value)
+    }
+
+    /// The last value returned for a counter, if any
+    pub(crate) fn current(&self, name: &str) -> crate::Result<Option<u64>> {
+        let counters = self.counters.lock().map_err(|e| crate::Error::StateError(e.to_string()))?;
+        Ok(counters.get(name).copied())
+    }
+
+    /// Write the counters right away, atomically, so a crash mid-write can't lose them.
+    fn persist(&self, counters: &BTreeMap<String, u64>) -> crate::Result<()> {
+        let contents = serde_json::to_string(counters).map_err(|e| crate::Error::SerializationError(e.to_string()))?;
+        write_atomically(&self.path, contents.as_bytes(), false)?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn counters_survive_a_reload() {
+        let dir = tempfile::tempdir().unwrap();
+        let sequences = Sequences::load(dir.path()).unwrap();
+        assert_eq!(sequences.next("invoice").unwrap(), 1);
+        assert_eq!(sequences.next("invoice").unwrap(), 2);
+        assert_eq!(sequences.current("order").unwrap(), None);
+
+        let reloaded = Sequences::load(dir.path()).unwrap();
+        assert_eq!(reloaded.current("invoice").unwrap(), Some(2));
+        assert_eq!(reloaded.next("invoice").unwrap(), 3);
+    }
+
+    #[test]
+    fn an_unreadable_file_is_an_error() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::write(dir.path().join(SEQUENCES_FILE), "{ not json").unwrap();
+        assert!(Sequences::load(dir.path()).is_err());
+    }
+}
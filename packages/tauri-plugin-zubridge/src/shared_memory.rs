@@ -0,0 +1,159 @@
+use std::fs::OpenOptions;
+use std::sync::Mutex;
+
+use memmap2::MmapMut;
+use serde::Serialize;
+use tempfile::TempDir;
+use tauri::http::{header, Request, Response, StatusCode};
+use tauri::{AppHandle, Manager, Runtime};
+
+use crate::desktop::Zubridge;
+use crate::utils::window_origin;
+
+/// How state updates are delivered to windows.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum StateTransport {
+    /// Emit the full state as the event payload.
+    #[default]
+    Event,
+    /// Experimental: write the serialized state to a memory-mapped file and emit only a
+    /// [`SharedMemoryDescriptor`]. Windows fetch the state through the `zubridge-shm` protocol.
+    /// Webviews can't map the file themselves, so each fetch still copies the state out of the
+    /// mapping into the response; what's saved is embedding very large states in event
+    /// payloads, which are escaped and serialized again on their way through the IPC channel.
+    SharedMemory,
+}
+
+/// Emitted instead of the state when using [`StateTransport::SharedMemory`].
+#[derive(Clone, Debug, Serialize)]
+pub struct SharedMemoryDescriptor {
+    pub revision: u64,
+    /// Fetch the state from `zubridge-shm://localhost/<shm_key>`.
+    pub shm_key: String,
+    pub length: usize,
+}
+
+struct Slot {
+    key: String,
+    map: MmapMut,
+    length: usize,
+}
+
+/// Double-buffered memory-mapped files holding the two most recent serialized states,
+/// so a window still reading the previous revision isn't affected by the next write.
+pub(crate) struct SharedMemoryTransport {
+    /// Removed when the transport is dropped.
+    dir: TempDir,
+    slots: Mutex<[Option<Slot>; 2]>,
+}
+
+impl SharedMemoryTransport {
+    pub(crate) fn new() -> crate::Result<Self> {
+        // A fresh, randomly named directory only this user can enter, so other local users can't
+        // read the state or swap the files for their own
+        let mut builder = tempfile::Builder::new();
+        builder.prefix("zubridge-shm-");
+        #[cfg(unix)]
+        builder.permissions(std::os::unix::fs::PermissionsExt::from_mode(0o700));
+        let dir = builder.tempdir()?;
+        Ok(Self {
+            dir,
+            slots: Mutex::new([None, None]),
+        })
+    }
+
+    /// Write a serialized state for `revision` and describe where windows can read it.
+    pub(crate) fn write(&self, revision: u64, bytes: &[u8]) -> crate::Result<SharedMemoryDescriptor> {
+        // Hold the lock for the whole write: the slot's file is truncated below, and reading
+        // the previous mapping of a truncated file would fault
+        let mut slots = self.slots.lock().map_err(|e| crate::Error::StateError(e.to_string()))?;
+
+        let index = (revision % 2) as usize;
+        // Unmap the slot's previous state first: Windows refuses to truncate or resize a file
+        // while it's mapped
+        slots[index] = None;
+        let file = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .create(true)
+            .truncate(true)
+            .open(self.dir.path().join(format!("state-{}", index)))?;
+        // Zero-length mappings aren't allowed
+        file.set_len(bytes.len().max(1) as u64)?;
+
+        // SAFETY: the file lives in a directory private to this process and is only
+        // resized or written while holding the slots lock
+        let mut map = unsafe { MmapMut::map_mut(&file)? };
+        map[..bytes.len()].copy_from_slice(bytes);
+
+        let key = format!("state-{}", revision);
+        slots[index] = Some(Slot {
+            key: key.clone(),
+            map,
+            length: bytes.len(),
+        });
+
+        Ok(SharedMemoryDescriptor {
+            revision,
+            shm_key: key,
+            length: bytes.len(),
+        })
+    }
+
+    /// Copy out the serialized state for a key, if it's still one of the two most recent
+    /// revisions. The copy is the protocol response's body, which must outlive the slots lock.
+    pub(crate) fn read(&self, key: &str) -> Option<Vec<u8>> {
+        let slots = self.slots.lock().ok()?;
+        slots
+            .iter()
+            .flatten()
+            .find(|slot| slot.key == key)
+            .map(|slot| slot.map[..slot.length].to_vec())
+    }
+}
+
+/// Serve states written by the shared memory transport on the `zubridge-shm` protocol.
+pub(crate) fn handle_request<R: Runtime>(app: &AppHandle<R>, window: &str, request: Request<Vec<u8>>) -> Response<Vec<u8>> {
+    let key = request.uri().path().trim_start_matches('/');
+    let body = app
+        .try_state::<Zubridge<R>>()
+        .and_then(|zubridge| zubridge.read_shared_state(key));
+
+    let mut response = Response::builder();
+    if let Some(origin) = window_origin(app, window) {
+        response = response.header(header::ACCESS_CONTROL_ALLOW_ORIGIN, origin);
+    }
+    match body {
+        Some(body) => response
+            .header(header::CONTENT_TYPE, "application/json")
+            .body(body),
+        None => response.status(StatusCode::NOT_FOUND).body(Vec::new()),
+    }
+    .unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn keeps_the_two_most_recent_revisions() {
+        let transport = SharedMemoryTransport::new().unwrap();
+        for revision in 1..=3 {
+            transport.write(revision, format!("{{\"revision\":{}}}", revision).as_bytes()).unwrap();
+        }
+        assert_eq!(transport.read("state-1"), None);
+        assert_eq!(transport.read("state-2").as_deref(), Some(&b"{\"revision\":2}"[..]));
+        assert_eq!(transport.read("state-3").as_deref(), Some(&b"{\"revision\":3}"[..]));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn keeps_the_files_private() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let transport = SharedMemoryTransport::new().unwrap();
+        let mode = std::fs::metadata(transport.dir.path()).unwrap().permissions().mode();
+        assert_eq!(mode & 0o777, 0o700);
+    }
+}
@@ -0,0 +1,82 @@
+use std::sync::Arc;
+
+use crate::models::*;
+
+/// Maps the arguments and working directory of a second launch to the action dispatched in
+/// the primary instance. Returning `None` skips the launch.
+pub type SecondInstanceMapper = Arc<dyn Fn(&[String], &str) -> Option<ZubridgeAction> + Send + Sync>;
+
+/// Turns second launches reported by `tauri-plugin-single-instance` into actions.
+#[derive(Clone)]
+pub struct SecondInstanceConfig {
+    /// The action dispatched with `{ args, cwd }` as payload, where `args` excludes the program
+    /// name. Defaults to "APP:SECOND_INSTANCE".
+    pub action_type: String,
+    /// Builds the action instead of `action_type`, e.g. `APP:OPEN_FILE` for a path argument.
+    pub mapper: Option<SecondInstanceMapper>,
+    /// The window unminimized and focused on a second launch. Defaults to "main".
+    pub focus_window: Option<String>,
+}
+
+impl Default for SecondInstanceConfig {
+    fn default() -> Self {
+        Self {
+            action_type: "APP:SECOND_INSTANCE".to_string(),
+            mapper: None,
+            focus_window: Some("main".to_string()),
+        }
+    }
+}
+
+impl SecondInstanceConfig {
+    /// Build the action with `mapper` instead of dispatching `action_type`.
+    pub fn with_mapper<F>(mut self, mapper: F) -> Self
+    where
+        F: Fn(&[String], &str) -> Option<ZubridgeAction> + Send + Sync + 'static,
+    {
+        self.mapper = Some(Arc::new(mapper));
+        self
+    }
+
+    pub(crate) fn action(&self, argv: &[String], cwd: &str) -> Option<ZubridgeAction> {
+        let args = argv.get(1..).unwrap_or_default();
+        match &self.mapper {
+            Some(mapper) => mapper(args, cwd),
+            None => Some(ZubridgeAction {
+                action_type: self.action_type.clone(),
+                payload: Some(serde_json::json!({ "args": args, "cwd": cwd })),
+            }),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn argv() -> Vec<String> {
+        vec!["app".into(), "notes.md".into()]
+    }
+
+    #[test]
+    fn dispatches_the_arguments_without_the_program() {
+        let action = SecondInstanceConfig::default().action(&argv(), "/home").unwrap();
+        assert_eq!(action.action_type, "APP:SECOND_INSTANCE");
+        assert_eq!(action.payload, Some(json!({ "args": ["notes.md"], "cwd": "/home" })));
+        assert_eq!(SecondInstanceConfig::default().action(&[], "/").unwrap().payload.unwrap()["args"], json!([]));
+    }
+
+    #[test]
+    fn mappers_build_or_skip_the_action() {
+        let config = SecondInstanceConfig::default().with_mapper(|args, _| {
+            let path = args.first()?;
+            Some(ZubridgeAction {
+                action_type: "APP:OPEN_FILE".into(),
+                payload: Some(json!(path)),
+            })
+        });
+        assert_eq!(config.action(&argv(), "/").unwrap().payload, Some(json!("notes.md")));
+        assert!(config.action(&argv()[..1], "/").is_none());
+    }
+}
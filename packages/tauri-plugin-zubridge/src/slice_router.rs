@@ -0,0 +1,152 @@
+use std::sync::{Arc, Mutex};
+
+use crate::models::*;
+use crate::utils::{catch_reducer_panic, glob_matches};
+
+/// A reducer for one slice of the state, run by the plugin instead of the state manager, so
+/// extensions can contribute reducers for their own slices.
+pub trait SliceReducer: Send + Sync + 'static {
+    /// The slice's state before any action was routed to it.
+    fn initial_state(&self) -> crate::Result<JsonValue>;
+
+    /// Apply a `{ type, payload }` action to the slice and return its new state.
+    fn reduce(&self, state: &JsonValue, action: &JsonValue) -> crate::Result<JsonValue>;
+}
+
+/// Routes actions to a [`SliceReducer`] owning the `key` slice.
+#[derive(Clone)]
+pub struct SliceRoute {
+    key: String,
+    action_types: Vec<String>,
+    reducer: Arc<dyn SliceReducer>,
+}
+
+impl SliceRoute {
+    /// Route actions whose type matches one of `action_types`, where `*` matches any run of
+    /// characters, to `reducer`, which owns the top-level `key` slice.
+    pub fn new<I, S, T>(key: impl Into<String>, action_types: I, reducer: T) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+        T: SliceReducer,
+    {
+        Self {
+            key: key.into(),
+            action_types: action_types.into_iter().map(Into::into).collect(),
+            reducer: Arc::new(reducer),
+        }
+    }
+
+    fn matches(&self, action_type: &str) -> bool {
+        self.action_types.iter().any(|pattern| glob_matches(pattern, action_type))
+    }
+}
+
+/// The routed slices and their current states.
+pub(crate) struct SliceRouter {
+    slices: Vec<(SliceRoute, Mutex<JsonValue>)>,
+}
+
+impl SliceRouter {
+    pub(crate) fn new(routes: Vec<SliceRoute>) -> crate::Result<Self> {
+        let slices = routes
+            .into_iter()
+            .map(|route| {
+                let state = catch_reducer_panic(|| route.reducer.initial_state())??;
+                Ok((route, Mutex::new(state)))
+            })
+            .collect::<crate::Result<_>>()?;
+        Ok(Self { slices })
+    }
+
+    pub(crate) fn is_empty(&self) -> bool {
+        self.slices.is_empty()
+    }
+
+    /// Whether any route takes `action_type`. Routed actions don't reach the state manager.
+    pub(crate) fn is_routed(&self, action_type: &str) -> bool {
+        self.slices.iter().any(|(route, _)| route.matches(action_type))
+    }
+
+    /// Whether `key` is a routed slice.
+    pub(crate) fn owns(&self, key: &str) -> bool {
+        self.slices.iter().any(|(route, _)| route.key == key)
+    }
+
+    /// Apply an action to every slice it's routed to. A failing reducer leaves its slice as it was.
+    pub(crate) fn apply(&self, action: &ZubridgeAction) -> crate::Result<()> {
+        let action_json = serde_json::json!({ "type": action.action_type, "payload": action.payload });
+        for (route, state) in self.slices.iter().filter(|(route, _)| route.matches(&action.action_type)) {
+            let mut state = state.lock().map_err(|e| crate::Error::StateError(e.to_string()))?;
+            let reduced = catch_reducer_panic(|| route.reducer.reduce(&state, &action_json))??;
+            *state = reduced;
+        }
+        Ok(())
+    }
+
+    /// Add the routed slices to the state, replacing keys of the same name.
+    pub(crate) fn insert_slices(&self, root: &mut serde_json::Map<String, JsonValue>) -> crate::Result<()> {
+        for (route, state) in &self.slices {
+            let state = state.lock().map_err(|e| crate::Error::StateError(e.to_string()))?;
+            root.insert(route.key.clone(), state.clone());
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    struct Counter;
+
+    impl SliceReducer for Counter {
+        fn initial_state(&self) -> crate::Result<JsonValue> {
+            Ok(json!(0))
+        }
+
+        fn reduce(&self, state: &JsonValue, action: &JsonValue) -> crate::Result<JsonValue> {
+            match action["type"].as_str() {
+                Some("COUNTER:FAIL") => Err(crate::Error::ActionRejected("failed".into())),
+                Some("COUNTER:PANIC") => panic!("broken"),
+                _ => Ok(json!(state.as_i64().unwrap_or(0) + 1)),
+            }
+        }
+    }
+
+    fn action(action_type: &str) -> ZubridgeAction {
+        ZubridgeAction {
+            action_type: action_type.to_string(),
+            payload: None,
+        }
+    }
+
+    fn counter(router: &SliceRouter) -> JsonValue {
+        let mut root = serde_json::Map::new();
+        router.insert_slices(&mut root).unwrap();
+        root["counter"].clone()
+    }
+
+    #[test]
+    fn routes_matching_actions_to_the_slice() {
+        let router = SliceRouter::new(vec![SliceRoute::new("counter", ["COUNTER:*"], Counter)]).unwrap();
+        assert!(router.is_routed("COUNTER:INCREMENT"));
+        assert!(!router.is_routed("TODO:ADD"));
+        assert!(router.owns("counter"));
+
+        router.apply(&action("COUNTER:INCREMENT")).unwrap();
+        router.apply(&action("TODO:ADD")).unwrap();
+        assert_eq!(counter(&router), json!(1));
+    }
+
+    #[test]
+    fn keeps_the_slice_when_its_reducer_fails() {
+        let router = SliceRouter::new(vec![SliceRoute::new("counter", ["COUNTER:*"], Counter)]).unwrap();
+        router.apply(&action("COUNTER:INCREMENT")).unwrap();
+        assert!(router.apply(&action("COUNTER:FAIL")).is_err());
+        assert!(matches!(router.apply(&action("COUNTER:PANIC")), Err(crate::Error::ReducerPanicked { .. })));
+        assert_eq!(counter(&router), json!(1));
+    }
+}
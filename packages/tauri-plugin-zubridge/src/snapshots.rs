@@ -0,0 +1,212 @@
+use std::path::{Path, PathBuf};
+
+use serde::Serialize;
+
+use crate::models::*;
+use crate::persistence::write_atomically;
+
+const SNAPSHOTS_DIR: &str = "snapshots";
+
+/// The action a window restoring a snapshot is checked against, by the session lock and
+/// `authorization`.
+pub(crate) const RESTORE_ACTION: &str = "__ZUBRIDGE:RESTORE_SNAPSHOT";
+
+/// One RFC 6902 JSON Patch operation.
+#[derive(Clone, Debug, Serialize)]
+pub struct PatchOperation {
+    /// "add", "remove" or "replace".
+    pub op: &'static str,
+    /// A JSON Pointer into the first snapshot.
+    pub path: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub value: Option<JsonValue>,
+}
+
+/// The difference between two snapshots: a JSON Patch turning the first into the second, and
+/// how many values it adds, removes and changes.
+#[derive(Clone, Debug, Serialize)]
+pub struct SnapshotDiff {
+    pub patch: Vec<PatchOperation>,
+    pub added: usize,
+    pub removed: usize,
+    pub changed: usize,
+}
+
+/// The outcome of `Zubridge::simulate`: the state an action would produce and how it differs
+/// from the current state.
+#[derive(Clone, Debug, Serialize)]
+pub struct Simulation {
+    pub state: JsonValue,
+    pub diff: SnapshotDiff,
+}
+
+/// States saved to `snapshots` in the plugin's data directory, named by id.
+pub(crate) struct Snapshots {
+    dir: PathBuf,
+}
+
+impl Snapshots {
+    pub(crate) fn new(data_dir: &Path) -> Self {
+        Self {
+            dir: data_dir.join(SNAPSHOTS_DIR),
+        }
+    }
+
+    /// Save a state under `id`.
+    pub(crate) fn save(&self, state: &JsonValue, id: &str) -> crate::Result<()> {
+        let contents = serde_json::to_string(state).map_err(|e| crate::Error::SerializationError(e.to_string()))?;
+        // Written right away, so it can be listed and restored as soon as this returns
+        write_atomically(&self.path(id)?, contents.as_bytes(), false)?;
+        Ok(())
+    }
+
+    pub(crate) fn load(&self, id: &str) -> crate::Result<JsonValue> {
+        let contents = std::fs::read_to_string(self.path(id)?).map_err(|e| match e.kind() {
+            std::io::ErrorKind::NotFound => crate::Error::UnknownSnapshot(id.to_string()),
+            _ => e.into(),
+        })?;
+        serde_json::from_str(&contents).map_err(|e| crate::Error::SerializationError(e.to_string()))
+    }
+
+    /// The ids of the saved snapshots, sorted.
+    pub(crate) fn list(&self) -> crate::Result<Vec<String>> {
+        let entries = match std::fs::read_dir(&self.dir) {
+            Ok(entries) => entries,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Vec::new()),
+            Err(e) => return Err(e.into()),
+        };
+        let mut ids: Vec<String> = entries
+            .filter_map(|entry| entry.ok())
+            .filter_map(|entry| entry.file_name().to_str()?.strip_suffix(".json").map(str::to_string))
+            .collect();
+        ids.sort();
+        Ok(ids)
+    }
+
+    fn path(&self, id: &str) -> crate::Result<PathBuf> {
+        let valid = !id.is_empty() && id.chars().all(|c| c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '.'));
+        if !valid || id.starts_with('.') {
+            return Err(crate::Error::UnknownSnapshot(id.to_string()));
+        }
+        Ok(self.dir.join(format!("{}.json", id)))
+    }
+}
+
+/// Diff two states.
+pub(crate) fn diff(a: &JsonValue, b: &JsonValue) -> SnapshotDiff {
+    let mut patch = Vec::new();
+    diff_values(a, b, &mut String::new(), &mut patch);
+    let count = |op: &str| patch.iter().filter(|operation| operation.op == op).count();
+    SnapshotDiff {
+        added: count("add"),
+        removed: count("remove"),
+        changed: count("replace"),
+        patch,
+    }
+}
+
+fn diff_values(a: &JsonValue, b: &JsonValue, path: &mut String, patch: &mut Vec<PatchOperation>) {
+    match (a, b) {
+        (JsonValue::Object(a), JsonValue::Object(b)) => {
+            for (key, a_value) in a {
+                let len = path.len();
+                push_segment(path, key);
+                match b.get(key) {
+                    Some(b_value) => diff_values(a_value, b_value, path, patch),
+                    None => patch.push(operation("remove", path, None)),
+                }
+                path.truncate(len);
+            }
+            for (key, b_value) in b.iter().filter(|(key, _)| !a.contains_key(*key)) {
+                let len = path.len();
+                push_segment(path, key);
+                patch.push(operation("add", path, Some(b_value.clone())));
+                path.truncate(len);
+            }
+        }
+        (JsonValue::Array(a), JsonValue::Array(b)) => {
+            for (index, (a_value, b_value)) in a.iter().zip(b).enumerate() {
+                let len = path.len();
+                push_segment(path, &index.to_string());
+                diff_values(a_value, b_value, path, patch);
+                path.truncate(len);
+            }
+            // Remove from the end so earlier indices stay valid while the patch is applied
+            for index in (b.len()..a.len()).rev() {
+                patch.push(operation("remove", &format!("{}/{}", path, index), None));
+            }
+            for value in b.iter().skip(a.len()) {
+                patch.push(operation("add", &format!("{}/-", path), Some(value.clone())));
+            }
+        }
+        _ if a != b => patch.push(operation("replace", path, Some(b.clone()))),
+        _ => {}
+    }
+}
+
+fn operation(op: &'static str, path: &str, value: Option<JsonValue>) -> PatchOperation {
+    PatchOperation {
+        op,
+        path: path.to_string(),
+        value,
+    }
+}
+
+/// Append a JSON Pointer segment, escaping `~` and `/`.
+fn push_segment(path: &mut String, segment: &str) {
+    path.push('/');
+    path.push_str(&segment.replace('~', "~0").replace('/', "~1"));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn diffs_states_as_a_json_patch() {
+        let a = json!({ "count": 1, "todos": ["a", "b", "c"], "old": true, "a/b": 1 });
+        let b = json!({ "count": 2, "todos": ["a"], "new": { "x": 1 }, "a/b": 1 });
+        let diff = diff(&a, &b);
+        let patch: Vec<_> = diff.patch.iter().map(|op| (op.op, op.path.as_str(), op.value.clone())).collect();
+        assert_eq!(
+            patch,
+            [
+                ("replace", "/count", Some(json!(2))),
+                ("remove", "/old", None),
+                ("remove", "/todos/2", None),
+                ("remove", "/todos/1", None),
+                ("add", "/new", Some(json!({ "x": 1 }))),
+            ]
+        );
+        assert_eq!((diff.added, diff.removed, diff.changed), (1, 3, 1));
+    }
+
+    #[test]
+    fn escapes_pointer_segments() {
+        let diff = diff(&json!({ "a/b~c": 1 }), &json!({ "a/b~c": 2, "list": [] }));
+        assert_eq!(diff.patch[0].path, "/a~1b~0c");
+        assert_eq!(diff.patch[1].path, "/list");
+    }
+
+    #[test]
+    fn saves_and_lists_snapshots_by_id() {
+        let dir = tempfile::tempdir().unwrap();
+        let snapshots = Snapshots::new(dir.path());
+        assert!(snapshots.list().unwrap().is_empty());
+        snapshots.save(&json!({ "count": 2 }), "b").unwrap();
+        snapshots.save(&json!({ "count": 1 }), "a").unwrap();
+        assert_eq!(snapshots.list().unwrap(), ["a", "b"]);
+        assert_eq!(snapshots.load("b").unwrap(), json!({ "count": 2 }));
+        assert!(matches!(snapshots.load("c"), Err(crate::Error::UnknownSnapshot(_))));
+    }
+
+    #[test]
+    fn rejects_ids_outside_the_snapshots_directory() {
+        let dir = tempfile::tempdir().unwrap();
+        let snapshots = Snapshots::new(dir.path());
+        for id in ["", "../state", ".hidden", "a/b"] {
+            assert!(snapshots.save(&json!({}), id).is_err(), "{}", id);
+        }
+    }
+}
@@ -0,0 +1,222 @@
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+
+use serde::{Deserialize, Serialize};
+
+use crate::load::{self, LoadProfile};
+use crate::metrics::MetricsSnapshot;
+use crate::models::*;
+use crate::utils::now_millis;
+
+/// The environment variable pointing to a soak config file, to start a soak run at launch.
+pub const SOAK_VAR: &str = "ZUBRIDGE_SOAK";
+
+/// A long-running dispatch pattern for catching leaks in middleware and effects, run with
+/// `Zubridge::start_soak`, `zubridge.soak` or a JSON file named by `ZUBRIDGE_SOAK`.
+#[derive(Clone, Debug, Deserialize)]
+#[serde(default)]
+pub struct SoakConfig {
+    /// Actions dispatched in order, over and over.
+    pub script: Vec<ZubridgeAction>,
+    /// Dispatches per second. Defaults to 50.
+    pub rate: u32,
+    /// How long the run lasts. Defaults to 4 hours.
+    pub duration_secs: u64,
+    /// How often memory and metrics are sampled and the report is rewritten. Defaults to a minute.
+    pub sample_interval_secs: u64,
+    /// Memory growth above which the report flags a suspected leak. Defaults to 50 MB per hour.
+    pub leak_threshold_mb_per_hour: f64,
+}
+
+impl Default for SoakConfig {
+    fn default() -> Self {
+        Self {
+            script: Vec::new(),
+            rate: 50,
+            duration_secs: 4 * 60 * 60,
+            sample_interval_secs: 60,
+            leak_threshold_mb_per_hour: 50.0,
+        }
+    }
+}
+
+/// One sample of a soak run.
+#[derive(Clone, Debug, Serialize)]
+pub struct SoakSample {
+    pub elapsed_secs: u64,
+    /// Resident memory of the process, where the platform reports it.
+    pub resident_bytes: Option<u64>,
+    pub dispatched: u64,
+    pub failed: u64,
+    /// Mean time dispatches waited for the state manager's lock during the interval.
+    pub mean_lock_wait_micros: u64,
+    pub metrics: MetricsSnapshot,
+}
+
+/// The report of a soak run, rewritten after every sample so it survives a crash.
+#[derive(Clone, Debug, Serialize)]
+pub struct SoakReport {
+    pub started_at: u64,
+    pub finished: bool,
+    pub samples: Vec<SoakSample>,
+    /// The trend of resident memory after the first sample, in bytes per hour.
+    pub memory_growth_per_hour: Option<f64>,
+    pub suspected_leak: bool,
+}
+
+/// Read a soak config from a JSON file.
+pub(crate) fn read_config(path: &Path) -> crate::Result<SoakConfig> {
+    let contents = std::fs::read_to_string(path)?;
+    serde_json::from_str(&contents).map_err(|e| crate::Error::ConfigError(e.to_string()))
+}
+
+/// Run a soak, writing the report to `report_path` after every sample. Returns early with an
+/// unfinished report when `stopped` returns true.
+pub(crate) fn run(
+    config: &SoakConfig,
+    dispatcher: Dispatcher,
+    metrics: impl Fn() -> MetricsSnapshot,
+    stopped: impl Fn() -> bool,
+    report_path: &Path,
+) -> crate::Result<SoakReport> {
+    let script = config.script.clone();
+    let profile = LoadProfile::new(move |index| script[index as usize % script.len()].clone())
+        .with_rate(config.rate)
+        .with_duration(Duration::from_secs(config.sample_interval_secs.max(1)));
+
+    let started = Instant::now();
+    let mut report = SoakReport {
+        started_at: now_millis(),
+        finished: false,
+        samples: Vec::new(),
+        memory_growth_per_hour: None,
+        suspected_leak: false,
+    };
+    while started.elapsed() < Duration::from_secs(config.duration_secs) {
+        if stopped() {
+            return Ok(report);
+        }
+        let run = load::run(&profile, dispatcher.clone(), metrics(), &metrics);
+        report.samples.push(SoakSample {
+            elapsed_secs: started.elapsed().as_secs(),
+            resident_bytes: resident_bytes(),
+            dispatched: run.dispatched,
+            failed: run.failed,
+            mean_lock_wait_micros: run.metrics.lock_wait_micros / run.metrics.dispatches.max(1),
+            metrics: run.metrics,
+        });
+        report.memory_growth_per_hour = memory_growth_per_hour(&report.samples);
+        report.suspected_leak = report
+            .memory_growth_per_hour
+            .is_some_and(|growth| growth > config.leak_threshold_mb_per_hour * 1024.0 * 1024.0);
+        write_report(report_path, &report)?;
+    }
+
+    report.finished = true;
+    write_report(report_path, &report)?;
+    Ok(report)
+}
+
+/// The path of a new soak report in the plugin's data directory.
+pub(crate) fn report_path(data_dir: &Path) -> PathBuf {
+    data_dir.join("soak").join(format!("soak-{}.json", now_millis()))
+}
+
+fn write_report(path: &Path, report: &SoakReport) -> crate::Result<()> {
+    if let Some(dir) = path.parent() {
+        std::fs::create_dir_all(dir)?;
+    }
+    let contents = serde_json::to_string_pretty(report).map_err(|e| crate::Error::SerializationError(e.to_string()))?;
+    std::fs::write(path, contents)?;
+    Ok(())
+}
+
+/// The least-squares slope of resident memory over time, skipping the first sample as warm-up.
+fn memory_growth_per_hour(samples: &[SoakSample]) -> Option<f64> {
+    let points: Vec<(f64, f64)> = samples
+        .iter()
+        .skip(1)
+        .filter_map(|sample| Some((sample.elapsed_secs as f64 / 3600.0, sample.resident_bytes? as f64)))
+        .collect();
+    if points.len() < 2 {
+        return None;
+    }
+    let n = points.len() as f64;
+    let mean_x = points.iter().map(|(x, _)| x).sum::<f64>() / n;
+    let mean_y = points.iter().map(|(_, y)| y).sum::<f64>() / n;
+    let covariance: f64 = points.iter().map(|(x, y)| (x - mean_x) * (y - mean_y)).sum();
+    let variance: f64 = points.iter().map(|(x, _)| (x - mean_x).powi(2)).sum();
+    (variance > 0.0).then(|| covariance / variance)
+}
+
+/// The resident memory of this process. Only Linux is supported for now.
+fn resident_bytes() -> Option<u64> {
+    let status = std::fs::read_to_string("/proc/self/status").ok()?;
+    let line = status.lines().find(|line| line.starts_with("VmRSS:"))?;
+    let kilobytes: u64 = line.split_whitespace().nth(1)?.parse().ok()?;
+    Some(kilobytes * 1024)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sample(elapsed_secs: u64, resident_bytes: u64) -> SoakSample {
+        SoakSample {
+            elapsed_secs,
+            resident_bytes: Some(resident_bytes),
+            dispatched: 0,
+            failed: 0,
+            mean_lock_wait_micros: 0,
+            metrics: MetricsSnapshot::default(),
+        }
+    }
+
+    #[test]
+    fn reads_configs_with_defaults() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("soak.json");
+        std::fs::write(&path, r#"{ "script": [{ "type": "TICK" }], "rate": 10 }"#).unwrap();
+        let config = read_config(&path).unwrap();
+        assert_eq!((config.script[0].action_type.as_str(), config.rate), ("TICK", 10));
+        assert_eq!(config.duration_secs, 4 * 60 * 60);
+        std::fs::write(&path, "{").unwrap();
+        assert!(matches!(read_config(&path), Err(crate::Error::ConfigError(_))));
+    }
+
+    #[test]
+    fn measures_memory_growth_after_the_first_sample() {
+        let mb = 1024 * 1024;
+        // A large warm-up sample doesn't count
+        let samples = [sample(0, 500 * mb), sample(1800, 100 * mb), sample(3600, 150 * mb)];
+        assert_eq!(memory_growth_per_hour(&samples), Some(100.0 * mb as f64));
+        assert_eq!(memory_growth_per_hour(&samples[..2]), None);
+    }
+
+    #[test]
+    fn writes_the_report_after_every_sample() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = report_path(dir.path());
+        let config = SoakConfig {
+            script: vec![ZubridgeAction {
+                action_type: "TICK".into(),
+                payload: None,
+            }],
+            rate: 100,
+            duration_secs: 1,
+            sample_interval_secs: 1,
+            ..Default::default()
+        };
+        let dispatcher = Dispatcher::new(|_| Ok(JsonValue::Null));
+
+        let report = run(&config, dispatcher.clone(), MetricsSnapshot::default, || false, &path).unwrap();
+        assert!(report.finished);
+        assert_eq!(report.samples.len(), 1);
+        assert!(report.samples[0].dispatched > 0);
+        let written: JsonValue = serde_json::from_str(&std::fs::read_to_string(&path).unwrap()).unwrap();
+        assert_eq!(written["finished"], true);
+
+        let stopped = run(&config, dispatcher, MetricsSnapshot::default, || true, &path).unwrap();
+        assert!(!stopped.finished && stopped.samples.is_empty());
+    }
+}
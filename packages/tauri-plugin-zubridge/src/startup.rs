@@ -0,0 +1,81 @@
+use std::sync::Mutex;
+
+use serde::Serialize;
+
+/// Which windows are swapped when startup completes.
+#[derive(Clone, Debug)]
+pub struct StartupConfig {
+    /// The splash window closed by `Zubridge::startup_complete`. Defaults to "splashscreen".
+    pub splash_window: String,
+    /// The window shown by `Zubridge::startup_complete`. Create it hidden to avoid white flashes.
+    /// Defaults to "main".
+    pub main_window: String,
+}
+
+impl Default for StartupConfig {
+    fn default() -> Self {
+        Self {
+            splash_window: "splashscreen".to_string(),
+            main_window: "main".to_string(),
+        }
+    }
+}
+
+/// The payload of `zubridge://startup` events.
+#[derive(Clone, Debug, Default, Serialize)]
+pub struct StartupProgress {
+    /// The stage currently loading, e.g. "config" or "persistence".
+    pub stage: String,
+    /// Overall progress between 0 and 1.
+    pub progress: f64,
+    pub done: bool,
+}
+
+/// The latest startup progress, so windows that load late can catch up with `zubridge.get-startup`.
+#[derive(Default)]
+pub(crate) struct Startup {
+    progress: Mutex<StartupProgress>,
+}
+
+impl Startup {
+    pub(crate) fn report(&self, stage: &str, progress: f64) -> crate::Result<StartupProgress> {
+        let mut current = self.progress.lock().map_err(|e| crate::Error::StateError(e.to_string()))?;
+        *current = StartupProgress {
+            stage: stage.to_string(),
+            progress: progress.clamp(0.0, 1.0),
+            done: false,
+        };
+        Ok(current.clone())
+    }
+
+    pub(crate) fn complete(&self) -> crate::Result<StartupProgress> {
+        let mut current = self.progress.lock().map_err(|e| crate::Error::StateError(e.to_string()))?;
+        *current = StartupProgress {
+            stage: "ready".to_string(),
+            progress: 1.0,
+            done: true,
+        };
+        Ok(current.clone())
+    }
+
+    pub(crate) fn get(&self) -> crate::Result<StartupProgress> {
+        let current = self.progress.lock().map_err(|e| crate::Error::StateError(e.to_string()))?;
+        Ok(current.clone())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn keeps_the_latest_progress() {
+        let startup = Startup::default();
+        assert_eq!(startup.get().unwrap().progress, 0.0);
+        let reported = startup.report("config", 1.5).unwrap();
+        assert_eq!((reported.stage.as_str(), reported.progress, reported.done), ("config", 1.0, false));
+        startup.complete().unwrap();
+        let current = startup.get().unwrap();
+        assert_eq!((current.stage.as_str(), current.done), ("ready", true));
+    }
+}
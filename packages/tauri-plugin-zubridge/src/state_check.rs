@@ -0,0 +1,101 @@
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+use crate::models::*;
+use crate::utils::now_millis;
+
+/// Brings state persisted by an earlier version of the app up to the shape the state manager
+/// expects, e.g. by renaming a field. Migrations run in order on persisted state that fails
+/// [`StateManager::validate_state`], so each should return state it doesn't apply to unchanged.
+pub type StateMigration = Arc<dyn Fn(JsonValue) -> JsonValue + Send + Sync>;
+
+/// Validate persisted `state`, running the `migrations` if it's invalid. Returns the state to
+/// restore, or the error it still fails with after migrating.
+pub(crate) fn check(
+    state_manager: &dyn StateManager,
+    state: JsonValue,
+    migrations: &[StateMigration],
+) -> crate::Result<JsonValue> {
+    let Err(e) = state_manager.validate_state(&state) else { return Ok(state) };
+    if migrations.is_empty() {
+        return Err(e);
+    }
+    log::info!("zubridge: migrating persisted state: {}", e);
+    let state = migrations.iter().fold(state, |state, migrate| migrate(state));
+    state_manager.validate_state(&state)?;
+    Ok(state)
+}
+
+/// Move an invalid persisted file aside as `<name>.invalid-<timestamp>`, next to it, so it can
+/// be inspected and isn't loaded again.
+pub(crate) fn quarantine(path: &Path) -> crate::Result<PathBuf> {
+    let name = path.file_name().map(|name| name.to_string_lossy().into_owned()).unwrap_or_default();
+    let quarantined = path.with_file_name(format!("{}.invalid-{}", name, now_millis()));
+    std::fs::rename(path, &quarantined)?;
+    Ok(quarantined)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    /// Accepts states with a numeric `count`.
+    struct Counter;
+
+    impl StateManager for Counter {
+        fn get_initial_state(&self) -> JsonValue {
+            json!({ "count": 0 })
+        }
+
+        fn dispatch_action(&mut self, _action: JsonValue) -> JsonValue {
+            self.get_initial_state()
+        }
+
+        fn validate_state(&self, state: &JsonValue) -> crate::Result<()> {
+            match state.get("count") {
+                Some(count) if count.is_number() => Ok(()),
+                _ => Err(crate::Error::InvalidState {
+                    path: "count".into(),
+                    message: "expected a number".into(),
+                }),
+            }
+        }
+    }
+
+    fn rename_total() -> StateMigration {
+        Arc::new(|mut state| {
+            if let Some(total) = state.as_object_mut().and_then(|state| state.remove("total")) {
+                state["count"] = total;
+            }
+            state
+        })
+    }
+
+    #[test]
+    fn migrates_invalid_states() {
+        let checked = check(&Counter, json!({ "total": 3 }), &[rename_total()]).unwrap();
+        assert_eq!(checked, json!({ "count": 3 }));
+        // Valid states skip the migrations
+        let checked = check(&Counter, json!({ "count": 1, "total": 3 }), &[rename_total()]).unwrap();
+        assert_eq!(checked, json!({ "count": 1, "total": 3 }));
+    }
+
+    #[test]
+    fn fails_with_states_still_invalid_after_migrating() {
+        assert!(check(&Counter, json!({ "total": 3 }), &[]).is_err());
+        assert!(check(&Counter, json!({ "count": "3" }), &[rename_total()]).is_err());
+    }
+
+    #[test]
+    fn moves_invalid_files_aside() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("state.json");
+        std::fs::write(&path, "{").unwrap();
+        let quarantined = quarantine(&path).unwrap();
+        assert!(!path.exists());
+        assert_eq!(std::fs::read_to_string(&quarantined).unwrap(), "{");
+        let name = quarantined.file_name().unwrap().to_string_lossy().into_owned();
+        assert!(name.starts_with("state.json.invalid-"));
+    }
+}
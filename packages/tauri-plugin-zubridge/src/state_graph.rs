@@ -0,0 +1,243 @@
+use std::collections::BTreeMap;
+use std::fmt::Write;
+
+use serde::Deserialize;
+
+use crate::models::*;
+
+/// How deep objects are expanded below the root.
+const MAX_DEPTH: usize = 3;
+/// How many keys of one object are drawn before the rest are summarized.
+const MAX_KEYS: usize = 25;
+
+/// The output format of `Zubridge::export_state_graph`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum GraphFormat {
+    /// A Mermaid flowchart, for Markdown docs.
+    Mermaid,
+    /// A Graphviz DOT digraph.
+    Graphviz,
+}
+
+/// Who owns a top-level slice.
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub(crate) enum SliceOwner {
+    /// The app's state manager.
+    App,
+    /// The plugin, e.g. feature flags or workflow runs.
+    Plugin,
+    /// A slice reducer.
+    Reducer,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum NodeKind {
+    Root,
+    Value,
+    Slice(SliceOwner),
+    Effect,
+}
+
+struct Node {
+    label: String,
+    kind: NodeKind,
+    lazy: bool,
+}
+
+/// The shape of the state, with its top-level slices and the effects writing to them.
+pub(crate) struct StateGraph {
+    nodes: Vec<Node>,
+    /// `(from, to, dashed)`
+    edges: Vec<(usize, usize, bool)>,
+    slices: BTreeMap<String, usize>,
+}
+
+impl StateGraph {
+    /// Draw the shape of `state`. Values are drawn by type rather than content, and arrays by the
+    /// shape of their first element.
+    pub(crate) fn new(state: &JsonValue, owner: impl Fn(&str) -> SliceOwner, is_lazy: impl Fn(&str) -> bool) -> Self {
+        let mut graph = Self {
+            nodes: vec![Node {
+                label: "state".to_string(),
+                kind: NodeKind::Root,
+                lazy: false,
+            }],
+            edges: Vec::new(),
+            slices: BTreeMap::new(),
+        };
+        if let Some(root) = state.as_object() {
+            for (key, value) in root {
+                let node = graph.add_value(0, key, value, 1);
+                graph.nodes[node].kind = NodeKind::Slice(owner(key));
+                graph.nodes[node].lazy = is_lazy(key);
+                graph.slices.insert(key.clone(), node);
+            }
+        }
+        graph
+    }
+
+    /// Add an effect, e.g. a workflow or sync task, with a dashed edge to the slice it writes to.
+    pub(crate) fn add_effect(&mut self, kind: &str, name: &str, slice: Option<&str>) {
+        let node = self.push(format!("{}: {}", kind, name), NodeKind::Effect);
+        if let Some(&target) = slice.and_then(|slice| self.slices.get(slice)) {
+            self.edges.push((node, target, true));
+        }
+    }
+
+    pub(crate) fn render(&self, format: GraphFormat) -> String {
+        match format {
+            GraphFormat::Mermaid => self.mermaid(),
+            GraphFormat::Graphviz => self.graphviz(),
+        }
+    }
+
+    fn add_value(&mut self, parent: usize, key: &str, value: &JsonValue, depth: usize) -> usize {
+        let (key, value) = match value {
+            JsonValue::Array(items) => (format!("{}[]", key), items.first()),
+            value => (key.to_string(), Some(value)),
+        };
+        let label = match value {
+            Some(value) => format!("{}: {}", key, type_name(value)),
+            None => format!("{}: empty", key),
+        };
+        let node = self.push(label, NodeKind::Value);
+        self.edges.push((parent, node, false));
+
+        if let Some(JsonValue::Object(fields)) = value {
+            if depth < MAX_DEPTH {
+                for (child, value) in fields.iter().take(MAX_KEYS) {
+                    self.add_value(node, child, value, depth + 1);
+                }
+                if fields.len() > MAX_KEYS {
+                    let more = self.push(format!("{} more", fields.len() - MAX_KEYS), NodeKind::Value);
+                    self.edges.push((node, more, false));
+                }
+            }
+        }
+        node
+    }
+
+    fn push(&mut self, label: String, kind: NodeKind) -> usize {
+        self.nodes.push(Node { label, kind, lazy: false });
+        self.nodes.len() - 1
+    }
+
+    fn mermaid(&self) -> String {
+        let mut out = String::from("flowchart LR\n");
+        for (id, node) in self.nodes.iter().enumerate() {
+            let label = node.label.replace('"', "#quot;");
+            let _ = match node.kind {
+                NodeKind::Effect => writeln!(out, "  n{}([\"{}\"])", id, label),
+                _ => writeln!(out, "  n{}[\"{}\"]", id, label),
+            };
+            if let Some(class) = class_name(node) {
+                let _ = writeln!(out, "  class n{} {}", id, class);
+            }
+        }
+        for (from, to, dashed) in &self.edges {
+            let arrow = if *dashed { "-.->" } else { "-->" };
+            let _ = writeln!(out, "  n{} {} n{}", from, arrow, to);
+        }
+        out.push_str("  classDef plugin fill:#eef,stroke:#669\n");
+        out.push_str("  classDef reducer fill:#efe,stroke:#696\n");
+        out.push_str("  classDef lazy stroke-dasharray:4 4\n");
+        out.push_str("  classDef effect fill:#ffe,stroke:#996\n");
+        out
+    }
+
+    fn graphviz(&self) -> String {
+        let mut out = String::from("digraph state {\n  rankdir=LR;\n  node [shape=box, fontname=\"Helvetica\"];\n");
+        for (id, node) in self.nodes.iter().enumerate() {
+            let label = node.label.replace('\\', "\\\\").replace('"', "\\\"");
+            let mut attributes = format!("label=\"{}\"", label);
+            match class_name(node) {
+                Some("plugin") => attributes.push_str(", style=filled, fillcolor=\"#eeeeff\""),
+                Some("reducer") => attributes.push_str(", style=filled, fillcolor=\"#eeffee\""),
+                Some("effect") => attributes.push_str(", shape=ellipse, style=filled, fillcolor=\"#ffffee\""),
+                _ => {}
+            }
+            if node.lazy {
+                attributes.push_str(", peripheries=2");
+            }
+            let _ = writeln!(out, "  n{} [{}];", id, attributes);
+        }
+        for (from, to, dashed) in &self.edges {
+            let style = if *dashed { " [style=dashed]" } else { "" };
+            let _ = writeln!(out, "  n{} -> n{}{};", from, to, style);
+        }
+        out.push_str("}\n");
+        out
+    }
+}
+
+fn type_name(value: &JsonValue) -> &'static str {
+    match value {
+        JsonValue::Null => "null",
+        JsonValue::Bool(_) => "boolean",
+        JsonValue::Number(_) => "number",
+        JsonValue::String(_) => "string",
+        JsonValue::Array(_) => "array",
+        JsonValue::Object(_) => "object",
+    }
+}
+
+fn class_name(node: &Node) -> Option<&'static str> {
+    match node.kind {
+        NodeKind::Slice(_) if node.lazy => Some("lazy"),
+        NodeKind::Slice(SliceOwner::Plugin) => Some("plugin"),
+        NodeKind::Slice(SliceOwner::Reducer) => Some("reducer"),
+        NodeKind::Effect => Some("effect"),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn graph() -> StateGraph {
+        let state = json!({
+            "todos": [{ "title": "a", "done": false }],
+            "flags": {},
+            "cache": null,
+        });
+        let owner = |key: &str| match key {
+            "flags" => SliceOwner::Plugin,
+            _ => SliceOwner::App,
+        };
+        let mut graph = StateGraph::new(&state, owner, |key| key == "cache");
+        graph.add_effect("workflow", "sync \"todos\"", Some("todos"));
+        graph
+    }
+
+    #[test]
+    fn renders_mermaid_flowcharts() {
+        let mermaid = graph().render(GraphFormat::Mermaid);
+        assert!(mermaid.starts_with("flowchart LR\n"));
+        assert!(mermaid.contains("n1[\"cache: null\"]\n  class n1 lazy"));
+        assert!(mermaid.contains("n2[\"flags: object\"]\n  class n2 plugin"));
+        assert!(mermaid.contains("n3[\"todos[]: object\"]"));
+        assert!(mermaid.contains("n4[\"done: boolean\"]"));
+        assert!(mermaid.contains("n6([\"workflow: sync #quot;todos#quot;\"])"));
+        assert!(mermaid.contains("n6 -.-> n3"));
+    }
+
+    #[test]
+    fn renders_graphviz_digraphs() {
+        let dot = graph().render(GraphFormat::Graphviz);
+        assert!(dot.starts_with("digraph state {"));
+        assert!(dot.contains("n1 [label=\"cache: null\", peripheries=2];"));
+        assert!(dot.contains("label=\"workflow: sync \\\"todos\\\"\", shape=ellipse"));
+        assert!(dot.contains("n0 -> n3;"));
+        assert!(dot.contains("n6 -> n3 [style=dashed];"));
+    }
+
+    #[test]
+    fn summarizes_wide_objects() {
+        let wide: serde_json::Map<_, _> = (0..MAX_KEYS + 3).map(|i| (format!("k{:02}", i), json!(i))).collect();
+        let graph = StateGraph::new(&json!({ "wide": wide }), |_| SliceOwner::App, |_| false);
+        assert!(graph.render(GraphFormat::Mermaid).contains("[\"3 more\"]"));
+    }
+}
@@ -0,0 +1,70 @@
+use std::sync::{Condvar, Mutex};
+use std::time::Instant;
+
+/// Wakes threads in `Zubridge::wait_for` whenever a state is published.
+#[derive(Default)]
+pub(crate) struct StateWaiters {
+    /// Incremented for every published state, including ones that failed to emit.
+    published: Mutex<u64>,
+    changed: Condvar,
+}
+
+impl StateWaiters {
+    pub(crate) fn published(&self) -> crate::Result<u64> {
+        let published = self.published.lock().map_err(|e| crate::Error::StateError(e.to_string()))?;
+        Ok(*published)
+    }
+
+    pub(crate) fn notify(&self) {
+        if let Ok(mut published) = self.published.lock() {
+            *published += 1;
+        }
+        self.changed.notify_all();
+    }
+
+    /// Wait until a state is published after `seen`, returning false if `deadline` passes first.
+    pub(crate) fn wait_past(&self, seen: u64, deadline: Instant) -> crate::Result<bool> {
+        let mut published = self.published.lock().map_err(|e| crate::Error::StateError(e.to_string()))?;
+        while *published == seen {
+            let Some(remaining) = deadline.checked_duration_since(Instant::now()) else {
+                return Ok(false);
+            };
+            published = self
+                .changed
+                .wait_timeout(published, remaining)
+                .map_err(|e| crate::Error::StateError(e.to_string()))?
+                .0;
+        }
+        Ok(true)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Arc;
+    use std::time::Duration;
+
+    #[test]
+    fn wakes_on_the_next_publish() {
+        let waiters = Arc::new(StateWaiters::default());
+        let seen = waiters.published().unwrap();
+        let publisher = waiters.clone();
+        let thread = std::thread::spawn(move || {
+            std::thread::sleep(Duration::from_millis(10));
+            publisher.notify();
+        });
+        assert!(waiters.wait_past(seen, Instant::now() + Duration::from_secs(5)).unwrap());
+        assert_eq!(waiters.published().unwrap(), seen + 1);
+        thread.join().unwrap();
+    }
+
+    #[test]
+    fn times_out_without_a_publish() {
+        let waiters = StateWaiters::default();
+        assert!(!waiters.wait_past(0, Instant::now() + Duration::from_millis(10)).unwrap());
+        // Already published past what was seen
+        waiters.notify();
+        assert!(waiters.wait_past(0, Instant::now()).unwrap());
+    }
+}
@@ -0,0 +1,273 @@
+use std::collections::BTreeMap;
+use std::sync::{Arc, Mutex};
+
+use serde::Serialize;
+
+use crate::models::*;
+
+pub(crate) const SEND_ACTION: &str = "CHART:SEND";
+pub(crate) const TRANSITIONED_ACTION: &str = "CHART:TRANSITIONED";
+
+/// Decides from the app state and the event payload whether a transition may be taken.
+pub type TransitionGuard = Arc<dyn Fn(&JsonValue, &JsonValue) -> bool + Send + Sync>;
+
+/// Runs when a state is entered or exited, given the event payload. The returned action is
+/// dispatched after the transition.
+pub type StateEffect = Arc<dyn Fn(&JsonValue) -> Option<ZubridgeAction> + Send + Sync>;
+
+#[derive(Clone)]
+struct Transition {
+    from: String,
+    event: String,
+    to: String,
+    guard: Option<TransitionGuard>,
+}
+
+/// A finite state machine kept in the `statecharts_key` slice. Events are sent with
+/// `CHART:SEND { chart, event, payload }`, and events without a transition from the current
+/// state fail with [`crate::Error::IllegalTransition`].
+#[derive(Clone)]
+pub struct Statechart {
+    pub name: String,
+    pub initial: String,
+    transitions: Vec<Transition>,
+    entry: Vec<(String, StateEffect)>,
+    exit: Vec<(String, StateEffect)>,
+}
+
+impl Statechart {
+    pub fn new(name: impl Into<String>, initial: impl Into<String>) -> Self {
+        Self {
+            name: name.into(),
+            initial: initial.into(),
+            transitions: Vec::new(),
+            entry: Vec::new(),
+            exit: Vec::new(),
+        }
+    }
+
+    /// Move from `from` to `to` on `event`. `*` as `from` matches any state.
+    pub fn transition(self, from: &str, event: &str, to: &str) -> Self {
+        self.add_transition(from, event, to, None)
+    }
+
+    /// Like [`Statechart::transition`], but only while `guard` holds. Guards are checked in the
+    /// order transitions were added, and the first transition whose guard holds is taken.
+    pub fn transition_if<F>(self, from: &str, event: &str, to: &str, guard: F) -> Self
+    where
+        F: Fn(&JsonValue, &JsonValue) -> bool + Send + Sync + 'static,
+    {
+        self.add_transition(from, event, to, Some(Arc::new(guard)))
+    }
+
+    fn add_transition(mut self, from: &str, event: &str, to: &str, guard: Option<TransitionGuard>) -> Self {
+        self.transitions.push(Transition {
+            from: from.to_string(),
+            event: event.to_string(),
+            to: to.to_string(),
+            guard,
+        });
+        self
+    }
+
+    pub fn on_entry<F>(mut self, state: &str, effect: F) -> Self
+    where
+        F: Fn(&JsonValue) -> Option<ZubridgeAction> + Send + Sync + 'static,
+    {
+        self.entry.push((state.to_string(), Arc::new(effect)));
+        self
+    }
+
+    pub fn on_exit<F>(mut self, state: &str, effect: F) -> Self
+    where
+        F: Fn(&JsonValue) -> Option<ZubridgeAction> + Send + Sync + 'static,
+    {
+        self.exit.push((state.to_string(), Arc::new(effect)));
+        self
+    }
+
+    fn has_guards(&self, from: &str, event: &str) -> bool {
+        self.candidates(from, event).any(|transition| transition.guard.is_some())
+    }
+
+    fn candidates<'a>(&'a self, from: &'a str, event: &'a str) -> impl Iterator<Item = &'a Transition> {
+        self.transitions
+            .iter()
+            .filter(move |transition| transition.event == event && (transition.from == from || transition.from == "*"))
+    }
+
+    fn effects(effects: &[(String, StateEffect)], state: &str, payload: &JsonValue) -> Vec<ZubridgeAction> {
+        effects
+            .iter()
+            .filter(|(name, _)| name == state)
+            .filter_map(|(_, effect)| effect(payload))
+            .collect()
+    }
+}
+
+#[derive(Clone, Debug, Serialize)]
+struct ChartState {
+    state: String,
+    previous: Option<String>,
+}
+
+/// The current state of every statechart, keyed by name.
+pub(crate) struct Statecharts {
+    charts: Vec<Statechart>,
+    states: Mutex<BTreeMap<String, ChartState>>,
+}
+
+impl Statecharts {
+    pub(crate) fn new(charts: Vec<Statechart>) -> Self {
+        let states = charts
+            .iter()
+            .map(|chart| {
+                let state = ChartState {
+                    state: chart.initial.clone(),
+                    previous: None,
+                };
+                (chart.name.clone(), state)
+            })
+            .collect();
+        Self {
+            charts,
+            states: Mutex::new(states),
+        }
+    }
+
+    pub(crate) fn is_empty(&self) -> bool {
+        self.charts.is_empty()
+    }
+
+    pub(crate) fn current(&self, chart: &str) -> Option<String> {
+        let states = self.states.lock().ok()?;
+        states.get(chart).map(|state| state.state.clone())
+    }
+
+    /// Take the transition for a `CHART:SEND` action. Returns the actions to pass on to the state
+    /// manager: the exit effects, `CHART:TRANSITIONED { chart, from, to, event, payload }`, then
+    /// the entry effects. `app_state` is only called if a guard needs it.
+    pub(crate) fn send(
+        &self,
+        action: &ZubridgeAction,
+        app_state: impl FnOnce() -> crate::Result<JsonValue>,
+    ) -> crate::Result<Vec<ZubridgeAction>> {
+        let field = |name: &str| action.payload.as_ref().and_then(|payload| payload.get(name));
+        let name = field("chart")
+            .and_then(JsonValue::as_str)
+            .ok_or_else(|| crate::Error::StateError(format!("{} requires a chart", SEND_ACTION)))?;
+        let event = field("event")
+            .and_then(JsonValue::as_str)
+            .ok_or_else(|| crate::Error::StateError(format!("{} requires an event", SEND_ACTION)))?;
+        let payload = field("payload").cloned().unwrap_or(JsonValue::Null);
+        let chart = self
+            .charts
+            .iter()
+            .find(|chart| chart.name == name)
+            .ok_or_else(|| crate::Error::StateError(format!("no statechart named {}", name)))?;
+
+        let from = self.current(name).unwrap_or_else(|| chart.initial.clone());
+        let app_state = if chart.has_guards(&from, event) { app_state()? } else { JsonValue::Null };
+        let illegal = || crate::Error::IllegalTransition {
+            chart: name.to_string(),
+            state: from.clone(),
+            event: event.to_string(),
+        };
+        let to = chart
+            .candidates(&from, event)
+            .find(|transition| transition.guard.as_ref().map_or(true, |guard| guard(&app_state, &payload)))
+            .map(|transition| transition.to.clone())
+            .ok_or_else(illegal)?;
+
+        {
+            let mut states = self.states.lock().map_err(|e| crate::Error::StateError(e.to_string()))?;
+            // Another event may have moved the chart while the guards ran
+            let state = states.get_mut(name).ok_or_else(illegal)?;
+            if state.state != from {
+                return Err(illegal());
+            }
+            state.previous = Some(std::mem::replace(&mut state.state, to.clone()));
+        }
+
+        let mut actions = Statechart::effects(&chart.exit, &from, &payload);
+        actions.push(ZubridgeAction {
+            action_type: TRANSITIONED_ACTION.to_string(),
+            payload: Some(serde_json::json!({ "chart": name, "from": from, "to": to, "event": event, "payload": payload })),
+        });
+        actions.extend(Statechart::effects(&chart.entry, &to, &payload));
+        Ok(actions)
+    }
+
+    pub(crate) fn slice(&self) -> crate::Result<JsonValue> {
+        let states = self.states.lock().map_err(|e| crate::Error::StateError(e.to_string()))?;
+        serde_json::to_value(&*states).map_err(|e| crate::Error::SerializationError(e.to_string()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn player() -> Statechart {
+        Statechart::new("player", "idle")
+            .transition("idle", "PLAY", "playing")
+            .transition_if("playing", "STOP", "idle", |state, _| state["canStop"] == true)
+            .transition("*", "RESET", "idle")
+            .on_exit("idle", |_| Some(action("PLAYER:WARM_UP")))
+            .on_entry("playing", |payload| {
+                Some(ZubridgeAction {
+                    action_type: "PLAYER:START".to_string(),
+                    payload: Some(payload.clone()),
+                })
+            })
+    }
+
+    fn action(action_type: &str) -> ZubridgeAction {
+        ZubridgeAction {
+            action_type: action_type.to_string(),
+            payload: None,
+        }
+    }
+
+    fn send(event: &str) -> ZubridgeAction {
+        ZubridgeAction {
+            action_type: SEND_ACTION.to_string(),
+            payload: Some(json!({ "chart": "player", "event": event, "payload": { "track": 1 } })),
+        }
+    }
+
+    #[test]
+    fn runs_exit_and_entry_effects_around_the_transition() {
+        let charts = Statecharts::new(vec![player()]);
+        let actions = charts.send(&send("PLAY"), || unreachable!("no guards")).unwrap();
+        let types: Vec<&str> = actions.iter().map(|action| action.action_type.as_str()).collect();
+        assert_eq!(types, ["PLAYER:WARM_UP", TRANSITIONED_ACTION, "PLAYER:START"]);
+        assert_eq!(actions[2].payload, Some(json!({ "track": 1 })));
+        assert_eq!(charts.slice().unwrap()["player"], json!({ "state": "playing", "previous": "idle" }));
+    }
+
+    #[test]
+    fn rejects_events_without_a_transition() {
+        let charts = Statecharts::new(vec![player()]);
+        let result = charts.send(&send("STOP"), || Ok(json!({ "canStop": true })));
+        assert!(matches!(result, Err(crate::Error::IllegalTransition { state, .. }) if state == "idle"));
+    }
+
+    #[test]
+    fn checks_guards_against_the_app_state() {
+        let charts = Statecharts::new(vec![player()]);
+        charts.send(&send("PLAY"), || Ok(JsonValue::Null)).unwrap();
+        assert!(charts.send(&send("STOP"), || Ok(json!({ "canStop": false }))).is_err());
+        charts.send(&send("STOP"), || Ok(json!({ "canStop": true }))).unwrap();
+        assert_eq!(charts.current("player").as_deref(), Some("idle"));
+    }
+
+    #[test]
+    fn matches_any_state_with_a_wildcard() {
+        let charts = Statecharts::new(vec![player()]);
+        charts.send(&send("PLAY"), || Ok(JsonValue::Null)).unwrap();
+        charts.send(&send("RESET"), || Ok(JsonValue::Null)).unwrap();
+        assert_eq!(charts.current("player").as_deref(), Some("idle"));
+    }
+}
@@ -0,0 +1,185 @@
+use std::collections::HashMap;
+use std::sync::Mutex;
+
+use serde::Serialize;
+use tauri::ipc::Channel;
+
+use crate::models::*;
+
+/// A state update sent to a window subscribed with `zubridge.subscribe-state`.
+#[derive(Clone, Debug, Serialize)]
+pub struct StateUpdate {
+    pub revision: u64,
+    /// The state, or a [`crate::SharedMemoryDescriptor`] with the shared memory transport.
+    pub state: JsonValue,
+    /// How many updates were superseded by this one while the window was catching up.
+    pub skipped: u64,
+}
+
+struct Subscriber {
+    channel: Channel<StateUpdate>,
+    /// Updates sent and not acknowledged yet.
+    in_flight: usize,
+    /// The latest update held back while `in_flight` is at the limit.
+    pending: Option<StateUpdate>,
+    skipped: u64,
+}
+
+/// Windows receiving state updates through their own channel instead of the global event.
+///
+/// Every update carries the full state, so a window that falls behind only needs the latest
+/// one: at most `max_in_flight` updates are sent before the window acknowledges one, and the
+/// newest of the updates published in the meantime replaces the others.
+pub(crate) struct Subscriptions {
+    max_in_flight: usize,
+    subscribers: Mutex<HashMap<String, Subscriber>>,
+}
+
+impl Subscriptions {
+    pub(crate) fn new(max_in_flight: usize) -> Self {
+        Self {
+            max_in_flight: max_in_flight.max(1),
+            subscribers: Mutex::new(HashMap::new()),
+        }
+    }
+
+    pub(crate) fn is_empty(&self) -> bool {
+        self.subscribers.lock().map(|subscribers| subscribers.is_empty()).unwrap_or(true)
+    }
+
+    pub(crate) fn is_subscribed(&self, window: &str) -> bool {
+        self.subscribers
+            .lock()
+            .map(|subscribers| subscribers.contains_key(window))
+            .unwrap_or(false)
+    }
+
+    /// Send updates to `window` through `channel`, replacing its previous subscription.
+    pub(crate) fn subscribe(&self, window: &str, channel: Channel<StateUpdate>) -> crate::Result<()> {
+        let mut subscribers = self.subscribers.lock().map_err(|e| crate::Error::StateError(e.to_string()))?;
+        subscribers.insert(
+            window.to_string(),
+            Subscriber {
+                channel,
+                in_flight: 0,
+                pending: None,
+                skipped: 0,
+            },
+        );
+        Ok(())
+    }
+
+    pub(crate) fn unsubscribe(&self, window: &str) -> crate::Result<()> {
+        let mut subscribers = self.subscribers.lock().map_err(|e| crate::Error::StateError(e.to_string()))?;
+        subscribers.remove(window);
+        Ok(())
+    }
+
+    /// Send an update to every subscriber with room for it, and hold it back for the others.
+    pub(crate) fn publish(&self, state: &JsonValue, revision: u64) -> crate::Result<()> {
+        let mut subscribers = self.subscribers.lock().map_err(|e| crate::Error::StateError(e.to_string()))?;
+        subscribers.retain(|window, subscriber| {
+            if subscriber.pending.replace(StateUpdate { revision, state: state.clone(), skipped: 0 }).is_some() {
+                subscriber.skipped += 1;
+            }
+            if subscriber.in_flight >= self.max_in_flight {
+                return true;
+            }
+            send_pending(window, subscriber)
+        });
+        Ok(())
+    }
+
+    /// Send an update to `window` alone, like [`Subscriptions::publish`]. Returns false if the
+    /// window isn't subscribed.
+    pub(crate) fn publish_to(&self, window: &str, state: &JsonValue, revision: u64) -> crate::Result<bool> {
+        let mut subscribers = self.subscribers.lock().map_err(|e| crate::Error::StateError(e.to_string()))?;
+        let Some(subscriber) = subscribers.get_mut(window) else { return Ok(false) };
+        if subscriber.pending.replace(StateUpdate { revision, state: state.clone(), skipped: 0 }).is_some() {
+            subscriber.skipped += 1;
+        }
+        if subscriber.in_flight < self.max_in_flight && !send_pending(window, subscriber) {
+            subscribers.remove(window);
+        }
+        Ok(true)
+    }
+
+    /// Mark an update as handled by `window`, and send it the update held back, if any.
+    pub(crate) fn acknowledge(&self, window: &str) -> crate::Result<()> {
+        let mut subscribers = self.subscribers.lock().map_err(|e| crate::Error::StateError(e.to_string()))?;
+        let Some(subscriber) = subscribers.get_mut(window) else { return Ok(()) };
+        subscriber.in_flight = subscriber.in_flight.saturating_sub(1);
+        if !send_pending(window, subscriber) {
+            subscribers.remove(window);
+        }
+        Ok(())
+    }
+}
+
+/// Send the held back update, if any. Returns false when the channel is gone.
+fn send_pending(window: &str, subscriber: &mut Subscriber) -> bool {
+    let Some(mut update) = subscriber.pending.take() else { return true };
+    update.skipped = std::mem::take(&mut subscriber.skipped);
+    match subscriber.channel.send(update) {
+        Ok(()) => {
+            subscriber.in_flight += 1;
+            true
+        }
+        Err(e) => {
+            log::debug!("zubridge: dropping the state subscription of {}: {}", window, e);
+            false
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Arc;
+    use tauri::ipc::InvokeResponseBody;
+
+    /// The `(revision, skipped)` of every update sent through a channel.
+    type Sent = Arc<Mutex<Vec<(u64, u64)>>>;
+
+    fn recording() -> (Channel<StateUpdate>, Sent) {
+        let sent = Arc::new(Mutex::new(Vec::new()));
+        let recorded = sent.clone();
+        let channel = Channel::new(move |body| {
+            let InvokeResponseBody::Json(json) = body else { unreachable!() };
+            let update: JsonValue = serde_json::from_str(&json).unwrap();
+            let field = |name: &str| update[name].as_u64().unwrap();
+            recorded.lock().unwrap().push((field("revision"), field("skipped")));
+            Ok(())
+        });
+        (channel, sent)
+    }
+
+    #[test]
+    fn holds_back_the_latest_update_until_acknowledged() {
+        let subscriptions = Subscriptions::new(1);
+        let (channel, sent) = recording();
+        subscriptions.subscribe("main", channel).unwrap();
+        for revision in 1..=3 {
+            subscriptions.publish(&JsonValue::Null, revision).unwrap();
+        }
+        assert_eq!(*sent.lock().unwrap(), [(1, 0)]);
+
+        subscriptions.acknowledge("main").unwrap();
+        assert_eq!(*sent.lock().unwrap(), [(1, 0), (3, 1)]);
+        subscriptions.acknowledge("main").unwrap();
+        assert!(subscriptions.publish_to("main", &JsonValue::Null, 4).unwrap());
+        assert!(!subscriptions.publish_to("other", &JsonValue::Null, 4).unwrap());
+        assert_eq!(sent.lock().unwrap().last(), Some(&(4, 0)));
+    }
+
+    #[test]
+    fn drops_subscribers_whose_channel_is_gone() {
+        let subscriptions = Subscriptions::new(4);
+        let closed = Channel::new(|_| Err(tauri::Error::FailedToReceiveMessage));
+        subscriptions.subscribe("main", closed).unwrap();
+        assert!(subscriptions.is_subscribed("main"));
+        subscriptions.publish(&JsonValue::Null, 1).unwrap();
+        assert!(!subscriptions.is_subscribed("main"));
+        assert!(subscriptions.is_empty());
+    }
+}
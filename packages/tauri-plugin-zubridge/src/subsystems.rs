@@ -0,0 +1,186 @@
+use std::sync::mpsc;
+use std::sync::{Arc, Condvar, Mutex};
+use std::thread::JoinHandle;
+use std::time::Duration;
+
+use serde::Serialize;
+
+/// Tells background loops to stop. Loops sleep with [`ShutdownSignal::sleep`] so they wake up
+/// as soon as shutdown starts.
+#[derive(Clone, Default)]
+pub(crate) struct ShutdownSignal {
+    stopped: Arc<(Mutex<bool>, Condvar)>,
+}
+
+impl ShutdownSignal {
+    /// Sleep for `duration`, returning true early if shutdown started.
+    pub(crate) fn sleep(&self, duration: Duration) -> bool {
+        let (stopped, condvar) = &*self.stopped;
+        let Ok(guard) = stopped.lock() else { return true };
+        match condvar.wait_timeout_while(guard, duration, |stopped| !*stopped) {
+            Ok((guard, _)) => *guard,
+            Err(_) => true,
+        }
+    }
+
+    fn trigger(&self) {
+        let (stopped, condvar) = &*self.stopped;
+        if let Ok(mut guard) = stopped.lock() {
+            *guard = true;
+        }
+        condvar.notify_all();
+    }
+}
+
+/// The state of a subsystem, as reported by `zubridge.health`.
+#[derive(Clone, Debug, Serialize)]
+pub struct SubsystemStatus {
+    pub name: String,
+    /// "running" or "stopped" for the plugin's background threads, "registered" for
+    /// subsystems registered by the app.
+    pub status: &'static str,
+}
+
+struct Subsystem {
+    name: String,
+    thread: Option<JoinHandle<()>>,
+    on_shutdown: Option<Box<dyn FnOnce() + Send>>,
+}
+
+/// The background work of the plugin and the app, such as schedulers, pollers, watchers and
+/// persistence writers. On exit, subsystems are shut down in the reverse order they were
+/// registered in, within the `shutdown_timeout` option.
+#[derive(Default)]
+pub struct Subsystems {
+    signal: ShutdownSignal,
+    entries: Arc<Mutex<Vec<Subsystem>>>,
+}
+
+impl Subsystems {
+    /// Run a named background thread. The thread should return once
+    /// [`ShutdownSignal::sleep`] returns true.
+    pub(crate) fn spawn(&self, name: &str, run: impl FnOnce(ShutdownSignal) + Send + 'static) {
+        let signal = self.signal.clone();
+        let thread = std::thread::Builder::new()
+            .name(format!("zubridge-{}", name))
+            .spawn(move || run(signal));
+        match thread {
+            Ok(thread) => self.push(Subsystem {
+                name: name.to_string(),
+                thread: Some(thread),
+                on_shutdown: None,
+            }),
+            Err(e) => log::warn!("zubridge: failed to start {}: {}", name, e),
+        }
+    }
+
+    /// Register a subsystem of the app, e.g. a devtools server, so `shutdown` runs on exit
+    /// after the subsystems registered later have stopped.
+    pub fn register(&self, name: impl Into<String>, shutdown: impl FnOnce() + Send + 'static) {
+        self.push(Subsystem {
+            name: name.into(),
+            thread: None,
+            on_shutdown: Some(Box::new(shutdown)),
+        });
+    }
+
+    /// The subsystems not shut down yet, oldest first. A background thread that stopped on its
+    /// own, e.g. after a panic, shows up as "stopped".
+    pub fn statuses(&self) -> Vec<SubsystemStatus> {
+        let Ok(entries) = self.entries.lock() else { return Vec::new() };
+        entries
+            .iter()
+            .map(|subsystem| SubsystemStatus {
+                name: subsystem.name.clone(),
+                status: match &subsystem.thread {
+                    Some(thread) if thread.is_finished() => "stopped",
+                    Some(_) => "running",
+                    None => "registered",
+                },
+            })
+            .collect()
+    }
+
+    fn push(&self, subsystem: Subsystem) {
+        if let Ok(mut entries) = self.entries.lock() {
+            entries.push(subsystem);
+        }
+    }
+
+    /// Stop every subsystem, newest first, waiting at most `timeout`. Subsystems still running
+    /// after the timeout are abandoned, so they can't block exit. Calling this again does nothing.
+    pub(crate) fn shutdown(&self, timeout: Duration) {
+        self.signal.trigger();
+        let entries = match self.entries.lock() {
+            Ok(mut entries) => std::mem::take(&mut *entries),
+            Err(_) => return,
+        };
+        if entries.is_empty() {
+            return;
+        }
+
+        let current = Arc::new(Mutex::new(String::new()));
+        let (done, finished) = mpsc::channel();
+        let stopping = current.clone();
+        std::thread::spawn(move || {
+            for subsystem in entries.into_iter().rev() {
+                if let Ok(mut current) = stopping.lock() {
+                    current.clone_from(&subsystem.name);
+                }
+                if let Some(on_shutdown) = subsystem.on_shutdown {
+                    on_shutdown();
+                }
+                if let Some(thread) = subsystem.thread {
+                    if thread.join().is_err() {
+                        log::warn!("zubridge: {} panicked while shutting down", subsystem.name);
+                    }
+                }
+            }
+            let _ = done.send(());
+        });
+
+        if finished.recv_timeout(timeout).is_err() {
+            let name = current.lock().map(|name| name.clone()).unwrap_or_default();
+            log::warn!("zubridge: shutdown timed out after {:?} waiting for {}", timeout, name);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Instant;
+
+    #[test]
+    fn shuts_down_newest_first() {
+        let subsystems = Subsystems::default();
+        let order = Arc::new(Mutex::new(Vec::new()));
+        for name in ["first", "second"] {
+            let order = order.clone();
+            subsystems.register(name, move || order.lock().unwrap().push(name));
+        }
+        let stopped = order.clone();
+        subsystems.spawn("loop", move |signal| {
+            while !signal.sleep(Duration::from_secs(60)) {}
+            stopped.lock().unwrap().push("loop");
+        });
+        let statuses: Vec<_> = subsystems.statuses().into_iter().map(|s| (s.name, s.status)).collect();
+        assert_eq!(
+            statuses,
+            [("first".into(), "registered"), ("second".into(), "registered"), ("loop".into(), "running")]
+        );
+
+        subsystems.shutdown(Duration::from_secs(5));
+        assert_eq!(*order.lock().unwrap(), ["loop", "second", "first"]);
+        assert!(subsystems.statuses().is_empty());
+    }
+
+    #[test]
+    fn abandons_subsystems_past_the_timeout() {
+        let subsystems = Subsystems::default();
+        subsystems.register("stuck", || std::thread::sleep(Duration::from_secs(60)));
+        let started = Instant::now();
+        subsystems.shutdown(Duration::from_millis(20));
+        assert!(started.elapsed() < Duration::from_secs(5));
+    }
+}
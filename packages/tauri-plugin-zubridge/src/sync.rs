@@ -0,0 +1,181 @@
+use std::collections::BTreeMap;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use serde::Serialize;
+
+use crate::models::*;
+use crate::utils::now_millis;
+
+/// Runs one sync with a snapshot of the state, dispatching whatever it fetched.
+/// Returning an error retries the task with backoff.
+pub type SyncFn = Arc<dyn Fn(&JsonValue, &Dispatcher) -> Result<(), String> + Send + Sync>;
+
+/// A task run on an interval by the sync scheduler.
+#[derive(Clone)]
+pub struct SyncTask {
+    /// The key the task's status is stored under in the sync slice.
+    pub name: String,
+    /// How long to wait after a successful run.
+    pub interval: Duration,
+    /// The longest wait between retries after failures. Defaults to 15 minutes.
+    pub max_backoff: Duration,
+    run: SyncFn,
+}
+
+impl SyncTask {
+    pub fn new<F>(name: impl Into<String>, interval: Duration, run: F) -> Self
+    where
+        F: Fn(&JsonValue, &Dispatcher) -> Result<(), String> + Send + Sync + 'static,
+    {
+        Self {
+            name: name.into(),
+            interval,
+            max_backoff: Duration::from_secs(900),
+            run: Arc::new(run),
+        }
+    }
+
+    pub fn max_backoff(mut self, max_backoff: Duration) -> Self {
+        self.max_backoff = max_backoff;
+        self
+    }
+
+    /// Run the task, turning a panic into a failure.
+    pub(crate) fn run(&self, state: &JsonValue, dispatcher: &Dispatcher) -> Result<(), String> {
+        std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| (self.run)(state, dispatcher)))
+            .unwrap_or_else(|_| Err(format!("sync task {} panicked", self.name)))
+    }
+
+    /// How long to wait before the next run. After failures the interval doubles up to
+    /// `max_backoff`, with up to half of it taken off at random so clients don't retry in lockstep.
+    pub(crate) fn next_delay(&self, failures: u32) -> Duration {
+        if failures == 0 {
+            return self.interval;
+        }
+        let backoff = self
+            .interval
+            .saturating_mul(2u32.saturating_pow(failures.min(16)))
+            .min(self.max_backoff.max(self.interval));
+        let jitter = (uuid::Uuid::new_v4().as_u128() % 1000) as u32;
+        backoff / 2 + (backoff / 2).saturating_mul(jitter) / 1000
+    }
+}
+
+/// Background sync tasks, with their status exposed in the `state_key` slice.
+#[derive(Clone)]
+pub struct SyncConfig {
+    pub tasks: Vec<SyncTask>,
+    /// The state key task statuses are stored under. Defaults to "sync".
+    pub state_key: String,
+}
+
+impl Default for SyncConfig {
+    fn default() -> Self {
+        Self {
+            tasks: Vec::new(),
+            state_key: "sync".to_string(),
+        }
+    }
+}
+
+#[derive(Clone, Debug, Default, Serialize)]
+struct SyncStatus {
+    running: bool,
+    /// When the task last succeeded, in milliseconds since the Unix epoch.
+    last_success: Option<u64>,
+    last_error: Option<String>,
+    /// Consecutive failures.
+    failures: u32,
+    /// When the task runs next, in milliseconds since the Unix epoch.
+    next_run: Option<u64>,
+}
+
+/// The status of every sync task, keyed by name.
+#[derive(Default)]
+pub(crate) struct SyncStatuses {
+    tasks: Mutex<BTreeMap<String, SyncStatus>>,
+}
+
+impl SyncStatuses {
+    pub(crate) fn started(&self, name: &str) -> crate::Result<()> {
+        let mut tasks = self.tasks.lock().map_err(|e| crate::Error::StateError(e.to_string()))?;
+        let status = tasks.entry(name.to_string()).or_default();
+        status.running = true;
+        status.next_run = None;
+        Ok(())
+    }
+
+    /// Record the result of a run and return the number of consecutive failures.
+    pub(crate) fn finished(&self, name: &str, result: Result<(), String>) -> crate::Result<u32> {
+        let mut tasks = self.tasks.lock().map_err(|e| crate::Error::StateError(e.to_string()))?;
+        let status = tasks.entry(name.to_string()).or_default();
+        status.running = false;
+        match result {
+            Ok(()) => {
+                status.last_success = Some(now_millis());
+                status.last_error = None;
+                status.failures = 0;
+            }
+            Err(error) => {
+                status.last_error = Some(error);
+                status.failures += 1;
+            }
+        }
+        Ok(status.failures)
+    }
+
+    pub(crate) fn scheduled(&self, name: &str, delay: Duration) -> crate::Result<()> {
+        let mut tasks = self.tasks.lock().map_err(|e| crate::Error::StateError(e.to_string()))?;
+        tasks.entry(name.to_string()).or_default().next_run = Some(now_millis() + delay.as_millis() as u64);
+        Ok(())
+    }
+
+    pub(crate) fn slice(&self) -> crate::Result<JsonValue> {
+        let tasks = self.tasks.lock().map_err(|e| crate::Error::StateError(e.to_string()))?;
+        serde_json::to_value(&*tasks).map_err(|e| crate::Error::SerializationError(e.to_string()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn task(interval: Duration) -> SyncTask {
+        SyncTask::new("inbox", interval, |_, _| Ok(())).max_backoff(Duration::from_secs(60))
+    }
+
+    #[test]
+    fn backs_off_with_jitter_after_failures() {
+        let task = task(Duration::from_secs(10));
+        assert_eq!(task.next_delay(0), Duration::from_secs(10));
+        for (failures, backoff) in [(1, 20), (2, 40), (3, 60), (30, 60)] {
+            let delay = task.next_delay(failures);
+            let backoff = Duration::from_secs(backoff);
+            assert!(delay >= backoff / 2 && delay <= backoff, "{:?} after {} failures", delay, failures);
+        }
+    }
+
+    #[test]
+    fn turns_panics_into_failures() {
+        let task = SyncTask::new("inbox", Duration::from_secs(1), |_, _| panic!("offline"));
+        let dispatcher = Dispatcher::new(|_| Ok(JsonValue::Null));
+        assert_eq!(task.run(&JsonValue::Null, &dispatcher), Err("sync task inbox panicked".to_string()));
+    }
+
+    #[test]
+    fn tracks_consecutive_failures() {
+        let statuses = SyncStatuses::default();
+        statuses.started("inbox").unwrap();
+        assert_eq!(statuses.slice().unwrap()["inbox"]["running"], true);
+        assert_eq!(statuses.finished("inbox", Err("offline".into())).unwrap(), 1);
+        assert_eq!(statuses.finished("inbox", Err("offline".into())).unwrap(), 2);
+        statuses.scheduled("inbox", Duration::from_secs(1)).unwrap();
+
+        let slice = statuses.slice().unwrap();
+        assert_eq!(slice["inbox"]["last_error"], "offline");
+        assert!(slice["inbox"]["next_run"].is_u64());
+        assert_eq!(statuses.finished("inbox", Ok(())).unwrap(), 0);
+        assert_eq!(statuses.slice().unwrap()["inbox"]["last_error"], JsonValue::Null);
+    }
+}
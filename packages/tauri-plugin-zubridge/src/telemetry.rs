@@ -0,0 +1,268 @@
+use std::collections::{HashMap, VecDeque};
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use serde::{Deserialize, Serialize};
+
+use crate::models::*;
+use crate::persistence::PersistenceWriter;
+use crate::subsystems::Subsystems;
+use crate::utils::now_millis;
+
+const QUEUE_FILE: &str = "telemetry-queue.json";
+/// How often the `telemetry` thread checks for a full batch.
+const BATCH_CHECK_INTERVAL: Duration = Duration::from_secs(1);
+
+/// An analytics event derived from a dispatched action.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct TelemetryEvent {
+    pub name: String,
+    /// The action payload, for mappings that opt in to including it.
+    pub properties: Option<JsonValue>,
+    pub install_id: String,
+    /// Milliseconds since the Unix epoch.
+    pub timestamp: u64,
+}
+
+/// Receives batches of telemetry events. Implement this to forward events to your analytics backend.
+pub trait TelemetrySink: Send + Sync + 'static {
+    /// Send a batch of events. Returning an error keeps the batch queued for the next flush.
+    fn send(&self, events: &[TelemetryEvent]) -> std::result::Result<(), String>;
+}
+
+#[derive(Clone, Debug)]
+struct TelemetryMapping {
+    event_name: String,
+    include_payload: bool,
+}
+
+/// Configures which actions are turned into telemetry events and how they're delivered.
+#[derive(Clone)]
+pub struct TelemetryConfig {
+    sink: Arc<dyn TelemetrySink>,
+    mappings: HashMap<String, TelemetryMapping>,
+    /// Flush once this many events are queued, within a second. Defaults to 20.
+    pub batch_size: usize,
+    /// Flush queued events at least this often. Defaults to 30 seconds.
+    pub flush_interval: Duration,
+    /// The maximum number of undelivered events kept while offline; the oldest are dropped first. Defaults to 1000.
+    pub max_queue: usize,
+}
+
+impl TelemetryConfig {
+    pub fn new<S: TelemetrySink>(sink: S) -> Self {
+        Self {
+            sink: Arc::new(sink),
+            mappings: HashMap::new(),
+            batch_size: 20,
+            flush_interval: Duration::from_secs(30),
+            max_queue: 1000,
+        }
+    }
+
+    /// Record `event_name` whenever `action_type` is dispatched.
+    pub fn track(mut self, action_type: impl Into<String>, event_name: impl Into<String>) -> Self {
+        self.mappings.insert(
+            action_type.into(),
+            TelemetryMapping {
+                event_name: event_name.into(),
+                include_payload: false,
+            },
+        );
+        self
+    }
+
+    /// Like [`TelemetryConfig::track`], but includes the action payload as the event properties.
+    pub fn track_with_payload(mut self, action_type: impl Into<String>, event_name: impl Into<String>) -> Self {
+        self.mappings.insert(
+            action_type.into(),
+            TelemetryMapping {
+                event_name: event_name.into(),
+                include_payload: true,
+            },
+        );
+        self
+    }
+}
+
+/// Queues telemetry events and delivers them to the sink in batches, from the `telemetry`
+/// background thread so a slow sink never holds up a dispatch.
+///
+/// Undelivered events are persisted so they survive restarts while the app is offline.
+pub(crate) struct Telemetry {
+    config: TelemetryConfig,
+    install_id: String,
+    queue_path: PathBuf,
+    writer: Arc<PersistenceWriter>,
+    queue: Mutex<VecDeque<TelemetryEvent>>,
+    /// Serializes flushes so batches are delivered in order.
+    flushing: Mutex<()>,
+}
+
+impl Telemetry {
+    /// Create the pipeline, restoring any events left undelivered by a previous run, and start
+    /// the `telemetry` thread. What's still queued on shutdown is flushed once it has stopped.
+    pub(crate) fn start(
+        config: TelemetryConfig,
+        install_id: String,
+        data_dir: PathBuf,
+        writer: Arc<PersistenceWriter>,
+        subsystems: &Subsystems,
+    ) -> Arc<Self> {
+        let queue_path = data_dir.join(QUEUE_FILE);
+        let queue: VecDeque<TelemetryEvent> = std::fs::read_to_string(&queue_path)
+            .ok()
+            .and_then(|contents| serde_json::from_str(&contents).ok())
+            .unwrap_or_default();
+
+        let telemetry = Arc::new(Self {
+            config,
+            install_id,
+            queue_path,
+            writer,
+            queue: Mutex::new(queue),
+            flushing: Mutex::new(()),
+        });
+
+        // Registered first so it runs last, once the background thread has stopped
+        let flushed = telemetry.clone();
+        subsystems.register("telemetry-flush", move || flushed.flush());
+        let background = telemetry.clone();
+        let interval = telemetry.config.flush_interval;
+        subsystems.spawn("telemetry", move |signal| {
+            let mut flushed = Instant::now();
+            while !signal.sleep(interval.min(BATCH_CHECK_INTERVAL)) {
+                if flushed.elapsed() >= interval || background.batch_ready() {
+                    background.flush();
+                    flushed = Instant::now();
+                }
+            }
+        });
+
+        telemetry
+    }
+
+    /// Queue an event if the action type is tracked. It's sent by the `telemetry` thread, with
+    /// the next full batch or after `flush_interval`.
+    pub(crate) fn record(&self, action_type: &str, payload: Option<&JsonValue>) {
+        let Some(mapping) = self.config.mappings.get(action_type) else {
+            return;
+        };
+
+        let event = TelemetryEvent {
+            name: mapping.event_name.clone(),
+            properties: if mapping.include_payload { payload.cloned() } else { None },
+            install_id: self.install_id.clone(),
+            timestamp: now_millis(),
+        };
+
+        if let Ok(mut queue) = self.queue.lock() {
+            queue.push_back(event);
+            while queue.len() > self.config.max_queue {
+                queue.pop_front();
+            }
+        }
+    }
+
+    fn batch_ready(&self) -> bool {
+        self.queue.lock().is_ok_and(|queue| queue.len() >= self.config.batch_size)
+    }
+
+    /// Send all queued events to the sink, persisting them if delivery fails.
+    pub(crate) fn flush(&self) {
+        let Ok(_flushing) = self.flushing.lock() else { return };
+
+        loop {
+            let batch: Vec<TelemetryEvent> = match self.queue.lock() {
+                Ok(queue) => queue.iter().take(self.config.batch_size.max(1)).cloned().collect(),
+                Err(_) => return,
+            };
+            if batch.is_empty() {
+                break;
+            }
+
+            if let Err(e) = self.config.sink.send(&batch) {
+                log::debug!("zubridge: telemetry delivery failed, keeping events queued: {}", e);
+                break;
+            }
+
+            if let Ok(mut queue) = self.queue.lock() {
+                let delivered = batch.len().min(queue.len());
+                queue.drain(..delivered);
+            }
+        }
+
+        self.persist();
+    }
+
+    fn persist(&self) {
+        let Ok(queue) = self.queue.lock() else { return };
+        if queue.is_empty() {
+            self.writer.remove(self.queue_path.clone());
+            return;
+        }
+        match serde_json::to_string(&*queue) {
+            Ok(contents) => self.writer.write(self.queue_path.clone(), contents),
+            Err(e) => log::warn!("zubridge: failed to persist telemetry queue: {}", e),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// The name of the thread a batch was sent from, and its size.
+    type Sent = (Option<String>, usize);
+
+    #[derive(Clone, Default)]
+    struct RecordingSink(Arc<Mutex<Vec<Sent>>>);
+
+    impl TelemetrySink for RecordingSink {
+        fn send(&self, events: &[TelemetryEvent]) -> std::result::Result<(), String> {
+            let thread = std::thread::current().name().map(str::to_string);
+            self.0.lock().unwrap().push((thread, events.len()));
+            Ok(())
+        }
+    }
+
+    fn start(sink: RecordingSink, batch_size: usize, subsystems: &Subsystems) -> (Arc<Telemetry>, tempfile::TempDir) {
+        let dir = tempfile::tempdir().unwrap();
+        let config = TelemetryConfig {
+            batch_size,
+            ..TelemetryConfig::new(sink).track("TODO:ADD", "todo_added")
+        };
+        let writer = PersistenceWriter::start(Default::default(), Default::default(), subsystems);
+        (Telemetry::start(config, "install".into(), dir.path().to_path_buf(), writer, subsystems), dir)
+    }
+
+    #[test]
+    fn sends_full_batches_from_the_background_thread() {
+        let sink = RecordingSink::default();
+        let subsystems = Subsystems::default();
+        let (telemetry, _dir) = start(sink.clone(), 2, &subsystems);
+        telemetry.record("TODO:ADD", None);
+        telemetry.record("TODO:REMOVE", None);
+        telemetry.record("TODO:ADD", None);
+        assert!(sink.0.lock().unwrap().is_empty());
+
+        let deadline = Instant::now() + Duration::from_secs(5);
+        while sink.0.lock().unwrap().is_empty() && Instant::now() < deadline {
+            std::thread::sleep(Duration::from_millis(20));
+        }
+        assert_eq!(*sink.0.lock().unwrap(), vec![(Some("zubridge-telemetry".to_string()), 2)]);
+        subsystems.shutdown(Duration::from_secs(5));
+    }
+
+    #[test]
+    fn flushes_what_is_left_on_shutdown() {
+        let sink = RecordingSink::default();
+        let subsystems = Subsystems::default();
+        let (telemetry, dir) = start(sink.clone(), 20, &subsystems);
+        telemetry.record("TODO:ADD", None);
+        subsystems.shutdown(Duration::from_secs(5));
+        assert_eq!(sink.0.lock().unwrap().iter().map(|(_, len)| len).sum::<usize>(), 1);
+        assert!(!dir.path().join(QUEUE_FILE).exists());
+    }
+}
@@ -0,0 +1,627 @@
+//! Helpers for testing apps that use zubridge without a real webview.
+//!
+//! Enable the `test` feature, then build an app backed by [`tauri::test::MockRuntime`]:
+//!
+//! ```rust,ignore
+//! let app = tauri_plugin_zubridge::test::mock_app(CounterState::default());
+//! let state = app.dispatch("INCREMENT", None)?;
+//! assert_eq!(state["counter"], 1);
+//! assert_eq!(app.last_state(), Some(state));
+//! ```
+
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+
+use tauri::ipc::{CallbackFn, InvokeBody};
+use tauri::test::{get_ipc_response, mock_builder, mock_context, noop_assets, MockRuntime, INVOKE_KEY};
+use tauri::utils::acl::capability::Capability;
+use tauri::utils::acl::manifest::Manifest;
+use tauri::utils::acl::resolved::Resolved;
+use tauri::utils::config::AppDirectoriesOverride;
+use tauri::utils::platform::Target;
+use tauri::webview::InvokeRequest;
+use tauri::{App, AppHandle, Listener, WebviewWindow, WebviewWindowBuilder};
+
+use crate::models::*;
+use crate::ZubridgeExt;
+
+/// An event emitted by the plugin.
+#[derive(Clone, Debug)]
+pub struct CapturedEvent {
+    pub event: String,
+    pub payload: JsonValue,
+}
+
+/// The plugin's permissions, as resolved by `tauri-build` when the plugin was built.
+const ACL_MANIFESTS: &str = include_str!(concat!(env!("OUT_DIR"), "/acl-manifests.json"));
+
+/// A MockRuntime app with the plugin installed, a `main` window granted `zubridge:default`, and
+/// a log of emitted events.
+///
+/// Plugin data (install id, experiment assignments, ...) is kept in a temporary directory
+/// that's removed when the app is dropped.
+pub struct MockApp {
+    app: App<MockRuntime>,
+    window: WebviewWindow<MockRuntime>,
+    events: Arc<Mutex<Vec<CapturedEvent>>>,
+    data_dir: PathBuf,
+}
+
+/// Build a mock app with the plugin installed using the default options.
+pub fn mock_app<S: StateManager>(state_manager: S) -> MockApp {
+    mock_app_with_options(state_manager, ZubridgeOptions::default())
+}
+
+/// Build a mock app with the plugin installed using the given options.
+pub fn mock_app_with_options<S: StateManager>(state_manager: S, options: ZubridgeOptions) -> MockApp {
+    let data_dir = std::env::temp_dir().join(format!("zubridge-test-{}", uuid::Uuid::new_v4()));
+    let mut context = mock_context(noop_assets());
+    context.config_mut().app.app_directories_override = Some(AppDirectoriesOverride::Root(data_dir.clone()));
+    *context.runtime_authority_mut() = runtime_authority();
+
+    let events = Arc::new(Mutex::new(Vec::new()));
+    let app = mock_builder()
+        .plugin(crate::plugin(state_manager, options))
+        .build(context)
+        .expect("failed to build mock app");
+
+    // The event names depend on the mock app's identifier
+    let names = app.zubridge().event_names().clone();
+    let captured = [
+        names.state_update,
+        names.import_progress,
+        names.ephemeral_update,
+        names.slice_update,
+        names.diagnostics,
+        names.startup,
+    ];
+
+    for name in captured {
+        let events = events.clone();
+        let event = name.clone();
+        app.listen_any(name, move |emitted| {
+            let payload = serde_json::from_str(emitted.payload()).unwrap_or(JsonValue::Null);
+            if let Ok(mut events) = events.lock() {
+                events.push(CapturedEvent { event: event.clone(), payload });
+            }
+        });
+    }
+
+    let window = WebviewWindowBuilder::new(&app, "main", Default::default())
+        .build()
+        .expect("failed to create mock window");
+
+    MockApp { app, window, events, data_dir }
+}
+
+impl MockApp {
+    pub fn app(&self) -> &App<MockRuntime> {
+        &self.app
+    }
+
+    pub fn handle(&self) -> &AppHandle<MockRuntime> {
+        self.app.handle()
+    }
+
+    /// The `main` window
+    pub fn window(&self) -> &WebviewWindow<MockRuntime> {
+        &self.window
+    }
+
+    /// The temporary directory holding plugin data
+    pub fn data_dir(&self) -> &Path {
+        &self.data_dir
+    }
+
+    /// Dispatch an action from the `main` window through `zubridge.dispatch-action`, over IPC
+    /// like the frontend does, and return the new state.
+    pub fn dispatch(&self, action_type: &str, payload: Option<JsonValue>) -> crate::Result<JsonValue> {
+        let action = ZubridgeAction {
+            action_type: action_type.to_string(),
+            payload,
+        };
+        self.invoke("dispatch_action", serde_json::json!({ "action": action }))
+    }
+
+    /// Invoke one of the plugin's commands, e.g. `dispatch_action`, from the `main` window with
+    /// `args`, and deserialize what it returns.
+    pub fn invoke<T: serde::de::DeserializeOwned>(&self, command: &str, args: JsonValue) -> crate::Result<T> {
+        let request = InvokeRequest {
+            cmd: format!("plugin:zubridge|{}", command),
+            callback: CallbackFn(0),
+            error: CallbackFn(1),
+            url: if cfg!(any(windows, target_os = "android")) {
+                "http://tauri.localhost"
+            } else {
+                "tauri://localhost"
+            }
+            .parse()
+            .expect("invalid mock window url"),
+            body: InvokeBody::Json(args),
+            headers: Default::default(),
+            invoke_key: INVOKE_KEY.to_string(),
+        };
+        get_ipc_response(&self.window, request)
+            .map_err(crate::Error::CommandFailed)?
+            .deserialize()
+            .map_err(|e| crate::Error::SerializationError(e.to_string()))
+    }
+
+    /// Run the plugin's page load hooks for the `main` window, as if its page was reloaded.
+    /// MockRuntime never loads a page, so they don't run otherwise.
+    pub fn reload_page(&self) -> crate::Result<()> {
+        let zubridge = self.app.zubridge();
+        zubridge.page_loading(self.window.label());
+        zubridge.page_loaded(self.window.label())
+    }
+
+    /// The state as returned by `zubridge.get-initial-state`
+    pub fn state(&self) -> crate::Result<JsonValue> {
+        self.app.zubridge().get_initial_state()
+    }
+
+    /// Wait until the state matches `predicate`, e.g. after work done on a background thread.
+    /// See `Zubridge::wait_for`.
+    pub fn wait_for(&self, predicate: impl Fn(&JsonValue) -> bool, timeout: std::time::Duration) -> crate::Result<JsonValue> {
+        self.app.zubridge().wait_for(predicate, timeout)
+    }
+
+    /// All events emitted so far, oldest first
+    pub fn events(&self) -> Vec<CapturedEvent> {
+        self.events.lock().map(|events| events.clone()).unwrap_or_default()
+    }
+
+    /// The payloads of every emitted `event`, oldest first. Event names are scoped to the app
+    /// identifier, so look them up with `app().zubridge().event_names()`
+    pub fn payloads(&self, event: &str) -> Vec<JsonValue> {
+        self.events()
+            .into_iter()
+            .filter(|captured| captured.event == event)
+            .map(|captured| captured.payload)
+            .collect()
+    }
+
+    /// The payload of the most recent state update
+    pub fn last_state(&self) -> Option<JsonValue> {
+        let event_name = self.app.zubridge().get_event_name();
+        self.payloads(&event_name).pop()
+    }
+
+    pub fn clear_events(&self) {
+        if let Ok(mut events) = self.events.lock() {
+            events.clear();
+        }
+    }
+}
+
+/// Grant the `main` window the plugin's default permissions, like an app's capability file.
+fn runtime_authority() -> tauri::ipc::RuntimeAuthority {
+    let acl: BTreeMap<String, Manifest> = serde_json::from_str(ACL_MANIFESTS).expect("invalid ACL manifests");
+    let capability: Capability = serde_json::from_value(serde_json::json!({
+        "identifier": "zubridge-test",
+        "windows": ["main"],
+        "permissions": ["zubridge:default"],
+    }))
+    .expect("invalid test capability");
+    let capabilities = BTreeMap::from([(capability.identifier.clone(), capability)]);
+    let resolved = Resolved::resolve(&acl, capabilities, Target::current()).expect("failed to resolve the ACL");
+    tauri::runtime_authority!(acl, resolved)
+}
+
+impl Drop for MockApp {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_dir_all(&self.data_dir);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[derive(Default)]
+    struct Counter {
+        counter: i64,
+    }
+
+    impl StateManager for Counter {
+        fn get_initial_state(&self) -> JsonValue {
+            json!({ "counter": self.counter })
+        }
+
+        fn dispatch_action(&mut self, action: JsonValue) -> JsonValue {
+            match action["type"].as_str() {
+                Some("INCREMENT") => self.counter += 1,
+                Some("ADD") => self.counter += action["payload"].as_i64().unwrap_or_default(),
+                Some("PANIC") => panic!("reducer failed"),
+                Some("BUMP_AND_PANIC") => {
+                    self.counter += 1;
+                    panic!("reducer failed halfway")
+                }
+                _ => {}
+            }
+            self.get_initial_state()
+        }
+
+        fn restore_state(&mut self, state: JsonValue) -> bool {
+            self.counter = state["counter"].as_i64().unwrap_or_default();
+            true
+        }
+    }
+
+    #[test]
+    fn dispatches_over_ipc() {
+        let app = mock_app(Counter::default());
+        let state = app.dispatch("INCREMENT", None).unwrap();
+        assert_eq!(state["counter"], 1);
+        assert_eq!(app.state().unwrap()["counter"], 1);
+        assert_eq!(app.last_state().unwrap()["counter"], 1);
+    }
+
+    #[test]
+    fn holds_back_only_the_transaction_threads_updates() {
+        let app = mock_app(Counter::default());
+        let zubridge = app.app().zubridge();
+        let increment = || {
+            let action = ZubridgeAction { action_type: "INCREMENT".to_string(), payload: None };
+            zubridge.dispatch_action(action).unwrap();
+        };
+
+        zubridge.transaction(|zubridge| {
+            increment();
+            zubridge.transaction(|_| increment());
+            assert_eq!(app.last_state(), None);
+            // Another thread's dispatch is published right away
+            std::thread::scope(|scope| {
+                scope.spawn(increment);
+            });
+            assert_eq!(app.last_state().unwrap()["counter"], 3);
+            increment();
+        });
+        let counters: Vec<JsonValue> =
+            app.payloads(&zubridge.get_event_name()).into_iter().map(|state| state["counter"].clone()).collect();
+        assert_eq!(counters, [json!(3), json!(4)]);
+    }
+
+    #[test]
+    fn returns_command_errors() {
+        let app = mock_app(Counter::default());
+        let Err(crate::Error::CommandFailed(error)) = app.dispatch("PANIC", None) else {
+            panic!("expected the dispatch to fail");
+        };
+        assert!(error.to_string().contains("Reducer panicked"), "{}", error);
+        assert_eq!(app.state().unwrap()["counter"], 0);
+    }
+
+    #[test]
+    fn rejects_oversized_payloads_from_windows() {
+        let options = ZubridgeOptions {
+            max_action_bytes: Some(4),
+            ..Default::default()
+        };
+        let app = mock_app_with_options(Counter::default(), options);
+        assert!(app.dispatch("ADD", Some(json!(1))).is_ok());
+        assert!(app.dispatch("ADD", Some(json!(123456))).is_err());
+        assert_eq!(app.state().unwrap()["counter"], 1);
+    }
+
+    #[test]
+    fn limits_window_payloads_and_uploads() {
+        let options = ZubridgeOptions {
+            max_action_bytes: Some(64),
+            max_upload_bytes: Some(8),
+            ..Default::default()
+        };
+        let app = mock_app_with_options(Counter::default(), options);
+        let zubridge = app.app().zubridge();
+        let large = json!("x".repeat(64));
+
+        let action = ZubridgeAction { action_type: "ADD".into(), payload: Some(large.clone()) };
+        let rejected = zubridge.dispatch_action_from("main", action);
+        assert!(matches!(rejected, Err(crate::Error::ActionTooLarge { bytes: 66, limit: 64, .. })));
+        let batch = json!({
+            "actions": [{ "type": "INCREMENT" }, { "type": "ADD", "payload": large }],
+            "onResult": "__CHANNEL__:1",
+        });
+        assert!(app.invoke::<JsonValue>("dispatch_batch", batch).is_err());
+        assert_eq!(app.state().unwrap()["counter"], 0);
+        // The app's own dispatches aren't limited
+        assert!(zubridge.dispatch_action(ZubridgeAction { action_type: "NOOP".into(), payload: Some(large) }).is_ok());
+
+        // Uploads are limited separately, and their action only carries the upload's id
+        assert_eq!(zubridge.upload_blob("main", "a", b"12345", None).unwrap(), None);
+        let finish = Some(("UPLOADED".to_string(), None));
+        assert!(zubridge.upload_blob("main", "a", b"678", finish.clone()).is_ok());
+        zubridge.upload_blob("main", "b", b"12345", None).unwrap();
+        let rejected = zubridge.upload_blob("main", "b", b"6789", finish);
+        assert!(matches!(rejected, Err(crate::Error::UploadTooLarge { bytes: 9, limit: 8 })));
+    }
+
+    #[test]
+    fn keeps_read_only_windows_from_saving_snapshots() {
+        let options = ZubridgeOptions {
+            read_only_windows: vec!["main".into()],
+            ..Default::default()
+        };
+        let app = mock_app_with_options(Counter::default(), options);
+        let saved = app.invoke::<String>("save_snapshot", json!({ "name": "observer" }));
+        assert!(matches!(saved, Err(crate::Error::CommandFailed(error)) if error.to_string().contains("read-only")));
+        assert!(app.app().zubridge().snapshots().unwrap().is_empty());
+    }
+
+    fn signed_app() -> MockApp {
+        let options = ZubridgeOptions {
+            payload_integrity: true,
+            ..Default::default()
+        };
+        mock_app_with_options(Counter::default(), options)
+    }
+
+    #[test]
+    fn hands_out_the_key_once_per_page_load() {
+        let app = signed_app();
+        let first: JsonValue = app.invoke("handshake", json!({})).unwrap();
+        assert_eq!(first["algorithm"], "HMAC-SHA256");
+        assert!(app.invoke::<JsonValue>("handshake", json!({})).is_err());
+
+        app.reload_page().unwrap();
+        let second: JsonValue = app.invoke("handshake", json!({})).unwrap();
+        assert_eq!(second["key"], first["key"]);
+        assert!(app.invoke::<JsonValue>("handshake", json!({})).is_err());
+    }
+
+    #[test]
+    fn signs_state_updates_with_the_handshake_key() {
+        let app = signed_app();
+        let handshake: JsonValue = app.invoke("handshake", json!({})).unwrap();
+        let key: Vec<u8> = (0..64)
+            .step_by(2)
+            .map(|i| u8::from_str_radix(&handshake["key"].as_str().unwrap()[i..i + 2], 16).unwrap())
+            .collect();
+        app.dispatch("INCREMENT", None).unwrap();
+
+        let update = app.last_state().unwrap();
+        let data = update["data"].as_str().unwrap();
+        let message = format!("{}.{}", update["revision"], data);
+        let signature = crate::utils::hex(&crate::utils::hmac_sha256(&key, message.as_bytes()));
+        assert_eq!(update["signature"], signature);
+        assert_eq!(serde_json::from_str::<JsonValue>(data).unwrap()["counter"], 1);
+    }
+
+    #[test]
+    fn rejects_window_writes_to_the_auth_slice() {
+        let options = ZubridgeOptions {
+            mutable_paths: vec!["*".into()],
+            authorization: Some(crate::AuthorizationConfig::default().require_role("ADMIN:*", ["admin"])),
+            ..Default::default()
+        };
+        let app = mock_app_with_options(Counter::default(), options);
+        let forbidden = |result: crate::Result<JsonValue>| {
+            matches!(result, Err(crate::Error::CommandFailed(error)) if error.to_string().contains("Forbidden"))
+        };
+        let set_path = |path: &str| {
+            app.dispatch("__ZUBRIDGE:SET_PATH", Some(json!({ "path": path, "value": ["admin"] })))
+        };
+        assert!(forbidden(set_path("auth.roles")));
+        assert!(forbidden(set_path("auth")));
+        assert!(set_path("counter").is_ok());
+
+        let batch = json!({
+            "actions": [{ "type": "__ZUBRIDGE:SET_PATH", "payload": { "path": "auth.roles" } }],
+            "onResult": "__CHANNEL__:1",
+        });
+        assert!(forbidden(app.invoke("dispatch_batch", batch)));
+    }
+
+    #[test]
+    fn watches_and_unwatches_concurrently() {
+        let app = mock_app(Counter::default());
+        let dir = app.data_dir().join("watched");
+        std::fs::create_dir_all(&dir).unwrap();
+        std::thread::scope(|scope| {
+            for name in ["a.json", "b.json"] {
+                let path = dir.join(name);
+                let fs_watch = app.app().zubridge().fs_watch();
+                scope.spawn(move || {
+                    for _ in 0..50 {
+                        fs_watch.watch_json(&path, "FILE_CHANGED").unwrap();
+                        fs_watch.unwatch(&path).unwrap();
+                    }
+                });
+            }
+        });
+    }
+
+    #[test]
+    fn publishes_changes_made_before_a_reducer_panicked() {
+        let app = mock_app(Counter::default());
+        assert_eq!(app.state().unwrap()["counter"], 0);
+        assert!(app.dispatch("PANIC", None).is_err());
+        assert_eq!(app.last_state(), None);
+
+        assert!(app.dispatch("BUMP_AND_PANIC", None).is_err());
+        assert_eq!(app.last_state().unwrap()["counter"], 1);
+        assert_eq!(app.state().unwrap()["counter"], 1);
+    }
+
+    #[test]
+    fn keeps_the_state_and_lock_usable_after_a_reducer_panicked() {
+        let app = mock_app(Counter::default());
+        let zubridge = app.app().zubridge();
+        app.dispatch("ADD", Some(json!(2))).unwrap();
+
+        let action = ZubridgeAction { action_type: "PANIC".to_string(), payload: None };
+        let Err(crate::Error::ReducerPanicked { message }) = zubridge.dispatch_action(action) else {
+            panic!("expected the panic to reach the caller");
+        };
+        assert_eq!(message, "reducer failed");
+        assert!(!zubridge.state_manager().unwrap().is_poisoned());
+        assert_eq!(app.state().unwrap()["counter"], 2);
+        assert_eq!(app.dispatch("INCREMENT", None).unwrap()["counter"], 3);
+    }
+
+    #[test]
+    fn checks_the_actions_interceptors_rewrite_actions_into() {
+        let rewrite: Arc<dyn crate::DispatchInterceptor> = Arc::new(|action: ZubridgeAction| {
+            let action_type = match action.action_type.as_str() {
+                "UI:BUMP" => "INCREMENT",
+                "UI:WIPE" => "DANGER:WIPE",
+                _ => return crate::Interception::Continue(action),
+            };
+            crate::Interception::Transformed(vec![ZubridgeAction {
+                action_type: action_type.into(),
+                payload: None,
+            }])
+        });
+        let options = ZubridgeOptions {
+            interceptors: vec![rewrite],
+            session_lock: Some(crate::SessionLockConfig {
+                allowed_actions: vec!["UI:*".into()],
+                ..Default::default()
+            }),
+            confirmations: Some(crate::ConfirmationsConfig::default().require("DANGER:*", "Wipe everything?")),
+            ..Default::default()
+        };
+        let app = mock_app_with_options(Counter::default(), options);
+        let zubridge = app.app().zubridge();
+        let dispatch = |action_type: &str| {
+            zubridge.dispatch_action(ZubridgeAction {
+                action_type: action_type.into(),
+                payload: None,
+            })
+        };
+
+        assert!(matches!(dispatch("UI:WIPE"), Err(crate::Error::ActionRejected(_))));
+        assert_eq!(dispatch("UI:BUMP").unwrap()["counter"], 1);
+        dispatch("SESSION:LOCK").unwrap();
+        assert!(matches!(dispatch("UI:BUMP"), Err(crate::Error::SessionLocked(_))));
+        assert_eq!(app.state().unwrap()["counter"], 1);
+    }
+
+    #[test]
+    fn rejects_plugin_handled_actions_in_atomic_batches() {
+        let options = ZubridgeOptions {
+            workspaces: Some(crate::WorkspacesConfig::new(json!({ "counter": 5 }))),
+            ..Default::default()
+        };
+        let app = mock_app_with_options(Counter::default(), options);
+        let zubridge = app.app().zubridge();
+        let action = |action_type: &str, payload: Option<JsonValue>| ZubridgeAction {
+            action_type: action_type.into(),
+            payload,
+        };
+        let actions = vec![
+            action("INCREMENT", None),
+            action("WORKSPACE:SWITCH", Some(json!({ "id": "other" }))),
+            action("PANIC", None),
+        ];
+        let mut results = Vec::new();
+
+        let batch = zubridge.dispatch_batch(actions, crate::BatchMode::Atomic, |result| results.push(result.ok));
+        assert!(matches!(batch, Err(crate::Error::BatchRolledBack { index: 1, .. })));
+        assert_eq!(results, [true, false]);
+        assert_eq!(zubridge.active_workspace().unwrap(), "default");
+        assert_eq!(app.state().unwrap()["counter"], 0);
+    }
+
+    #[derive(Default)]
+    struct Profile {
+        avatar: Option<String>,
+    }
+
+    impl StateManager for Profile {
+        fn get_initial_state(&self) -> JsonValue {
+            json!({ "avatar": self.avatar })
+        }
+
+        fn dispatch_action(&mut self, action: JsonValue) -> JsonValue {
+            if action["type"] == "SET_AVATAR" {
+                self.avatar = action["payload"].as_str().map(str::to_string);
+            }
+            self.get_initial_state()
+        }
+
+        fn restore_state(&mut self, state: JsonValue) -> bool {
+            self.avatar = state["avatar"].as_str().map(str::to_string);
+            true
+        }
+    }
+
+    #[test]
+    fn keeps_blobs_referred_to_by_inactive_workspaces_and_snapshots() {
+        let options = ZubridgeOptions {
+            blobs: Some(crate::BlobStoreConfig {
+                grace_period: std::time::Duration::ZERO,
+                ..Default::default()
+            }),
+            workspaces: Some(crate::WorkspacesConfig::new(json!({ "avatar": null }))),
+            ..Default::default()
+        };
+        let app = mock_app_with_options(Profile::default(), options);
+        let zubridge = app.app().zubridge();
+        let put = |data: &[u8]| zubridge.put_blob(data, "image/png").unwrap();
+        let (parked, snapshotted, dropped) = (put(b"parked"), put(b"snapshotted"), put(b"dropped"));
+
+        app.dispatch("SET_AVATAR", Some(json!(parked.url))).unwrap();
+        zubridge.switch_workspace("other").unwrap();
+        app.dispatch("SET_AVATAR", Some(json!(snapshotted.url))).unwrap();
+        zubridge.save_snapshot(Some("before")).unwrap();
+        app.dispatch("SET_AVATAR", Some(json!(dropped.url))).unwrap();
+        app.dispatch("SET_AVATAR", None).unwrap();
+
+        assert_eq!(zubridge.collect_blobs().unwrap(), 1);
+        assert!(zubridge.blob_path(&parked.id).is_ok());
+        assert!(zubridge.blob_path(&snapshotted.id).is_ok());
+        assert!(zubridge.blob_path(&dropped.id).is_err());
+        // Switching back finds the parked workspace's blob
+        let state = zubridge.switch_workspace("default").unwrap();
+        assert!(zubridge.blob_path(state["avatar"].as_str().unwrap()).is_ok());
+    }
+
+    #[test]
+    fn aggregates_only_the_app_state() {
+        let options = ZubridgeOptions {
+            aggregations: vec![crate::Aggregation::count("profile", "profile")],
+            ..Default::default()
+        };
+        let app = mock_app_with_options(Counter::default(), options);
+        app.app().zubridge().register_slice("profile", Profile::default()).unwrap();
+
+        let state = app.state().unwrap();
+        assert_eq!(state["profile"], json!({ "avatar": null }));
+        assert_eq!(state["stats"]["profile"], 0);
+    }
+
+    #[test]
+    fn serves_blobs_with_the_content_type_they_were_stored_with() {
+        let options = ZubridgeOptions { blobs: Some(Default::default()), ..Default::default() };
+        let app = mock_app_with_options(Profile::default(), options);
+        let blob = app.app().zubridge().put_blob(b"<script></script>", "text/plain").unwrap();
+
+        let url = blob.url.replace("type=text%2Fplain", "type=text%2Fhtml");
+        let request = tauri::http::Request::builder().uri(url).body(Vec::new()).unwrap();
+        let response = crate::blobs::handle_request(app.app().handle(), "main", request);
+        assert_eq!(response.status(), 200);
+        assert_eq!(response.headers()["content-type"], "text/plain");
+        assert_eq!(response.headers()["x-content-type-options"], "nosniff");
+    }
+
+    #[test]
+    fn moves_aside_workspace_states_that_do_not_parse() {
+        let options = ZubridgeOptions {
+            workspaces: Some(crate::WorkspacesConfig::new(json!({ "counter": 5 }))),
+            ..Default::default()
+        };
+        let app = mock_app_with_options(Counter::default(), options);
+        let dir = crate::utils::plugin_data_dir(app.handle()).unwrap().join("workspaces");
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(dir.join("other.json"), "{ not json").unwrap();
+
+        let state = app.app().zubridge().switch_workspace("other").unwrap();
+        assert_eq!(state["counter"], 5);
+        assert!(!dir.join("other.json").exists());
+        let quarantined = std::fs::read_dir(&dir).unwrap().filter_map(Result::ok);
+        assert!(quarantined.into_iter().any(|entry| entry.file_name().to_string_lossy().starts_with("other.json.invalid-")));
+    }
+}
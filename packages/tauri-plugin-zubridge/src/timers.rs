@@ -0,0 +1,195 @@
+use std::collections::BTreeMap;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use serde::Serialize;
+
+use crate::models::*;
+
+pub(crate) const START_ACTION: &str = "TIMER:START";
+pub(crate) const PAUSE_ACTION: &str = "TIMER:PAUSE";
+pub(crate) const RESUME_ACTION: &str = "TIMER:RESUME";
+pub(crate) const CANCEL_ACTION: &str = "TIMER:CANCEL";
+const DONE_ACTION: &str = "TIMER:DONE";
+
+/// Timers and stopwatches run by the plugin, ticked into the `state_key` slice.
+#[derive(Clone, Debug)]
+pub struct TimersConfig {
+    /// How often running timers are written to the state. Defaults to 1 second.
+    pub resolution: Duration,
+    /// The state key timers are stored under. Defaults to "timers".
+    pub state_key: String,
+}
+
+impl Default for TimersConfig {
+    fn default() -> Self {
+        Self {
+            resolution: Duration::from_secs(1),
+            state_key: "timers".to_string(),
+        }
+    }
+}
+
+struct Timer {
+    /// None for stopwatches, which count up until cancelled.
+    duration: Option<Duration>,
+    /// Time elapsed before the timer was last resumed.
+    elapsed: Duration,
+    /// When the timer was last started or resumed, if it's running.
+    resumed: Option<Instant>,
+    done_action: String,
+}
+
+impl Timer {
+    fn elapsed(&self) -> Duration {
+        self.elapsed + self.resumed.map(|resumed| resumed.elapsed()).unwrap_or_default()
+    }
+
+    fn is_done(&self) -> bool {
+        self.duration.is_some_and(|duration| self.elapsed() >= duration)
+    }
+}
+
+#[derive(Serialize)]
+struct TimerEntry {
+    /// Milliseconds, or null for stopwatches.
+    duration: Option<u64>,
+    elapsed: u64,
+    remaining: Option<u64>,
+    running: bool,
+}
+
+/// The running and paused timers, keyed by id.
+#[derive(Default)]
+pub(crate) struct Timers {
+    timers: Mutex<BTreeMap<String, Timer>>,
+}
+
+impl Timers {
+    /// Start, pause, resume or cancel the timer named by the action's `id` payload.
+    pub(crate) fn apply(&self, action: &ZubridgeAction) -> crate::Result<()> {
+        let field = |name: &str| action.payload.as_ref().and_then(|payload| payload.get(name));
+        let id = field("id")
+            .and_then(JsonValue::as_str)
+            .ok_or_else(|| crate::Error::StateError(format!("{} requires an id", action.action_type)))?;
+
+        let mut timers = self.timers.lock().map_err(|e| crate::Error::StateError(e.to_string()))?;
+        match action.action_type.as_str() {
+            START_ACTION => {
+                let timer = Timer {
+                    duration: field("duration").and_then(JsonValue::as_u64).map(Duration::from_millis),
+                    elapsed: Duration::ZERO,
+                    resumed: Some(Instant::now()),
+                    done_action: field("action").and_then(JsonValue::as_str).unwrap_or(DONE_ACTION).to_string(),
+                };
+                timers.insert(id.to_string(), timer);
+            }
+            PAUSE_ACTION => {
+                if let Some(timer) = timers.get_mut(id) {
+                    timer.elapsed = timer.elapsed();
+                    timer.resumed = None;
+                }
+            }
+            RESUME_ACTION => {
+                if let Some(timer) = timers.get_mut(id) {
+                    timer.resumed.get_or_insert_with(Instant::now);
+                }
+            }
+            _ => {
+                timers.remove(id);
+            }
+        }
+        Ok(())
+    }
+
+    /// Remove finished timers, returning their completion actions, and whether any timer is running.
+    pub(crate) fn tick(&self) -> crate::Result<(Vec<ZubridgeAction>, bool)> {
+        let mut timers = self.timers.lock().map_err(|e| crate::Error::StateError(e.to_string()))?;
+        let mut done = Vec::new();
+        timers.retain(|id, timer| {
+            if !timer.is_done() {
+                return true;
+            }
+            done.push(ZubridgeAction {
+                action_type: timer.done_action.clone(),
+                payload: Some(serde_json::json!({ "id": id })),
+            });
+            false
+        });
+        let running = timers.values().any(|timer| timer.resumed.is_some());
+        Ok((done, running))
+    }
+
+    pub(crate) fn slice(&self) -> crate::Result<JsonValue> {
+        let timers = self.timers.lock().map_err(|e| crate::Error::StateError(e.to_string()))?;
+        let entries: BTreeMap<&String, TimerEntry> = timers
+            .iter()
+            .map(|(id, timer)| {
+                let elapsed = timer.elapsed();
+                let entry = TimerEntry {
+                    duration: timer.duration.map(|duration| duration.as_millis() as u64),
+                    elapsed: elapsed.as_millis() as u64,
+                    remaining: timer.duration.map(|duration| duration.saturating_sub(elapsed).as_millis() as u64),
+                    running: timer.resumed.is_some(),
+                };
+                (id, entry)
+            })
+            .collect();
+        serde_json::to_value(entries).map_err(|e| crate::Error::SerializationError(e.to_string()))
+    }
+}
+
+pub(crate) fn is_timer_action(action_type: &str) -> bool {
+    matches!(action_type, START_ACTION | PAUSE_ACTION | RESUME_ACTION | CANCEL_ACTION)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn action(action_type: &str, payload: JsonValue) -> ZubridgeAction {
+        ZubridgeAction {
+            action_type: action_type.into(),
+            payload: Some(payload),
+        }
+    }
+
+    #[test]
+    fn finished_timers_dispatch_their_action() {
+        let timers = Timers::default();
+        timers.apply(&action(START_ACTION, json!({ "id": "a", "duration": 0, "action": "ALARM" }))).unwrap();
+        timers.apply(&action(START_ACTION, json!({ "id": "b", "duration": 0 }))).unwrap();
+        timers.apply(&action(START_ACTION, json!({ "id": "watch" }))).unwrap();
+
+        let (done, running) = timers.tick().unwrap();
+        let done: Vec<_> = done.iter().map(|action| (action.action_type.as_str(), action.payload.clone())).collect();
+        assert_eq!(done, [("ALARM", Some(json!({ "id": "a" }))), (DONE_ACTION, Some(json!({ "id": "b" })))]);
+        assert!(running);
+        assert_eq!(timers.slice().unwrap()["watch"]["duration"], JsonValue::Null);
+    }
+
+    #[test]
+    fn paused_timers_stop_running() {
+        let timers = Timers::default();
+        timers.apply(&action(START_ACTION, json!({ "id": "a", "duration": 60_000 }))).unwrap();
+        timers.apply(&action(PAUSE_ACTION, json!({ "id": "a" }))).unwrap();
+        let (done, running) = timers.tick().unwrap();
+        assert!(done.is_empty() && !running);
+        let slice = timers.slice().unwrap();
+        assert_eq!(slice["a"]["running"], false);
+        assert_eq!(slice["a"]["duration"], 60_000);
+
+        timers.apply(&action(RESUME_ACTION, json!({ "id": "a" }))).unwrap();
+        assert!(timers.tick().unwrap().1);
+        timers.apply(&action(CANCEL_ACTION, json!({ "id": "a" }))).unwrap();
+        assert_eq!(timers.slice().unwrap(), json!({}));
+    }
+
+    #[test]
+    fn timer_actions_require_an_id() {
+        let timers = Timers::default();
+        assert!(timers.apply(&action(START_ACTION, json!({ "duration": 10 }))).is_err());
+        assert!(is_timer_action(CANCEL_ACTION) && !is_timer_action(DONE_ACTION));
+    }
+}
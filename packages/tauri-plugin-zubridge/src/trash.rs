@@ -0,0 +1,295 @@
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use serde::{Deserialize, Serialize};
+
+use crate::models::*;
+use crate::persistence::PersistenceWriter;
+use crate::utils::now_millis;
+
+const TRASH_FILE: &str = "trash.json";
+
+pub(crate) const RESTORE_ACTION: &str = "TRASH:RESTORE";
+pub(crate) const PURGE_ACTION: &str = "TRASH:PURGE";
+
+/// A collection whose deleted entities are moved to the trash.
+#[derive(Clone, Debug)]
+struct TrashCollection {
+    name: String,
+    /// Dot-separated path of the collection in the state.
+    path: String,
+    delete_action: String,
+    restore_action: String,
+}
+
+/// Keeps deleted entities in the `state_key` slice so deletions can be undone, purging them
+/// after `retention`.
+#[derive(Clone, Debug)]
+pub struct TrashConfig {
+    collections: Vec<TrashCollection>,
+    /// How long deleted entities are kept. Defaults to 30 days.
+    pub retention: Duration,
+    /// The state key the trash is stored under. Defaults to "trash".
+    pub state_key: String,
+}
+
+impl Default for TrashConfig {
+    fn default() -> Self {
+        Self {
+            collections: Vec::new(),
+            retention: Duration::from_secs(30 * 24 * 60 * 60),
+            state_key: "trash".to_string(),
+        }
+    }
+}
+
+impl TrashConfig {
+    /// Move entities to the trash when `delete_action` is dispatched with an `id` or `ids`
+    /// payload. `path` is the dot-separated path of the collection, either an object keyed by id
+    /// or a normalized `{ ids, entities }` object. Restoring an entity dispatches
+    /// `restore_action` with `{ id, entity }`.
+    pub fn collection(
+        mut self,
+        name: impl Into<String>,
+        path: impl Into<String>,
+        delete_action: impl Into<String>,
+        restore_action: impl Into<String>,
+    ) -> Self {
+        self.collections.push(TrashCollection {
+            name: name.into(),
+            path: path.into(),
+            delete_action: delete_action.into(),
+            restore_action: restore_action.into(),
+        });
+        self
+    }
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+struct TrashEntry {
+    entity: JsonValue,
+    /// Milliseconds since the Unix epoch.
+    deleted_at: u64,
+}
+
+type TrashEntries = BTreeMap<String, BTreeMap<String, TrashEntry>>;
+
+/// Deleted entities keyed by collection and id, persisted to the plugin's data directory.
+pub(crate) struct Trash {
+    config: TrashConfig,
+    path: PathBuf,
+    writer: Arc<PersistenceWriter>,
+    entries: Mutex<TrashEntries>,
+}
+
+impl Trash {
+    pub(crate) fn load(config: TrashConfig, data_dir: &Path, writer: Arc<PersistenceWriter>) -> Self {
+        let path = data_dir.join(TRASH_FILE);
+        let entries = std::fs::read_to_string(&path)
+            .ok()
+            .and_then(|contents| serde_json::from_str(&contents).ok())
+            .unwrap_or_default();
+        Self {
+            config,
+            path,
+            writer,
+            entries: Mutex::new(entries),
+        }
+    }
+
+    pub(crate) fn is_delete_action(&self, action_type: &str) -> bool {
+        self.config.collections.iter().any(|collection| collection.delete_action == action_type)
+    }
+
+    /// Copy the entities a delete action removes from `state` into the trash.
+    pub(crate) fn observe_delete(&self, action: &ZubridgeAction, state: &JsonValue) -> crate::Result<()> {
+        let field = |name: &str| action.payload.as_ref().and_then(|payload| payload.get(name));
+        let ids: Vec<String> = match (field("id"), field("ids")) {
+            (Some(id), _) => vec![id_string(id)],
+            (None, Some(JsonValue::Array(ids))) => ids.iter().map(id_string).collect(),
+            _ => return Err(crate::Error::StateError(format!("{} requires an id or ids", action.action_type))),
+        };
+
+        let deleted_at = now_millis();
+        self.update(|entries| {
+            for collection in self.config.collections.iter().filter(|collection| collection.delete_action == action.action_type) {
+                let Some(entities) = entities_at(state, &collection.path) else { continue };
+                for id in &ids {
+                    if let Some(entity) = entities.get(id) {
+                        let entry = TrashEntry {
+                            entity: entity.clone(),
+                            deleted_at,
+                        };
+                        entries.entry(collection.name.clone()).or_default().insert(id.clone(), entry);
+                    }
+                }
+            }
+        })
+    }
+
+    /// Apply `TRASH:RESTORE { collection, id }` or `TRASH:PURGE { collection?, id? }`. Returns the
+    /// action that puts a restored entity back.
+    pub(crate) fn apply(&self, action: &ZubridgeAction) -> crate::Result<Option<ZubridgeAction>> {
+        let field = |name: &str| action.payload.as_ref().and_then(|payload| payload.get(name));
+        let name = field("collection").and_then(JsonValue::as_str);
+        let id = field("id").map(id_string);
+
+        if action.action_type == PURGE_ACTION {
+            self.update(|entries| match (name, &id) {
+                (Some(name), Some(id)) => {
+                    if let Some(collection) = entries.get_mut(name) {
+                        collection.remove(id);
+                    }
+                }
+                (Some(name), None) => {
+                    entries.remove(name);
+                }
+                _ => entries.clear(),
+            })?;
+            return Ok(None);
+        }
+
+        let (Some(name), Some(id)) = (name, id) else {
+            return Err(crate::Error::StateError(format!("{} requires a collection and an id", RESTORE_ACTION)));
+        };
+        let collection = self
+            .config
+            .collections
+            .iter()
+            .find(|collection| collection.name == name)
+            .ok_or_else(|| crate::Error::StateError(format!("no trash collection named {}", name)))?;
+        let mut restored = None;
+        self.update(|entries| {
+            restored = entries.get_mut(name).and_then(|collection| collection.remove(&id));
+        })?;
+        Ok(restored.map(|entry| ZubridgeAction {
+            action_type: collection.restore_action.clone(),
+            payload: Some(serde_json::json!({ "id": id, "entity": entry.entity })),
+        }))
+    }
+
+    /// Change the entries, purge expired ones and persist them.
+    fn update(&self, change: impl FnOnce(&mut TrashEntries)) -> crate::Result<()> {
+        let mut entries = self.entries.lock().map_err(|e| crate::Error::StateError(e.to_string()))?;
+        change(&mut entries);
+        let cutoff = now_millis().saturating_sub(self.config.retention.as_millis() as u64);
+        for collection in entries.values_mut() {
+            collection.retain(|_, entry| entry.deleted_at > cutoff);
+        }
+        entries.retain(|_, collection| !collection.is_empty());
+
+        let contents = serde_json::to_string(&*entries).map_err(|e| crate::Error::SerializationError(e.to_string()))?;
+        self.writer.write(self.path.clone(), contents);
+        Ok(())
+    }
+
+    /// Purge expired entries, e.g. on startup.
+    pub(crate) fn purge_expired(&self) -> crate::Result<()> {
+        self.update(|_| {})
+    }
+
+    pub(crate) fn state_key(&self) -> &str {
+        &self.config.state_key
+    }
+
+    pub(crate) fn slice(&self) -> crate::Result<JsonValue> {
+        let entries = self.entries.lock().map_err(|e| crate::Error::StateError(e.to_string()))?;
+        serde_json::to_value(&*entries).map_err(|e| crate::Error::SerializationError(e.to_string()))
+    }
+}
+
+pub(crate) fn is_trash_action(action_type: &str) -> bool {
+    matches!(action_type, RESTORE_ACTION | PURGE_ACTION)
+}
+
+fn id_string(id: &JsonValue) -> String {
+    match id {
+        JsonValue::String(id) => id.clone(),
+        other => other.to_string(),
+    }
+}
+
+/// The entities of the collection at `path`, keyed by id.
+fn entities_at<'a>(state: &'a JsonValue, path: &str) -> Option<&'a serde_json::Map<String, JsonValue>> {
+    let collection = path
+        .split('.')
+        .filter(|segment| !segment.is_empty())
+        .try_fold(state, |value, segment| value.get(segment))?;
+    collection.get("entities").unwrap_or(collection).as_object()
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+    use crate::error_policy::ErrorPolicy;
+    use crate::persistence::PersistenceConfig;
+    use crate::subsystems::Subsystems;
+
+    fn config() -> TrashConfig {
+        TrashConfig::default().collection("todos", "todos", "TODO:DELETE", "TODO:RESTORE")
+    }
+
+    fn action(action_type: &str, payload: JsonValue) -> ZubridgeAction {
+        ZubridgeAction {
+            action_type: action_type.to_string(),
+            payload: Some(payload),
+        }
+    }
+
+    /// The trash in `dir`, with the subsystems that write it.
+    fn load(config: TrashConfig, dir: &Path) -> (Trash, Subsystems) {
+        let subsystems = Subsystems::default();
+        let writer = PersistenceWriter::start(PersistenceConfig::default(), ErrorPolicy::default(), &subsystems);
+        (Trash::load(config, dir, writer), subsystems)
+    }
+
+    fn state() -> JsonValue {
+        json!({ "todos": { "ids": ["1", "2"], "entities": { "1": { "title": "a" }, "2": { "title": "b" } } } })
+    }
+
+    #[test]
+    fn restores_deleted_entities() {
+        let dir = tempfile::tempdir().unwrap();
+        let (trash, subsystems) = load(config(), dir.path());
+        assert!(trash.is_delete_action("TODO:DELETE"));
+        trash.observe_delete(&action("TODO:DELETE", json!({ "ids": ["1", "2"] })), &state()).unwrap();
+
+        let restore = action(RESTORE_ACTION, json!({ "collection": "todos", "id": "1" }));
+        let restored = trash.apply(&restore).unwrap().unwrap();
+        assert_eq!(restored.action_type, "TODO:RESTORE");
+        assert_eq!(restored.payload, Some(json!({ "id": "1", "entity": { "title": "a" } })));
+        assert!(trash.apply(&restore).unwrap().is_none());
+
+        // Persisted, so the trash survives a restart
+        subsystems.shutdown(Duration::from_secs(5));
+        let (reloaded, _subsystems) = load(config(), dir.path());
+        assert_eq!(reloaded.slice().unwrap(), json!({ "todos": { "2": trash.slice().unwrap()["todos"]["2"] } }));
+    }
+
+    #[test]
+    fn purges_expired_entries() {
+        let dir = tempfile::tempdir().unwrap();
+        let config = TrashConfig {
+            retention: Duration::ZERO,
+            ..config()
+        };
+        let (trash, _subsystems) = load(config, dir.path());
+        trash.observe_delete(&action("TODO:DELETE", json!({ "id": 1 })), &state()).unwrap();
+        assert_eq!(trash.slice().unwrap(), json!({}));
+    }
+
+    #[test]
+    fn purges_on_request() {
+        let dir = tempfile::tempdir().unwrap();
+        let (trash, _subsystems) = load(config(), dir.path());
+        trash.observe_delete(&action("TODO:DELETE", json!({ "ids": ["1", "2"] })), &state()).unwrap();
+        trash.apply(&action(PURGE_ACTION, json!({ "collection": "todos", "id": "1" }))).unwrap();
+        assert_eq!(trash.slice().unwrap()["todos"].as_object().unwrap().len(), 1);
+        trash.apply(&action(PURGE_ACTION, json!({}))).unwrap();
+        assert_eq!(trash.slice().unwrap(), json!({}));
+    }
+}
@@ -0,0 +1,193 @@
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use serde::Serialize;
+use tauri::menu::{Menu, MenuItemKind};
+use tauri::Runtime;
+
+use crate::models::*;
+use crate::utils::get_path;
+
+/// Decides from the current state whether a menu item is enabled.
+pub type StatePredicate = Arc<dyn Fn(&JsonValue) -> bool + Send + Sync>;
+
+/// A check menu item bound to a state path.
+#[derive(Clone, Debug)]
+struct CheckedBinding {
+    item_id: String,
+    path: String,
+    /// Checked while the path equals this value, or while it's `true` if unset.
+    value: Option<JsonValue>,
+}
+
+/// Maps tray and menu item ids to the actions dispatched when they're clicked, and enables,
+/// disables, checks or unchecks items from the state.
+///
+/// Items are looked up in every menu passed to `Zubridge::bind_menu`, including submenus.
+#[derive(Clone, Default)]
+pub struct TrayActionMap {
+    actions: HashMap<String, ZubridgeAction>,
+    enabled_when: Vec<(String, StatePredicate)>,
+    checked: Vec<CheckedBinding>,
+}
+
+impl TrayActionMap {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Dispatch `action_type` when the item `item_id` is clicked.
+    pub fn action(self, item_id: &str, action_type: &str) -> Self {
+        self.insert(item_id, action_type, None)
+    }
+
+    /// Dispatch `action_type` with `payload` when the item `item_id` is clicked.
+    pub fn action_with_payload(self, item_id: &str, action_type: &str, payload: impl Into<JsonValue>) -> Self {
+        self.insert(item_id, action_type, Some(payload.into()))
+    }
+
+    /// Dispatch a typed action when the item `item_id` is clicked. Fails if the action doesn't
+    /// serialize to a `{ type, payload }` object, see [`ZubridgeAction::from_typed`].
+    pub fn typed_action<A: Serialize>(mut self, item_id: &str, action: &A) -> crate::Result<Self> {
+        self.actions.insert(item_id.to_string(), ZubridgeAction::from_typed(action)?);
+        Ok(self)
+    }
+
+    /// Enable the item `item_id` only while `predicate` holds for the emitted state.
+    pub fn enabled_when<F>(mut self, item_id: &str, predicate: F) -> Self
+    where
+        F: Fn(&JsonValue) -> bool + Send + Sync + 'static,
+    {
+        self.enabled_when.push((item_id.to_string(), Arc::new(predicate)));
+        self
+    }
+
+    /// Bind a check item to a boolean state path (e.g. `theme.is_dark`) and dispatch
+    /// `action_type` when it's clicked.
+    pub fn checked(mut self, item_id: &str, path: &str, action_type: &str) -> Self {
+        self.checked.push(CheckedBinding {
+            item_id: item_id.to_string(),
+            path: path.to_string(),
+            value: None,
+        });
+        self.insert(item_id, action_type, None)
+    }
+
+    /// Bind one of a group of radio-style check items to an enum state path. The item is
+    /// checked while the path equals `value`, and clicking it dispatches `action_type` with
+    /// `value` as payload.
+    pub fn checked_value(mut self, item_id: &str, path: &str, value: impl Into<JsonValue>, action_type: &str) -> Self {
+        let value = value.into();
+        self.checked.push(CheckedBinding {
+            item_id: item_id.to_string(),
+            path: path.to_string(),
+            value: Some(value.clone()),
+        });
+        self.insert(item_id, action_type, Some(value))
+    }
+
+    fn insert(mut self, item_id: &str, action_type: &str, payload: Option<JsonValue>) -> Self {
+        let action = ZubridgeAction {
+            action_type: action_type.to_string(),
+            payload,
+        };
+        self.actions.insert(item_id.to_string(), action);
+        self
+    }
+
+    /// The action mapped to a menu item, if any.
+    pub fn get(&self, item_id: &str) -> Option<&ZubridgeAction> {
+        self.actions.get(item_id)
+    }
+
+    /// Enable, disable, check or uncheck the items of `menu` according to `state`.
+    pub(crate) fn update_menu<R: Runtime>(&self, menu: &Menu<R>, state: &JsonValue) -> crate::Result<()> {
+        let items = menu.items()?;
+        for (item_id, predicate) in &self.enabled_when {
+            if let Some(item) = find_item(&items, item_id)? {
+                set_enabled(&item, predicate(state))?;
+            }
+        }
+
+        for binding in &self.checked {
+            let Some(item) = find_item(&items, &binding.item_id)? else { continue };
+            let Some(item) = item.as_check_menuitem() else {
+                log::warn!("zubridge: menu item {} is bound to {} but isn't a check item", binding.item_id, binding.path);
+                continue;
+            };
+            let current = get_path(state, &binding.path);
+            let checked = match &binding.value {
+                Some(value) => current == Some(value),
+                None => current.and_then(JsonValue::as_bool).unwrap_or(false),
+            };
+            item.set_checked(checked)?;
+        }
+        Ok(())
+    }
+}
+
+/// Find an item by id, searching submenus too.
+pub(crate) fn find_item<R: Runtime>(items: &[MenuItemKind<R>], id: &str) -> crate::Result<Option<MenuItemKind<R>>> {
+    for item in items {
+        if item.id() == id {
+            return Ok(Some(item.clone()));
+        }
+        if let Some(submenu) = item.as_submenu() {
+            if let Some(found) = find_item(&submenu.items()?, id)? {
+                return Ok(Some(found));
+            }
+        }
+    }
+    Ok(None)
+}
+
+fn set_enabled<R: Runtime>(item: &MenuItemKind<R>, enabled: bool) -> crate::Result<()> {
+    match item {
+        MenuItemKind::MenuItem(item) => item.set_enabled(enabled)?,
+        MenuItemKind::Check(item) => item.set_enabled(enabled)?,
+        MenuItemKind::Icon(item) => item.set_enabled(enabled)?,
+        MenuItemKind::Submenu(item) => item.set_enabled(enabled)?,
+        MenuItemKind::Predefined(_) => {}
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+    use tauri::menu::{CheckMenuItem, MenuItem, Submenu};
+
+    #[test]
+    fn maps_items_to_actions() {
+        let map = TrayActionMap::new()
+            .action("quit", "APP:QUIT")
+            .action_with_payload("zoom", "VIEW:ZOOM", 2)
+            .checked_value("dark", "theme", "dark", "THEME:SET");
+        assert_eq!(map.get("quit").unwrap().action_type, "APP:QUIT");
+        assert_eq!(map.get("zoom").unwrap().payload, Some(json!(2)));
+        assert_eq!(map.get("dark").unwrap().payload, Some(json!("dark")));
+        assert!(map.get("other").is_none());
+        assert!(TrayActionMap::new().typed_action("quit", &"APP:QUIT").is_err());
+    }
+
+    #[test]
+    fn updates_items_from_the_state() {
+        let app = tauri::test::mock_app();
+        let save = MenuItem::with_id(&app, "save", "Save", true, None::<&str>).unwrap();
+        let dark = CheckMenuItem::with_id(&app, "dark", "Dark", true, false, None::<&str>).unwrap();
+        let sidebar = CheckMenuItem::with_id(&app, "sidebar", "Sidebar", true, true, None::<&str>).unwrap();
+        let view = Submenu::with_items(&app, "View", true, &[&dark, &sidebar]).unwrap();
+        let menu = Menu::with_items(&app, &[&save, &view]).unwrap();
+        let map = TrayActionMap::new()
+            .enabled_when("save", |state| state["dirty"] == true)
+            .checked_value("dark", "theme", "dark", "THEME:SET")
+            .checked("sidebar", "layout.sidebar", "LAYOUT:TOGGLE_SIDEBAR");
+
+        map.update_menu(&menu, &json!({ "dirty": false, "theme": "dark", "layout": {} })).unwrap();
+        assert!(!save.is_enabled().unwrap());
+        assert!(dark.is_checked().unwrap());
+        assert!(!sidebar.is_checked().unwrap());
+        assert!(find_item(&menu.items().unwrap(), "sidebar").unwrap().is_some());
+    }
+}
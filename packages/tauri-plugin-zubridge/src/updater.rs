@@ -0,0 +1,241 @@
+use std::sync::{Arc, Mutex};
+
+use serde::Serialize;
+
+use crate::models::*;
+
+pub(crate) const CHECK_ACTION: &str = "UPDATER:CHECK";
+pub(crate) const INSTALL_ACTION: &str = "UPDATER:INSTALL";
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum UpdaterStatus {
+    #[default]
+    Idle,
+    Checking,
+    UpToDate,
+    Available,
+    Downloading,
+    Installed,
+    Error,
+}
+
+/// The contents of the `updater` slice.
+#[derive(Clone, Debug, Default, Serialize)]
+pub struct UpdaterState {
+    pub status: UpdaterStatus,
+    /// The version of the available update.
+    pub version: Option<String>,
+    pub notes: Option<String>,
+    /// Bytes downloaded so far.
+    pub downloaded: u64,
+    /// The size of the update, if known.
+    pub total: Option<u64>,
+    pub error: Option<String>,
+}
+
+/// Checks for and installs updates, typically with `tauri-plugin-updater`.
+///
+/// Both methods are called on a background thread and report what happens through the reporter.
+pub trait UpdaterBackend: Send + Sync + 'static {
+    /// Check for an update, then call [`UpdateReporter::available`] or [`UpdateReporter::up_to_date`].
+    fn check(&self, reporter: UpdateReporter);
+
+    /// Download and install the update found by the last check.
+    fn install(&self, reporter: UpdateReporter);
+}
+
+/// Updates the `updater` slice from an [`UpdaterBackend`] and emits the new state.
+#[derive(Clone)]
+pub struct UpdateReporter {
+    state: Arc<Mutex<UpdaterState>>,
+    notify: Arc<dyn Fn() + Send + Sync>,
+}
+
+impl UpdateReporter {
+    pub fn available(&self, version: &str, notes: Option<String>) {
+        self.update(|state| {
+            state.status = UpdaterStatus::Available;
+            state.version = Some(version.to_string());
+            state.notes = notes;
+        });
+    }
+
+    pub fn up_to_date(&self) {
+        self.update(|state| state.status = UpdaterStatus::UpToDate);
+    }
+
+    /// Report a downloaded chunk of `chunk_length` bytes.
+    pub fn downloading(&self, chunk_length: u64, total: Option<u64>) {
+        self.update(|state| {
+            state.status = UpdaterStatus::Downloading;
+            state.downloaded += chunk_length;
+            state.total = total;
+        });
+    }
+
+    pub fn installed(&self) {
+        self.update(|state| state.status = UpdaterStatus::Installed);
+    }
+
+    pub fn failed(&self, error: impl ToString) {
+        self.update(|state| {
+            state.status = UpdaterStatus::Error;
+            state.error = Some(error.to_string());
+        });
+    }
+
+    fn update(&self, apply: impl FnOnce(&mut UpdaterState)) {
+        match self.state.lock() {
+            Ok(mut state) => apply(&mut state),
+            Err(e) => {
+                log::warn!("zubridge: failed to update the updater slice: {}", e);
+                return;
+            }
+        }
+        (self.notify)();
+    }
+}
+
+/// Mirrors an [`UpdaterBackend`] into the `state_key` slice and handles `UPDATER:CHECK` and
+/// `UPDATER:INSTALL`.
+#[derive(Clone)]
+pub struct UpdaterConfig {
+    backend: Arc<dyn UpdaterBackend>,
+    /// The state key the updater status is stored under. Defaults to "updater".
+    pub state_key: String,
+}
+
+impl UpdaterConfig {
+    pub fn new<B: UpdaterBackend>(backend: B) -> Self {
+        Self {
+            backend: Arc::new(backend),
+            state_key: "updater".to_string(),
+        }
+    }
+}
+
+pub(crate) struct Updater {
+    config: UpdaterConfig,
+    state: Arc<Mutex<UpdaterState>>,
+}
+
+impl Updater {
+    pub(crate) fn new(config: UpdaterConfig) -> Self {
+        Self {
+            config,
+            state: Arc::new(Mutex::new(UpdaterState::default())),
+        }
+    }
+
+    /// Start checking or installing on a background thread. `notify` emits the state.
+    pub(crate) fn start(&self, action_type: &str, notify: Arc<dyn Fn() + Send + Sync>) -> crate::Result<()> {
+        {
+            let mut state = self.state.lock().map_err(|e| crate::Error::StateError(e.to_string()))?;
+            if matches!(state.status, UpdaterStatus::Checking | UpdaterStatus::Downloading) {
+                // Already busy
+                return Ok(());
+            }
+            if action_type == CHECK_ACTION {
+                *state = UpdaterState {
+                    status: UpdaterStatus::Checking,
+                    ..Default::default()
+                };
+            } else {
+                state.status = UpdaterStatus::Downloading;
+                state.downloaded = 0;
+                state.error = None;
+            }
+        }
+
+        let reporter = UpdateReporter {
+            state: self.state.clone(),
+            notify,
+        };
+        let backend = self.config.backend.clone();
+        let install = action_type == INSTALL_ACTION;
+        std::thread::spawn(move || {
+            if install {
+                backend.install(reporter);
+            } else {
+                backend.check(reporter);
+            }
+        });
+        Ok(())
+    }
+
+    pub(crate) fn state_key(&self) -> &str {
+        &self.config.state_key
+    }
+
+    pub(crate) fn slice(&self) -> crate::Result<JsonValue> {
+        let state = self.state.lock().map_err(|e| crate::Error::StateError(e.to_string()))?;
+        serde_json::to_value(&*state).map_err(|e| crate::Error::SerializationError(e.to_string()))
+    }
+}
+
+pub(crate) fn is_updater_action(action_type: &str) -> bool {
+    matches!(action_type, CHECK_ACTION | INSTALL_ACTION)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+    use std::sync::mpsc;
+    use std::time::Duration;
+
+    /// Finds version 2.0 and installs it in two chunks.
+    struct Backend;
+
+    impl UpdaterBackend for Backend {
+        fn check(&self, reporter: UpdateReporter) {
+            reporter.available("2.0.0", Some("notes".into()));
+        }
+
+        fn install(&self, reporter: UpdateReporter) {
+            reporter.downloading(10, Some(20));
+            reporter.downloading(10, Some(20));
+            reporter.installed();
+        }
+    }
+
+    fn start(updater: &Updater, action_type: &str, notifications: usize) {
+        let (sender, receiver) = mpsc::channel();
+        let notify: Arc<dyn Fn() + Send + Sync> = Arc::new(move || {
+            let _ = sender.send(());
+        });
+        updater.start(action_type, notify).unwrap();
+        for _ in 0..notifications {
+            receiver.recv_timeout(Duration::from_secs(5)).unwrap();
+        }
+    }
+
+    #[test]
+    fn mirrors_checks_and_installs_into_the_slice() {
+        let updater = Updater::new(UpdaterConfig::new(Backend));
+        assert_eq!(updater.state_key(), "updater");
+        assert_eq!(updater.slice().unwrap()["status"], "idle");
+
+        start(&updater, CHECK_ACTION, 1);
+        let slice = updater.slice().unwrap();
+        assert_eq!(slice["status"], "available");
+        assert_eq!(slice["version"], "2.0.0");
+
+        start(&updater, INSTALL_ACTION, 3);
+        let slice = updater.slice().unwrap();
+        assert_eq!(slice["status"], "installed");
+        assert_eq!((slice["downloaded"].clone(), slice["total"].clone()), (json!(20), json!(20)));
+        assert_eq!(slice["version"], "2.0.0");
+    }
+
+    #[test]
+    fn ignores_actions_while_busy() {
+        let updater = Updater::new(UpdaterConfig::new(Backend));
+        updater.state.lock().unwrap().status = UpdaterStatus::Downloading;
+        start(&updater, CHECK_ACTION, 0);
+        assert_eq!(updater.slice().unwrap()["status"], "downloading");
+        assert!(is_updater_action(INSTALL_ACTION));
+        assert!(!is_updater_action("UPDATER:CANCEL"));
+    }
+}
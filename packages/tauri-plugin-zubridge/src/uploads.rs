@@ -0,0 +1,229 @@
+//! Binary payloads too large for an action, sent in chunks through `zubridge.upload-blob` and
+//! written to a temp file. Once the last chunk arrives, an action referencing the file by an
+//! opaque id is dispatched in its place, and the app claims the file with
+//! `Zubridge::take_upload`.
+
+use std::collections::hash_map::Entry;
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::Write;
+use std::path::PathBuf;
+use std::sync::Mutex;
+
+use tempfile::TempDir;
+
+use crate::models::*;
+
+/// The upload a chunk belongs to, chosen by the frontend.
+pub(crate) const UPLOAD_ID_HEADER: &str = "zubridge-upload-id";
+/// Sent with the last chunk: the type of the action to dispatch once the file is complete.
+pub(crate) const ACTION_TYPE_HEADER: &str = "zubridge-action-type";
+/// Sent with the last chunk: an optional JSON object merged into the action's payload.
+pub(crate) const PAYLOAD_HEADER: &str = "zubridge-payload";
+
+struct Upload {
+    path: PathBuf,
+    file: File,
+    bytes: u64,
+}
+
+#[derive(Default)]
+struct State {
+    /// Created on the first upload, private to the current user, and deleted with everything
+    /// in it on shutdown.
+    dir: Option<TempDir>,
+    /// Uploads in progress, by window and the frontend's id.
+    pending: HashMap<(String, String), Upload>,
+    /// Finished uploads the app hasn't claimed yet, by the id in their action.
+    finished: HashMap<String, PathBuf>,
+}
+
+pub(crate) struct Uploads {
+    max_bytes: Option<u64>,
+    max_pending: usize,
+    state: Mutex<State>,
+}
+
+impl Uploads {
+    pub(crate) fn new(max_bytes: Option<u64>, max_pending: usize) -> Self {
+        Self {
+            max_bytes,
+            max_pending,
+            state: Mutex::new(State::default()),
+        }
+    }
+
+    /// Append `chunk` to the upload `id` from `window`, starting it if it's the first one.
+    /// Fails, dropping the upload, once it's over `max_bytes`.
+    pub(crate) fn append(&self, window: &str, id: &str, chunk: &[u8]) -> crate::Result<()> {
+        let mut state = self.state.lock().map_err(|e| crate::Error::StateError(e.to_string()))?;
+        let State { dir, pending, .. } = &mut *state;
+        let window_pending = pending.keys().filter(|(label, _)| label == window).count();
+        let upload = match pending.entry((window.to_string(), id.to_string())) {
+            Entry::Occupied(entry) => entry.into_mut(),
+            Entry::Vacant(_) if window_pending >= self.max_pending => {
+                return Err(crate::Error::UploadError(format!(
+                    "{} already has {} uploads in progress",
+                    window, self.max_pending
+                )));
+            }
+            Entry::Vacant(entry) => {
+                let dir = match dir {
+                    Some(dir) => dir,
+                    None => dir.insert(private_dir()?),
+                };
+                // The frontend's id never ends up in the path
+                let path = dir.path().join(format!("{}.bin", uuid::Uuid::new_v4()));
+                let file = File::create(&path)?;
+                entry.insert(Upload { path, file, bytes: 0 })
+            }
+        };
+
+        let bytes = upload.bytes + chunk.len() as u64;
+        if let Some(limit) = self.max_bytes.filter(|limit| bytes > *limit) {
+            if let Some(upload) = pending.remove(&(window.to_string(), id.to_string())) {
+                delete(&upload.path);
+            }
+            return Err(crate::Error::UploadTooLarge { bytes, limit });
+        }
+        upload.file.write_all(chunk)?;
+        upload.bytes = bytes;
+        Ok(())
+    }
+
+    /// Close the upload `id` from `window`, returning the id its action refers to it by, and
+    /// its size.
+    pub(crate) fn finish(&self, window: &str, id: &str) -> crate::Result<(String, u64)> {
+        let mut state = self.state.lock().map_err(|e| crate::Error::StateError(e.to_string()))?;
+        let mut upload = state
+            .pending
+            .remove(&(window.to_string(), id.to_string()))
+            .ok_or_else(|| crate::Error::UploadError(format!("unknown upload: {}", id)))?;
+        if let Err(e) = upload.file.flush() {
+            delete(&upload.path);
+            return Err(e.into());
+        }
+        // Random, so a window can't name another window's upload, or any other file
+        let upload_id = uuid::Uuid::new_v4().to_string();
+        state.finished.insert(upload_id.clone(), upload.path);
+        Ok((upload_id, upload.bytes))
+    }
+
+    /// Claim a finished upload, returning the path of its file.
+    pub(crate) fn take(&self, upload_id: &str) -> crate::Result<PathBuf> {
+        let mut state = self.state.lock().map_err(|e| crate::Error::StateError(e.to_string()))?;
+        state
+            .finished
+            .remove(upload_id)
+            .ok_or_else(|| crate::Error::UploadError(format!("unknown upload: {}", upload_id)))
+    }
+
+    /// Delete a finished upload the app won't claim, e.g. because its action failed.
+    pub(crate) fn forget(&self, upload_id: &str) {
+        if let Ok(path) = self.take(upload_id) {
+            delete(&path);
+        }
+    }
+
+    /// Delete the unfinished uploads from `window`.
+    pub(crate) fn discard(&self, window: &str) {
+        let Ok(mut state) = self.state.lock() else { return };
+        state.pending.retain(|(label, _), upload| {
+            if label != window {
+                return true;
+            }
+            delete(&upload.path);
+            false
+        });
+    }
+
+    /// Delete the temp directory, with every upload in it, claimed or not.
+    pub(crate) fn clear(&self) {
+        let Ok(mut state) = self.state.lock() else { return };
+        if let Some(Err(e)) = std::mem::take(&mut *state).dir.map(TempDir::close) {
+            log::debug!("zubridge: failed to delete the uploads directory: {}", e);
+        }
+    }
+}
+
+fn private_dir() -> crate::Result<TempDir> {
+    let mut builder = tempfile::Builder::new();
+    builder.prefix("zubridge-uploads-");
+    #[cfg(unix)]
+    builder.permissions(std::os::unix::fs::PermissionsExt::from_mode(0o700));
+    Ok(builder.tempdir()?)
+}
+
+fn delete(path: &std::path::Path) {
+    if let Err(e) = std::fs::remove_file(path) {
+        log::debug!("zubridge: failed to delete upload {}: {}", path.display(), e);
+    }
+}
+
+/// The action dispatched for a finished upload: `payload`, an object, with the `upload` id to
+/// claim the file with and its size in `bytes` added.
+pub(crate) fn upload_action(
+    action_type: String,
+    payload: Option<JsonValue>,
+    upload_id: &str,
+    bytes: u64,
+) -> crate::Result<ZubridgeAction> {
+    let mut fields = match payload {
+        None => serde_json::Map::new(),
+        Some(JsonValue::Object(fields)) => fields,
+        Some(_) => return Err(crate::Error::UploadError("the upload payload is not an object".into())),
+    };
+    fields.insert("upload".into(), JsonValue::String(upload_id.to_string()));
+    fields.insert("bytes".into(), bytes.into());
+    Ok(ZubridgeAction {
+        action_type,
+        payload: Some(JsonValue::Object(fields)),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn finished_uploads_are_claimed_by_id() {
+        let uploads = Uploads::new(None, 4);
+        uploads.append("main", "a", b"hello ").unwrap();
+        uploads.append("main", "a", b"world").unwrap();
+        let (upload_id, bytes) = uploads.finish("main", "a").unwrap();
+        assert_eq!(bytes, 11);
+
+        let path = uploads.take(&upload_id).unwrap();
+        assert_eq!(std::fs::read(&path).unwrap(), b"hello world");
+        assert!(uploads.take(&upload_id).is_err());
+    }
+
+    #[test]
+    fn uploads_over_the_limit_are_dropped() {
+        let uploads = Uploads::new(Some(8), 4);
+        uploads.append("main", "a", b"12345").unwrap();
+        let result = uploads.append("main", "a", b"67890");
+        assert!(matches!(result, Err(crate::Error::UploadTooLarge { bytes: 10, limit: 8 })));
+        assert!(uploads.finish("main", "a").is_err());
+    }
+
+    #[test]
+    fn uploads_in_progress_are_capped_per_window() {
+        let uploads = Uploads::new(None, 1);
+        uploads.append("main", "a", b"1").unwrap();
+        assert!(uploads.append("main", "b", b"1").is_err());
+        uploads.append("other", "b", b"1").unwrap();
+    }
+
+    #[test]
+    fn forgotten_uploads_are_deleted() {
+        let uploads = Uploads::new(None, 4);
+        uploads.append("main", "a", b"data").unwrap();
+        let (upload_id, _) = uploads.finish("main", "a").unwrap();
+        let dir = uploads.state.lock().unwrap().dir.as_ref().unwrap().path().to_path_buf();
+        uploads.forget(&upload_id);
+        assert_eq!(std::fs::read_dir(&dir).unwrap().count(), 0);
+        uploads.clear();
+        assert!(!dir.exists());
+    }
+}
@@ -0,0 +1,184 @@
+use hmac::{Hmac, Mac};
+use sha2::Sha256;
+
+/// Match a window label or state path against a pattern where `*` matches any run of characters.
+pub(crate) fn glob_matches(pattern: &str, text: &str) -> bool {
+    let mut parts = pattern.split('*');
+    let first = parts.next().unwrap_or_default();
+    let Some(mut rest) = text.strip_prefix(first) else {
+        return false;
+    };
+
+    let parts: Vec<&str> = parts.collect();
+    let Some((last, middle)) = parts.split_last() else {
+        // No wildcard at all
+        return rest.is_empty();
+    };
+
+    for part in middle {
+        match rest.find(part) {
+            Some(index) => rest = &rest[index + part.len()..],
+            None => return false,
+        }
+    }
+    rest.ends_with(last)
+}
+
+/// The directory the plugin keeps its own files in, created on first use.
+pub(crate) fn plugin_data_dir<R: tauri::Runtime>(app: &tauri::AppHandle<R>) -> crate::Result<std::path::PathBuf> {
+    use tauri::Manager;
+
+    let dir = app.path().app_data_dir()?.join("zubridge");
+    std::fs::create_dir_all(&dir)?;
+    Ok(dir)
+}
+
+/// The origin of the page shown in the window `label`, which the plugin's protocols allow to
+/// read their responses, like Tauri's asset protocol. Other origins, e.g. an embedded frame,
+/// can't.
+pub(crate) fn window_origin<R: tauri::Runtime>(app: &tauri::AppHandle<R>, label: &str) -> Option<String> {
+    use tauri::Manager;
+
+    let url = app.get_webview_window(label)?.url().ok()?;
+    let host = url.host_str()?;
+    Some(match url.port() {
+        Some(port) => format!("{}://{}:{}", url.scheme(), host, port),
+        None => format!("{}://{}", url.scheme(), host),
+    })
+}
+
+/// A random 256-bit key from the OS's secure random number generator.
+pub(crate) fn random_key() -> [u8; 32] {
+    use chacha20poly1305::aead::{KeyInit, OsRng};
+
+    chacha20poly1305::ChaCha20Poly1305::generate_key(&mut OsRng).into()
+}
+
+/// A 64-bit FNV-1a hash, which unlike `DefaultHasher` is stable across Rust releases.
+pub(crate) fn stable_hash(input: &str) -> u64 {
+    input.bytes().fold(0xcbf29ce484222325, |hash, byte| {
+        (hash ^ u64::from(byte)).wrapping_mul(0x100000001b3)
+    })
+}
+
+/// HMAC-SHA256 of `message` keyed with `key`.
+pub(crate) fn hmac_sha256(key: &[u8], message: &[u8]) -> [u8; 32] {
+    let mut mac = Hmac::<Sha256>::new_from_slice(key).expect("HMAC takes keys of any length");
+    mac.update(message);
+    mac.finalize().into_bytes().into()
+}
+
+/// Lowercase hex encoding of `bytes`.
+pub(crate) fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|byte| format!("{:02x}", byte)).collect()
+}
+
+/// Decode hex, in either case. Returns `None` if `text` isn't valid hex.
+pub(crate) fn unhex(text: &str) -> Option<Vec<u8>> {
+    if text.len() % 2 != 0 {
+        return None;
+    }
+    (0..text.len())
+        .step_by(2)
+        .map(|index| u8::from_str_radix(text.get(index..index + 2)?, 16).ok())
+        .collect()
+}
+
+/// Run a call into the state manager, turning a panic into [`crate::Error::ReducerPanicked`].
+/// The panic is caught while the state manager's lock is still held, so the mutex isn't poisoned.
+pub(crate) fn catch_reducer_panic<T>(call: impl FnOnce() -> T) -> crate::Result<T> {
+    std::panic::catch_unwind(std::panic::AssertUnwindSafe(call)).map_err(|panic| {
+        let message = panic
+            .downcast_ref::<&str>()
+            .map(|message| message.to_string())
+            .or_else(|| panic.downcast_ref::<String>().cloned())
+            .unwrap_or_else(|| "unknown panic".to_string());
+        crate::Error::ReducerPanicked { message }
+    })
+}
+
+/// Milliseconds since the Unix epoch.
+pub(crate) fn now_millis() -> u64 {
+    std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map(|elapsed| elapsed.as_millis() as u64)
+        .unwrap_or_default()
+}
+
+/// Replace the values at a dot-separated path with `"[redacted]"`.
+/// A `*` segment matches every key of an object or element of an array.
+pub(crate) fn redact_path(value: &mut serde_json::Value, path: &str) {
+    let segments: Vec<&str> = path.split('.').filter(|segment| !segment.is_empty()).collect();
+    redact_segments(value, &segments);
+}
+
+fn redact_segments(value: &mut serde_json::Value, segments: &[&str]) {
+    let Some((segment, rest)) = segments.split_first() else {
+        *value = serde_json::Value::String("[redacted]".to_string());
+        return;
+    };
+
+    match value {
+        serde_json::Value::Object(map) if *segment == "*" => {
+            map.values_mut().for_each(|child| redact_segments(child, rest));
+        }
+        serde_json::Value::Object(map) => {
+            if let Some(child) = map.get_mut(*segment) {
+                redact_segments(child, rest);
+            }
+        }
+        serde_json::Value::Array(items) if *segment == "*" => {
+            items.iter_mut().for_each(|child| redact_segments(child, rest));
+        }
+        serde_json::Value::Array(items) => {
+            if let Some(child) = segment.parse::<usize>().ok().and_then(|index| items.get_mut(index)) {
+                redact_segments(child, rest);
+            }
+        }
+        _ => {}
+    }
+}
+
+/// Get the value at a dot-separated path, where numeric segments index into arrays.
+/// An empty path returns the whole value.
+pub(crate) fn get_path<'a>(value: &'a serde_json::Value, path: &str) -> Option<&'a serde_json::Value> {
+    path.split('.')
+        .filter(|segment| !segment.is_empty())
+        .try_fold(value, |current, segment| match current {
+            serde_json::Value::Object(map) => map.get(segment),
+            serde_json::Value::Array(items) => segment.parse::<usize>().ok().and_then(|index| items.get(index)),
+            _ => None,
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn matches_globs() {
+        assert!(glob_matches("main", "main"));
+        assert!(!glob_matches("main", "main-2"));
+        assert!(glob_matches("settings-*", "settings-general"));
+        assert!(glob_matches("*:*:done", "todos:list:done"));
+        assert!(glob_matches("*", ""));
+        assert!(!glob_matches("a*b*c", "acb"));
+    }
+
+    #[test]
+    fn catches_reducer_panics_without_poisoning_the_lock() {
+        let state = std::sync::Mutex::new(1);
+        let mut guard = state.lock().unwrap();
+        let Err(crate::Error::ReducerPanicked { message }) = catch_reducer_panic(|| {
+            *guard += 1;
+            panic!("reducer failed for {}", *guard)
+        }) else {
+            panic!("expected the panic to be caught");
+        };
+        drop(guard);
+
+        assert_eq!(message, "reducer failed for 2");
+        assert!(!state.is_poisoned());
+        assert_eq!(catch_reducer_panic(|| *state.lock().unwrap()).unwrap(), 2);
+    }
+}
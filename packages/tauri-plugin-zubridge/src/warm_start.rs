@@ -0,0 +1,195 @@
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use crate::models::*;
+use crate::persistence::PersistenceWriter;
+use crate::sensitive::SensitivePaths;
+use crate::utils::redact_path;
+
+const CACHE_FILE: &str = "warm_start.json";
+
+/// What's kept of the last emitted state for the next launch.
+#[derive(Clone, Debug)]
+pub struct WarmStartConfig {
+    /// Top-level slices kept, e.g. the ones the first screen renders. Every slice is kept
+    /// when empty.
+    pub slices: Vec<String>,
+    /// Dot-separated paths replaced with `"[redacted]"` before writing. `*` matches any key.
+    pub redact_paths: Vec<String>,
+    /// States larger than this once encoded aren't kept. Defaults to 256 KiB.
+    pub max_bytes: usize,
+    /// How often the cache is written at most. Defaults to 2 seconds.
+    pub write_interval: Duration,
+}
+
+impl Default for WarmStartConfig {
+    fn default() -> Self {
+        Self {
+            slices: Vec::new(),
+            redact_paths: Vec::new(),
+            max_bytes: 256 * 1024,
+            write_interval: Duration::from_secs(2),
+        }
+    }
+}
+
+/// The last emitted state, written to `warm_start.json` in the plugin's data directory.
+pub(crate) struct WarmStartCache {
+    config: WarmStartConfig,
+    path: PathBuf,
+    sensitive: Option<Arc<SensitivePaths>>,
+    writer: Arc<PersistenceWriter>,
+    /// The latest state not written yet, and when the cache was last written.
+    pending: Mutex<(Option<JsonValue>, Option<Instant>)>,
+}
+
+impl WarmStartCache {
+    pub(crate) fn new(
+        config: WarmStartConfig,
+        data_dir: &Path,
+        sensitive: Option<Arc<SensitivePaths>>,
+        writer: Arc<PersistenceWriter>,
+    ) -> Self {
+        Self {
+            config,
+            path: data_dir.join(CACHE_FILE),
+            sensitive,
+            writer,
+            pending: Mutex::new((None, None)),
+        }
+    }
+
+    /// The state cached by the previous launch, if any. An unreadable cache, or one encrypted
+    /// with another key, is ignored.
+    pub(crate) fn cached(&self) -> Option<JsonValue> {
+        let contents = std::fs::read_to_string(&self.path).ok()?;
+        let mut state = serde_json::from_str(&contents).ok()?;
+        if let Some(sensitive) = &self.sensitive {
+            if let Err(e) = sensitive.decrypt(&mut state) {
+                log::warn!("zubridge: ignoring the warm start cache: {}", e);
+                return None;
+            }
+        }
+        Some(state)
+    }
+
+    /// Keep `state` for the next launch, writing it unless the cache was written within
+    /// `write_interval`.
+    pub(crate) fn store(&self, state: &JsonValue) -> crate::Result<()> {
+        let state = self.prepare(state)?;
+        let mut pending = self.pending.lock().map_err(|e| crate::Error::StateError(e.to_string()))?;
+        pending.0 = Some(state);
+        if pending.1.is_some_and(|written| written.elapsed() < self.config.write_interval) {
+            return Ok(());
+        }
+        let Some(state) = pending.0.take() else { return Ok(()) };
+        pending.1 = Some(Instant::now());
+        drop(pending);
+        self.write(&state)
+    }
+
+    /// Queue the state held back by `store` for writing, e.g. on shutdown.
+    pub(crate) fn flush(&self) {
+        let Some(state) = self.pending.lock().ok().and_then(|mut pending| pending.0.take()) else { return };
+        if let Err(e) = self.write(&state) {
+            log::warn!("zubridge: failed to write the warm start cache: {}", e);
+        }
+    }
+
+    fn prepare(&self, state: &JsonValue) -> crate::Result<JsonValue> {
+        let mut state = match (state, self.config.slices.is_empty()) {
+            (JsonValue::Object(root), false) => JsonValue::Object(
+                root.iter()
+                    .filter(|(key, _)| self.config.slices.contains(key))
+                    .map(|(key, value)| (key.clone(), value.clone()))
+                    .collect(),
+            ),
+            (state, _) => state.clone(),
+        };
+        for path in &self.config.redact_paths {
+            redact_path(&mut state, path);
+        }
+        if let Some(sensitive) = &self.sensitive {
+            sensitive.encrypt(&mut state)?;
+        }
+        Ok(state)
+    }
+
+    fn write(&self, state: &JsonValue) -> crate::Result<()> {
+        let contents = serde_json::to_string(state).map_err(|e| crate::Error::SerializationError(e.to_string()))?;
+        if contents.len() > self.config.max_bytes {
+            // A stale cache would be worse than none
+            self.writer.remove(self.path.clone());
+        } else {
+            self.writer.write(self.path.clone(), contents);
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::error_policy::ErrorPolicy;
+    use crate::persistence::PersistenceConfig;
+    use crate::subsystems::Subsystems;
+    use serde_json::json;
+
+    fn cache(config: WarmStartConfig, data_dir: &Path) -> (WarmStartCache, Subsystems) {
+        let subsystems = Subsystems::default();
+        let writer = PersistenceWriter::start(PersistenceConfig::default(), ErrorPolicy::default(), &subsystems);
+        (WarmStartCache::new(config, data_dir, None, writer), subsystems)
+    }
+
+    #[test]
+    fn keeps_the_configured_slices_redacted() {
+        let dir = tempfile::tempdir().unwrap();
+        let config = WarmStartConfig {
+            slices: vec!["user".into()],
+            redact_paths: vec!["user.token".into()],
+            ..Default::default()
+        };
+        let (warm_start, subsystems) = cache(config, dir.path());
+        assert_eq!(warm_start.cached(), None);
+        warm_start.store(&json!({ "user": { "name": "a", "token": "secret" }, "todos": [] })).unwrap();
+        subsystems.shutdown(Duration::from_secs(5));
+
+        let (warm_start, _subsystems) = cache(WarmStartConfig::default(), dir.path());
+        assert_eq!(warm_start.cached(), Some(json!({ "user": { "name": "a", "token": "[redacted]" } })));
+    }
+
+    #[test]
+    fn writes_at_most_once_per_interval() {
+        let dir = tempfile::tempdir().unwrap();
+        let config = WarmStartConfig {
+            write_interval: Duration::from_secs(60),
+            ..Default::default()
+        };
+        let (warm_start, subsystems) = cache(config, dir.path());
+        warm_start.store(&json!({ "count": 1 })).unwrap();
+        warm_start.store(&json!({ "count": 2 })).unwrap();
+        subsystems.shutdown(Duration::from_secs(5));
+        assert_eq!(warm_start.cached(), Some(json!({ "count": 1 })));
+
+        let (warm_start, subsystems) = cache(WarmStartConfig::default(), dir.path());
+        warm_start.store(&json!({ "count": 3 })).unwrap();
+        warm_start.flush();
+        subsystems.shutdown(Duration::from_secs(5));
+        assert_eq!(warm_start.cached(), Some(json!({ "count": 3 })));
+    }
+
+    #[test]
+    fn drops_states_over_the_size_limit() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::write(dir.path().join(CACHE_FILE), r#"{ "count": 1 }"#).unwrap();
+        let config = WarmStartConfig {
+            max_bytes: 8,
+            ..Default::default()
+        };
+        let (warm_start, subsystems) = cache(config, dir.path());
+        warm_start.store(&json!({ "count": 1000 })).unwrap();
+        subsystems.shutdown(Duration::from_secs(5));
+        assert_eq!(warm_start.cached(), None);
+    }
+}
@@ -0,0 +1,162 @@
+//! Experimental: [`SliceReducer`]s compiled to WebAssembly and run in a wasmtime sandbox, with no
+//! imports, a memory cap and a fuel budget per call.
+//!
+//! A module exports its linear memory as `memory` and:
+//!
+//! - `alloc(len: i32) -> i32`, returning a pointer to `len` writable bytes
+//! - `reduce(state_ptr: i32, state_len: i32, action_ptr: i32, action_len: i32) -> i64`, taking
+//!   the slice state and a `{ type, payload }` action as UTF-8 JSON and returning the new state
+//! - optionally `initial_state() -> i64`; the slice starts as `null` without it
+//!
+//! Returned JSON is passed as `(ptr << 32) | len`.
+
+use std::path::Path;
+use std::sync::Mutex;
+
+use wasmtime::{Config, Engine, Instance, Linker, Memory, Module, Store, StoreLimits, StoreLimitsBuilder, TypedFunc};
+
+use crate::models::*;
+use crate::slice_router::SliceReducer;
+
+/// Resource limits of a [`WasmReducer`].
+#[derive(Clone, Copy, Debug)]
+pub struct WasmLimits {
+    /// Fuel available to each call, roughly the number of instructions. Defaults to 10 million.
+    pub fuel: u64,
+    /// The most linear memory the module may grow to. Defaults to 16 MB.
+    pub max_memory_bytes: usize,
+}
+
+impl Default for WasmLimits {
+    fn default() -> Self {
+        Self {
+            fuel: 10_000_000,
+            max_memory_bytes: 16 * 1024 * 1024,
+        }
+    }
+}
+
+struct Loaded {
+    store: Store<StoreLimits>,
+    memory: Memory,
+    alloc: TypedFunc<i32, i32>,
+    reduce: TypedFunc<(i32, i32, i32, i32), i64>,
+    initial_state: Option<TypedFunc<(), i64>>,
+}
+
+/// A slice reducer loaded from a WebAssembly module.
+pub struct WasmReducer {
+    engine: Engine,
+    module: Module,
+    limits: WasmLimits,
+    /// The module's instance, created on first use and again after a call fails, since a trap
+    /// can leave its memory in any state.
+    loaded: Mutex<Option<Loaded>>,
+}
+
+impl WasmReducer {
+    /// Compile a module from its binary or text format.
+    pub fn from_bytes(bytes: impl AsRef<[u8]>, limits: WasmLimits) -> crate::Result<Self> {
+        let mut config = Config::new();
+        config.consume_fuel(true);
+        let engine = Engine::new(&config).map_err(wasm_error)?;
+        let module = Module::new(&engine, bytes).map_err(wasm_error)?;
+        Ok(Self {
+            engine,
+            module,
+            limits,
+            loaded: Mutex::new(None),
+        })
+    }
+
+    pub fn from_file(path: impl AsRef<Path>, limits: WasmLimits) -> crate::Result<Self> {
+        Self::from_bytes(std::fs::read(path)?, limits)
+    }
+
+    fn instantiate(&self) -> crate::Result<Loaded> {
+        let limits = StoreLimitsBuilder::new()
+            .memory_size(self.limits.max_memory_bytes)
+            .instances(1)
+            .build();
+        let mut store = Store::new(&self.engine, limits);
+        store.limiter(|limits| limits);
+        store.set_fuel(self.limits.fuel).map_err(wasm_error)?;
+        // Nothing is linked, so the module can't reach the host
+        let instance: Instance = Linker::new(&self.engine).instantiate(&mut store, &self.module).map_err(wasm_error)?;
+        let memory = instance
+            .get_memory(&mut store, "memory")
+            .ok_or_else(|| crate::Error::WasmError("the module doesn't export its memory".into()))?;
+        let alloc = instance.get_typed_func(&mut store, "alloc").map_err(wasm_error)?;
+        let reduce = instance.get_typed_func(&mut store, "reduce").map_err(wasm_error)?;
+        let initial_state = instance.get_typed_func(&mut store, "initial_state").ok();
+        Ok(Loaded {
+            store,
+            memory,
+            alloc,
+            reduce,
+            initial_state,
+        })
+    }
+
+    /// Run `call` against the instance, creating it first if needed, and drop the instance if
+    /// the call fails.
+    fn with_instance(&self, call: impl FnOnce(&mut Loaded) -> crate::Result<JsonValue>) -> crate::Result<JsonValue> {
+        let mut loaded = self.loaded.lock().map_err(|e| crate::Error::StateError(e.to_string()))?;
+        let mut instance = match loaded.take() {
+            Some(instance) => instance,
+            None => self.instantiate()?,
+        };
+        instance.store.set_fuel(self.limits.fuel).map_err(wasm_error)?;
+        let result = call(&mut instance);
+        if result.is_ok() {
+            *loaded = Some(instance);
+        }
+        result
+    }
+}
+
+impl Loaded {
+    fn write(&mut self, bytes: &[u8]) -> crate::Result<(i32, i32)> {
+        let len = i32::try_from(bytes.len()).map_err(|_| crate::Error::WasmError("input too large".into()))?;
+        let ptr = self.alloc.call(&mut self.store, len).map_err(wasm_error)?;
+        self.memory.write(&mut self.store, ptr as u32 as usize, bytes).map_err(wasm_error)?;
+        Ok((ptr, len))
+    }
+
+    fn read(&self, packed: i64) -> crate::Result<JsonValue> {
+        let (ptr, len) = ((packed as u64 >> 32) as usize, (packed as u64 & 0xffff_ffff) as usize);
+        let mut bytes = vec![0; len];
+        self.memory.read(&self.store, ptr, &mut bytes).map_err(wasm_error)?;
+        serde_json::from_slice(&bytes).map_err(|e| crate::Error::WasmError(format!("invalid JSON from the module: {}", e)))
+    }
+}
+
+impl SliceReducer for WasmReducer {
+    fn initial_state(&self) -> crate::Result<JsonValue> {
+        self.with_instance(|instance| match instance.initial_state.clone() {
+            Some(initial_state) => {
+                let packed = initial_state.call(&mut instance.store, ()).map_err(wasm_error)?;
+                instance.read(packed)
+            }
+            None => Ok(JsonValue::Null),
+        })
+    }
+
+    fn reduce(&self, state: &JsonValue, action: &JsonValue) -> crate::Result<JsonValue> {
+        let state = serde_json::to_vec(state).map_err(|e| crate::Error::SerializationError(e.to_string()))?;
+        let action = serde_json::to_vec(action).map_err(|e| crate::Error::SerializationError(e.to_string()))?;
+        self.with_instance(|instance| {
+            let (state_ptr, state_len) = instance.write(&state)?;
+            let (action_ptr, action_len) = instance.write(&action)?;
+            let packed = instance
+                .reduce
+                .call(&mut instance.store, (state_ptr, state_len, action_ptr, action_len))
+                .map_err(wasm_error)?;
+            instance.read(packed)
+        })
+    }
+}
+
+fn wasm_error(e: impl std::fmt::Display) -> crate::Error {
+    crate::Error::WasmError(e.to_string())
+}
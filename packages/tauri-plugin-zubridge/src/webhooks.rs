@@ -0,0 +1,300 @@
+//! POST signed JSON payloads to companion services when matching actions are dispatched or
+//! the state starts matching a predicate.
+
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use serde::Serialize;
+
+use crate::error_policy::ErrorPolicy;
+use crate::ids::IdGenerator;
+use crate::models::*;
+use crate::utils::{glob_matches, hex, hmac_sha256, now_millis};
+
+/// The header carrying `sha256=<hex HMAC-SHA256 of the body>` for webhooks with a secret.
+pub const SIGNATURE_HEADER: &str = "X-Zubridge-Signature";
+/// The header carrying the delivery id, which stays the same across retries.
+pub const DELIVERY_HEADER: &str = "X-Zubridge-Delivery";
+
+type WebhookPredicate = Arc<dyn Fn(&JsonValue) -> bool + Send + Sync>;
+
+#[derive(Clone)]
+enum Trigger {
+    /// Action types, where `*` matches any run of characters.
+    Actions(Vec<String>),
+    /// Fires when the predicate starts holding for the emitted state.
+    State(WebhookPredicate),
+}
+
+/// A URL notified when its trigger fires.
+#[derive(Clone)]
+pub struct Webhook {
+    name: String,
+    url: String,
+    secret: Option<String>,
+    trigger: Trigger,
+}
+
+impl Webhook {
+    /// Notify `url` whenever an action whose type matches one of `action_types` is dispatched,
+    /// where `*` matches any run of characters.
+    pub fn on_actions<I, S>(name: impl Into<String>, url: impl Into<String>, action_types: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        Self {
+            name: name.into(),
+            url: url.into(),
+            secret: None,
+            trigger: Trigger::Actions(action_types.into_iter().map(Into::into).collect()),
+        }
+    }
+
+    /// Notify `url` with the emitted state whenever `predicate` starts holding for it. The
+    /// webhook fires again only after the predicate stopped holding in between.
+    pub fn when<F>(name: impl Into<String>, url: impl Into<String>, predicate: F) -> Self
+    where
+        F: Fn(&JsonValue) -> bool + Send + Sync + 'static,
+    {
+        Self {
+            name: name.into(),
+            url: url.into(),
+            secret: None,
+            trigger: Trigger::State(Arc::new(predicate)),
+        }
+    }
+
+    /// Sign payloads with this secret, see [`SIGNATURE_HEADER`].
+    pub fn with_secret(mut self, secret: impl Into<String>) -> Self {
+        self.secret = Some(secret.into());
+        self
+    }
+}
+
+/// Webhooks and how failed deliveries are retried.
+#[derive(Clone)]
+pub struct WebhooksConfig {
+    pub webhooks: Vec<Webhook>,
+    /// How many times a delivery is attempted before it's dropped. Defaults to 5.
+    pub max_attempts: u32,
+    /// The wait before the first retry, doubled after every failed attempt. Defaults to 1 second.
+    pub initial_backoff: Duration,
+    /// The longest wait between retries. Defaults to 1 minute.
+    pub max_backoff: Duration,
+    /// How long a single attempt may take. Defaults to 10 seconds.
+    pub timeout: Duration,
+}
+
+impl Default for WebhooksConfig {
+    fn default() -> Self {
+        Self {
+            webhooks: Vec::new(),
+            max_attempts: 5,
+            initial_backoff: Duration::from_secs(1),
+            max_backoff: Duration::from_secs(60),
+            timeout: Duration::from_secs(10),
+        }
+    }
+}
+
+impl WebhooksConfig {
+    pub fn new(webhooks: Vec<Webhook>) -> Self {
+        Self {
+            webhooks,
+            ..Default::default()
+        }
+    }
+}
+
+/// The JSON body POSTed to a webhook.
+#[derive(Clone, Debug, Serialize)]
+pub struct WebhookPayload {
+    pub webhook: String,
+    /// The delivery id, also sent as [`DELIVERY_HEADER`].
+    pub id: String,
+    /// Set for webhooks created with [`Webhook::on_actions`].
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub action: Option<ZubridgeAction>,
+    /// Set for webhooks created with [`Webhook::when`].
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub state: Option<JsonValue>,
+    /// Milliseconds since the Unix epoch.
+    pub timestamp: u64,
+}
+
+/// Matches dispatched actions and emitted states against the configured webhooks and delivers
+/// their payloads on the async runtime.
+pub(crate) struct Webhooks {
+    config: WebhooksConfig,
+    client: reqwest::Client,
+    ids: Arc<dyn IdGenerator>,
+    policy: ErrorPolicy,
+    /// Whether each webhook's state predicate held for the last emitted state.
+    matched: Mutex<Vec<bool>>,
+}
+
+impl Webhooks {
+    pub(crate) fn new(config: WebhooksConfig, ids: Arc<dyn IdGenerator>, policy: ErrorPolicy) -> Self {
+        let matched = Mutex::new(vec![false; config.webhooks.len()]);
+        Self {
+            config,
+            client: reqwest::Client::new(),
+            ids,
+            policy,
+            matched,
+        }
+    }
+
+    pub(crate) fn action_dispatched(&self, action: &ZubridgeAction) {
+        for webhook in &self.config.webhooks {
+            let Trigger::Actions(patterns) = &webhook.trigger else { continue };
+            if patterns.iter().any(|pattern| glob_matches(pattern, &action.action_type)) {
+                self.deliver(webhook, Some(action.clone()), None);
+            }
+        }
+    }
+
+    pub(crate) fn state_published(&self, state: &JsonValue) {
+        let Ok(mut matched) = self.matched.lock() else { return };
+        for (webhook, was_matched) in self.config.webhooks.iter().zip(matched.iter_mut()) {
+            let Trigger::State(predicate) = &webhook.trigger else { continue };
+            let is_matched = predicate(state);
+            if is_matched && !*was_matched {
+                self.deliver(webhook, None, Some(state.clone()));
+            }
+            *was_matched = is_matched;
+        }
+    }
+
+    fn deliver(&self, webhook: &Webhook, action: Option<ZubridgeAction>, state: Option<JsonValue>) {
+        let payload = WebhookPayload {
+            webhook: webhook.name.clone(),
+            id: self.ids.next_id(),
+            action,
+            state,
+            timestamp: now_millis(),
+        };
+        let body = match serde_json::to_vec(&payload) {
+            Ok(body) => body,
+            Err(e) => {
+                self.policy.report(format_args!("failed to serialize webhook {}", webhook.name), &e);
+                return;
+            }
+        };
+        let signature = webhook.secret.as_ref().map(|secret| format!("sha256={}", hex(&hmac_sha256(secret.as_bytes(), &body))));
+
+        let client = self.client.clone();
+        let config = self.config.clone();
+        let (name, url, policy) = (webhook.name.clone(), webhook.url.clone(), self.policy);
+        tauri::async_runtime::spawn(async move {
+            let mut backoff = config.initial_backoff;
+            for attempt in 1..=config.max_attempts.max(1) {
+                let mut request = client
+                    .post(&url)
+                    .timeout(config.timeout)
+                    .header(reqwest::header::CONTENT_TYPE, "application/json")
+                    .header(DELIVERY_HEADER, &payload.id)
+                    .body(body.clone());
+                if let Some(signature) = &signature {
+                    request = request.header(SIGNATURE_HEADER, signature);
+                }
+                let error = match request.send().await {
+                    Ok(response) if response.status().is_success() => return,
+                    Ok(response) => format!("status {}", response.status()),
+                    Err(e) => e.to_string(),
+                };
+                if attempt == config.max_attempts.max(1) {
+                    policy.report(format_args!("webhook {} failed after {} attempts", name, attempt), &error);
+                    return;
+                }
+                log::debug!("zubridge: webhook {} attempt {} failed: {}", name, attempt, error);
+                tokio::time::sleep(backoff).await;
+                backoff = backoff.saturating_mul(2).min(config.max_backoff);
+            }
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::{BufRead, BufReader, Read, Write};
+    use std::net::TcpListener;
+    use std::sync::mpsc;
+
+    /// Accept requests on a local port, answering each with 200 and sending on its headers,
+    /// lowercased, and body.
+    fn serve() -> (String, mpsc::Receiver<(String, String)>) {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://{}/hook", listener.local_addr().unwrap());
+        let (sender, requests) = mpsc::channel();
+        std::thread::spawn(move || {
+            for stream in listener.incoming() {
+                let mut reader = BufReader::new(stream.unwrap());
+                let mut headers = String::new();
+                // Up to the blank line ending the headers
+                while reader.read_line(&mut headers).unwrap() > 2 {}
+                let headers = headers.to_lowercase();
+                let length = headers
+                    .lines()
+                    .find_map(|line| line.strip_prefix("content-length: "))
+                    .map_or(0, |length| length.trim().parse().unwrap());
+                let mut body = vec![0; length];
+                reader.read_exact(&mut body).unwrap();
+                let _ = reader.get_mut().write_all(b"HTTP/1.1 200 OK\r\ncontent-length: 0\r\n\r\n");
+                if sender.send((headers, String::from_utf8(body).unwrap())).is_err() {
+                    return;
+                }
+            }
+        });
+        (url, requests)
+    }
+
+    fn webhooks(webhooks: Vec<Webhook>) -> Webhooks {
+        Webhooks::new(WebhooksConfig::new(webhooks), Arc::new(|| "delivery".to_string()), ErrorPolicy::default())
+    }
+
+    fn action(action_type: &str) -> ZubridgeAction {
+        ZubridgeAction {
+            action_type: action_type.into(),
+            payload: None,
+        }
+    }
+
+    #[test]
+    fn delivers_signed_payloads_for_matching_actions() {
+        let (url, requests) = serve();
+        let webhooks = webhooks(vec![Webhook::on_actions("orders", url, ["ORDER:*"]).with_secret("secret")]);
+        webhooks.action_dispatched(&action("TODO:ADD"));
+        webhooks.action_dispatched(&action("ORDER:PLACED"));
+
+        let (headers, body) = requests.recv_timeout(Duration::from_secs(10)).unwrap();
+        let signature = format!("sha256={}", hex(&hmac_sha256(b"secret", body.as_bytes())));
+        assert!(headers.contains(&format!("x-zubridge-signature: {}", signature)));
+        assert!(headers.contains("x-zubridge-delivery: delivery"));
+        let payload: JsonValue = serde_json::from_str(&body).unwrap();
+        assert_eq!(payload["webhook"], "orders");
+        assert_eq!(payload["action"]["action_type"], "ORDER:PLACED");
+        assert!(requests.recv_timeout(Duration::from_millis(100)).is_err());
+    }
+
+    #[test]
+    fn fires_state_webhooks_when_the_predicate_starts_holding() {
+        let (url, requests) = serve();
+        let webhooks = webhooks(vec![Webhook::when("done", url, |state| state["done"] == true)]);
+        let done = serde_json::json!({ "done": true });
+        webhooks.state_published(&done);
+        webhooks.state_published(&done);
+        webhooks.state_published(&serde_json::json!({ "done": false }));
+        webhooks.state_published(&done);
+
+        for _ in 0..2 {
+            let (headers, body) = requests.recv_timeout(Duration::from_secs(10)).unwrap();
+            assert!(!headers.contains("x-zubridge-signature"));
+            let payload: JsonValue = serde_json::from_str(&body).unwrap();
+            assert_eq!(payload["state"], done);
+        }
+        assert!(requests.recv_timeout(Duration::from_millis(100)).is_err());
+    }
+}
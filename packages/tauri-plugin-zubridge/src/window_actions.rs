@@ -0,0 +1,267 @@
+use std::collections::BTreeMap;
+use std::sync::Mutex;
+
+use serde::Serialize;
+use tauri::{AppHandle, Manager, Runtime, Url, WebviewUrl, WebviewWindowBuilder};
+
+use crate::models::*;
+use crate::utils::glob_matches;
+
+pub(crate) const OPEN_ACTION: &str = "WINDOW:OPEN";
+pub(crate) const CLOSE_ACTION: &str = "WINDOW:CLOSE";
+pub(crate) const FOCUS_ACTION: &str = "WINDOW:FOCUS";
+pub(crate) const SHOW_ACTION: &str = "WINDOW:SHOW";
+pub(crate) const HIDE_ACTION: &str = "WINDOW:HIDE";
+
+/// A window that can be opened by label with `WINDOW:OPEN`.
+#[derive(Clone, Debug)]
+pub struct WindowDefinition {
+    pub label: String,
+    /// An app path (e.g. `settings.html`) or an external `http(s)` URL.
+    pub url: String,
+    pub title: Option<String>,
+    pub size: Option<(f64, f64)>,
+}
+
+impl WindowDefinition {
+    pub fn new(label: impl Into<String>, url: impl Into<String>) -> Self {
+        Self {
+            label: label.into(),
+            url: url.into(),
+            title: None,
+            size: None,
+        }
+    }
+
+    pub fn title(mut self, title: impl Into<String>) -> Self {
+        self.title = Some(title.into());
+        self
+    }
+
+    pub fn size(mut self, width: f64, height: f64) -> Self {
+        self.size = Some((width, height));
+        self
+    }
+}
+
+/// Built-in `WINDOW:OPEN`, `WINDOW:CLOSE`, `WINDOW:FOCUS`, `WINDOW:SHOW` and `WINDOW:HIDE`
+/// actions, with the open windows mirrored into the `state_key` slice.
+#[derive(Clone, Debug)]
+pub struct WindowActionsConfig {
+    /// Windows that `WINDOW:OPEN` can create by label.
+    pub definitions: Vec<WindowDefinition>,
+    /// The state key the window registry is mirrored under. Defaults to "windows".
+    pub state_key: String,
+    /// Labels of windows that are hidden instead of closed when the user closes them,
+    /// where `*` matches any run of characters.
+    pub close_to_tray: Vec<String>,
+    /// Labels of windows hidden as soon as they're created, e.g. to start minimized to the tray.
+    pub start_hidden: Vec<String>,
+}
+
+impl WindowActionsConfig {
+    pub(crate) fn closes_to_tray(&self, label: &str) -> bool {
+        self.close_to_tray.iter().any(|pattern| glob_matches(pattern, label))
+    }
+
+    pub(crate) fn starts_hidden(&self, label: &str) -> bool {
+        self.start_hidden.iter().any(|pattern| glob_matches(pattern, label))
+    }
+}
+
+impl Default for WindowActionsConfig {
+    fn default() -> Self {
+        Self {
+            definitions: Vec::new(),
+            state_key: "windows".to_string(),
+            close_to_tray: Vec::new(),
+            start_hidden: Vec::new(),
+        }
+    }
+}
+
+#[derive(Clone, Debug, Serialize)]
+struct WindowEntry {
+    title: Option<String>,
+    focused: bool,
+    visible: bool,
+}
+
+/// The open windows, keyed by label.
+#[derive(Default)]
+pub(crate) struct WindowRegistry {
+    windows: Mutex<BTreeMap<String, WindowEntry>>,
+}
+
+impl WindowRegistry {
+    pub(crate) fn opened(&self, label: &str, title: Option<String>, visible: bool) -> crate::Result<()> {
+        let mut windows = self.windows.lock().map_err(|e| crate::Error::StateError(e.to_string()))?;
+        windows.insert(label.to_string(), WindowEntry { title, focused: false, visible });
+        Ok(())
+    }
+
+    /// Returns whether the registry changed.
+    pub(crate) fn visible(&self, label: &str, visible: bool) -> crate::Result<bool> {
+        let mut windows = self.windows.lock().map_err(|e| crate::Error::StateError(e.to_string()))?;
+        match windows.get_mut(label) {
+            Some(entry) if entry.visible != visible => {
+                entry.visible = visible;
+                Ok(true)
+            }
+            _ => Ok(false),
+        }
+    }
+
+    pub(crate) fn closed(&self, label: &str) -> crate::Result<bool> {
+        let mut windows = self.windows.lock().map_err(|e| crate::Error::StateError(e.to_string()))?;
+        Ok(windows.remove(label).is_some())
+    }
+
+    /// Returns whether the registry changed.
+    pub(crate) fn focused(&self, label: &str, focused: bool) -> crate::Result<bool> {
+        let mut windows = self.windows.lock().map_err(|e| crate::Error::StateError(e.to_string()))?;
+        match windows.get_mut(label) {
+            Some(entry) if entry.focused != focused => {
+                entry.focused = focused;
+                Ok(true)
+            }
+            _ => Ok(false),
+        }
+    }
+
+    pub(crate) fn slice(&self) -> crate::Result<JsonValue> {
+        let windows = self.windows.lock().map_err(|e| crate::Error::StateError(e.to_string()))?;
+        serde_json::to_value(&*windows).map_err(|e| crate::Error::SerializationError(e.to_string()))
+    }
+}
+
+pub(crate) fn is_window_action(action_type: &str) -> bool {
+    matches!(action_type, OPEN_ACTION | CLOSE_ACTION | FOCUS_ACTION | SHOW_ACTION | HIDE_ACTION)
+}
+
+/// Open, close, focus, show or hide the window named by the action's `label` payload.
+/// Returns whether the registry changed.
+pub(crate) fn apply<R: Runtime>(
+    app: &AppHandle<R>,
+    config: &WindowActionsConfig,
+    registry: &WindowRegistry,
+    action: &ZubridgeAction,
+) -> crate::Result<bool> {
+    let payload = action.payload.as_ref();
+    let label = payload
+        .and_then(|payload| payload.get("label"))
+        .and_then(JsonValue::as_str)
+        .ok_or_else(|| crate::Error::WindowError(format!("{} requires a label", action.action_type)))?;
+    let window = app.get_webview_window(label);
+
+    match (action.action_type.as_str(), window) {
+        (OPEN_ACTION, Some(window)) | (FOCUS_ACTION, Some(window)) | (SHOW_ACTION, Some(window)) => {
+            window.show()?;
+            window.unminimize()?;
+            window.set_focus()?;
+            return registry.visible(label, true);
+        }
+        (HIDE_ACTION, Some(window)) => {
+            window.hide()?;
+            return registry.visible(label, false);
+        }
+        (OPEN_ACTION, None) => {
+            let definition = match config.definitions.iter().find(|definition| definition.label == label) {
+                Some(definition) => definition.clone(),
+                None => {
+                    let url = payload
+                        .and_then(|payload| payload.get("url"))
+                        .and_then(JsonValue::as_str)
+                        .ok_or_else(|| crate::Error::WindowError(format!("no window definition for {}", label)))?;
+                    let mut definition = WindowDefinition::new(label, url);
+                    definition.title = payload
+                        .and_then(|payload| payload.get("title"))
+                        .and_then(JsonValue::as_str)
+                        .map(str::to_string);
+                    definition
+                }
+            };
+            build(app, &definition)?;
+        }
+        (CLOSE_ACTION, Some(window)) => window.close()?,
+        (CLOSE_ACTION, None) => {}
+        (_, None) => return Err(crate::Error::WindowError(format!("no window labelled {}", label))),
+        _ => {}
+    }
+    Ok(false)
+}
+
+fn build<R: Runtime>(app: &AppHandle<R>, definition: &WindowDefinition) -> crate::Result<()> {
+    let url = match Url::parse(&definition.url) {
+        Ok(url) if matches!(url.scheme(), "http" | "https") => WebviewUrl::External(url),
+        _ => WebviewUrl::App(definition.url.clone().into()),
+    };
+
+    let mut builder = WebviewWindowBuilder::new(app, &definition.label, url);
+    if let Some(title) = &definition.title {
+        builder = builder.title(title);
+    }
+    if let Some((width, height)) = definition.size {
+        builder = builder.inner_size(width, height);
+    }
+    builder.build()?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn action(action_type: &str, payload: JsonValue) -> ZubridgeAction {
+        ZubridgeAction {
+            action_type: action_type.into(),
+            payload: Some(payload),
+        }
+    }
+
+    #[test]
+    fn tracks_open_windows() {
+        let registry = WindowRegistry::default();
+        registry.opened("main", Some("Main".into()), true).unwrap();
+        assert!(registry.focused("main", true).unwrap());
+        assert!(!registry.focused("main", true).unwrap());
+        assert!(registry.visible("main", false).unwrap());
+        assert!(!registry.visible("other", false).unwrap());
+        assert_eq!(
+            registry.slice().unwrap(),
+            json!({ "main": { "title": "Main", "focused": true, "visible": false } })
+        );
+        assert!(registry.closed("main").unwrap());
+        assert!(!registry.closed("main").unwrap());
+    }
+
+    #[test]
+    fn matches_window_labels_against_patterns() {
+        let config = WindowActionsConfig {
+            close_to_tray: vec!["main".into()],
+            start_hidden: vec!["panel-*".into()],
+            ..Default::default()
+        };
+        assert!(config.closes_to_tray("main") && !config.closes_to_tray("panel-1"));
+        assert!(config.starts_hidden("panel-1") && !config.starts_hidden("main"));
+    }
+
+    #[test]
+    fn rejects_actions_on_unknown_windows() {
+        let app = tauri::test::mock_app();
+        let (config, registry) = (WindowActionsConfig::default(), WindowRegistry::default());
+        let apply = |action: ZubridgeAction| apply(app.handle(), &config, &registry, &action);
+        assert!(matches!(apply(action(FOCUS_ACTION, json!({}))), Err(crate::Error::WindowError(_))));
+        assert!(matches!(
+            apply(action(HIDE_ACTION, json!({ "label": "main" }))),
+            Err(crate::Error::WindowError(_))
+        ));
+        assert!(matches!(
+            apply(action(OPEN_ACTION, json!({ "label": "settings" }))),
+            Err(crate::Error::WindowError(_))
+        ));
+        assert!(!apply(action(CLOSE_ACTION, json!({ "label": "main" }))).unwrap());
+        assert!(is_window_action(SHOW_ACTION) && !is_window_action("WINDOW:RESIZE"));
+    }
+}
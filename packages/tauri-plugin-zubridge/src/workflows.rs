@@ -0,0 +1,400 @@
+use std::collections::BTreeMap;
+use std::future::Future;
+use std::path::PathBuf;
+use std::pin::Pin;
+use std::sync::{Arc, Mutex};
+
+use serde::{Deserialize, Serialize};
+use tauri::async_runtime::{self, Sender};
+
+use crate::ids::IdGenerator;
+use crate::models::*;
+use crate::persistence::PersistenceWriter;
+use crate::utils::glob_matches;
+
+const RUNS_FILE: &str = "workflows.json";
+
+pub(crate) const START_ACTION: &str = "WORKFLOW:START";
+const COMPLETED_ACTION: &str = "WORKFLOW:COMPLETED";
+const FAILED_ACTION: &str = "WORKFLOW:FAILED";
+
+/// The body of a workflow. Returning an error dispatches `WORKFLOW:FAILED`.
+pub type WorkflowFn =
+    Arc<dyn Fn(WorkflowContext) -> Pin<Box<dyn Future<Output = Result<(), String>> + Send>> + Send + Sync>;
+
+/// A long-running task that can wait for actions, dispatch others and checkpoint its progress.
+///
+/// Runs that haven't finished when the app quits are started again on the next launch, with the
+/// same input and the last saved progress. Use [`WorkflowContext::progress`] to skip the steps
+/// that already happened.
+#[derive(Clone)]
+pub struct Workflow {
+    pub name: String,
+    run: WorkflowFn,
+}
+
+impl Workflow {
+    pub fn new<F, Fut>(name: impl Into<String>, run: F) -> Self
+    where
+        F: Fn(WorkflowContext) -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = Result<(), String>> + Send + 'static,
+    {
+        Self {
+            name: name.into(),
+            run: Arc::new(move |context| Box::pin(run(context))),
+        }
+    }
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+struct WorkflowRun {
+    workflow: String,
+    input: JsonValue,
+    progress: JsonValue,
+    /// The action pattern the run is waiting for.
+    waiting_for: Option<String>,
+}
+
+struct Waiter {
+    run_id: String,
+    pattern: String,
+    sender: Sender<ZubridgeAction>,
+}
+
+/// The running workflows, persisted to the plugin's data directory.
+pub(crate) struct Workflows {
+    workflows: Vec<Workflow>,
+    ids: Arc<dyn IdGenerator>,
+    path: PathBuf,
+    writer: Arc<PersistenceWriter>,
+    runs: Mutex<BTreeMap<String, WorkflowRun>>,
+    waiters: Mutex<Vec<Waiter>>,
+}
+
+impl Workflows {
+    pub(crate) fn load(
+        workflows: Vec<Workflow>,
+        data_dir: &std::path::Path,
+        ids: Arc<dyn IdGenerator>,
+        writer: Arc<PersistenceWriter>,
+    ) -> Self {
+        let path = data_dir.join(RUNS_FILE);
+        let runs = std::fs::read_to_string(&path)
+            .ok()
+            .and_then(|contents| serde_json::from_str(&contents).ok())
+            .unwrap_or_default();
+        Self {
+            workflows,
+            ids,
+            path,
+            writer,
+            runs: Mutex::new(runs),
+            waiters: Mutex::new(Vec::new()),
+        }
+    }
+
+    pub(crate) fn is_empty(&self) -> bool {
+        self.workflows.is_empty()
+    }
+
+    /// Start a run of `workflow`, returning its id.
+    pub(crate) fn start(self: &Arc<Self>, workflow: &str, input: JsonValue, dispatcher: Dispatcher) -> crate::Result<String> {
+        let id = self.ids.next_id();
+        let run = WorkflowRun {
+            workflow: workflow.to_string(),
+            input,
+            progress: JsonValue::Null,
+            waiting_for: None,
+        };
+        self.spawn(id.clone(), run, dispatcher)?;
+        Ok(id)
+    }
+
+    /// Start every persisted run again, e.g. after a restart.
+    pub(crate) fn resume(self: &Arc<Self>, dispatcher: Dispatcher) -> crate::Result<()> {
+        let runs = self.runs.lock().map_err(|e| crate::Error::StateError(e.to_string()))?.clone();
+        for (id, mut run) in runs {
+            log::debug!("zubridge: resuming workflow {} ({})", run.workflow, id);
+            run.waiting_for = None;
+            if let Err(e) = self.spawn(id.clone(), run, dispatcher.clone()) {
+                log::warn!("zubridge: failed to resume workflow {}: {}", id, e);
+            }
+        }
+        Ok(())
+    }
+
+    fn spawn(self: &Arc<Self>, id: String, run: WorkflowRun, dispatcher: Dispatcher) -> crate::Result<()> {
+        let workflow = self
+            .workflows
+            .iter()
+            .find(|workflow| workflow.name == run.workflow)
+            .ok_or_else(|| crate::Error::StateError(format!("no workflow named {}", run.workflow)))?
+            .clone();
+        let context = WorkflowContext {
+            id: id.clone(),
+            input: run.input.clone(),
+            dispatcher: dispatcher.clone(),
+            workflows: self.clone(),
+        };
+        self.update(|runs| {
+            runs.insert(id.clone(), run);
+        })?;
+
+        let workflows = self.clone();
+        async_runtime::spawn(async move {
+            let result = (workflow.run)(context).await;
+            if let Ok(mut waiters) = workflows.waiters.lock() {
+                waiters.retain(|waiter| waiter.run_id != id);
+            }
+            if let Err(e) = workflows.update(|runs| {
+                runs.remove(&id);
+            }) {
+                log::warn!("zubridge: failed to finish workflow {}: {}", id, e);
+            }
+
+            let (action_type, payload) = match result {
+                Ok(()) => (COMPLETED_ACTION, serde_json::json!({ "id": id, "workflow": workflow.name })),
+                Err(error) => {
+                    log::warn!("zubridge: workflow {} ({}) failed: {}", workflow.name, id, error);
+                    (FAILED_ACTION, serde_json::json!({ "id": id, "workflow": workflow.name, "error": error }))
+                }
+            };
+            let action = ZubridgeAction {
+                action_type: action_type.to_string(),
+                payload: Some(payload),
+            };
+            if let Err(e) = dispatcher.dispatch(action) {
+                log::warn!("zubridge: failed to dispatch {}: {}", action_type, e);
+            }
+        });
+        Ok(())
+    }
+
+    /// Resolve the runs waiting for an action the state manager just handled.
+    pub(crate) fn observe(&self, action: &ZubridgeAction) {
+        let Ok(mut waiters) = self.waiters.lock() else { return };
+        let mut resolved = Vec::new();
+        waiters.retain(|waiter| {
+            if !glob_matches(&waiter.pattern, &action.action_type) {
+                return true;
+            }
+            if waiter.sender.try_send(action.clone()).is_ok() {
+                resolved.push(waiter.run_id.clone());
+            }
+            false
+        });
+        drop(waiters);
+
+        for id in resolved {
+            let result = self.update(|runs| {
+                if let Some(run) = runs.get_mut(&id) {
+                    run.waiting_for = None;
+                }
+            });
+            if let Err(e) = result {
+                log::warn!("zubridge: failed to update workflow {}: {}", id, e);
+            }
+        }
+    }
+
+    /// Change the runs and persist them.
+    fn update(&self, change: impl FnOnce(&mut BTreeMap<String, WorkflowRun>)) -> crate::Result<()> {
+        let mut runs = self.runs.lock().map_err(|e| crate::Error::StateError(e.to_string()))?;
+        change(&mut runs);
+        if runs.is_empty() {
+            self.writer.remove(self.path.clone());
+        } else {
+            let contents = serde_json::to_string(&*runs).map_err(|e| crate::Error::SerializationError(e.to_string()))?;
+            self.writer.write(self.path.clone(), contents);
+        }
+        Ok(())
+    }
+
+    pub(crate) fn slice(&self) -> crate::Result<JsonValue> {
+        let runs = self.runs.lock().map_err(|e| crate::Error::StateError(e.to_string()))?;
+        serde_json::to_value(&*runs).map_err(|e| crate::Error::SerializationError(e.to_string()))
+    }
+}
+
+/// Passed to a running workflow.
+pub struct WorkflowContext {
+    id: String,
+    input: JsonValue,
+    dispatcher: Dispatcher,
+    workflows: Arc<Workflows>,
+}
+
+impl WorkflowContext {
+    /// The id of this run.
+    pub fn id(&self) -> &str {
+        &self.id
+    }
+
+    /// The input the run was started with.
+    pub fn input(&self) -> &JsonValue {
+        &self.input
+    }
+
+    /// The progress last saved with [`WorkflowContext::save`], or null.
+    pub fn progress(&self) -> JsonValue {
+        self.workflows
+            .runs
+            .lock()
+            .ok()
+            .and_then(|runs| runs.get(&self.id).map(|run| run.progress.clone()))
+            .unwrap_or(JsonValue::Null)
+    }
+
+    /// Persist the run's progress, so it survives restarts.
+    pub fn save(&self, progress: impl Into<JsonValue>) -> crate::Result<()> {
+        let progress = progress.into();
+        self.workflows.update(|runs| {
+            if let Some(run) = runs.get_mut(&self.id) {
+                run.progress = progress;
+            }
+        })
+    }
+
+    /// Dispatch an action and return the updated state.
+    pub fn dispatch(&self, action: ZubridgeAction) -> crate::Result<JsonValue> {
+        self.dispatcher.dispatch(action)
+    }
+
+    /// Wait for the next action whose type matches `pattern`, where `*` matches any run of
+    /// characters. Actions dispatched before `take` is called aren't seen.
+    pub async fn take(&self, pattern: &str) -> crate::Result<ZubridgeAction> {
+        let (sender, mut receiver) = async_runtime::channel(1);
+        {
+            let mut waiters = self.workflows.waiters.lock().map_err(|e| crate::Error::StateError(e.to_string()))?;
+            waiters.push(Waiter {
+                run_id: self.id.clone(),
+                pattern: pattern.to_string(),
+                sender,
+            });
+        }
+        self.workflows.update(|runs| {
+            if let Some(run) = runs.get_mut(&self.id) {
+                run.waiting_for = Some(pattern.to_string());
+            }
+        })?;
+
+        receiver
+            .recv()
+            .await
+            .ok_or_else(|| crate::Error::StateError(format!("workflow {} stopped waiting for {}", self.id, pattern)))
+    }
+}
+
+/// The workflow and input of a `WORKFLOW:START` action.
+pub(crate) fn start_request(action: &ZubridgeAction) -> crate::Result<(String, JsonValue)> {
+    let payload = action.payload.as_ref();
+    let workflow = payload
+        .and_then(|payload| payload.get("workflow"))
+        .and_then(JsonValue::as_str)
+        .ok_or_else(|| crate::Error::StateError(format!("{} requires a workflow", START_ACTION)))?;
+    let input = payload.and_then(|payload| payload.get("input")).cloned().unwrap_or(JsonValue::Null);
+    Ok((workflow.to_string(), input))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::error_policy::ErrorPolicy;
+    use crate::persistence::PersistenceConfig;
+    use crate::subsystems::Subsystems;
+    use serde_json::json;
+    use std::sync::mpsc;
+    use std::time::{Duration, Instant};
+
+    fn load(workflows: Vec<Workflow>, data_dir: &std::path::Path) -> (Arc<Workflows>, Subsystems) {
+        let subsystems = Subsystems::default();
+        let writer = PersistenceWriter::start(PersistenceConfig::default(), ErrorPolicy::default(), &subsystems);
+        let ids = Arc::new(|| "run".to_string());
+        (Arc::new(Workflows::load(workflows, data_dir, ids, writer)), subsystems)
+    }
+
+    /// A dispatcher sending on every action it's given.
+    fn recording() -> (Dispatcher, mpsc::Receiver<ZubridgeAction>) {
+        let (sender, actions) = mpsc::channel();
+        let sender = Mutex::new(sender);
+        let dispatcher = Dispatcher::new(move |action| {
+            sender.lock().unwrap().send(action).unwrap();
+            Ok(JsonValue::Null)
+        });
+        (dispatcher, actions)
+    }
+
+    fn approval() -> Workflow {
+        Workflow::new("approval", |context| async move {
+            if context.progress().is_null() {
+                context.save("requested").map_err(|e| e.to_string())?;
+            }
+            let approved = context.take("APPROVAL:*").await.map_err(|e| e.to_string())?;
+            match approved.action_type.as_str() {
+                "APPROVAL:GRANTED" => Ok(()),
+                _ => Err(format!("{} was denied", context.input())),
+            }
+        })
+    }
+
+    fn wait_until(condition: impl Fn() -> bool) {
+        let deadline = Instant::now() + Duration::from_secs(10);
+        while !condition() {
+            assert!(Instant::now() < deadline, "timed out");
+            std::thread::sleep(Duration::from_millis(5));
+        }
+    }
+
+    fn action(action_type: &str) -> ZubridgeAction {
+        ZubridgeAction {
+            action_type: action_type.into(),
+            payload: None,
+        }
+    }
+
+    #[test]
+    fn runs_wait_for_matching_actions() {
+        let dir = tempfile::tempdir().unwrap();
+        let (workflows, _subsystems) = load(vec![approval()], dir.path());
+        let (dispatcher, actions) = recording();
+        assert_eq!(workflows.start("approval", json!("order"), dispatcher).unwrap(), "run");
+        wait_until(|| workflows.slice().unwrap()["run"]["waiting_for"] == "APPROVAL:*");
+        assert_eq!(workflows.slice().unwrap()["run"]["progress"], "requested");
+
+        workflows.observe(&action("TODO:ADD"));
+        workflows.observe(&action("APPROVAL:GRANTED"));
+        let completed = actions.recv_timeout(Duration::from_secs(10)).unwrap();
+        assert_eq!(completed.action_type, COMPLETED_ACTION);
+        assert_eq!(completed.payload, Some(json!({ "id": "run", "workflow": "approval" })));
+        assert_eq!(workflows.slice().unwrap(), json!({}));
+    }
+
+    #[test]
+    fn resumes_persisted_runs_with_their_progress() {
+        let dir = tempfile::tempdir().unwrap();
+        let (workflows, subsystems) = load(vec![approval()], dir.path());
+        let (dispatcher, _actions) = recording();
+        workflows.start("approval", json!("order"), dispatcher).unwrap();
+        wait_until(|| !workflows.slice().unwrap()["run"]["waiting_for"].is_null());
+        subsystems.shutdown(Duration::from_secs(5));
+
+        let (workflows, _subsystems) = load(vec![approval()], dir.path());
+        let (dispatcher, actions) = recording();
+        workflows.resume(dispatcher).unwrap();
+        wait_until(|| !workflows.slice().unwrap()["run"]["waiting_for"].is_null());
+        workflows.observe(&action("APPROVAL:DENIED"));
+        let failed = actions.recv_timeout(Duration::from_secs(10)).unwrap();
+        assert_eq!(failed.action_type, FAILED_ACTION);
+        assert_eq!(failed.payload.unwrap()["error"], "\"order\" was denied");
+    }
+
+    #[test]
+    fn start_requests_name_a_workflow() {
+        let start = ZubridgeAction {
+            action_type: START_ACTION.into(),
+            payload: Some(json!({ "workflow": "approval", "input": 1 })),
+        };
+        assert_eq!(start_request(&start).unwrap(), ("approval".to_string(), json!(1)));
+        assert!(start_request(&action(START_ACTION)).is_err());
+    }
+}
@@ -0,0 +1,270 @@
+use std::collections::{BTreeSet, HashMap};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+
+use crate::models::*;
+use crate::persistence::PersistenceWriter;
+use crate::sensitive::SensitivePaths;
+
+const WORKSPACES_DIR: &str = "workspaces";
+const ACTIVE_FILE: &str = "active";
+
+pub(crate) const SWITCH_ACTION: &str = "WORKSPACE:SWITCH";
+
+/// Separate states per workspace or tenant, swapped in and out of the state manager with
+/// `WORKSPACE:SWITCH { id }`.
+#[derive(Clone, Debug)]
+pub struct WorkspacesConfig {
+    /// The state a workspace starts with the first time it's switched to.
+    pub template: JsonValue,
+    /// The workspace active on first launch. Defaults to "default".
+    pub initial_workspace: String,
+    /// The state key `{ active, workspaces }` is stored under. Defaults to "workspace".
+    pub state_key: String,
+}
+
+impl WorkspacesConfig {
+    pub fn new(template: JsonValue) -> Self {
+        Self {
+            template,
+            initial_workspace: "default".to_string(),
+            state_key: "workspace".to_string(),
+        }
+    }
+}
+
+/// The active workspace, and the states of the others, kept in memory once they've been active
+/// and persisted to `workspaces` in the plugin's data directory.
+pub(crate) struct Workspaces {
+    config: WorkspacesConfig,
+    dir: PathBuf,
+    sensitive: Option<Arc<SensitivePaths>>,
+    writer: Arc<PersistenceWriter>,
+    active: Mutex<String>,
+    parked: Mutex<HashMap<String, JsonValue>>,
+    /// Every workspace with a saved state.
+    saved: Mutex<BTreeSet<String>>,
+}
+
+impl Workspaces {
+    pub(crate) fn load(
+        config: WorkspacesConfig,
+        data_dir: &Path,
+        sensitive: Option<Arc<SensitivePaths>>,
+        writer: Arc<PersistenceWriter>,
+    ) -> Self {
+        let dir = data_dir.join(WORKSPACES_DIR);
+        let active = std::fs::read_to_string(dir.join(ACTIVE_FILE))
+            .ok()
+            .map(|id| id.trim().to_string())
+            .filter(|id| valid_id(id))
+            .unwrap_or_else(|| config.initial_workspace.clone());
+        let saved = std::fs::read_dir(&dir)
+            .map(|entries| {
+                entries
+                    .filter_map(|entry| entry.ok())
+                    .filter_map(|entry| entry.file_name().to_str()?.strip_suffix(".json").map(str::to_string))
+                    .collect()
+            })
+            .unwrap_or_default();
+        Self {
+            config,
+            dir,
+            sensitive,
+            writer,
+            active: Mutex::new(active),
+            parked: Mutex::new(HashMap::new()),
+            saved: Mutex::new(saved),
+        }
+    }
+
+    pub(crate) fn active(&self) -> crate::Result<String> {
+        Ok(self.active.lock().map_err(|e| crate::Error::StateError(e.to_string()))?.clone())
+    }
+
+    /// The workspace `WORKSPACE:SWITCH` switches to.
+    pub(crate) fn target(action: &ZubridgeAction) -> crate::Result<String> {
+        let id = action
+            .payload
+            .as_ref()
+            .and_then(|payload| payload.get("id"))
+            .and_then(JsonValue::as_str)
+            .ok_or_else(|| crate::Error::ActionRejected(format!("{} requires an id", SWITCH_ACTION)))?;
+        validate(id)?;
+        Ok(id.to_string())
+    }
+
+    /// The state of workspace `id`: as it was when it was last active, or the template if it
+    /// never was.
+    pub(crate) fn state_of(&self, id: &str) -> crate::Result<JsonValue> {
+        if let Some(state) = self.parked.lock().map_err(|e| crate::Error::StateError(e.to_string()))?.remove(id) {
+            return Ok(state);
+        }
+        Ok(self.read_saved(id)?.unwrap_or_else(|| self.config.template.clone()))
+    }
+
+    /// The states of every workspace but the active one, e.g. to find the blobs they refer to.
+    pub(crate) fn inactive_states(&self) -> crate::Result<Vec<JsonValue>> {
+        let active = self.active()?;
+        let saved = self.saved.lock().map_err(|e| crate::Error::StateError(e.to_string()))?.clone();
+        let mut states = Vec::new();
+        for id in saved.iter().filter(|id| **id != active) {
+            let parked = self.parked.lock().map_err(|e| crate::Error::StateError(e.to_string()))?.get(id).cloned();
+            match parked {
+                Some(state) => states.push(state),
+                None => states.extend(self.read_saved(id)?),
+            }
+        }
+        Ok(states)
+    }
+
+    /// The file workspace `id`'s state was saved to, if it was.
+    pub(crate) fn saved_file(&self, id: &str) -> Option<PathBuf> {
+        Some(self.path(id)).filter(|path| path.exists())
+    }
+
+    pub(crate) fn template(&self) -> JsonValue {
+        self.config.template.clone()
+    }
+
+    /// Keep the state of workspace `id` in memory and persist it.
+    pub(crate) fn park(&self, id: &str, state: JsonValue) -> crate::Result<()> {
+        self.save(id, &state)?;
+        self.parked
+            .lock()
+            .map_err(|e| crate::Error::StateError(e.to_string()))?
+            .insert(id.to_string(), state);
+        Ok(())
+    }
+
+    /// Persist the state of workspace `id`, e.g. the active one on shutdown.
+    pub(crate) fn save(&self, id: &str, state: &JsonValue) -> crate::Result<()> {
+        let mut state = state.clone();
+        if let Some(sensitive) = &self.sensitive {
+            sensitive.encrypt(&mut state)?;
+        }
+        let contents = serde_json::to_string(&state).map_err(|e| crate::Error::SerializationError(e.to_string()))?;
+        self.writer.write(self.path(id), contents);
+        self.saved
+            .lock()
+            .map_err(|e| crate::Error::StateError(e.to_string()))?
+            .insert(id.to_string());
+        Ok(())
+    }
+
+    pub(crate) fn set_active(&self, id: &str) -> crate::Result<()> {
+        self.writer.write(self.dir.join(ACTIVE_FILE), id);
+        *self.active.lock().map_err(|e| crate::Error::StateError(e.to_string()))? = id.to_string();
+        Ok(())
+    }
+
+    pub(crate) fn state_key(&self) -> &str {
+        &self.config.state_key
+    }
+
+    /// `{ active, workspaces }`, where `workspaces` lists every workspace with a saved state.
+    pub(crate) fn slice(&self) -> crate::Result<JsonValue> {
+        let active = self.active()?;
+        let mut ids = self.saved.lock().map_err(|e| crate::Error::StateError(e.to_string()))?.clone();
+        ids.insert(active.clone());
+        Ok(serde_json::json!({ "active": active, "workspaces": ids }))
+    }
+
+    /// The state saved for workspace `id`, decrypted, if there is one.
+    fn read_saved(&self, id: &str) -> crate::Result<Option<JsonValue>> {
+        let contents = match std::fs::read_to_string(self.path(id)) {
+            Ok(contents) => contents,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+            Err(e) => return Err(e.into()),
+        };
+        let mut state = serde_json::from_str(&contents).map_err(|e| crate::Error::SerializationError(e.to_string()))?;
+        if let Some(sensitive) = &self.sensitive {
+            sensitive.decrypt(&mut state)?;
+        }
+        Ok(Some(state))
+    }
+
+    fn path(&self, id: &str) -> PathBuf {
+        self.dir.join(format!("{}.json", id))
+    }
+}
+
+pub(crate) fn validate(id: &str) -> crate::Result<()> {
+    if !valid_id(id) {
+        return Err(crate::Error::ActionRejected(format!("invalid workspace id: {}", id)));
+    }
+    Ok(())
+}
+
+/// Workspace ids end up in file names, so they're limited to letters, digits, `-` and `_`.
+fn valid_id(id: &str) -> bool {
+    !id.is_empty() && id.chars().all(|c| c.is_ascii_alphanumeric() || matches!(c, '-' | '_'))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use serde_json::json;
+
+    use super::*;
+    use crate::error_policy::ErrorPolicy;
+    use crate::persistence::PersistenceConfig;
+    use crate::subsystems::Subsystems;
+
+    fn load(dir: &Path, sensitive: Option<Arc<SensitivePaths>>) -> (Workspaces, Subsystems) {
+        let subsystems = Subsystems::default();
+        let writer = PersistenceWriter::start(PersistenceConfig::default(), ErrorPolicy::default(), &subsystems);
+        let config = WorkspacesConfig::new(json!({ "todos": [] }));
+        (Workspaces::load(config, dir, sensitive, writer), subsystems)
+    }
+
+    #[test]
+    fn starts_new_workspaces_from_the_template() {
+        let dir = tempfile::tempdir().unwrap();
+        let (workspaces, _subsystems) = load(dir.path(), None);
+        assert_eq!(workspaces.active().unwrap(), "default");
+        assert_eq!(workspaces.state_of("acme").unwrap(), json!({ "todos": [] }));
+        assert!(workspaces.saved_file("acme").is_none());
+    }
+
+    #[test]
+    fn keeps_switched_away_states_across_restarts() {
+        let dir = tempfile::tempdir().unwrap();
+        let (workspaces, subsystems) = load(dir.path(), None);
+        workspaces.park("default", json!({ "todos": ["a"] })).unwrap();
+        workspaces.set_active("acme").unwrap();
+        assert_eq!(workspaces.slice().unwrap(), json!({ "active": "acme", "workspaces": ["acme", "default"] }));
+        subsystems.shutdown(Duration::from_secs(5));
+
+        let (reloaded, _subsystems) = load(dir.path(), None);
+        assert_eq!(reloaded.active().unwrap(), "acme");
+        assert_eq!(reloaded.state_of("default").unwrap(), json!({ "todos": ["a"] }));
+    }
+
+    #[test]
+    fn encrypts_saved_states() {
+        let dir = tempfile::tempdir().unwrap();
+        let paths = vec!["token".to_string()];
+        let sensitive = Arc::new(SensitivePaths::new(paths, Some([1; 32]), dir.path(), "app").unwrap());
+        let (workspaces, subsystems) = load(dir.path(), Some(sensitive.clone()));
+        workspaces.save("default", &json!({ "token": "secret" })).unwrap();
+        subsystems.shutdown(Duration::from_secs(5));
+
+        let saved = std::fs::read_to_string(workspaces.saved_file("default").unwrap()).unwrap();
+        assert!(!saved.contains("secret"));
+        let (reloaded, _subsystems) = load(dir.path(), Some(sensitive));
+        assert_eq!(reloaded.state_of("default").unwrap(), json!({ "token": "secret" }));
+    }
+
+    #[test]
+    fn rejects_ids_that_are_not_file_names() {
+        let switch = |id: &str| ZubridgeAction {
+            action_type: SWITCH_ACTION.to_string(),
+            payload: Some(json!({ "id": id })),
+        };
+        assert_eq!(Workspaces::target(&switch("team-1")).unwrap(), "team-1");
+        assert!(Workspaces::target(&switch("../state")).is_err());
+        assert!(Workspaces::target(&switch("")).is_err());
+    }
+}
@@ -40,14 +40,43 @@ export interface BackendOptions<T = unknown> extends BaseBackendOptions<T> {
 // --- Internal Store and Synchronization Logic ---
 
 // Default command names
+/**
+ * The protocol version sent to tauri-plugin-zubridge with `get_initial_state`
+ */
+const PROTOCOL_VERSION = 1;
+
+/**
+ * Unwrap the plugin's `{ protocol_version, state }` response, rejecting protocols this package
+ * doesn't speak
+ */
+function unwrapInitialState<R>(response: { protocol_version: number; state: R }): R {
+  if (response.protocol_version !== PROTOCOL_VERSION) {
+    throw new Error(
+      `Zubridge Tauri: the plugin speaks protocol ${response.protocol_version}, ` +
+        `but @zubridge/tauri expects protocol ${PROTOCOL_VERSION}. Update both packages together.`,
+    );
+  }
+  return response.state;
+}
+
+/**
+ * Whether an error is a protocol mismatch, raised by the plugin or by `unwrapInitialState`,
+ * which falling back to the direct command format would only hide
+ */
+function isProtocolMismatch(error: unknown): boolean {
+  const message = error instanceof Error ? error.message : String(error);
+  return message.includes('speaks protocol');
+}
+
 const DEFAULT_COMMANDS = {
   // Plugin format (Tauri v2)
   PLUGIN_GET_INITIAL_STATE: 'plugin:zubridge|get_initial_state',
   PLUGIN_DISPATCH_ACTION: 'plugin:zubridge|dispatch_action',
+  PLUGIN_GET_EVENT_NAMES: 'plugin:zubridge|get_event_names',
   // Direct format (Tauri v1 or custom implementation)
   DIRECT_GET_INITIAL_STATE: 'get_initial_state',
   DIRECT_DISPATCH_ACTION: 'dispatch_action',
-  // Default event name
+  // Default event name, used by the direct format and plugins without scoped event names
   STATE_UPDATE_EVENT: 'zubridge://state-update',
 };
 
@@ -73,6 +102,23 @@ let activeCommands = {
   stateUpdateEvent: DEFAULT_COMMANDS.STATE_UPDATE_EVENT,
 };
 
+/**
+ * The plugin's state update event, scoped to the app identifier. Fails if the plugin doesn't
+ * report it, since guessing a name the backend doesn't emit on would leave the window without updates
+ */
+async function pluginStateUpdateEvent(invoke: BackendOptions['invoke']): Promise<string> {
+  try {
+    const names = await invoke<{ stateUpdate: string }>(DEFAULT_COMMANDS.PLUGIN_GET_EVENT_NAMES);
+    return names.stateUpdate;
+  } catch (error) {
+    throw new Error(
+      `Zubridge Tauri: Failed to get the plugin's event names, so state updates can't be received. ` +
+        `Check that the window is granted 'zubridge:allow-get-event-names', or set 'stateUpdateEvent'. ` +
+        `Cause: ${error instanceof Error ? error.message : String(error)}`,
+    );
+  }
+}
+
 /**
  * Helper function to invoke commands with fallback support
  * Tries both plugin format and direct format if command name isn't specified
@@ -89,12 +135,17 @@ async function invokeWithFallback<R>(
   }
 
   // Try plugin format first, then direct format
+  let state: R;
   try {
-    const result = await invoke<R>(DEFAULT_COMMANDS.PLUGIN_GET_INITIAL_STATE, args);
+    const response = await invoke<{ protocol_version: number; state: R }>(DEFAULT_COMMANDS.PLUGIN_GET_INITIAL_STATE, {
+      ...args,
+      protocolVersion: PROTOCOL_VERSION,
+    });
     activeCommands.getInitialState = DEFAULT_COMMANDS.PLUGIN_GET_INITIAL_STATE;
     activeCommands.dispatchAction = DEFAULT_COMMANDS.PLUGIN_DISPATCH_ACTION;
-    return result;
+    state = unwrapInitialState(response);
   } catch (pluginError) {
+    if (isProtocolMismatch(pluginError)) throw pluginError;
     console.log('Zubridge Tauri: Plugin format failed, trying direct format...');
     try {
       const result = await invoke<R>(DEFAULT_COMMANDS.DIRECT_GET_INITIAL_STATE, args);
@@ -109,6 +160,69 @@ async function invokeWithFallback<R>(
       );
     }
   }
+  // Outside the try, so a missing event name isn't mistaken for a backend without the plugin
+  if (!commandConfig?.stateUpdateEvent) {
+    activeCommands.stateUpdateEvent = await pluginStateUpdateEvent(invoke);
+  }
+  return state;
+}
+
+/**
+ * Emitted instead of the full state when the backend uses the shared memory transport
+ */
+export interface SharedMemoryDescriptor {
+  revision: number;
+  shm_key: string;
+  length: number;
+}
+
+const SHARED_MEMORY_PROTOCOL = 'zubridge-shm';
+let lastSharedMemoryRevision = 0;
+
+function isSharedMemoryDescriptor(payload: unknown): payload is SharedMemoryDescriptor {
+  if (typeof payload !== 'object' || payload === null) return false;
+  const keys = Object.keys(payload);
+  const descriptor = payload as Partial<SharedMemoryDescriptor>;
+  return (
+    keys.length === 3 &&
+    typeof descriptor.revision === 'number' &&
+    typeof descriptor.shm_key === 'string' &&
+    typeof descriptor.length === 'number'
+  );
+}
+
+/**
+ * Builds the URL of a shared memory state, using Tauri's conversion when available
+ * so the platform-specific custom protocol format is used
+ */
+function sharedMemoryUrl(key: string): string {
+  const internals = (globalThis as any).__TAURI_INTERNALS__;
+  if (typeof internals?.convertFileSrc === 'function') {
+    return internals.convertFileSrc(key, SHARED_MEMORY_PROTOCOL);
+  }
+  return `${SHARED_MEMORY_PROTOCOL}://localhost/${key}`;
+}
+
+/**
+ * Resolves a state update payload to the state it carries.
+ * Shared memory descriptors are fetched from the custom protocol; stale revisions resolve to null.
+ */
+async function resolveStateUpdate(payload: AnyState | SharedMemoryDescriptor): Promise<AnyState | null> {
+  if (!isSharedMemoryDescriptor(payload)) {
+    return payload as AnyState;
+  }
+
+  const response = await fetch(sharedMemoryUrl(payload.shm_key));
+  if (!response.ok) {
+    console.warn(`Zubridge Tauri: Shared memory state ${payload.shm_key} is no longer available.`);
+    return null;
+  }
+  const state = (await response.json()) as AnyState;
+
+  // Fetches can resolve out of order, never replace a newer state with an older one
+  if (payload.revision < lastSharedMemoryRevision) return null;
+  lastSharedMemoryRevision = payload.revision;
+  return state;
 }
 
 /**
@@ -171,18 +285,23 @@ export async function initializeBridge(options?: BackendOptions): Promise<void>
       );
 
       console.log(`Zubridge Tauri: Setting up state update listener on ${activeCommands.stateUpdateEvent}...`);
-      unlistenStateUpdate = await currentListen(activeCommands.stateUpdateEvent, (event: BridgeEvent<AnyState>) => {
-        console.log('Zubridge Tauri: Received state update event.', event.payload);
-        internalStore.setState(
-          (prevState: BridgeState) => {
-            return {
-              ...event.payload,
-              __bridge_status: prevState.__bridge_status,
-            };
-          },
-          true, // Replace state
-        );
-      });
+      unlistenStateUpdate = await currentListen(
+        activeCommands.stateUpdateEvent,
+        async (event: BridgeEvent<AnyState | SharedMemoryDescriptor>) => {
+          console.log('Zubridge Tauri: Received state update event.', event.payload);
+          const nextState = await resolveStateUpdate(event.payload);
+          if (!nextState) return;
+          internalStore.setState(
+            (prevState: BridgeState) => {
+              return {
+                ...nextState,
+                __bridge_status: prevState.__bridge_status,
+              };
+            },
+            true, // Replace state
+          );
+        },
+      );
       console.log('Zubridge Tauri: State update listener active.');
 
       // Set status to ready NOW THAT LISTENER IS ACTIVE
@@ -225,6 +344,7 @@ export function cleanupZubridge(): void {
   }
   initializePromise = null;
   isInitializing = false;
+  lastSharedMemoryRevision = 0;
   // Reset to a clean initial state
   internalStore.setState({ __bridge_status: 'uninitialized' } as BridgeState, true);
   providedInvoke = null;
@@ -352,7 +472,13 @@ export function useZubridgeDispatch<S extends AnyState = AnyState>(): DispatchFu
       await currentInvoke(activeCommands.dispatchAction, actionPayload);
       return Promise.resolve();
     } catch (error) {
-      const errorMessage = error instanceof Error ? error.message : String(error);
+      // The plugin rejects with `{ message, breadcrumbs }` when dispatch breadcrumbs are on
+      const errorMessage =
+        error instanceof Error
+          ? error.message
+          : typeof error === 'object' && error !== null && 'message' in error
+            ? String(error.message)
+            : String(error);
       console.error(`[useZubridgeDispatch] Error invoking dispatch action for ${action.type}:`, errorMessage, error);
       // Rethrow or handle error as needed by the application
       throw error;
@@ -27,9 +27,12 @@ const mockInvoke = vi.fn(async (cmd: string, args?: any): Promise<any> => {
   switch (cmd) {
     // Plugin format (Tauri v2)
     case 'plugin:zubridge|get_initial_state':
-      return Promise.resolve(mockBackendState);
+      return Promise.resolve({ protocol_version: 1, state: mockBackendState });
     case 'plugin:zubridge|dispatch_action':
       return Promise.resolve();
+    case 'plugin:zubridge|get_event_names':
+      // A backend running with `legacy_event_names`
+      return Promise.resolve({ stateUpdate: 'zubridge://state-update' });
     // Direct format (Tauri v1)
     case 'get_initial_state':
       return Promise.resolve(mockBackendState);
@@ -295,7 +298,7 @@ describe('@zubridge/tauri', () => {
       });
 
       // Should try plugin format first, then fall back to direct format
-      expect(v1Invoke).toHaveBeenCalledWith('plugin:zubridge|get_initial_state', undefined);
+      expect(v1Invoke).toHaveBeenCalledWith('plugin:zubridge|get_initial_state', { protocolVersion: 1 });
       expect(v1Listen).toHaveBeenCalledWith('zubridge://state-update', expect.any(Function));
       expect(internalStore.getState().__bridge_status).toBe('ready');
     });
@@ -315,11 +318,57 @@ describe('@zubridge/tauri', () => {
         });
       });
 
-      expect(v2Invoke).toHaveBeenCalledWith('plugin:zubridge|get_initial_state', undefined);
+      expect(v2Invoke).toHaveBeenCalledWith('plugin:zubridge|get_initial_state', { protocolVersion: 1 });
       expect(v2Listen).toHaveBeenCalledWith('zubridge://state-update', expect.any(Function));
       expect(internalStore.getState().__bridge_status).toBe('ready');
     });
 
+    it('should listen on the event name reported by the plugin', async () => {
+      const scopedInvoke = vi.fn(async (cmd: string, args?: any) => {
+        if (cmd === 'plugin:zubridge|get_event_names') {
+          return { stateUpdate: 'com_example_app/zubridge/state-update' };
+        }
+        return mockInvoke(cmd, args);
+      });
+      const scopedListen = vi.fn(async (event: string, callback: any) => {
+        return mockListenRaw(event, callback);
+      });
+
+      await act(async () => {
+        await initializeBridge({
+          invoke: scopedInvoke,
+          listen: scopedListen,
+        });
+      });
+
+      expect(scopedInvoke).toHaveBeenCalledWith('plugin:zubridge|get_event_names');
+      expect(scopedListen).toHaveBeenCalledWith('com_example_app/zubridge/state-update', expect.any(Function));
+      expect(internalStore.getState().__bridge_status).toBe('ready');
+    });
+
+    it('should fail instead of guessing the event name when the plugin does not report it', async () => {
+      const deniedInvoke = vi.fn(async (cmd: string, args?: any) => {
+        if (cmd === 'plugin:zubridge|get_event_names') {
+          throw new Error('zubridge.get_event_names not allowed');
+        }
+        return mockInvoke(cmd, args);
+      });
+      const deniedListen = vi.fn(async (event: string, callback: any) => {
+        return mockListenRaw(event, callback);
+      });
+
+      await expect(
+        act(async () => {
+          await initializeBridge({
+            invoke: deniedInvoke,
+            listen: deniedListen,
+          });
+        }),
+      ).rejects.toThrow("zubridge:allow-get-event-names");
+      expect(deniedInvoke).not.toHaveBeenCalledWith('get_initial_state', undefined);
+      expect(deniedListen).not.toHaveBeenCalledWith('zubridge://state-update', expect.any(Function));
+    });
+
     it('should handle initialization failure (invoke)', async () => {
       const mockInvokeError = vi.fn().mockRejectedValue(new Error('Test init error'));
       const mockListenRaw = vi.fn();
@@ -432,7 +481,7 @@ describe('@zubridge/tauri', () => {
       });
 
       // Verify that the plugin format was tried first and then the direct format
-      expect(mockPluginFailInvoke).toHaveBeenCalledWith('plugin:zubridge|get_initial_state', undefined);
+      expect(mockPluginFailInvoke).toHaveBeenCalledWith('plugin:zubridge|get_initial_state', { protocolVersion: 1 });
       expect(mockPluginFailInvoke).toHaveBeenCalledWith('get_initial_state', undefined);
 
       // Wait for the state to be updated after the async operations complete
@@ -504,7 +553,7 @@ describe('@zubridge/tauri', () => {
       ).rejects.toThrow('Failed to connect to backend');
 
       // Check that invoke was called with the plugin command format
-      expect(failingInvoke).toHaveBeenCalledWith('plugin:zubridge|get_initial_state', undefined);
+      expect(failingInvoke).toHaveBeenCalledWith('plugin:zubridge|get_initial_state', { protocolVersion: 1 });
 
       // Verify bridge status was set to error
       expect(internalStore.getState().__bridge_status).toBe('error');
@@ -556,6 +605,29 @@ describe('@zubridge/tauri', () => {
       expect(internalStore.getState().__bridge_status).toBe('ready');
     });
 
+    it('should fetch the state when a shared memory descriptor is received', async () => {
+      const fetchMock = vi.fn(async () => ({
+        ok: true,
+        json: async () => ({ counter: 42, message: 'Shared Memory' }),
+      }));
+      vi.stubGlobal('fetch', fetchMock);
+
+      await act(async () => {
+        await initializeBridge(mockLegacyOptions);
+      });
+      await waitFor(() => expect(stateUpdateListener).toBeInstanceOf(Function));
+
+      simulateStateUpdate({ revision: 1, shm_key: 'state-1', length: 40 });
+
+      await waitFor(() => {
+        expect(internalStore.getState().counter).toBe(42);
+      });
+      expect(fetchMock).toHaveBeenCalledWith('zubridge-shm://localhost/state-1');
+      expect(internalStore.getState().shm_key).toBeUndefined();
+
+      vi.unstubAllGlobals();
+    });
+
     it('should handle multiple successive state updates correctly', async () => {
       cleanupZubridge();
 